@@ -0,0 +1,11 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| ort_openrouter_cli::fuzz::chunked(data));
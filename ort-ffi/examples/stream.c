@@ -0,0 +1,52 @@
+/*
+ * Stream an answer to stdout, stop after `max` bytes if given.
+ *
+ * cargo build --release
+ * cc -Iinclude examples/stream.c -Ltarget/release -l:libort.a -o stream
+ * OPENROUTER_API_KEY=... ./stream "Write a haiku about C" [max]
+ */
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "ort.h"
+
+struct progress {
+	size_t written;
+	size_t max;
+};
+
+static void on_event(ort_request *req, int event, const char *text, size_t len, void *user_data)
+{
+	struct progress *p = user_data;
+	switch (event) {
+	case ORT_CONTENT:
+		fwrite(text, 1, len, stdout);
+		fflush(stdout);
+		p->written += len;
+		if (p->max && p->written >= p->max)
+			ort_cancel(req);
+		break;
+	case ORT_STATS:
+		fprintf(stderr, "\n%s\n", text);
+		break;
+	case ORT_ERROR:
+		fprintf(stderr, "\nerror: %s\n", text);
+		break;
+	}
+}
+
+int main(int argc, char **argv)
+{
+	if (argc < 2) {
+		fprintf(stderr, "usage: %s <prompt> [max bytes]\n", argv[0]);
+		return 2;
+	}
+	struct progress p = { 0, argc > 2 ? strtoul(argv[2], NULL, 10) : 0 };
+	int status;
+	char *answer = ort_query(getenv("ORT_CONFIG"), getenv("OPENROUTER_API_KEY"), NULL, NULL,
+				 argv[1], on_event, &p, &status);
+	if (status == ORT_CANCELLED)
+		fprintf(stderr, "\ncancelled\n");
+	ort_free(answer);
+	return status;
+}
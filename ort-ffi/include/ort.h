@@ -0,0 +1,61 @@
+/*
+ * ort: Open Router CLI
+ * https://github.com/grahamking/ort
+ *
+ * MIT License
+ * Copyright (c) 2026 Graham King
+ *
+ * C ABI for ort. Link with -lort (libort.so or libort.a from the ort-ffi crate).
+ * Matches src/input/ffi.rs, `cargo test` in ort-ffi checks the two agree.
+ */
+
+#ifndef ORT_H
+#define ORT_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Callback `event` values. START is once the request is sent, empty text,
+ * so another thread can get `req` to cancel it. */
+#define ORT_START 1
+#define ORT_CONTENT 2
+#define ORT_REASONING 3
+#define ORT_STATS 4
+#define ORT_ERROR 5
+
+/* `status` after ort_cancel, like a shell's Ctrl-C. Otherwise 0 for success,
+ * or the CLI's exit codes: 1 error, 2 bad arguments, 3 no network,
+ * 4 refused, 5 over max cost. */
+#define ORT_CANCELLED 130
+
+/* One running ort_query */
+typedef struct ort_request ort_request;
+
+/* Called with each piece of the answer as it streams. `text` is `len` bytes
+ * plus a NUL, and only valid during the call. */
+typedef void (*ort_callback)(ort_request *req, int event, const char *text, size_t len,
+                             void *user_data);
+
+/* Send `prompt` to `model` with an optional `system` prompt. `config` is
+ * ort.cfg text, e.g. "base_url: ...", or NULL for the defaults. NULL `api_key`
+ * or `model` come from `config`. Blocks until the answer is complete, calling
+ * `cb` (may be NULL) as it streams. Returns the whole answer, free it with
+ * ort_free, or NULL on error. `status` (may be NULL) gets 0 or why it failed. */
+char *ort_query(const char *config, const char *api_key, const char *model, const char *system,
+                const char *prompt, ort_callback cb, void *user_data, int *status);
+
+/* Stop `req` soon, from its callback or another thread. Only while its
+ * ort_query is running. */
+void ort_cancel(ort_request *req);
+
+/* Free an answer from ort_query. NULL is fine. */
+void ort_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ORT_H */
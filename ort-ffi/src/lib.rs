@@ -0,0 +1,69 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Builds ort's C ABI into libort.so and libort.a. The code is in the main
+//! crate, src/input/ffi.rs, the header is include/ort.h.
+
+pub use ort_openrouter_cli::ffi::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = include_str!("../include/ort.h");
+    const SOURCE: &str = include_str!("../../src/input/ffi.rs");
+
+    /// Every exported function and constant is in the header, with the same value
+    #[test]
+    fn header_matches() {
+        let mut num_fns = 0;
+        for line in SOURCE.lines() {
+            let Some(rest) = line.strip_prefix("pub unsafe extern \"C\" fn ") else {
+                continue;
+            };
+            let name = &rest[..rest.find('(').unwrap()];
+            assert!(
+                HEADER.contains(&(name.to_string() + "(")),
+                "{name} is not in ort.h"
+            );
+            num_fns += 1;
+        }
+        assert_eq!(num_fns, 3);
+
+        for (name, value) in [
+            ("ORT_START", ORT_START),
+            ("ORT_CONTENT", ORT_CONTENT),
+            ("ORT_REASONING", ORT_REASONING),
+            ("ORT_STATS", ORT_STATS),
+            ("ORT_ERROR", ORT_ERROR),
+            ("ORT_CANCELLED", ORT_CANCELLED),
+        ] {
+            let define = format!("#define {name} {value}\n");
+            assert!(HEADER.contains(&define), "ort.h is missing {define}");
+        }
+    }
+
+    #[test]
+    fn missing_prompt() {
+        let mut status = -1;
+        let answer = unsafe {
+            ort_query(
+                std::ptr::null(),
+                c"sk-or-test".as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                None,
+                std::ptr::null_mut(),
+                &mut status,
+            )
+        };
+        assert!(answer.is_null());
+        // Same as the CLI's bad usage exit code
+        assert_eq!(status, 2);
+        unsafe { ort_free(answer) };
+    }
+}
@@ -10,15 +10,23 @@
 pub mod alloc;
 pub mod base64;
 pub mod buf_read;
+pub mod clean;
 pub mod config;
+pub mod cpu;
 pub mod data;
 pub mod dir;
 pub mod error;
 pub mod file;
 pub mod io;
 pub mod json_parser;
+pub mod models;
+pub mod paths;
+pub mod request_id;
 pub mod resolver;
+pub mod secrets;
 pub mod stats;
+pub mod term;
 pub mod time;
+pub mod tokens;
 pub mod tools;
 pub mod utils;
@@ -43,6 +43,29 @@ static mut ARENA: Arena = Arena {
     offset: 0,
 };
 
+static mut USAGE: AllocStats = AllocStats {
+    count: 0,
+    bytes: 0,
+    mapped: 0,
+};
+
+/// What ArenaAlloc handed out since the start, for `--verbose`.
+/// Nothing is freed, so these are also the peak.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AllocStats {
+    /// Number of allocations
+    pub count: usize,
+    /// Bytes asked for, after rounding up to ALIGN
+    pub bytes: usize,
+    /// Bytes of mmap'd arena
+    pub mapped: usize,
+}
+
+/// All zero unless ArenaAlloc is the global allocator, e.g. not in tests
+pub fn alloc_stats() -> AllocStats {
+    unsafe { USAGE }
+}
+
 pub struct ArenaAlloc;
 
 #[inline]
@@ -63,6 +86,7 @@ unsafe fn map_chunk(min_size: usize) -> Arena {
         syscall::exit(1);
     }
 
+    unsafe { USAGE.mapped += size };
     Arena {
         base,
         size,
@@ -108,6 +132,8 @@ unsafe impl core::alloc::GlobalAlloc for ArenaAlloc {
 
             let ptr = ARENA.base.add(ARENA.offset);
             ARENA.offset += alloc_size;
+            USAGE.count += 1;
+            USAGE.bytes += alloc_size;
             ptr
         }
     }
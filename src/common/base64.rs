@@ -67,12 +67,43 @@ pub fn encode(data: &[u8]) -> String {
     }
 }
 
+/// Base64 decode, padding optional
+pub fn decode(data: &str) -> Result<Vec<u8>, &'static str> {
+    let data = data.trim_end_matches(PAD as char).as_bytes();
+    if data.len() % 4 == 1 {
+        return Err("base64: invalid length");
+    }
+    let mut out = Vec::with_capacity(data.len() / 4 * 3 + 2);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &b in data {
+        acc = (acc << 6) | decode_byte(b)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+fn decode_byte(b: u8) -> Result<u8, &'static str> {
+    match b {
+        b'A'..=b'Z' => Ok(b - b'A'),
+        b'a'..=b'z' => Ok(b - b'a' + 26),
+        b'0'..=b'9' => Ok(b - b'0' + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err("base64: invalid character"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate test;
     use test::Bencher;
 
-    use super::encode;
+    use super::{decode, encode};
 
     #[test]
     fn encodes_rfc4648_vectors() {
@@ -97,6 +128,28 @@ mod tests {
         assert_eq!(encode(&input), "AAEC/f7/");
     }
 
+    #[test]
+    fn decodes_rfc4648_vectors() {
+        let cases = [
+            ("", b"" as &[u8]),
+            ("Zg==", b"f"),
+            ("Zm8=", b"fo"),
+            ("Zm9v", b"foo"),
+            ("Zm9vYg", b"foob"),
+            ("Zm9vYmE=", b"fooba"),
+            ("Zm9vYmFy", b"foobar"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(decode(input).unwrap(), expected);
+        }
+        assert_eq!(
+            decode("AAEC/f7/").unwrap(),
+            [0x00, 0x01, 0x02, 0xfd, 0xfe, 0xff]
+        );
+        assert!(decode("Zm9v!").is_err());
+        assert!(decode("Z").is_err());
+    }
+
     #[bench]
     fn bench_encode_short(b: &mut Bencher) {
         let input = b"Hello";
@@ -9,20 +9,24 @@ use core::ffi::c_int;
 
 extern crate alloc;
 use alloc::string::String;
+use alloc::vec::Vec;
 use core::cmp;
 
 use crate::{
-    ErrorKind, OrtResult, Read, common::io::ReadLine, input::prompt::PromptReader, net::AsFd,
-    ort_error,
+    ErrorKind, OrtResult, Read, common::io::ReadLine, input::prompt::PromptReader, ort_error,
+    transport::AsFd,
 };
 
 const BUF_SIZE: usize = 8 * 1024;
+/// `read_line` gives longer lines in pieces of about this size
+pub const DEFAULT_MAX_LINE: usize = 64 * 1024;
 
 pub struct OrtBufReader<R: Read> {
     inner: R,
     buf: [u8; BUF_SIZE],
     pos: usize, // index of next unread byte in `buf`
     cap: usize, // number of bytes currently in `buf`
+    max_line: usize,
 }
 
 impl<T: Read + AsFd> AsFd for OrtBufReader<T> {
@@ -55,9 +59,17 @@ impl<R: Read> OrtBufReader<R> {
             buf: [0; BUF_SIZE],
             pos: 0,
             cap: 0,
+            max_line: DEFAULT_MAX_LINE,
         }
     }
 
+    /// Most bytes `read_line` returns at once. A longer line comes over
+    /// several calls, each cut on a character boundary. Only the last piece
+    /// ends in a newline.
+    pub fn set_max_line(&mut self, max_line: usize) {
+        self.max_line = max_line.max(1);
+    }
+
     #[inline(always)]
     fn buffer_consumed(&self) -> bool {
         /*
@@ -87,6 +99,16 @@ impl<R: Read> OrtBufReader<R> {
         Ok(())
     }
 
+    /// The underlying reader, for example to write a response on the same socket.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// The underlying reader, if everything it gave us has been read
+    pub fn into_inner(self) -> Option<R> {
+        self.buffer_consumed().then_some(self.inner)
+    }
+
     /// Reads exactly `buf.len()` bytes into `buf`.
     ///
     /// Returns an error if EOF is reached before the buffer is full.
@@ -132,16 +154,18 @@ impl<R: Read> OrtBufReader<R> {
     }
 }
 
-impl<R: Read> ReadLine for OrtBufReader<R> {
+impl<R: Read> OrtBufReader<R> {
     /// Reads all bytes up to and including a newline (0x0A) and appends
-    /// them to `buf`.
+    /// them to `buf`, whatever their encoding.
     ///
-    /// Existing content of `buf` is preserved.
-    /// Returns the number of bytes appended.
-    ///
-    /// On EOF with no new data, returns `Ok(0)`.
-    /// Assumes the stream is valid UTF-8.
-    fn read_line(&mut self, buf: &mut String) -> OrtResult<usize> {
+    /// Returns the number of bytes appended, 0 on EOF with no new data.
+    pub fn read_line_bytes(&mut self, buf: &mut Vec<u8>) -> OrtResult<usize> {
+        self.read_line_bytes_max(buf, usize::MAX)
+    }
+
+    /// `read_line_bytes`, but stop after about `max` bytes. The cut never
+    /// splits a UTF-8 character, so it can go up to 3 bytes over.
+    fn read_line_bytes_max(&mut self, buf: &mut Vec<u8>, max: usize) -> OrtResult<usize> {
         let mut total = 0;
 
         loop {
@@ -155,40 +179,66 @@ impl<R: Read> ReadLine for OrtBufReader<R> {
 
             // Search for newline in the current buffered data
             let available = &self.buf[self.pos..self.cap];
-            let mut newline_rel = None;
-
-            for (i, &b) in available.iter().enumerate() {
-                if b == b'\n' {
-                    newline_rel = Some(i);
-                    break;
-                }
-            }
+            let newline_rel = available.iter().position(|&b| b == b'\n');
 
-            let end = match newline_rel {
+            let mut end = match newline_rel {
                 Some(i) => self.pos + i + 1, // include newline
                 None => self.cap,
             };
+            let room = max - total;
+            if end - self.pos > room {
+                end = self.pos + room;
+                // Finish the character we are in
+                while end < self.cap && is_utf8_continuation(self.buf[end]) {
+                    end += 1;
+                }
+            }
 
-            let chunk = &self.buf[self.pos..end];
-
-            // Interpret as UTF-8 and append to the caller's String
-            let s = core::str::from_utf8(chunk)
-                .map_err(|_| ort_error(ErrorKind::FormatError, "utf8 decode"))?;
-            buf.push_str(s);
-
-            total += chunk.len();
+            buf.extend_from_slice(&self.buf[self.pos..end]);
+            total += end - self.pos;
             self.pos = end;
 
-            if newline_rel.is_some() {
+            if total != 0 && buf[buf.len() - 1] == b'\n' {
                 // We have consumed up to and including the newline
                 return Ok(total);
             }
+            // Full, and the next byte, if we have it, starts a new character
+            if total >= max && (self.pos < self.cap || buf[buf.len() - 1] < 0x80) {
+                return Ok(total);
+            }
 
             // Otherwise loop and refill
         }
     }
 }
 
+fn is_utf8_continuation(b: u8) -> bool {
+    b & 0b1100_0000 == 0b1000_0000
+}
+
+impl<R: Read> ReadLine for OrtBufReader<R> {
+    /// Reads all bytes up to and including a newline (0x0A) and appends
+    /// them to `buf`. A line longer than `set_max_line` comes in pieces.
+    ///
+    /// Existing content of `buf` is preserved.
+    /// Returns the number of bytes appended.
+    ///
+    /// On EOF with no new data, returns `Ok(0)`.
+    /// Assumes the stream is valid UTF-8.
+    fn read_line(&mut self, buf: &mut String) -> OrtResult<usize> {
+        // As bytes first, a multi-byte character can be split between two reads
+        let v = unsafe { buf.as_mut_vec() };
+        let start = v.len();
+        let res = self.read_line_bytes_max(v, self.max_line);
+        if res.is_ok() && core::str::from_utf8(&v[start..]).is_ok() {
+            return res;
+        }
+        // Keep `buf` valid UTF-8
+        v.truncate(start);
+        res.and(Err(ort_error(ErrorKind::FormatError, "utf8 decode")))
+    }
+}
+
 impl<T: Read + AsFd> PromptReader for OrtBufReader<T> {}
 
 /*
@@ -199,7 +249,9 @@ impl<T: Read + Write> OrtBufReader<TlsStream<T>> {
 }
 */
 
-pub fn fd_read_to_string(fd: c_int, buffer: &mut String) {
+/// Read `fd` to the end. False if it wasn't UTF-8: with `is_lossy` those
+/// bytes become U+FFFD, otherwise `buffer` is left empty.
+pub fn fd_read_to_string(fd: c_int, buffer: &mut String, is_lossy: bool) -> bool {
     const READ_CHUNK: usize = 64 * 1024;
 
     // Write bytes directly into String's backing Vec<u8> for speed.
@@ -232,10 +284,17 @@ pub fn fd_read_to_string(fd: c_int, buffer: &mut String) {
         }
     }
 
-    // Maintain String's UTF-8 invariant. On invalid UTF-8, clear to a valid value.
-    if core::str::from_utf8(v.as_slice()).is_err() {
+    // Maintain String's UTF-8 invariant
+    if core::str::from_utf8(v.as_slice()).is_ok() {
+        return true;
+    }
+    if is_lossy {
+        let lossy = String::from_utf8_lossy(v).into_owned();
+        *v = lossy.into_bytes();
+    } else {
         v.clear();
     }
+    false
 }
 
 #[cfg(test)]
@@ -316,4 +375,61 @@ mod tests {
         assert!(out.contains("native_finish_reason"));
         assert!(!out.contains("EXTRA_STUFF"));
     }
+
+    /// A multi-byte character split between two fills of the buffer
+    #[test]
+    fn test_read_line_split_char() {
+        let s = "a".repeat(BUF_SIZE - 1) + "é\nnext";
+        let reader = StringReader { data: s, pos: 0 };
+        let mut candidate = OrtBufReader::new(reader);
+        let mut out = String::new();
+        candidate.read_line(&mut out).unwrap();
+        assert!(out.ends_with("aé\n"));
+
+        let reader = StringReader {
+            data: "ok\n".to_string(),
+            pos: 0,
+        };
+        let mut candidate = OrtBufReader::new(reader);
+        let mut bytes = alloc::vec![0xff];
+        candidate.read_line_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, b"\xffok\n");
+    }
+
+    /// One huge line comes in pieces, none of them splitting a character
+    #[test]
+    fn test_read_line_in_pieces() {
+        let line = "é".repeat(BUF_SIZE) + "abc\n";
+        let reader = StringReader {
+            data: line.clone() + "next\n",
+            pos: 0,
+        };
+        let mut candidate = OrtBufReader::new(reader);
+        candidate.set_max_line(1001);
+        let mut pieces = alloc::vec::Vec::new();
+        loop {
+            let mut piece = String::new();
+            assert!(candidate.read_line(&mut piece).unwrap() > 0);
+            assert!(piece.len() <= 1002, "{}", piece.len());
+            pieces.push(piece);
+            if pieces.last().unwrap().ends_with('\n') {
+                break;
+            }
+        }
+        assert!(pieces.len() > 10);
+        assert_eq!(pieces.concat(), line);
+
+        let mut out = String::new();
+        candidate.read_line(&mut out).unwrap();
+        assert_eq!(out, "next\n");
+        // Headers are always read whole
+        let reader = StringReader {
+            data: "x".repeat(100) + "\n",
+            pos: 0,
+        };
+        let mut candidate = OrtBufReader::new(reader);
+        candidate.set_max_line(10);
+        let mut bytes = alloc::vec::Vec::new();
+        assert_eq!(candidate.read_line_bytes(&mut bytes).unwrap(), 101);
+    }
 }
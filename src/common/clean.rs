@@ -0,0 +1,214 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--clean-input`: tidy piped and file input before it costs tokens.
+//! Strips terminal escape codes and control characters, keeps only the
+//! final state of `\r` redrawn lines (progress bars), drops lines that are
+//! mostly control bytes, and trims trailing space and runs of blank lines.
+
+extern crate alloc;
+use alloc::string::String;
+
+use crate::common::utils;
+
+/// A line with this percent or more of control or undecodable characters is binary
+const MAX_JUNK_PERCENT: usize = 30;
+/// Shorter lines are judged on their junk only once they are this long
+const MIN_JUDGED_LINE: usize = 8;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// Cleaned `text`. Says what it did to `source` in `report`, if anything.
+pub fn clean(original: &str, source: &str, report: &mut String) -> String {
+    let text = strip_escapes(original);
+    let mut out = String::with_capacity(text.len());
+    let mut dropped_lines = 0;
+    let mut dropped_bytes = 0;
+    let mut is_prev_blank = true; // No blank lines at the start
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // The terminal only showed what came after the last carriage return
+        let line = line.rsplit('\r').next().unwrap_or(line);
+        if is_binary(line) {
+            dropped_lines += 1;
+            dropped_bytes += line.len() + 1;
+            continue;
+        }
+        if dropped_lines != 0 {
+            push_dropped(&mut out, dropped_lines, dropped_bytes);
+            (dropped_lines, dropped_bytes) = (0, 0);
+            is_prev_blank = false;
+        }
+        let start = out.len();
+        out.extend(line.chars().filter(|c| *c == '\t' || !c.is_control()));
+        out.truncate(start + out[start..].trim_end().len());
+        let is_blank = out.len() == start;
+        if is_blank && is_prev_blank {
+            continue;
+        }
+        out.push('\n');
+        is_prev_blank = is_blank;
+    }
+    if dropped_lines != 0 {
+        push_dropped(&mut out, dropped_lines, dropped_bytes);
+    }
+    out.truncate(out.trim_end().len());
+    if original.ends_with('\n') && !out.is_empty() {
+        out.push('\n');
+    }
+
+    if out != original {
+        report.push_str("Cleaned ");
+        report.push_str(source);
+        report.push_str(": ");
+        report.push_str(&utils::num_to_string(original.len()));
+        report.push_str(" to ");
+        report.push_str(&utils::num_to_string(out.len()));
+        report.push_str(" bytes\n");
+    }
+    out
+}
+
+fn push_dropped(out: &mut String, lines: usize, bytes: usize) {
+    out.push_str("[... dropped ");
+    out.push_str(&utils::num_to_string(lines));
+    out.push_str(if lines == 1 {
+        " binary line, "
+    } else {
+        " binary lines, "
+    });
+    out.push_str(&utils::num_to_string(bytes));
+    out.push_str(" bytes ...]\n");
+}
+
+/// Control characters (other than tab) and U+FFFD, which is what bytes
+/// that were not UTF-8 became when read.
+fn is_junk(c: char) -> bool {
+    (c.is_control() && c != '\t') || c == char::REPLACEMENT_CHARACTER
+}
+
+fn is_binary(line: &str) -> bool {
+    let mut chars = 0;
+    let mut junk = 0;
+    for c in line.chars() {
+        chars += 1;
+        junk += is_junk(c) as usize;
+    }
+    chars >= MIN_JUDGED_LINE && junk * 100 >= chars * MAX_JUNK_PERCENT
+}
+
+/// Remove ANSI / VT100 escape sequences: colors and cursor movement (CSI),
+/// window titles and hyperlinks (OSC), and the short ones.
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters and intermediates, then a final byte in @ to ~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC, DCS, SOS, PM, APC: a string ended by BEL or ESC \
+            Some(']' | 'P' | 'X' | '^' | '_') => {
+                while let Some(c) = chars.next() {
+                    if c == BEL {
+                        break;
+                    }
+                    if c == ESC && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // e.g. ESC ( B, character set selection: intermediates then one more
+            Some(' '..='/') => {
+                while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                chars.next();
+            }
+            // Two character sequences, e.g. ESC 7, ESC M
+            Some(_) | None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    fn clean_str(s: &str) -> String {
+        clean(s, "stdin", &mut String::new())
+    }
+
+    #[test]
+    fn strips_escapes() {
+        let colored =
+            "\x1b[1;31merror\x1b[0m: \x1b]8;;https://x.dev\x07link\x1b]8;;\x07 \x1b(Bdone\x1b7\n";
+        assert_eq!(clean_str(colored), "error: link done\n");
+    }
+
+    #[test]
+    fn progress_bars_and_whitespace() {
+        let log = "\n\nDownloading\r\n 10%\r 50%\r100%\r\n\n\n\nok  \t\nbell\x07 and null\0\n\n";
+        assert_eq!(clean_str(log), "Downloading\n100%\n\nok\nbell and null\n");
+        // Tabs inside a line, and text that is already clean, stay
+        assert_eq!(clean_str("a\tb\né ✓\n"), "a\tb\né ✓\n");
+    }
+
+    #[test]
+    fn drops_binary_lines() {
+        // How a binary file reads: bytes that aren't UTF-8 become U+FFFD
+        let mut bytes: Vec<u8> = b"header ok\n".to_vec();
+        let mut x: u32 = 12345;
+        for _ in 0..600 {
+            x = x.wrapping_mul(1103515245).wrapping_add(12345);
+            bytes.push((x >> 16) as u8);
+        }
+        bytes.extend_from_slice(b"\ntrailer ok\n");
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+
+        let mut report = String::new();
+        let out = clean(&text, "blob.bin", &mut report);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "header ok");
+        assert!(lines[1].starts_with("[... dropped "), "{out}");
+        assert!(lines[1].ends_with(" bytes ...]"));
+        assert_eq!(*lines.last().unwrap(), "trailer ok");
+        assert!(out.len() < 100);
+        assert!(report.starts_with("Cleaned blob.bin: "), "{report}");
+    }
+
+    #[test]
+    fn keeps_long_lines() {
+        // base64 and minified code are text, only control bytes make binary
+        let blob = "QUJD".repeat(100);
+        let text = "see attached\n".to_string() + &blob + "\nthanks\n";
+        assert_eq!(clean_str(&text), text);
+        let prose = "word ".repeat(100);
+        assert_eq!(clean_str(&prose), prose.trim_end());
+    }
+
+    #[test]
+    fn no_report_when_clean() {
+        let mut report = String::new();
+        assert_eq!(
+            clean("fn main() {}\n", "a.rs", &mut report),
+            "fn main() {}\n"
+        );
+        assert!(report.is_empty());
+    }
+}
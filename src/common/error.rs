@@ -34,6 +34,10 @@ pub enum ErrorKind {
     InvalidMessageSchema,
     ParsingToolCallParams,
     ToolDoesNotExist,
+    // The models cache says the model can't do what we asked
+    ModelUnsupported,
+    // A --ctx file or diff looks like it has a key in it
+    SecretDetected,
 
     // Output & streaming
     //
@@ -67,11 +71,22 @@ pub enum ErrorKind {
     SocketConnectFailed,
     SocketReadFailed,
     SocketWriteFailed,
+    // bind or listen failed, usually port in use
+    SocketBindFailed,
+    SocketAcceptFailed,
+    // --offline, or the network is unreachable
+    NoNetwork,
 
     // Generic I/O
     UnexpectedEof,
     // O_NONBLOCK socket has no data to read right now
     WouldBlock,
+    // No reasoning or content within --first-token-deadline
+    FirstTokenTimeout,
+    // The whole run took longer than --deadline
+    DeadlineExceeded,
+    // Nothing on stdin within --stdin-timeout
+    StdinTimeout,
 
     // HTTP chunked transfer decoding
     //
@@ -85,7 +100,17 @@ pub enum ErrorKind {
 
     // HTTP / higher-level protocol
     HttpStatusError,
+    // HTTP 4xx other than 408 and 429, the request itself was refused
+    HttpClientError,
     HttpConnectError,
+    // HTTP 402, the account is out of credits
+    InsufficientCredits,
+    // The model refused, or the provider's content filter stopped it
+    ModelRefused,
+    // --max-cost, the estimated cost went over the cap
+    CostLimitExceeded,
+    // --strict-reasoning, the reasoning went well over --reasoning-tokens
+    ReasoningBudgetExceeded,
 
     // TLS handshake / record processing
     //
@@ -112,12 +137,21 @@ pub enum ErrorKind {
     TlsServerNotTls13,
     TlsMissingServerKey,
     TlsAes128GcmDecryptFailed,
+    // Server side, for the mock server in tests
+    TlsBadClientHello,
 
     // Time
     TscCpuidLeafUnavailable,
     TscInvalidCalibration,
     TscMissingCrystalClock,
 
+    // External commands
+    //
+    // Child process did not finish in time, was killed
+    CommandTimeout,
+    // A configured hook exited non-zero
+    HookFailed,
+
     // Misc
     FormatError,
     RateLimited,
@@ -149,6 +183,8 @@ impl ErrorKind {
             ErrorKind::InvalidMessageSchema => "InvalidMessageSchema",
             ErrorKind::ParsingToolCallParams => "ParsingToolCallParams",
             ErrorKind::ToolDoesNotExist => "ToolDoesNotExist",
+            ErrorKind::ModelUnsupported => "ModelUnsupported",
+            ErrorKind::SecretDetected => "SecretDetected",
             ErrorKind::StdoutWriteFailed => "StdoutWriteFailed",
             ErrorKind::MissingUsageStats => "MissingUsageStats",
             ErrorKind::ResponseStreamError => "ResponseStreamError",
@@ -166,14 +202,25 @@ impl ErrorKind {
             ErrorKind::SocketConnectFailed => "SocketConnectFailed",
             ErrorKind::SocketReadFailed => "SocketReadFailed",
             ErrorKind::SocketWriteFailed => "SocketWriteFailed",
+            ErrorKind::SocketBindFailed => "SocketBindFailed",
+            ErrorKind::SocketAcceptFailed => "SocketAcceptFailed",
+            ErrorKind::NoNetwork => "NoNetwork",
             ErrorKind::UnexpectedEof => "UnexpectedEof",
             ErrorKind::WouldBlock => "WouldBlock",
+            ErrorKind::FirstTokenTimeout => "FirstTokenTimeout",
+            ErrorKind::DeadlineExceeded => "DeadlineExceeded",
+            ErrorKind::StdinTimeout => "StdinTimeout",
             ErrorKind::ChunkedEofInSize => "ChunkedEofInSize",
             ErrorKind::ChunkedSizeReadError => "ChunkedSizeReadError",
             ErrorKind::ChunkedInvalidSize => "ChunkedInvalidSize",
             ErrorKind::ChunkedDataReadError => "ChunkedDataReadError",
             ErrorKind::HttpStatusError => "HttpStatusError",
+            ErrorKind::HttpClientError => "HttpClientError",
             ErrorKind::HttpConnectError => "HttpConnectError",
+            ErrorKind::InsufficientCredits => "InsufficientCredits",
+            ErrorKind::ModelRefused => "ModelRefused",
+            ErrorKind::CostLimitExceeded => "CostLimitExceeded",
+            ErrorKind::ReasoningBudgetExceeded => "ReasoningBudgetExceeded",
             ErrorKind::TlsExpectedHandshakeRecord => "TlsExpectedHandshakeRecord",
             ErrorKind::TlsExpectedServerHello => "TlsExpectedServerHello",
             ErrorKind::TlsExpectedChangeCipherSpec => "TlsExpectedChangeCipherSpec",
@@ -196,11 +243,15 @@ impl ErrorKind {
             ErrorKind::TlsServerNotTls13 => "TlsServerNotTls13",
             ErrorKind::TlsMissingServerKey => "TlsMissingServerKey",
             ErrorKind::TlsAes128GcmDecryptFailed => "TlsAes128GcmDecryptFailed",
+            ErrorKind::TlsBadClientHello => "TlsBadClientHello",
 
             ErrorKind::TscCpuidLeafUnavailable => "TscCpuidLeafUnavailable",
             ErrorKind::TscInvalidCalibration => "TscInvalidCalibration",
             ErrorKind::TscMissingCrystalClock => "TscMissingCrystalClock",
 
+            ErrorKind::CommandTimeout => "CommandTimeout",
+            ErrorKind::HookFailed => "HookFailed",
+
             ErrorKind::FormatError => "FormatError",
             ErrorKind::RateLimited => "RateLimited",
             ErrorKind::Other => "Other",
@@ -208,6 +259,28 @@ impl ErrorKind {
     }
 }
 
+impl ErrorKind {
+    /// The process exit code, so scripts can tell bad usage, no network,
+    /// refusals and going over --max-cost apart from other failures.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::InvalidArguments => EXIT_USAGE,
+            ErrorKind::NoNetwork => EXIT_NO_NETWORK,
+            ErrorKind::ModelRefused => EXIT_REFUSED,
+            ErrorKind::CostLimitExceeded | ErrorKind::ReasoningBudgetExceeded => EXIT_OVER_BUDGET,
+            ErrorKind::DeadlineExceeded => EXIT_DEADLINE,
+            _ => EXIT_ERROR,
+        }
+    }
+}
+
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NO_NETWORK: i32 = 3;
+pub const EXIT_REFUSED: i32 = 4;
+pub const EXIT_OVER_BUDGET: i32 = 5;
+pub const EXIT_DEADLINE: i32 = 6;
+
 pub type OrtResult<T> = Result<T, OrtError>;
 
 #[derive(Clone, Copy, Debug)]
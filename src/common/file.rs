@@ -23,15 +23,58 @@ impl File {
     /// Path must end with a null byte.
     pub unsafe fn create(path: &[u8]) -> OrtResult<Self> {
         //crate::utils::print_string(c"\ncreate: ", unsafe { str::from_utf8_unchecked(path) });
+        unsafe { Self::create_mode(path, 0o660) }
+    }
+
+    /// As `create`, but only the user can read it, for conversations and
+    /// other private state. The mode only applies to a new file.
+    ///
+    /// # Safety
+    /// Path must end with a null byte.
+    pub unsafe fn create_private(path: &[u8]) -> OrtResult<Self> {
+        unsafe { Self::create_mode(path, 0o600) }
+    }
+
+    /// As `create`, but None if the file is already there, it is left alone.
+    ///
+    /// # Safety
+    /// Path must end with a null byte.
+    pub unsafe fn create_new(path: &[u8]) -> OrtResult<Option<Self>> {
+        let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_EXCL;
+        match syscall::open(path.as_ptr() as *const c_char, flags, 0o660) {
+            Ok(fd) => Ok(Some(File { fd })),
+            Err("File exists") => Ok(None),
+            Err(e) => Err(ort_error(ErrorKind::FileCreateFailed, e)),
+        }
+    }
 
+    unsafe fn create_mode(path: &[u8], mode: c_int) -> OrtResult<Self> {
         let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_TRUNC;
-        let fd = syscall::open(path.as_ptr() as *const c_char, flags, 0o660 as c_int)
+        let fd = syscall::open(path.as_ptr() as *const c_char, flags, mode)
             .map_err(|e| ort_error(ErrorKind::FileCreateFailed, e))?;
         if fd == -1 {
             return Err(ort_error(ErrorKind::FileCreateFailed, "open64 failed"));
         }
         Ok(File { fd })
     }
+
+    /// Overwrite bytes at `offset`, leaving the write position where it was.
+    pub fn write_at(&mut self, buf: &[u8], offset: usize) -> OrtResult<usize> {
+        let bytes_written = syscall::pwrite(
+            self.fd,
+            buf.as_ptr() as *const c_void,
+            buf.len(),
+            offset as i64,
+        );
+        if bytes_written < 0 {
+            Err(ort_error(
+                ErrorKind::FileWriteFailed,
+                "syscall pwrite error",
+            ))
+        } else {
+            Ok(bytes_written as usize)
+        }
+    }
 }
 
 impl Read for File {
@@ -59,9 +102,28 @@ impl Write for File {
         // The stdlib version is a no-op on Unix. It does not fsync.
         Ok(())
     }
+
+    fn truncate(&mut self) -> OrtResult<()> {
+        if syscall::ftruncate(self.fd, 0) < 0 || syscall::lseek(self.fd, 0, syscall::SEEK_SET) < 0 {
+            return Err(ort_error(
+                ErrorKind::FileWriteFailed,
+                "syscall ftruncate error",
+            ));
+        }
+        Ok(())
+    }
 }
 
 pub fn last_modified(path: &CStr) -> OrtResult<time::Instant> {
+    metadata(path).map(|m| m.modified)
+}
+
+pub struct Metadata {
+    pub size: u64,
+    pub modified: time::Instant,
+}
+
+pub fn metadata(path: &CStr) -> OrtResult<Metadata> {
     let mut st = MaybeUninit::<syscall::Stat>::uninit();
     if syscall::stat(path.as_ptr(), &mut st).is_err() {
         // In debug build print the path.
@@ -71,8 +133,8 @@ pub fn last_modified(path: &CStr) -> OrtResult<time::Instant> {
         return Err(ort_error(ErrorKind::FileStatFailed, ""));
     }
     let st = unsafe { st.assume_init() };
-    Ok(time::Instant::new(
-        st.st_mtime as u64,
-        st.st_mtime_nsec as u64,
-    ))
+    Ok(Metadata {
+        size: st.st_size as u64,
+        modified: time::Instant::new(st.st_mtime as u64, st.st_mtime_nsec as u64),
+    })
 }
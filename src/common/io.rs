@@ -71,6 +71,11 @@ pub trait Write {
         self.write_str(c.encode_utf8(&mut [0; MAX_LEN_UTF8]))
     }
 
+    /// Throw away everything written so far. Only a file or a buffer can.
+    fn truncate(&mut self) -> OrtResult<()> {
+        Err(ort_error(ErrorKind::Other, "Can't truncate this writer"))
+    }
+
     /* Not used yet
     fn write_byte(&mut self, b: u8) -> OrtResult<()> {
         // TODO Override this in File, and other places where we can be more efficient
@@ -91,6 +96,11 @@ impl Write for String {
     fn flush(&mut self) -> OrtResult<()> {
         Ok(())
     }
+
+    fn truncate(&mut self) -> OrtResult<()> {
+        self.clear();
+        Ok(())
+    }
 }
 
 impl Write for Vec<u8> {
@@ -102,4 +112,9 @@ impl Write for Vec<u8> {
     fn flush(&mut self) -> OrtResult<()> {
         Ok(())
     }
+
+    fn truncate(&mut self) -> OrtResult<()> {
+        self.clear();
+        Ok(())
+    }
 }
@@ -832,4 +832,204 @@ impl<'a> Parser<'a> {
 
         Ok(i)
     }
+
+    /// Strict recursive check of one value, leaving `i` where it failed
+    fn check_value(&mut self, depth: usize) -> Result<(), &'static str> {
+        if depth > MAX_CHECK_DEPTH {
+            return Err("nested too deeply");
+        }
+        self.skip_ws();
+        match self.peek() {
+            None => Err("unexpected end of input"),
+            Some(b'{') => {
+                self.i += 1;
+                self.skip_ws();
+                if self.try_consume(b'}') {
+                    return Ok(());
+                }
+                loop {
+                    self.skip_ws();
+                    if self.peek() != Some(b'"') {
+                        return Err("expected '\"' to start a key");
+                    }
+                    self.check_string()?;
+                    self.skip_ws();
+                    if !self.try_consume(b':') {
+                        return Err("expected ':'");
+                    }
+                    self.check_value(depth + 1)?;
+                    self.skip_ws();
+                    if self.try_consume(b'}') {
+                        return Ok(());
+                    }
+                    if !self.try_consume(b',') {
+                        return Err("expected ',' or '}'");
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.i += 1;
+                self.skip_ws();
+                if self.try_consume(b']') {
+                    return Ok(());
+                }
+                loop {
+                    self.check_value(depth + 1)?;
+                    self.skip_ws();
+                    if self.try_consume(b']') {
+                        return Ok(());
+                    }
+                    if !self.try_consume(b',') {
+                        return Err("expected ',' or ']'");
+                    }
+                }
+            }
+            Some(b'"') => self.check_string(),
+            Some(b't' | b'f' | b'n') => {
+                self.i = self.find_value_end()?;
+                Ok(())
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                self.i = self.scan_number_end()?;
+                Ok(())
+            }
+            Some(_) => Err("unexpected character"),
+        }
+    }
+
+    fn check_string(&mut self) -> Result<(), &'static str> {
+        let start = self.i;
+        self.i += 1; // opening quote
+        while let Some(c) = self.peek() {
+            match c {
+                b'"' => {
+                    self.i += 1;
+                    return Ok(());
+                }
+                b'\\' => {
+                    self.i += 1;
+                    match self.peek() {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => self.i += 1,
+                        Some(b'u') => {
+                            self.i += 1;
+                            for _ in 0..4 {
+                                if !self.peek().is_some_and(|h| h.is_ascii_hexdigit()) {
+                                    return Err("invalid \\u escape");
+                                }
+                                self.i += 1;
+                            }
+                        }
+                        _ => return Err("invalid escape"),
+                    }
+                }
+                0..0x20 => return Err("control character in string, escape it"),
+                _ => self.i += 1,
+            }
+        }
+        // Point at where it started, the end of the file doesn't help
+        self.i = start;
+        Err("unterminated string")
+    }
+
+    /// 1 based line and column of the current position. Column counts characters.
+    fn line_col(&self) -> (usize, usize) {
+        let before = &self.b[..self.i.min(self.b.len())];
+        let line_start = before
+            .iter()
+            .rposition(|c| *c == b'\n')
+            .map(|p| p + 1)
+            .unwrap_or(0);
+        let line = before.iter().filter(|c| **c == b'\n').count() + 1;
+        let col = String::from_utf8_lossy(&before[line_start..])
+            .chars()
+            .count()
+            + 1;
+        (line, col)
+    }
+}
+
+// --------------------------------------------
+
+/// Deeper than this is an error in `check`, so a hostile file can't overflow the stack
+const MAX_CHECK_DEPTH: usize = 256;
+
+/// Where and why a JSON document is invalid
+#[derive(Debug, PartialEq)]
+pub struct JsonError {
+    pub msg: &'static str,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl JsonError {
+    /// e.g. "expected ':' at line 12 col 8"
+    pub fn as_string(&self) -> String {
+        let mut out = self.msg.to_string();
+        out.push_str(" at line ");
+        out.push_str(&crate::utils::num_to_string(self.line));
+        out.push_str(" col ");
+        out.push_str(&crate::utils::num_to_string(self.col));
+        out
+    }
+}
+
+/// Strictly check all of `json`, to say where it is broken after a parse
+/// failed. Slower than the parser, keep it out of the streaming path.
+pub fn check(json: &str) -> Result<(), JsonError> {
+    let mut p = Parser::new(json);
+    let res = p.check_value(0).and_then(|_| {
+        p.skip_ws();
+        if p.eof() {
+            Ok(())
+        } else {
+            Err("trailing characters after JSON value")
+        }
+    });
+    res.map_err(|msg| {
+        let (line, col) = p.line_col();
+        JsonError { msg, line, col }
+    })
+}
+
+/// `err` from parsing `json`, with the line and column if the JSON itself is broken
+pub fn locate_error(json: &str, err: Cow<'static, str>) -> Cow<'static, str> {
+    match check(json) {
+        Err(json_err) => json_err.as_string().into(),
+        Ok(()) => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_locates_errors() {
+        assert!(check(r#" {"a": [1, -2.5e3, true, null, "x\n\u00e9"], "b": {}} "#).is_ok());
+
+        let err = |s: &str| check(s).unwrap_err().as_string();
+        assert_eq!(
+            err("{\n  \"a\": 1,\n  \"b\" 2\n}"),
+            "expected ':' at line 3 col 7"
+        );
+        assert_eq!(
+            err(r#"{"a": [1, 2}"#),
+            "expected ',' or ']' at line 1 col 12"
+        );
+        assert_eq!(err(r#"{"é": tru}"#), "bad literal at line 1 col 7");
+        assert_eq!(err(r#"{"a": "x\q"}"#), "invalid escape at line 1 col 10");
+        assert_eq!(
+            err(r#"{"a": 1,}"#),
+            "expected '\"' to start a key at line 1 col 9"
+        );
+        assert_eq!(
+            err(r#"{"a": 1} x"#),
+            "trailing characters after JSON value at line 1 col 10"
+        );
+        assert_eq!(err(r#"{"a": "#), "unexpected end of input at line 1 col 7");
+        assert_eq!(
+            err(&"[".repeat(1000)),
+            "nested too deeply at line 1 col 258"
+        );
+    }
 }
@@ -0,0 +1,356 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! What each model can do, cached from the models API by `ort list`, so we
+//! can catch a request the model can't handle before sending it.
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::cli::Env;
+use crate::common::data::strip_model_suffix;
+use crate::common::json_parser::{JsonField, autoparser};
+use crate::common::{file, paths, utils};
+use crate::{ErrorKind, OrtResult, PromptOpts, ReasoningEffort, Write, ort_error};
+
+const CACHE_FILENAME: &str = "models.tsv";
+
+/// One line of the cache: id, input modalities, output modalities,
+/// supported parameters, then dollars per prompt and per completion token.
+/// Lists are comma separated.
+#[derive(Clone, Copy)]
+pub struct ModelInfo<'a> {
+    pub id: &'a str,
+    inputs: &'a str,
+    outputs: &'a str,
+    params: &'a str,
+    prompt_price: &'a str,
+    completion_price: &'a str,
+}
+
+impl<'a> ModelInfo<'a> {
+    fn from_line(line: &'a str) -> Option<Self> {
+        let mut parts = line.split('\t');
+        Some(ModelInfo {
+            id: parts.next()?,
+            inputs: parts.next()?,
+            outputs: parts.next()?,
+            params: parts.next()?,
+            // Caches from before prices were added don't have them
+            prompt_price: parts.next().unwrap_or_default(),
+            completion_price: parts.next().unwrap_or_default(),
+        })
+    }
+}
+
+/// What a model costs, in dollars per token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Price {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+fn has(list: &str, item: &str) -> bool {
+    list.split(',').any(|x| x == item)
+}
+
+#[derive(Clone, Copy)]
+enum Need {
+    ImageInput,
+    ImageOutput,
+    Reasoning,
+    Tools,
+}
+
+impl Need {
+    fn is_met(self, m: &ModelInfo) -> bool {
+        match self {
+            Need::ImageInput => has(m.inputs, "image"),
+            Need::ImageOutput => has(m.outputs, "image"),
+            Need::Reasoning => has(m.params, "reasoning"),
+            Need::Tools => has(m.params, "tools"),
+        }
+    }
+
+    fn problem(self) -> &'static str {
+        match self {
+            Need::ImageInput => " does not accept image input (-f)",
+            Need::ImageOutput => " does not generate images (--output-image)",
+            Need::Reasoning => " does not support reasoning (-r)",
+            Need::Tools => " does not support tool calls",
+        }
+    }
+}
+
+/// Convert the models API JSON into cache lines.
+pub fn to_cache(json: &str) -> OrtResult<String> {
+    let bad_json = |_| ort_error(ErrorKind::FormatError, "models JSON");
+    let mut fields = [JsonField::new_vec_raw("data")];
+    autoparser(json, &mut fields).map_err(bad_json)?;
+    let data = fields[0].get_vec_raw().unwrap_or_default();
+
+    let mut out = String::with_capacity(data.len() * 128);
+    for model in data {
+        let mut fields = [
+            JsonField::new_simple_string("id"),
+            JsonField::new_raw("architecture"),
+            JsonField::new_vec_raw("supported_parameters"),
+            JsonField::new_raw("pricing"),
+        ];
+        autoparser(&model, &mut fields).map_err(bad_json)?;
+        let Some(id) = fields[0].get_string() else {
+            continue;
+        };
+        let mut arch = [
+            JsonField::new_vec_raw("input_modalities"),
+            JsonField::new_vec_raw("output_modalities"),
+        ];
+        if let Some(a) = fields[1].get_raw() {
+            autoparser(&a, &mut arch).map_err(bad_json)?;
+        }
+        // Prices are strings, "0.000003"
+        let mut pricing = [
+            JsonField::new_simple_string("prompt"),
+            JsonField::new_simple_string("completion"),
+        ];
+        if let Some(p) = fields[3].get_raw() {
+            autoparser(&p, &mut pricing).map_err(bad_json)?;
+        }
+        out.push_str(&id);
+        for list in [
+            arch[0].get_vec_raw(),
+            arch[1].get_vec_raw(),
+            fields[2].get_vec_raw(),
+        ] {
+            out.push('\t');
+            for (i, item) in list.unwrap_or_default().iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                out.push_str(item.trim_matches('"'));
+            }
+        }
+        for price in &mut pricing {
+            out.push('\t');
+            out.push_str(&price.get_string().unwrap_or_default());
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn cache_path(env: &Env) -> OrtResult<String> {
+    paths::cache_file(env, CACHE_FILENAME)
+}
+
+/// Save the models API response for `check`
+pub fn save(env: &Env, json: &str) -> OrtResult<()> {
+    let cache = to_cache(json)?;
+    let path = CString::new(cache_path(env)?)
+        .map_err(|_| ort_error(ErrorKind::Other, "Cache path contains nul byte"))?;
+    let mut f = unsafe { file::File::create(path.as_bytes_with_nul())? };
+    f.write_all(cache.as_bytes())?;
+    Ok(())
+}
+
+/// The cache contents, if `ort list` has been run
+pub fn load(env: &Env) -> Option<String> {
+    utils::filename_read_to_string(&cache_path(env).ok()?).ok()
+}
+
+/// `:nitro` and `:floor` only change the routing, they are the plain model.
+/// `:free` models are listed as themselves.
+fn find<'a>(cache: &'a str, id: &str) -> Option<ModelInfo<'a>> {
+    let find_id = |id: &str| {
+        cache
+            .lines()
+            .filter_map(ModelInfo::from_line)
+            .find(|m| m.id == id)
+    };
+    find_id(id).or_else(|| find_id(strip_model_suffix(id)))
+}
+
+/// The model's price, if the cache has it. Models with per request or
+/// per image charges come out low, only tokens are counted.
+pub fn price(cache: &str, id: &str) -> Option<Price> {
+    let model = find(cache, id)?;
+    Some(Price {
+        prompt: model.prompt_price.parse().ok()?,
+        completion: model.completion_price.parse().ok()?,
+    })
+}
+
+/// Another model from the same vendor that can do it.
+/// The API lists newest first, so that's what we suggest.
+fn suggest<'a>(cache: &'a str, id: &str, need: Need) -> Option<&'a str> {
+    let vendor = id.split_once('/').map(|(v, _)| v)?;
+    cache
+        .lines()
+        .filter_map(ModelInfo::from_line)
+        .find(|m| m.id.split_once('/').map(|(v, _)| v) == Some(vendor) && need.is_met(m))
+        .map(|m| m.id)
+}
+
+/// The `:free` models from the same vendor as `id`, at most `max`. Its own
+/// free variant first, then newest first.
+pub fn free_variants<'a>(cache: &'a str, id: &str, max: usize) -> Vec<&'a str> {
+    let Some((vendor, _)) = id.split_once('/') else {
+        return Vec::new();
+    };
+    let own = strip_model_suffix(id).to_string() + ":free";
+    let mut free: Vec<&str> = cache
+        .lines()
+        .filter_map(ModelInfo::from_line)
+        .map(|m| m.id)
+        .filter(|m| m.ends_with(":free") && m.split_once('/').map(|(v, _)| v) == Some(vendor))
+        .collect();
+    if let Some(idx) = free.iter().position(|m| *m == own) {
+        let m = free.remove(idx);
+        free.insert(0, m);
+    }
+    free.truncate(max);
+    free
+}
+
+/// Check the request against what the models can do. Models not in the cache
+/// pass, they may be newer than it.
+/// Reasoning from the config file is dropped for a model without it, only an
+/// explicit `-r` is an error.
+/// The error is a message for the user.
+pub fn check(
+    cache: &str,
+    opts: &mut PromptOpts,
+    explicit_effort: bool,
+    has_tools: bool,
+) -> Result<(), String> {
+    let wants_reasoning = match opts.effort {
+        Some(ReasoningEffort::None) => false,
+        None => opts.reasoning_tokens.is_some(),
+        Some(_) => true,
+    };
+    for id in opts.models.clone() {
+        let Some(model) = find(cache, &id) else {
+            continue;
+        };
+        let needs = [
+            (!opts.files.is_empty(), Need::ImageInput),
+            (opts.output_image.is_some(), Need::ImageOutput),
+            (wants_reasoning, Need::Reasoning),
+            (has_tools, Need::Tools),
+        ];
+        for (_, need) in needs.into_iter().filter(|(wanted, _)| *wanted) {
+            if need.is_met(&model) {
+                continue;
+            }
+            if matches!(need, Need::Reasoning) && !explicit_effort {
+                opts.effort = None;
+                continue;
+            }
+            let mut msg = "model ".to_string() + &id + need.problem();
+            if let Some(other) = suggest(cache, &id, need) {
+                msg.push_str("; try ");
+                msg.push_str(other);
+            }
+            msg.push('\n');
+            return Err(msg);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    use super::*;
+
+    const MODELS_JSON: &str = r#"{"data":[
+        {"id":"acme/vision-2","name":"Vision","architecture":{"modality":"text+image->text","input_modalities":["text","image"],"output_modalities":["text"],"tokenizer":"Other","instruct_type":null},"pricing":{"prompt":"0.000001","completion":"0.000004","request":"0"},"supported_parameters":["max_tokens","reasoning","tools"]},
+        {"id":"acme/text-1","architecture":{"input_modalities":["text"],"output_modalities":["text"]},"supported_parameters":["max_tokens"]},
+        {"id":"other/thing","architecture":null,"supported_parameters":null}
+    ]}"#;
+
+    #[test]
+    fn test_to_cache() {
+        let cache = to_cache(MODELS_JSON).unwrap();
+        assert_eq!(
+            cache,
+            "acme/vision-2\ttext,image\ttext\tmax_tokens,reasoning,tools\t0.000001\t0.000004\n\
+             acme/text-1\ttext\ttext\tmax_tokens\t\t\n\
+             other/thing\t\t\t\t\t\n"
+        );
+    }
+
+    #[test]
+    fn test_price() {
+        let cache = to_cache(MODELS_JSON).unwrap();
+        assert_eq!(
+            price(&cache, "acme/vision-2"),
+            Some(Price {
+                prompt: 0.000001,
+                completion: 0.000004
+            })
+        );
+        assert_eq!(price(&cache, "acme/text-1"), None);
+        assert_eq!(
+            price(&cache, "acme/vision-2:nitro"),
+            price(&cache, "acme/vision-2")
+        );
+        // A cache from before prices
+        assert_eq!(price("acme/old\ttext\ttext\ttools\n", "acme/old"), None);
+    }
+
+    #[test]
+    fn test_free_variants() {
+        let cache = "acme/new:free\t\t\t\t0\t0\n\
+                     acme/big\t\t\t\t0.1\t0.1\n\
+                     other/small:free\t\t\t\t0\t0\n\
+                     acme/big:free\t\t\t\t0\t0\n\
+                     acme/old:free\t\t\t\t0\t0\n";
+        assert_eq!(
+            free_variants(cache, "acme/big:nitro", 2),
+            vec!["acme/big:free", "acme/new:free"]
+        );
+        assert_eq!(
+            free_variants(cache, "acme/other", 5),
+            vec!["acme/new:free", "acme/big:free", "acme/old:free"]
+        );
+        assert!(free_variants(cache, "nobody/model", 3).is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        let cache = to_cache(MODELS_JSON).unwrap();
+        let mut opts = PromptOpts {
+            models: vec!["acme/text-1".to_string()],
+            effort: None,
+            ..Default::default()
+        };
+        assert!(check(&cache, &mut opts, false, false).is_ok());
+
+        opts.files = vec!["cat.png".to_string()];
+        assert_eq!(
+            check(&cache, &mut opts, false, false).unwrap_err(),
+            "model acme/text-1 does not accept image input (-f); try acme/vision-2\n"
+        );
+        opts.files.clear();
+
+        // From config file, quietly turned off
+        opts.effort = Some(ReasoningEffort::High);
+        assert!(check(&cache, &mut opts, false, false).is_ok());
+        assert!(opts.effort.is_none());
+
+        opts.effort = Some(ReasoningEffort::High);
+        assert!(check(&cache, &mut opts, true, false).is_err());
+
+        // Unknown models are not checked
+        opts.models = vec!["new/model".to_string()];
+        assert!(check(&cache, &mut opts, true, true).is_ok());
+    }
+}
@@ -0,0 +1,346 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Where ort keeps its files. Each location can be moved, e.g. onto a USB
+//! stick or out of a read-only HOME in a container. The first that is set wins:
+//! - config: `ORT_CONFIG_DIR`, `${XDG_CONFIG_HOME}`, `~/.config`
+//! - cache: `ORT_CACHE_DIR`, config `cache_dir`, `${XDG_CACHE_HOME}/ort`, `~/.cache/ort`
+//! - state: `ORT_STATE_DIR`, config `state_dir`, `${XDG_STATE_HOME}/ort`, `~/.local/state/ort`
+//! - history: `ORT_HISTORY_FILE`, config `history_file`, `history` in the state dir
+//!
+//! The config can't move itself, `--config path` picks another file.
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+
+use crate::cli::Env;
+use crate::common::config::Cfg;
+use crate::common::{dir, file, utils};
+use crate::{ErrorKind, OrtResult, Write as _, ort_error, syscall};
+
+/// The prompt history, in the state dir
+const HISTORY_FILENAME: &str = "history";
+
+/// The config's keys. Only written once, after it loads.
+#[derive(Clone, Copy)]
+struct FromConfig {
+    cache_dir: Option<&'static str>,
+    state_dir: Option<&'static str>,
+    history_file: Option<&'static str>,
+}
+
+static mut FROM_CONFIG: FromConfig = FromConfig {
+    cache_dir: None,
+    state_dir: None,
+    history_file: None,
+};
+
+/// Use the config's `cache_dir`, `state_dir` and `history_file` from now on,
+/// where the environment doesn't say otherwise
+pub fn set_from_config(cfg: &Cfg) {
+    let leak = |s: &Option<String>| s.clone().map(|s| &*s.leak());
+    unsafe {
+        FROM_CONFIG = FromConfig {
+            cache_dir: leak(&cfg.cache_dir),
+            state_dir: leak(&cfg.state_dir),
+            history_file: leak(&cfg.history_file),
+        };
+    }
+}
+
+fn from_config() -> FromConfig {
+    unsafe { FROM_CONFIG }
+}
+
+/// Where `ort.cfg` and the other named configs are
+pub fn config_dir(env: &Env) -> OrtResult<String> {
+    resolve(
+        env.ORT_CONFIG_DIR,
+        None,
+        env.XDG_CONFIG_HOME,
+        env.HOME,
+        ".config",
+        "",
+    )
+}
+
+/// The full path of config `filename`
+pub fn config_file(env: &Env, filename: &str) -> OrtResult<String> {
+    Ok(config_dir(env)? + "/" + filename)
+}
+
+/// Data we can fetch again: the models cache and the update check
+pub fn cache_dir(env: &Env) -> OrtResult<String> {
+    let dir = resolve(
+        env.ORT_CACHE_DIR,
+        from_config().cache_dir,
+        env.XDG_CACHE_HOME,
+        env.HOME,
+        ".cache",
+        "ort",
+    )?;
+    utils::ensure_dir_all_exist(&dir);
+    Ok(dir)
+}
+
+pub fn cache_file(env: &Env, filename: &str) -> OrtResult<String> {
+    Ok(cache_dir(env)? + "/" + filename)
+}
+
+/// Data we can't get back: conversations, saved answers, the queue and
+/// the log. The first time it moves them out of the default cache dir,
+/// where ort used to keep them.
+pub fn state_dir(env: &Env) -> OrtResult<String> {
+    let state = resolve(
+        env.ORT_STATE_DIR,
+        from_config().state_dir,
+        env.XDG_STATE_HOME,
+        env.HOME,
+        ".local/state",
+        "ort",
+    )?;
+    let c_state = CString::new(state.as_str())
+        .map_err(|_| ort_error(ErrorKind::Other, "Null byte in state dir"))?;
+    if !utils::path_exists(&c_state) {
+        // ~/.local/state is often missing too
+        utils::ensure_dir_all_exist(&state);
+        // Only where older ort put them. A cache dir you chose is not ours
+        // to empty, and it may be the state dir.
+        if let Ok(cache) = resolve(None, None, env.XDG_CACHE_HOME, env.HOME, ".cache", "ort")
+            && cache != state
+        {
+            let moved = migrate_state(&cache, &state);
+            if moved > 0 {
+                let msg = "Moved ".to_string()
+                    + &utils::num_to_string(moved)
+                    + " conversation, history and log files from "
+                    + &cache
+                    + " to "
+                    + &state
+                    + "\n";
+                utils::warn(&msg);
+            }
+        }
+    }
+    Ok(state)
+}
+
+pub fn state_file(env: &Env, filename: &str) -> OrtResult<String> {
+    Ok(state_dir(env)? + "/" + filename)
+}
+
+/// The prompts typed at the terminal, for the up arrow and Ctrl-R
+pub fn history_file(env: &Env) -> OrtResult<String> {
+    let file = env
+        .ORT_HISTORY_FILE
+        .or(from_config().history_file)
+        .filter(|f| !f.is_empty());
+    match file {
+        Some(file) => {
+            if let Some((dir, _)) = file.rsplit_once('/') {
+                utils::ensure_dir_all_exist(dir);
+            }
+            Ok(file.to_string())
+        }
+        None => state_file(env, HISTORY_FILENAME),
+    }
+}
+
+/// The first of `var` (environment), `cfg` (config key), `xdg/sub` and
+/// `home/home_default/sub`. Empty is the same as unset.
+fn resolve(
+    var: Option<&str>,
+    cfg: Option<&str>,
+    xdg: Option<&str>,
+    home: Option<&str>,
+    home_default: &str,
+    sub: &str,
+) -> OrtResult<String> {
+    let is_set = |s: &&str| !s.is_empty();
+    if let Some(dir) = var.filter(is_set).or(cfg.filter(is_set)) {
+        return Ok(dir.trim_end_matches('/').to_string());
+    }
+    let base = match (xdg.filter(is_set), home.filter(is_set)) {
+        (Some(xdg), _) => xdg.trim_end_matches('/').to_string(),
+        (None, Some(home)) => home.trim_end_matches('/').to_string() + "/" + home_default,
+        (None, None) => {
+            return Err(ort_error(
+                ErrorKind::MissingHomeDir,
+                "Could not get home dir. Is $HOME set?",
+            ));
+        }
+    };
+    Ok(if sub.is_empty() {
+        base
+    } else {
+        base + "/" + sub
+    })
+}
+
+/// Is this file in the cache dir one that belongs in the state dir
+fn is_state_file(name: &str) -> bool {
+    (name.starts_with("last-") && name.ends_with(".json"))
+        || name == HISTORY_FILENAME
+        || name == "log.jsonl"
+        // Saved answers, without `save_dir`
+        || name.ends_with(".md")
+}
+
+/// Move the state files from `cache_dir` to `state_dir`, returns how many.
+/// Best effort, a file that won't move stays where it is.
+fn migrate_state(cache_dir: &str, state_dir: &str) -> usize {
+    let Ok(c_dir) = CString::new(cache_dir) else {
+        return 0;
+    };
+    let Ok(files) = dir::DirFiles::new(c_dir.as_c_str()) else {
+        return 0;
+    };
+    let mut moved = 0;
+    for name in files.filter(|name| is_state_file(name)) {
+        let from = cache_dir.to_string() + "/" + &name;
+        let to = state_dir.to_string() + "/" + &name;
+        let (Ok(c_from), Ok(c_to)) = (CString::new(from.as_str()), CString::new(to.as_str()))
+        else {
+            continue;
+        };
+        if syscall::rename(c_from.as_ptr(), c_to.as_ptr()) == 0 {
+            moved += 1;
+            continue;
+        }
+        // A different filesystem, copy it instead. Conversations are private.
+        let Ok(contents) = utils::filename_read_to_bytes(&from) else {
+            continue;
+        };
+        let is_copied = unsafe { file::File::create_private(c_to.as_bytes_with_nul()) }
+            .and_then(|mut f| f.write_all(&contents))
+            .is_ok();
+        if is_copied {
+            syscall::unlink(c_from.as_ptr());
+            moved += 1;
+        }
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh dir for one test, tests run in parallel and so can others
+    fn test_dir(name: &str) -> String {
+        "/tmp/ort-".to_string() + name + "-" + &utils::num_to_string(syscall::getpid())
+    }
+
+    #[test]
+    fn precedence() {
+        let home = Some("/home/u");
+        let xdg = Some("/xdg");
+        let r = |var, cfg, xdg, home| resolve(var, cfg, xdg, home, ".cache", "ort");
+        // Each level wins over everything below it
+        assert_eq!(r(Some("/env"), Some("/cfg"), xdg, home).unwrap(), "/env");
+        assert_eq!(r(Some("/env/"), None, None, None).unwrap(), "/env");
+        assert_eq!(r(None, Some("/cfg"), xdg, home).unwrap(), "/cfg");
+        assert_eq!(r(None, Some("/cfg"), None, None).unwrap(), "/cfg");
+        assert_eq!(r(None, None, xdg, home).unwrap(), "/xdg/ort");
+        assert_eq!(r(None, None, Some("/xdg/"), None).unwrap(), "/xdg/ort");
+        assert_eq!(r(None, None, None, home).unwrap(), "/home/u/.cache/ort");
+        // Set but empty is unset
+        assert_eq!(r(Some(""), Some("/cfg"), xdg, home).unwrap(), "/cfg");
+        assert_eq!(
+            r(Some(""), Some(""), Some(""), home).unwrap(),
+            "/home/u/.cache/ort"
+        );
+        let err = r(Some(""), None, Some(""), Some("")).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::MissingHomeDir));
+        // The config dir has no `ort` under it
+        let config = resolve(None, None, None, home, ".config", "").unwrap();
+        assert_eq!(config, "/home/u/.config");
+    }
+
+    #[test]
+    fn env_locations() {
+        let dir = test_dir("paths-test");
+        let at = |sub: &str| &*(dir.clone() + sub).leak();
+        let env = Env {
+            HOME: Some("/nonexistent-home"),
+            ORT_CONFIG_DIR: Some("/usb/ort/config"),
+            ORT_CACHE_DIR: Some(at("/cache")),
+            ORT_STATE_DIR: Some(at("/state")),
+            ORT_HISTORY_FILE: Some(at("/hist/prompts")),
+            ..Default::default()
+        };
+        assert_eq!(
+            config_file(&env, "ort.cfg").unwrap(),
+            "/usb/ort/config/ort.cfg"
+        );
+        assert_eq!(
+            cache_file(&env, "models.tsv").unwrap(),
+            dir.clone() + "/cache/models.tsv"
+        );
+        assert_eq!(
+            state_file(&env, "last-0.json").unwrap(),
+            dir.clone() + "/state/last-0.json"
+        );
+        assert_eq!(history_file(&env).unwrap(), dir.clone() + "/hist/prompts");
+        let exists = |path: &str| utils::path_exists(&CString::new(path).unwrap());
+        assert!(exists(&(dir.clone() + "/cache")));
+        assert!(exists(&(dir.clone() + "/state")));
+        assert!(exists(&(dir.clone() + "/hist")));
+
+        let state_only = Env {
+            ORT_STATE_DIR: Some(at("/state")),
+            XDG_CACHE_HOME: Some(at("/xdg-cache")),
+            ..Default::default()
+        };
+        assert_eq!(
+            history_file(&state_only).unwrap(),
+            dir.clone() + "/state/history"
+        );
+    }
+
+    #[test]
+    fn state_migration() {
+        let dir = test_dir("state-migrate-test");
+        let (cache, state) = (dir.clone() + "/cache", dir.clone() + "/state/ort");
+        utils::ensure_dir_all_exist(&cache);
+        utils::ensure_dir_all_exist(&state);
+        let write = |path: String, contents: &str| {
+            let c_path = CString::new(path).unwrap();
+            let mut f = unsafe { file::File::create(c_path.as_bytes_with_nul()) }.unwrap();
+            f.write_all(contents.as_bytes()).unwrap();
+        };
+        for name in ["last-0.json", "history", "log.jsonl", "models.tsv"] {
+            write(cache.clone() + "/" + name, name);
+        }
+
+        assert_eq!(migrate_state(&cache, &state), 3);
+        let exists = |path: String| utils::path_exists(&CString::new(path).unwrap());
+        assert!(exists(cache.clone() + "/models.tsv"));
+        assert!(!exists(cache.clone() + "/history"));
+        assert_eq!(
+            utils::filename_read_to_string(&(state.clone() + "/last-0.json")).unwrap(),
+            "last-0.json"
+        );
+        assert!(!exists(state + "/models.tsv"));
+
+        // Only out of the default cache dir, not one you chose
+        let at = |sub: &str| &*(dir.clone() + sub).leak();
+        utils::ensure_dir_all_exist(at("/xdg/ort"));
+        utils::ensure_dir_all_exist(at("/chosen"));
+        write(dir.clone() + "/xdg/ort/last-1.json", "default");
+        write(dir.clone() + "/chosen/last-2.json", "chosen");
+        let env = Env {
+            XDG_CACHE_HOME: Some(at("/xdg")),
+            ORT_CACHE_DIR: Some(at("/chosen")),
+            ORT_STATE_DIR: Some(at("/new-state")),
+            ..Default::default()
+        };
+        assert_eq!(state_dir(&env).unwrap(), dir.clone() + "/new-state");
+        assert!(exists(dir.clone() + "/new-state/last-1.json"));
+        assert!(exists(dir.clone() + "/chosen/last-2.json"));
+    }
+}
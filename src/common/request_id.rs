@@ -0,0 +1,97 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Our own id for each attempt at a request, a ULID. It goes in the
+//! `X-Client-Request-Id` header, the log and --dump-wire, and is printed when
+//! the run fails, so the attempts of a failure with retries can be matched up.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::syscall;
+
+/// Crockford's base32, as ULID uses
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+struct Attempts {
+    is_locked: AtomicBool,
+    ids: UnsafeCell<Vec<String>>,
+}
+
+// Only touched with `is_locked` held
+unsafe impl Sync for Attempts {}
+
+static ATTEMPTS: Attempts = Attempts {
+    is_locked: AtomicBool::new(false),
+    ids: UnsafeCell::new(Vec::new()),
+};
+
+fn with_ids<F: FnOnce(&mut Vec<String>) -> R, R>(f: F) -> R {
+    while ATTEMPTS
+        .is_locked
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    let out = f(unsafe { &mut *ATTEMPTS.ids.get() });
+    ATTEMPTS.is_locked.store(false, Ordering::Release);
+    out
+}
+
+/// A new id, not remembered. For `ort serve-openai` which runs forever.
+pub fn new() -> String {
+    // getrandom fills multiples of 8 bytes, ULID wants 10
+    let mut random = [0u8; 16];
+    syscall::getrandom(&mut random);
+    encode(syscall::unix_time_ms(), &random[..10])
+}
+
+/// A new id for one attempt of this run, remembered for `attempts`
+pub fn attempt() -> String {
+    let id = new();
+    with_ids(|ids| ids.push(id.clone()));
+    id
+}
+
+/// Every attempt's id so far, oldest first
+pub fn attempts() -> Vec<String> {
+    with_ids(|ids| ids.clone())
+}
+
+/// 48 bits of milliseconds then 80 random bits, 26 characters
+fn encode(unix_ms: u64, random: &[u8]) -> String {
+    let mut n = ((unix_ms & 0xffff_ffff_ffff) as u128) << 80;
+    for (i, b) in random.iter().enumerate() {
+        n |= (*b as u128) << (72 - i * 8);
+    }
+    let mut out = [0u8; 26];
+    for c in out.iter_mut().rev() {
+        *c = ALPHABET[(n & 0x1f) as usize];
+        n >>= 5;
+    }
+    // Only ALPHABET bytes
+    unsafe { String::from_utf8_unchecked(out.to_vec()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode;
+
+    #[test]
+    fn test_encode() {
+        // The example from the ULID spec
+        let id = encode(1469918176385, &[0; 10]);
+        assert_eq!(id.len(), 26);
+        assert_eq!(&id[..10], "01ARYZ6S41");
+        assert_eq!(&id[10..], "0000000000000000");
+        assert!(encode(1469918176385, &[0xff; 10]).ends_with("ZZZZZZZZZZZZZZZZ"));
+    }
+}
@@ -62,6 +62,9 @@ pub unsafe fn resolve(host: &str) -> OrtResult<Vec<Ipv4Addr>> {
         &addr as *const _ as *const syscall::sockaddr,
         addr_len,
     );
+    if res == syscall::ENETUNREACH {
+        return Err(ort_error(ErrorKind::NoNetwork, "network unreachable"));
+    }
     if res < 0 {
         return Err(ort_error(ErrorKind::DnsResolveFailed, "connect failed"));
     }
@@ -4,24 +4,282 @@
 //! MIT License
 //! Copyright (c) 2025 Graham King
 
+use core::net::SocketAddrV4;
 use core::ops::AddAssign;
+use core::str::FromStr;
 use core::time::Duration;
 
 extern crate alloc;
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::string::ToString;
+use alloc::vec::Vec;
 
-use crate::utils;
+use crate::common::alloc::{AllocStats, alloc_stats};
+use crate::common::data::ModelSource;
+use crate::{Priority, syscall, utils};
+
+/// How costs are shown, config `currency_display`
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum CurrencyDisplay {
+    #[default]
+    Cents,
+    Dollars,
+    Both,
+}
+
+impl FromStr for CurrencyDisplay {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cents" => Ok(CurrencyDisplay::Cents),
+            "dollars" => Ok(CurrencyDisplay::Dollars),
+            "both" => Ok(CurrencyDisplay::Both),
+            _ => Err("CurrencyDisplay: Invalid string value"),
+        }
+    }
+}
 
 #[derive(Default, Clone)]
 pub struct Stats {
     pub used_model: String,
     pub provider: String,
+    /// How we asked OpenRouter to pick the provider, -p / --priority
+    pub priority: Option<Priority>,
     pub cost_in_cents: Option<f64>, // Divide by 100 for $
+    pub currency: CurrencyDisplay,
+    /// Agent mode: cost of every prompt so far, in cents
+    pub session_cost_in_cents: Option<f32>,
+    /// Bring Your Own Key, the provider bills for inference directly
+    pub is_byok: bool,
+    /// In dollars, what a BYOK provider charged
+    pub upstream_cost: Option<f32>,
     pub elapsed_time: Duration,
     pub time_to_first_token: Option<Duration>,
-    pub inter_token_latency_ms: u128,
+    pub inter_token_latency_ms: u32,
     pub web_search_requests: Option<u32>,
+    /// From the final usage message. Includes reasoning tokens.
+    pub completion_tokens: Option<u32>,
+    pub reasoning_tokens: Option<u32>,
+    /// Time spent streaming reasoning, from first reasoning chunk to first content chunk.
+    pub reasoning_time: Option<Duration>,
+    /// Time spent streaming content, from first content chunk to the end.
+    pub content_time: Option<Duration>,
+    /// Median and 95th percentile gap between stream chunks.
+    pub chunk_gap_p50: Option<Duration>,
+    pub chunk_gap_p95: Option<Duration>,
+    /// --deadline cut the answer short
+    pub is_truncated: bool,
+    /// We hung up and OpenRouter stopped the generation. The cost is what
+    /// it billed for the part that was made.
+    pub is_cancelled: bool,
+    /// --samples, how many completions the cost covers
+    pub samples: u32,
+    /// Stream events that weren't valid JSON, skipped
+    pub malformed_events: u32,
+    /// Where the answer was saved as markdown, with config save_answer
+    pub saved_to: Option<Box<str>>,
+    /// `--verbose` extras, boxed to keep Stats small
+    pub verbose: Option<Box<Verbose>>,
+    /// `--show-connection`, who served the request
+    pub connection: Option<Box<Connection>>,
+    /// `--show-model-source`, why this model
+    pub model_source: Option<Box<Provenance>>,
+    /// `--reasoning-tokens`, and how much reasoning we counted
+    pub reasoning_budget: Option<ReasoningBudget>,
+    /// `--retries`, every try in order, the last one is this answer
+    pub attempts: Vec<Attempt>,
+}
+
+/// One try at the pinned provider
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Attempt {
+    /// Why it failed, None if it answered
+    pub error: Option<String>,
+    pub elapsed: Duration,
+    pub time_to_first_token: Option<Duration>,
+}
+
+/// "1 failed after 800ms (HttpStatusError), 2 ok in 2.1s (400ms TTFT)"
+pub(crate) fn attempts_string(attempts: &[Attempt]) -> String {
+    let mut s = String::new();
+    for (i, a) in attempts.iter().enumerate() {
+        if i != 0 {
+            s.push_str(", ");
+        }
+        s.push_str(&utils::num_to_string(i + 1));
+        match a.error.as_deref() {
+            Some(err) => {
+                s.push_str(" failed after ");
+                s.push_str(&format_duration(a.elapsed));
+                s.push_str(" (");
+                s.push_str(err);
+                s.push(')');
+            }
+            None => {
+                s.push_str(" ok in ");
+                s.push_str(&format_duration(a.elapsed));
+                if let Some(ttft) = a.time_to_first_token {
+                    s.push_str(" (");
+                    s.push_str(&format_duration(ttft));
+                    s.push_str(" TTFT)");
+                }
+            }
+        }
+    }
+    s
+}
+
+/// Our own count of the reasoning, because some providers ignore the budget
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ReasoningBudget {
+    pub budget: u32,
+    /// Estimated tokens of reasoning streamed
+    pub estimate: u32,
+    /// It went well over, so the rest wasn't shown
+    pub is_hidden: bool,
+}
+
+/// Which layer chose the model, and what happened after
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Provenance {
+    pub source: ModelSource,
+    /// With `ModelSource::Profile`, its name
+    pub profile: Option<String>,
+    /// Models given up on after a first token timeout, in order
+    pub fallback_from: Vec<String>,
+    /// Slow providers skipped after a first token timeout
+    pub provider_retries: u32,
+}
+
+impl Provenance {
+    // "config profile work", "fallback from a/b (config), 1 provider skipped"
+    fn as_string(&self) -> String {
+        let mut s = String::from(self.source.as_str());
+        if let Some(profile) = self.profile.as_deref() {
+            s.push(' ');
+            s.push_str(profile);
+        }
+        if !self.fallback_from.is_empty() {
+            s = "fallback from ".to_string() + &self.fallback_from.join(", ") + " (" + &s + ")";
+        }
+        if self.provider_retries != 0 {
+            s.push_str(", ");
+            s.push_str(&utils::num_to_string(self.provider_retries));
+            s.push_str(if self.provider_retries == 1 {
+                " provider skipped"
+            } else {
+                " providers skipped"
+            });
+        }
+        s
+    }
+}
+
+/// Where the request went, to diagnose regional routing
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Connection {
+    pub peer: Option<SocketAddrV4>,
+    /// From the status line, e.g. "HTTP/1.1"
+    pub protocol: String,
+    /// Cloudflare data center, e.g. "SJC"
+    pub colo: Option<String>,
+}
+
+impl Connection {
+    // "104.18.2.115:443, HTTP/1.1 over TLS 1.3, Cloudflare SJC"
+    fn as_string(&self) -> String {
+        let mut s = match self.peer {
+            Some(peer) => peer.to_string() + ", ",
+            None => String::new(),
+        };
+        s.push_str(&self.protocol);
+        s.push_str(" over TLS 1.3");
+        if let Some(colo) = self.colo.as_deref() {
+            s.push_str(", Cloudflare ");
+            s.push_str(colo);
+        }
+        s
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Verbose {
+    /// The whole process so far
+    pub memory: Memory,
+    /// How long the answer waited on our own output
+    pub writer_lag: Option<WriterLag>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Memory {
+    pub peak_rss_kib: Option<u64>,
+    pub alloc: AllocStats,
+}
+
+impl Memory {
+    pub fn now() -> Self {
+        Memory {
+            peak_rss_kib: syscall::peak_rss_kib(),
+            alloc: alloc_stats(),
+        }
+    }
+
+    // "3.1 MB peak RSS, 1,234 allocations, 812.0 KB allocated (1.0 MB mapped)"
+    fn as_string(&self) -> String {
+        let mut parts: Vec<String> = Vec::with_capacity(3);
+        if let Some(kib) = self.peak_rss_kib {
+            parts.push(utils::format_size(kib * 1024) + " peak RSS");
+        }
+        // No counts from the system allocator
+        if self.alloc.count != 0 {
+            parts.push(
+                utils::with_thousands(&utils::num_to_string(self.alloc.count)) + " allocations",
+            );
+            parts.push(
+                utils::format_size(self.alloc.bytes as u64)
+                    + " allocated ("
+                    + &utils::format_size(self.alloc.mapped as u64)
+                    + " mapped)",
+            );
+        }
+        parts.join(", ")
+    }
+}
+
+/// Time from an event arriving off the network to the writers (screen, file,
+/// last and answer) being done with it. When this is near the chunk gap the
+/// terminal, not the provider, is what's slow.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WriterLag {
+    pub events: u32,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl WriterLag {
+    pub fn record(&mut self, lag: Duration) {
+        self.events += 1;
+        self.max = self.max.max(lag);
+        self.total += lag;
+    }
+
+    pub fn avg(&self) -> Duration {
+        if self.events == 0 {
+            return Duration::ZERO;
+        }
+        self.total / self.events
+    }
+
+    // "412 events, max 3ms, avg 180µs"
+    fn as_string(&self) -> String {
+        utils::with_thousands(&utils::num_to_string(self.events))
+            + " events, max "
+            + &format_lag(self.max)
+            + ", avg "
+            + &format_lag(self.avg())
+    }
 }
 
 impl AddAssign for Stats {
@@ -29,12 +287,36 @@ impl AddAssign for Stats {
         if let Some(cost) = other.cost_in_cents {
             *self.cost_in_cents.get_or_insert(0.0) += cost;
         }
+        self.is_byok |= other.is_byok;
+        self.is_truncated |= other.is_truncated;
+        self.is_cancelled |= other.is_cancelled;
+        self.samples = self.samples.max(other.samples);
+        self.malformed_events += other.malformed_events;
+        if let Some(cost) = other.upstream_cost {
+            *self.upstream_cost.get_or_insert(0.0) += cost;
+        }
         if let Some(web_searches) = other.web_search_requests {
             *self.web_search_requests.get_or_insert(0) += web_searches;
         }
+        if let Some(tokens) = other.completion_tokens {
+            *self.completion_tokens.get_or_insert(0) += tokens;
+        }
+        if let Some(tokens) = other.reasoning_tokens {
+            *self.reasoning_tokens.get_or_insert(0) += tokens;
+        }
+        if let Some(d) = other.reasoning_time {
+            *self.reasoning_time.get_or_insert(Duration::ZERO) += d;
+        }
+        if let Some(d) = other.content_time {
+            *self.content_time.get_or_insert(Duration::ZERO) += d;
+        }
         if self.time_to_first_token.is_none() {
             self.time_to_first_token = other.time_to_first_token;
         }
+        if self.chunk_gap_p50.is_none() {
+            self.chunk_gap_p50 = other.chunk_gap_p50;
+            self.chunk_gap_p95 = other.chunk_gap_p95;
+        }
         self.elapsed_time += other.elapsed_time;
         if self.provider.is_empty() {
             self.provider = other.provider;
@@ -42,6 +324,27 @@ impl AddAssign for Stats {
         if self.used_model.is_empty() {
             self.used_model = other.used_model;
         }
+        if self.priority.is_none() {
+            self.priority = other.priority;
+        }
+        if self.connection.is_none() {
+            self.connection = other.connection;
+        }
+        if self.model_source.is_none() {
+            self.model_source = other.model_source;
+        }
+        match (self.reasoning_budget.as_mut(), other.reasoning_budget) {
+            (Some(rb), Some(other)) => {
+                rb.estimate += other.estimate;
+                rb.is_hidden |= other.is_hidden;
+            }
+            (None, other) => self.reasoning_budget = other,
+            (Some(_), None) => {}
+        }
+        if self.attempts.is_empty() {
+            self.attempts = other.attempts;
+        }
+        self.currency = other.currency;
     }
 }
 
@@ -50,16 +353,67 @@ impl Stats {
         &self.provider
     }
 
+    /// Generated tokens per second of streaming time, using the provider's token count.
+    /// None if the provider did not report usage or we have no timing.
+    pub fn tokens_per_sec(&self) -> Option<f64> {
+        let tokens = self.completion_tokens?;
+        let stream_time =
+            self.reasoning_time.unwrap_or_default() + self.content_time.unwrap_or_default();
+        if tokens == 0 || stream_time.is_zero() {
+            return None;
+        }
+        Some(tokens as f64 / stream_time.as_secs_f64())
+    }
+
+    /// Store p50 and p95 of the gaps between stream chunks.
+    pub fn set_chunk_gaps(&mut self, mut gaps: Vec<Duration>) {
+        if gaps.is_empty() {
+            return;
+        }
+        gaps.sort_unstable();
+        self.chunk_gap_p50 = Some(percentile(&gaps, 50));
+        self.chunk_gap_p95 = Some(percentile(&gaps, 95));
+    }
+
     pub(crate) fn as_string(&self) -> String {
         // "{used_model} at {provider}. {cost_in_cents:.4} cents. {elapsed_time} ({time_to_first_token} TTFT, {inter_token_latency_ms}ms ITL)",
         let mut s = String::with_capacity(256);
         s.push_str(&self.used_model);
         s.push_str(" at ");
         s.push_str(&self.provider);
+        if let Some(p) = self.priority {
+            s.push_str(" (by ");
+            s.push_str(p.as_str());
+            s.push(')');
+        }
         s.push_str(". ");
         if let Some(cost_in_cents) = self.cost_in_cents {
-            s.push_str(&utils::float_to_string(cost_in_cents, 4));
-            s.push_str(" cents. ");
+            push_cost(&mut s, cost_in_cents, self.currency);
+            if self.is_cancelled {
+                s.push_str(" for the cancelled part");
+            } else if self.samples > 1 {
+                s.push_str(" for ");
+                s.push_str(&utils::num_to_string(self.samples));
+                s.push_str(" samples");
+            }
+            if let Some(session) = self.session_cost_in_cents {
+                s.push_str(" (session ");
+                push_cost(&mut s, session as f64, self.currency);
+                s.push(')');
+            }
+            s.push_str(". ");
+        }
+        if self.is_byok {
+            s.push_str("BYOK");
+            if let Some(upstream) = self.upstream_cost {
+                s.push_str(" (upstream $");
+                s.push_str(&utils::with_thousands(&utils::float_to_string(
+                    upstream as f64,
+                    4,
+                )));
+                s.push(')');
+            }
+            s.push_str(". ");
         }
         if let Some(web_search_requests) = self.web_search_requests {
             s.push_str(&utils::num_to_string(web_search_requests));
@@ -76,12 +430,143 @@ impl Stats {
             ));
             s.push_str(" TTFT, ");
             s.push_str(&utils::num_to_string(self.inter_token_latency_ms as usize));
-            s.push_str("ms ITL)");
+            s.push_str("ms ITL");
+            if let Some(tps) = self.tokens_per_sec() {
+                s.push_str(", ");
+                s.push_str(&utils::num_to_string(tps as usize));
+                s.push_str(" tok/s");
+            }
+            if let (Some(p50), Some(p95)) = (self.chunk_gap_p50, self.chunk_gap_p95) {
+                s.push_str(", gap p50 ");
+                s.push_str(&format_duration(p50));
+                s.push_str(" p95 ");
+                s.push_str(&format_duration(p95));
+            }
+            s.push(')');
+        }
+        if self.is_truncated {
+            if !s.ends_with(". ") {
+                s.push_str(". ");
+            }
+            s.push_str("Truncated by --deadline");
+        }
+        if self.malformed_events != 0 {
+            if !s.ends_with(". ") {
+                s.push_str(". ");
+            }
+            s.push_str(&utils::num_to_string(self.malformed_events));
+            s.push_str(if self.malformed_events == 1 {
+                " malformed event skipped"
+            } else {
+                " malformed events skipped"
+            });
+        }
+        if let Some(rb) = self.reasoning_budget {
+            if !s.ends_with(". ") {
+                s.push_str(". ");
+            }
+            // The provider's count if it sent one
+            match self.reasoning_tokens {
+                Some(tokens) => {
+                    s.push_str("Reasoning ");
+                    s.push_str(&utils::with_thousands(&utils::num_to_string(tokens)));
+                }
+                None => {
+                    s.push_str("Reasoning ~");
+                    s.push_str(&utils::with_thousands(&utils::num_to_string(rb.estimate)));
+                }
+            }
+            s.push_str(" tokens of a ");
+            s.push_str(&utils::with_thousands(&utils::num_to_string(rb.budget)));
+            s.push_str(" budget");
+            if rb.is_hidden {
+                s.push_str(", the rest not shown");
+            }
+        }
+        if !self.attempts.is_empty() {
+            if !s.ends_with(". ") {
+                s.push_str(". ");
+            }
+            s.push_str("Attempts: ");
+            s.push_str(&attempts_string(&self.attempts));
+        }
+        if let Some(saved_to) = self.saved_to.as_deref() {
+            s.push_str(". Saved to ");
+            s.push_str(saved_to);
+        }
+        if let Some(conn) = self.connection.as_deref() {
+            if !s.ends_with(". ") {
+                s.push_str(". ");
+            }
+            s.push_str("Connection: ");
+            s.push_str(&conn.as_string());
+        }
+        if let Some(source) = self.model_source.as_deref() {
+            if !s.ends_with(". ") {
+                s.push_str(". ");
+            }
+            s.push_str("Model from: ");
+            s.push_str(&source.as_string());
+        }
+        if let Some(verbose) = self.verbose.as_deref() {
+            // After the timings, or straight after the cost if there are none
+            if !s.ends_with(". ") {
+                s.push_str(". ");
+            }
+            s.push_str("Memory: ");
+            s.push_str(&verbose.memory.as_string());
+            if let Some(lag) = verbose.writer_lag {
+                s.push_str(". Writer lag: ");
+                s.push_str(&lag.as_string());
+            }
         }
         s
     }
 }
 
+// "0.0600 cents", "$0.000600" or both
+pub(crate) fn push_cost(s: &mut String, cents: f64, currency: CurrencyDisplay) {
+    let cents = utils::float_to_string(cents, 4);
+    let as_cents = || utils::with_thousands(&cents) + " cents";
+    let as_dollars = || "$".to_string() + &utils::with_thousands(&cents_to_dollars(&cents));
+    match currency {
+        CurrencyDisplay::Cents => s.push_str(&as_cents()),
+        CurrencyDisplay::Dollars => s.push_str(&as_dollars()),
+        CurrencyDisplay::Both => {
+            s.push_str(&as_cents());
+            s.push_str(" (");
+            s.push_str(&as_dollars());
+            s.push(')');
+        }
+    }
+}
+
+// Move the decimal point of "1234.5678" two places left. Dividing the float
+// by 100 instead would come out a little different after truncation.
+fn cents_to_dollars(cents: &str) -> String {
+    let digits: String = cents.chars().filter(|c| *c != '.').collect();
+    // At least one digit before the point
+    let digits = "00".to_string() + &digits;
+    let (int, frac) = digits.split_at(digits.len() - 6);
+    let int = int.trim_start_matches('0');
+    let int = if int.is_empty() { "0" } else { int };
+    int.to_string() + "." + frac
+}
+
+// Nearest-rank percentile. `sorted` must be sorted and not empty.
+fn percentile(sorted: &[Duration], pct: usize) -> Duration {
+    let rank = (pct * sorted.len()).div_ceil(100);
+    sorted[rank.saturating_sub(1).min(sorted.len() - 1)]
+}
+
+// Writing is usually well under a millisecond, so show those in microseconds
+fn format_lag(d: Duration) -> String {
+    if d >= Duration::from_millis(1) {
+        return format_duration(d);
+    }
+    utils::num_to_string(d.as_micros() as usize) + "µs"
+}
+
 // Format the Duration as minutes, seconds and milliseconds.
 // examples: 3m12s, 5s, 400ms, 12m, 4s
 fn format_duration(d: Duration) -> String {
@@ -126,9 +611,18 @@ fn format_duration(d: Duration) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::format_duration;
+    use super::{
+        Attempt, Connection, CurrencyDisplay, Memory, ModelSource, Priority, Provenance,
+        ReasoningBudget, Stats, Verbose, WriterLag, format_duration, format_lag, percentile,
+    };
+    use crate::common::alloc::AllocStats;
     use core::time::Duration;
 
+    extern crate alloc;
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+    use alloc::vec;
+
     #[test]
     fn format_duration_zero() {
         assert_eq!(format_duration(Duration::from_millis(0)), "0ms");
@@ -160,4 +654,303 @@ mod tests {
         let d = Duration::from_secs(3 * 60 + 12);
         assert_eq!(format_duration(d), "3m12s");
     }
+
+    #[test]
+    fn percentile_nearest_rank() {
+        let gaps: alloc::vec::Vec<Duration> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&gaps, 50), Duration::from_millis(10));
+        assert_eq!(percentile(&gaps, 95), Duration::from_millis(19));
+        assert_eq!(percentile(&gaps[..1], 95), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn chunk_gaps_unsorted() {
+        let mut stats = Stats::default();
+        stats.set_chunk_gaps(vec![
+            Duration::from_millis(30),
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+        ]);
+        assert_eq!(stats.chunk_gap_p50, Some(Duration::from_millis(20)));
+        assert_eq!(stats.chunk_gap_p95, Some(Duration::from_millis(30)));
+    }
+
+    #[test]
+    fn byok_shows_upstream_cost() {
+        let stats = Stats {
+            used_model: "openai/gpt-5".to_string(),
+            provider: "OpenAI".to_string(),
+            cost_in_cents: Some(0.06),
+            is_byok: true,
+            upstream_cost: Some(0.012),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "openai/gpt-5 at OpenAI. 0.0600 cents. BYOK (upstream $0.0120). "
+        );
+    }
+
+    #[test]
+    fn truncated_by_deadline() {
+        let mut stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            is_truncated: true,
+            ..Default::default()
+        };
+        assert_eq!(stats.as_string(), "m at p. Truncated by --deadline");
+        stats.elapsed_time = Duration::from_secs(60);
+        assert_eq!(stats.as_string(), "m at p. 1m. Truncated by --deadline");
+    }
+
+    #[test]
+    fn currency_display() {
+        let mut stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            cost_in_cents: Some(123456.5),
+            ..Default::default()
+        };
+        assert_eq!(stats.as_string(), "m at p. 123,456.5000 cents. ");
+        stats.currency = CurrencyDisplay::Dollars;
+        assert_eq!(stats.as_string(), "m at p. $1,234.565000. ");
+        stats.cost_in_cents = Some(0.06);
+        stats.currency = CurrencyDisplay::Both;
+        stats.session_cost_in_cents = Some(0.5);
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 0.0600 cents ($0.000600) (session 0.5000 cents ($0.005000)). "
+        );
+    }
+
+    #[test]
+    fn shows_priority() {
+        let stats = Stats {
+            used_model: "openai/gpt-oss-20b".to_string(),
+            provider: "DeepInfra".to_string(),
+            priority: Some(Priority::Throughput),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "openai/gpt-oss-20b at DeepInfra (by throughput). "
+        );
+    }
+
+    #[test]
+    fn shows_memory() {
+        let mut stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            elapsed_time: Duration::from_secs(5),
+            verbose: Some(Box::new(Verbose {
+                memory: Memory {
+                    peak_rss_kib: Some(3200),
+                    alloc: AllocStats {
+                        count: 1234,
+                        bytes: 831_488,
+                        mapped: 1024 * 1024,
+                    },
+                },
+                writer_lag: None,
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 5s. Memory: 3.1 MB peak RSS, 1,234 allocations, 812.0 KB allocated (1.0 MB mapped)"
+        );
+        stats.verbose = Some(Box::new(Verbose {
+            memory: Memory {
+                peak_rss_kib: Some(900),
+                alloc: AllocStats::default(),
+            },
+            writer_lag: None,
+        }));
+        assert!(stats.as_string().ends_with("Memory: 900.0 KB peak RSS"));
+    }
+
+    #[test]
+    fn shows_writer_lag() {
+        let mut lag = WriterLag::default();
+        assert_eq!(lag.avg(), Duration::ZERO);
+        lag.record(Duration::from_micros(100));
+        lag.record(Duration::from_micros(300));
+        lag.record(Duration::from_millis(3));
+        let stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            elapsed_time: Duration::from_secs(5),
+            verbose: Some(Box::new(Verbose {
+                memory: Memory {
+                    peak_rss_kib: Some(900),
+                    alloc: AllocStats::default(),
+                },
+                writer_lag: Some(lag),
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 5s. Memory: 900.0 KB peak RSS. Writer lag: 3 events, max 3ms, avg 1ms"
+        );
+        assert_eq!(format_lag(Duration::from_micros(180)), "180µs");
+    }
+
+    #[test]
+    fn shows_cancelled_cost() {
+        let stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            cost_in_cents: Some(0.03),
+            is_cancelled: true,
+            is_truncated: true,
+            elapsed_time: Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 0.0300 cents for the cancelled part. 1m. Truncated by --deadline"
+        );
+
+        let stats = Stats {
+            is_cancelled: false,
+            is_truncated: false,
+            samples: 3,
+            ..stats
+        };
+        assert_eq!(stats.as_string(), "m at p. 0.0300 cents for 3 samples. 1m");
+
+        let stats = Stats {
+            malformed_events: 2,
+            ..stats
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 0.0300 cents for 3 samples. 1m. 2 malformed events skipped"
+        );
+
+        let stats = Stats {
+            malformed_events: 0,
+            reasoning_budget: Some(ReasoningBudget {
+                budget: 2000,
+                estimate: 3100,
+                is_hidden: true,
+            }),
+            ..stats
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 0.0300 cents for 3 samples. 1m. Reasoning ~3,100 tokens of a 2,000 budget, the rest not shown"
+        );
+        let stats = Stats {
+            reasoning_tokens: Some(3412),
+            ..stats
+        };
+        assert!(
+            stats
+                .as_string()
+                .ends_with("Reasoning 3,412 tokens of a 2,000 budget, the rest not shown")
+        );
+    }
+
+    #[test]
+    fn shows_attempts() {
+        let stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            elapsed_time: Duration::from_secs(5),
+            attempts: vec![
+                Attempt {
+                    error: Some("HttpStatusError: 502".to_string()),
+                    elapsed: Duration::from_millis(800),
+                    time_to_first_token: None,
+                },
+                Attempt {
+                    error: None,
+                    elapsed: Duration::from_secs(5),
+                    time_to_first_token: Some(Duration::from_millis(400)),
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 5s. Attempts: 1 failed after 800ms (HttpStatusError: 502), 2 ok in 5s (400ms TTFT)"
+        );
+    }
+
+    #[test]
+    fn shows_connection() {
+        let mut stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            elapsed_time: Duration::from_secs(5),
+            connection: Some(Box::new(Connection {
+                peer: Some("104.18.2.115:443".parse().unwrap()),
+                protocol: "HTTP/1.1".to_string(),
+                colo: Some("SJC".to_string()),
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 5s. Connection: 104.18.2.115:443, HTTP/1.1 over TLS 1.3, Cloudflare SJC"
+        );
+        stats.connection = Some(Box::new(Connection {
+            protocol: "HTTP/1.1".to_string(),
+            ..Default::default()
+        }));
+        assert!(
+            stats
+                .as_string()
+                .ends_with("Connection: HTTP/1.1 over TLS 1.3")
+        );
+    }
+
+    #[test]
+    fn shows_model_source() {
+        let mut stats = Stats {
+            used_model: "m".to_string(),
+            provider: "p".to_string(),
+            elapsed_time: Duration::from_secs(5),
+            model_source: Some(Box::new(Provenance {
+                source: ModelSource::Profile,
+                profile: Some("work".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.as_string(),
+            "m at p. 5s. Model from: config profile work"
+        );
+        stats.model_source = Some(Box::new(Provenance {
+            source: ModelSource::Config,
+            fallback_from: vec!["a/b".to_string()],
+            provider_retries: 2,
+            ..Default::default()
+        }));
+        assert!(
+            stats
+                .as_string()
+                .ends_with("Model from: fallback from a/b (config), 2 providers skipped"),
+            "{}",
+            stats.as_string()
+        );
+    }
+
+    #[test]
+    fn tokens_per_sec_uses_usage_tokens() {
+        let mut stats = Stats {
+            completion_tokens: Some(300),
+            reasoning_time: Some(Duration::from_secs(1)),
+            content_time: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        assert_eq!(stats.tokens_per_sec(), Some(100.0));
+        stats.completion_tokens = None;
+        assert_eq!(stats.tokens_per_sec(), None);
+    }
 }
@@ -0,0 +1,67 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! What stdout can display, worked out once in main and handed to everything
+//! that writes colors, spinners or a pager. ort only runs on Linux, so there
+//! are no legacy Windows consoles to look for.
+
+use core::ffi::c_int;
+
+use crate::syscall;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Term {
+    /// A file or a pipe: plain text, nobody is watching it arrive
+    Pipe,
+    /// A terminal without escape codes, e.g. TERM=dumb in Emacs or a CI log,
+    /// or no TERM at all. Plain text, but a person is reading it.
+    Dumb,
+    /// A terminal that does ANSI escape codes
+    Ansi,
+}
+
+impl Term {
+    /// `fd` is usually stdout, `term_var` the TERM environment variable.
+    pub fn detect(fd: c_int, term_var: Option<&str>) -> Term {
+        if !syscall::isatty(fd) {
+            return Term::Pipe;
+        }
+        Term::from_var(term_var)
+    }
+
+    /// A terminal's capabilities from its TERM
+    fn from_var(term_var: Option<&str>) -> Term {
+        match term_var.map(str::trim) {
+            None | Some("") | Some("dumb") => Term::Dumb,
+            Some(_) => Term::Ansi,
+        }
+    }
+
+    /// A person is watching: worth an update check, not the place for bare output
+    pub fn is_tty(self) -> bool {
+        self != Term::Pipe
+    }
+
+    /// Colors, spinner, cursor movement and the pager
+    pub fn has_ansi(self) -> bool {
+        self == Term::Ansi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Term;
+
+    #[test]
+    fn from_var() {
+        assert_eq!(Term::from_var(Some("xterm-256color")), Term::Ansi);
+        assert_eq!(Term::from_var(Some("dumb")), Term::Dumb);
+        assert_eq!(Term::from_var(Some("")), Term::Dumb);
+        assert_eq!(Term::from_var(None), Term::Dumb);
+        assert!(Term::Dumb.is_tty() && !Term::Dumb.has_ansi());
+        assert!(!Term::Pipe.is_tty() && !Term::Pipe.has_ansi());
+    }
+}
@@ -8,6 +8,9 @@
 use core::ops::Sub;
 use core::time::Duration;
 
+extern crate alloc;
+use alloc::string::String;
+
 use crate::{ErrorKind, OrtResult, ort_error};
 
 #[derive(Copy, Clone, PartialEq, PartialOrd)]
@@ -20,6 +23,10 @@ impl Instant {
     pub fn new(secs: u64, nanos: u64) -> Self {
         Instant { secs, nanos }
     }
+
+    pub fn as_secs(&self) -> u64 {
+        self.secs
+    }
 }
 
 impl Sub for Instant {
@@ -100,3 +107,90 @@ pub fn elapsed_duration(start: Ticks, end: Ticks, calibration: TscCalibration) -
     let subsec_nanos = (nanos % 1_000_000_000u128) as u32;
     Duration::new(secs, subsec_nanos)
 }
+
+/// ISO 8601 UTC, e.g. "2026-10-17T14:23:01Z"
+pub fn utc_timestamp(unix_secs: u64) -> String {
+    // Howard Hinnant's days_from_civil, in reverse
+    let days = (unix_secs / 86_400) as i64;
+    let secs_of_day = unix_secs % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let mut s = String::with_capacity(20);
+    for (n, width, sep) in [
+        (year as u64, 4, '-'),
+        (month as u64, 2, '-'),
+        (day as u64, 2, 'T'),
+        (secs_of_day / 3600, 2, ':'),
+        (secs_of_day % 3600 / 60, 2, ':'),
+        (secs_of_day % 60, 2, 'Z'),
+    ] {
+        let digits = crate::utils::num_to_string(n);
+        for _ in digits.len()..width {
+            s.push('0');
+        }
+        s.push_str(&digits);
+        s.push(sep);
+    }
+    s
+}
+
+/// Seconds since the epoch from an HTTP Date header value,
+/// e.g. "Sat, 17 Oct 2026 16:03:41 GMT"
+pub fn parse_http_date(s: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let mut parts = s.split_ascii_whitespace().skip(1);
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month_name)? as i64 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut hms = parts.next()?.split(':').map(|n| n.parse::<u64>().ok());
+    let secs_of_day = hms.next()?? * 3600 + hms.next()?? * 60 + hms.next()??;
+
+    // Howard Hinnant's days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    if days < 0 {
+        return None;
+    }
+    Some(days as u64 * 86_400 + secs_of_day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_http_date, utc_timestamp};
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(1_792_253_021), "2026-10-17T16:03:41Z");
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(
+            parse_http_date("Sat, 17 Oct 2026 16:03:41 GMT"),
+            Some(1_792_253_021)
+        );
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"),
+            Some(951_782_400)
+        );
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
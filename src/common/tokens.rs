@@ -0,0 +1,202 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Estimate how many tokens some text is, without the real tokenizers.
+//! A rough count, for budgeting prompts.
+
+use crate::common::data::{Content, Message};
+
+/// Tokenizers differ mostly in how much of a word one token covers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Family {
+    OpenAi,
+    Anthropic,
+    Google,
+    Llama,
+    Other,
+}
+
+impl Family {
+    pub const ALL: [Family; 5] = [
+        Family::OpenAi,
+        Family::Anthropic,
+        Family::Google,
+        Family::Llama,
+        Family::Other,
+    ];
+
+    /// From an OpenRouter model id, e.g. "anthropic/claude-sonnet-4"
+    pub fn from_model(model: &str) -> Family {
+        let vendor = model.split_once('/').map(|(v, _)| v).unwrap_or(model);
+        match vendor {
+            "openai" => Family::OpenAi,
+            "anthropic" => Family::Anthropic,
+            "google" => Family::Google,
+            "meta-llama" => Family::Llama,
+            _ => Family::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Family::OpenAi => "openai",
+            Family::Anthropic => "anthropic",
+            Family::Google => "google",
+            Family::Llama => "llama",
+            Family::Other => "other",
+        }
+    }
+
+    // Average letters and digits per token of a long word, times ten
+    fn word_chars_x10(&self) -> usize {
+        match self {
+            Family::OpenAi => 45,
+            Family::Anthropic => 38,
+            Family::Google => 45,
+            Family::Llama => 42,
+            Family::Other => 40,
+        }
+    }
+}
+
+/// Estimated token count of `text` for that tokenizer family.
+pub fn estimate(text: &str, family: Family) -> usize {
+    let mut counter = Counter::new(family);
+    counter.add(text);
+    counter.count()
+}
+
+/// An estimate of text that arrives in pieces, e.g. streamed one token at a
+/// time. A word split across pieces counts once, as in `estimate`.
+#[derive(Debug)]
+pub struct Counter {
+    per_token: usize,
+    tokens: usize,
+    // Length of the current run of ASCII letters and digits
+    word: usize,
+    prev_newline: bool,
+    prev_symbol: Option<char>,
+}
+
+impl Counter {
+    pub fn new(family: Family) -> Self {
+        Counter {
+            per_token: family.word_chars_x10(),
+            tokens: 0,
+            word: 0,
+            prev_newline: false,
+            prev_symbol: None,
+        }
+    }
+
+    pub fn add(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_ascii_alphanumeric() {
+                self.word += 1;
+                self.prev_symbol = None;
+                continue;
+            }
+            if self.word > 0 {
+                self.tokens += word_tokens(self.word, self.per_token);
+                self.word = 0;
+            }
+            if c == '\n' {
+                self.prev_symbol = None;
+                // A run of newlines is usually one token
+                if !self.prev_newline {
+                    self.tokens += 1;
+                }
+                self.prev_newline = true;
+                continue;
+            }
+            self.prev_newline = false;
+            if c == ' ' || c == '\t' {
+                // Joined to the next word
+                self.prev_symbol = None;
+                continue;
+            }
+            // Runs like "```" or "---" are usually one token
+            if self.prev_symbol != Some(c) {
+                self.tokens += 1;
+            }
+            self.prev_symbol = Some(c);
+        }
+    }
+
+    /// Everything so far, the word in progress too
+    pub fn count(&self) -> usize {
+        if self.word > 0 {
+            self.tokens + word_tokens(self.word, self.per_token)
+        } else {
+            self.tokens
+        }
+    }
+}
+
+/// Tokenizers have most common words whole, only long ones are split.
+/// Rounding down keeps a word under about twice the average as one token.
+fn word_tokens(len: usize, per_token: usize) -> usize {
+    (len * 10 / per_token).max(1)
+}
+
+/// Estimated prompt tokens of a conversation. Only the text, images and
+/// files are not counted.
+pub fn estimate_messages(messages: &[Message], family: Family) -> usize {
+    let mut tokens = 0;
+    for m in messages {
+        for text in m.content.iter().filter_map(Content::text) {
+            tokens += estimate(text, family);
+        }
+        for tc in &m.tool_calls {
+            tokens += estimate(&tc.function.arguments, family);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(estimate("", Family::OpenAi), 0);
+        // The same as OpenAI's tokenizer
+        assert_eq!(estimate("Hello world", Family::OpenAi), 2);
+        assert_eq!(estimate("Hello, world!", Family::OpenAi), 4);
+        let text = "The quick brown fox jumps over the lazy dog";
+        assert_eq!(estimate(text, Family::OpenAi), 9);
+        // Each CJK character is about one token
+        assert_eq!(estimate("你好世界", Family::Other), 4);
+        assert_eq!(estimate("```rust", Family::OpenAi), 2);
+        // Claude's tokenizer is a little less efficient
+        let text = "Internationalization considerations";
+        assert!(estimate(text, Family::Anthropic) > estimate(text, Family::OpenAi));
+    }
+
+    #[test]
+    fn counter_streamed() {
+        let text = "The quick brown fox jumps over the lazy dog.\n\n```rust";
+        let mut counter = Counter::new(Family::OpenAi);
+        // As a model streams it, a token or part of a word at a time
+        for piece in [
+            "The", " qu", "ick", " brown", " fox", " jumps", " over", " the",
+        ] {
+            counter.add(piece);
+        }
+        for piece in [" lazy", " dog", ".", "\n", "\n", "``", "`", "rust"] {
+            counter.add(piece);
+        }
+        assert_eq!(counter.count(), estimate(text, Family::OpenAi));
+    }
+
+    #[test]
+    fn test_from_model() {
+        assert_eq!(Family::from_model("openai/gpt-5"), Family::OpenAi);
+        assert_eq!(Family::from_model("meta-llama/llama-4"), Family::Llama);
+        assert_eq!(Family::from_model("z-ai/glm-4.5"), Family::Other);
+    }
+}
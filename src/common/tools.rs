@@ -18,7 +18,7 @@ use crate::{
     common::{
         data::{Tool, ToolParameter},
         file::File,
-        json_parser::{JsonField, autoparser},
+        json_parser::{JsonField, Parser, autoparser},
     },
     syscall::system,
     utils,
@@ -26,6 +26,9 @@ use crate::{
 
 pub const ALL_TOOLS: &[&Tool] = &[&TOOL_READ, &TOOL_BASH, &TOOL_WRITE, &TOOL_EDIT];
 
+/// Without `confirm_tools` in ort.cfg, ask before running these
+pub const DEFAULT_CONFIRM_TOOLS: &[&str] = &["bash", "write", "edit"];
+
 const TOOL_READ: Tool = Tool {
     name: "read",
     description: "Read the contents of a text file.",
@@ -149,6 +152,69 @@ pub fn parse_function(f: &Function) -> OrtResult<Box<dyn ActiveTool>> {
     }
 }
 
+/// Does tool `name` match one of `patterns`? A `*` matches any run of characters.
+pub fn is_match<S: AsRef<str>>(patterns: &[S], name: &str) -> bool {
+    patterns
+        .iter()
+        .any(|p| glob(p.as_ref().as_bytes(), name.as_bytes()))
+}
+
+fn glob(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| glob(rest, &name[i..])),
+        Some((c, rest)) => name.first() == Some(c) && glob(rest, &name[1..]),
+    }
+}
+
+/// Tool call arguments as one `key: value` line each, strings unescaped.
+/// Falls back to the raw JSON if it isn't a flat object.
+pub fn pretty_arguments(arguments: &str) -> String {
+    pretty_object(arguments).unwrap_or_else(|| arguments.to_string() + "\n")
+}
+
+fn pretty_object(json: &str) -> Option<String> {
+    let mut p = Parser::new(json);
+    p.skip_ws();
+    p.expect(b'{').ok()?;
+    p.skip_ws();
+    let mut out = String::with_capacity(json.len() + 32);
+    if p.try_consume(b'}') {
+        return Some(out);
+    }
+    loop {
+        let key = p.parse_string().ok()?;
+        p.skip_ws();
+        p.expect(b':').ok()?;
+        p.skip_ws();
+        let value = if p.peek() == Some(b'"') {
+            p.parse_string().ok()?
+        } else {
+            p.value_slice().ok()?.to_string()
+        };
+        out.push_str("  ");
+        out.push_str(&key);
+        out.push(':');
+        if value.contains('\n') {
+            // Multi-line, e.g. file content, goes indented below the key
+            for line in value.lines() {
+                out.push_str("\n    ");
+                out.push_str(line);
+            }
+        } else {
+            out.push(' ');
+            out.push_str(&value);
+        }
+        out.push('\n');
+        p.skip_ws();
+        if p.try_consume(b'}') {
+            return Some(out);
+        }
+        p.expect(b',').ok()?;
+        p.skip_ws();
+    }
+}
+
 pub trait ActiveTool {
     /// Run this tool.
     /// On success return Ok(success(..)) which generates the JSON for the model.
@@ -387,7 +453,31 @@ fn success(nums: &[(&'static str, usize)], strs: &[(&'static str, &str)]) -> Str
 
 #[cfg(test)]
 mod test {
-    use super::success;
+    use super::{is_match, pretty_arguments, success};
+
+    #[test]
+    pub fn test_is_match() {
+        let patterns = ["bash", "write*", "*delete*"];
+        assert!(is_match(&patterns, "bash"));
+        assert!(is_match(&patterns, "write"));
+        assert!(is_match(&patterns, "write_file"));
+        assert!(is_match(&patterns, "fs_delete_dir"));
+        assert!(!is_match(&patterns, "read"));
+        assert!(!is_match(&patterns, "bash2"));
+        assert!(!is_match::<&str>(&[], "bash"));
+    }
+
+    #[test]
+    pub fn test_pretty_arguments() {
+        let s = pretty_arguments(r#"{"command": "rm -rf target", "timeout": 5}"#);
+        assert_eq!(s, "  command: rm -rf target\n  timeout: 5\n");
+
+        let s = pretty_arguments(r#"{"path":"a.txt","content":"one\ntwo"}"#);
+        assert_eq!(s, "  path: a.txt\n  content:\n    one\n    two\n");
+
+        assert_eq!(pretty_arguments("[1, 2]"), "[1, 2]\n");
+    }
+
     #[test]
     pub fn test_success() {
         let res = success(
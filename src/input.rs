@@ -7,6 +7,22 @@
 pub mod agent;
 pub mod args;
 pub mod cli;
+pub mod credits;
+pub mod doctor;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod history;
+pub mod lineedit;
 pub mod list;
+pub mod panel;
 pub mod prompt;
+pub mod queue;
+pub mod replay;
+pub mod review;
+pub mod serve;
+pub mod stdin;
 pub mod to_json;
+pub mod tokens;
+pub mod update;
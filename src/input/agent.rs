@@ -5,6 +5,7 @@
 //! Copyright (c) 2026 Graham King
 
 extern crate alloc;
+use alloc::boxed::Box;
 use alloc::ffi::CString;
 use alloc::string::String;
 use alloc::string::ToString;
@@ -15,20 +16,26 @@ use core::{ffi::c_void, mem::MaybeUninit};
 
 use crate::Role;
 use crate::common::config::Cfg;
-use crate::common::data::Content;
+use crate::common::data::{Content, Function, Verbosity};
+use crate::common::models;
 use crate::common::stats::Stats;
+use crate::common::term::Term;
 use crate::common::tools::{self};
 use crate::ort_error;
+use crate::output::metrics;
 use crate::{
     ErrorKind, Message, OrtResult, PromptOpts, Response, Write,
     cli::Env,
     common::{data::Tool, error},
-    input::prompt::ActivePrompt,
+    input::prompt::{ActivePrompt, CostLimit},
     output::{OutputWriter, agent::AgentWriter, last_writer::LastWriter},
-    syscall::{self, IN_CLOSE_WRITE, IN_MOVED_TO},
+    syscall::{self, IN_CLOSE_WRITE, IN_MOVED_TO, O_CLOEXEC, O_RDWR},
     utils,
 };
 
+/// Without --max-steps, most model calls for one prompt
+const DEFAULT_MAX_STEPS: u32 = 50;
+
 pub fn run<W: Write + Send>(
     api_key: &str,
     cfg: &Cfg,
@@ -37,9 +44,10 @@ pub fn run<W: Write + Send>(
     // This contains the system prompt
     // It grows to contain the whole conversation
     mut messages: Vec<crate::Message>,
+    term: Term,
     w_core: &mut W,
 ) -> OrtResult<()> {
-    opts.quiet = Some(true);
+    opts.verbosity = Some(opts.verbosity.unwrap_or_default().max(Verbosity::Quiet));
 
     // Watch the file immediately
     let filename = opts.prompt_filename.as_ref().unwrap().to_string();
@@ -80,18 +88,32 @@ pub fn run<W: Write + Send>(
         }
     }
 
-    let mut output_writer = AgentWriter::new(w_core, opts.show_reasoning.unwrap_or(false));
+    let mut output_writer = AgentWriter::new(w_core, opts.show_reasoning.unwrap_or(false), term);
 
     // First prompt is already in `messages`, added in `input/cli.rs::main`.
     let inital_prompt = opts.prompt.take().unwrap(); // Safety: Always have initial prompt
     output_writer.write(Response::Prompt(inital_prompt))?;
 
-    let mut total_stats = Stats::default();
+    let max_steps = opts.max_steps.unwrap_or(DEFAULT_MAX_STEPS);
+    let mut session_cost_in_cents = 0.0;
+    let mut num_prompts = 0;
 
     loop {
-        // Send a prompt, run all the requested tools
+        let mut total_stats = Stats::default();
+        // Send a prompt, run all the requested tools, until the model stops
+        // asking for tools or we run out of steps.
         let mut has_tool_call = true;
+        let mut step = 0;
         while has_tool_call {
+            if step == max_steps {
+                let msg = "Stopped after ".to_string()
+                    + &utils::num_to_string(max_steps)
+                    + " steps (--max-steps). Send another prompt to continue.\n";
+                output_writer.writer.write_str(&msg)?;
+                break;
+            }
+            step += 1;
+            output_writer.step(step, max_steps);
             has_tool_call = run_single(
                 api_key,
                 cfg,
@@ -103,7 +125,13 @@ pub fn run<W: Write + Send>(
                 &mut total_stats,
             )?;
         }
-        output_writer.write(Response::Stats(total_stats.clone()))?;
+        // Stats are for this prompt, plus the session cost once there is more than one
+        num_prompts += 1;
+        session_cost_in_cents += total_stats.cost_in_cents.unwrap_or_default();
+        if num_prompts > 1 {
+            total_stats.session_cost_in_cents = Some(session_cost_in_cents as f32);
+        }
+        output_writer.write(Response::Stats(Box::new(total_stats)))?;
 
         // Wait for the next user prompt
         let Some(prompt) = next_prompt(ifd, &filename)? else {
@@ -151,7 +179,17 @@ fn run_single<W: Write + Send>(
     output_writer: &mut AgentWriter<W>,
     total_stats: &mut Stats,
 ) -> OrtResult<bool> {
-    let mut last_writer = LastWriter::new(opts.clone(), messages.clone(), tools.to_vec(), env)?;
+    let mut last_writer = LastWriter::new(
+        opts.clone(),
+        messages.clone(),
+        tools.to_vec(),
+        env,
+        cfg.save_reasoning,
+    )?;
+    let assume_yes = opts.assume_yes;
+    let cost_limit = opts
+        .max_cost
+        .and_then(|_| CostLimit::new(models::load(env).as_deref(), &opts, 0, messages));
     let mut active_prompt = ActivePrompt::new(
         api_key.to_string(),
         cfg,
@@ -161,6 +199,7 @@ fn run_single<W: Write + Send>(
         0,
         Some(env),
     )?;
+    active_prompt.cost_limit = cost_limit;
     active_prompt.start()?;
 
     let mut assistant_message = String::new();
@@ -187,6 +226,17 @@ fn run_single<W: Write + Send>(
 
                             for tool_call in tool_calls {
                                 let active_tool = tools::parse_function(&tool_call.function)?;
+                                if !assume_yes && needs_confirm(cfg, &tool_call.function.name) {
+                                    output_writer.writer.flush()?;
+                                    if !confirm(&tool_call.function) {
+                                        output_writer.writer.write_str("Declined\n")?;
+                                        tool_call_results.push((
+                                            tool_call.id.clone().unwrap(),
+                                            error("The user declined this tool call"),
+                                        ));
+                                        continue;
+                                    }
+                                }
                                 output_writer
                                     .write(Response::ToolDisplay(active_tool.display()))?;
                                 let res = active_tool.run();
@@ -212,6 +262,14 @@ fn run_single<W: Write + Send>(
                     last_writer.write(event)?;
                 }
             }
+            Err(err)
+                if matches!(
+                    err.kind,
+                    ErrorKind::CostLimitExceeded | ErrorKind::ReasoningBudgetExceeded
+                ) =>
+            {
+                return Err(err);
+            }
             Err(err) => {
                 utils::print_string(c"active_prompt.next: ", &err.as_string());
             }
@@ -240,14 +298,60 @@ fn run_single<W: Write + Send>(
     };
 
     let stats = active_prompt.stop();
+    let metrics_stats = cfg.metrics.is_some().then(|| stats.clone());
     *total_stats += stats;
 
     output_writer.stop(true)?;
     last_writer.stop(true)?; // Finalize JSON
+    if let Some(stats) = metrics_stats {
+        metrics::record(cfg, &stats);
+    }
 
     Ok(has_tool_call)
 }
 
+fn needs_confirm(cfg: &Cfg, name: &str) -> bool {
+    match cfg.confirm_tools.as_deref() {
+        Some(patterns) => tools::is_match(patterns, name),
+        None => tools::is_match(tools::DEFAULT_CONFIRM_TOOLS, name),
+    }
+}
+
+/// Show the proposed call and ask y/N on the terminal, because stdin and
+/// stdout may be redirected. Without a terminal the answer is no.
+fn confirm(function: &Function) -> bool {
+    let Ok(fd) = syscall::open(c"/dev/tty".as_ptr(), O_RDWR | O_CLOEXEC, 0) else {
+        utils::print_string(
+            c"No terminal to confirm tool call, declined: ",
+            &function.name,
+        );
+        return false;
+    };
+    let question = "\n".to_string()
+        + &function.name
+        + "\n"
+        + &tools::pretty_arguments(&function.arguments)
+        + "Run it? [y/N] ";
+    syscall::write(fd, question.as_ptr().cast(), question.len());
+
+    // Read the whole line so nothing is left over for the next question
+    let mut answer = None;
+    let mut buf = [0u8; 64];
+    loop {
+        let n = syscall::read(fd, buf.as_mut_ptr().cast(), buf.len());
+        if n <= 0 {
+            break;
+        }
+        let got = &buf[..n as usize];
+        answer.get_or_insert(got[0]);
+        if got.contains(&b'\n') {
+            break;
+        }
+    }
+    syscall::close(fd);
+    matches!(answer, Some(b'y' | b'Y'))
+}
+
 fn error(msg: &str) -> String {
     r#"{"success": false, "error": ""#.to_string() + msg + r#""}"#
 }
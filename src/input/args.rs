@@ -18,31 +18,146 @@ use crate::Priority;
 use crate::PromptOpts;
 use crate::ReasoningEffort;
 use crate::cli::Env;
+use crate::common::buf_read;
+use crate::common::config;
+use crate::common::data::{Extract, Flush, ModelSource, ModelSuffix, StdinPos, Verbosity};
 use crate::common::utils;
+use crate::input::to_json::{BODY_FIELDS, json_scalar};
 use crate::{ErrorKind, ort_error};
 use crate::{OrtError, syscall};
 
 const MAX_CONCURRENT_MODELS: usize = 10;
 
+/// Most models a --models-file may list, they all run at once too
+const MAX_MODELS_FILE: usize = 50;
+
 /// Prefixing the system prompt or user prompt with this byte means it's a filename, read the
 /// contents.
 const FILE_INDICATOR: u8 = b'@';
 
+/// Default listen port for `ort serve-openai`
+const DEFAULT_SERVE_PORT: u16 = 8080;
+
 pub struct ListOpts {
     pub config_file: Option<String>,
     pub is_json: bool,
 }
 
+pub struct ServeOpts {
+    pub config_file: Option<String>,
+    pub port: u16,
+}
+
+pub struct TokensOpts {
+    // A filename, or None / "-" for stdin
+    pub source: Option<String>,
+    // Only count for this model's tokenizer family
+    pub model: Option<String>,
+}
+
+pub struct DoctorOpts {
+    pub config_file: Option<String>,
+}
+
+pub enum ConfigAction {
+    // The `[name]` sections of the config
+    Profiles,
+}
+
+pub struct ConfigOpts {
+    pub config_file: Option<String>,
+    pub action: ConfigAction,
+}
+
+pub enum ReviewSource {
+    // Uncommitted changes, `git diff HEAD`
+    WorkingTree,
+    // `git diff --staged`
+    Staged,
+    // A git range, e.g. `main..HEAD`
+    Range(String),
+    // A patch file, e.g. from a PR
+    PatchFile(String),
+}
+
+/// Where `-c` gets the conversation, instead of the last one
+pub enum Transcript {
+    // A file, `--from chat.json`
+    File(String),
+    // Piped in with `--from -`, already read
+    Piped(String),
+}
+
+pub enum HistoryAction {
+    // A file, or "-" for stdin
+    Import(String),
+    // To this file, or stdout
+    Export(Option<String>),
+    // Summarize saved conversations. With `--prune --older-than`, first
+    // delete those not written to for this many seconds.
+    Stats { prune_older_than: Option<u64> },
+    // The saved conversations by title
+    List,
+}
+
+pub struct HistoryOpts {
+    pub config_file: Option<String>,
+    pub action: HistoryAction,
+}
+
+pub struct ReplayOpts {
+    pub config_file: Option<String>,
+    // A --dump-wire response.http, or a bare SSE stream
+    pub capture: String,
+    // --fast, no pauses between reads
+    pub is_fast: bool,
+    pub show_reasoning: bool,
+    pub verbosity: Verbosity,
+    pub is_raw: bool,
+    pub flush: Flush,
+}
+
+pub struct FlushQueueOpts {
+    pub config_file: Option<String>,
+}
+
+pub struct ReviewOpts {
+    pub config_file: Option<String>,
+    pub source: ReviewSource,
+    pub model: Option<String>,
+    // --allow-secrets, send the diff even if it looks like it has keys in
+    pub allow_secrets: bool,
+}
+
+pub struct PanelOpts {
+    pub config_file: Option<String>,
+    // --personas, names of `persona.<name>` lines in the config
+    pub personas: Vec<String>,
+    pub model: Option<String>,
+    // --judge, one more request to bring the answers together
+    pub judge: bool,
+    pub question: String,
+}
+
 pub enum Cmd {
     List(ListOpts),
+    Tokens(TokensOpts),
+    Doctor(DoctorOpts),
+    Config(ConfigOpts),
+    Serve(ServeOpts),
+    Review(ReviewOpts),
+    Panel(PanelOpts),
+    History(HistoryOpts),
+    Replay(ReplayOpts),
+    FlushQueue(FlushQueueOpts),
     Prompt(crate::PromptOpts),
     Agent(crate::PromptOpts),
-    ContinueConversation(crate::PromptOpts),
+    ContinueConversation(crate::PromptOpts, Option<Transcript>),
 }
 
 pub fn parse_prompt_args(
     args: &[String],
-    stdin: Option<String>,
+    mut stdin: Option<String>,
     env: &Env,
 ) -> Result<Cmd, ArgParseError> {
     // Only the prompt is required. Everything else can come from config file
@@ -52,15 +167,56 @@ pub fn parse_prompt_args(
     let mut config_file = None;
     let mut models: Vec<String> = vec![];
     let mut system: Option<String> = None;
+    // --system-file wins over -s wherever they are on the line
+    let mut system_file: Option<String> = None;
+    let mut append_system: Option<String> = None;
     let mut priority: Option<Priority> = None;
-    let mut quiet: Option<bool> = None;
+    let mut verbosity: Option<Verbosity> = None;
     let mut effort: Option<ReasoningEffort> = None;
     let mut show_reasoning: Option<bool> = None;
     let mut provider: Option<String> = None;
     let mut continue_conversation = false;
+    // --from or --from-openai
+    let mut from_file: Option<String> = None;
     let mut merge_config = true;
     let mut files: Vec<String> = vec![];
     let mut include_web_tools: Option<bool> = None;
+    let mut output_image: Option<String> = None;
+    let mut first_token_deadline_ms: Option<u32> = None;
+    let mut deadline_ms: Option<u32> = None;
+    let mut n: Option<u32> = None;
+    let mut raw = false;
+    let mut max_steps: Option<u32> = None;
+    let mut labels: Vec<(String, String)> = vec![];
+    let mut params: Vec<(String, String)> = vec![];
+    let mut extract_code = false;
+    let mut extract_all = false;
+    let mut is_json = false;
+    let mut has_models_file = false;
+    let mut out_dir: Option<String> = None;
+    let mut max_cost: Option<f64> = None;
+    let mut assume_yes = false;
+    let mut flush = Flush::default();
+    let mut verbose = false;
+    let mut allow_secrets = false;
+    let mut clean_input = false;
+    let mut oneline = false;
+    let mut tee: Option<String> = None;
+    let mut tee_all = false;
+    let mut queue_on_fail = false;
+    let mut save_code: Option<String> = None;
+    let mut reasoning_tokens: Option<u32> = None;
+    let mut strict_reasoning = false;
+    let mut pin_provider: Option<String> = None;
+    let mut retries: Option<u32> = None;
+    let mut model_suffix: Option<ModelSuffix> = None;
+    let mut ctx: Vec<(String, String)> = vec![];
+    let mut stdin_pos = StdinPos::default();
+    let mut template: Option<String> = None;
+    // --prompt-fd or --prompt-file, so the prompt isn't in argv
+    let mut prompt_flag: Option<String> = None;
+    // Only an error once we know there was no --clean-input
+    let mut is_prompt_fd_lossy = false;
 
     // If the prompt is '@<filename>' we save filename in here
     // Agent mode needs it
@@ -95,9 +251,26 @@ pub fn parse_prompt_args(
                     return Err(ArgParseError::new_str("Missing value for -m"));
                 }
                 models.push(args[i].clone());
-                if models.len() > MAX_CONCURRENT_MODELS {
-                    return Err(ArgParseError::new_str("Too many '-m' flags, max 10"));
+                i += 1;
+            }
+            "--models-file" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --models-file"));
+                }
+                let contents = utils::filename_read_to_string(&args[i]).map_err(|err| {
+                    ArgParseError::new("--models-file ".to_string() + &args[i] + ": " + err)
+                })?;
+                models.extend(parse_models_file(&contents));
+                has_models_file = true;
+                i += 1;
+            }
+            "--out-dir" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --out-dir"));
                 }
+                out_dir = Some(args[i].trim_end_matches('/').to_string());
                 i += 1;
             }
             "-s" => {
@@ -108,32 +281,143 @@ pub fn parse_prompt_args(
                 system = Some(args[i].clone());
                 i += 1;
             }
-            "-p" => {
+            "--system-file" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --system-file"));
+                }
+                let contents = utils::filename_read_utf8(&args[i], config::MAX_SYSTEM_FILE_BYTES)
+                    .map_err(|err| {
+                    ArgParseError::new(
+                        "--system-file ".to_string() + &config::system_file_error(&args[i], err),
+                    )
+                })?;
+                system_file = Some(contents);
+                i += 1;
+            }
+            "--append-system" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --append-system"));
+                }
+                append_system = Some(substitute_system_vars(args[i].clone(), env)?);
+                i += 1;
+            }
+            "-p" | "--priority" => {
                 i += 1;
                 if i >= args.len() {
-                    return Err(ArgParseError::new_str("Missing value for -p"));
+                    return Err(ArgParseError::new("Missing value for ".to_string() + arg));
                 }
                 let val = args[i].clone();
                 match val.as_str() {
                     // Safety: The 'parse' can handle exactly the three strings we match on
                     "price" | "throughput" | "latency" => priority = val.parse().ok(),
                     _ => {
-                        return Err(ArgParseError::new_str(
-                            "Invalid -p value: must be one of price|throughput|latency",
+                        return Err(ArgParseError::new(
+                            "Invalid ".to_string()
+                                + arg
+                                + " value: must be one of price|throughput|latency",
                         ));
                     }
                 }
                 i += 1;
             }
+            // Whichever order, --silent wins over -q
             "-q" => {
-                quiet = Some(true);
+                verbosity = verbosity.max(Some(Verbosity::Quiet));
+                i += 1;
+            }
+            "--silent" => {
+                verbosity = Some(Verbosity::Silent);
+                i += 1;
+            }
+            "--raw" => {
+                raw = true;
+                i += 1;
+            }
+            "--flush" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --flush"));
+                }
+                flush = Flush::from_str(&args[i]).map_err(|_| {
+                    ArgParseError::new_str("Invalid --flush value: must be chunk, line or bytes")
+                })?;
+                i += 1;
+            }
+            "--line-buffered" => {
+                flush = Flush::Line;
+                i += 1;
+            }
+            "--verbose" => {
+                verbose = true;
+                i += 1;
+            }
+            "--allow-secrets" => {
+                allow_secrets = true;
+                i += 1;
+            }
+            "--clean-input" => {
+                clean_input = true;
+                i += 1;
+            }
+            "--oneline" => {
+                oneline = true;
+                i += 1;
+            }
+            "--tee" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --tee"));
+                }
+                tee = Some(args[i].clone());
+                i += 1;
+            }
+            "--tee-all" => {
+                tee_all = true;
+                i += 1;
+            }
+            "--queue-on-fail" => {
+                queue_on_fail = true;
+                i += 1;
+            }
+            "--save-code" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --save-code"));
+                }
+                save_code = Some(args[i].clone());
+                i += 1;
+            }
+            "--free" | "--nitro" | "--floor" => {
+                let suffix = match arg.as_str() {
+                    "--free" => ModelSuffix::Free,
+                    "--nitro" => ModelSuffix::Nitro,
+                    _ => ModelSuffix::Floor,
+                };
+                if model_suffix.is_some_and(|s| s != suffix) {
+                    return Err(ArgParseError::new_str(
+                        "Only one of --free, --nitro and --floor",
+                    ));
+                }
+                model_suffix = Some(suffix);
                 i += 1;
             }
             "-r" => {
                 i += 1;
-                let r_cfg = ReasoningEffort::from_str(args[i].as_str())
-                    .map_err(|_| ArgParseError::new_str("Invalid -r value"))?;
-                effort = Some(r_cfg);
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for -r"));
+                }
+                // A number is a budget, the same as --reasoning-tokens
+                if let Ok(budget) = args[i].parse::<u32>()
+                    && budget > 0
+                {
+                    reasoning_tokens = Some(budget);
+                } else {
+                    let r_cfg = ReasoningEffort::from_str(args[i].as_str())
+                        .map_err(|_| ArgParseError::new_str("Invalid -r value"))?;
+                    effort = Some(r_cfg);
+                }
                 i += 1;
             }
             "-rr" => {
@@ -148,10 +432,37 @@ pub fn parse_prompt_args(
                 provider = Some(utils::slug(args[i].as_ref()));
                 i += 1;
             }
+            "--pin-provider" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --pin-provider"));
+                }
+                pin_provider = Some(utils::slug(args[i].as_ref()));
+                i += 1;
+            }
+            "--retries" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --retries"));
+                }
+                let n = args[i]
+                    .parse::<u32>()
+                    .map_err(|_| ArgParseError::new_str("Invalid --retries, a number e.g. 3"))?;
+                retries = Some(n);
+                i += 1;
+            }
             "-c" => {
                 continue_conversation = true;
                 i += 1;
             }
+            "--from" | "--from-openai" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new("Missing value for ".to_string() + arg));
+                }
+                from_file = Some(args[i].clone());
+                i += 1;
+            }
             "-nc" => {
                 merge_config = false;
                 i += 1;
@@ -168,6 +479,233 @@ pub fn parse_prompt_args(
                 files.push(args[i].clone());
                 i += 1;
             }
+            "--output-image" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --output-image"));
+                }
+                output_image = Some(args[i].clone());
+                i += 1;
+            }
+            // --n is the API's name for it
+            "--samples" | "--n" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --samples"));
+                }
+                let num = args[i]
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|num| *num >= 1)
+                    .ok_or_else(|| ArgParseError::new_str("Invalid --samples value"))?;
+                n = Some(num);
+                i += 1;
+            }
+            "--max-steps" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --max-steps"));
+                }
+                let num = args[i]
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|num| *num >= 1)
+                    .ok_or_else(|| ArgParseError::new_str("Invalid --max-steps value"))?;
+                max_steps = Some(num);
+                i += 1;
+            }
+            "--extract" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --extract"));
+                }
+                if args[i] != "code" {
+                    return Err(ArgParseError::new_str(
+                        "Invalid --extract value: must be code",
+                    ));
+                }
+                extract_code = true;
+                i += 1;
+            }
+            "--all" => {
+                extract_all = true;
+                i += 1;
+            }
+            "--json" => {
+                is_json = true;
+                i += 1;
+            }
+            "--yes" => {
+                assume_yes = true;
+                i += 1;
+            }
+            "--label" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --label"));
+                }
+                let (k, v) = args[i]
+                    .split_once('=')
+                    .filter(|(k, _)| !k.is_empty())
+                    .ok_or_else(|| ArgParseError::new_str("Invalid --label, expected key=value"))?;
+                labels.push((k.to_string(), v.to_string()));
+                i += 1;
+            }
+            "--param" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --param"));
+                }
+                let (k, v) = args[i]
+                    .split_once('=')
+                    .filter(|(k, _)| !k.is_empty())
+                    .ok_or_else(|| ArgParseError::new_str("Invalid --param, expected key=value"))?;
+                if BODY_FIELDS.contains(&k) {
+                    return Err(ArgParseError::new(
+                        "--param ".to_string() + k + ": ort sets that field itself",
+                    ));
+                }
+                let v = json_scalar(v).ok_or_else(|| {
+                    ArgParseError::new(
+                        "--param ".to_string()
+                            + k
+                            + ": value must be a number, true, false, null or a \"quoted string\"",
+                    )
+                })?;
+                params.retain(|(pk, _)| pk != k);
+                params.push((k.to_string(), v));
+                i += 1;
+            }
+            "--max-cost" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --max-cost"));
+                }
+                let cost = args[i]
+                    .trim_start_matches('$')
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|c| c.is_finite() && *c > 0.0)
+                    .ok_or_else(|| {
+                        ArgParseError::new_str("Invalid --max-cost, dollars e.g. 0.05")
+                    })?;
+                max_cost = Some(cost);
+                i += 1;
+            }
+            "--reasoning-tokens" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str(
+                        "Missing value for --reasoning-tokens",
+                    ));
+                }
+                let budget = args[i]
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| {
+                        ArgParseError::new_str("Invalid --reasoning-tokens, a number e.g. 2000")
+                    })?;
+                reasoning_tokens = Some(budget);
+                i += 1;
+            }
+            "--strict-reasoning" => {
+                strict_reasoning = true;
+                i += 1;
+            }
+            "--prompt-fd" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --prompt-fd"));
+                }
+                let fd = utils::parse_u32(args[i].as_bytes())
+                    .map_err(|_| ArgParseError::new_str("Invalid --prompt-fd, e.g. 3"))?
+                    as i32;
+                let mut buffer = String::with_capacity(8 * 1024);
+                is_prompt_fd_lossy = !buf_read::fd_read_to_string(fd, &mut buffer, true);
+                // Leave stdin, stdout and stderr alone
+                if fd > 2 {
+                    syscall::close(fd);
+                }
+                if buffer.trim().is_empty() {
+                    return Err(ArgParseError::new(
+                        "Nothing to read on --prompt-fd ".to_string() + &args[i],
+                    ));
+                }
+                prompt_flag = Some(buffer);
+                i += 1;
+            }
+            "--prompt-file" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --prompt-file"));
+                }
+                let contents = utils::filename_read_to_string(&args[i]).map_err(|err| {
+                    ArgParseError::new("--prompt-file ".to_string() + &args[i] + ": " + err)
+                })?;
+                prompt_filename = Some(args[i].clone());
+                prompt_flag = Some(contents);
+                i += 1;
+            }
+            "--ctx" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --ctx"));
+                }
+                let contents = utils::filename_read_to_string(&args[i]).map_err(|err| {
+                    ArgParseError::new("--ctx ".to_string() + &args[i] + ": " + err)
+                })?;
+                ctx.push((args[i].clone(), contents));
+                i += 1;
+            }
+            "--stdin-pos" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --stdin-pos"));
+                }
+                stdin_pos = StdinPos::from_str(&args[i]).map_err(|_| {
+                    ArgParseError::new_str("Invalid --stdin-pos value: must be before or after")
+                })?;
+                i += 1;
+            }
+            "--template" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --template"));
+                }
+                let contents = utils::filename_read_to_string(&args[i]).map_err(|err| {
+                    ArgParseError::new("--template ".to_string() + &args[i] + ": " + err)
+                })?;
+                template = Some(contents);
+                i += 1;
+            }
+            "--first-token-deadline" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str(
+                        "Missing value for --first-token-deadline",
+                    ));
+                }
+                first_token_deadline_ms = Some(parse_duration_ms(&args[i]).ok_or_else(|| {
+                    ArgParseError::new_str("Invalid --first-token-deadline, e.g. 5s or 500ms")
+                })?);
+                i += 1;
+            }
+            "--deadline" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --deadline"));
+                }
+                deadline_ms = Some(parse_duration_ms(&args[i]).ok_or_else(|| {
+                    ArgParseError::new_str("Invalid --deadline, e.g. 60s or 500ms")
+                })?);
+                i += 1;
+            }
+            "--" => {
+                // Everything after is the prompt, even what looks like a flag
+                prompt_parts.extend_from_slice(&args[i + 1..]);
+                break;
+            }
             s if s.starts_with('-') => {
                 return Err(ArgParseError::new("Unknown flag: ".to_string() + s));
             }
@@ -179,49 +717,73 @@ pub fn parse_prompt_args(
         }
     }
 
+    if has_models_file {
+        if models.is_empty() {
+            return Err(ArgParseError::new_str("No models in --models-file"));
+        }
+        if models.len() > MAX_MODELS_FILE {
+            return Err(ArgParseError::new_str("Too many models, max 50"));
+        }
+    } else if models.len() > MAX_CONCURRENT_MODELS {
+        return Err(ArgParseError::new_str("Too many '-m' flags, max 10"));
+    }
+
+    if is_prompt_fd_lossy && !clean_input {
+        return Err(ArgParseError::new_str(
+            "--prompt-fd isn't UTF-8, use --clean-input to keep its text",
+        ));
+    }
+
     let mut prompt = "".to_string();
     if !prompt_parts.is_empty() {
+        if prompt_flag.is_some() {
+            return Err(ArgParseError::new_str(
+                "Give the prompt as an argument or with --prompt-fd / --prompt-file, not both",
+            ));
+        }
         prompt = prompt_parts.join(" ");
     };
-    // If a prompt was piped in use it
-    if let Some(stdin) = stdin {
-        prompt.push_str("\n\n");
-        prompt.push_str(&stdin);
-    }
-
-    if prompt.is_empty() {
-        return Err(ArgParseError::new_str("Missing prompt."));
+    // With `--from -` stdin is the conversation, so the prompt must be an argument
+    let transcript = match from_file {
+        Some(f) if f == "-" => Some(Transcript::Piped(stdin.take().ok_or_else(|| {
+            ArgParseError::new_str("--from - needs the conversation piped in")
+        })?)),
+        Some(f) => Some(Transcript::File(f)),
+        None => None,
+    };
+    // A piped in prompt is joined on later, by PromptOpts::assemble_prompt
+    if prompt.is_empty() && stdin.is_none() && prompt_flag.is_none() && template.is_none() {
+        return Err(ArgParseError::missing_prompt());
     };
 
     // Read system and user prompt from a file
-    if prompt.bytes().next() == Some(FILE_INDICATOR) {
+    if let Some(p) = prompt_flag {
+        // Used as is, even if it starts with '@'
+        prompt = p;
+    } else if prompt.bytes().next() == Some(FILE_INDICATOR) {
         let filename = &prompt[1..];
         prompt_filename = Some(filename.to_string());
         prompt = utils::filename_read_to_string(filename).map_err(ArgParseError::new_str)?;
     }
-    if let Some(system_prompt) = system.as_ref()
+    // A terminal-less run with nothing piped reads an empty stdin
+    let has_stdin = stdin.as_deref().is_some_and(|s| !s.is_empty());
+    check_template(template.as_deref(), &prompt, has_stdin, !ctx.is_empty())?;
+    if let Some(sp) = system_file {
+        system = Some(substitute_system_vars(sp, env)?);
+    } else if let Some(system_prompt) = system.as_ref()
         && system_prompt.bytes().next() == Some(FILE_INDICATOR)
     {
-        let mut sp = utils::filename_read_to_string(&system_prompt[1..])
+        let sp = utils::filename_read_to_string(&system_prompt[1..])
             .map_err(|err| ArgParseError::new("System prompt file: ".to_string() + err))?;
-        // System prompt variable substitution. PWD is current working directory.
-        if let Some(pwd) = env.PWD {
-            sp = sp.replace("$PWD", pwd);
-        }
-        // This one is more expensive so only do it if necessary
-        if sp.contains("$DATE") {
-            // Shelling to `date` is much simpler and shorter than converting kernel clock
-            match syscall::system("date") {
-                Ok(current_date) => sp = sp.replace("$DATE", &current_date.stdout),
-                Err(err) => {
-                    return Err(ArgParseError::new(
-                        "Failed running `date` to substitute $DATE in system prompt: ".to_string()
-                            + &err.as_string(),
-                    ));
-                }
-            };
+        system = Some(substitute_system_vars(sp, env)?);
+    }
+    if pin_provider.is_some() {
+        if provider.is_some() {
+            return Err(ArgParseError::new_str(
+                "--pin-provider and -pr don't go together",
+            ));
         }
-        system = Some(sp);
+        provider = pin_provider.clone();
     }
 
     let prompt_opts = PromptOpts {
@@ -230,17 +792,163 @@ pub fn parse_prompt_args(
         models,
         provider,
         system,
+        append_system,
         priority,
         effort,
         show_reasoning,
-        quiet,
+        verbosity,
         merge_config,
         files,
         prompt_filename,
         include_web_tools,
+        output_image,
+        first_token_deadline_ms,
+        deadline_ms,
+        ignore_providers: vec![],
+        stdin,
+        n,
+        raw,
+        max_steps,
+        labels,
+        params,
+        user: None,
+        extract: match (extract_code, extract_all) {
+            (true, false) => Some(Extract::Code),
+            (true, true) => Some(Extract::AllCode),
+            (false, true) => {
+                return Err(ArgParseError::new_str(
+                    "--all only applies to --extract code",
+                ));
+            }
+            (false, false) => None,
+        },
+        is_json,
+        out_dir,
+        max_cost,
+        assume_yes,
+        flush,
+        verbose,
+        ctx,
+        stdin_pos,
+        template,
+        allow_secrets,
+        clean_input,
+        oneline,
+        model_suffix,
+        tee,
+        tee_all,
+        model_source: ModelSource::Cli,
+        queue_on_fail,
+        save_code,
+        reasoning_tokens,
+        strict_reasoning,
+        pin_provider: pin_provider.is_some(),
+        retries,
     };
+    if let (Some(suffix), Some(priority)) = (model_suffix, prompt_opts.priority)
+        && suffix.priority().is_some_and(|p| p != priority)
+    {
+        return Err(ArgParseError::new(
+            "--".to_string() + &suffix.as_str()[1..] + " conflicts with -p " + priority.as_str(),
+        ));
+    }
+    if prompt_opts.max_steps.is_some() && !is_agent {
+        return Err(ArgParseError::new_str(
+            "--max-steps only applies to ort agent",
+        ));
+    }
+    if prompt_opts.assume_yes && !is_agent {
+        return Err(ArgParseError::new_str("--yes only applies to ort agent"));
+    }
+    if prompt_opts.extract.is_some() && is_agent {
+        return Err(ArgParseError::new_str(
+            "--extract does not apply to ort agent",
+        ));
+    }
+    if prompt_opts.oneline && is_agent {
+        return Err(ArgParseError::new_str(
+            "--oneline does not apply to ort agent",
+        ));
+    }
+    if prompt_opts.oneline && prompt_opts.extract.is_some() {
+        return Err(ArgParseError::new_str(
+            "--oneline and --extract don't go together",
+        ));
+    }
+    if prompt_opts.tee_all && prompt_opts.tee.is_none() {
+        return Err(ArgParseError::new_str("--tee-all only applies to --tee"));
+    }
+    if prompt_opts.tee.is_some() && is_agent {
+        return Err(ArgParseError::new_str("--tee does not apply to ort agent"));
+    }
+    if prompt_opts.tee.is_some()
+        && (prompt_opts.models.len() > 1 || prompt_opts.is_json || prompt_opts.out_dir.is_some())
+    {
+        return Err(ArgParseError::new_str(
+            "--tee takes one model, use --out-dir to save several answers",
+        ));
+    }
+    if prompt_opts.queue_on_fail && is_agent {
+        return Err(ArgParseError::new_str(
+            "--queue-on-fail does not apply to ort agent",
+        ));
+    }
+    if prompt_opts.queue_on_fail
+        && (prompt_opts.models.len() > 1 || prompt_opts.is_json || prompt_opts.out_dir.is_some())
+    {
+        return Err(ArgParseError::new_str("--queue-on-fail takes one model"));
+    }
+    if prompt_opts.save_code.is_some() && is_agent {
+        return Err(ArgParseError::new_str(
+            "--save-code does not apply to ort agent",
+        ));
+    }
+    if prompt_opts.save_code.is_some()
+        && (prompt_opts.models.len() > 1 || prompt_opts.is_json || prompt_opts.out_dir.is_some())
+    {
+        return Err(ArgParseError::new_str("--save-code takes one model"));
+    }
+    if prompt_opts.retries.is_some() && !prompt_opts.pin_provider {
+        return Err(ArgParseError::new_str(
+            "--retries only applies to --pin-provider",
+        ));
+    }
+    if prompt_opts.pin_provider && is_agent {
+        return Err(ArgParseError::new_str(
+            "--pin-provider does not apply to ort agent",
+        ));
+    }
+    if prompt_opts.pin_provider
+        && (prompt_opts.models.len() > 1 || prompt_opts.is_json || prompt_opts.out_dir.is_some())
+    {
+        return Err(ArgParseError::new_str("--pin-provider takes one model"));
+    }
+    if prompt_opts.strict_reasoning && prompt_opts.reasoning_tokens.is_none() {
+        return Err(ArgParseError::new_str(
+            "--strict-reasoning only applies to --reasoning-tokens",
+        ));
+    }
+    if prompt_opts.reasoning_tokens.is_some() && prompt_opts.effort == Some(ReasoningEffort::None) {
+        return Err(ArgParseError::new_str(
+            "--reasoning-tokens needs reasoning on, not -r off",
+        ));
+    }
+    if transcript.is_some() && is_agent {
+        return Err(ArgParseError::new_str("--from does not apply to ort agent"));
+    }
+    continue_conversation |= transcript.is_some();
+    if prompt_opts.is_json && (is_agent || continue_conversation) {
+        return Err(ArgParseError::new_str(
+            "--json does not apply to ort agent, -c or --from",
+        ));
+    }
+    if prompt_opts.out_dir.is_some() && (is_agent || continue_conversation) {
+        return Err(ArgParseError::new_str(
+            "--out-dir does not apply to ort agent, -c or --from",
+        ));
+    }
     if continue_conversation {
-        Ok(Cmd::ContinueConversation(prompt_opts))
+        Ok(Cmd::ContinueConversation(prompt_opts, transcript))
     } else if is_agent {
         Ok(Cmd::Agent(prompt_opts))
     } else {
@@ -248,9 +956,105 @@ pub fn parse_prompt_args(
     }
 }
 
-pub fn parse_list_args(args: &[String]) -> Result<Cmd, ArgParseError> {
-    let mut config_file = None;
-    let mut is_json = false;
+/// One model per line. Blank lines and anything after a `#` are skipped.
+fn parse_models_file(contents: &str) -> impl Iterator<Item = String> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+}
+
+/// A system prompt from a file can use `$PWD`, the current directory, and
+/// `$DATE`, the output of `date`
+fn substitute_system_vars(mut sp: String, env: &Env) -> Result<String, ArgParseError> {
+    if let Some(pwd) = env.PWD {
+        sp = sp.replace("$PWD", pwd);
+    }
+    // This one is more expensive so only do it if necessary
+    if sp.contains("$DATE") {
+        // Shelling to `date` is much simpler and shorter than converting kernel clock
+        match syscall::system("date") {
+            Ok(current_date) => sp = sp.replace("$DATE", &current_date.stdout),
+            Err(err) => {
+                return Err(ArgParseError::new(
+                    "Failed running `date` to substitute $DATE in system prompt: ".to_string()
+                        + &err.as_string(),
+                ));
+            }
+        };
+    }
+    Ok(sp)
+}
+
+/// Every source a template names must be there, and a prompt argument given
+/// alongside `--template` must have its `{arg}`
+fn check_template(
+    template: Option<&str>,
+    prompt: &str,
+    has_stdin: bool,
+    has_ctx: bool,
+) -> Result<(), ArgParseError> {
+    let Some(text) = template else {
+        return Ok(());
+    };
+    if text.contains("{stdin}") && !has_stdin {
+        return Err(ArgParseError::new_str(
+            "The template uses {stdin} but nothing was piped in",
+        ));
+    }
+    if text.contains("{ctx}") && !has_ctx {
+        return Err(ArgParseError::new_str(
+            "The template uses {ctx} but there is no --ctx",
+        ));
+    }
+    if text.contains("{arg}") == prompt.is_empty() {
+        return Err(ArgParseError::new_str(
+            "--template needs a prompt argument exactly when it has {arg}",
+        ));
+    }
+    Ok(())
+}
+
+/// "5s", "500ms" or a plain number of seconds
+pub(in crate::input) fn parse_duration_ms(s: &str) -> Option<u32> {
+    let (num, mult) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 1)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1000)
+    } else {
+        (s, 1000)
+    };
+    if !num.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    utils::parse_u32(num.as_bytes())
+        .ok()
+        .and_then(|n| n.checked_mul(mult))
+        .filter(|n| *n > 0)
+}
+
+/// "30d", "12h", "90m" or "45s"
+fn parse_age_secs(s: &str) -> Option<u64> {
+    let unit = match s.as_bytes().last()? {
+        b'd' => 86_400,
+        b'h' => 3600,
+        b'm' => 60,
+        b's' => 1,
+        _ => return None,
+    };
+    let num = &s[..s.len() - 1];
+    if num.is_empty() || !num.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    utils::parse_u32(num.as_bytes())
+        .ok()
+        .map(|n| u64::from(n) * unit)
+}
+
+pub fn parse_list_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+    let mut is_json = false;
 
     let mut i = 2;
     while i < args.len() {
@@ -281,10 +1085,488 @@ pub fn parse_list_args(args: &[String]) -> Result<Cmd, ArgParseError> {
     }))
 }
 
+pub fn parse_tokens_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut source = None;
+    let mut model = None;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "-m" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for -m"));
+                }
+                model = Some(args[i].clone());
+            }
+            x if source.is_none() => {
+                source = Some(x.to_string());
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid tokens argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    Ok(Cmd::Tokens(TokensOpts { source, model }))
+}
+
+pub fn parse_doctor_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid doctor argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    Ok(Cmd::Doctor(DoctorOpts { config_file }))
+}
+
+pub fn parse_config_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+    let mut action = None;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            "profiles" if action.is_none() => {
+                action = Some(ConfigAction::Profiles);
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid config argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+    let Some(action) = action else {
+        return Err(ArgParseError::new_str("Usage: ort config profiles"));
+    };
+
+    Ok(Cmd::Config(ConfigOpts {
+        config_file,
+        action,
+    }))
+}
+
+pub fn parse_review_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+    let mut model = None;
+    let mut source = None;
+    let mut allow_secrets = false;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            "-m" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for -m"));
+                }
+                model = Some(args[i].clone());
+            }
+            "--allow-secrets" => {
+                allow_secrets = true;
+            }
+            "--staged" if source.is_none() => {
+                source = Some(ReviewSource::Staged);
+            }
+            x if source.is_none() && !x.starts_with('-') => {
+                source = Some(if x.contains("..") {
+                    ReviewSource::Range(x.to_string())
+                } else {
+                    ReviewSource::PatchFile(x.to_string())
+                });
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid review argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    Ok(Cmd::Review(ReviewOpts {
+        config_file,
+        source: source.unwrap_or(ReviewSource::WorkingTree),
+        model,
+        allow_secrets,
+    }))
+}
+
+pub fn parse_panel_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+    let mut personas: Vec<String> = vec![];
+    let mut model = None;
+    let mut judge = false;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            "--personas" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --personas"));
+                }
+                personas.extend(
+                    args[i]
+                        .split(',')
+                        .map(|p| p.trim().to_string())
+                        .filter(|p| !p.is_empty()),
+                );
+            }
+            "-m" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for -m"));
+                }
+                model = Some(args[i].clone());
+            }
+            "--judge" => judge = true,
+            x if x.starts_with('-') => {
+                return Err(ArgParseError::new(
+                    "Invalid panel argument: ".to_string() + x,
+                ));
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+
+    if personas.is_empty() {
+        return Err(ArgParseError::new_str(
+            "ort panel needs --personas, e.g. --personas skeptic,optimist",
+        ));
+    }
+    // As with a prompt, the rest is the question
+    let question = args[i..].join(" ");
+    if question.is_empty() {
+        return Err(ArgParseError::missing_prompt());
+    }
+    Ok(Cmd::Panel(PanelOpts {
+        config_file,
+        personas,
+        model,
+        judge,
+        question,
+    }))
+}
+
+pub fn parse_replay_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+    let mut capture = None;
+    let mut is_fast = false;
+    let mut show_reasoning = false;
+    let mut verbosity = Verbosity::Normal;
+    let mut is_raw = false;
+    let mut flush = Flush::default();
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            "--fast" => is_fast = true,
+            "-rr" => show_reasoning = true,
+            "-q" => verbosity = verbosity.max(Verbosity::Quiet),
+            "--silent" => verbosity = Verbosity::Silent,
+            "--raw" => is_raw = true,
+            "--line-buffered" => flush = Flush::Line,
+            "--flush" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --flush"));
+                }
+                flush = Flush::from_str(&args[i]).map_err(|_| {
+                    ArgParseError::new_str("Invalid --flush value: must be chunk, line or bytes")
+                })?;
+            }
+            x if capture.is_none() && !x.starts_with('-') => {
+                capture = Some(x.to_string());
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid replay argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    let capture = capture.ok_or_else(|| {
+        ArgParseError::new_str("Usage: ort replay <response.http|capture.sse> [--fast] [-rr]")
+    })?;
+    Ok(Cmd::Replay(ReplayOpts {
+        config_file,
+        capture,
+        is_fast,
+        show_reasoning,
+        verbosity,
+        is_raw,
+        flush,
+    }))
+}
+
+pub fn parse_history_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+    let mut action = None;
+    let mut is_prune = false;
+    let mut older_than = None;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            "import" if action.is_none() => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing file for history import"));
+                }
+                action = Some(HistoryAction::Import(args[i].clone()));
+            }
+            "export" if action.is_none() => {
+                let target = args.get(i + 1).filter(|a| !a.starts_with('-')).cloned();
+                if target.is_some() {
+                    i += 1;
+                }
+                action = Some(HistoryAction::Export(target));
+            }
+            "list" if action.is_none() => {
+                action = Some(HistoryAction::List);
+            }
+            "stats" if action.is_none() => {
+                action = Some(HistoryAction::Stats {
+                    prune_older_than: None,
+                });
+            }
+            "--prune" => {
+                is_prune = true;
+            }
+            "--older-than" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --older-than"));
+                }
+                older_than = Some(parse_age_secs(&args[i]).ok_or_else(|| {
+                    ArgParseError::new_str("Invalid --older-than, e.g. 30d, 12h or 90m")
+                })?);
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid history argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    let mut action = action.ok_or_else(|| {
+        ArgParseError::new_str(
+            "Usage: ort history import <file|-> | export [file] | list | stats [--prune --older-than 30d]",
+        )
+    })?;
+    match (&mut action, is_prune, older_than) {
+        (HistoryAction::Stats { prune_older_than }, true, Some(secs)) => {
+            *prune_older_than = Some(secs);
+        }
+        (_, false, None) => {}
+        (HistoryAction::Stats { .. }, _, _) => {
+            return Err(ArgParseError::new_str(
+                "--prune and --older-than go together",
+            ));
+        }
+        _ => {
+            return Err(ArgParseError::new_str(
+                "--prune only applies to ort history stats",
+            ));
+        }
+    }
+    Ok(Cmd::History(HistoryOpts {
+        config_file,
+        action,
+    }))
+}
+
+pub fn parse_flush_queue_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid flush-queue argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    Ok(Cmd::FlushQueue(FlushQueueOpts { config_file }))
+}
+
+pub fn parse_serve_args(args: &[String]) -> Result<Cmd, ArgParseError> {
+    let mut config_file = None;
+    let mut port = DEFAULT_SERVE_PORT;
+
+    let mut i = 2;
+    while i < args.len() {
+        let arg = &args[i];
+        match arg.as_str() {
+            "--cfg" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --cfg"));
+                }
+                config_file = Some(args[i].clone());
+            }
+            "--port" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(ArgParseError::new_str("Missing value for --port"));
+                }
+                port = args[i]
+                    .parse()
+                    .map_err(|_| ArgParseError::new_str("Invalid --port value"))?;
+            }
+            x => {
+                return Err(ArgParseError::new(
+                    "Invalid serve-openai argument: ".to_string() + x,
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    Ok(Cmd::Serve(ServeOpts { config_file, port }))
+}
+
+/// Every option that takes the next argument as its value, in any command.
+/// `cli` needs them to find where the options end and the prompt starts.
+pub(crate) const VALUE_FLAGS: &[&str] = &[
+    // Before the command, see `cli::main`
+    "--stdin-timeout",
+    "--config",
+    "--connect-ip",
+    "--dump-wire",
+    "--dump-wire-max-bytes",
+    "--session",
+    "--profile",
+    // Prompts
+    "--cfg",
+    "-m",
+    "--models-file",
+    "--out-dir",
+    "-s",
+    "--system-file",
+    "--append-system",
+    "-p",
+    "--priority",
+    "--flush",
+    "--tee",
+    "--save-code",
+    "-r",
+    "-pr",
+    "--pin-provider",
+    "--retries",
+    "--from",
+    "--from-openai",
+    "-f",
+    "--output-image",
+    "--samples",
+    "--n",
+    "--max-steps",
+    "--extract",
+    "--label",
+    "--param",
+    "--max-cost",
+    "--reasoning-tokens",
+    "--prompt-fd",
+    "--prompt-file",
+    "--ctx",
+    "--stdin-pos",
+    "--template",
+    "--first-token-deadline",
+    "--deadline",
+    // The other commands
+    "--personas",
+    "--older-than",
+    "--port",
+];
+
 #[derive(Debug)]
 pub struct ArgParseError {
     s: Cow<'static, str>,
     is_help: bool,
+    is_missing_prompt: bool,
+    /// Reading the input failed, the arguments were fine
+    input_err: Option<OrtError>,
 }
 
 impl ArgParseError {
@@ -292,6 +1574,8 @@ impl ArgParseError {
         ArgParseError {
             s: Cow::Owned(s),
             is_help: false,
+            is_missing_prompt: false,
+            input_err: None,
         }
     }
 
@@ -299,6 +1583,8 @@ impl ArgParseError {
         ArgParseError {
             s: Cow::Borrowed(s),
             is_help: false,
+            is_missing_prompt: false,
+            input_err: None,
         }
     }
 
@@ -306,17 +1592,48 @@ impl ArgParseError {
         ArgParseError {
             s: Cow::Borrowed(""),
             is_help: true,
+            is_missing_prompt: false,
+            input_err: None,
         }
     }
 
+    pub fn missing_prompt() -> Self {
+        ArgParseError {
+            s: Cow::Borrowed("Missing prompt."),
+            is_help: false,
+            is_missing_prompt: true,
+            input_err: None,
+        }
+    }
+
+    /// e.g. nothing on stdin within `--stdin-timeout`. No usage for this one.
+    pub fn input(err: OrtError) -> Self {
+        ArgParseError {
+            s: Cow::Borrowed(err.context),
+            is_help: false,
+            is_missing_prompt: false,
+            input_err: Some(err),
+        }
+    }
+
+    pub fn is_input(&self) -> bool {
+        self.input_err.is_some()
+    }
+
     pub fn is_help(&self) -> bool {
         self.is_help
     }
+
+    pub fn is_missing_prompt(&self) -> bool {
+        self.is_missing_prompt
+    }
 }
 
 impl From<ArgParseError> for OrtError {
     fn from(err: ArgParseError) -> OrtError {
-        let _ = err;
+        if let Some(input_err) = err.input_err {
+            return input_err;
+        }
         match err.s {
             Cow::Borrowed(static_str) => ort_error(ErrorKind::InvalidArguments, static_str),
             Cow::Owned(owned_str) => {
@@ -354,4 +1671,785 @@ mod tests {
         assert_eq!(opts.config_file.as_deref(), Some("local"));
         assert!(opts.is_json);
     }
+
+    #[test]
+    fn parse_max_steps_agent_only() {
+        let env = Env::default();
+        let args = strings(&["ort", "agent", "--max-steps", "5", "Fix the tests"]);
+        let Ok(Cmd::Agent(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected agent command");
+        };
+        assert_eq!(opts.max_steps, Some(5));
+
+        let args = strings(&["ort", "--max-steps", "5", "Hello"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+        let args = strings(&["ort", "agent", "--max-steps", "0", "Hello"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+    }
+
+    #[test]
+    fn parse_yes_agent_only() {
+        let env = Env::default();
+        let args = strings(&["ort", "agent", "--yes", "Fix the tests"]);
+        let Ok(Cmd::Agent(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected agent command");
+        };
+        assert!(opts.assume_yes);
+
+        let args = strings(&["ort", "--yes", "Hello"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+    }
+
+    #[test]
+    fn parse_flush() {
+        let env = Env::default();
+        let flush_of = |args: &[&str]| match parse_prompt_args(&strings(args), None, &env) {
+            Ok(Cmd::Prompt(opts)) => Some(opts.flush),
+            _ => None,
+        };
+        assert_eq!(flush_of(&["ort", "Hello"]), Some(Flush::Chunk));
+        assert_eq!(
+            flush_of(&["ort", "--flush", "line", "Hi"]),
+            Some(Flush::Line)
+        );
+        assert_eq!(
+            flush_of(&["ort", "--line-buffered", "Hi"]),
+            Some(Flush::Line)
+        );
+        assert_eq!(
+            flush_of(&["ort", "--flush", "4096", "Hi"]),
+            Some(Flush::Bytes(4096))
+        );
+        assert_eq!(flush_of(&["ort", "--flush", "0", "Hi"]), None);
+        assert_eq!(flush_of(&["ort", "--flush", "word", "Hi"]), None);
+    }
+
+    #[test]
+    fn parse_verbose() {
+        let env = Env::default();
+        let verbose_of = |args: &[&str]| match parse_prompt_args(&strings(args), None, &env) {
+            Ok(Cmd::Prompt(opts)) => opts.verbose,
+            _ => panic!("not a prompt"),
+        };
+        assert!(!verbose_of(&["ort", "Hello"]));
+        assert!(verbose_of(&["ort", "--verbose", "Hello"]));
+    }
+
+    #[test]
+    fn parse_double_dash() {
+        let env = Env::default();
+        let args = strings(&["ort", "-q", "--", "--verbose", "means?"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.prompt.as_deref(), Some("--verbose means?"));
+        assert!(!opts.verbose);
+    }
+
+    #[test]
+    fn parse_model_suffix() {
+        let env = Env::default();
+        let parse = |args: &[&str]| parse_prompt_args(&strings(args), None, &env);
+        let opts = |args: &[&str]| match parse(args) {
+            Ok(Cmd::Prompt(opts)) => opts,
+            _ => panic!("not a prompt"),
+        };
+        let mut conflict = opts(&["ort", "-m", "a/b:free", "--nitro", "Hi"]);
+        assert!(
+            conflict
+                .merge(&crate::common::config::Cfg::default())
+                .is_err()
+        );
+        let mut nitro = opts(&["ort", "-m", "a/b", "--nitro", "Hi"]);
+        nitro.merge(&crate::common::config::Cfg::default()).unwrap();
+        assert_eq!(nitro.models, ["a/b:nitro"]);
+        assert_eq!(nitro.priority, Some(Priority::Throughput));
+
+        let err = |args: &[&str]| match parse(args) {
+            Err(err) => err.s.to_string(),
+            Ok(_) => panic!("expected an error"),
+        };
+        assert_eq!(
+            err(&["ort", "--free", "--floor", "Hi"]),
+            "Only one of --free, --nitro and --floor"
+        );
+        assert_eq!(
+            err(&["ort", "-p", "latency", "--floor", "Hi"]),
+            "--floor conflicts with -p latency"
+        );
+        assert!(parse(&["ort", "-p", "price", "--floor", "--free", "Hi"]).is_err());
+        assert!(parse(&["ort", "-p", "price", "--floor", "Hi"]).is_ok());
+    }
+
+    #[test]
+    fn parse_deadline() {
+        let env = Env::default();
+        match parse_prompt_args(&strings(&["ort", "--deadline", "60s", "Hi"]), None, &env) {
+            Ok(Cmd::Prompt(opts)) => assert_eq!(opts.deadline_ms, Some(60_000)),
+            _ => panic!("not a prompt"),
+        }
+        let Err(err) =
+            parse_prompt_args(&strings(&["ort", "--deadline", "soon", "Hi"]), None, &env)
+        else {
+            panic!("expected an error");
+        };
+        assert_eq!(err.s.to_string(), "Invalid --deadline, e.g. 60s or 500ms");
+    }
+
+    #[test]
+    fn parse_allow_secrets() {
+        let env = Env::default();
+        match parse_prompt_args(&strings(&["ort", "--allow-secrets", "Hi"]), None, &env) {
+            Ok(Cmd::Prompt(opts)) => assert!(opts.allow_secrets),
+            _ => panic!("not a prompt"),
+        }
+        match parse_review_args(&strings(&["ort", "review", "--allow-secrets", "--staged"])) {
+            Ok(Cmd::Review(opts)) => {
+                assert!(opts.allow_secrets);
+                assert!(matches!(opts.source, ReviewSource::Staged));
+            }
+            _ => panic!("not a review"),
+        }
+    }
+
+    #[test]
+    fn parse_first_token_deadline() {
+        assert_eq!(parse_duration_ms("5s"), Some(5000));
+        assert_eq!(parse_duration_ms("750ms"), Some(750));
+        assert_eq!(parse_duration_ms("2"), Some(2000));
+        assert_eq!(parse_duration_ms("0s"), None);
+        assert_eq!(parse_duration_ms("soon"), None);
+        assert_eq!(parse_duration_ms("5m"), None);
+    }
+
+    #[test]
+    fn parse_serve_args_port() {
+        let Cmd::Serve(opts) =
+            parse_serve_args(&strings(&["ort", "serve-openai", "--port", "9000"])).unwrap()
+        else {
+            panic!("expected serve command");
+        };
+        assert_eq!(opts.port, 9000);
+        assert!(opts.config_file.is_none());
+
+        let Cmd::Serve(opts) = parse_serve_args(&strings(&["ort", "serve-openai"])).unwrap() else {
+            panic!("expected serve command");
+        };
+        assert_eq!(opts.port, DEFAULT_SERVE_PORT);
+    }
+
+    #[test]
+    fn parse_labels() {
+        let env = Env::default();
+        let args = strings(&["ort", "--label", "project=ort", "--label", "k=a=b", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(
+            opts.labels,
+            vec![
+                ("project".to_string(), "ort".to_string()),
+                ("k".to_string(), "a=b".to_string())
+            ]
+        );
+
+        let args = strings(&["ort", "--label", "project", "Hello"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+    }
+
+    #[test]
+    fn parse_models_file_lines() {
+        let models: Vec<String> = parse_models_file(
+            "# Eval sweep\nopenai/gpt-5\n\n  anthropic/claude-sonnet-4 # fast\n#x/y\n",
+        )
+        .collect();
+        assert_eq!(models, ["openai/gpt-5", "anthropic/claude-sonnet-4"]);
+
+        let env = Env::default();
+        let many: Vec<&str> = (0..11).flat_map(|_| ["-m", "a/b"]).collect();
+        let args = strings(&[&["ort"], &many[..], &["Hello"]].concat());
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+        let args = strings(&["ort", "--models-file", "/nonexistent/models.txt", "Hi"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+        let args = strings(&["ort", "--out-dir", "out", "-c", "Hi"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+    }
+
+    #[test]
+    fn parse_params() {
+        let env = Env::default();
+        let args = strings(&[
+            "ort",
+            "--param",
+            "top_k=40",
+            "--param",
+            "stop=\"END\"",
+            "--param",
+            "top_k=50",
+            "Hello",
+        ]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(
+            opts.params,
+            vec![
+                ("stop".to_string(), "\"END\"".to_string()),
+                ("top_k".to_string(), "50".to_string())
+            ]
+        );
+
+        for bad in ["top_k", "=1", "top_k=forty", "model=\"x\"", "stop=[1]"] {
+            let args = strings(&["ort", "--param", bad, "Hello"]);
+            assert!(parse_prompt_args(&args, None, &env).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parse_max_cost() {
+        let env = Env::default();
+        let args = strings(&["ort", "--max-cost", "$0.05", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.max_cost, Some(0.05));
+
+        for bad in ["0", "-1", "lots"] {
+            let args = strings(&["ort", "--max-cost", bad, "Hello"]);
+            assert!(parse_prompt_args(&args, None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_prompt_fd_and_file() {
+        const TEST_PATH_C: &core::ffi::CStr = c"/tmp/ort-prompt-file-test.txt";
+        const TEST_PATH: &str = "/tmp/ort-prompt-file-test.txt";
+        let env = Env::default();
+        let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_TRUNC;
+        let fd = syscall::open(TEST_PATH_C.as_ptr(), flags, 0o600).unwrap();
+        let secret = "@not-a-file, the token is hunter2";
+        syscall::write(fd, secret.as_ptr().cast(), secret.len());
+        syscall::close(fd);
+
+        let args = strings(&["ort", "--prompt-file", TEST_PATH]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.prompt.as_deref(), Some(secret));
+        assert_eq!(opts.prompt_filename.as_deref(), Some(TEST_PATH));
+
+        let flags = syscall::O_CLOEXEC | syscall::O_RDONLY;
+        let fd = syscall::open(TEST_PATH_C.as_ptr(), flags, 0).unwrap();
+        let fd_str = utils::num_to_string(fd);
+        let args = strings(&["ort", "--prompt-fd", &fd_str]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.prompt.as_deref(), Some(secret));
+        assert!(opts.prompt_filename.is_none());
+
+        let args = strings(&["ort", "--prompt-file", TEST_PATH, "Hello"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+    }
+
+    #[test]
+    fn parse_system_file() {
+        // Unique, other test runs may be using /tmp too
+        let test_path =
+            "/tmp/ort-system-file-arg-test-".to_string() + &utils::num_to_string(syscall::getpid());
+        let test_path_c = alloc::ffi::CString::new(test_path.as_str()).unwrap();
+        let env = Env {
+            PWD: Some("/work"),
+            ..Default::default()
+        };
+        let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_TRUNC;
+        let fd = syscall::open(test_path_c.as_ptr(), flags, 0o600).unwrap();
+        let prompt = "Work in $PWD.\n\nLine \"two\".\n";
+        syscall::write(fd, prompt.as_ptr().cast(), prompt.len());
+        syscall::close(fd);
+
+        // Before or after -s, the file wins
+        for args in [
+            &[
+                "ort",
+                "-s",
+                "Be brief",
+                "--system-file",
+                &test_path,
+                "Hello",
+            ][..],
+            &[
+                "ort",
+                "--system-file",
+                &test_path,
+                "-s",
+                "Be brief",
+                "Hello",
+            ],
+        ] {
+            let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&strings(args), None, &env) else {
+                panic!("expected prompt command");
+            };
+            assert_eq!(
+                opts.system.as_deref(),
+                Some("Work in /work.\n\nLine \"two\".\n")
+            );
+        }
+
+        let fd = syscall::open(test_path_c.as_ptr(), flags, 0o600).unwrap();
+        let not_utf8 = b"caf\xe9\n";
+        syscall::write(fd, not_utf8.as_ptr().cast(), not_utf8.len());
+        syscall::close(fd);
+        let args = strings(&["ort", "--system-file", &test_path, "Hello"]);
+        let Err(err) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected invalid UTF-8 to fail");
+        };
+        assert!(err.s.ends_with(": not UTF-8 text"), "{}", err.s);
+    }
+
+    #[test]
+    fn parse_ctx_and_template() {
+        // Unique, other test runs may be using /tmp too
+        let pid = utils::num_to_string(syscall::getpid());
+        let ctx_path = "/tmp/ort-ctx-test-".to_string() + &pid + ".rs";
+        let template_path = "/tmp/ort-template-test-".to_string() + &pid + ".txt";
+        let env = Env::default();
+        let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_TRUNC;
+        for (path, contents) in [
+            (&ctx_path, "fn main() {}\n"),
+            (&template_path, "{arg}\n\n{ctx}"),
+        ] {
+            let c_path = alloc::ffi::CString::new(path.as_str()).unwrap();
+            let fd = syscall::open(c_path.as_ptr(), flags, 0o600).unwrap();
+            syscall::write(fd, contents.as_ptr().cast(), contents.len());
+            syscall::close(fd);
+        }
+        let parse = |args: &[&str], stdin: Option<&str>| {
+            parse_prompt_args(&strings(args), stdin.map(|s| s.to_string()), &env)
+        };
+
+        let Ok(Cmd::Prompt(opts)) = parse(
+            &["ort", "--ctx", &ctx_path, "--stdin-pos", "before", "Why?"],
+            Some("log"),
+        ) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.ctx, [(ctx_path.clone(), "fn main() {}\n".to_string())]);
+        assert_eq!(opts.stdin_pos, StdinPos::Before);
+        assert!(parse(&["ort", "--stdin-pos", "middle", "Hi"], None).is_err());
+
+        let Ok(Cmd::Prompt(opts)) = parse(
+            &[
+                "ort",
+                "--template",
+                &template_path,
+                "--ctx",
+                &ctx_path,
+                "Why?",
+            ],
+            None,
+        ) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.template.as_deref(), Some("{arg}\n\n{ctx}"));
+
+        // A source the template names is missing, or the argument has no place
+        assert!(parse(&["ort", "--template", &template_path, "Why?"], None).is_err());
+        assert!(
+            parse(
+                &["ort", "--template", &template_path, "--ctx", &ctx_path],
+                None
+            )
+            .is_err()
+        );
+        // Without a template braces are just text
+        assert!(parse(&["ort", "Explain {stdin}"], None).is_ok());
+        assert!(parse(&["ort", "What does {x} do?"], None).is_ok());
+    }
+
+    #[test]
+    fn parse_priority_long_flag() {
+        let env = Env::default();
+        let args = strings(&["ort", "--priority", "throughput", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.priority, Some(Priority::Throughput));
+
+        let args = strings(&["ort", "--priority", "cheap", "Hello"]);
+        let Err(err) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected an error");
+        };
+        assert!(err.s.contains("--priority"));
+    }
+
+    #[test]
+    fn parse_extract() {
+        let env = Env::default();
+        let args = strings(&["ort", "--extract", "code", "--all", "Write a script"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.extract, Some(Extract::AllCode));
+
+        for args in [
+            &["ort", "--extract", "prose", "Hello"][..],
+            &["ort", "--all", "Hello"],
+            &["ort", "agent", "--extract", "code", "Hello"],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_oneline() {
+        let env = Env::default();
+        let args = strings(&["ort", "--oneline", "command to untar a .tar.zst"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert!(opts.oneline);
+
+        for args in [
+            &["ort", "agent", "--oneline", "Hello"][..],
+            &["ort", "--oneline", "--extract", "code", "Hello"],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_tee() {
+        let env = Env::default();
+        let args = strings(&["ort", "--tee", "out.md", "--tee-all", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.tee.as_deref(), Some("out.md"));
+        assert!(opts.tee_all);
+
+        for args in [
+            &["ort", "--tee-all", "Hello"][..],
+            &["ort", "--tee"],
+            &["ort", "agent", "--tee", "out.md", "Hello"],
+            &["ort", "-m", "a/b", "-m", "c/d", "--tee", "out.md", "Hello"],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_queue_on_fail() {
+        let env = Env::default();
+        let args = strings(&["ort", "--queue-on-fail", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert!(opts.queue_on_fail);
+
+        for args in [
+            &["ort", "agent", "--queue-on-fail", "Hello"][..],
+            &["ort", "-m", "a/b", "-m", "c/d", "--queue-on-fail", "Hello"],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_reasoning_tokens() {
+        let env = Env::default();
+        let args = strings(&[
+            "ort",
+            "--reasoning-tokens",
+            "2000",
+            "--strict-reasoning",
+            "Hello",
+        ]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.reasoning_tokens, Some(2000));
+        assert!(opts.strict_reasoning);
+        let args = strings(&["ort", "-r", "1500", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.reasoning_tokens, Some(1500));
+        assert_eq!(opts.effort, None);
+
+        for args in [
+            &["ort", "--reasoning-tokens"][..],
+            &["ort", "--reasoning-tokens", "0", "Hello"],
+            &["ort", "--reasoning-tokens", "lots", "Hello"],
+            &["ort", "--strict-reasoning", "Hello"],
+            &["ort", "-r", "off", "--reasoning-tokens", "2000", "Hello"],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_pin_provider() {
+        let env = Env::default();
+        let args = strings(&["ort", "--pin-provider", "Chutes", "--retries", "3", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.provider.as_deref(), Some("chutes"));
+        assert!(opts.pin_provider);
+        assert_eq!(opts.retries, Some(3));
+
+        for args in [
+            &["ort", "--pin-provider"][..],
+            &["ort", "--retries", "3", "Hello"],
+            &["ort", "--pin-provider", "chutes", "--retries", "x", "Hello"],
+            &["ort", "--pin-provider", "chutes", "-pr", "novita", "Hello"],
+            &["ort", "agent", "--pin-provider", "chutes", "Hello"],
+            &[
+                "ort",
+                "-m",
+                "a/b",
+                "-m",
+                "c/d",
+                "--pin-provider",
+                "chutes",
+                "Hello",
+            ],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_save_code() {
+        let env = Env::default();
+        let args = strings(&["ort", "--save-code", "src/", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.save_code.as_deref(), Some("src/"));
+
+        for args in [
+            &["ort", "--save-code"][..],
+            &["ort", "agent", "--save-code", "src", "Hello"],
+            &[
+                "ort",
+                "-m",
+                "a/b",
+                "-m",
+                "c/d",
+                "--save-code",
+                "src",
+                "Hello",
+            ],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_samples() {
+        let env = Env::default();
+        for flag in ["--samples", "--n"] {
+            let args = strings(&["ort", flag, "3", "Hello"]);
+            let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+                panic!("expected prompt command");
+            };
+            assert_eq!(opts.n, Some(3));
+        }
+        let args = strings(&["ort", "--samples", "0", "Hello"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+    }
+
+    #[test]
+    fn parse_verbosity() {
+        let env = Env::default();
+        for (flags, expected) in [
+            (&[][..], None),
+            (&["-q"][..], Some(Verbosity::Quiet)),
+            (&["--silent"][..], Some(Verbosity::Silent)),
+            (&["--silent", "-q"][..], Some(Verbosity::Silent)),
+            (&["-q", "--silent"][..], Some(Verbosity::Silent)),
+        ] {
+            let mut args = strings(&["ort"]);
+            args.extend(strings(flags));
+            args.push("Hello".to_string());
+            let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+                panic!("expected prompt command");
+            };
+            assert_eq!(opts.verbosity, expected, "{flags:?}");
+        }
+    }
+
+    #[test]
+    fn parse_append_system() {
+        let env = Env::default();
+        let args = strings(&["ort", "--append-system", "Cite sources.", "Hello"]);
+        let Ok(Cmd::Prompt(mut opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert_eq!(opts.append_system.as_deref(), Some("Cite sources."));
+        let cfg = crate::common::config::Cfg {
+            system_prompt: Some("Be brief.".to_string()),
+            ..Default::default()
+        };
+        opts.merge(&cfg).unwrap();
+        assert_eq!(opts.system.as_deref(), Some("Be brief.\n\nCite sources."));
+        assert!(opts.append_system.is_none());
+
+        let args = strings(&["ort", "--append-system", "Cite sources.", "Hello"]);
+        let Ok(Cmd::Prompt(mut opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        opts.merge(&crate::common::config::Cfg::default()).unwrap();
+        assert_eq!(opts.system.as_deref(), Some("Cite sources."));
+    }
+
+    #[test]
+    fn parse_json() {
+        let env = Env::default();
+        let args = strings(&["ort", "-m", "a/b", "-m", "c/d", "--json", "Hello"]);
+        let Ok(Cmd::Prompt(opts)) = parse_prompt_args(&args, None, &env) else {
+            panic!("expected prompt command");
+        };
+        assert!(opts.is_json);
+        assert_eq!(opts.models.len(), 2);
+
+        for args in [
+            &["ort", "agent", "--json", "Hello"][..],
+            &["ort", "-c", "--json", "Hello"],
+        ] {
+            assert!(parse_prompt_args(&strings(args), None, &env).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_config() {
+        let Ok(Cmd::Config(opts)) =
+            parse_config_args(&strings(&["ort", "config", "profiles", "--cfg", "x.cfg"]))
+        else {
+            panic!("expected config command");
+        };
+        assert!(matches!(opts.action, ConfigAction::Profiles));
+        assert_eq!(opts.config_file.as_deref(), Some("x.cfg"));
+        assert!(parse_config_args(&strings(&["ort", "config"])).is_err());
+    }
+
+    #[test]
+    fn parse_from_openai_and_history() {
+        let env = Env::default();
+        let args = strings(&["ort", "--from-openai", "chat.json", "Go on"]);
+        let Ok(Cmd::ContinueConversation(opts, Some(Transcript::File(path)))) =
+            parse_prompt_args(&args, None, &env)
+        else {
+            panic!("expected continue command");
+        };
+        assert_eq!(path, "chat.json");
+        assert_eq!(opts.prompt.as_deref(), Some("Go on"));
+
+        // Piped in, stdin is the conversation not part of the prompt
+        let args = strings(&["ort", "-c", "--from", "-", "Go on"]);
+        let piped = Some("[]".to_string());
+        let Ok(Cmd::ContinueConversation(opts, Some(Transcript::Piped(json)))) =
+            parse_prompt_args(&args, piped.clone(), &env)
+        else {
+            panic!("expected piped continue command");
+        };
+        assert_eq!(json, "[]");
+        assert_eq!(opts.stdin, None);
+        assert_eq!(opts.prompt.as_deref(), Some("Go on"));
+        // Nothing piped, or no prompt left
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+        let args = strings(&["ort", "-c", "--from", "-"]);
+        assert!(parse_prompt_args(&args, piped, &env).is_err());
+        let args = strings(&["ort", "agent", "--from-openai", "chat.json", "Go on"]);
+        assert!(parse_prompt_args(&args, None, &env).is_err());
+
+        let Ok(Cmd::History(opts)) =
+            parse_history_args(&strings(&["ort", "history", "import", "chat.json"]))
+        else {
+            panic!("expected history command");
+        };
+        assert!(matches!(opts.action, HistoryAction::Import(f) if f == "chat.json"));
+        let Ok(Cmd::History(opts)) = parse_history_args(&strings(&["ort", "history", "export"]))
+        else {
+            panic!("expected history command");
+        };
+        assert!(matches!(opts.action, HistoryAction::Export(None)));
+        let Ok(Cmd::History(opts)) = parse_history_args(&strings(&["ort", "history", "list"]))
+        else {
+            panic!("expected history command");
+        };
+        assert!(matches!(opts.action, HistoryAction::List));
+        assert!(parse_history_args(&strings(&["ort", "history"])).is_err());
+        assert!(parse_history_args(&strings(&["ort", "history", "import"])).is_err());
+    }
+
+    #[test]
+    fn parse_replay() {
+        let args = strings(&["ort", "replay", "wire/response.http", "--fast", "-rr"]);
+        let Ok(Cmd::Replay(opts)) = parse_replay_args(&args) else {
+            panic!("expected replay command");
+        };
+        assert_eq!(opts.capture, "wire/response.http");
+        assert!(opts.is_fast && opts.show_reasoning);
+        assert!(opts.verbosity == Verbosity::Normal && !opts.is_raw);
+        assert_eq!(opts.flush, Flush::Chunk);
+
+        assert!(parse_replay_args(&strings(&["ort", "replay"])).is_err());
+        assert!(parse_replay_args(&strings(&["ort", "replay", "a.sse", "b.sse"])).is_err());
+        assert!(parse_replay_args(&strings(&["ort", "replay", "a.sse", "--slow"])).is_err());
+    }
+
+    #[test]
+    fn parse_panel() {
+        let args = strings(&[
+            "ort",
+            "panel",
+            "--personas",
+            "skeptic, optimist,engineer",
+            "--judge",
+            "Should",
+            "we rewrite it?",
+        ]);
+        let Ok(Cmd::Panel(opts)) = parse_panel_args(&args) else {
+            panic!("expected panel command");
+        };
+        assert_eq!(opts.personas, ["skeptic", "optimist", "engineer"]);
+        assert!(opts.judge);
+        assert!(opts.model.is_none());
+        assert_eq!(opts.question, "Should we rewrite it?");
+
+        assert!(parse_panel_args(&strings(&["ort", "panel", "Why?"])).is_err());
+        assert!(parse_panel_args(&strings(&["ort", "panel", "--personas", "a"])).is_err());
+        assert!(
+            parse_panel_args(&strings(&["ort", "panel", "--personas", "a", "-x", "Hi"])).is_err()
+        );
+    }
+
+    #[test]
+    fn parse_history_stats() {
+        let parse = |a: &[&str]| match parse_history_args(&strings(a)) {
+            Ok(Cmd::History(HistoryOpts {
+                action: HistoryAction::Stats { prune_older_than },
+                ..
+            })) => Ok(prune_older_than),
+            Ok(_) => panic!("expected history stats"),
+            Err(err) => Err(err),
+        };
+        assert_eq!(parse(&["ort", "history", "stats"]).unwrap(), None);
+        assert_eq!(
+            parse(&["ort", "history", "stats", "--prune", "--older-than", "30d"]).unwrap(),
+            Some(30 * 86_400)
+        );
+        assert_eq!(
+            parse(&["ort", "history", "stats", "--older-than", "12h", "--prune"]).unwrap(),
+            Some(12 * 3600)
+        );
+        assert!(parse(&["ort", "history", "stats", "--prune"]).is_err());
+        assert!(parse(&["ort", "history", "stats", "--older-than", "30d"]).is_err());
+        assert!(parse(&["ort", "history", "stats", "--prune", "--older-than", "30"]).is_err());
+        assert!(parse(&["ort", "history", "stats", "--prune", "--older-than", "d"]).is_err());
+        assert!(parse_history_args(&strings(&["ort", "history", "export", "--prune"])).is_err());
+    }
 }
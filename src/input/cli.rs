@@ -5,30 +5,56 @@
 //! Copyright (c) 2025-2026 Graham King
 
 use core::ffi::{c_int, c_void};
+use core::net::Ipv4Addr;
 
 extern crate alloc;
 use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 
-use crate::OrtResult;
 use crate::Write;
-use crate::common::buf_read;
-use crate::common::config;
+use crate::common::config::{self, Cfg, ConfigSource};
+use crate::common::models;
+use crate::common::paths;
+use crate::common::request_id;
+use crate::common::term::Term;
+use crate::common::utils;
 use crate::input::agent;
 use crate::input::args;
 use crate::input::args::Cmd;
+use crate::input::doctor;
+use crate::input::history;
+use crate::input::lineedit;
 use crate::input::list;
+use crate::input::panel;
 use crate::input::prompt;
+use crate::input::queue;
+use crate::input::replay;
+use crate::input::review;
+use crate::input::serve;
+use crate::input::stdin;
+use crate::input::to_json::write_json_str;
+use crate::input::tokens;
+use crate::input::update;
+use crate::output::metrics;
 use crate::syscall;
+use crate::transport::{trace, wire_dump};
 use crate::{ErrorKind, ort_error};
+use crate::{OrtError, OrtResult, PromptOpts, Verbosity};
 
 const STDIN_FILENO: i32 = 0;
 const STDERR_FILENO: i32 = 0;
 
+/// A long agent session has many, only the last few matter for an error
+const MAX_SHOWN_ATTEMPTS: usize = 5;
+
 // Keep default mode in sync with common/data.rs DEFAULT_MODEL
-const USAGE: &str = "Usage: ort [--cfg ort.cfg] [-m <model>] [-s \"<system prompt>\"] [-p <price|throughput|latency>] [-pr provider-slug] [-r] [-rr] [-q] [-nc] [-ws] <prompt>\n\
+const USAGE: &str = "Usage: ort [--cfg ort.cfg | --config path | --no-config] [--profile name] [-m <model>] [-s \"<system prompt>\"] [--system-file path] [--append-system \"text\"] [-p|--priority <price|throughput|latency>] [-pr provider-slug | --pin-provider slug [--retries N]] [--free|--nitro|--floor] [-r] [-rr] [--reasoning-tokens 2000 [--strict-reasoning]] [-q|--silent] [--raw] [--flush chunk|line|N] [--line-buffered] [--verbose] [--ctx file] [--allow-secrets] [--clean-input] [--stdin-pos before|after] [--stdin-null | --stdin-timeout 10s] [--template file] [-nc] [-ws] [--offline] [--no-pager] [--no-update-check] [--connect-ip 104.18.2.115] [--show-connection] [--show-model-source] [--dump-wire dir] [--trace] [--samples 1] [--first-token-deadline 5s] [--deadline 60s] [--max-cost 0.05] [--output-image out.png] [--models-file models.txt] [--out-dir dir] [--label key=value] [--param key=value] [--extract code [--all]] [--oneline] [--tee out.md [--tee-all]] [--queue-on-fail] [--save-code dir] [--session name] [-c [--from chat.json|-]] <prompt | --prompt-fd N | --prompt-file path>\n\
 Defaults: -m nvidia/nemotron-3-super-120b-a12b:free -s omitted ; -p omitted\n\
 Example:\n  ort -p price -m openai/gpt-oss-20b -r low -rr -s \"Respond like a pirate\" \"Write a limerick about AI\"
 
+Other commands: ort list [-json] ; ort tokens [file|-] [-m model] ; ort doctor ; ort config profiles ; ort review [--staged|<range>|<file.patch>] [-m model] [--allow-secrets] ; ort panel --personas a,b [--judge] [-m model] <question> ; ort history import <file|->|export [file]|list|stats [--prune --older-than 30d] ; ort replay <response.http|capture.sse> [--fast] ; ort flush-queue ; ort serve-openai [--port 8080]
+
 See https://github.com/grahamking/ort for full docs.
 ";
 
@@ -43,10 +69,23 @@ pub struct Env {
     pub HOME: Option<&'static str>,
     pub PWD: Option<&'static str>,
     pub TMUX_PANE: Option<&'static str>,
+    /// Names the session for `last-` files, overrides TMUX_PANE. Also `--session`.
+    pub ORT_SESSION: Option<&'static str>,
+    /// The `[name]` section of the config to use. Also `--profile`.
+    pub ORT_PROFILE: Option<&'static str>,
     pub XDG_CONFIG_HOME: Option<&'static str>,
     pub XDG_CACHE_HOME: Option<&'static str>,
+    pub XDG_STATE_HOME: Option<&'static str>,
+    /// Move the config, cache and state dirs and the prompt history. See `paths`.
+    pub ORT_CONFIG_DIR: Option<&'static str>,
+    pub ORT_CACHE_DIR: Option<&'static str>,
+    pub ORT_STATE_DIR: Option<&'static str>,
+    pub ORT_HISTORY_FILE: Option<&'static str>,
     pub OPENROUTER_API_KEY: Option<&'static str>,
     pub NVIDIA_API_KEY: Option<&'static str>,
+    pub SSLKEYLOGFILE: Option<&'static str>,
+    /// "dumb", or unset, means no colors or spinner. See `Term`.
+    pub TERM: Option<&'static str>,
 }
 
 fn parse_args(args: &[String], env: &Env) -> Result<args::Cmd, args::ArgParseError> {
@@ -57,43 +96,241 @@ fn parse_args(args: &[String], env: &Env) -> Result<args::Cmd, args::ArgParseErr
 
     if args[1].as_str() == "list" {
         args::parse_list_args(args)
+    } else if args[1].as_str() == "doctor" {
+        args::parse_doctor_args(args)
+    } else if args[1].as_str() == "config" {
+        args::parse_config_args(args)
+    } else if args[1].as_str() == "tokens" {
+        args::parse_tokens_args(args)
+    } else if args[1].as_str() == "review" {
+        args::parse_review_args(args)
+    } else if args[1].as_str() == "panel" {
+        args::parse_panel_args(args)
+    } else if args[1].as_str() == "history" {
+        args::parse_history_args(args)
+    } else if args[1].as_str() == "replay" {
+        args::parse_replay_args(args)
+    } else if args[1].as_str() == "flush-queue" {
+        args::parse_flush_queue_args(args)
+    } else if args[1].as_str() == "serve-openai" {
+        args::parse_serve_args(args)
     } else {
+        // Before reading stdin, it decides how
+        if find_option(args, "--clean-input").is_some() {
+            stdin::set_lossy();
+        }
         let is_pipe_input = !syscall::isatty(STDIN_FILENO);
         let stdin = if is_pipe_input {
-            let mut buffer = String::with_capacity(8 * 1024);
-            buf_read::fd_read_to_string(STDIN_FILENO, &mut buffer);
-            Some(buffer)
+            stdin::read().map_err(args::ArgParseError::input)?
         } else {
             None
         };
-        args::parse_prompt_args(args, stdin, env)
+        match args::parse_prompt_args(args, stdin, env) {
+            // No prompt given, ask for one on the terminal
+            Err(err) if err.is_missing_prompt() && !is_pipe_input && !stdin::is_null() => {
+                match lineedit::read_prompt(env) {
+                    Some(prompt) => args::parse_prompt_args(args, Some(prompt), env),
+                    None => Err(err),
+                }
+            }
+            res => res,
+        }
     }
 }
 
 pub fn main<W: Write + Send>(
     args: &[String],
-    env: Env,
-    is_terminal: bool,
+    mut env: Env,
+    term: Term,
     w: &mut W,
 ) -> OrtResult<c_int> {
-    let cmd = match parse_args(args, &env) {
+    // These go with any command, so they are handled here
+    let mut args = args.to_vec();
+    let is_offline = take_flag(&mut args, "--offline");
+    let is_no_pager = take_flag(&mut args, "--no-pager");
+    let is_no_update_check = take_flag(&mut args, "--no-update-check");
+    let is_show_connection = take_flag(&mut args, "--show-connection");
+    let is_show_model_source = take_flag(&mut args, "--show-model-source");
+    let is_trace = take_flag(&mut args, "--trace");
+    if take_flag(&mut args, "--stdin-null") {
+        stdin::set_null();
+    }
+    if let Some(t) = take_value(
+        &mut args,
+        "--stdin-timeout",
+        "Missing value for --stdin-timeout",
+    )? {
+        let ms = match t.as_str() {
+            "0" => 0,
+            t => args::parse_duration_ms(t).ok_or_else(|| {
+                ort_error(
+                    ErrorKind::InvalidArguments,
+                    "Invalid --stdin-timeout, e.g. 10s, 500ms or 0 to wait forever",
+                )
+            })?,
+        };
+        stdin::set_timeout_ms(ms);
+    }
+    let is_no_config = take_flag(&mut args, "--no-config");
+    let config_path = take_value(&mut args, "--config", "Missing path for --config")?;
+    if is_no_config && config_path.is_some() {
+        return Err(ort_error(
+            ErrorKind::InvalidArguments,
+            "Use one of --config and --no-config",
+        ));
+    }
+    let connect_ip = take_value(&mut args, "--connect-ip", "Missing IP for --connect-ip")?;
+    if connect_ip
+        .as_deref()
+        .is_some_and(|ip| ip.parse::<Ipv4Addr>().is_err())
+    {
+        return Err(ort_error(
+            ErrorKind::InvalidArguments,
+            "--connect-ip needs an IPv4 address, e.g. 104.18.2.115",
+        ));
+    }
+    let dump_wire = take_value(
+        &mut args,
+        "--dump-wire",
+        "Missing directory for --dump-wire",
+    )?;
+    let dump_wire_max = take_value(
+        &mut args,
+        "--dump-wire-max-bytes",
+        "Missing value for --dump-wire-max-bytes",
+    )?
+    .map(|n| n.parse::<usize>())
+    .transpose()
+    .map_err(|_| {
+        ort_error(
+            ErrorKind::InvalidArguments,
+            "Invalid --dump-wire-max-bytes value",
+        )
+    })?;
+    if let Some(name) = take_value(&mut args, "--session", "Missing name for --session")? {
+        env.ORT_SESSION = Some(name.leak());
+    }
+    if let Some(name) = take_value(&mut args, "--profile", "Missing name for --profile")? {
+        if is_no_config {
+            return Err(ort_error(
+                ErrorKind::InvalidArguments,
+                "Use one of --profile and --no-config",
+            ));
+        }
+        env.ORT_PROFILE = Some(name.leak());
+    }
+    if let Some(dir) = dump_wire {
+        wire_dump::set_dir(dir.trim_end_matches('/').to_string().leak(), dump_wire_max);
+    }
+    let cmd = match parse_args(&args, &env) {
         Ok(cmd) => cmd,
         Err(err) if err.is_help() => {
             print_usage();
             return Ok(0);
         }
         Err(err) => {
-            print_usage();
+            if !err.is_input() {
+                print_usage();
+            }
             return Err(err.into());
         }
     };
-    let config_file = match &cmd {
+    if is_trace {
+        // The requests of several models overlap, their steps would mix
+        if let Cmd::Prompt(opts) | Cmd::Agent(opts) | Cmd::ContinueConversation(opts, _) = &cmd
+            && opts.models.len() > 1
+        {
+            return Err(ort_error(
+                ErrorKind::InvalidArguments,
+                "--trace works with one model",
+            ));
+        }
+        trace::set_on();
+    }
+    // Before the config loads, so its notices go quiet too
+    utils::set_silent(match &cmd {
+        Cmd::Prompt(opts) | Cmd::Agent(opts) | Cmd::ContinueConversation(opts, _) => {
+            opts.verbosity == Some(Verbosity::Silent)
+        }
+        Cmd::Replay(opts) => opts.verbosity == Verbosity::Silent,
+        _ => false,
+    });
+    let config_file: Option<String> = match &cmd {
+        Cmd::Tokens(_) => None,
+        Cmd::Doctor(opts) => opts.config_file.as_deref(),
+        Cmd::Config(opts) => opts.config_file.as_deref(),
         Cmd::List(opts) => opts.config_file.as_deref(),
-        Cmd::Prompt(opts) | Cmd::Agent(opts) | Cmd::ContinueConversation(opts) => {
+        Cmd::Serve(opts) => opts.config_file.as_deref(),
+        Cmd::Review(opts) => opts.config_file.as_deref(),
+        Cmd::Panel(opts) => opts.config_file.as_deref(),
+        Cmd::History(opts) => opts.config_file.as_deref(),
+        Cmd::Replay(opts) => opts.config_file.as_deref(),
+        Cmd::FlushQueue(opts) => opts.config_file.as_deref(),
+        Cmd::Prompt(opts) | Cmd::Agent(opts) | Cmd::ContinueConversation(opts, _) => {
             opts.config_file.as_deref()
         }
+    }
+    .map(|name| name.to_string());
+    let source = match (config_path.as_deref(), config_file.as_deref()) {
+        (Some(_), Some(_)) => {
+            return Err(ort_error(
+                ErrorKind::InvalidArguments,
+                "Use one of --cfg and --config",
+            ));
+        }
+        (None, Some(_)) if is_no_config => {
+            return Err(ort_error(
+                ErrorKind::InvalidArguments,
+                "Use one of --cfg and --no-config",
+            ));
+        }
+        _ if is_no_config => ConfigSource::Empty,
+        (Some(path), None) => ConfigSource::Path(path),
+        (None, name) => ConfigSource::Named(name.unwrap_or("ort.cfg")),
+    };
+    // These need no config or API key. Doctor checks those itself.
+    let cmd = match cmd {
+        Cmd::Tokens(opts) => return tokens::run(opts, w).map(|_| 0),
+        Cmd::Doctor(_) => return doctor::run(&env, source, is_offline, w),
+        // Listing them works whatever ORT_PROFILE says
+        Cmd::Config(opts) => {
+            let profile = env.ORT_PROFILE.take();
+            let cfg = config::Cfg::load(&env, source)?;
+            return run_config(&cfg, profile, opts, w).map(|_| 0);
+        }
+        cmd => cmd,
+    };
+    let mut cfg = config::Cfg::load(&env, source)?;
+    paths::set_from_config(&cfg);
+    cfg.use_pager &= !is_no_pager;
+    cfg.show_connection = is_show_connection;
+    cfg.show_model_source = is_show_model_source;
+    // Skip the resolver and the config's `dns` for this run
+    if let Some(ip) = connect_ip {
+        cfg.dns = vec![ip];
+    }
+    // Someone is there to read the notice
+    if cfg.update_check && !is_no_update_check && !is_offline && term.is_tty() {
+        update::check(&env);
+    }
+
+    #[cfg(feature = "sslkeylog")]
+    if let Some(path) = env.SSLKEYLOGFILE {
+        utils::warn("Writing TLS secrets to SSLKEYLOGFILE\n");
+        crate::transport::tls::set_key_log_file(path);
+    }
+    // Only needs config for the default model
+    let cmd = match cmd {
+        Cmd::History(opts) => {
+            return history::run(&env, &cfg, opts, is_offline, w).map(|_| 0);
+        }
+        Cmd::Replay(opts) => return replay::run(&cfg, opts, term, w).map(|_| 0),
+        cmd => cmd,
     };
-    let cfg = config::Cfg::load(&env, config_file.unwrap_or("ort.cfg"))?;
+    let mut cmd = cmd;
+    if let Cmd::Prompt(opts) | Cmd::Agent(opts) | Cmd::ContinueConversation(opts, _) = &mut cmd {
+        opts.assemble_prompt(&cfg)?;
+    }
 
     // Fail fast if key missing
     let api_key_ref = env.OPENROUTER_API_KEY.unwrap_or_default();
@@ -101,6 +338,8 @@ pub fn main<W: Write + Send>(
     if api_key.is_empty() {
         api_key = match cfg.get_api_key() {
             Some(k) => k.to_string(),
+            // Not needed, we won't get as far as sending anything
+            None if is_offline => String::new(),
             None => {
                 return Err(ort_error(
                     ErrorKind::MissingApiKey,
@@ -112,13 +351,16 @@ pub fn main<W: Write + Send>(
 
     let cmd_result = match cmd {
         args::Cmd::Prompt(mut cli_opts) => {
+            let explicit_effort = cli_opts.effort.is_some() || cli_opts.reasoning_tokens.is_some();
             if cli_opts.merge_config {
-                cli_opts.merge(&cfg);
+                cli_opts.merge(&cfg)?;
             } else {
-                cli_opts.merge(&config::Cfg::default());
+                cli_opts.merge(&config::Cfg::default())?;
             }
+            check_models(&env, &mut cli_opts, explicit_effort, false)?;
             let messages = cli_opts.messages()?;
-            if cli_opts.models.len() == 1 {
+            require_network(is_offline)?;
+            if cli_opts.models.len() == 1 && !cli_opts.is_json && cli_opts.out_dir.is_none() {
                 prompt::run(
                     &api_key,
                     &cfg,
@@ -126,28 +368,377 @@ pub fn main<W: Write + Send>(
                     cli_opts,
                     messages,
                     alloc::vec![],
-                    !is_terminal,
+                    term,
                     w,
                 )
             } else {
-                prompt::run_multi(&api_key, &cfg, cli_opts, messages, w)
+                prompt::run_multi(&api_key, &cfg, &env, cli_opts, messages, w)
             }
         }
         args::Cmd::Agent(mut cli_opts) => {
+            let explicit_effort = cli_opts.effort.is_some() || cli_opts.reasoning_tokens.is_some();
             if cli_opts.merge_config {
-                cli_opts.merge(&cfg);
+                cli_opts.merge(&cfg)?;
             } else {
-                cli_opts.merge(&config::Cfg::default());
+                cli_opts.merge(&config::Cfg::default())?;
             }
+            check_models(&env, &mut cli_opts, explicit_effort, true)?;
             // Agent mode always includes server-side web tools
             cli_opts.include_web_tools = Some(true);
             let messages = cli_opts.messages()?;
-            agent::run(&api_key, &cfg, &env, cli_opts, messages, w)
+            require_network(is_offline)?;
+            agent::run(&api_key, &cfg, &env, cli_opts, messages, term, w)
+        }
+        args::Cmd::ContinueConversation(cli_opts, from) => {
+            require_network(is_offline)?;
+            prompt::run_continue(&api_key, &cfg, &env, cli_opts, from, term, w)
+        }
+        args::Cmd::Tokens(_)
+        | args::Cmd::Doctor(_)
+        | args::Cmd::Config(_)
+        | args::Cmd::History(_)
+        | args::Cmd::Replay(_) => {
+            unreachable!(
+                "tokens, doctor, config, history and replay are handled before loading the API key"
+            )
+        }
+        args::Cmd::List(args) => {
+            require_network(is_offline).and_then(|_| list::run(&api_key, &cfg, &env, args, w))
         }
-        args::Cmd::ContinueConversation(cli_opts) => {
-            prompt::run_continue(&api_key, &cfg, &env, cli_opts, !is_terminal, w)
+        args::Cmd::Review(opts) => review::run(&api_key, &cfg, opts, is_offline, term, w),
+        args::Cmd::Panel(opts) => panel::run(&api_key, &cfg, opts, is_offline, term, w),
+        args::Cmd::Serve(opts) => {
+            require_network(is_offline).and_then(|_| serve::run(&api_key, &cfg, opts))
+        }
+        args::Cmd::FlushQueue(_) => {
+            require_network(is_offline).and_then(|_| queue::flush(&api_key, &cfg, &env, term, w))
         }
-        args::Cmd::List(args) => list::run(&api_key, &cfg, args, w),
     };
+    if let Err(err) = &cmd_result {
+        // Our id for the last attempts, to match them up in the log and a bug report
+        let attempts = request_id::attempts();
+        if !attempts.is_empty() {
+            let recent = &attempts[attempts.len().saturating_sub(MAX_SHOWN_ATTEMPTS)..];
+            utils::print_error(c"Request ids: ", &recent.join(", "));
+        }
+        run_on_error_hook(&cfg, err);
+        // --offline, or nowhere to send them anyway
+        if !matches!(err.kind, ErrorKind::NoNetwork) {
+            metrics::record_error(&cfg, "", err);
+        }
+    }
     cmd_result.map(|_| 0)
 }
+
+/// `ort config profiles`: the profiles in the config, `*` on `profile`
+fn run_config<W: Write>(
+    cfg: &Cfg,
+    profile: Option<&str>,
+    opts: args::ConfigOpts,
+    w: &mut W,
+) -> OrtResult<()> {
+    let args::ConfigAction::Profiles = opts.action;
+    if cfg.profiles.is_empty() {
+        w.write_str("No profiles. Add a `[name]` section to the config, see the README.\n")?;
+    }
+    for name in &cfg.profiles {
+        let mark = if profile == Some(name.as_str()) {
+            "* "
+        } else {
+            "  "
+        };
+        w.write_str(mark)?;
+        w.write_str(name)?;
+        w.write_str("\n")?;
+    }
+    w.flush()
+}
+
+/// The command words, `ort list` etc. Anything else is a prompt.
+const SUBCOMMANDS: [&str; 11] = [
+    "agent",
+    "list",
+    "doctor",
+    "config",
+    "tokens",
+    "review",
+    "panel",
+    "history",
+    "replay",
+    "flush-queue",
+    "serve-openai",
+];
+
+/// Where the options end: at the first positional argument, such as the
+/// prompt, or at `--`. After that `--offline` is the user's text, not ours.
+fn options_end(args: &[String]) -> usize {
+    let mut i = if args
+        .get(1)
+        .is_some_and(|a| SUBCOMMANDS.contains(&a.as_str()))
+    {
+        2
+    } else {
+        1
+    };
+    while let Some(arg) = args.get(i) {
+        if arg == "--" || !arg.starts_with('-') {
+            return i;
+        }
+        i += if args::VALUE_FLAGS.contains(&arg.as_str()) {
+            2
+        } else {
+            1
+        };
+    }
+    args.len()
+}
+
+/// The position of option `name`, if it is before the options end
+fn find_option(args: &[String], name: &str) -> Option<usize> {
+    args[..options_end(args).min(args.len())]
+        .iter()
+        .position(|a| a == name)
+}
+
+/// Remove `name` from the args, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match find_option(args, name) {
+        Some(idx) => {
+            args.remove(idx);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Remove `name` and the value after it from the args, returning the value.
+fn take_value(
+    args: &mut Vec<String>,
+    name: &str,
+    missing: &'static str,
+) -> OrtResult<Option<String>> {
+    let Some(idx) = find_option(args, name) else {
+        return Ok(None);
+    };
+    args.remove(idx);
+    if idx == args.len() {
+        return Err(ort_error(ErrorKind::InvalidArguments, missing));
+    }
+    Ok(Some(args.remove(idx)))
+}
+
+/// With --offline everything up to the request still runs, so the config,
+/// prompt files and images are checked, then we stop.
+pub(crate) fn require_network(is_offline: bool) -> OrtResult<()> {
+    if is_offline {
+        Err(ort_error(
+            ErrorKind::NoNetwork,
+            "--offline, not sending the request",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Catch requests the model can't handle before we send them,
+/// if `ort list` has cached the model capabilities.
+fn check_models(
+    env: &Env,
+    opts: &mut PromptOpts,
+    explicit_effort: bool,
+    has_tools: bool,
+) -> OrtResult<()> {
+    let Some(cache) = models::load(env) else {
+        return Ok(());
+    };
+    models::check(&cache, opts, explicit_effort, has_tools).map_err(|msg| {
+        syscall::write(2, msg.as_ptr().cast(), msg.len());
+        ort_error(ErrorKind::ModelUnsupported, "model capability check")
+    })
+}
+
+/// Run the `pre_request` hook, if configured, with the request body on stdin.
+/// Returns the body to send. The hook replaces it by printing a new one,
+/// and blocks the request by exiting non-zero.
+pub(crate) fn run_pre_request_hook(cfg: &Cfg, body: String) -> OrtResult<String> {
+    let Some(command) = cfg.pre_request.as_deref() else {
+        return Ok(body);
+    };
+    let out = run_hook(command, body.as_bytes(), cfg.hook_timeout_ms)?;
+    if out.exit_code != 0 {
+        return Err(ort_error(
+            ErrorKind::HookFailed,
+            "pre_request hook rejected the request",
+        ));
+    }
+    if out.stdout.trim().is_empty() {
+        Ok(body)
+    } else {
+        Ok(out.stdout)
+    }
+}
+
+/// Run the `post_response` hook, if configured, with the response on stdin.
+/// A failing hook is reported but does not fail the prompt, the answer is already out.
+pub(crate) fn run_post_response_hook(cfg: &Cfg, model: &str, provider: &str, content: &str) {
+    let Some(command) = cfg.post_response.as_deref() else {
+        return;
+    };
+    let mut payload: Vec<u8> = Vec::with_capacity(64 + model.len() + content.len());
+    let res = post_response_payload(&mut payload, model, provider, content)
+        .and_then(|_| run_hook(command, &payload, cfg.hook_timeout_ms));
+    report_hook_result("post_response", res);
+}
+
+fn post_response_payload<W: Write>(
+    w: &mut W,
+    model: &str,
+    provider: &str,
+    content: &str,
+) -> OrtResult<()> {
+    w.write_str("{\"model\":")?;
+    write_json_str(w, model)?;
+    w.write_str(",\"provider\":")?;
+    write_json_str(w, provider)?;
+    w.write_str(",\"content\":")?;
+    write_json_str(w, content)?;
+    w.write_char('}')?;
+    Ok(())
+}
+
+/// Run the `on_error` hook, if configured, with the error on stdin.
+fn run_on_error_hook(cfg: &Cfg, err: &OrtError) {
+    let Some(command) = cfg.on_error.as_deref() else {
+        return;
+    };
+    let mut payload: Vec<u8> = Vec::with_capacity(64);
+    let res = on_error_payload(&mut payload, err)
+        .and_then(|_| run_hook(command, &payload, cfg.hook_timeout_ms));
+    report_hook_result("on_error", res);
+}
+
+fn on_error_payload<W: Write>(w: &mut W, err: &OrtError) -> OrtResult<()> {
+    w.write_str("{\"error\":")?;
+    write_json_str(w, &err.as_string())?;
+    w.write_char('}')?;
+    Ok(())
+}
+
+/// Run a hook command. Its stderr is passed through to ours.
+fn run_hook(command: &str, payload: &[u8], timeout_ms: u32) -> OrtResult<syscall::ProcessOutput> {
+    let out = syscall::system_with_input(command, payload, timeout_ms)?;
+    if !out.stderr.is_empty() {
+        syscall::write(2, out.stderr.as_ptr().cast(), out.stderr.len());
+    }
+    Ok(out)
+}
+
+fn report_hook_result(name: &str, res: OrtResult<syscall::ProcessOutput>) {
+    let mut msg = String::with_capacity(64);
+    msg.push_str("Hook ");
+    msg.push_str(name);
+    match res {
+        Ok(out) if out.exit_code == 0 => return,
+        Ok(out) => {
+            msg.push_str(" exited with status ");
+            msg.push_str(&crate::utils::num_to_string(out.exit_code));
+        }
+        Err(err) => {
+            msg.push_str(" failed: ");
+            msg.push_str(&err.as_string());
+        }
+    }
+    msg.push('\n');
+    syscall::write(2, msg.as_ptr().cast(), msg.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn global_flags_stop_at_the_prompt() {
+        let mut args: Vec<String> = [
+            "ort",
+            "--session",
+            "w",
+            "-m",
+            "a/b",
+            "--offline",
+            "what",
+            "does",
+            "--trace",
+            "do",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert!(take_flag(&mut args, "--offline"));
+        // Part of the prompt
+        assert!(!take_flag(&mut args, "--trace"));
+        assert_eq!(
+            take_value(&mut args, "--session", "").unwrap().as_deref(),
+            Some("w")
+        );
+        assert_eq!(args, ["ort", "-m", "a/b", "what", "does", "--trace", "do"]);
+
+        let mut args: Vec<String> = ["ort", "--", "--offline", "please"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(!take_flag(&mut args, "--offline"));
+        // After the command word, up to its first argument
+        let mut args: Vec<String> = ["ort", "panel", "--offline", "is", "--trace", "on?"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(take_flag(&mut args, "--offline"));
+        assert!(!take_flag(&mut args, "--trace"));
+        let mut args: Vec<String> = ["ort", "agent", "--offline", "--trace", "hi"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(take_flag(&mut args, "--offline"));
+        assert!(take_flag(&mut args, "--trace"));
+        assert_eq!(args, ["ort", "agent", "hi"]);
+    }
+
+    #[test]
+    fn pre_request_hook_replaces_body() {
+        let cfg = Cfg {
+            pre_request: Some("sed s/Hello/Bonjour/".to_string()),
+            hook_timeout_ms: 5000,
+            ..Cfg::default()
+        };
+        let body = run_pre_request_hook(&cfg, r#"{"content":"Hello"}"#.to_string()).unwrap();
+        assert_eq!(body, r#"{"content":"Bonjour"}"#);
+    }
+
+    #[test]
+    fn pre_request_hook_can_block() {
+        let cfg = Cfg {
+            pre_request: Some("exit 1".to_string()),
+            hook_timeout_ms: 5000,
+            ..Cfg::default()
+        };
+        let res = run_pre_request_hook(&cfg, "{}".to_string());
+        assert!(matches!(
+            res,
+            Err(OrtError {
+                kind: ErrorKind::HookFailed,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn post_response_payload_is_json() {
+        let mut payload: Vec<u8> = Vec::new();
+        post_response_payload(&mut payload, "m", "p", "say \"hi\"").unwrap();
+        assert_eq!(
+            str::from_utf8(&payload).unwrap(),
+            r#"{"model":"m","provider":"p","content":"say \"hi\""}"#
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! A 402 means the account is out of credits. Say what's left, from the
+//! credits API, and which free models could answer instead.
+
+extern crate alloc;
+use alloc::string::String;
+
+use crate::common::json_parser::{JsonField, autoparser};
+use crate::common::models;
+use crate::utils;
+
+/// How many `:free` models to suggest
+const MAX_FREE_SUGGESTIONS: usize = 3;
+
+const TOP_UP_URL: &str = "https://openrouter.ai/settings/credits";
+
+/// In dollars
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(in crate::input) struct Credits {
+    pub total: f64,
+    pub usage: f64,
+}
+
+impl Credits {
+    /// `{"data":{"total_credits":10,"total_usage":9.98}}`
+    pub(in crate::input) fn from_json(json: &str) -> Option<Credits> {
+        let mut outer = [JsonField::new_raw("data")];
+        autoparser(json, &mut outer).ok()?;
+        let data = outer[0].get_raw()?;
+        let mut fields = [
+            JsonField::new_float("total_credits"),
+            JsonField::new_float("total_usage"),
+        ];
+        autoparser(&data, &mut fields).ok()?;
+        Some(Credits {
+            total: fields[0].get_float()? as f64,
+            usage: fields[1].get_float()? as f64,
+        })
+    }
+}
+
+/// What to tell the user after a 402 for `model`. `credits` if the API
+/// answered, `models_cache` if `ort list` has run.
+pub(in crate::input) fn advice(
+    credits: Option<Credits>,
+    models_cache: Option<&str>,
+    model: &str,
+) -> String {
+    let mut msg = String::from("Out of credits");
+    if let Some(c) = credits {
+        msg.push_str(": $");
+        msg.push_str(&utils::float_to_string(c.total - c.usage, 2));
+        msg.push_str(" left of $");
+        msg.push_str(&utils::float_to_string(c.total, 2));
+    }
+    msg.push_str(". Top up at ");
+    msg.push_str(TOP_UP_URL);
+    msg.push('\n');
+    match models_cache {
+        Some(cache) => {
+            let free = models::free_variants(cache, model, MAX_FREE_SUGGESTIONS);
+            if !free.is_empty() {
+                msg.push_str("Free instead: ");
+                msg.push_str(&free.join(", "));
+                msg.push('\n');
+            }
+        }
+        None => msg.push_str("Run `ort list` to find free models, they end in `:free`.\n"),
+    }
+    msg.push_str("Set `free_fallback` in the config to switch to one automatically.\n");
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_credits() {
+        let json = r#"{"data":{"total_credits":10,"total_usage":9.985}}"#;
+        let c = Credits::from_json(json).unwrap();
+        assert!((c.total - 10.0).abs() < 1e-6 && (c.usage - 9.985).abs() < 1e-6);
+        assert!(Credits::from_json(r#"{"error":{"code":401}}"#).is_none());
+    }
+
+    #[test]
+    fn advice_message() {
+        let cache = "acme/big:free\t\t\t\t0\t0\nacme/big\t\t\t\t0.1\t0.1\n";
+        let credits = Credits {
+            total: 10.0,
+            usage: 9.5,
+        };
+        assert_eq!(
+            advice(Some(credits), Some(cache), "acme/big"),
+            "Out of credits: $0.50 left of $10.00. Top up at https://openrouter.ai/settings/credits\n\
+             Free instead: acme/big:free\n\
+             Set `free_fallback` in the config to switch to one automatically.\n"
+        );
+        let msg = advice(None, None, "acme/big");
+        assert!(msg.starts_with("Out of credits. Top up"), "{msg}");
+        assert!(msg.contains("Run `ort list`"), "{msg}");
+    }
+}
@@ -0,0 +1,395 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `ort doctor`: check everything ort needs, one line per check, with a hint
+//! for each failure.
+
+use core::ffi::c_int;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::cli::Env;
+use crate::common::config::{self, Cfg, ConfigSource};
+use crate::common::{buf_read, cpu, paths, resolver, time};
+use crate::{ErrorKind, OrtResult, TlsStream, Write, http, syscall, utils};
+
+/// More than this between our clock and the server's is a problem
+const MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+#[derive(Default)]
+struct Report {
+    out: String,
+    failed: usize,
+}
+
+impl Report {
+    fn line(&mut self, status: &str, name: &str, detail: &str) {
+        self.out.push_str(status);
+        self.out.push_str(name);
+        self.out.push_str(": ");
+        self.out.push_str(detail);
+        self.out.push('\n');
+    }
+
+    fn pass(&mut self, name: &str, detail: &str) {
+        self.line("ok    ", name, detail);
+    }
+
+    fn skip(&mut self, name: &str, detail: &str) {
+        self.line("skip  ", name, detail);
+    }
+
+    fn fail(&mut self, name: &str, detail: &str, hint: &str) {
+        self.line("FAIL  ", name, detail);
+        self.out.push_str("      hint: ");
+        self.out.push_str(hint);
+        self.out.push('\n');
+        self.failed += 1;
+    }
+}
+
+/// Exit code is 1 if any check failed
+pub fn run<W: Write>(
+    env: &Env,
+    source: ConfigSource,
+    is_offline: bool,
+    w: &mut W,
+) -> OrtResult<c_int> {
+    let mut r = Report::default();
+
+    let mut cfg = check_config(&mut r, env, source);
+    if source != ConfigSource::Empty {
+        check_project_config(&mut r, env, &mut cfg);
+    }
+    let api_key = check_api_key(&mut r, env, &cfg);
+    check_cpu(&mut r);
+    paths::set_from_config(&cfg);
+    check_dir(&mut r, env, "cache dir", paths::cache_dir, "ORT_CACHE_DIR");
+    check_dir(&mut r, env, "state dir", paths::state_dir, "ORT_STATE_DIR");
+    if is_offline {
+        r.skip("network", "--offline");
+    } else {
+        check_network(&mut r, &cfg, api_key.as_deref());
+    }
+
+    if r.failed == 0 {
+        r.out.push_str("\nAll checks passed\n");
+    } else {
+        r.out.push('\n');
+        r.out.push_str(&utils::num_to_string(r.failed));
+        r.out.push_str(" check(s) failed\n");
+    }
+    w.write_str(&r.out)?;
+    w.flush()?;
+    Ok(if r.failed == 0 { 0 } else { 1 })
+}
+
+/// Only reported if there is one
+fn check_project_config(r: &mut Report, env: &Env, cfg: &mut Cfg) {
+    let Some(pwd) = env.PWD else {
+        return;
+    };
+    let (dir, contents) = match config::find_project_file(pwd) {
+        Ok(Some(found)) => found,
+        Ok(None) => return,
+        Err(err) => {
+            r.fail(
+                "project config",
+                &err.as_string(),
+                "check the permissions of .ort.cfg",
+            );
+            return;
+        }
+    };
+    match cfg.overlay(&dir, &contents) {
+        Ok(()) => {
+            let msg = dir + "/" + config::PROJECT_FILENAME + " parsed";
+            r.pass("project config", &msg);
+        }
+        Err(err) => r.fail(
+            "project config",
+            &err.as_string(),
+            "fix that line, a project config can only set prompt options, see the README",
+        ),
+    }
+}
+
+fn check_config(r: &mut Report, env: &Env, source: ConfigSource) -> Cfg {
+    let filename = match source {
+        ConfigSource::Named(filename) | ConfigSource::Path(filename) => filename,
+        ConfigSource::Empty => {
+            r.pass("config", "--no-config, using defaults");
+            return Cfg::default();
+        }
+    };
+    let profile = env.ORT_PROFILE.filter(|p| !p.is_empty());
+    // Relative paths in it are relative to where it is, as for a prompt
+    let path = source.path(env).ok().flatten();
+    let dir = path
+        .as_deref()
+        .and_then(|p| p.rsplit_once('/'))
+        .map(|(dir, _)| dir);
+    match source.read(env) {
+        Ok(None) => {
+            r.pass(
+                "config",
+                &(filename.to_string() + " not found, using defaults"),
+            );
+            Cfg::default()
+        }
+        Ok(Some(s)) => match Cfg::from_str_in(&s, dir, profile) {
+            Ok(cfg) if config::migrate(&s).is_some() => {
+                let msg = filename.to_string()
+                    + " parsed, an older format that the next run upgrades (keeping a backup)";
+                r.pass("config", &msg);
+                cfg
+            }
+            Ok(cfg) => {
+                let mut msg = filename.to_string() + " parsed";
+                if let Some(profile) = profile {
+                    msg = msg + ", profile " + profile;
+                }
+                r.pass("config", &msg);
+                cfg
+            }
+            Err(err) => {
+                r.fail(
+                    "config",
+                    &err.as_string(),
+                    "fix that line in ${XDG_CONFIG_HOME}/ort.cfg, see the README for the format",
+                );
+                Cfg::default()
+            }
+        },
+        Err(err) => {
+            r.fail(
+                "config",
+                &err.as_string(),
+                "check HOME, XDG_CONFIG_HOME or ORT_CONFIG_DIR, and the permissions of ort.cfg",
+            );
+            Cfg::default()
+        }
+    }
+}
+
+fn check_api_key(r: &mut Report, env: &Env, cfg: &Cfg) -> Option<String> {
+    let (key, source) = match (env.OPENROUTER_API_KEY, cfg.get_api_key()) {
+        (Some(k), _) if !k.is_empty() => (k, "OPENROUTER_API_KEY"),
+        (_, Some(k)) => (k, "ort.cfg"),
+        _ => {
+            r.fail(
+                "api key",
+                "not set",
+                "export OPENROUTER_API_KEY, or add `api_key: sk-or-...` to ort.cfg",
+            );
+            return None;
+        }
+    };
+    let detail = utils::mask_secret(key) + " from " + source;
+    // Other servers have their own key formats
+    let is_openrouter = cfg.base_url.contains("openrouter.ai");
+    if is_openrouter && (!key.starts_with("sk-or-") || key.contains(char::is_whitespace)) {
+        r.fail(
+            "api key",
+            &(detail + " does not look like an OpenRouter key"),
+            "keys start with sk-or-, copy it again from https://openrouter.ai/keys",
+        );
+    } else {
+        r.pass("api key", &detail);
+    }
+    Some(key.to_string())
+}
+
+/// Without these the TLS code uses slower portable versions
+fn check_cpu(r: &mut Report) {
+    let mut missing = Vec::new();
+    for (has, name) in [
+        (cpu::has_aes(), "AES-NI"),
+        (cpu::has_pclmul(), "PCLMULQDQ"),
+        (cpu::has_sha(), "SHA-NI"),
+    ] {
+        if !has {
+            missing.push(name);
+        }
+    }
+    if missing.is_empty() {
+        r.pass("cpu", "AES-NI, PCLMULQDQ and SHA-NI");
+    } else {
+        r.pass(
+            "cpu",
+            &("no ".to_string() + &missing.join(", ") + ", using slower portable code"),
+        );
+    }
+}
+
+/// `find_dir` is `paths::cache_dir` or `paths::state_dir`, `var` its
+/// environment variable
+fn check_dir(
+    r: &mut Report,
+    env: &Env,
+    name: &str,
+    find_dir: fn(&Env) -> OrtResult<String>,
+    var: &str,
+) {
+    let dir = match find_dir(env) {
+        Ok(dir) => dir,
+        Err(err) => {
+            r.fail(name, &err.as_string(), &("set HOME or ".to_string() + var));
+            return;
+        }
+    };
+    let is_writable = CString::new(dir.as_str())
+        .map(|c| syscall::access(c.as_ptr(), syscall::W_OK) == 0)
+        .unwrap_or(false);
+    if is_writable {
+        r.pass(name, &(dir + " is writable"));
+    } else {
+        r.fail(
+            name,
+            &(dir + " is not writable"),
+            &("fix its permissions, or point ".to_string() + var + " somewhere writable"),
+        );
+    }
+}
+
+/// DNS, TCP, TLS then one small request. Stops at the first failure, the
+/// later steps depend on it.
+fn check_network(r: &mut Report, cfg: &Cfg, api_key: Option<&str>) {
+    let (host, port, base_path) = http::split_url(&cfg.base_url);
+
+    let ips: Vec<Ipv4Addr> = if cfg.dns.is_empty() {
+        match unsafe { resolver::resolve(host) } {
+            Ok(ips) if !ips.is_empty() => {
+                r.pass("dns", &(host.to_string() + " is " + &ips[0].to_string()));
+                ips
+            }
+            Ok(_) => {
+                r.fail(
+                    "dns",
+                    &(host.to_string() + " has no addresses"),
+                    "check /etc/resolv.conf, or set `dns: <ip>` in ort.cfg",
+                );
+                return;
+            }
+            Err(err) => {
+                let hint = if matches!(err.kind, ErrorKind::NoNetwork) {
+                    "no network, check your connection"
+                } else {
+                    "check /etc/resolv.conf, or set `dns: <ip>` in ort.cfg"
+                };
+                r.fail("dns", &err.as_string(), hint);
+                return;
+            }
+        }
+    } else {
+        let Ok(ips) = cfg.dns.iter().map(|a| a.parse::<Ipv4Addr>()).collect() else {
+            r.fail(
+                "dns",
+                "invalid address in ort.cfg",
+                "`dns:` takes IPv4 addresses",
+            );
+            return;
+        };
+        r.pass("dns", "using the addresses in ort.cfg");
+        ips
+    };
+
+    let addrs = ips
+        .into_iter()
+        .map(|ip| SocketAddr::new(IpAddr::V4(ip), port))
+        .collect();
+    let endpoint = host.to_string() + ":" + &utils::num_to_string(port);
+    let tcp = match http::connect(addrs, None) {
+        Ok(tcp) => {
+            r.pass("tcp", &(endpoint.clone() + " reachable"));
+            tcp
+        }
+        Err(err) => {
+            r.fail(
+                "tcp",
+                &(endpoint + " " + &err.as_string()),
+                "a firewall or proxy may block outbound connections to that port",
+            );
+            return;
+        }
+    };
+
+    let mut tls = match TlsStream::connect(tcp, host) {
+        Ok(tls) => {
+            r.pass("tls", "handshake done");
+            tls
+        }
+        Err(err) => {
+            r.fail(
+                "tls",
+                &err.as_string(),
+                "ort only speaks TLS 1.3. A proxy intercepting TLS would cause this.",
+            );
+            return;
+        }
+    };
+
+    // Any response has a Date header for the clock check, and /key
+    // tells us if the API key is accepted.
+    let url = base_path.to_string() + "/key";
+    if let Err(err) = http::send_get(&mut tls, api_key.unwrap_or_default(), host, &url) {
+        r.fail(
+            "http",
+            &err.as_string(),
+            "try again, or use --dump-wire to see more",
+        );
+        return;
+    }
+    let mut reader = buf_read::OrtBufReader::new(tls);
+    let header = match http::parse_header(&mut reader) {
+        Ok(header) => header,
+        Err(err) => {
+            r.fail(
+                "http",
+                err.status_line(),
+                "try again, or use --dump-wire to see more",
+            );
+            return;
+        }
+    };
+
+    if api_key.is_some() {
+        match header.status {
+            200 => r.pass("api key", "accepted by the server"),
+            401 | 403 => r.fail(
+                "api key",
+                &("rejected: ".to_string() + &header.status_line),
+                "create a new key at https://openrouter.ai/keys",
+            ),
+            // Other servers may not have /key
+            _ => r.skip(
+                "api key",
+                &("not verified: ".to_string() + &header.status_line),
+            ),
+        }
+    }
+
+    let date = header.get("date").and_then(time::parse_http_date);
+    let Some(server_time) = date else {
+        r.skip("clock", "no Date header in the response");
+        return;
+    };
+    let skew = server_time.abs_diff(syscall::unix_time());
+    let detail = utils::num_to_string(skew) + "s from " + host;
+    if skew > MAX_CLOCK_SKEW_SECS {
+        r.fail(
+            "clock",
+            &detail,
+            "sync your clock, e.g. `timedatectl set-ntp true`",
+        );
+    } else {
+        r.pass("clock", &detail);
+    }
+}
@@ -0,0 +1,241 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! C ABI, so editors and tools in other languages can embed ort instead of
+//! running it. The `ort-ffi` crate builds this into libort.so / libort.a and
+//! has the header, `ort-ffi/include/ort.h`. Keep the two in sync.
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec;
+
+use core::ffi::{CStr, c_char, c_int, c_void};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+use crate::common::config::Cfg;
+use crate::input::prompt::ActivePrompt;
+use crate::transport::AsFd as _;
+use crate::{ErrorKind, OrtResult, PromptOpts, Response, ThinkEvent};
+use crate::{ort_error, syscall};
+
+/// Callback `event` values. START is once the request is sent, empty text,
+/// so another thread can get `req` to cancel it.
+pub const ORT_START: c_int = 1;
+pub const ORT_CONTENT: c_int = 2;
+pub const ORT_REASONING: c_int = 3;
+pub const ORT_STATS: c_int = 4;
+pub const ORT_ERROR: c_int = 5;
+
+/// `status` after `ort_cancel`, like a shell's Ctrl-C. The others are the
+/// CLI's exit codes.
+pub const ORT_CANCELLED: c_int = 130;
+
+/// Called with each piece of the answer as it streams. `text` is `len` bytes
+/// plus a NUL, and only valid during the call.
+pub type OrtCallback = Option<
+    unsafe extern "C" fn(
+        req: *mut OrtRequest,
+        event: c_int,
+        text: *const c_char,
+        len: usize,
+        user_data: *mut c_void,
+    ),
+>;
+
+/// One running `ort_query`, opaque to C
+pub struct OrtRequest {
+    is_cancelled: AtomicBool,
+    // Our own dup of the socket once connected, -1 before and after. Whoever
+    // swaps it out closes it, so `ort_cancel` never shuts down a reused fd.
+    fd: AtomicI32,
+}
+
+impl OrtRequest {
+    /// Take the socket dup, if nobody else has
+    fn take_fd(&self) -> Option<c_int> {
+        let fd = self.fd.swap(-1, Ordering::AcqRel);
+        (fd >= 0).then_some(fd)
+    }
+}
+
+/// Send `prompt` to `model` with an optional `system` prompt. `config` is
+/// ort.cfg text, e.g. "base_url: ...", or NULL for the defaults. NULL
+/// `api_key` or `model` come from `config`. Blocks until the answer is
+/// complete, calling `cb` (may be NULL) as it streams. Returns the whole
+/// answer, free it with `ort_free`, or NULL on error. `status` (may be NULL)
+/// gets 0 or why it failed.
+///
+/// # Safety
+/// The strings must be NUL terminated UTF-8, or NULL where allowed.
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn ort_query(
+    config: *const c_char,
+    api_key: *const c_char,
+    model: *const c_char,
+    system: *const c_char,
+    prompt: *const c_char,
+    cb: OrtCallback,
+    user_data: *mut c_void,
+    status: *mut c_int,
+) -> *mut c_char {
+    let req = OrtRequest {
+        is_cancelled: AtomicBool::new(false),
+        fd: AtomicI32::new(-1),
+    };
+    // Only atomics, so sharing it with the callback is fine
+    let req_ptr = ptr::from_ref(&req).cast_mut();
+    let mut emit = |event: c_int, text: &str| {
+        let Some(cb) = cb else {
+            return;
+        };
+        let text = CString::new(text.replace('\0', "_")).unwrap_or_default();
+        unsafe { cb(req_ptr, event, text.as_ptr(), text.count_bytes(), user_data) };
+    };
+
+    let args = [config, api_key, model, system, prompt];
+    let res = unsafe { query(&req, args, &mut emit) };
+    let (answer, code) = match res {
+        Ok(answer) => (CString::new(answer.replace('\0', "_")).ok(), 0),
+        Err(_) if req.is_cancelled.load(Ordering::Acquire) => (None, ORT_CANCELLED),
+        Err(err) => {
+            emit(ORT_ERROR, &err.as_string());
+            (None, err.kind.exit_code())
+        }
+    };
+    if !status.is_null() {
+        unsafe { *status = code };
+    }
+    answer.map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Stop `req` soon, from its callback or another thread. Only while its
+/// `ort_query` is running.
+///
+/// # Safety
+/// `req` must be the one passed to the callback, or NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ort_cancel(req: *mut OrtRequest) {
+    let Some(req) = (unsafe { req.as_ref() }) else {
+        return;
+    };
+    req.is_cancelled.store(true, Ordering::Release);
+    if let Some(fd) = req.take_fd() {
+        // Wakes a read blocked waiting for the model. The dup shares the
+        // socket, so this reaches the read.
+        syscall::shutdown(fd, syscall::SHUT_RDWR);
+        syscall::close(fd);
+    }
+}
+
+/// Free an answer from `ort_query`. NULL is fine.
+///
+/// # Safety
+/// `s` must come from `ort_query`, and only be freed once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ort_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// `args` are ort_query's config, api_key, model, system and prompt
+unsafe fn query<F: FnMut(c_int, &str)>(
+    req: &OrtRequest,
+    args: [*const c_char; 5],
+    emit: &mut F,
+) -> OrtResult<String> {
+    let [config, api_key, model, system, prompt] = args.map(|s| unsafe { to_str(s) });
+    let cfg = match config? {
+        Some(s) => Cfg::from_str(s)?,
+        None => Cfg::default(),
+    };
+    let api_key = api_key?.or(cfg.get_api_key());
+    let (Some(api_key), Some(prompt)) = (api_key, prompt?) else {
+        return Err(ort_error(
+            ErrorKind::InvalidArguments,
+            "api_key and prompt are required",
+        ));
+    };
+    let mut opts = PromptOpts {
+        prompt: Some(prompt.to_string()),
+        models: model?.map(|m| vec![m.to_string()]).unwrap_or_default(),
+        system: system?.map(|s| s.to_string()),
+        ..Default::default()
+    };
+    opts.merge(&cfg)?;
+    let messages = opts.messages()?;
+
+    let mut active_prompt =
+        ActivePrompt::new(api_key.to_string(), &cfg, opts, messages, vec![], 0, None)?;
+    active_prompt.start()?;
+    let fd = syscall::fcntl(active_prompt.as_fd(), syscall::F_DUPFD_CLOEXEC, 0);
+    if fd >= 0 {
+        req.fd.store(fd, Ordering::Release);
+    }
+    emit(ORT_START, "");
+
+    let mut answer = String::new();
+    let res = stream(req, &mut active_prompt, &mut answer, emit);
+    if let Some(fd) = req.take_fd() {
+        syscall::close(fd);
+    }
+    res?;
+
+    let stats = active_prompt.stop();
+    emit(ORT_STATS, &stats.as_string());
+    if let Some(msg) = active_prompt.refusal() {
+        emit(ORT_ERROR, &msg);
+        return Err(ort_error(ErrorKind::ModelRefused, "refused or filtered"));
+    }
+    Ok(answer)
+}
+
+fn stream<F: FnMut(c_int, &str)>(
+    req: &OrtRequest,
+    active_prompt: &mut ActivePrompt,
+    answer: &mut String,
+    emit: &mut F,
+) -> OrtResult<()> {
+    let cancelled = || ort_error(ErrorKind::Other, "cancelled");
+    while !req.is_cancelled.load(Ordering::Acquire) {
+        let Some(events) = active_prompt.next()? else {
+            break;
+        };
+        for event in events {
+            match event {
+                Response::Content(text) => {
+                    emit(ORT_CONTENT, &text);
+                    answer.push_str(&text);
+                }
+                Response::Think(ThinkEvent::Content(text)) => emit(ORT_REASONING, &text),
+                Response::Error(msg) => emit(ORT_ERROR, &msg),
+                _ => {}
+            }
+            if req.is_cancelled.load(Ordering::Acquire) {
+                return Err(cancelled());
+            }
+        }
+    }
+    // A cancel during a read ends the stream early, it isn't EOF
+    if req.is_cancelled.load(Ordering::Acquire) {
+        return Err(cancelled());
+    }
+    Ok(())
+}
+
+/// None for NULL
+unsafe fn to_str<'a>(s: *const c_char) -> OrtResult<Option<&'a str>> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map(Some)
+        .map_err(|_| ort_error(ErrorKind::InvalidArguments, "not UTF-8"))
+}
@@ -0,0 +1,79 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Entry points for the fuzz targets in `fuzz/`. Each takes whatever bytes
+//! the fuzzer made up. Errors are fine, a panic is a bug.
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::common::buf_read::OrtBufReader;
+use crate::common::config::Cfg;
+use crate::common::io::ReadLine as _;
+use crate::input::prompt::ActivePrompt;
+use crate::input::replay::ReplaySource;
+use crate::transport::chunked;
+use crate::{ChatCompletionsResponse, PromptOpts};
+
+/// One SSE `data:` payload
+pub fn chat_completions(data: &[u8]) {
+    if let Ok(json) = str::from_utf8(data) {
+        let _ = ChatCompletionsResponse::from_json(json);
+    }
+}
+
+/// The `opts` of a last-<session>.json
+pub fn prompt_opts(data: &[u8]) {
+    if let Ok(json) = str::from_utf8(data) {
+        let _ = PromptOpts::from_json(json);
+    }
+}
+
+/// A transfer encoding chunked body, line by line as the SSE reader takes it
+pub fn chunked(data: &[u8]) {
+    let source = ReplaySource::new(data.to_vec(), vec![], true);
+    let mut lines = chunked::read::<_, 64>(OrtBufReader::new(source));
+    let mut buf = String::new();
+    while let Ok(n) = lines.read_line(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        buf.clear();
+    }
+}
+
+/// A whole captured response, as `ort replay` plays it: the HTTP header if
+/// there is one, chunked or not, then the SSE events and their JSON.
+pub fn sse(data: &[u8]) {
+    let cfg = Cfg {
+        // Small, so the fuzzer reaches the skipping
+        max_line_bytes: Some(4096),
+        ..Cfg::default()
+    };
+    let Ok(mut active_prompt) = ActivePrompt::new(
+        String::new(),
+        &cfg,
+        PromptOpts::default(),
+        vec![],
+        Vec::new(),
+        0,
+        None,
+    ) else {
+        return;
+    };
+    let source = ReplaySource::new(data.to_vec(), vec![], true);
+    if active_prompt
+        .start_replay(source, data.starts_with(b"HTTP/"))
+        .is_err()
+    {
+        return;
+    }
+    // After an error the stream is over and the next call says so
+    while !matches!(active_prompt.next(), Ok(None)) {}
+    active_prompt.stop();
+}
@@ -0,0 +1,622 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `ort history import <file|->` and `ort history export [file]`: move a
+//! conversation between ort's last file and the OpenAI messages format.
+//! `ort history stats`: what the saved conversations take up, and pruning.
+//! `ort history list`: the saved conversations by title, which `title_model`
+//! writes the first time each is listed.
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::cli::Env;
+use crate::common::config::Cfg;
+use crate::common::data::Content;
+use crate::common::models::{self, Price};
+use crate::common::stats::push_cost;
+use crate::common::tokens::{self, Family};
+use crate::common::{dir, file, paths};
+use crate::input::args::{HistoryAction, HistoryOpts};
+use crate::input::prompt;
+use crate::input::stdin;
+use crate::output::from_json;
+use crate::output::last_writer;
+use crate::utils::format_size;
+use crate::{
+    Context as _, ErrorKind, LastData, Message, OrtResult, PromptOpts, Role, Verbosity, Write,
+};
+use crate::{ort_error, syscall, utils};
+
+/// `ort history list` titles, one `<file>\t<key>\t<title>` line each, in the state dir
+const TITLES_FILE: &str = "titles.tsv";
+
+const TITLE_PROMPT: &str =
+    "Write a title of at most five words for this conversation. Reply with the title only.";
+
+/// How much of the first prompt and answer the title model sees
+const TITLE_SOURCE_BYTES: usize = 2000;
+
+const MAX_TITLE_WORDS: usize = 5;
+
+pub fn run<W: Write>(
+    env: &Env,
+    cfg: &Cfg,
+    opts: HistoryOpts,
+    is_offline: bool,
+    w: &mut W,
+) -> OrtResult<()> {
+    match opts.action {
+        HistoryAction::Import(source) => {
+            let last = import_file(&source, cfg)?;
+            let mut f = last_writer::create_last_file(env)?;
+            last.to_json_writer(&mut f)?;
+            f.persist()?;
+
+            let mut msg = String::from("Imported ");
+            msg.push_str(&utils::num_to_string(last.messages.len()));
+            msg.push_str(" messages, continue with: ort -c \"next prompt\"\n");
+            w.write_str(&msg)?;
+        }
+        HistoryAction::Export(target) => {
+            let last = prompt::load_last_data(env)?;
+            let mut out = String::with_capacity(4096);
+            from_json::write_openai_messages(&last.messages, &mut out)?;
+            out.push('\n');
+            match target {
+                Some(path) => {
+                    let c_path = CString::new(path)
+                        .map_err(|_| ort_error(ErrorKind::Other, "Path contains nul byte"))?;
+                    let mut f = unsafe { file::File::create(c_path.as_bytes_with_nul())? };
+                    f.write_str(&out)?;
+                    f.flush()?;
+                }
+                None => {
+                    w.write_str(&out)?;
+                }
+            }
+        }
+        HistoryAction::Stats { prune_older_than } => {
+            let out = stats(env, cfg, prune_older_than)?;
+            w.write_str(&out)?;
+        }
+        HistoryAction::List => {
+            // History doesn't need the key otherwise, so no key means no new titles
+            let api_key = env
+                .OPENROUTER_API_KEY
+                .filter(|k| !k.is_empty())
+                .or(cfg.get_api_key())
+                .filter(|_| !is_offline);
+            let out = list(env, cfg, api_key)?;
+            w.write_str(&out)?;
+        }
+    }
+    w.flush()
+}
+
+/// A title `ort history list` made. `key` says which conversation it was
+/// for, a pane's file is reused for the next one.
+struct Title {
+    name: String,
+    key: String,
+    title: String,
+}
+
+fn list(env: &Env, cfg: &Cfg, api_key: Option<&str>) -> OrtResult<String> {
+    let state_dir = paths::state_dir(env)?;
+    let now = syscall::unix_time();
+    let c_dir = CString::new(state_dir.as_str())
+        .map_err(|_| ort_error(ErrorKind::DirOpenFailed, "Null byte in state dir"))?;
+    // (name, age in seconds, the conversation if it parses)
+    let mut saved: Vec<(String, u64, Option<LastData>)> = Vec::new();
+    for name in dir::DirFiles::new(c_dir.as_c_str())? {
+        if !name.starts_with("last-") || !name.ends_with(".json") {
+            continue;
+        }
+        let path = state_dir.clone() + "/" + &name;
+        let c_path = CString::new(path.as_str())
+            .map_err(|_| ort_error(ErrorKind::FileStatFailed, "Null byte in file name"))?;
+        let age_secs = now.saturating_sub(file::last_modified(c_path.as_c_str())?.as_secs());
+        let last = utils::filename_read_to_string(&path)
+            .ok()
+            .and_then(|json| LastData::from_json(&json).ok());
+        saved.push((name, age_secs, last));
+    }
+    let mut out = String::with_capacity(1024);
+    if saved.is_empty() {
+        out.push_str("No saved conversations in ");
+        out.push_str(&state_dir);
+        out.push('\n');
+        return Ok(out);
+    }
+    // Newest first
+    saved.sort_by_key(|(_, age_secs, _)| *age_secs);
+
+    let titles_path = state_dir + "/" + TITLES_FILE;
+    let mut titles = utils::filename_read_to_string(&titles_path)
+        .map(|s| parse_titles(&s))
+        .unwrap_or_default();
+    if let (Some(model), Some(api_key)) = (cfg.title_model.as_deref(), api_key) {
+        add_titles(cfg, api_key, model, &saved, &mut titles, &titles_path)?;
+    }
+
+    for (name, age_secs, last) in &saved {
+        out.push_str(name);
+        out.push_str("  ");
+        out.push_str(&format_age(*age_secs));
+        out.push_str(" ago  ");
+        let Some(last) = last else {
+            out.push_str("unreadable\n");
+            continue;
+        };
+        let key = title_key(last);
+        match titles
+            .iter()
+            .find(|t| &t.name == name && Some(&t.key) == key.as_ref())
+        {
+            Some(t) => out.push_str(&t.title),
+            // No title_model, or it failed: the start of the first prompt
+            None => {
+                let prompt = first_text(&last.messages, Role::User).unwrap_or_default();
+                let mut snippet: String = prompt.split_whitespace().collect::<Vec<_>>().join(" ");
+                if snippet.len() > 50 {
+                    snippet.truncate(snippet.floor_char_boundary(50));
+                    snippet.push_str("...");
+                }
+                out.push('"');
+                out.push_str(&snippet);
+                out.push('"');
+            }
+        }
+        out.push_str("  ");
+        out.push_str(&plural(last.messages.len(), "message"));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Ask `model` for a title for every conversation that doesn't have one,
+/// all at once, and save them with the others.
+fn add_titles(
+    cfg: &Cfg,
+    api_key: &str,
+    model: &str,
+    saved: &[(String, u64, Option<LastData>)],
+    titles: &mut Vec<Title>,
+    titles_path: &str,
+) -> OrtResult<()> {
+    // (file name, key) of each prompt
+    let mut wanted = Vec::new();
+    let mut prompts = Vec::new();
+    for (name, _, last) in saved {
+        let Some((last, key)) = last.as_ref().and_then(|l| Some((l, title_key(l)?))) else {
+            continue;
+        };
+        if titles.iter().any(|t| &t.name == name && t.key == key) {
+            continue;
+        }
+        prompts.push(title_prompt(cfg, model, last)?);
+        wanted.push((name.clone(), key));
+    }
+    if prompts.is_empty() {
+        return Ok(());
+    }
+    prompt::run_parallel(api_key, cfg, prompts, None, |i, out| {
+        let (name, key) = &wanted[i];
+        match (out.error(), clean_title(out.contents())) {
+            (None, Some(title)) => {
+                titles.retain(|t| &t.name != name);
+                titles.push(Title {
+                    name: name.clone(),
+                    key: key.clone(),
+                    title,
+                });
+            }
+            (err, _) => {
+                let msg = "No title for ".to_string()
+                    + name
+                    + ": "
+                    + err.unwrap_or("empty answer")
+                    + "\n";
+                utils::warn(&msg);
+            }
+        }
+    })?;
+
+    // Forget the conversations that are gone
+    titles.retain(|t| saved.iter().any(|(name, _, _)| *name == t.name));
+    let mut contents = String::with_capacity(titles.len() * 64);
+    for t in titles.iter() {
+        contents.push_str(&t.name);
+        contents.push('\t');
+        contents.push_str(&t.key);
+        contents.push('\t');
+        contents.push_str(&t.title);
+        contents.push('\n');
+    }
+    let mut f = last_writer::LastFile::create(titles_path.to_string())?;
+    f.write_str(&contents)?;
+    f.persist()
+}
+
+fn parse_titles(s: &str) -> Vec<Title> {
+    s.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            Some(Title {
+                name: parts.next()?.to_string(),
+                key: parts.next()?.to_string(),
+                title: parts.next().filter(|t| !t.is_empty())?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Which conversation a title is for: a hash of its first prompt.
+/// None if there's no text to make a title from.
+fn title_key(last: &LastData) -> Option<String> {
+    let prompt = first_text(&last.messages, Role::User)?;
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in prompt.bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Some(utils::num_to_string(hash))
+}
+
+fn title_prompt(
+    cfg: &Cfg,
+    model: &str,
+    last: &LastData,
+) -> OrtResult<(PromptOpts, Vec<Message>, usize)> {
+    let mut text = String::with_capacity(2 * TITLE_SOURCE_BYTES + 32);
+    for (role, label) in [(Role::User, "User: "), (Role::Assistant, "\n\nAssistant: ")] {
+        if let Some(s) = first_text(&last.messages, role) {
+            text.push_str(label);
+            text.push_str(&s[..s.floor_char_boundary(TITLE_SOURCE_BYTES)]);
+        }
+    }
+    let mut opts = PromptOpts {
+        prompt: Some(text),
+        models: vec![model.to_string()],
+        system: Some(TITLE_PROMPT.to_string()),
+        verbosity: Some(Verbosity::Quiet),
+        user: cfg.user_id.clone(),
+        ..Default::default()
+    };
+    let messages = opts.messages()?;
+    Ok((opts, messages, 0))
+}
+
+/// The first text of the first message from `role`
+fn first_text(messages: &[Message], role: Role) -> Option<&str> {
+    messages.iter().filter(|m| m.role == role).find_map(|m| {
+        m.content.iter().find_map(|c| match c {
+            Content::Text(t) if !t.trim().is_empty() => Some(t.as_str()),
+            _ => None,
+        })
+    })
+}
+
+/// One line of at most MAX_TITLE_WORDS words, without the quotes or
+/// markdown models like to add
+fn clean_title(answer: &str) -> Option<String> {
+    let line = answer.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line.trim_start_matches(['#', ' ']);
+    let words: Vec<&str> = line
+        .split_whitespace()
+        .map(|w| w.trim_matches(['"', '\'', '*', '`']))
+        .filter(|w| !w.is_empty())
+        .take(MAX_TITLE_WORDS)
+        .collect();
+    let title = words.join(" ");
+    let title = title.trim_end_matches(['.', ':']);
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// One saved conversation, a last-<pane>.json
+struct Saved {
+    name: String,
+    size: u64,
+    age_secs: u64,
+    // None if the file doesn't parse
+    summary: Option<Summary>,
+}
+
+struct Summary {
+    model: String,
+    num_messages: usize,
+    tokens: usize,
+    // None without a cached price for the model
+    cost_in_cents: Option<f64>,
+}
+
+fn stats(env: &Env, cfg: &Cfg, prune_older_than: Option<u64>) -> OrtResult<String> {
+    let state_dir = paths::state_dir(env)?;
+    let models_cache = models::load(env);
+    let now = syscall::unix_time();
+
+    let c_dir = CString::new(state_dir.as_str())
+        .map_err(|_| ort_error(ErrorKind::DirOpenFailed, "Null byte in state dir"))?;
+    let mut saved = Vec::new();
+    let mut out = String::with_capacity(1024);
+    let (mut num_pruned, mut pruned_bytes) = (0, 0);
+    for name in dir::DirFiles::new(c_dir.as_c_str())? {
+        if !name.starts_with("last-") || !name.ends_with(".json") {
+            continue;
+        }
+        let path = state_dir.clone() + "/" + &name;
+        let c_path = CString::new(path.as_str())
+            .map_err(|_| ort_error(ErrorKind::FileStatFailed, "Null byte in file name"))?;
+        let meta = file::metadata(c_path.as_c_str())?;
+        let age_secs = now.saturating_sub(meta.modified.as_secs());
+
+        if prune_older_than.is_some_and(|max| age_secs > max) {
+            if syscall::unlink(c_path.as_ptr()) < 0 {
+                return Err(ort_error(ErrorKind::Other, "Failed deleting conversation"));
+            }
+            out.push_str("Removed ");
+            out.push_str(&name);
+            out.push_str(", ");
+            out.push_str(&format_age(age_secs));
+            out.push_str(" old\n");
+            num_pruned += 1;
+            pruned_bytes += meta.size;
+            continue;
+        }
+
+        let summary = utils::filename_read_to_string(&path)
+            .ok()
+            .and_then(|json| LastData::from_json(&json).ok())
+            .map(|last| summarize(&last, models_cache.as_deref()));
+        saved.push(Saved {
+            name,
+            size: meta.size,
+            age_secs,
+            summary,
+        });
+    }
+    if prune_older_than.is_some() {
+        out.push_str("Removed ");
+        out.push_str(&plural(num_pruned, "conversation"));
+        out.push_str(", freed ");
+        out.push_str(&format_size(pruned_bytes));
+        out.push_str("\n\n");
+    }
+    if saved.is_empty() {
+        out.push_str("No saved conversations in ");
+        out.push_str(&state_dir);
+        out.push('\n');
+        return Ok(out);
+    }
+
+    // Newest first
+    saved.sort_by_key(|s| s.age_secs);
+    let (mut total_size, mut total_tokens, mut total_cents) = (0, 0, None);
+    for s in &saved {
+        total_size += s.size;
+        out.push_str(&s.name);
+        out.push_str("  ");
+        match &s.summary {
+            Some(sum) => {
+                total_tokens += sum.tokens;
+                out.push_str(&sum.model);
+                out.push_str("  ");
+                out.push_str(&plural(sum.num_messages, "message"));
+                out.push_str("  ~");
+                out.push_str(&utils::with_thousands(&utils::num_to_string(sum.tokens)));
+                out.push_str(" tokens  ");
+                match sum.cost_in_cents {
+                    Some(cents) => {
+                        *total_cents.get_or_insert(0.0) += cents;
+                        out.push('~');
+                        push_cost(&mut out, cents, cfg.currency_display);
+                    }
+                    None => out.push_str("no price"),
+                }
+            }
+            None => out.push_str("unreadable"),
+        }
+        out.push_str("  ");
+        out.push_str(&format_size(s.size));
+        out.push_str("  ");
+        out.push_str(&format_age(s.age_secs));
+        out.push_str(" ago\n");
+    }
+
+    out.push('\n');
+    out.push_str(&plural(saved.len(), "conversation"));
+    out.push_str(", ");
+    out.push_str(&format_size(total_size));
+    out.push_str(" on disk, ~");
+    out.push_str(&utils::with_thousands(&utils::num_to_string(total_tokens)));
+    match total_cents {
+        Some(cents) => {
+            out.push_str(" tokens, ~");
+            push_cost(&mut out, cents, cfg.currency_display);
+        }
+        None => out.push_str(" tokens, no prices, run `ort list` to cache them"),
+    }
+    out.push('\n');
+    let (newest, oldest) = (&saved[0], &saved[saved.len() - 1]);
+    out.push_str("Newest ");
+    out.push_str(&newest.name);
+    out.push_str(", ");
+    out.push_str(&format_age(newest.age_secs));
+    out.push_str(" ago. Oldest ");
+    out.push_str(&oldest.name);
+    out.push_str(", ");
+    out.push_str(&format_age(oldest.age_secs));
+    out.push_str(" ago.\n");
+    out.push_str(
+        "Token counts and costs are estimates, costs from the prices `ort list` caches.\n",
+    );
+    Ok(out)
+}
+
+fn summarize(last: &LastData, models_cache: Option<&str>) -> Summary {
+    let model = last
+        .opts
+        .models
+        .first()
+        .filter(|m| !m.is_empty())
+        .cloned()
+        .unwrap_or_else(|| "unknown model".into());
+    let family = Family::from_model(&model);
+    let price = models_cache.and_then(|cache| models::price(cache, &model));
+    Summary {
+        num_messages: last.messages.len(),
+        tokens: tokens::estimate_messages(&last.messages, family),
+        cost_in_cents: price.map(|p| estimate_cost_in_cents(&last.messages, family, &p)),
+        model,
+    }
+}
+
+/// Each assistant message is one request: everything before it is the
+/// prompt, it is the completion. Text only, like the token estimate.
+fn estimate_cost_in_cents(messages: &[Message], family: Family, price: &Price) -> f64 {
+    let mut prompt_tokens = 0;
+    let mut dollars = 0.0;
+    for m in messages {
+        let tokens = tokens::estimate_messages(core::slice::from_ref(m), family);
+        if matches!(m.role, Role::Assistant) {
+            dollars += prompt_tokens as f64 * price.prompt + tokens as f64 * price.completion;
+        }
+        prompt_tokens += tokens;
+    }
+    dollars * 100.0
+}
+
+fn plural(n: usize, word: &str) -> String {
+    let mut s = utils::num_to_string(n) + " " + word;
+    if n != 1 {
+        s.push('s');
+    }
+    s
+}
+
+/// The biggest whole unit: "45s", "12m", "3h", "41d"
+fn format_age(secs: u64) -> String {
+    let (n, unit) = match secs {
+        0..3600 if secs < 60 => (secs, "s"),
+        0..3600 => (secs / 60, "m"),
+        3600..86_400 => (secs / 3600, "h"),
+        _ => (secs / 86_400, "d"),
+    };
+    utils::num_to_string(n) + unit
+}
+
+/// Load a conversation from another tool. The model and the rest of the
+/// options come from config unless the file names an OpenRouter model.
+pub(in crate::input) fn import_file(source: &str, cfg: &Cfg) -> OrtResult<LastData> {
+    let json = if source == "-" {
+        stdin::read()?.unwrap_or_default()
+    } else {
+        utils::filename_read_to_string(source)
+            .map_err(|err| ort_error(ErrorKind::FileReadFailed, err))
+            .context("read conversation")?
+    };
+    import_json(&json, cfg)
+}
+
+/// `import_file` for a conversation we already have, e.g. piped in
+pub(in crate::input) fn import_json(json: &str, cfg: &Cfg) -> OrtResult<LastData> {
+    let mut last = from_json::to_last_data(json).map_err(|err| {
+        utils::print_string(c"Failed parsing conversation: ", &err);
+        ort_error(
+            ErrorKind::HistoryParseFailed,
+            "Failed to parse conversation file",
+        )
+    })?;
+    if last.messages.is_empty() {
+        return Err(ort_error(
+            ErrorKind::HistoryParseFailed,
+            "No messages in conversation file",
+        ));
+    }
+    last.opts.merge(cfg)?;
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        let messages = [
+            Message::user("Hello there".to_string()),
+            Message::assistant("Hi".to_string()),
+            Message::user("Bye".to_string()),
+            Message::assistant("Goodbye".to_string()),
+        ];
+        let family = Family::from_model("openai/gpt-5");
+        let t = |m: &Message| tokens::estimate_messages(core::slice::from_ref(m), family) as f64;
+        let price = Price {
+            prompt: 0.000001,
+            completion: 0.000002,
+        };
+        let expected = (t(&messages[0]) * price.prompt
+            + t(&messages[1]) * price.completion
+            + (t(&messages[0]) + t(&messages[1]) + t(&messages[2])) * price.prompt
+            + t(&messages[3]) * price.completion)
+            * 100.0;
+        let got = estimate_cost_in_cents(&messages, family, &price);
+        assert!((got - expected).abs() < 1e-12);
+        assert!(got > 0.0);
+    }
+
+    #[test]
+    fn test_clean_title() {
+        assert_eq!(
+            clean_title("\n\"Fixing the TLS Handshake.\"\nBecause...").as_deref(),
+            Some("Fixing the TLS Handshake")
+        );
+        assert_eq!(
+            clean_title("## **Rust no_std CLI argument parsing tips**").as_deref(),
+            Some("Rust no_std CLI argument parsing")
+        );
+        assert!(clean_title(" \n\"\"\n").is_none());
+    }
+
+    #[test]
+    fn test_titles_file() {
+        let last = |prompt: &str| LastData {
+            opts: PromptOpts::default(),
+            messages: vec![
+                Message::system("Be brief".to_string()),
+                Message::user(prompt.to_string()),
+            ],
+            tools: vec![],
+        };
+        let key = title_key(&last("Hello")).unwrap();
+        assert_eq!(title_key(&last("Hello")), Some(key.clone()));
+        assert_ne!(title_key(&last("Hello!")), Some(key.clone()));
+        assert!(title_key(&last("  ")).is_none());
+
+        let s =
+            "last-7.json\t".to_string() + &key + "\tSaying hello\nbroken line\nlast-8.json\t1\t\n";
+        let titles = parse_titles(&s);
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0].name, "last-7.json");
+        assert_eq!(titles[0].key, key);
+        assert_eq!(titles[0].title, "Saying hello");
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_age(45), "45s");
+        assert_eq!(format_age(12 * 60 + 5), "12m");
+        assert_eq!(format_age(3 * 3600), "3h");
+        assert_eq!(format_age(41 * 86_400 + 7), "41d");
+        assert_eq!(plural(1, "message"), "1 message");
+        assert_eq!(plural(3, "message"), "3 messages");
+    }
+}
@@ -0,0 +1,882 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Read a prompt from the terminal when none was given on the command line.
+//! Enter sends it. A line ending in `\` continues on the next line, a first
+//! line of `<<EOF` reads until a line of `EOF`, and a multi-line paste stays
+//! one prompt (bracketed paste).
+//!
+//! Editing keys are the usual readline ones: arrows, Ctrl-A/E/B/F, Alt-B/F,
+//! Ctrl-K/U/W to kill and Ctrl-Y to yank, Up/Down or Ctrl-P/N for history and
+//! Ctrl-R to search it. History is kept in `paths::history_file`. On a
+//! terminal without escape codes (TERM=dumb) the terminal edits the line.
+
+use core::ffi::{c_char, c_void};
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::common::term::Term;
+use crate::common::{file, paths, utils};
+use crate::input::cli::Env;
+use crate::{Write, syscall};
+
+const STDIN: i32 = 0;
+// Echo goes to stderr so it works with stdout redirected
+const ECHO: i32 = 2;
+
+const PROMPT: &str = "> ";
+const SEARCH_PROMPT: &str = "(reverse-i-search)`";
+const PASTE_ON: &str = "\x1b[?2004h";
+const PASTE_OFF: &str = "\x1b[?2004l";
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+const HISTORY_MAX: usize = 1000;
+
+const CTRL_A: u8 = 0x01;
+const CTRL_B: u8 = 0x02;
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_E: u8 = 0x05;
+const CTRL_F: u8 = 0x06;
+const CTRL_G: u8 = 0x07;
+const CTRL_H: u8 = 0x08;
+const CTRL_K: u8 = 0x0b;
+const CTRL_N: u8 = 0x0e;
+const CTRL_P: u8 = 0x10;
+const CTRL_R: u8 = 0x12;
+const CTRL_U: u8 = 0x15;
+const CTRL_W: u8 = 0x17;
+const CTRL_Y: u8 = 0x19;
+const ESC: u8 = 0x1b;
+const BACKSPACE: u8 = 0x7f;
+
+#[derive(Debug, PartialEq)]
+enum Done {
+    Submit(String),
+    Cancel,
+}
+
+struct Search {
+    query: String,
+    // Index in history of the match shown
+    found: Option<usize>,
+}
+
+#[derive(Default)]
+struct Editor {
+    // Lines already finished with Enter, after `\` or in a heredoc
+    done: String,
+    // The text being edited, and the cursor as a byte offset into it
+    buf: String,
+    cursor: usize,
+    // Delimiter of a `<<EOF` prompt
+    heredoc: Option<String>,
+    is_paste: bool,
+    // Escape sequence in progress
+    esc: Option<Vec<u8>>,
+    // Partial UTF-8 character
+    utf8: Vec<u8>,
+    // Last text killed, for Ctrl-Y
+    kill: String,
+    // Oldest first
+    history: Vec<String>,
+    // The history entry being shown, and what was typed before moving to it
+    hist_pos: Option<usize>,
+    saved: String,
+    search: Option<Search>,
+    // Terminal width, to know where lines wrap. 0 if unknown.
+    cols: usize,
+    // Screen row of the cursor, counting from the first line of `buf`.
+    // None means the cursor is just after `buf`, which is all on screen.
+    shown_row: Option<usize>,
+    // `buf` or the cursor changed since the screen was drawn
+    is_dirty: bool,
+}
+
+impl Editor {
+    /// Handle one byte of input. What to show the user goes in `echo`.
+    fn feed(&mut self, b: u8, echo: &mut String) -> Option<Done> {
+        if let Some(seq) = self.esc.as_mut() {
+            seq.push(b);
+            let is_end = match seq[1] {
+                b'[' => seq.len() > 2 && (0x40..=0x7e).contains(&b),
+                b'O' => seq.len() == 3,
+                // Alt-<key>
+                _ => true,
+            };
+            if is_end {
+                let seq = self.esc.take().unwrap();
+                self.escape(&seq);
+            }
+            return None;
+        }
+        if b == ESC {
+            self.esc = Some(alloc::vec![ESC]);
+            return None;
+        }
+        if self.search.is_some() && self.search_key(b) {
+            return None;
+        }
+        match b {
+            CTRL_C => return Some(Done::Cancel),
+            CTRL_D if self.done.is_empty() && self.buf.is_empty() && self.heredoc.is_none() => {
+                return Some(Done::Cancel);
+            }
+            CTRL_D if self.cursor < self.buf.len() => self.delete(self.cursor..self.next_char()),
+            CTRL_D => return Some(self.submit(echo)),
+            b'\r' | b'\n' if self.is_paste => self.insert("\n", echo),
+            b'\r' | b'\n' => return self.end_line(echo),
+            BACKSPACE | CTRL_H => self.delete(self.prev_char()..self.cursor),
+            CTRL_A => self.move_to(self.line_start()),
+            CTRL_E => self.move_to(self.line_end()),
+            CTRL_B => self.move_to(self.prev_char()),
+            CTRL_F => self.move_to(self.next_char()),
+            CTRL_K => self.kill(self.cursor..self.line_end()),
+            CTRL_U => self.kill(self.line_start()..self.cursor),
+            CTRL_W => self.kill(self.word_start()..self.cursor),
+            CTRL_Y => {
+                let s = self.kill.clone();
+                self.insert(&s, echo);
+            }
+            CTRL_P => self.history_move(true),
+            CTRL_N => self.history_move(false),
+            CTRL_R => {
+                self.touch();
+                self.search = Some(Search {
+                    query: String::new(),
+                    found: None,
+                });
+            }
+            b'\t' => self.insert("\t", echo),
+            b if b < 0x20 => {}
+            b => {
+                if let Some(s) = self.decode(b) {
+                    self.insert(&s, echo);
+                }
+            }
+        }
+        None
+    }
+
+    // A whole character once all its bytes are in
+    fn decode(&mut self, b: u8) -> Option<String> {
+        self.utf8.push(b);
+        match core::str::from_utf8(&self.utf8) {
+            Ok(s) => {
+                let s = s.to_string();
+                self.utf8.clear();
+                Some(s)
+            }
+            Err(e) if e.error_len().is_some() => {
+                self.utf8.clear();
+                None
+            }
+            // Wait for the rest of the character
+            Err(_) => None,
+        }
+    }
+
+    fn escape(&mut self, seq: &[u8]) {
+        if self.search.is_some() {
+            self.accept_search();
+        }
+        match seq {
+            PASTE_START => self.is_paste = true,
+            PASTE_END => self.is_paste = false,
+            b"\x1b[D" | b"\x1bOD" => self.move_to(self.prev_char()),
+            b"\x1b[C" | b"\x1bOC" => self.move_to(self.next_char()),
+            b"\x1b[A" | b"\x1bOA" => self.history_move(true),
+            b"\x1b[B" | b"\x1bOB" => self.history_move(false),
+            b"\x1b[H" | b"\x1bOH" | b"\x1b[1~" | b"\x1b[7~" => self.move_to(self.line_start()),
+            b"\x1b[F" | b"\x1bOF" | b"\x1b[4~" | b"\x1b[8~" => self.move_to(self.line_end()),
+            b"\x1b[3~" => self.delete(self.cursor..self.next_char()),
+            b"\x1bb" | b"\x1b[1;5D" | b"\x1b[1;3D" => self.move_to(self.word_start()),
+            b"\x1bf" | b"\x1b[1;5C" | b"\x1b[1;3C" => self.move_to(self.word_end()),
+            b"\x1bd" => self.kill(self.cursor..self.word_end()),
+            _ => {}
+        }
+    }
+
+    fn insert(&mut self, s: &str, echo: &mut String) {
+        if self.cursor == self.buf.len() && !self.is_dirty && self.search.is_none() {
+            // Typing at the end, no need to redraw
+            for c in s.chars() {
+                if c == '\n' {
+                    echo.push_str("\r\n");
+                } else {
+                    echo.push(c);
+                }
+            }
+            self.shown_row = None;
+        } else {
+            self.touch();
+        }
+        self.buf.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    fn delete(&mut self, range: core::ops::Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.touch();
+        self.cursor = range.start;
+        self.buf.replace_range(range, "");
+    }
+
+    fn kill(&mut self, range: core::ops::Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.kill = self.buf[range.clone()].to_string();
+        self.delete(range);
+    }
+
+    fn move_to(&mut self, pos: usize) {
+        if pos != self.cursor {
+            self.touch();
+            self.cursor = pos;
+        }
+    }
+
+    fn prev_char(&self) -> usize {
+        let c = self.buf[..self.cursor].chars().next_back();
+        self.cursor - c.map_or(0, char::len_utf8)
+    }
+
+    fn next_char(&self) -> usize {
+        let c = self.buf[self.cursor..].chars().next();
+        self.cursor + c.map_or(0, char::len_utf8)
+    }
+
+    fn line_start(&self) -> usize {
+        self.buf[..self.cursor].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self) -> usize {
+        self.buf[self.cursor..]
+            .find('\n')
+            .map_or(self.buf.len(), |i| self.cursor + i)
+    }
+
+    fn word_start(&self) -> usize {
+        let before = self.buf[..self.cursor].trim_end();
+        before
+            .rfind(|c: char| c.is_ascii_whitespace())
+            .map_or(0, |i| i + 1)
+    }
+
+    fn word_end(&self) -> usize {
+        let after = &self.buf[self.cursor..];
+        let rest = after.trim_start();
+        let word = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        self.cursor + (after.len() - rest.len()) + word
+    }
+
+    fn history_move(&mut self, is_older: bool) {
+        let pos = match (self.hist_pos, is_older) {
+            (None, true) if !self.history.is_empty() => self.history.len() - 1,
+            (Some(p), true) if p > 0 => p - 1,
+            (Some(p), false) if p + 1 < self.history.len() => p + 1,
+            (Some(_), false) => {
+                // Back to what was being typed
+                self.touch();
+                self.buf = core::mem::take(&mut self.saved);
+                self.cursor = self.buf.len();
+                self.hist_pos = None;
+                return;
+            }
+            _ => return,
+        };
+        self.show_history(pos);
+    }
+
+    fn show_history(&mut self, pos: usize) {
+        self.touch();
+        if self.hist_pos.is_none() {
+            self.saved = core::mem::take(&mut self.buf);
+        }
+        self.buf = self.history[pos].clone();
+        self.cursor = self.buf.len();
+        self.hist_pos = Some(pos);
+    }
+
+    /// A key while searching history. False if it ends the search and
+    /// should be handled as usual.
+    fn search_key(&mut self, b: u8) -> bool {
+        let Some(search) = self.search.as_mut() else {
+            return false;
+        };
+        let newest = self.history.len();
+        match b {
+            // Older matches
+            CTRL_R => {
+                let before = search.found.unwrap_or(newest);
+                self.find(before);
+            }
+            BACKSPACE | CTRL_H => {
+                search.query.pop();
+                self.find(newest);
+            }
+            CTRL_G | CTRL_C => self.search = None,
+            b if b >= 0x20 => {
+                if let Some(s) = self.decode(b) {
+                    let search = self.search.as_mut().unwrap();
+                    search.query.push_str(&s);
+                    // The match shown may still do
+                    let from = search.found.map_or(newest, |f| f + 1);
+                    self.find(from);
+                }
+            }
+            _ => {
+                self.accept_search();
+                return false;
+            }
+        }
+        self.is_dirty = true;
+        true
+    }
+
+    // The newest entry before `before` containing the query.
+    // Keeps the current match if there's no other.
+    fn find(&mut self, before: usize) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let found = self.history[..before]
+            .iter()
+            .rposition(|h| h.contains(search.query.as_str()));
+        if found.is_some() {
+            search.found = found;
+        }
+    }
+
+    fn accept_search(&mut self) {
+        if let Some(Search {
+            found: Some(pos), ..
+        }) = self.search.take()
+        {
+            self.show_history(pos);
+        }
+        self.is_dirty = true;
+    }
+
+    fn end_line(&mut self, echo: &mut String) -> Option<Done> {
+        self.move_to(self.buf.len());
+        self.render(echo);
+        echo.push_str("\r\n");
+
+        let line_start = self.buf.rfind('\n').map_or(0, |i| i + 1);
+        let line = &self.buf[line_start..];
+        if let Some(delim) = self.heredoc.as_deref() {
+            if line.trim_end() != delim {
+                self.next_line();
+                return None;
+            }
+            self.buf.truncate(line_start);
+            let mut text = self.take_text();
+            if text.ends_with('\n') {
+                text.pop();
+            }
+            return Some(Done::Submit(text));
+        }
+        if self.done.is_empty()
+            && let Some(delim) = heredoc_delimiter(&self.buf)
+        {
+            self.heredoc = Some(delim);
+            self.buf.clear();
+            self.cursor = 0;
+            self.shown_row = Some(0);
+            return None;
+        }
+        if line.ends_with('\\') {
+            self.buf.pop();
+            self.next_line();
+            return None;
+        }
+        Some(Done::Submit(self.take_text()))
+    }
+
+    /// A whole line from a terminal that did its own editing
+    fn plain_line(&mut self, line: &str) -> Option<Done> {
+        self.buf.push_str(line.strip_suffix('\r').unwrap_or(line));
+        self.cursor = self.buf.len();
+        // The terminal already showed it
+        let mut echo = String::new();
+        self.end_line(&mut echo)
+    }
+
+    fn submit(&mut self, echo: &mut String) -> Done {
+        self.move_to(self.buf.len());
+        self.render(echo);
+        echo.push_str("\r\n");
+        Done::Submit(self.take_text())
+    }
+
+    // Finish the line being edited and start a new one
+    fn next_line(&mut self) {
+        self.done.push_str(&self.buf);
+        self.done.push('\n');
+        self.buf.clear();
+        self.cursor = 0;
+        self.hist_pos = None;
+        self.shown_row = Some(0);
+    }
+
+    // Everything typed, ready for the next prompt
+    fn take_text(&mut self) -> String {
+        let mut text = core::mem::take(&mut self.done);
+        text.push_str(&self.buf);
+        self.buf.clear();
+        self.cursor = 0;
+        self.heredoc = None;
+        self.hist_pos = None;
+        self.shown_row = Some(0);
+        text
+    }
+
+    fn prompt(&self) -> &'static str {
+        if self.done.is_empty() && self.heredoc.is_none() {
+            PROMPT
+        } else {
+            ""
+        }
+    }
+
+    // Screen row and column of `text[..upto]`, after the prompt
+    fn position(&self, text: &str, upto: usize) -> (usize, usize) {
+        let (mut row, mut col) = (0, self.prompt().len());
+        for c in text[..upto].chars() {
+            match c {
+                '\n' => {
+                    row += 1;
+                    col = 0;
+                }
+                '\t' => col = (col / 8 + 1) * 8,
+                _ => col += 1,
+            }
+            if self.cols != 0 && col >= self.cols {
+                row += 1;
+                col = 0;
+            }
+        }
+        (row, col)
+    }
+
+    // Call before changing `buf` or the cursor, while the screen still shows them
+    fn touch(&mut self) {
+        if self.shown_row.is_none() {
+            let (row, col) = self.position(&self.buf, self.buf.len());
+            // A full last line leaves the cursor at its end, not on the next
+            let is_pending_wrap = col == 0 && row > 0 && !self.buf.ends_with('\n');
+            self.shown_row = Some(if is_pending_wrap { row - 1 } else { row });
+        }
+        self.is_dirty = true;
+    }
+
+    /// Redraw the text being edited, if it changed
+    fn render(&mut self, echo: &mut String) {
+        if !self.is_dirty {
+            return;
+        }
+        self.is_dirty = false;
+
+        let mut text = String::new();
+        let cursor = match &self.search {
+            Some(search) => {
+                text.push_str(SEARCH_PROMPT);
+                text.push_str(&search.query);
+                text.push_str("': ");
+                let cursor = text.len();
+                if let Some(pos) = search.found {
+                    text.push_str(&self.history[pos]);
+                }
+                cursor
+            }
+            None => {
+                text.push_str(&self.buf);
+                self.cursor
+            }
+        };
+
+        cursor_move(echo, self.shown_row.unwrap_or(0), 'A');
+        echo.push('\r');
+        echo.push_str(self.prompt());
+        echo.push_str("\x1b[J");
+        for c in text.chars() {
+            if c == '\n' {
+                echo.push_str("\r\n");
+            } else {
+                echo.push(c);
+            }
+        }
+        let (end_row, end_col) = self.position(&text, text.len());
+        if end_col == 0 && end_row > 0 && !text.ends_with('\n') {
+            // Move past a full last line, as we count it
+            echo.push_str("\r\n");
+        }
+        let (row, col) = self.position(&text, cursor);
+        cursor_move(echo, end_row - row, 'A');
+        echo.push('\r');
+        cursor_move(echo, col, 'C');
+        self.shown_row = Some(row);
+    }
+}
+
+// Move the terminal cursor `n` places, 'A' is up and 'C' right
+fn cursor_move(echo: &mut String, n: usize, dir: char) {
+    if n != 0 {
+        echo.push_str("\x1b[");
+        echo.push_str(&utils::num_to_string(n));
+        echo.push(dir);
+    }
+}
+
+// "<<EOF", "<< 'END'" -> the delimiter
+fn heredoc_delimiter(line: &str) -> Option<String> {
+    let word = line.strip_prefix("<<")?.trim().trim_matches(['\'', '"']);
+    let is_word = !word.is_empty() && word.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+    is_word.then(|| word.to_string())
+}
+
+fn history_path(env: &Env) -> Option<String> {
+    paths::history_file(env).ok()
+}
+
+/// One prompt per line, oldest first, with newlines and backslashes escaped.
+/// The file is cut back to the last HISTORY_MAX when it gets twice that.
+fn load_history(path: &str) -> Vec<String> {
+    let Ok(contents) = utils::filename_read_to_string(path) else {
+        return Vec::new();
+    };
+    let lines: Vec<&str> = contents.lines().collect();
+    let skip = lines.len().saturating_sub(HISTORY_MAX);
+    if skip > HISTORY_MAX
+        && let Ok(c_path) = CString::new(path)
+        && let Ok(mut f) = unsafe { file::File::create(c_path.as_bytes_with_nul()) }
+    {
+        for line in &lines[skip..] {
+            let _ = f.write_all(line.as_bytes());
+            let _ = f.write_all(b"\n");
+        }
+    }
+    lines[skip..].iter().map(|l| unescape(l)).collect()
+}
+
+fn save_history(path: &str, prompt: &str) {
+    let Ok(c_path) = CString::new(path) else {
+        return;
+    };
+    let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_APPEND;
+    // Prompts can be private
+    let Ok(fd) = syscall::open(c_path.as_ptr() as *const c_char, flags, 0o600) else {
+        return;
+    };
+    if fd < 0 {
+        return;
+    }
+    let mut line = escape(prompt);
+    line.push('\n');
+    syscall::write(fd, line.as_ptr() as *const c_void, line.len());
+    let _ = syscall::close(fd);
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// Put the terminal back how we found it, however we leave
+struct RawMode(syscall::termios);
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        syscall::tcsetattr(STDIN, &self.0);
+        write_echo(PASTE_OFF);
+    }
+}
+
+fn write_echo(s: &str) {
+    syscall::write(ECHO, s.as_ptr().cast(), s.len());
+}
+
+/// Ask for a prompt on the terminal. None if stdin isn't a terminal,
+/// or the user gave up (Ctrl-C, or Ctrl-D on an empty prompt).
+pub fn read_prompt(env: &Env) -> Option<String> {
+    if !syscall::isatty(STDIN) {
+        return None;
+    }
+    let path = history_path(env);
+    let mut ed = Editor {
+        history: path.as_deref().map(load_history).unwrap_or_default(),
+        ..Default::default()
+    };
+    // Editing redraws with escape codes, a dumb terminal would print them
+    let prompt = if Term::detect(ECHO, env.TERM).has_ansi() {
+        read_edited(&mut ed)?
+    } else {
+        read_plain(&mut ed)
+    };
+    if prompt.trim().is_empty() {
+        return None;
+    }
+    if let Some(path) = path
+        && ed.history.last() != Some(&prompt)
+    {
+        save_history(&path, &prompt);
+    }
+    Some(prompt)
+}
+
+/// Read with the terminal doing the editing, a line at a time
+fn read_plain(ed: &mut Editor) -> String {
+    write_echo(PROMPT);
+    let mut pending = Vec::new();
+    let mut input = [0u8; 256];
+    loop {
+        let n = syscall::read(STDIN, input.as_mut_ptr().cast(), input.len());
+        if n <= 0 {
+            // End of input, send what we have
+            ed.buf.push_str(&String::from_utf8_lossy(&pending));
+            return ed.take_text();
+        }
+        pending.extend_from_slice(&input[..n as usize]);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            match ed.plain_line(&String::from_utf8_lossy(&line[..end])) {
+                Some(Done::Submit(s)) if s.trim().is_empty() => write_echo(PROMPT),
+                Some(Done::Submit(s)) => return s,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Read with our own editing and history, the terminal in raw mode
+fn read_edited(ed: &mut Editor) -> Option<String> {
+    let mut orig = syscall::termios::default();
+    if syscall::tcgetattr(STDIN, &mut orig) < 0 {
+        return None;
+    }
+    let mut raw = orig;
+    raw.c_lflag &= !(syscall::ICANON | syscall::ECHO | syscall::ISIG | syscall::IEXTEN);
+    raw.c_iflag &= !(syscall::ICRNL | syscall::IXON);
+    raw.c_cc[syscall::VMIN] = 1;
+    raw.c_cc[syscall::VTIME] = 0;
+    if syscall::tcsetattr(STDIN, &raw) < 0 {
+        return None;
+    }
+    let _restore = RawMode(orig);
+    write_echo(PASTE_ON);
+    write_echo(PROMPT);
+
+    ed.cols = syscall::terminal_size(ECHO).map_or(0, |(_, cols)| cols as usize);
+    ed.shown_row = Some(0);
+    let mut echo = String::new();
+    let mut input = [0u8; 256];
+    let prompt = 'read: loop {
+        let n = syscall::read(STDIN, input.as_mut_ptr().cast(), input.len());
+        if n <= 0 {
+            // End of input, send what we have
+            break ed.take_text();
+        }
+        for b in &input[..n as usize] {
+            match ed.feed(*b, &mut echo) {
+                Some(Done::Submit(s)) if s.trim().is_empty() => {
+                    echo.push_str(PROMPT);
+                }
+                Some(Done::Submit(s)) => {
+                    write_echo(&echo);
+                    break 'read s;
+                }
+                Some(Done::Cancel) => {
+                    write_echo("\r\n");
+                    return None;
+                }
+                None => {}
+            }
+        }
+        // Redraw once per read, so a big paste isn't drawn a byte at a time
+        ed.render(&mut echo);
+        write_echo(&echo);
+        echo.clear();
+    };
+    Some(prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_with_history(history: &[&str], input: &[u8]) -> Option<Done> {
+        let mut ed = Editor {
+            history: history.iter().map(|h| h.to_string()).collect(),
+            cols: 20,
+            shown_row: Some(0),
+            ..Default::default()
+        };
+        let mut echo = String::new();
+        input.iter().find_map(|b| {
+            let done = ed.feed(*b, &mut echo);
+            ed.render(&mut echo);
+            done
+        })
+    }
+
+    fn type_in(input: &[u8]) -> Option<Done> {
+        type_with_history(&[], input)
+    }
+
+    fn submit(s: &str) -> Option<Done> {
+        Some(Done::Submit(s.to_string()))
+    }
+
+    #[test]
+    fn enter_sends() {
+        assert_eq!(type_in(b"hello\r"), submit("hello"));
+        assert_eq!(type_in(b"helo\x7flo\r"), submit("hello"));
+        assert_eq!(type_in("café\r".as_bytes()), submit("café"));
+        assert_eq!(type_in(b"abc\x03"), Some(Done::Cancel));
+        assert_eq!(type_in(b"\x04"), Some(Done::Cancel));
+    }
+
+    #[test]
+    fn backslash_continues() {
+        assert_eq!(type_in(b"one \\\rtwo\r"), submit("one \ntwo"));
+    }
+
+    #[test]
+    fn heredoc() {
+        assert_eq!(
+            type_in(b"<<EOF\rline 1\\\rline 2\rEOF\r"),
+            submit("line 1\\\nline 2")
+        );
+        assert_eq!(type_in(b"<< 'END'\rx\rEND\r"), submit("x"));
+    }
+
+    #[test]
+    fn plain_lines() {
+        let lines = |lines: &[&str]| {
+            let mut ed = Editor::default();
+            lines.iter().find_map(|line| ed.plain_line(line))
+        };
+        assert_eq!(lines(&["hello"]), submit("hello"));
+        assert_eq!(lines(&["one \\", "two\r"]), submit("one \ntwo"));
+        assert_eq!(lines(&["<<EOF", "a", "b", "EOF"]), submit("a\nb"));
+        assert_eq!(lines(&["<<EOF", "a"]), None);
+    }
+
+    #[test]
+    fn bracketed_paste_is_one_prompt() {
+        assert_eq!(
+            type_in(b"Explain: \x1b[200~fn a()\r{}\x1b[201~\r"),
+            submit("Explain: fn a()\n{}")
+        );
+    }
+
+    #[test]
+    fn cursor_movement() {
+        // Left arrow, then insert in the middle
+        assert_eq!(type_in(b"ac\x1b[Db\r"), submit("abc"));
+        assert_eq!(type_in("éa\x01b\x05c\r".as_bytes()), submit("béac"));
+        // Alt-b back a word, Delete
+        assert_eq!(type_in(b"one two\x1bb\x1b[3~T\r"), submit("one Two"));
+        // Ctrl-D deletes under the cursor, Enter sends the whole line
+        assert_eq!(type_in(b"abcd\x02\x02\x04\r"), submit("abd"));
+    }
+
+    #[test]
+    fn kill_and_yank() {
+        assert_eq!(type_in(b"one two\x17\x01\x19 \r"), submit("two one "));
+        assert_eq!(type_in(b"one two\x1bb\x0b\x15\x19\r"), submit("one "));
+    }
+
+    #[test]
+    fn history_up_down() {
+        let history = ["first", "second"];
+        assert_eq!(type_with_history(&history, b"\x1b[A\r"), submit("second"));
+        assert_eq!(
+            type_with_history(&history, b"\x10\x10\x10!\r"),
+            submit("first!")
+        );
+        // Down past the newest gets back what was typed
+        assert_eq!(
+            type_with_history(&history, b"new\x1b[A\x1b[B\r"),
+            submit("new")
+        );
+    }
+
+    #[test]
+    fn history_search() {
+        let history = ["cargo build", "git status", "cargo test"];
+        assert_eq!(
+            type_with_history(&history, b"\x12car\r"),
+            submit("cargo test")
+        );
+        assert_eq!(
+            type_with_history(&history, b"\x12car\x12\r"),
+            submit("cargo build")
+        );
+        // Editing keys end the search and act on the match
+        assert_eq!(
+            type_with_history(&history, b"\x12st\x05 -v\r"),
+            submit("cargo test -v")
+        );
+        // Ctrl-G gives up, leaving what was typed
+        assert_eq!(type_with_history(&history, b"x\x12git\x07\r"), submit("x"));
+    }
+
+    #[test]
+    fn render_wraps_at_width() {
+        let mut ed = Editor {
+            cols: 10,
+            shown_row: Some(0),
+            ..Default::default()
+        };
+        let mut echo = String::new();
+        for b in b"0123456789abcd\x01" {
+            ed.feed(*b, &mut echo);
+        }
+        echo.clear();
+        ed.render(&mut echo);
+        // "> " and 14 chars is two rows, cursor back on the first
+        assert_eq!(echo, "\x1b[1A\r> \x1b[J0123456789abcd\x1b[1A\r\x1b[2C");
+        assert_eq!(ed.shown_row, Some(0));
+    }
+
+    #[test]
+    fn history_file_escapes() {
+        let prompt = "a\\nb\nc";
+        assert_eq!(unescape(&escape(prompt)), prompt);
+        assert_eq!(escape(prompt), "a\\\\nb\\nc");
+    }
+}
@@ -8,9 +8,10 @@ use core::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 extern crate alloc;
 use alloc::string::String;
-use alloc::vec;
 use alloc::vec::Vec;
 
+use crate::cli::Env;
+use crate::common::models;
 use crate::utils::print_string;
 use crate::{
     Context, OrtResult, Read, Write, chunked,
@@ -26,6 +27,7 @@ const MAX_TOTAL_SLUG_LEN: usize = 16 * 1024;
 pub fn run<W: Write + Send>(
     api_key: &str,
     cfg: &config::Cfg,
+    env: &Env,
     opts: args::ListOpts,
     w: &mut W,
 ) -> OrtResult<()> {
@@ -52,7 +54,10 @@ pub fn run<W: Write + Send>(
         }
     };
     let mut reader = buf_read::OrtBufReader::new(reader);
-    let response_body = http::skip_header(&mut reader)?;
+    let response_body = http::read_header(&mut reader)?.body();
+
+    // Kept for the model capabilities cache
+    let mut body: Vec<u8> = Vec::with_capacity(512 * 1024);
 
     if opts.is_json {
         // The full JSON. User should use `jq` or similar to pretty it.
@@ -64,6 +69,7 @@ pub fn run<W: Write + Send>(
                 while let Some(chunk) = chunked.next_chunk() {
                     let chunk = chunk?;
                     w.write_all(chunk.as_bytes()).context("write models JSON")?;
+                    body.extend_from_slice(chunk.as_bytes());
                 }
             }
             http::ResponseBody::ContentLength(len) => {
@@ -75,6 +81,7 @@ pub fn run<W: Write + Send>(
                         .read_exact(&mut buf[..n])
                         .context("read models body")?;
                     w.write_all(&buf[..n]).context("write models JSON")?;
+                    body.extend_from_slice(&buf[..n]);
                     remaining -= n;
                 }
             }
@@ -87,6 +94,7 @@ pub fn run<W: Write + Send>(
                     }
                     w.write_all(&buf[..bytes_read])
                         .context("write models JSON")?;
+                    body.extend_from_slice(&buf[..bytes_read]);
                 }
             }
         }
@@ -103,6 +111,7 @@ pub fn run<W: Write + Send>(
                 let mut chunked = chunked::read::<_, MAX_CHUNK_SIZE>(reader);
                 while let Some(chunk) = chunked.next_chunk() {
                     let chunk = chunk?;
+                    body.extend_from_slice(chunk.as_bytes());
                     for (pos, section) in chunk.split(r#""id":""#).enumerate() {
                         let maybe_next_id = if pos == 0 && !partial.is_empty() {
                             // We have a partial from previous iteration
@@ -132,23 +141,22 @@ pub fn run<W: Write + Send>(
                 }
             }
             http::ResponseBody::ContentLength(len) => {
-                let mut models = vec![0u8; len];
-                reader.read_exact(&mut models).context("read models body")?;
-                let models = core::str::from_utf8(&models)
+                body.resize(len, 0);
+                reader.read_exact(&mut body).context("read models body")?;
+                let models = core::str::from_utf8(&body)
                     .map_err(|_| ort_error(ErrorKind::FormatError, "models JSON utf8"))?;
                 push_slugs(models, &mut slugs, &mut total_slug_len);
             }
             http::ResponseBody::UntilEof => {
-                let mut models = Vec::with_capacity(512 * 1024);
                 let mut buf: [u8; 4096] = [0; 4096];
                 loop {
                     let bytes_read = reader.read(&mut buf).context("read models body")?;
                     if bytes_read == 0 {
                         break;
                     }
-                    models.extend_from_slice(&buf[..bytes_read]);
+                    body.extend_from_slice(&buf[..bytes_read]);
                 }
-                let models = core::str::from_utf8(&models)
+                let models = core::str::from_utf8(&body)
                     .map_err(|_| ort_error(ErrorKind::FormatError, "models JSON utf8"))?;
                 push_slugs(models, &mut slugs, &mut total_slug_len);
             }
@@ -174,6 +182,14 @@ pub fn run<W: Write + Send>(
 
         let _ = w.write(&out[..out_len]); // one syscall
     }
+
+    // Not being able to cache must not fail the listing
+    let saved = core::str::from_utf8(&body)
+        .map_err(|_| ort_error(ErrorKind::FormatError, "models JSON utf8"))
+        .and_then(|json| models::save(env, json));
+    if let Err(err) = saved {
+        print_string(c"Could not save models cache: ", &err.as_string());
+    }
     Ok(())
 }
 
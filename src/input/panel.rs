@@ -0,0 +1,235 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `ort panel --personas a,b,c "question"`: ask the same model the same
+//! question under several system prompts at once, print each answer under
+//! its persona, and with `--judge` a synthesis of them all.
+
+extern crate alloc;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::common::config::Cfg;
+use crate::common::stats::{self, Stats};
+use crate::common::term::Term;
+use crate::common::utils;
+use crate::input::args::PanelOpts;
+use crate::input::{cli, prompt};
+use crate::{
+    DEFAULT_MODEL, ErrorKind, Message, OrtResult, PromptOpts, Verbosity, Write, ort_error,
+};
+
+const JUDGE_PROMPT: &str = "You are chairing a panel. Several experts, each with a different outlook, answered the same question. Write the best single answer: say where they agree, weigh where they disagree and why, and give your conclusion. Refer to the experts by name. Be concise.";
+
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// The text, or why it failed. None if interrupted.
+type Answer = Option<Result<String, String>>;
+
+pub fn run<W: Write>(
+    api_key: &str,
+    cfg: &Cfg,
+    opts: PanelOpts,
+    is_offline: bool,
+    term: Term,
+    w: &mut W,
+) -> OrtResult<()> {
+    let personas = find_personas(cfg, &opts.personas)?;
+    let model = opts
+        .model
+        .or_else(|| cfg.models.first().cloned())
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+
+    let mut msg = "Asking ".to_string()
+        + &utils::num_to_string(personas.len())
+        + " personas with "
+        + &model
+        + "\n";
+    let _ = w.write_str(&msg);
+    let _ = w.flush();
+    cli::require_network(is_offline)?;
+
+    let mut prompts = Vec::with_capacity(personas.len());
+    for (_, system) in &personas {
+        prompts.push(prompt_for(cfg, &model, system, &opts.question)?);
+    }
+    let mut answers: Vec<Answer> = vec![None; personas.len()];
+    let mut total = Stats::default();
+    prompt::run_parallel(api_key, cfg, prompts, None, |i, out| {
+        answers[i] = Some(match out.error() {
+            None => Ok(out.contents().to_string()),
+            Some(err) => Err(err.to_string()),
+        });
+        if let Some(s) = out.stats() {
+            total += s.clone();
+        }
+    })?;
+
+    msg.clear();
+    for ((name, _), answer) in personas.iter().zip(&answers) {
+        write_answer(&mut msg, name, answer, term.has_ansi());
+    }
+    w.write_str(&msg)?;
+    w.flush()?;
+
+    let answered: Vec<(&str, &str)> = personas
+        .iter()
+        .zip(&answers)
+        .filter_map(|((name, _), answer)| match answer {
+            Some(Ok(text)) => Some((name.as_str(), text.as_str())),
+            _ => None,
+        })
+        .collect();
+    msg.clear();
+    if opts.judge && !answered.is_empty() {
+        let judge_msg = judge_message(&opts.question, &answered);
+        let prompts = vec![prompt_for(cfg, &model, JUDGE_PROMPT, &judge_msg)?];
+        let mut synthesis: Answer = None;
+        prompt::run_parallel(api_key, cfg, prompts, None, |_, out| {
+            synthesis = Some(match out.error() {
+                None => Ok(out.contents().to_string()),
+                Some(err) => Err(err.to_string()),
+            });
+            if let Some(s) = out.stats() {
+                total += s.clone();
+            }
+        })?;
+        write_answer(&mut msg, "Synthesis", &synthesis, term.has_ansi());
+    }
+
+    msg.push_str("\nPanel: ");
+    msg.push_str(&utils::num_to_string(answered.len()));
+    msg.push_str(" of ");
+    msg.push_str(&utils::num_to_string(personas.len()));
+    msg.push_str(" answered");
+    if let Some(cents) = total.cost_in_cents {
+        msg.push_str(", ");
+        stats::push_cost(&mut msg, cents, cfg.currency_display);
+    }
+    msg.push('\n');
+    w.write_str(&msg)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// (name, system prompt) of each, in the order asked for
+fn find_personas<'a>(cfg: &'a Cfg, names: &[String]) -> OrtResult<Vec<&'a (String, String)>> {
+    names
+        .iter()
+        .map(|name| {
+            cfg.personas.iter().find(|(n, _)| n == name).ok_or_else(|| {
+                let msg = "Unknown persona ".to_string()
+                    + name
+                    + ", add `persona."
+                    + name
+                    + ": <system prompt>` to ort.cfg";
+                ort_error(ErrorKind::InvalidArguments, msg.leak())
+            })
+        })
+        .collect()
+}
+
+fn prompt_for(
+    cfg: &Cfg,
+    model: &str,
+    system: &str,
+    question: &str,
+) -> OrtResult<(PromptOpts, Vec<Message>, usize)> {
+    let mut opts = PromptOpts {
+        prompt: Some(question.to_string()),
+        models: vec![model.to_string()],
+        system: Some(system.to_string()),
+        provider: cfg.provider.clone(),
+        priority: cfg.priority,
+        verbosity: Some(Verbosity::Quiet),
+        user: cfg.user_id.clone(),
+        ..Default::default()
+    };
+    if cfg.effort.is_some() {
+        opts.effort = cfg.effort;
+    }
+    let messages = opts.messages()?;
+    Ok((opts, messages, 0))
+}
+
+/// The question, then each persona's answer under its name
+fn judge_message(question: &str, answered: &[(&str, &str)]) -> String {
+    let mut s = "The question:\n\n".to_string() + question + "\n";
+    for (name, text) in answered {
+        s.push_str("\n## ");
+        s.push_str(name);
+        s.push_str("\n\n");
+        s.push_str(text.trim());
+        s.push('\n');
+    }
+    s
+}
+
+fn write_answer(out: &mut String, name: &str, answer: &Answer, is_ansi: bool) {
+    if is_ansi {
+        out.push_str(BOLD);
+    }
+    out.push('\n');
+    out.push_str(name);
+    if is_ansi {
+        out.push_str(RESET);
+    }
+    out.push('\n');
+    match answer {
+        Some(Ok(text)) => {
+            out.push_str(text.trim());
+            out.push('\n');
+        }
+        Some(Err(err)) => {
+            out.push_str("  request failed: ");
+            out.push_str(err);
+            out.push('\n');
+        }
+        None => out.push_str("  no answer\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn personas_in_order_asked() {
+        let cfg = Cfg::from_str("persona.a: Be A\npersona.b: Be B\n").unwrap();
+        let names = ["b".to_string(), "a".to_string()];
+        let found = find_personas(&cfg, &names).unwrap();
+        assert_eq!(found[0].1, "Be B");
+        assert_eq!(found[1].1, "Be A");
+
+        let err = find_personas(&cfg, &["c".to_string()]).err().unwrap();
+        assert_eq!(
+            err.context,
+            "Unknown persona c, add `persona.c: <system prompt>` to ort.cfg"
+        );
+    }
+
+    #[test]
+    fn answers_under_names() {
+        let mut out = String::new();
+        write_answer(&mut out, "skeptic", &Some(Ok("No.\n".to_string())), false);
+        write_answer(&mut out, "optimist", &Some(Err("429".to_string())), false);
+        write_answer(&mut out, "engineer", &None, false);
+        assert_eq!(
+            out,
+            "\nskeptic\nNo.\n\noptimist\n  request failed: 429\n\nengineer\n  no answer\n"
+        );
+
+        assert_eq!(
+            judge_message(
+                "Why?",
+                &[("skeptic", "Because.\n"), ("optimist", "Why not!")]
+            ),
+            "The question:\n\nWhy?\n\n## skeptic\n\nBecause.\n\n## optimist\n\nWhy not!\n"
+        );
+    }
+}
@@ -6,6 +6,7 @@
 
 use core::cmp::max;
 use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::time::Duration;
 
 extern crate alloc;
 use alloc::boxed::Box;
@@ -14,54 +15,92 @@ use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
 
-use crate::cli::Env;
+use crate::cli::{self, Env};
+use crate::common::base64;
 use crate::common::buf_read::OrtBufReader;
-use crate::common::data::{Tool, ToolCall};
-use crate::net::AsFd;
+use crate::common::data::{
+    Choice, Content, Generation, ModelSource, Tool, ToolCall, Verbosity, append_system,
+};
 use crate::output::logger::Logger;
-use crate::{Context as _, OrtError, chunked};
+use crate::transport::{AsFd, pool, socket, trace};
+use crate::{Context as _, OrtError, TcpSocket, TlsStream, chunked};
 
 use crate::ChatCompletionsResponse;
 use crate::OrtResult;
 use crate::build_body;
-use crate::common::config::{self, Cfg};
+use crate::common::config::Cfg;
 use crate::common::dir;
 use crate::common::file;
-use crate::common::io::{ReadLine, Write};
+use crate::common::io::{Read, ReadLine, Write};
+use crate::common::models;
+use crate::common::paths;
+use crate::common::request_id;
 use crate::common::resolver;
-use crate::common::stats::{self, Stats};
+use crate::common::stats::{
+    self, Attempt, Connection, Memory, Provenance, ReasoningBudget, Stats, Verbose, WriterLag,
+};
+use crate::common::term::Term;
 use crate::common::time;
+use crate::common::tokens::{self, Family};
 use crate::common::utils;
 use crate::http::{self, ContentLengthReader};
+use crate::input::args::Transcript;
+use crate::input::credits::{self, Credits};
+use crate::input::history;
+use crate::input::queue;
+use crate::input::to_json::{write_json_str, write_stats_json};
 use crate::ort_error;
 use crate::output::OutputWriter;
-use crate::output::last_writer::LastWriter;
-use crate::output::writer::{CollectedWriter, ConsoleWriter, FileWriter};
+use crate::output::answer_writer::AnswerWriter;
+use crate::output::extract::ExtractWriter;
+use crate::output::last_writer::{LastWriter, SessionLock};
+use crate::output::metrics;
+use crate::output::oneline::OneLineWriter;
+use crate::output::pager::Pager;
+use crate::output::save_code::SaveCodeWriter;
+use crate::output::tee::TeeWriter;
+use crate::output::writer::{CollectedWriter, ConsoleWriter, FileWriter, sample_label};
 use crate::syscall::{self, F_SETFL, O_NONBLOCK, SOCK_CLOEXEC, SOCK_STREAM};
-use crate::utils::print_string;
+use crate::transport::event_loop::{Event, EventLoop};
 use crate::{ErrorKind, LastData};
 use crate::{Message, PromptOpts};
 use crate::{Response, ThinkEvent};
 
-const EPOLL_WAIT_TIMEOUT_MS: i32 = 100;
-
 /// Same size as input/list.rs but likely could be much smaller
 /// Same size means the generic is shared, smaller code.
 const MAX_CHUNK_SIZE: usize = 128 * 1024;
 
-struct EpollFd(i32);
+/// --first-token-deadline skips at most this many slow providers per model
+const MAX_PROVIDER_FAILOVERS: usize = 3;
 
-impl EpollFd {
-    fn raw(&self) -> i32 {
-        self.0
-    }
-}
+/// SSE `event:` names some OpenAI compatible servers end the stream with,
+/// instead of or as well as `data: [DONE]`
+const END_EVENTS: [&str; 4] = ["done", "end", "complete", "message_stop"];
 
-impl Drop for EpollFd {
-    fn drop(&mut self) {
-        let _ = syscall::close(self.0);
-    }
-}
+/// What a prompt that ran out of --deadline ends with
+const DEADLINE_PASSED: &str = "--deadline passed, the answer is truncated";
+
+/// Malformed events are skipped and counted. This many means the stream is junk.
+const MAX_MALFORMED_EVENTS: u32 = 20;
+/// With --verbose, how many malformed events to print, and how much of each
+const MAX_LOGGED_MALFORMED: u32 = 3;
+const MALFORMED_SNIPPET_BYTES: usize = 80;
+
+/// With no usage chunk, or after we hang up early, how long to keep asking
+/// for the generation's stats
+const LATE_STATS_MS: u64 = 2000;
+const LATE_STATS_RETRY_MS: u64 = 400;
+
+/// `--retries` waits about this long before the first retry, doubling each time
+const RETRY_BASE_MS: u64 = 500;
+const RETRY_MAX_MS: u64 = 8_000;
+
+/// How far over --reasoning-tokens the reasoning can go, in percent, before
+/// it's hidden or, with --strict-reasoning, stopped
+const REASONING_SLACK_PCT: u64 = 50;
+
+/// After a 402, how long to wait for the credits API
+const CREDITS_MS: u64 = 3000;
 
 #[allow(clippy::too_many_arguments)]
 pub fn run<W: Write + Send>(
@@ -71,18 +110,57 @@ pub fn run<W: Write + Send>(
     opts: PromptOpts,
     messages: Vec<Message>,
     tools: Vec<&'static Tool>,
-    is_pipe_output: bool, // Are we redirecting stdout?
+    term: Term,
     w_core: &mut W,
 ) -> OrtResult<()> {
-    let show_reasoning = opts.show_reasoning.unwrap();
-    let is_quiet = opts.quiet.unwrap_or_default();
+    // Extracted code going to a pipe or file gets nothing else with it,
+    // and neither does a --oneline answer wherever it goes
+    let is_bare = (!term.is_tty() && opts.extract.is_some()) || opts.oneline;
+    let show_reasoning = opts.show_reasoning.unwrap() && !is_bare;
+    let mut verbosity = opts.verbosity.unwrap_or_default();
+    if is_bare {
+        verbosity = verbosity.max(Verbosity::Quiet);
+    }
+    let output_image = opts.output_image.clone();
     //let model_name = opts.common.model.clone().unwrap();
 
-    let mut output_writer: Box<dyn OutputWriter> = if is_pipe_output {
-        Box::new(FileWriter::new(w_core, show_reasoning, is_quiet))
+    let mut tee_file = match opts.tee.as_deref() {
+        Some(path) => {
+            let c_path = CString::new(path)
+                .map_err(|_| ort_error(ErrorKind::Other, "--tee path contains nul byte"))?;
+            Some(unsafe { file::File::create(c_path.as_bytes_with_nul())? })
+        }
+        None => None,
+    };
+
+    let mut output_writer: Box<dyn OutputWriter> = if !term.has_ansi() {
+        Box::new(FileWriter::new(
+            w_core,
+            show_reasoning,
+            verbosity,
+            opts.flush,
+        ))
     } else {
-        Box::new(ConsoleWriter::new(w_core, show_reasoning, is_quiet))
+        let mut console =
+            ConsoleWriter::new(w_core, show_reasoning, verbosity, opts.raw, opts.flush);
+        if cfg.use_pager {
+            console.pager = Pager::new();
+        }
+        Box::new(console)
     };
+    if let Some(mode) = opts.extract {
+        output_writer = Box::new(ExtractWriter::new(output_writer, mode));
+    }
+    if opts.oneline {
+        output_writer = Box::new(OneLineWriter::new(output_writer));
+    }
+    if let Some(dir) = opts.save_code.as_deref() {
+        output_writer = Box::new(SaveCodeWriter::new(output_writer, dir));
+    }
+    if let Some(f) = tee_file.as_mut() {
+        let copy = FileWriter::new(f, opts.tee_all, Verbosity::Quiet, opts.flush);
+        output_writer = Box::new(TeeWriter::new(output_writer, copy));
+    }
 
     let mut last_writer = if cfg.save_to_file {
         Some(LastWriter::new(
@@ -90,251 +168,1052 @@ pub fn run<W: Write + Send>(
             messages.clone(),
             tools.clone(),
             env,
+            cfg.save_reasoning,
         )?)
     } else {
         None
     };
 
-    let mut active_prompt = ActivePrompt::new(
-        api_key.to_string(),
-        cfg,
-        opts,
-        messages,
-        tools.clone(),
-        0,
-        Some(env),
-    )?;
-    active_prompt.start()?;
+    let mut answer_writer = if cfg.save_answer {
+        Some(AnswerWriter::new(
+            env,
+            cfg.save_dir.as_deref(),
+            &opts.models[0],
+        )?)
+    } else {
+        None
+    };
 
-    loop {
-        match active_prompt.next() {
-            Ok(None) => {
-                break;
+    // Only collected for the post_response hook
+    let mut response_content = String::new();
+    let has_post_response = cfg.post_response.is_some();
+
+    // Failing over doesn't get more time
+    let deadline = opts
+        .deadline_ms
+        .map(|ms| syscall::monotonic_ms() + ms as u64);
+    // Past --deadline we still show and save what came
+    let mut is_timed_out = false;
+
+    // --verbose: is the time going on the network or on our output?
+    let tsc = if opts.verbose {
+        time::tsc_calibration().ok()
+    } else {
+        None
+    };
+    let mut writer_lag = WriterLag::default();
+
+    // With --first-token-deadline we may need another go at it
+    let mut opts = opts;
+    let mut fallback_models: Vec<String> = cfg
+        .models
+        .iter()
+        .filter(|m| !opts.models.contains(m) && !opts.pin_provider)
+        .cloned()
+        .collect();
+    // --retries, every try so far
+    let mut attempts: Vec<Attempt> = Vec::new();
+    // Once the answer is on screen a failure is final
+    let mut is_answering = false;
+
+    let mut needs_sample_label = opts.n.is_some_and(|n| n > 1);
+
+    // --show-model-source
+    let mut provenance = Provenance {
+        source: opts.model_source,
+        profile: match opts.model_source {
+            ModelSource::Profile => cfg.profile.clone(),
+            _ => None,
+        },
+        ..Default::default()
+    };
+
+    let mut active_prompt = 'attempt: loop {
+        // Nothing of a failed attempt stays in the --tee file
+        output_writer.restart()?;
+        let mut active_prompt = ActivePrompt::new(
+            api_key.to_string(),
+            cfg,
+            opts.clone(),
+            messages.clone(),
+            tools.clone(),
+            0,
+            Some(env),
+        )?;
+        active_prompt.first_token_deadline_ms = opts.first_token_deadline_ms;
+        active_prompt.deadline = deadline;
+        active_prompt.has_free_fallback = free_fallback(cfg, &opts.models[0]).is_some();
+        if opts.queue_on_fail {
+            active_prompt.queue_path = Some(queue::new_path(env)?);
+        }
+        if opts.max_cost.is_some() {
+            active_prompt.cost_limit =
+                CostLimit::new(models::load(env).as_deref(), &opts, 0, &messages);
+        }
+        if let Err(err) = active_prompt.start() {
+            if !matches!(err.kind, ErrorKind::InsufficientCredits) {
+                if let Some(max) = opts.retries
+                    && retry(
+                        &mut attempts,
+                        max,
+                        active_prompt.failed_attempt(err.as_string()),
+                        is_retryable(err.kind),
+                        deadline,
+                    )
+                {
+                    continue 'attempt;
+                }
+                return Err(err);
             }
-            Ok(Some(out)) => {
-                for event in out {
-                    output_writer.write(event.clone())?;
+            match free_fallback(cfg, &opts.models[0]) {
+                Some(free) => {
+                    let msg = "Out of credits, asking ".to_string() + free + " instead\n";
+                    utils::warn(&msg);
+                    provenance.fallback_from.push(opts.models[0].clone());
+                    provenance.source = ModelSource::Config;
+                    provenance.profile = None;
+                    opts.models[0] = free.to_string();
+                    continue 'attempt;
+                }
+                None => {
+                    let balance = active_prompt.fetch_credits().ok();
+                    let cache = models::load(env);
+                    utils::warn(&credits::advice(balance, cache.as_deref(), &opts.models[0]));
+                    return Err(err);
+                }
+            }
+        }
+
+        loop {
+            match active_prompt.next() {
+                Ok(None) => {
+                    break 'attempt active_prompt;
+                }
+                Ok(Some(out)) => {
+                    // The provider failed before answering, worth another try
+                    if let Some(max) = opts.retries
+                        && !is_answering
+                        && let Some(Response::Error(msg)) =
+                            out.iter().find(|e| matches!(e, Response::Error(_)))
+                        && retry(
+                            &mut attempts,
+                            max,
+                            active_prompt.failed_attempt(msg.clone()),
+                            error_status(msg).is_none_or(is_retryable_status),
+                            deadline,
+                        )
+                    {
+                        continue 'attempt;
+                    }
+                    let arrived = time::Ticks::now();
+                    for event in out {
+                        is_answering |= matches!(event, Response::Content(_) | Response::Think(_));
+                        if has_post_response && let Response::Content(c) = &event {
+                            response_content.push_str(c);
+                        }
+                        // Only on screen, the saved answer is the first sample alone
+                        if needs_sample_label && matches!(event, Response::Content(_)) {
+                            output_writer.write(Response::Content(sample_label(1)))?;
+                            needs_sample_label = false;
+                        }
+                        output_writer.write(event.clone())?;
+                        if let Some(aw) = answer_writer.as_mut() {
+                            aw.write(event.clone())?;
+                        }
+                        if let Some(lw) = last_writer.as_mut() {
+                            lw.write(event)?;
+                        }
+                        if let Some(tc) = tsc {
+                            writer_lag.record(time::elapsed_duration(
+                                arrived,
+                                time::Ticks::now(),
+                                tc,
+                            ));
+                        }
+                    }
+                }
+                Err(err) if matches!(err.kind, ErrorKind::DeadlineExceeded) => {
+                    is_timed_out = true;
+                    break 'attempt active_prompt;
+                }
+                Err(err) if matches!(err.kind, ErrorKind::FirstTokenTimeout) => {
+                    // The same provider again, not the next one
+                    if opts.pin_provider {
+                        if let Some(max) = opts.retries
+                            && retry(
+                                &mut attempts,
+                                max,
+                                active_prompt.failed_attempt(err.as_string()),
+                                true,
+                                deadline,
+                            )
+                        {
+                            continue 'attempt;
+                        }
+                        return Err(err);
+                    }
+                    let tried = opts.models[0].clone();
+                    if fail_over(&mut opts, active_prompt.provider(), &mut fallback_models) {
+                        if opts.models[0] == tried {
+                            provenance.provider_retries += 1;
+                        } else {
+                            provenance.fallback_from.push(tried);
+                            // The fallbacks are the config's models
+                            provenance.source = cfg.model_source;
+                            provenance.profile = cfg.profile.clone();
+                        }
+                        continue 'attempt;
+                    }
+                    return Err(err);
+                }
+                // Keep what we have, it might still be useful
+                Err(err)
+                    if matches!(
+                        err.kind,
+                        ErrorKind::CostLimitExceeded | ErrorKind::ReasoningBudgetExceeded
+                    ) =>
+                {
+                    // What the part we got actually cost
+                    if cfg.metrics.is_some() {
+                        metrics::record(cfg, &active_prompt.stop());
+                    }
+                    output_writer.stop(false)?;
+                    if let Some(mut aw) = answer_writer {
+                        aw.stop(false)?;
+                    }
                     if let Some(lw) = last_writer.as_mut() {
-                        lw.write(event)?;
+                        lw.stop(false)?;
                     }
+                    return Err(err);
+                }
+                Err(err) => {
+                    // TODO? 429 is useful to know about
+                    // let err_str = err.as_string();
+                    // if err_str.contains("429 Too Many Requests") {
+                    utils::print_error(c"active_prompt.next: ", &err.as_string());
                 }
-            }
-            Err(err) => {
-                // TODO? 429 is useful to know about
-                // let err_str = err.as_string();
-                // if err_str.contains("429 Too Many Requests") {
-                utils::print_string(c"active_prompt.next: ", &err.as_string());
             }
         }
+    };
+
+    // The first sample was streamed, now the others
+    for (i, text) in active_prompt.take_other_choices().into_iter().enumerate() {
+        let out = "\n\n".to_string() + &sample_label(i + 2) + &text;
+        output_writer.write(Response::Content(out))?;
     }
 
-    // Clean finish, send stats
-    let stats = active_prompt.stop();
-    output_writer.write(Response::Stats(stats))?;
+    // Clean finish, or out of time, send stats
+    let mut stats = active_prompt.stop();
+    stats.is_truncated = is_timed_out;
+    if opts.retries.is_some() {
+        attempts.push(Attempt {
+            error: None,
+            elapsed: stats.elapsed_time,
+            time_to_first_token: stats.time_to_first_token,
+        });
+        stats.attempts = attempts;
+    }
+    if cfg.show_model_source {
+        stats.model_source = Some(Box::new(provenance));
+    }
+    let metrics_stats = cfg.metrics.is_some().then(|| stats.clone());
+    // Hooks expect a whole answer
+    if !is_timed_out {
+        cli::run_post_response_hook(cfg, &stats.used_model, &stats.provider, &response_content);
+    }
+    if let Some(mut aw) = answer_writer {
+        aw.write(Response::Stats(Box::new(stats.clone())))?;
+        aw.stop(true)?;
+        stats.saved_to = Some(aw.path().into());
+    }
+    if opts.verbose {
+        stats.verbose = Some(Box::new(Verbose {
+            memory: Memory::now(),
+            writer_lag: (writer_lag.events != 0).then_some(writer_lag),
+        }));
+    }
+    output_writer.write(Response::Stats(Box::new(stats)))?;
     output_writer.stop(true)?; // prints stats
+    drop(output_writer);
+    if let Some(stats) = metrics_stats {
+        metrics::record(cfg, &stats);
+    }
     // Finalize JSON
     if let Some(lw) = last_writer.as_mut() {
         lw.stop(true)?;
     }
 
+    if let Some(output_image) = output_image.as_deref() {
+        save_images(output_image, active_prompt.take_images(), w_core)?;
+    }
+
+    if is_timed_out {
+        return Err(ort_error(ErrorKind::DeadlineExceeded, DEADLINE_PASSED));
+    }
+
+    // Not a success, even if some content came first. Scripts check the exit code.
+    if let Some(mut msg) = active_prompt.refusal() {
+        msg.push('\n');
+        syscall::write(2, msg.as_ptr().cast(), msg.len());
+        return Err(ort_error(ErrorKind::ModelRefused, "refused or filtered"));
+    }
+
     Ok(())
 }
 
-/// The full path of the file where we stored the last conversation
-fn last_file(env: &Env) -> OrtResult<String> {
-    let mut last_path = [0u8; 128];
-    let cache_dir_end = config::cache_dir(env, &mut last_path)?;
-    last_path[cache_dir_end] = b'/';
-    let last_filename = utils::last_filename(env);
-    let start = cache_dir_end + 1;
-    let end = start + last_filename.len();
-    last_path[start..end].copy_from_slice(last_filename.as_bytes());
+/// The config's `free_fallback`, unless that's the model that is out of credits
+fn free_fallback<'a>(cfg: &'a Cfg, model: &str) -> Option<&'a str> {
+    cfg.free_fallback.as_deref().filter(|free| *free != model)
+}
+
+/// `--retries`: record a failed try, and say whether to have another.
+/// Waits before saying yes, but not past `deadline`. Giving up prints them all.
+fn retry(
+    attempts: &mut Vec<Attempt>,
+    max_retries: u32,
+    attempt: Attempt,
+    is_retryable: bool,
+    deadline: Option<u64>,
+) -> bool {
+    let why = attempt.error.clone().unwrap_or_default();
+    attempts.push(attempt);
+    if is_retryable && attempts.len() <= max_retries as usize {
+        let mut random = [0u8; 8];
+        syscall::getrandom(&mut random);
+        let mut wait_ms = retry_wait_ms(attempts.len(), u64::from_le_bytes(random));
+        if let Some(deadline) = deadline {
+            wait_ms = wait_ms.min(deadline.saturating_sub(syscall::monotonic_ms()));
+        }
+        let msg = "Attempt ".to_string()
+            + &utils::num_to_string(attempts.len())
+            + " failed: "
+            + &why
+            + ", trying again in "
+            + &utils::num_to_string(wait_ms)
+            + "ms\n";
+        utils::warn(&msg);
+        syscall::sleep_ms(wait_ms);
+        return true;
+    }
+    utils::warn(&("Attempts: ".to_string() + &stats::attempts_string(attempts) + "\n"));
+    false
+}
 
-    let cs = CString::new(&last_path[..end]).expect("Null bytes in config cache dir");
-    if utils::path_exists(cs.as_ref()) {
-        Ok(unsafe { String::from_utf8_unchecked(last_path[..end].into()) })
+/// Exponential backoff before retry number `n` (from 1), with jitter so that
+/// many clients don't all come back at once: between half and all of
+/// `RETRY_BASE_MS` doubled each time, up to `RETRY_MAX_MS`.
+fn retry_wait_ms(n: usize, random: u64) -> u64 {
+    let full = RETRY_BASE_MS
+        .saturating_mul(1 << (n.saturating_sub(1)).min(16))
+        .min(RETRY_MAX_MS);
+    full / 2 + random % (full / 2 + 1)
+}
+
+/// Another try could go differently. Not for errors that are about us, or
+/// that already did something, like queueing the request.
+fn is_retryable(kind: ErrorKind) -> bool {
+    !matches!(
+        kind,
+        ErrorKind::NoNetwork
+            | ErrorKind::HttpClientError
+            | ErrorKind::InsufficientCredits
+            | ErrorKind::CostLimitExceeded
+            | ErrorKind::DeadlineExceeded
+            | ErrorKind::MissingApiKey
+    )
+}
+
+/// A timeout, rate limit or server error. Other 4xx are about the request
+/// (bad model id, bad key, too long) and will fail the same way again.
+fn is_retryable_status(status: u16) -> bool {
+    !(400..500).contains(&status) || status == 408 || status == 429
+}
+
+/// The HTTP status at the end of a stream error, e.g. 502 from
+/// "Chutes: overloaded (502)"
+fn error_status(msg: &str) -> Option<u16> {
+    msg.strip_suffix(')')?.rsplit_once('(')?.1.parse().ok()
+}
+
+/// "Malformed event at byte 1,234: <why>: data: {"id":"gen-1","cho..."
+fn malformed_message(offset: usize, data: &str, err: &str) -> String {
+    let mut msg = "Malformed event at byte ".to_string()
+        + &utils::with_thousands(&utils::num_to_string(offset))
+        + ": "
+        + err
+        + ": "
+        + &data[..data.floor_char_boundary(MALFORMED_SNIPPET_BYTES)];
+    if data.len() > MALFORMED_SNIPPET_BYTES {
+        msg.push_str("...");
+    }
+    msg.push('\n');
+    msg
+}
+
+/// After a first token timeout choose what to try next: the same model
+/// without the slow provider, then the other models from the config file.
+/// Returns false when there is nothing left to try.
+fn fail_over(opts: &mut PromptOpts, provider: &str, fallback_models: &mut Vec<String>) -> bool {
+    let deadline_ms = opts.first_token_deadline_ms.unwrap_or_default();
+    let mut msg = String::with_capacity(128);
+    msg.push_str("No first token after ");
+    msg.push_str(&utils::num_to_string(deadline_ms));
+    msg.push_str("ms from ");
+    msg.push_str(&opts.models[0]);
+
+    // If the stream never named the provider, fall back to the one we asked for
+    let provider = if provider.is_empty() {
+        opts.provider.clone().unwrap_or_default()
+    } else {
+        provider.to_string()
+    };
+    let can_retry_provider = !provider.is_empty()
+        && !opts.ignore_providers.contains(&provider)
+        && opts.ignore_providers.len() < MAX_PROVIDER_FAILOVERS;
+    if can_retry_provider {
+        msg.push_str(" on ");
+        msg.push_str(&provider);
+        msg.push_str(", trying next provider\n");
+        if opts.provider.as_deref() == Some(provider.as_str()) {
+            opts.provider = None;
+        }
+        opts.ignore_providers.push(provider);
+    } else if !fallback_models.is_empty() {
+        let next_model = fallback_models.remove(0);
+        msg.push_str(", trying ");
+        msg.push_str(&next_model);
+        msg.push('\n');
+        opts.models = vec![next_model];
+        // Provider slugs are per model
+        opts.ignore_providers.clear();
     } else {
-        let cache_dir = unsafe { str::from_utf8_unchecked(&last_path[..cache_dir_end]) };
-        most_recent(cache_dir, "last-").context("most_recent")
+        msg.push_str(", giving up\n");
+        syscall::write(2, msg.as_ptr().cast(), msg.len());
+        return false;
+    }
+    utils::warn(&msg);
+    true
+}
+
+/// Write the generated images to `path`, then `path` with -2, -3, etc
+/// before the extension. Prints where each one went.
+fn save_images<W: Write>(path: &str, images: Vec<Content>, w: &mut W) -> OrtResult<()> {
+    if images.is_empty() {
+        w.write_str("No images in response\n")?;
+        return Ok(());
+    }
+    for (idx, image) in images.into_iter().enumerate() {
+        let base64 = match image {
+            Content::Image { base64, .. } => base64,
+            Content::ImageUrl(url) => {
+                // Hosted image, nothing to decode
+                w.write_str(&url)?;
+                w.write_char('\n')?;
+                continue;
+            }
+            _ => continue,
+        };
+        let bytes = base64::decode(&base64)
+            .map_err(|err| ort_error(ErrorKind::FormatError, err))
+            .context("decode image")?;
+        let image_path = numbered_path(path, idx);
+        let c_path = CString::new(image_path.as_str())
+            .map_err(|_| ort_error(ErrorKind::Other, "Image path contains nul byte"))?;
+        let mut f = unsafe { file::File::create(c_path.as_bytes_with_nul())? };
+        f.write_all(&bytes)?;
+        w.write_str(&image_path)?;
+        w.write_char('\n')?;
+    }
+    Ok(())
+}
+
+/// out.png, out-2.png, out-3.png, ...
+fn numbered_path(path: &str, idx: usize) -> String {
+    if idx == 0 {
+        return path.to_string();
+    }
+    let suffix = "-".to_string() + &utils::num_to_string(idx + 1);
+    // Only a dot in the file name counts, not one in a directory
+    let name_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = name_start + dot;
+            path[..dot].to_string() + &suffix + &path[dot..]
+        }
+        _ => path.to_string() + &suffix,
+    }
+}
+
+/// The full paths of the files `-c` may continue, best first: this session's
+/// last conversation, then the other sessions' newest first.
+/// A named session only continues itself.
+fn last_files(env: &Env) -> OrtResult<Vec<String>> {
+    let state_dir = paths::state_dir(env)?;
+    let own = state_dir.clone() + "/" + &utils::last_filename(env);
+    if env.ORT_SESSION.is_some_and(|s| !s.is_empty()) {
+        return Ok(vec![own]);
+    }
+    let cs = CString::new(own.as_str()).expect("Null bytes in config state dir");
+    let mut paths = Vec::with_capacity(4);
+    if utils::path_exists(cs.as_ref()) {
+        paths.push(own);
+    }
+    for path in by_recency(&state_dir, "last-", ".json").context("by_recency")? {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    if paths.is_empty() {
+        return Err(ort_error(
+            ErrorKind::HistoryLookupFailed,
+            "No files found starting with prefix",
+        ));
     }
+    Ok(paths)
 }
 
+/// The last conversation. A file that doesn't parse (a full disk, a crash
+/// before it reached the disk, a hand edit) is skipped with a warning and
+/// the next most recent one is used.
 pub(in crate::input) fn load_last_data(env: &Env) -> OrtResult<LastData> {
-    let last_file_path = last_file(env)?;
-    match utils::filename_read_to_string(&last_file_path) {
-        Ok(hist_str) => LastData::from_json(&hist_str).map_err(|err| {
-            print_string(c"Failed parsing history: ", &err);
-            ort_error(ErrorKind::HistoryParseFailed, "Failed to parse last")
-        }),
-        Err("NOT FOUND") => Err(ort_error(
-            ErrorKind::HistoryMissing,
-            "No last conversation, cannot continue",
-        )),
-        Err(_e) => {
-            #[cfg(debug_assertions)]
-            {
-                // In debug build print the path.
-                let c_last_file = CString::new(last_file_path).unwrap();
-                syscall::write(2, c_last_file.as_ptr().cast(), c_last_file.count_bytes());
-            }
-            Err(ort_error(
-                ErrorKind::HistoryReadFailed,
-                "Error reading last conversation file",
-            ))
+    let mut last_err = ort_error(
+        ErrorKind::HistoryMissing,
+        "No last conversation, cannot continue",
+    );
+    for last_file_path in last_files(env)? {
+        match utils::filename_read_to_string(&last_file_path) {
+            Ok(hist_str) => match LastData::from_json(&hist_str) {
+                Ok(data) => return Ok(data),
+                Err(err) => {
+                    let msg =
+                        "Skipping damaged ".to_string() + &last_file_path + ": " + &err + "\n";
+                    utils::warn(&msg);
+                    last_err = ort_error(ErrorKind::HistoryParseFailed, "Failed to parse last");
+                }
+            },
+            Err("NOT FOUND") => {}
+            Err(_e) => {
+                #[cfg(debug_assertions)]
+                {
+                    // In debug build print the path.
+                    let c_last_file = CString::new(last_file_path).unwrap();
+                    syscall::write(2, c_last_file.as_ptr().cast(), c_last_file.count_bytes());
+                }
+                last_err = ort_error(
+                    ErrorKind::HistoryReadFailed,
+                    "Error reading last conversation file",
+                );
+            }
         }
     }
+    Err(last_err)
 }
 
 /// The `-c` continue operation. Load the most recent conversation for this
-/// pane to populate the context, then run with the new prompt.
+/// pane, or `from_file` for `--from-openai`, to populate the context, then
+/// run with the new prompt.
 pub fn run_continue<W: Write + Send>(
     api_key: &str,
     cfg: &Cfg,
     env: &Env,
     mut opts: crate::PromptOpts,
-    is_pipe_output: bool,
+    from: Option<Transcript>,
+    term: Term,
     w: &mut W,
 ) -> OrtResult<()> {
-    let mut last = load_last_data(env)?;
+    // Until our answer is saved, so the next -c here continues from it
+    let mut _lock = None;
+    let mut last = match from {
+        Some(Transcript::File(path)) => history::import_file(&path, cfg)?,
+        Some(Transcript::Piped(json)) => history::import_json(&json, cfg)?,
+        None => {
+            _lock = Some(SessionLock::acquire(env)?);
+            load_last_data(env)?
+        }
+    };
+    // Saved reasoning is for reading back, the API only takes it alone
+    for m in last.messages.iter_mut() {
+        m.reasoning = None;
+    }
 
-    opts.merge_opts(last.opts);
+    let system = pin_system(
+        &mut last.messages,
+        opts.system.take(),
+        opts.append_system.take(),
+    );
+    opts.merge_opts(last.opts)?;
+    opts.system = system;
     last.messages
         .push(crate::Message::user(opts.prompt.take().unwrap()));
 
-    run(
-        api_key,
-        cfg,
-        env,
-        opts,
-        last.messages,
-        last.tools,
-        is_pipe_output,
-        w,
-    )
+    run(api_key, cfg, env, opts, last.messages, last.tools, term, w)
+}
+
+/// The system prompt of a continued conversation. It stays as it was unless
+/// `-s` replaces it or `--append-system` adds to it (after any `-s`). Only
+/// the first system or developer message is the system prompt, and it keeps
+/// its role. Returns it, as now sent.
+fn pin_system(
+    messages: &mut Vec<crate::Message>,
+    replace: Option<String>,
+    append: Option<String>,
+) -> Option<String> {
+    let idx = messages
+        .iter()
+        .position(|m| matches!(m.role, crate::Role::System | crate::Role::Developer));
+    let current = idx.and_then(|i| messages[i].text()).map(|s| s.to_string());
+    if replace.is_none() && append.is_none() {
+        return current;
+    }
+    let mut system = replace.or(current);
+    if let Some(extra) = append {
+        system = Some(append_system(system, extra));
+    }
+    let text = system.clone().unwrap_or_default();
+    match idx {
+        Some(i) => messages[i] = crate::Message::new(messages[i].role, Some(text), None),
+        None => messages.insert(0, crate::Message::system(text)),
+    }
+    system
 }
 
 pub fn run_multi<W: Write + Send>(
     api_key: &str,
     cfg: &Cfg,
+    env: &Env,
     opts: PromptOpts,
     messages: Vec<crate::Message>,
     w: &mut W,
 ) -> OrtResult<()> {
     let num_models = opts.models.len();
-    let mut msg = String::with_capacity(32);
-    msg.push_str("Calling ");
-    msg.push_str(&utils::num_to_string(num_models));
-    msg.push_str(" models...\r");
-    let _ = w.write(msg.as_bytes());
-    let _ = w.flush();
-
-    let epoll_fd = syscall::epoll_create(num_models as i32);
-    if epoll_fd < 0 {
-        return Err(ort_error(ErrorKind::Other, "epoll_create"));
-    }
-    let epoll_fd = EpollFd(epoll_fd);
-    let mut names = Vec::with_capacity(num_models); // debug
-    let mut active_prompts = Vec::with_capacity(num_models);
-    let mut active_writers = Vec::with_capacity(num_models);
+    let prompts = (0..num_models)
+        .map(|idx| (opts.clone(), messages.clone(), idx))
+        .collect();
+    let models_cache = opts.max_cost.and_then(|_| models::load(env));
+    // Any answer cut short by --deadline makes the whole run fail
+    let mut is_timed_out = false;
+    if let Some(dir) = opts.out_dir.as_deref() {
+        utils::ensure_dir_all_exist(dir);
+    }
+    let save = |idx: usize, output_writer: &CollectedWriter| {
+        if let Some(dir) = opts.out_dir.as_deref()
+            && let Err(err) = save_answer(dir, &opts.models[idx], output_writer)
+        {
+            utils::print_error(c"--out-dir: ", &err.as_string());
+        }
+    };
+
+    if opts.is_json {
+        let mut results: Vec<Option<CollectedWriter>> = (0..num_models).map(|_| None).collect();
+        run_parallel(
+            api_key,
+            cfg,
+            prompts,
+            models_cache.as_deref(),
+            |idx, output_writer| {
+                is_timed_out |= output_writer.error() == Some(DEADLINE_PASSED);
+                save(idx, output_writer);
+                results[idx] = Some(output_writer.clone());
+            },
+        )?;
+        // One write once everything is in, so a reader never sees half a document
+        let mut out: Vec<u8> = Vec::with_capacity(4096);
+        multi_json(&mut out, &opts.models, &results)?;
+        w.write_all(&out)?;
+        w.flush()?;
+        return deadline_result(is_timed_out);
+    }
+
+    if opts.verbosity.unwrap_or_default().show_progress() {
+        let mut msg = String::with_capacity(32);
+        msg.push_str("Calling ");
+        msg.push_str(&utils::num_to_string(num_models));
+        msg.push_str(" models...\r");
+        let _ = w.write(msg.as_bytes());
+        let _ = w.flush();
+    }
+
+    run_parallel(
+        api_key,
+        cfg,
+        prompts,
+        models_cache.as_deref(),
+        |idx, output_writer| {
+            is_timed_out |= output_writer.error() == Some(DEADLINE_PASSED);
+            save(idx, output_writer);
+            let _ = w.write(output_writer.output.as_ref().unwrap().as_bytes());
+            let _ = w.write("\n\n".as_bytes());
+            let _ = w.flush();
+        },
+    )?;
+    deadline_result(is_timed_out)
+}
+
+/// --out-dir: the answer in <dir>/<model slug>.md, and each other --samples
+/// answer in <slug>-2.md, <slug>-3.md, etc. Nothing for a model with no answer.
+fn save_answer(dir: &str, model: &str, output_writer: &CollectedWriter) -> OrtResult<()> {
+    if output_writer.contents().is_empty() {
+        return Ok(());
+    }
+    let path = dir.to_string() + "/" + &utils::slug(model) + ".md";
+    let answers = core::iter::once(output_writer.contents())
+        .chain(output_writer.samples.iter().map(String::as_str));
+    for (i, answer) in answers.enumerate() {
+        let path = if i == 0 {
+            path.clone()
+        } else {
+            numbered_path(&path, i)
+        };
+        let c_path = CString::new(path)
+            .map_err(|_| ort_error(ErrorKind::FileCreateFailed, "Null byte in --out-dir"))?;
+        let mut f = unsafe { file::File::create(c_path.as_bytes_with_nul()) }?;
+        f.write_str(answer)?;
+        f.flush()?;
+    }
+    Ok(())
+}
+
+fn deadline_result(is_timed_out: bool) -> OrtResult<()> {
+    if is_timed_out {
+        Err(ort_error(ErrorKind::DeadlineExceeded, DEADLINE_PASSED))
+    } else {
+        Ok(())
+    }
+}
+
+/// `--json` with several models: {"<model>": {"content", "stats", "error"}, ...}
+fn multi_json<W: Write>(
+    w: &mut W,
+    models: &[String],
+    results: &[Option<CollectedWriter>],
+) -> OrtResult<()> {
+    w.write_char('{')?;
+    for (i, (model, result)) in models.iter().zip(results).enumerate() {
+        if i != 0 {
+            w.write_char(',')?;
+        }
+        write_json_str(w, model)?;
+        w.write_str(":{\"content\":")?;
+        match result {
+            Some(r) if r.error().is_none() || !r.contents().is_empty() => {
+                write_json_str(w, r.contents())?
+            }
+            _ => {
+                w.write_str("null")?;
+            }
+        }
+        // --samples, all of them including the first
+        if let Some(r) = result.as_ref().filter(|r| !r.samples.is_empty()) {
+            w.write_str(",\"samples\":[")?;
+            write_json_str(w, r.contents())?;
+            for sample in &r.samples {
+                w.write_char(',')?;
+                write_json_str(w, sample)?;
+            }
+            w.write_char(']')?;
+        }
+        w.write_str(",\"stats\":")?;
+        match result.as_ref().and_then(|r| r.stats()) {
+            Some(stats) => write_stats_json(w, stats)?,
+            None => {
+                w.write_str("null")?;
+            }
+        }
+        w.write_str(",\"error\":")?;
+        match result {
+            Some(r) => match r.error() {
+                Some(err) => write_json_str(w, err)?,
+                None => {
+                    w.write_str("null")?;
+                }
+            },
+            // Interrupted
+            None => write_json_str(w, "No answer")?,
+        }
+        w.write_char('}')?;
+    }
+    w.write_str("}\n")?;
+    Ok(())
+}
+
+/// Send all the prompts at once. Each is (opts, messages, index of the model in opts).
+/// `models_cache` has the prices for --max-cost.
+/// `on_done` gets the prompt's index and its output as each one finishes, or fails.
+pub(in crate::input) fn run_parallel<F: FnMut(usize, &CollectedWriter)>(
+    api_key: &str,
+    cfg: &Cfg,
+    prompts: Vec<(PromptOpts, Vec<Message>, usize)>,
+    models_cache: Option<&str>,
+    mut on_done: F,
+) -> OrtResult<()> {
+    let mut event_loop = EventLoop::new(prompts.len())?;
+    // One --deadline for all of them
+    let deadline = prompts
+        .first()
+        .and_then(|(opts, _, _)| opts.deadline_ms)
+        .map(|ms| syscall::monotonic_ms() + ms as u64);
+    // (index in prompts, prompt, its output, is it done)
+    let mut active = Vec::with_capacity(prompts.len());
 
     // Start all the queries.
     // We negotiate TLS one at a time, should start epoll earlier to do all at once.
-    for idx in 0..num_models {
-        let model_name = opts.models.get(idx).unwrap().clone();
-        names.push(model_name);
-
-        let mut active_prompt = ActivePrompt::new(
+    for (idx, (opts, messages, model_idx)) in prompts.into_iter().enumerate() {
+        let cost_limit = CostLimit::new(models_cache, &opts, model_idx, &messages);
+        let first_token_deadline_ms = opts.first_token_deadline_ms;
+        let started = ActivePrompt::new(
             api_key.to_string(),
             cfg,
-            opts.clone(),
-            messages.clone(),
+            opts,
+            messages,
             vec![],
-            idx,
+            model_idx,
             None,
-        )?;
-        active_prompt.start()?;
+        )
+        .and_then(|mut active_prompt| {
+            active_prompt.cost_limit = cost_limit;
+            active_prompt.first_token_deadline_ms = first_token_deadline_ms;
+            active_prompt.deadline = deadline;
+            active_prompt.start().map(|_| active_prompt)
+        });
+        let active_prompt = match started {
+            Ok(p) => p,
+            Err(err) => {
+                // Report it and carry on with the others
+                let mut output_writer = CollectedWriter::new();
+                output_writer.write(Response::Error(err.as_string()))?;
+                output_writer.stop(false)?;
+                on_done(idx, &output_writer);
+                continue;
+            }
+        };
         let socket_fd = active_prompt.as_fd();
-
-        active_prompts.push(active_prompt);
-        active_writers.push(CollectedWriter::new());
+        let token = active.len() as u64;
+        // Without this a model that never answers would block the others
+        if let Some(deadline) = active_prompt.read_deadline() {
+            event_loop.set_timer(token, deadline);
+        }
+        active.push((idx, active_prompt, CollectedWriter::new(), false));
 
         syscall::fcntl(socket_fd, F_SETFL, SOCK_STREAM | SOCK_CLOEXEC | O_NONBLOCK);
-        let mut event = syscall::epoll_event {
-            events: syscall::EPOLLIN,
-            data: active_prompts.len() as u64 - 1,
-        };
-        if syscall::epoll_ctl(
-            epoll_fd.raw(),
-            syscall::EPOLL_CTL_ADD,
-            socket_fd,
-            &mut event,
-        ) < 0
-        {
-            return Err(ort_error(ErrorKind::Other, "epoll_ctl"));
-        }
+        event_loop.add(socket_fd, token)?;
     }
 
-    let mut ready_events = vec![syscall::epoll_event { events: 0, data: 0 }; active_prompts.len()];
-    while !ready_events.is_empty() {
-        let num_ready = syscall::epoll_wait(
-            epoll_fd.raw(),
-            ready_events.as_mut_ptr(),
-            ready_events.len() as i32,
-            EPOLL_WAIT_TIMEOUT_MS,
-        );
-        if num_ready < 0 {
+    let mut num_running = active.len();
+    let mut events = Vec::with_capacity(active.len());
+    while num_running > 0 {
+        if !event_loop.wait(&mut events) {
             // Ctrl-C
             break;
         }
-        if num_ready == 0 {
-            continue;
-        }
-
-        let mut num_done = 0;
-        for evt in ready_events[..num_ready as usize].iter() {
-            let active_prompt = &mut active_prompts[evt.data as usize];
-            let output_writer = &mut active_writers[evt.data as usize];
-            //let name = &names[evt.data as usize];
+        for evt in &events {
+            let token = match *evt {
+                Event::Readable(token) => token,
+                // Only matters if the first token hasn't come yet
+                Event::Timer(token) => token,
+            };
+            let (idx, active_prompt, output_writer, is_done) = &mut active[token as usize];
+            if *is_done {
+                // Its socket and its timer in the same wait
+                continue;
+            }
 
             // TODO: loop until WouldBlock?
 
-            match active_prompt.next() {
+            *is_done = match active_prompt.next() {
                 Ok(None) => {
-                    num_done += 1;
-
                     let stats = active_prompt.stop();
-                    output_writer.write(Response::Stats(stats))?;
-                    output_writer.stop(true)?;
-
-                    let _ = w.write(output_writer.output.as_ref().unwrap().as_bytes());
-                    let _ = w.write("\n\n".as_bytes());
-                    let _ = w.flush();
+                    metrics::record(cfg, &stats);
+                    output_writer.write(Response::Stats(Box::new(stats)))?;
+                    output_writer.samples = active_prompt.take_other_choices();
+                    true
                 }
                 Ok(Some(out)) => {
+                    // A provider's error event ends its stream
+                    let is_error = out.iter().any(|e| matches!(e, Response::Error(_)));
                     for event in out {
                         output_writer.write(event.clone())?;
                     }
+                    is_error
                 }
+                // With a deadline a WouldBlock comes back as FirstTokenTimeout
                 Err(OrtError {
-                    kind: ErrorKind::WouldBlock,
+                    kind:
+                        ErrorKind::WouldBlock
+                        | ErrorKind::FirstTokenTimeout
+                        | ErrorKind::DeadlineExceeded,
                     ..
-                }) => {
+                }) if !active_prompt.is_past_deadline() => {
                     // we read all the data, back to epoll_wait
+                    false
+                }
+                Err(OrtError {
+                    kind: ErrorKind::FirstTokenTimeout,
+                    ..
+                }) => {
+                    let msg = "No first token after ".to_string()
+                        + &utils::num_to_string(
+                            active_prompt.first_token_deadline_ms.unwrap_or_default(),
+                        )
+                        + "ms";
+                    output_writer.write(Response::Error(msg))?;
+                    true
+                }
+                // Keeps what came, like the single prompt does
+                Err(OrtError {
+                    kind: ErrorKind::DeadlineExceeded,
+                    ..
+                }) => {
+                    output_writer.write(Response::Error(DEADLINE_PASSED.to_string()))?;
+                    true
                 }
                 Err(err) => {
-                    utils::print_string(c"active_prompt.next: ", &err.as_string());
+                    let model = &active_prompt.opts.models[active_prompt.model_idx];
+                    metrics::record_error(cfg, model, &err);
+                    output_writer.write(Response::Error(err.as_string()))?;
+                    true
                 }
+            };
+            // After the first token only --deadline is left
+            match active_prompt.read_deadline() {
+                Some(deadline) => event_loop.set_timer(token, deadline),
+                None => event_loop.clear_timer(token),
+            }
+            if *is_done {
+                num_running -= 1;
+                event_loop.clear_timer(token);
+                event_loop.remove(active_prompt.as_fd());
+                output_writer.stop(true)?;
+                on_done(*idx, output_writer);
             }
-        }
-
-        for _ in 0..num_done {
-            let _ = ready_events.pop();
         }
     }
     Ok(())
 }
 
-pub trait PromptReader: ReadLine + AsFd {}
+pub trait PromptReader: ReadLine + AsFd {
+    /// The connection, if the response was read to its end and it can
+    /// carry another request
+    fn into_idle(self: Box<Self>) -> Option<TlsStream<TcpSocket>> {
+        None
+    }
+}
+
+/// --max-cost on our side. OpenRouter only sends the real cost at the end,
+/// so estimate it from the model's price as the answer streams.
+pub(in crate::input) struct CostLimit {
+    max_cost: f64,
+    price: models::Price,
+    prompt_cost: f64,
+    // Reasoning and content, as they stream
+    completion: tokens::Counter,
+}
+
+impl CostLimit {
+    /// None without --max-cost, or if the `ort list` cache doesn't have the
+    /// model's price. That one says so on stderr.
+    pub(in crate::input) fn new(
+        models_cache: Option<&str>,
+        opts: &PromptOpts,
+        model_idx: usize,
+        messages: &[Message],
+    ) -> Option<Self> {
+        let max_cost = opts.max_cost?;
+        let model = &opts.models[model_idx];
+        let Some(price) = models_cache.and_then(|cache| models::price(cache, model)) else {
+            let msg = "No price for ".to_string()
+                + model
+                + ", run `ort list` to fetch prices. Only OpenRouter's max_price limits this request.\n";
+            utils::warn(&msg);
+            return None;
+        };
+        let family = Family::from_model(model);
+        Some(CostLimit {
+            max_cost,
+            price,
+            prompt_cost: tokens::estimate_messages(messages, family) as f64 * price.prompt,
+            completion: tokens::Counter::new(family),
+        })
+    }
+
+    /// Reasoning and content both count
+    fn add(&mut self, text: &str) {
+        self.completion.add(text);
+    }
+
+    /// In dollars
+    fn estimate(&self) -> f64 {
+        self.prompt_cost + self.completion.count() as f64 * self.price.completion
+    }
+
+    fn is_over(&self) -> bool {
+        self.estimate() > self.max_cost
+    }
+
+    /// Says why on stderr
+    fn error(&self) -> OrtError {
+        let msg = "Stopped: estimated cost $".to_string()
+            + &utils::float_to_string(self.estimate(), 6)
+            + " is over --max-cost $"
+            + &utils::float_to_string(self.max_cost, 6)
+            + "\n";
+        syscall::write(2, msg.as_ptr().cast(), msg.len());
+        ort_error(ErrorKind::CostLimitExceeded, "over --max-cost")
+    }
+}
+
+/// `--reasoning-tokens`, checked on our side too, some providers ignore it.
+/// Well over the budget the rest of the reasoning is hidden, or with
+/// `--strict-reasoning` the request stops.
+struct ReasoningLimit {
+    reasoning: tokens::Counter,
+    is_strict: bool,
+    counts: ReasoningBudget,
+}
+
+impl ReasoningLimit {
+    fn new(opts: &PromptOpts, model_idx: usize) -> Option<Self> {
+        Some(ReasoningLimit {
+            reasoning: tokens::Counter::new(Family::from_model(&opts.models[model_idx])),
+            is_strict: opts.strict_reasoning,
+            counts: ReasoningBudget {
+                budget: opts.reasoning_tokens?,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Count some streamed reasoning
+    fn add(&mut self, text: &str) {
+        self.reasoning.add(text);
+        self.counts.estimate = self.reasoning.count() as u32;
+    }
+
+    /// Our count is an estimate, and providers go a little over, so only
+    /// well over counts
+    fn is_over(&self) -> bool {
+        self.counts.estimate as u64 * 100 > self.counts.budget as u64 * (100 + REASONING_SLACK_PCT)
+    }
+
+    fn notice(&self) -> String {
+        "\n[Reasoning is well over the ".to_string()
+            + &utils::with_thousands(&utils::num_to_string(self.counts.budget))
+            + " token budget, not showing the rest]\n"
+    }
+
+    /// Says why on stderr
+    fn error(&self) -> OrtError {
+        let msg = "Stopped: reasoning of ~".to_string()
+            + &utils::num_to_string(self.counts.estimate)
+            + " tokens is well over the --reasoning-tokens budget of "
+            + &utils::num_to_string(self.counts.budget)
+            + "\n";
+        syscall::write(2, msg.as_ptr().cast(), msg.len());
+        ort_error(
+            ErrorKind::ReasoningBudgetExceeded,
+            "over --reasoning-tokens",
+        )
+    }
+}
 
 pub(in crate::input) struct ActivePrompt {
     api_key: String,
@@ -353,6 +1232,12 @@ pub(in crate::input) struct ActivePrompt {
     tsc_calibration: Option<time::TscCalibration>,
     token_stream_start: Option<time::Ticks>,
     start: Option<time::Ticks>,
+    reasoning_start: Option<time::Ticks>,
+    content_start: Option<time::Ticks>,
+    last_chunk: Option<time::Ticks>,
+    /// When the stream ended, the timing stats stop here
+    end: Option<time::Ticks>,
+    chunk_gaps: Vec<Duration>,
     num_tokens: usize,
     is_start: bool,
     is_first_reasoning: bool,
@@ -360,7 +1245,54 @@ pub(in crate::input) struct ActivePrompt {
     line_buf: String,
 
     pending_tool_calls: Vec<ToolCall>,
+    images: Vec<Content>,
+    // Content of choices 1..n, when n > 1
+    other_choices: Vec<String>,
+    // The model declined, streamed like content
+    refusal: String,
+    finish_reason: Option<String>,
+    // The stream ended, by marker, EOF or error. `next` only returns None now.
+    is_stream_done: bool,
+    // Bytes of stream body read so far, to place a malformed event
+    stream_offset: usize,
+    // Why the latest event didn't parse, if no good one came after it
+    last_malformed: Option<String>,
+    // The server answered with one JSON document, not an event stream
+    is_json_body: bool,
+
+    /// Give up if no reasoning or content by then. Set before `start`.
+    pub first_token_deadline_ms: Option<u32>,
+    /// Stop once the estimated cost is over --max-cost. Set before `start`.
+    pub cost_limit: Option<CostLimit>,
+    // --reasoning-tokens, counting the reasoning as it streams
+    reasoning_limit: Option<ReasoningLimit>,
+    /// --deadline for the whole run, in syscall::monotonic_ms time. Set before `start`.
+    pub deadline: Option<u64>,
+    /// --queue-on-fail, where to save the request if the server can't be
+    /// reached. Set before `start`.
+    pub queue_path: Option<String>,
+    /// A request `--queue-on-fail` saved, sent as it is. Set before `start`.
+    pub body: Option<String>,
+    /// A 402 is followed by asking the config's `free_fallback`, so it isn't
+    /// fatal. Set before `start`.
+    pub has_free_fallback: bool,
+    // Absolute, in syscall::monotonic_ms time
+    first_token_deadline: Option<u64>,
+    // We hung up early, past --deadline or over --max-cost. OpenRouter
+    // still bills for what was made, so ask it for the cost.
+    is_cancelled: bool,
     logger: Option<Logger>,
+
+    // To ask for stats afterwards if the stream did not include usage
+    addrs: Vec<SocketAddr>,
+    generation_id: Option<String>,
+    has_usage: bool,
+    // The server will take another request on our connection
+    is_keep_alive: bool,
+    // Our id for the attempt that got an answer, the --trace trace id
+    client_id: Option<String>,
+    // `ort replay`, never touch the network
+    is_replay: bool,
 }
 
 impl ActivePrompt {
@@ -374,6 +1306,28 @@ impl ActivePrompt {
         model_idx: usize,
         env: Option<&Env>,
     ) -> OrtResult<Self> {
+        let logger = match env {
+            Some(env) => {
+                let mut l = Logger::new(env)?;
+                l.add_secret(&api_key);
+                for secret in [
+                    cfg.api_key.as_deref(),
+                    env.OPENROUTER_API_KEY,
+                    env.NVIDIA_API_KEY,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    l.add_secret(secret);
+                }
+                for secret in &cfg.redact {
+                    l.add_secret(secret);
+                }
+                Some(l)
+            }
+            None => None,
+        };
+        let reasoning_limit = ReasoningLimit::new(&opts, model_idx);
         Ok(ActivePrompt {
             api_key,
             cfg: cfg.clone(),
@@ -390,6 +1344,8 @@ impl ActivePrompt {
                     .expect("Missing model name"),
                 // Provider doesn't make sense for build.nvidia.com
                 provider: "".to_string(),
+                priority: opts.priority,
+                currency: cfg.currency_display,
                 ..Default::default()
             },
             // TODO: Should we warn this CPU doesn't have TSC calibration, so no timing?
@@ -398,27 +1354,65 @@ impl ActivePrompt {
             opts,
             token_stream_start: None,
             start: None,
+            reasoning_start: None,
+            content_start: None,
+            last_chunk: None,
+            end: None,
+            chunk_gaps: Vec::new(),
             num_tokens: 0,
             is_start: true,
             is_first_reasoning: true,
             is_first_content: true,
             line_buf: String::with_capacity(1024),
             pending_tool_calls: vec![],
-            logger: if let Some(env) = env {
-                Some(Logger::new(env)?)
-            } else {
-                None
-            },
+            images: vec![],
+            other_choices: vec![],
+            refusal: String::new(),
+            finish_reason: None,
+            is_stream_done: false,
+            stream_offset: 0,
+            last_malformed: None,
+            is_json_body: false,
+            first_token_deadline_ms: None,
+            first_token_deadline: None,
+            cost_limit: None,
+            reasoning_limit,
+            deadline: None,
+            queue_path: None,
+            body: None,
+            has_free_fallback: false,
+            is_cancelled: false,
+            addrs: vec![],
+            generation_id: None,
+            has_usage: false,
+            is_keep_alive: false,
+            client_id: None,
+            is_replay: false,
+            logger,
         })
     }
 
     /// Start the HTTP request
     pub fn start(&mut self) -> OrtResult<()> {
-        let body = match build_body(self.model_idx, &self.opts, &self.messages, &self.tools) {
-            Ok(b) => b,
-            Err(err) => {
-                print_string(c"FATAL: build_body: ", &err.as_string());
-                return Err(ort_error(ErrorKind::Other, "build body"));
+        // The prompt alone is too much, don't send it
+        if let Some(cl) = self.cost_limit.as_ref()
+            && cl.is_over()
+        {
+            return Err(cl.error());
+        }
+        let body = match self.body.take() {
+            // The hook already ran on it
+            Some(body) => body,
+            None => {
+                let body = match build_body(self.model_idx, &self.opts, &self.messages, &self.tools)
+                {
+                    Ok(b) => b,
+                    Err(err) => {
+                        utils::print_error(c"FATAL: build_body: ", &err.as_string());
+                        return Err(ort_error(ErrorKind::Other, "build body"));
+                    }
+                };
+                cli::run_pre_request_hook(&self.cfg, body)?
             }
         };
         if let Some(l) = self.logger.as_mut() {
@@ -426,14 +1420,19 @@ impl ActivePrompt {
         }
         let (host, port, base_path) = http::split_url(&self.cfg.base_url);
         self.start = Some(time::Ticks::now());
-        let addrs = if self.cfg.dns.is_empty() {
+        trace::begin();
+        self.first_token_deadline = self
+            .first_token_deadline_ms
+            .map(|ms| syscall::monotonic_ms() + ms as u64);
+        let addrs: Vec<SocketAddr> = if self.cfg.dns.is_empty() {
             let ips = match unsafe { resolver::resolve(host) } {
                 Ok(ips) => ips,
                 Err(err) => {
-                    print_string(c"FATAL: resolving host: ", &err.as_string());
-                    return Err(ort_error(ErrorKind::DnsResolveFailed, ""));
+                    utils::print_error(c"FATAL: resolving host: ", &err.as_string());
+                    return Err(self.unreachable(&body, ErrorKind::DnsResolveFailed, ""));
                 }
             };
+            trace::mark("dns");
             ips.into_iter()
                 .map(|ip| SocketAddr::new(IpAddr::V4(ip), port))
                 .collect()
@@ -447,57 +1446,252 @@ impl ActivePrompt {
                 })
                 .collect()
         };
-        let mut buf_reader =
-            match http::chat_completions(&self.api_key, host, base_path, addrs, &body) {
-                Ok(r) => r,
+        self.addrs = addrs;
+        let mut is_gzip = self.cfg.is_gzip(body.len());
+        // Left open by an earlier request. If it fails us, a new one.
+        let mut conn = pool::take(host, port, self.deadline);
+        let (buf_reader, header, client_id) = loop {
+            let is_reused = conn.is_some();
+            let client_id = request_id::attempt();
+            trace::set_id(&client_id);
+            let mut buf_reader = match http::chat_completions(
+                &self.api_key,
+                host,
+                base_path,
+                self.addrs.clone(),
+                &body,
+                is_gzip,
+                self.deadline,
+                conn.take(),
+                &client_id,
+            ) {
+                Ok(r) => r,
+                Err(err) => {
+                    log_failed_attempt(self.logger.as_mut(), &client_id, &err.as_string());
+                    if is_reused {
+                        continue;
+                    }
+                    if self.is_past_run_deadline() {
+                        return Err(self.timeout_error());
+                    }
+                    utils::print_error(c"FATAL running chat_completions: ", &err.as_string());
+                    if matches!(err.kind, ErrorKind::HttpConnectError | ErrorKind::NoNetwork) {
+                        return Err(self.unreachable(
+                            &body,
+                            ErrorKind::HttpConnectError,
+                            "running chat_completions",
+                        ));
+                    }
+                    return Err(ort_error(err.kind, "running chat_completions"));
+                }
+            };
+            let res = http::read_header(&mut buf_reader);
+            if let Err(err) = &res {
+                log_failed_attempt(self.logger.as_mut(), &client_id, &err.as_string());
+            }
+            match res {
+                Ok(header) => break (buf_reader, header, client_id),
+                Err(err) if is_reused && err.is_no_response() => continue,
+                // 415 Unsupported Media Type
+                Err(err) if is_gzip && err.status_line().contains(" 415") => {
+                    let msg = "Server refused a gzip request body, sending it uncompressed. Remove gzip_min_bytes from the config.\n";
+                    utils::warn(msg);
+                    is_gzip = false;
+                }
+                Err(_) if self.is_past_run_deadline() => {
+                    return Err(self.timeout_error());
+                }
+                Err(err) if err.status_line().contains(" 402") => {
+                    if !self.has_free_fallback {
+                        utils::print_error(c"FATAL reading response header: ", &err.as_string());
+                    }
+                    return Err(ort_error(
+                        ErrorKind::InsufficientCredits,
+                        "reading response header",
+                    ));
+                }
+                Err(err) if err.status_line().contains(" 429") => {
+                    utils::print_error(c"FATAL reading response header: ", &err.as_string());
+                    return Err(ort_error(ErrorKind::RateLimited, "reading response header"));
+                }
+                Err(err) if err.status_code().is_some_and(|s| !is_retryable_status(s)) => {
+                    utils::print_error(c"FATAL reading response header: ", &err.as_string());
+                    return Err(ort_error(
+                        ErrorKind::HttpClientError,
+                        "reading response header",
+                    ));
+                }
+                Err(err) => {
+                    utils::print_error(c"FATAL reading response header: ", &err.as_string());
+                    return Err(ort_error(
+                        ErrorKind::HttpStatusError,
+                        "reading response header",
+                    ));
+                }
+            }
+        };
+        if self.cfg.show_connection {
+            self.stats.connection = Some(Box::new(Connection {
+                peer: socket::peer_addr(buf_reader.as_fd()),
+                protocol: header
+                    .status_line
+                    .split_once(' ')
+                    .map(|(p, _)| p)
+                    .unwrap_or_default()
+                    .to_string(),
+                colo: header.cf_colo().map(|c| c.to_string()),
+            }));
+        }
+        self.is_keep_alive = header.is_keep_alive();
+        if let Some(l) = self.logger.as_mut() {
+            l.log(&header_log_line(&client_id, &header));
+        }
+        self.client_id = Some(client_id);
+        self.reader = Some(body_reader(buf_reader, header.body()));
+        self.is_json_body = self.check_body_kind(&header)?;
+
+        Ok(())
+    }
+
+    /// The request never reached the server. With --queue-on-fail it is
+    /// saved for `ort flush-queue` and that's NoNetwork, otherwise `kind`.
+    fn unreachable(&self, body: &str, kind: ErrorKind, context: &'static str) -> OrtError {
+        let Some(path) = self.queue_path.as_deref() else {
+            return ort_error(kind, context);
+        };
+        if let Err(err) = queue::save(path, body) {
+            utils::print_error(c"Queueing the request: ", &err.as_string());
+            return ort_error(kind, context);
+        }
+        let msg = "Queued the request as ".to_string() + path + "\n";
+        utils::warn(&msg);
+        ort_error(
+            ErrorKind::NoNetwork,
+            "server unreachable, send the request later with `ort flush-queue`",
+        )
+    }
+
+    /// `start` for `ort replay`: read a captured response from `source`
+    /// instead of sending the request. `has_header` if it starts with the
+    /// HTTP response header, otherwise it is only the SSE stream.
+    pub(in crate::input) fn start_replay<T: Read + AsFd + 'static>(
+        &mut self,
+        source: T,
+        has_header: bool,
+    ) -> OrtResult<()> {
+        self.is_replay = true;
+        self.start = Some(time::Ticks::now());
+        let mut buf_reader = OrtBufReader::new(source);
+        let header = if has_header {
+            match http::read_header(&mut buf_reader) {
+                Ok(header) => Some(header),
                 Err(err) => {
-                    print_string(c"FATAL running chat_completions: ", &err.as_string());
-                    return Err(ort_error(ErrorKind::Other, "running chat_completions"));
+                    utils::print_error(c"Captured response: ", &err.as_string());
+                    return Err(ort_error(
+                        ErrorKind::HttpStatusError,
+                        "reading response header",
+                    ));
                 }
-            };
-
-        match http::skip_header(&mut buf_reader) {
-            Ok(http::ResponseBody::Chunked) => {
-                // Transfer encoding chunked, this is what OpenRouter does.
-                let chunk_reader = chunked::read::<_, MAX_CHUNK_SIZE>(buf_reader);
-                self.reader = Some(Box::new(chunk_reader));
-            }
-            Ok(http::ResponseBody::ContentLength(len)) => {
-                // Content-Length with keep-alive. Stop at the body length.
-                // Rare except for upstream errors which are non-streaming.
-                let content_reader = ContentLengthReader::new(buf_reader, len);
-                self.reader = Some(Box::new(OrtBufReader::new(content_reader)));
-            }
-            Ok(http::ResponseBody::UntilEof) => {
-                // OpenRouter does chunked. Only seen this on local dev server.
-                self.reader = Some(Box::new(buf_reader));
-            }
-            Err(err) => {
-                print_string(c"FATAL running skip_header: ", &err.as_string());
-                return Err(ort_error(ErrorKind::HttpStatusError, "running skip_header"));
             }
+        } else {
+            None
+        };
+        let body = header
+            .as_ref()
+            .map_or(http::ResponseBody::UntilEof, |h| h.body());
+        self.reader = Some(body_reader(buf_reader, body));
+        if let Some(header) = header {
+            self.is_json_body = self.check_body_kind(&header)?;
         }
-
         Ok(())
     }
 
+    /// Whether a 200 response is one JSON document rather than an event
+    /// stream. Anything else, e.g. a proxy's login page, is an error with
+    /// the start of it. Call once `reader` is set.
+    fn check_body_kind(&mut self, header: &http::ResponseHeader) -> OrtResult<bool> {
+        match header.body_kind() {
+            http::BodyKind::EventStream => Ok(false),
+            http::BodyKind::Json => Ok(true),
+            http::BodyKind::Other => {
+                self.line_buf.clear();
+                if let Some(reader) = self.reader.as_mut() {
+                    let _ = reader.read_line(&mut self.line_buf);
+                }
+                let mut msg =
+                    "Content-Type ".to_string() + header.content_type().unwrap_or_default();
+                let start = self.line_buf.trim();
+                if !start.is_empty() {
+                    msg.push_str(": ");
+                    msg.push_str(&start[..start.floor_char_boundary(MALFORMED_SNIPPET_BYTES)]);
+                }
+                utils::print_error(c"Not an event stream: ", &msg);
+                self.is_stream_done = true;
+                Err(ort_error(
+                    ErrorKind::ResponseStreamError,
+                    "unexpected Content-Type",
+                ))
+            }
+        }
+    }
+
     pub fn next(&mut self) -> OrtResult<Option<Vec<Response>>> {
         let mut queue = vec![];
 
+        // Checked here so the text that went over is still shown
+        if let Some(cl) = self.cost_limit.as_ref()
+            && cl.is_over()
+        {
+            self.is_cancelled = true;
+            return Err(cl.error());
+        }
+        if let Some(rl) = self.reasoning_limit.as_ref()
+            && rl.is_strict
+            && rl.is_over()
+        {
+            self.is_cancelled = true;
+            return Err(rl.error());
+        }
+        if self.is_stream_done {
+            return Ok(None);
+        }
+
         loop {
             self.line_buf.clear();
-            match self
-                .reader
-                .as_mut()
-                .unwrap()
-                .read_line(&mut self.line_buf)?
-            {
-                0 => {
+            if let Some(deadline) = self.read_deadline() {
+                let now = syscall::monotonic_ms();
+                if now >= deadline {
+                    return Err(self.timeout_error());
+                }
+                // Don't block in read past the deadline
+                socket::set_read_timeout(self.as_fd(), deadline - now);
+            }
+            let line_offset = self.stream_offset;
+            match self.read_sse_line() {
+                Ok(0) => {
                     // EOF
-                    return Ok(None);
+                    return Ok(self.end_stream(queue));
+                }
+                Ok(n) => {
+                    self.stream_offset += n;
                 }
-                _ => {
-                    // success
+                // The read timeout can fire a hair before our clock agrees
+                Err(err)
+                    if self.read_deadline().is_some()
+                        && (matches!(err.kind, ErrorKind::WouldBlock)
+                            || self.is_past_deadline()) =>
+                {
+                    return Err(self.timeout_error());
+                }
+                // The answer was complete. Some servers hang up rather than
+                // send a marker or the last chunk.
+                Err(_) if self.finish_reason.is_some() => {
+                    return Ok(self.end_stream(queue));
+                }
+                Err(err) => {
+                    // Nothing more will come, the caller can finish up
+                    self.is_stream_done = true;
+                    return Err(err);
                 }
             }
             let line = self.line_buf.trim();
@@ -507,22 +1701,38 @@ impl ActivePrompt {
             if self.is_start {
                 // Very first message from server, usually
                 // : OPENROUTER PROCESSING
+                // but it might go straight to the data.
                 queue.push(Response::Start);
                 self.is_start = false;
-                return Ok(Some(queue));
+                if line.is_empty() || line.starts_with(':') {
+                    return Ok(Some(queue));
+                }
             }
 
             // SSE heartbeats and blank lines
             if line.is_empty() || line.starts_with(':') {
                 continue;
             }
-            // Skip HTTP headers
-            let Some(data) = line.strip_prefix("data: ") else {
+            if let Some(event) = line.strip_prefix("event:") {
+                if END_EVENTS.contains(&event.trim()) {
+                    return Ok(self.end_stream(queue));
+                }
                 continue;
-            };
-            if data == "[DONE]" {
-                return Ok(None);
             }
+            let data = if self.is_json_body {
+                // The whole body, one response in the same shape as an event
+                line
+            } else {
+                // Skip HTTP headers. The space after the colon is optional.
+                let Some(data) = line.strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.strip_prefix(' ').unwrap_or(data);
+                if data == "[DONE]" {
+                    return Ok(self.end_stream(queue));
+                }
+                data
+            };
 
             // Log now that it's interesting
             if let Some(l) = self.logger.as_mut() {
@@ -532,11 +1742,36 @@ impl ActivePrompt {
             // Each data: line is a JSON chunk in OpenAI streaming format
             match ChatCompletionsResponse::from_json(data) {
                 Ok(mut v) => {
+                    self.last_malformed = None;
+                    if self.generation_id.is_none() {
+                        self.generation_id = v.id.take();
+                    }
+                    // Usage has the final say, this is for failover
+                    if self.stats.provider.is_empty()
+                        && let Some(provider) = v.provider.as_ref()
+                    {
+                        self.stats.provider = provider.clone();
+                    }
+                    // The provider failed part way, otherwise we'd end with an empty answer
+                    if let Some(err) = v.error {
+                        let msg = if self.stats.provider.is_empty() {
+                            err
+                        } else {
+                            self.stats.provider.clone() + ": " + &err
+                        };
+                        queue.push(Response::Error(msg));
+                        return Ok(Some(queue));
+                    }
                     // Handle last message which contains the "usage" key
                     // Do this before getting choices because it's empty on last message.
                     if let Some(usage) = v.usage {
+                        self.has_usage = true;
                         self.stats.cost_in_cents = Some(usage.cost as f64 * 100.0); // convert to cents
+                        self.stats.is_byok = usage.is_byok;
+                        self.stats.upstream_cost = usage.upstream_cost;
                         self.stats.web_search_requests = usage.web_search_requests;
+                        self.stats.completion_tokens = usage.completion_tokens;
+                        self.stats.reasoning_tokens = usage.reasoning_tokens;
                         if let Some(provider) = v.provider {
                             self.stats.provider = provider;
                         }
@@ -546,10 +1781,24 @@ impl ActivePrompt {
                     }
 
                     // Standard OpenAI stream delta shape
-                    let Some(choice) = v.choices.pop() else {
+                    // With --n the choices are interleaved. Stream the first,
+                    // collect the others for the end.
+                    let mut primary = None;
+                    for c in v.choices {
+                        if c.index == 0 {
+                            primary = Some(c);
+                        } else {
+                            self.collect_choice(c);
+                        }
+                    }
+                    let Some(mut choice) = primary else {
                         continue;
                     };
 
+                    // Generated images usually arrive whole in a single chunk
+                    let has_images = !choice.delta.images.is_empty();
+                    self.images.append(&mut choice.delta.images);
+
                     let has_reasoning = choice
                         .delta
                         .reasoning
@@ -560,12 +1809,30 @@ impl ActivePrompt {
                     let has_content = content.map(|x| !x.is_empty()).unwrap_or(false);
                     let has_tool_calls = !choice.delta.tool_calls.is_empty();
                     let is_finished = choice.finish_reason.is_some();
+                    if let Some(refusal) = choice.delta.refusal.as_deref() {
+                        self.refusal.push_str(refusal);
+                    }
+                    if is_finished {
+                        self.finish_reason = choice.finish_reason.clone();
+                    }
 
-                    if !(has_reasoning || has_content || has_tool_calls || is_finished) {
+                    if !(has_reasoning
+                        || has_content
+                        || has_tool_calls
+                        || has_images
+                        || is_finished)
+                    {
                         continue;
                     }
 
+                    if self.first_token_deadline.take().is_some() {
+                        socket::set_read_timeout(self.as_fd(), 0);
+                    }
+
                     // Record time to first token
+                    if self.token_stream_start.is_none() {
+                        trace::mark("first_content_token");
+                    }
                     if self.stats.time_to_first_token.is_none() {
                         let first_token = time::Ticks::now();
                         self.stats.time_to_first_token = self
@@ -574,6 +1841,21 @@ impl ActivePrompt {
                         self.token_stream_start = Some(time::Ticks::now());
                     }
 
+                    // Gap since the previous chunk that carried text
+                    if has_reasoning || has_content {
+                        let now = time::Ticks::now();
+                        if let (Some(tc), Some(prev)) = (self.tsc_calibration, self.last_chunk) {
+                            self.chunk_gaps.push(time::elapsed_duration(prev, now, tc));
+                        }
+                        self.last_chunk = Some(now);
+                        if has_reasoning && self.reasoning_start.is_none() {
+                            self.reasoning_start = Some(now);
+                        }
+                        if has_content && self.content_start.is_none() {
+                            self.content_start = Some(now);
+                        }
+                    }
+
                     // Handle tool calls
                     if has_tool_calls {
                         // TODO: Think about ownership, reduce copying
@@ -594,6 +1876,12 @@ impl ActivePrompt {
                         && !reasoning_content.is_empty()
                     {
                         self.num_tokens += 1;
+                        if let Some(cl) = self.cost_limit.as_mut() {
+                            cl.add(reasoning_content);
+                        }
+                        if let Some(rl) = self.reasoning_limit.as_mut() {
+                            rl.add(reasoning_content);
+                        }
                         if self.is_first_reasoning {
                             if reasoning_content.trim().is_empty() {
                                 // Don't allow starting with carriage return or blank space, that messes up the display
@@ -602,9 +1890,20 @@ impl ActivePrompt {
                             queue.push(Response::Think(ThinkEvent::Start));
                             self.is_first_reasoning = false;
                         }
-                        let r_event =
-                            Response::Think(ThinkEvent::Content(reasoning_content.to_string()));
-                        queue.push(r_event);
+                        match self.reasoning_limit.as_mut() {
+                            Some(rl) if !rl.is_strict && rl.is_over() => {
+                                if !rl.counts.is_hidden {
+                                    rl.counts.is_hidden = true;
+                                    queue.push(Response::Think(ThinkEvent::Content(rl.notice())));
+                                }
+                            }
+                            _ => {
+                                let r_event = Response::Think(ThinkEvent::Content(
+                                    reasoning_content.to_string(),
+                                ));
+                                queue.push(r_event);
+                            }
+                        }
                     }
 
                     // Handle regular content
@@ -612,6 +1911,9 @@ impl ActivePrompt {
                         && !content.is_empty()
                     {
                         self.num_tokens += 1;
+                        if let Some(cl) = self.cost_limit.as_mut() {
+                            cl.add(content);
+                        }
                         if self.is_first_content && content.trim().is_empty() {
                             // Don't allow starting with carriage return or blank space, that messes up the display
                             if queue.is_empty() {
@@ -638,7 +1940,21 @@ impl ActivePrompt {
                     }
                 }
                 Err(err) => {
-                    utils::print_string(c"Malformed: ", &err);
+                    self.stats.malformed_events += 1;
+                    let count = self.stats.malformed_events;
+                    if count >= MAX_MALFORMED_EVENTS {
+                        self.is_stream_done = true;
+                        queue.push(Response::Error("Malformed event: ".to_string() + &err));
+                        return Ok(Some(queue));
+                    }
+                    if self.opts.verbose && count <= MAX_LOGGED_MALFORMED {
+                        utils::warn(&malformed_message(line_offset, data, &err));
+                        if count == MAX_LOGGED_MALFORMED {
+                            utils::warn("Not showing any more malformed events\n");
+                        }
+                    }
+                    self.last_malformed = Some(err.into_owned());
+                    continue;
                 }
             }
 
@@ -652,20 +1968,320 @@ impl ActivePrompt {
     }
 
     pub fn stop(&mut self) -> Stats {
+        // Cancelled or failed, the stream ends now. Either way the times stop
+        // before the cleanup and stats fetch below.
+        let end = *self.end.get_or_insert_with(time::Ticks::now);
+        let mut stream_elapsed_time = None;
         if let Some(tc) = self.tsc_calibration {
-            let now = time::Ticks::now();
             if let Some(start) = self.start.take() {
-                self.stats.elapsed_time = time::elapsed_duration(start, now, tc);
+                self.stats.elapsed_time = time::elapsed_duration(start, end, tc);
             }
-            if let Some(token_stream_start) = self.token_stream_start {
-                let stream_elapsed_time = time::elapsed_duration(token_stream_start, now, tc);
-                self.stats.inter_token_latency_ms =
-                    stream_elapsed_time.as_millis() / max(self.num_tokens, 1) as u128;
+            if let Some(reasoning_start) = self.reasoning_start {
+                let reasoning_end = self.content_start.unwrap_or(end);
+                self.stats.reasoning_time =
+                    Some(time::elapsed_duration(reasoning_start, reasoning_end, tc));
             }
-        };
+            if let Some(content_start) = self.content_start {
+                self.stats.content_time = Some(time::elapsed_duration(content_start, end, tc));
+            }
+            stream_elapsed_time = self
+                .token_stream_start
+                .map(|token_stream_start| time::elapsed_duration(token_stream_start, end, tc));
+        }
+        // Before fetching the stats, that's another request
+        if self.client_id.is_some() {
+            trace::mark("stream_end");
+        }
+        trace::finish();
+        // First, so fetching the stats can use it
+        self.release_connection();
+        if !self.has_usage && !self.is_replay && self.has_generation_record() {
+            if self.is_cancelled {
+                self.reader = None;
+            }
+            if let Err(err) = self.fetch_late_stats() {
+                utils::warn(&("Fetching generation stats: ".to_string() + &err.as_string() + "\n"));
+            }
+        }
+        if let Some(stream_elapsed_time) = stream_elapsed_time {
+            // Prefer the provider's token count, a chunk can carry several tokens.
+            // That can need the stats fetch, the time doesn't.
+            let num_tokens = self
+                .stats
+                .completion_tokens
+                .map(|t| t as usize)
+                .unwrap_or(self.num_tokens);
+            self.stats.inter_token_latency_ms =
+                (stream_elapsed_time.as_millis() / max(num_tokens, 1) as u128) as u32;
+        }
+        self.stats
+            .set_chunk_gaps(core::mem::take(&mut self.chunk_gaps));
+        self.stats.samples = self.opts.n.unwrap_or(1);
+        self.stats.reasoning_budget = self.reasoning_limit.as_ref().map(|rl| rl.counts);
         self.stats.clone()
     }
 
+    /// Keep the connection for the next request, if the stream finished
+    /// and the server didn't say it would close it
+    fn release_connection(&mut self) {
+        if !self.is_keep_alive || !self.is_stream_done || self.is_cancelled {
+            return;
+        }
+        if let Some(conn) = self.reader.take().and_then(|r| r.into_idle()) {
+            let (host, port, _) = http::split_url(&self.cfg.base_url);
+            pool::put(host, port, conn);
+        }
+    }
+
+    /// This try so far, for `--retries`. Unlike `stop` it doesn't ask the
+    /// server for anything.
+    pub(in crate::input) fn failed_attempt(&self, error: String) -> Attempt {
+        let elapsed = match (self.tsc_calibration, self.start) {
+            (Some(tc), Some(start)) => time::elapsed_duration(start, time::Ticks::now(), tc),
+            _ => Duration::ZERO,
+        };
+        Attempt {
+            error: Some(error),
+            elapsed,
+            time_to_first_token: self.stats.time_to_first_token,
+        }
+    }
+
+    /// Provider serving the request, empty if not known yet
+    pub fn provider(&self) -> &str {
+        &self.stats.provider
+    }
+
+    /// When a read gives up, in `syscall::monotonic_ms`: the sooner of
+    /// --deadline and, until the first token, --first-token-deadline.
+    pub(in crate::input) fn read_deadline(&self) -> Option<u64> {
+        match (self.deadline, self.first_token_deadline) {
+            (Some(run), Some(first)) => Some(run.min(first)),
+            (run, first) => run.or(first),
+        }
+    }
+
+    fn is_past_deadline(&self) -> bool {
+        self.read_deadline()
+            .is_some_and(|deadline| syscall::monotonic_ms() >= deadline)
+    }
+
+    fn is_past_run_deadline(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| syscall::monotonic_ms() >= deadline)
+    }
+
+    /// A read timed out, say which deadline it was
+    fn timeout_error(&mut self) -> OrtError {
+        if self.deadline.is_some()
+            && (self.deadline == self.read_deadline() || self.is_past_run_deadline())
+        {
+            self.is_cancelled = true;
+            ort_error(ErrorKind::DeadlineExceeded, "")
+        } else {
+            ort_error(ErrorKind::FirstTokenTimeout, "")
+        }
+    }
+
+    fn collect_choice(&mut self, choice: Choice) {
+        let n = self.opts.n.unwrap_or(1);
+        if choice.index >= n {
+            // Didn't ask for it
+            return;
+        }
+        let idx = choice.index as usize - 1;
+        if self.other_choices.len() <= idx {
+            self.other_choices.resize(idx + 1, String::new());
+        }
+        if let Some(text) = choice.delta.text() {
+            // Each sample is billed
+            if let Some(cl) = self.cost_limit.as_mut() {
+                cl.add(text);
+            }
+            self.other_choices[idx].push_str(text);
+        }
+    }
+
+    /// Why we got no answer, if the model refused or the provider's
+    /// content filter cut it off.
+    pub fn refusal(&self) -> Option<String> {
+        if !self.refusal.is_empty() {
+            return Some("Model refused: ".to_string() + self.refusal.trim());
+        }
+        if self.finish_reason.as_deref() == Some("content_filter") {
+            return Some("Response stopped by the provider's content filter".to_string());
+        }
+        None
+    }
+
+    /// One whole line of the SSE stream into `line_buf`, or with
+    /// `is_json_body` the whole body. The readers give long lines in pieces,
+    /// put them back together. A line over `max_line_bytes` is dropped as it
+    /// arrives and `line_buf` is left blank, so memory stays bounded.
+    /// Returns 0 on EOF.
+    fn read_sse_line(&mut self) -> OrtResult<usize> {
+        let max = self.cfg.max_line_bytes();
+        let reader = self.reader.as_mut().unwrap();
+        let mut total = 0;
+        let mut skipped = 0;
+        loop {
+            let n = reader.read_line(&mut self.line_buf)?;
+            if n == 0 {
+                break;
+            }
+            total += n;
+            if self.line_buf.ends_with('\n') && !self.is_json_body {
+                break;
+            }
+            if skipped != 0 || self.line_buf.len() > max {
+                skipped += self.line_buf.len();
+                self.line_buf.clear();
+            }
+        }
+        if skipped != 0 {
+            skipped += self.line_buf.len();
+            self.line_buf.clear();
+            let msg = "Skipped a ".to_string()
+                + &utils::format_size(skipped as u64)
+                + " event, over max_line_bytes\n";
+            utils::warn(&msg);
+        }
+        Ok(total)
+    }
+
+    /// No more reads. Whatever is queued still goes out first.
+    /// Ending on a malformed event, before the answer finished, means the
+    /// stream was cut short.
+    fn end_stream(&mut self, mut queue: Vec<Response>) -> Option<Vec<Response>> {
+        self.is_stream_done = true;
+        self.end.get_or_insert_with(time::Ticks::now);
+        if let Some(err) = self.last_malformed.take()
+            && self.finish_reason.is_none()
+        {
+            queue.push(Response::Error("Malformed event: ".to_string() + &err));
+        }
+        (!queue.is_empty()).then_some(queue)
+    }
+
+    /// Full text of the second and later choices
+    pub fn take_other_choices(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.other_choices)
+    }
+
+    /// Images the model generated, if we asked for them
+    pub fn take_images(&mut self) -> Vec<Content> {
+        core::mem::take(&mut self.images)
+    }
+
+    /// Some providers never send the final usage chunk. Ask OpenRouter for
+    /// the stats of the generation instead.
+    /// `deadline` is in `syscall::monotonic_ms` time.
+    fn fetch_generation_stats(&mut self, deadline: Option<u64>) -> OrtResult<()> {
+        let Some(generation_id) = self.generation_id.as_deref() else {
+            // Not OpenRouter, or the request failed early
+            return Ok(());
+        };
+        let (host, port, base_path) = http::split_url(&self.cfg.base_url);
+        // Usually the connection the answer came on
+        let mut conn = pool::take(host, port, deadline);
+        let (reader, header) = loop {
+            let is_reused = conn.is_some();
+            let tls = match http::generation(
+                &self.api_key,
+                host,
+                base_path,
+                self.addrs.clone(),
+                generation_id,
+                deadline,
+                conn.take(),
+            ) {
+                Ok(tls) => tls,
+                Err(_) if is_reused => continue,
+                Err(err) => return Err(err),
+            };
+            let mut reader = OrtBufReader::new(tls);
+            match http::read_header(&mut reader) {
+                Ok(header) => break (reader, header),
+                Err(err) if is_reused && err.is_no_response() => continue,
+                Err(err) => return Err(err.into()),
+            }
+        };
+        let (body, rest) = http::read_body_keep(reader, header.body())?;
+        if header.is_keep_alive()
+            && let Some(tls) = rest.and_then(|r| r.into_inner())
+        {
+            pool::put(host, port, tls);
+        }
+        let generation = Generation::from_json(&body).map_err(|err| {
+            utils::print_error(c"Malformed generation: ", &err);
+            ort_error(ErrorKind::FormatError, "generation JSON")
+        })?;
+
+        self.stats.cost_in_cents = Some(generation.total_cost as f64 * 100.0); // convert to cents
+        self.stats.completion_tokens = generation.completion_tokens;
+        self.stats.reasoning_tokens = generation.reasoning_tokens;
+        self.stats.is_cancelled = generation.is_cancelled;
+        if let Some(provider) = generation.provider {
+            self.stats.provider = provider;
+        }
+        if let Some(model) = generation.model {
+            self.stats.used_model = model;
+        }
+        Ok(())
+    }
+
+    /// After a 402, what the key has bought and used
+    fn fetch_credits(&mut self) -> OrtResult<Credits> {
+        let (host, _, base_path) = http::split_url(&self.cfg.base_url);
+        let deadline = syscall::monotonic_ms() + CREDITS_MS;
+        let tls = http::credits(
+            &self.api_key,
+            host,
+            base_path,
+            self.addrs.clone(),
+            Some(deadline),
+        )?;
+        let mut reader = OrtBufReader::new(tls);
+        // Quietly, the 402 was the error
+        let header = http::read_header(&mut reader)
+            .map_err(|_| ort_error(ErrorKind::HttpStatusError, "credits"))?;
+        let body = http::read_body(reader, header.body())?;
+        Credits::from_json(&body).ok_or(ort_error(ErrorKind::FormatError, "credits JSON"))
+    }
+
+    /// OpenRouter keeps a record of each generation, other servers using
+    /// the same API don't
+    fn has_generation_record(&self) -> bool {
+        self.cfg.base_url.contains("openrouter.ai")
+            || self
+                .generation_id
+                .as_deref()
+                .is_some_and(|id| id.starts_with("gen-"))
+    }
+
+    /// The stats of a generation with no usage chunk, or that we hung up on.
+    /// OpenRouter's record of it takes a moment to appear, so try a few
+    /// times, for at most `LATE_STATS_MS` and never past `--deadline`. The
+    /// cost stays unknown when that has passed.
+    fn fetch_late_stats(&mut self) -> OrtResult<()> {
+        let now = syscall::monotonic_ms();
+        let mut deadline = now + LATE_STATS_MS;
+        if let Some(run_deadline) = self.deadline {
+            deadline = deadline.min(run_deadline);
+        }
+        if deadline <= now {
+            return Ok(());
+        }
+        loop {
+            let res = self.fetch_generation_stats(Some(deadline));
+            let now = syscall::monotonic_ms();
+            if res.is_ok() || now + LATE_STATS_RETRY_MS >= deadline {
+                return res;
+            }
+            syscall::sleep_ms(LATE_STATS_RETRY_MS);
+        }
+    }
+
     /*
     fn has_pending_data(&self) -> bool {
         self.reader
@@ -676,6 +2292,69 @@ impl ActivePrompt {
     */
 }
 
+/// The SSE lines of a response body, however the server framed it
+fn body_reader<T: Read + AsFd + 'static>(
+    buf_reader: OrtBufReader<T>,
+    body: http::ResponseBody,
+) -> Box<dyn PromptReader> {
+    match body {
+        http::ResponseBody::Chunked => {
+            // Transfer encoding chunked, this is what OpenRouter does.
+            Box::new(chunked::read::<_, MAX_CHUNK_SIZE>(buf_reader))
+        }
+        http::ResponseBody::ContentLength(len) => {
+            // Content-Length with keep-alive. Stop at the body length.
+            // Rare except for upstream errors which are non-streaming.
+            let content_reader = ContentLengthReader::new(buf_reader, len);
+            Box::new(OrtBufReader::new(content_reader))
+        }
+        http::ResponseBody::UntilEof => {
+            // OpenRouter does chunked. Only seen this on local dev server.
+            Box::new(buf_reader)
+        }
+    }
+}
+
+/// An attempt that got no usable response, for the log
+fn log_failed_attempt(logger: Option<&mut Logger>, client_id: &str, err: &str) {
+    let Some(l) = logger else {
+        return;
+    };
+    let mut line = String::with_capacity(64 + err.len());
+    line.push_str("{\"client_request_id\":");
+    let _ = write_json_str(&mut line, client_id);
+    line.push_str(",\"error\":");
+    let _ = write_json_str(&mut line, err.trim_end_matches('\0').trim());
+    line.push('}');
+    l.log(&line);
+}
+
+/// The response status, request ids and rate limits as a JSON line for the log
+fn header_log_line(client_id: &str, header: &http::ResponseHeader) -> String {
+    let mut out = String::with_capacity(128);
+    out.push_str("{\"client_request_id\":");
+    let _ = write_json_str(&mut out, client_id);
+    out.push_str(",\"status\":");
+    out.push_str(&utils::num_to_string(header.status));
+    let request_id = header.request_id().map(|id| ("x-request-id", id));
+    for (name, value) in request_id.into_iter().chain(header.ratelimit()) {
+        out.push(',');
+        let _ = write_json_str(&mut out, name);
+        out.push(':');
+        let _ = write_json_str(&mut out, value);
+    }
+    out.push('}');
+    out
+}
+
+impl Drop for ActivePrompt {
+    /// A request that failed, or is being retried, never got to `stop`.
+    /// Its spans show where the time went.
+    fn drop(&mut self) {
+        trace::finish();
+    }
+}
+
 impl AsFd for ActivePrompt {
     fn as_fd(&self) -> i32 {
         self.reader.as_ref().unwrap().as_fd()
@@ -684,40 +2363,477 @@ impl AsFd for ActivePrompt {
 
 /// Find the most recent file in `dir` that starts with `filename_prefix`.
 /// Uses the minimal amount of disk access to go as fast as possible.
-fn most_recent(dir: &str, filename_prefix: &str) -> OrtResult<String> {
+fn by_recency(dir: &str, filename_prefix: &str, suffix: &str) -> OrtResult<Vec<String>> {
     let c_dir = CString::new(dir)
-        .map_err(|_| ort_error(ErrorKind::FileReadFailed, "Null byte in most_recent dir"))?;
+        .map_err(|_| ort_error(ErrorKind::FileReadFailed, "Null byte in by_recency dir"))?;
     let dir_files = dir::DirFiles::new(c_dir.as_c_str())?;
 
-    let mut most_recent_file: Option<(String, time::Instant)> = None;
+    let mut files: Vec<(String, time::Instant)> = Vec::new();
     for name in dir_files {
-        if !name.starts_with(filename_prefix) {
+        // The suffix leaves out lock files and another run's temporary file
+        if !name.starts_with(filename_prefix) || !name.ends_with(suffix) {
             continue;
         }
         let path = dir.to_string() + "/" + &name;
         let c_name = CString::new(path.clone()).map_err(|_| {
             ort_error(
                 ErrorKind::FileReadFailed,
-                "Null byte in most_recent_file name",
+                "Null byte in by_recency file name",
             )
         })?;
         let modified_time = file::last_modified(c_name.as_c_str())?;
+        files.push((path, modified_time));
+    }
+    files.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(core::cmp::Ordering::Equal));
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
 
-        if let Some((_, prev_time)) = &most_recent_file {
-            if modified_time > *prev_time {
-                most_recent_file = Some((path, modified_time));
-            }
-        } else {
-            most_recent_file = Some((path, modified_time));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_last_skips_damaged() {
+        let state_home =
+            "/tmp/ort-load-last-test-".to_string() + &utils::num_to_string(syscall::getpid());
+        let dir = state_home.clone() + "/ort";
+        utils::ensure_dir_all_exist(&dir);
+        let write = |name: &str, contents: &str| {
+            let path = CString::new(dir.clone() + "/" + name).unwrap();
+            let mut f = unsafe { file::File::create(path.as_bytes_with_nul()) }.unwrap();
+            f.write_str(contents).unwrap();
+        };
+        let env = Env {
+            TMUX_PANE: Some("%7"),
+            XDG_STATE_HOME: Some(state_home.leak()),
+            ..Env::default()
+        };
+
+        // Oldest first
+        write(
+            "last-8.json",
+            r#"{"opts":{},"messages":[{"role":"user","content":"Hi"}]}"#,
+        );
+        syscall::sleep_ms(20);
+        write("last-7.json", r#"{"opts":{},"messages":[{"role":"us"#);
+        syscall::sleep_ms(20);
+        write("last-9.json.123.tmp", r#"{"opts":{"#);
+        write("last-9.lock", "");
+
+        let data = load_last_data(&env).unwrap();
+        assert_eq!(data.messages[0].text(), Some("Hi"));
+
+        // A named session doesn't fall back to the others
+        write("last-s-work.json", "{");
+        let named = Env {
+            ORT_SESSION: Some("work"),
+            ..env
+        };
+        let ret = load_last_data(&named);
+        assert!(matches!(ret, Err(err) if matches!(err.kind, ErrorKind::HistoryParseFailed)));
+    }
+
+    #[test]
+    fn test_pin_system() {
+        use crate::Message;
+        let convo = || {
+            vec![
+                Message::system("Be brief.".to_string()),
+                Message::user("Hi".to_string()),
+                Message::assistant("Hello".to_string()),
+            ]
+        };
+        let text = |m: &[Message]| {
+            m.iter()
+                .map(|m| m.text().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Kept as is
+        let mut messages = convo();
+        assert_eq!(
+            pin_system(&mut messages, None, None).as_deref(),
+            Some("Be brief.")
+        );
+        assert_eq!(text(&messages), ["Be brief.", "Hi", "Hello"]);
+
+        // Replaced, not added
+        let mut messages = convo();
+        pin_system(&mut messages, Some("Be a pirate.".to_string()), None);
+        assert_eq!(text(&messages), ["Be a pirate.", "Hi", "Hello"]);
+
+        // Added to
+        let mut messages = convo();
+        let system = pin_system(&mut messages, None, Some("Cite sources.".to_string()));
+        assert_eq!(system.as_deref(), Some("Be brief.\n\nCite sources."));
+        assert_eq!(text(&messages)[0], "Be brief.\n\nCite sources.");
+        assert_eq!(messages.len(), 3);
+
+        // Both, -s first
+        let mut messages = convo();
+        pin_system(
+            &mut messages,
+            Some("Be a pirate.".to_string()),
+            Some("Cite sources.".to_string()),
+        );
+        assert_eq!(text(&messages)[0], "Be a pirate.\n\nCite sources.");
+
+        // A conversation without one gets one at the start
+        let mut messages = convo().split_off(1);
+        assert_eq!(pin_system(&mut messages, None, None), None);
+        pin_system(&mut messages, None, Some("Cite sources.".to_string()));
+        assert!(matches!(messages[0].role, crate::Role::System));
+        assert_eq!(text(&messages), ["Cite sources.", "Hi", "Hello"]);
+
+        // A developer message is the system prompt too
+        let mut messages = convo();
+        messages[0] = Message::new(crate::Role::Developer, Some("Be brief.".to_string()), None);
+        pin_system(&mut messages, Some("Be a pirate.".to_string()), None);
+        assert!(matches!(messages[0].role, crate::Role::Developer));
+        assert_eq!(text(&messages), ["Be a pirate.", "Hi", "Hello"]);
+    }
+
+    #[test]
+    fn test_multi_json() {
+        let mut ok = CollectedWriter::new();
+        ok.write(Response::Content("Hi \"there\"".to_string()))
+            .unwrap();
+        let stats = Stats {
+            used_model: "a/b".to_string(),
+            provider: "P".to_string(),
+            cost_in_cents: Some(0.25),
+            completion_tokens: Some(3),
+            elapsed_time: Duration::from_millis(1500),
+            ..Default::default()
+        };
+        ok.write(Response::Stats(Box::new(stats))).unwrap();
+        let mut failed = CollectedWriter::new();
+        failed
+            .write(Response::Error("429 Too Many Requests".to_string()))
+            .unwrap();
+
+        let models = ["a/b".to_string(), "c/d".to_string(), "e/f".to_string()];
+        let mut out: Vec<u8> = Vec::new();
+        multi_json(&mut out, &models, &[Some(ok), Some(failed), None]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"a/b":{"content":"Hi \"there\"","stats":{"model":"a/b","provider":"P","#,
+                r#""cost_cents":0.250000,"is_byok":false,"is_cancelled":false,"elapsed_ms":1500,"#,
+                r#""time_to_first_token_ms":null,"completion_tokens":3,"reasoning_tokens":null,"#,
+                r#""tokens_per_sec":null,"inter_token_latency_ms":0,"web_search_requests":null},"#,
+                r#""error":null},"#,
+                r#""c/d":{"content":null,"stats":null,"error":"429 Too Many Requests"},"#,
+                r#""e/f":{"content":null,"stats":null,"error":"No answer"}}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_multi_json_samples() {
+        let mut sampled = CollectedWriter::new();
+        sampled.write(Response::Content("One".to_string())).unwrap();
+        sampled.samples = vec!["Two".to_string(), "Three".to_string()];
+        let mut out: Vec<u8> = Vec::new();
+        multi_json(&mut out, &["a/b".to_string()], &[Some(sampled)]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"a/b":{"content":"One","samples":["One","Two","Three"],"stats":null,"error":null}}"#
+                .to_string() + "\n"
+        );
+    }
+
+    #[test]
+    fn test_header_log_line() {
+        let header = http::ResponseHeader {
+            status: 200,
+            status_line: "HTTP/1.1 200 OK".to_string(),
+            headers: vec![
+                ("content-type".to_string(), "text/event-stream".to_string()),
+                ("x-request-id".to_string(), "req-1".to_string()),
+                ("x-ratelimit-remaining".to_string(), "19".to_string()),
+            ],
+        };
+        assert_eq!(
+            header_log_line("01ARYZ6S41TSV4RRFFQ69G5FAV", &header),
+            r#"{"client_request_id":"01ARYZ6S41TSV4RRFFQ69G5FAV","status":200,"x-request-id":"req-1","x-ratelimit-remaining":"19"}"#
+        );
+    }
+
+    #[test]
+    fn collect_other_choices() {
+        let opts = PromptOpts {
+            n: Some(3),
+            ..PromptOpts::default()
+        };
+        let mut p = ActivePrompt::new(
+            String::new(),
+            &Cfg::default(),
+            opts,
+            vec![],
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        for s in [
+            r#"{"index":2,"delta":{"content":"Two"}}"#,
+            r#"{"index":1,"delta":{"content":"One"}}"#,
+            r#"{"index":2,"delta":{"content":" more"}}"#,
+            r#"{"index":5,"delta":{"content":"Not asked for"}}"#,
+        ] {
+            p.collect_choice(Choice::from_json(s).unwrap());
         }
+        assert_eq!(p.take_other_choices(), ["One", "Two more"]);
     }
 
-    most_recent_file
-        .map(|(path, _)| Ok(path))
-        .unwrap_or_else(|| {
-            Err(ort_error(
-                ErrorKind::HistoryLookupFailed,
-                "No files found starting with prefix",
-            ))
-        })
+    #[test]
+    fn refusal_and_content_filter() {
+        let choice = Choice::from_json(
+            r#"{"index":0,"delta":{"role":"assistant","content":null,"refusal":"I can't help with that."},"finish_reason":"stop"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            choice.delta.refusal.as_deref(),
+            Some("I can't help with that.")
+        );
+
+        let mut p = ActivePrompt::new(
+            String::new(),
+            &Cfg::default(),
+            PromptOpts::default(),
+            vec![],
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        assert!(p.refusal().is_none());
+        p.finish_reason = Some("content_filter".to_string());
+        assert!(p.refusal().unwrap().contains("content filter"));
+        p.refusal.push_str("No.");
+        assert_eq!(p.refusal().as_deref(), Some("Model refused: No."));
+    }
+
+    #[test]
+    fn cost_limit_estimate() {
+        let cache = "acme/m\ttext\ttext\t\t0.001\t0.002\n";
+        let messages = vec![Message::user("Hello world".to_string())];
+        let mut opts = PromptOpts {
+            models: vec!["acme/m".to_string()],
+            ..PromptOpts::default()
+        };
+        assert!(CostLimit::new(Some(cache), &opts, 0, &messages).is_none());
+
+        opts.max_cost = Some(0.005);
+        let mut cl = CostLimit::new(Some(cache), &opts, 0, &messages).unwrap();
+        // 2 prompt tokens
+        assert!((cl.estimate() - 0.002).abs() < 1e-9);
+        assert!(!cl.is_over());
+        // 2 more at the completion price, however it streams
+        for piece in ["Hel", "lo", " wor", "ld"] {
+            cl.add(piece);
+        }
+        assert!((cl.estimate() - 0.006).abs() < 1e-9);
+        assert!(cl.is_over());
+        assert!(matches!(cl.error().kind, ErrorKind::CostLimitExceeded));
+
+        // No price, OpenRouter's max_price still applies
+        assert!(CostLimit::new(None, &opts, 0, &messages).is_none());
+    }
+
+    #[test]
+    fn reasoning_limit_slack() {
+        let mut opts = PromptOpts {
+            models: vec!["acme/m".to_string()],
+            ..PromptOpts::default()
+        };
+        assert!(ReasoningLimit::new(&opts, 0).is_none());
+
+        opts.reasoning_tokens = Some(2);
+        let mut rl = ReasoningLimit::new(&opts, 0).unwrap();
+        // 2 tokens, on budget
+        for piece in ["Hel", "lo", " world"] {
+            rl.add(piece);
+        }
+        assert!(!rl.is_over());
+        // 4 is well over
+        rl.add(" Hello world");
+        assert!(rl.is_over());
+        assert_eq!(
+            rl.notice(),
+            "\n[Reasoning is well over the 2 token budget, not showing the rest]\n"
+        );
+        assert!(matches!(
+            rl.error().kind,
+            ErrorKind::ReasoningBudgetExceeded
+        ));
+    }
+
+    #[test]
+    fn generation_record() {
+        let local = Cfg {
+            base_url: "localhost:8080/v1".to_string(),
+            ..Cfg::default()
+        };
+        for (cfg, id, expected) in [
+            (Cfg::default(), None, true),
+            (Cfg::default(), Some("chatcmpl-1"), true),
+            (local.clone(), Some("gen-1"), true),
+            (local.clone(), Some("chatcmpl-1"), false),
+            (local, None, false),
+        ] {
+            let mut p = ActivePrompt::new(
+                String::new(),
+                &cfg,
+                PromptOpts::default(),
+                vec![],
+                vec![],
+                0,
+                None,
+            )
+            .unwrap();
+            p.generation_id = id.map(str::to_string);
+            assert_eq!(p.has_generation_record(), expected, "{id:?}");
+        }
+    }
+
+    #[test]
+    fn deadline_or_first_token_deadline() {
+        let mut p = ActivePrompt::new(
+            String::new(),
+            &Cfg::default(),
+            PromptOpts::default(),
+            vec![],
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        assert_eq!(p.read_deadline(), None);
+
+        p.first_token_deadline = Some(u64::MAX - 1);
+        p.deadline = Some(u64::MAX);
+        assert_eq!(p.read_deadline(), Some(u64::MAX - 1));
+        assert!(matches!(
+            p.timeout_error().kind,
+            ErrorKind::FirstTokenTimeout
+        ));
+        assert!(!p.is_cancelled);
+
+        // Out of time overall beats waiting for the first token
+        p.deadline = Some(0);
+        assert_eq!(p.read_deadline(), Some(0));
+        assert!(matches!(
+            p.timeout_error().kind,
+            ErrorKind::DeadlineExceeded
+        ));
+        assert!(p.is_cancelled);
+
+        // After the first token
+        p.first_token_deadline = None;
+        p.deadline = Some(u64::MAX);
+        assert_eq!(p.read_deadline(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn malformed_snippet() {
+        let data = "{\"id\":\"gen-1\",\"choices\":[".to_string() + &"é".repeat(60);
+        let msg = malformed_message(1234, &data, "bad choices");
+        assert!(
+            msg.starts_with("Malformed event at byte 1,234: bad choices: {\"id\":\"gen-1\""),
+            "{msg}"
+        );
+        assert!(msg.ends_with("é...\n"), "{msg}");
+        assert_eq!(
+            malformed_message(0, "{", "cut short"),
+            "Malformed event at byte 0: cut short: {\n"
+        );
+    }
+
+    #[test]
+    fn fail_over_providers_then_models() {
+        let mut opts = PromptOpts {
+            models: vec!["a/one".to_string()],
+            provider: Some("slow".to_string()),
+            first_token_deadline_ms: Some(10),
+            ..PromptOpts::default()
+        };
+        let mut fallback = vec!["b/two".to_string()];
+
+        // The stream didn't say who served it, so blame the one we asked for
+        assert!(fail_over(&mut opts, "", &mut fallback));
+        assert!(opts.provider.is_none());
+        assert_eq!(opts.ignore_providers, ["slow"]);
+
+        // Nobody to blame, move to the next model
+        assert!(fail_over(&mut opts, "", &mut fallback));
+        assert_eq!(opts.models, ["b/two"]);
+        assert!(opts.ignore_providers.is_empty());
+
+        assert!(fail_over(&mut opts, "other", &mut fallback));
+        assert_eq!(opts.ignore_providers, ["other"]);
+        assert!(!fail_over(&mut opts, "other", &mut fallback));
+    }
+
+    #[test]
+    fn retries_then_gives_up() {
+        let failed = |why: &str| Attempt {
+            error: Some(why.to_string()),
+            ..Default::default()
+        };
+        // A deadline that has passed, so no waiting
+        let now = Some(0);
+        let mut attempts = vec![];
+        assert!(retry(&mut attempts, 2, failed("a"), true, now));
+        assert!(retry(&mut attempts, 2, failed("b"), true, now));
+        assert!(!retry(&mut attempts, 2, failed("c"), true, now));
+        assert_eq!(attempts.len(), 3);
+
+        // Another try won't help
+        let mut attempts = vec![];
+        assert!(!retry(
+            &mut attempts,
+            2,
+            failed("a"),
+            is_retryable(ErrorKind::NoNetwork),
+            now
+        ));
+        assert!(is_retryable(ErrorKind::HttpStatusError));
+        assert!(is_retryable(ErrorKind::SocketReadFailed));
+        assert!(!is_retryable(ErrorKind::HttpClientError));
+    }
+
+    #[test]
+    fn retry_statuses() {
+        for status in [408, 429, 500, 502, 503] {
+            assert!(is_retryable_status(status), "{status}");
+        }
+        for status in [400, 401, 403, 404, 422] {
+            assert!(!is_retryable_status(status), "{status}");
+        }
+        assert_eq!(error_status("Chutes: overloaded (502)"), Some(502));
+        assert_eq!(error_status("No endpoints found (404)"), Some(404));
+        assert_eq!(error_status("Provider disconnected (server_error)"), None);
+    }
+
+    #[test]
+    fn retry_wait_backs_off() {
+        assert_eq!(retry_wait_ms(1, 0), 250);
+        assert_eq!(retry_wait_ms(1, u64::MAX), 250 + u64::MAX % 251);
+        assert!((500..=1000).contains(&retry_wait_ms(2, 12345)));
+        assert!((1000..=2000).contains(&retry_wait_ms(3, 12345)));
+        // Capped
+        assert!((4000..=8000).contains(&retry_wait_ms(10, 12345)));
+        assert!((4000..=8000).contains(&retry_wait_ms(100, u64::MAX)));
+    }
+
+    #[test]
+    fn numbered_image_paths() {
+        assert_eq!(numbered_path("out.png", 0), "out.png");
+        assert_eq!(numbered_path("out.png", 1), "out-2.png");
+        assert_eq!(numbered_path("a.b/out", 2), "a.b/out-3");
+        assert_eq!(numbered_path("/tmp/.hidden", 1), "/tmp/.hidden-2");
+    }
 }
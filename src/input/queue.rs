@@ -0,0 +1,362 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--queue-on-fail` saves the request body of a prompt that couldn't reach
+//! the server in the state dir's `queue/`. `ort flush-queue` sends them
+//! later, oldest first, one at a time and a little apart, and saves each
+//! answer as the conversation of the session that asked.
+
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::cli::{self, Env};
+use crate::common::config::Cfg;
+use crate::common::data::Content;
+use crate::common::term::Term;
+use crate::common::{dir, utils};
+use crate::input::history;
+use crate::input::prompt::ActivePrompt;
+use crate::output::OutputWriter;
+use crate::output::answer_writer::AnswerWriter;
+use crate::output::last_writer::{self, LastFile, LastWriter, SessionLock};
+use crate::output::metrics;
+use crate::output::writer::{ConsoleWriter, FileWriter};
+use crate::{ErrorKind, LastData, Message, OrtError, OrtResult, Response, Role, Write};
+use crate::{ort_error, syscall};
+
+/// In the state dir
+const QUEUE_DIR: &str = "queue";
+
+/// How much of each prompt `ort flush-queue` shows as it sends it
+const SNIPPET_BYTES: usize = 60;
+
+/// Between two queued prompts, so a long queue doesn't run into the rate limit
+const SEND_INTERVAL_MS: u64 = 1000;
+
+/// Where this run's request goes if it is queued:
+/// `<unix time>-<pid>-<session>.json`. The name sorts oldest first, and
+/// the session is the conversation the answer joins.
+pub(in crate::input) fn new_path(env: &Env) -> OrtResult<String> {
+    Ok(queue_dir(env)?
+        + "/"
+        + &utils::num_to_string(syscall::unix_time())
+        + "-"
+        + &utils::num_to_string(syscall::getpid())
+        + "-"
+        + &utils::session_key(env)
+        + ".json")
+}
+
+/// Save a request body. All or nothing, `ort flush-queue` never sees half of one.
+pub(in crate::input) fn save(path: &str, body: &str) -> OrtResult<()> {
+    if let Some((dir, _)) = path.rsplit_once('/') {
+        utils::ensure_dir_exists(dir);
+    }
+    let mut f = LastFile::create(path.to_string())?;
+    f.write_str(body)?;
+    f.persist()
+}
+
+/// `ort flush-queue`: send everything queued. No network or a 429 stops it,
+/// the rest would fail the same way. A prompt that fails for another reason
+/// stays in the queue and the others carry on.
+pub fn flush<W: Write + Send>(
+    api_key: &str,
+    cfg: &Cfg,
+    env: &Env,
+    term: Term,
+    w: &mut W,
+) -> OrtResult<()> {
+    let dir = queue_dir(env)?;
+    let names = queued(&dir)?;
+    if names.is_empty() {
+        w.write_str("Nothing queued\n")?;
+        return w.flush();
+    }
+    let total = utils::num_to_string(names.len());
+    let mut num_sent = 0;
+    for (i, name) in names.iter().enumerate() {
+        if i > 0 {
+            syscall::sleep_ms(SEND_INTERVAL_MS);
+        }
+        let path = dir.clone() + "/" + name;
+        let res = utils::filename_read_to_string(&path)
+            .map_err(|err| ort_error(ErrorKind::FileReadFailed, err))
+            .and_then(|body| {
+                let last = history::import_json(&body, cfg)?;
+                let header = "[".to_string()
+                    + &utils::num_to_string(i + 1)
+                    + "/"
+                    + &total
+                    + "] "
+                    + &prompt_snippet(&last.messages)
+                    + "\n";
+                w.write_str(&header)?;
+                w.flush()?;
+                send(api_key, cfg, env, term, body, last, session_of(name), w)
+            });
+        match res {
+            Ok(()) => {
+                let c_path = CString::new(path)
+                    .map_err(|_| ort_error(ErrorKind::Other, "Null byte in queue file name"))?;
+                syscall::unlink(c_path.as_ptr());
+                num_sent += 1;
+            }
+            Err(err) if is_stop(&err) => {
+                let msg = "Stopped, ".to_string()
+                    + &utils::num_to_string(names.len() - num_sent)
+                    + " left in "
+                    + &dir
+                    + "\n";
+                utils::warn(&msg);
+                return Err(err);
+            }
+            Err(err) => {
+                utils::print_error(c"Left in the queue: ", &(path + ": " + &err.as_string()));
+            }
+        }
+        w.write_str("\n")?;
+    }
+    let summary = "Sent ".to_string()
+        + &utils::num_to_string(num_sent)
+        + " of "
+        + &total
+        + " queued prompts\n";
+    w.write_str(&summary)?;
+    w.flush()?;
+    if num_sent < names.len() {
+        return Err(ort_error(
+            ErrorKind::Other,
+            "not all queued prompts were sent",
+        ));
+    }
+    Ok(())
+}
+
+/// Send one queued request `body` and stream the answer, like a prompt.
+/// `last` is the conversation in it.
+#[allow(clippy::too_many_arguments)]
+fn send<W: Write + Send>(
+    api_key: &str,
+    cfg: &Cfg,
+    env: &Env,
+    term: Term,
+    body: String,
+    last: LastData,
+    session: &str,
+    w: &mut W,
+) -> OrtResult<()> {
+    let opts = last.opts;
+
+    let mut active_prompt = ActivePrompt::new(
+        api_key.to_string(),
+        cfg,
+        opts.clone(),
+        last.messages.clone(),
+        vec![],
+        0,
+        Some(env),
+    )?;
+    active_prompt.body = Some(body);
+    active_prompt.start()?;
+
+    let show_reasoning = opts.show_reasoning.unwrap_or_default();
+    let verbosity = opts.verbosity.unwrap_or_default();
+    let mut output_writer: Box<dyn OutputWriter> = if !term.has_ansi() {
+        Box::new(FileWriter::new(w, show_reasoning, verbosity, opts.flush))
+    } else {
+        Box::new(ConsoleWriter::new(
+            w,
+            show_reasoning,
+            verbosity,
+            opts.raw,
+            opts.flush,
+        ))
+    };
+    // Until the answer is saved, a `-c` in that session waits for it
+    let mut _lock = None;
+    let mut last_writer = if cfg.save_to_file {
+        _lock = Some(SessionLock::acquire_for(env, session)?);
+        let f = LastFile::create(last_path(env, session, &last.messages)?)?;
+        Some(LastWriter::with_file(
+            f,
+            opts.clone(),
+            last.messages,
+            vec![],
+            cfg.save_reasoning,
+        ))
+    } else {
+        None
+    };
+    let mut answer_writer = if cfg.save_answer {
+        Some(AnswerWriter::new(
+            env,
+            cfg.save_dir.as_deref(),
+            &opts.models[0],
+        )?)
+    } else {
+        None
+    };
+
+    let mut content = String::new();
+    while let Some(events) = active_prompt.next()? {
+        for event in events {
+            if let Response::Content(c) = &event {
+                content.push_str(c);
+            }
+            output_writer.write(event.clone())?;
+            if let Some(aw) = answer_writer.as_mut() {
+                aw.write(event.clone())?;
+            }
+            if let Some(lw) = last_writer.as_mut() {
+                lw.write(event)?;
+            }
+        }
+    }
+    let mut stats = active_prompt.stop();
+    if cfg.metrics.is_some() {
+        metrics::record(cfg, &stats);
+    }
+    cli::run_post_response_hook(cfg, &stats.used_model, &stats.provider, &content);
+    if let Some(mut aw) = answer_writer {
+        aw.write(Response::Stats(Box::new(stats.clone())))?;
+        aw.stop(true)?;
+        stats.saved_to = Some(aw.path().into());
+    }
+    output_writer.write(Response::Stats(Box::new(stats)))?;
+    output_writer.stop(true)?;
+    if let Some(lw) = last_writer.as_mut() {
+        lw.stop(true)?;
+    }
+    // It was answered, sending it again won't change that
+    if let Some(mut msg) = active_prompt.refusal() {
+        msg.push('\n');
+        syscall::write(2, msg.as_ptr().cast(), msg.len());
+    }
+    Ok(())
+}
+
+/// Where the answer to a prompt queued from `session` is saved: over the
+/// session's conversation if that is still the one the prompt continued,
+/// otherwise in a new file, so nothing said since is lost.
+fn last_path(env: &Env, session: &str, messages: &[Message]) -> OrtResult<String> {
+    let path = last_writer::state_path(env, &("last-".to_string() + session + ".json"))?;
+    let Ok(saved) = utils::filename_read_to_string(&path) else {
+        return Ok(path);
+    };
+    if LastData::from_json(&saved).is_ok_and(|saved| is_start_of(&saved.messages, messages)) {
+        return Ok(path);
+    }
+    let filename = "last-".to_string()
+        + session
+        + "-queued-"
+        + &utils::num_to_string(syscall::unix_time())
+        + ".json";
+    let new_path = last_writer::state_path(env, &filename)?;
+    utils::warn(
+        &("The conversation went on since this was queued, saving it to ".to_string()
+            + &new_path
+            + "\n"),
+    );
+    Ok(new_path)
+}
+
+/// Does `messages` carry on from `saved`
+fn is_start_of(saved: &[Message], messages: &[Message]) -> bool {
+    saved.len() <= messages.len()
+        && saved
+            .iter()
+            .zip(messages)
+            .all(|(s, m)| s.role == m.role && s.text() == m.text())
+}
+
+/// The network is still down, or the server wants us to slow down
+fn is_stop(err: &OrtError) -> bool {
+    matches!(
+        err.kind,
+        ErrorKind::NoNetwork
+            | ErrorKind::DnsResolveFailed
+            | ErrorKind::HttpConnectError
+            | ErrorKind::RateLimited
+    )
+}
+
+fn queue_dir(env: &Env) -> OrtResult<String> {
+    last_writer::state_path(env, QUEUE_DIR)
+}
+
+/// The queued requests in `dir`, oldest first
+fn queued(dir: &str) -> OrtResult<Vec<String>> {
+    let c_dir = CString::new(dir)
+        .map_err(|_| ort_error(ErrorKind::DirOpenFailed, "Null byte in queue dir"))?;
+    if !utils::path_exists(&c_dir) {
+        return Ok(vec![]);
+    }
+    let mut names: Vec<String> = dir::DirFiles::new(c_dir.as_c_str())?
+        .filter(|name| name.ends_with(".json"))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// "1760000000-4242-s-work.json" was queued from session "s-work"
+fn session_of(name: &str) -> &str {
+    let stem = name.strip_suffix(".json").unwrap_or(name);
+    stem.splitn(3, '-').nth(2).unwrap_or(stem)
+}
+
+/// The start of the last prompt, on one line
+fn prompt_snippet(messages: &[Message]) -> String {
+    let text = messages
+        .iter()
+        .rev()
+        .filter(|m| m.role == Role::User)
+        .find_map(|m| {
+            m.content.iter().find_map(|c| match c {
+                Content::Text(t) if !t.trim().is_empty() => Some(t.as_str()),
+                _ => None,
+            })
+        })
+        .unwrap_or_default();
+    let mut snippet = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if snippet.len() > SNIPPET_BYTES {
+        snippet.truncate(snippet.floor_char_boundary(SNIPPET_BYTES));
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_names() {
+        assert_eq!(session_of("1760000000-4242-s-work.json"), "s-work");
+        assert_eq!(session_of("1760000000-4242-3.json"), "3");
+
+        let messages = [
+            Message::user("First".to_string()),
+            Message::assistant("Hi".to_string()),
+            Message::user("  What  is\nthe time? ".to_string()),
+        ];
+        assert_eq!(prompt_snippet(&messages), "What is the time?");
+        let long = [Message::user("word ".repeat(20))];
+        let snippet = prompt_snippet(&long);
+        assert!(snippet.ends_with("...") && snippet.len() == SNIPPET_BYTES + 3);
+
+        // The session's conversation is only replaced by one that carries it on
+        assert!(is_start_of(&messages[..1], &messages));
+        assert!(is_start_of(&messages, &messages));
+        assert!(!is_start_of(&messages, &messages[..2]));
+        let other = [Message::user("Second".to_string())];
+        assert!(!is_start_of(&other, &messages));
+    }
+}
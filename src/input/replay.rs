@@ -0,0 +1,517 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `ort replay <capture>`: play a `--dump-wire` response.http, or a saved SSE
+//! stream, through the parser and writers again, to reproduce display bugs
+//! without the network or the spend. A response.http is paced like the
+//! original from the timing.tsv next to it, unless `--fast`.
+
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::common::config::Cfg;
+use crate::common::term::Term;
+use crate::input::args::ReplayOpts;
+use crate::input::prompt::ActivePrompt;
+use crate::output::OutputWriter;
+use crate::output::pager::Pager;
+use crate::output::writer::{ConsoleWriter, FileWriter};
+use crate::transport::AsFd;
+use crate::{ErrorKind, OrtResult, PromptOpts, Read, Response, Write};
+use crate::{ort_error, syscall, utils};
+
+/// Without a timing.tsv, the pause before each SSE event
+const EVENT_GAP_MS: u64 = 20;
+
+pub fn run<W: Write + Send>(cfg: &Cfg, opts: ReplayOpts, term: Term, w: &mut W) -> OrtResult<()> {
+    let data = utils::filename_read_to_bytes(&opts.capture)
+        .map_err(|err| ort_error(ErrorKind::FileReadFailed, err))?;
+    let has_header = data.starts_with(b"HTTP/");
+    let reads = if has_header {
+        let dir = opts.capture.rsplit_once('/').map_or(".", |(dir, _)| dir);
+        let timing = utils::filename_read_to_string(&(dir.to_string() + "/timing.tsv")).ok();
+        timing.map(|t| recorded_reads(&t)).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let source = ReplaySource::new(data, reads, opts.is_fast);
+
+    let mut output_writer: Box<dyn OutputWriter> = if !term.has_ansi() {
+        Box::new(FileWriter::new(
+            w,
+            opts.show_reasoning,
+            opts.verbosity,
+            opts.flush,
+        ))
+    } else {
+        let mut console = ConsoleWriter::new(
+            w,
+            opts.show_reasoning,
+            opts.verbosity,
+            opts.is_raw,
+            opts.flush,
+        );
+        if cfg.use_pager {
+            console.pager = Pager::new();
+        }
+        Box::new(console)
+    };
+
+    // Only for the stats, the capture usually names the model
+    let prompt_opts = PromptOpts {
+        models: vec!["unknown model".to_string()],
+        ..Default::default()
+    };
+    let mut active_prompt =
+        ActivePrompt::new(String::new(), cfg, prompt_opts, vec![], vec![], 0, None)?;
+    active_prompt.start_replay(source, has_header)?;
+    while let Some(events) = active_prompt.next()? {
+        for event in events {
+            output_writer.write(event)?;
+        }
+    }
+    let stats = active_prompt.stop();
+    output_writer.write(Response::Stats(Box::new(stats)))?;
+    output_writer.stop(true)?;
+
+    if let Some(mut msg) = active_prompt.refusal() {
+        msg.push('\n');
+        syscall::write(2, msg.as_ptr().cast(), msg.len());
+        return Err(ort_error(ErrorKind::ModelRefused, "refused or filtered"));
+    }
+    Ok(())
+}
+
+/// The (ms after the request was sent, bytes) of each read in a timing.tsv
+fn recorded_reads(timing: &str) -> Vec<(u64, usize)> {
+    let mut sent_ms = 0;
+    let mut reads = Vec::new();
+    for line in timing.lines() {
+        let Some((ms, event)) = line.split_once('\t') else {
+            continue;
+        };
+        let Ok(ms) = ms.parse::<u64>() else {
+            // The header line
+            continue;
+        };
+        if event == "request sent" {
+            sent_ms = ms;
+        } else if let Some(n) = event.strip_prefix("read ").and_then(|n| n.parse().ok()) {
+            reads.push((ms.saturating_sub(sent_ms), n));
+        }
+    }
+    reads
+}
+
+/// One read per SSE event, `EVENT_GAP_MS` apart
+fn event_reads(data: &[u8]) -> Vec<(u64, usize)> {
+    let mut reads = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let len = data[start..]
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .map_or(data.len() - start, |i| i + 2);
+        reads.push(((reads.len() as u64 + 1) * EVENT_GAP_MS, len));
+        start += len;
+    }
+    reads
+}
+
+/// The captured bytes, handed out in the same pieces and at the same pace as
+/// the server sent them
+pub(in crate::input) struct ReplaySource {
+    data: Vec<u8>,
+    pos: usize,
+    // (ms after start, bytes) of each read still to come, reversed
+    reads: Vec<(u64, usize)>,
+    start_ms: u64,
+    is_fast: bool,
+}
+
+impl ReplaySource {
+    /// With no `reads`, one SSE event at a time
+    pub(in crate::input) fn new(data: Vec<u8>, reads: Vec<(u64, usize)>, is_fast: bool) -> Self {
+        let mut reads = if reads.is_empty() {
+            event_reads(&data)
+        } else {
+            reads
+        };
+        reads.reverse();
+        ReplaySource {
+            data,
+            pos: 0,
+            reads,
+            start_ms: syscall::monotonic_ms(),
+            is_fast,
+        }
+    }
+}
+
+impl Read for ReplaySource {
+    fn read(&mut self, buf: &mut [u8]) -> OrtResult<usize> {
+        let remaining = self.data.len() - self.pos;
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        // Past the recorded reads, the rest in one go
+        let (at_ms, len) = self.reads.last().copied().unwrap_or((0, remaining));
+        if !self.is_fast {
+            let now = syscall::monotonic_ms();
+            let due = self.start_ms + at_ms;
+            if due > now {
+                syscall::sleep_ms(due - now);
+            }
+        }
+        let n = len.min(remaining).min(buf.len());
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        // A read bigger than `buf` finishes on the next call, without waiting
+        match self.reads.last_mut() {
+            Some(last) if last.1 > n => last.1 -= n,
+            Some(_) => {
+                self.reads.pop();
+            }
+            None => {}
+        }
+        Ok(n)
+    }
+}
+
+impl AsFd for ReplaySource {
+    fn as_fd(&self) -> i32 {
+        -1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_reads() {
+        let timing = "# 2026-10-17T16:03:41Z\nms\tevent\n0\tconnected\n42\trequest sent\n\
+                      342\tread 180\n390\tread 97\n391\teof\n";
+        assert_eq!(recorded_reads(timing), [(300, 180), (348, 97)]);
+    }
+
+    #[test]
+    fn test_event_reads() {
+        let sse = b": OPENROUTER PROCESSING\n\ndata: {}\n\ndata: [DONE]\n";
+        assert_eq!(event_reads(sse), [(20, 25), (40, 10), (60, 13)]);
+    }
+
+    #[test]
+    fn test_replay_source() {
+        let reads = vec![(0, 3), (0, 4)];
+        let mut source = ReplaySource::new(b"abcdefghij".to_vec(), reads, true);
+        let mut buf = [0u8; 2];
+        let mut got = Vec::new();
+        loop {
+            let n = source.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            got.push(buf[..n].to_vec());
+        }
+        // 3 split by the buffer, then 4 the same, then the rest
+        assert_eq!(
+            got,
+            [&b"ab"[..], b"c", b"de", b"fg", b"hi", b"j"].map(|s| s.to_vec())
+        );
+    }
+    #[test]
+    fn test_error_event_mid_stream() {
+        let sse = br#": OPENROUTER PROCESSING
+
+data: {"id":"gen-1","provider":"Chutes","choices":[{"index":0,"delta":{"content":"Hel"}}]}
+
+data: {"id":"gen-1","error":{"code":502,"message":"overloaded"},"choices":[{"index":0,"delta":{"content":""},"finish_reason":"error"}]}
+
+data: [DONE]
+"#;
+        let Replayed {
+            errors,
+            content,
+            result,
+        } = replay(&Cfg::default(), sse, false);
+        assert!(result.is_ok());
+        assert_eq!(errors, ["Chutes: overloaded (502)"]);
+        assert_eq!(content, "Hel");
+    }
+
+    /// A replayed capture, as `next` gave it until it said the stream is over
+    struct Replayed {
+        content: String,
+        errors: Vec<String>,
+        // Reported once, then the stream is over
+        result: OrtResult<()>,
+    }
+
+    fn replay(cfg: &Cfg, captured: &[u8], has_header: bool) -> Replayed {
+        let opts = PromptOpts::default();
+        let mut active_prompt =
+            ActivePrompt::new(String::new(), cfg, opts, vec![], vec![], 0, None).unwrap();
+        let source = ReplaySource::new(captured.to_vec(), vec![], true);
+        active_prompt.start_replay(source, has_header).unwrap();
+        let mut out = Replayed {
+            content: String::new(),
+            errors: Vec::new(),
+            result: Ok(()),
+        };
+        loop {
+            match active_prompt.next() {
+                Ok(Some(events)) => {
+                    for event in events {
+                        match event {
+                            Response::Error(msg) => out.errors.push(msg),
+                            Response::Content(c) => out.content.push_str(&c),
+                            _ => {}
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => out.result = Err(err),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_no_processing_comment() {
+        let sse = br#"data: {"id":"gen-1","choices":[{"index":0,"delta":{"content":"Hi"}}]}
+
+data: [DONE]
+"#;
+        let Replayed {
+            content, result, ..
+        } = replay(&Cfg::default(), sse, false);
+        assert_eq!(content, "Hi");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_stream_terminators() {
+        // `event: done` ends it, whatever the server sends after
+        let sse = br#": PROCESSING
+
+data:{"id":"gen-1","choices":[{"index":0,"delta":{"content":"Hi"}}]}
+
+event: done
+data: {}
+
+data: {"id":"gen-1","choices":[{"index":0,"delta":{"content":" again"}}]}
+"#;
+        let Replayed {
+            content, result, ..
+        } = replay(&Cfg::default(), sse, false);
+        assert_eq!(content, "Hi");
+        assert!(result.is_ok());
+
+        // No marker and no final chunk, the server hung up after finishing
+        let chunked = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            d\r\n: PROCESSING\n\r\n\
+            5e\r\ndata: {\"id\":\"gen-1\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\n\r\n";
+        let Replayed {
+            content, result, ..
+        } = replay(&Cfg::default(), chunked.as_bytes(), true);
+        assert_eq!(content, "Hi");
+        assert!(result.is_ok());
+
+        // Cut off before the answer finished is an error, once
+        let cut = chunked.replace(r#","finish_reason":"stop""#, r#","finish_reason":null"#);
+        let cut = cut.replace("5e\r\n", "5c\r\n");
+        let Replayed {
+            content, result, ..
+        } = replay(&Cfg::default(), cut.as_bytes(), true);
+        assert_eq!(content, "Hi");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_json_body() {
+        let response = |content_type: &str, body: &str| {
+            "HTTP/1.1 200 OK\r\nContent-Type: ".to_string()
+                + content_type
+                + "\r\nContent-Length: "
+                + &utils::num_to_string(body.len())
+                + "\r\n\r\n"
+                + body
+        };
+        // Not streamed, one completion with "message" instead of "delta"
+        let completion = "{\"id\":\"gen-1\",\"choices\":[{\"index\":0,\n  \"message\":{\"role\":\"assistant\",\"content\":\"Hi there\"},\"finish_reason\":\"stop\"}]}\n";
+        let captured = response("application/json; charset=utf-8", completion);
+        let Replayed {
+            content, result, ..
+        } = replay(&Cfg::default(), captured.as_bytes(), true);
+        assert_eq!(content, "Hi there");
+        assert!(result.is_ok());
+
+        let error = r#"{"error":{"message":"No endpoints found","code":404}}"#;
+        let captured = response("application/json", error);
+        let Replayed {
+            errors,
+            content,
+            result,
+        } = replay(&Cfg::default(), captured.as_bytes(), true);
+        assert!(result.is_ok());
+        assert_eq!(errors, ["No endpoints found (404)"]);
+        assert_eq!(content, "");
+
+        // A proxy's page is not an answer
+        let captured = response("text/html", "<html>Sign in to the wifi</html>");
+        let mut active_prompt = ActivePrompt::new(
+            String::new(),
+            &Cfg::default(),
+            PromptOpts::default(),
+            vec![],
+            vec![],
+            0,
+            None,
+        )
+        .unwrap();
+        let source = ReplaySource::new(captured.into_bytes(), vec![], true);
+        assert!(active_prompt.start_replay(source, true).is_err());
+    }
+
+    #[test]
+    fn test_bad_chunks() {
+        let start = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nd\r\n: PROCESSING\n\r\n";
+        // Found by fuzz/: a NUL in the size, a size to exhaust memory, not UTF-8
+        for bad in [
+            &b"1\0\r\nx\r\n"[..],
+            b"ffffffffffff\r\nx\r\n",
+            b"3\r\nx\xff\xfe\r\n",
+        ] {
+            let captured = [&start[..], bad].concat();
+            let Replayed {
+                content, result, ..
+            } = replay(&Cfg::default(), &captured, true);
+            assert_eq!(content, "");
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_truncated_events() {
+        let sse = r#": OPENROUTER PROCESSING
+
+data: {"id":"gen-1","provider":"Chutes","choices":[{"index":0,"delta":{"role":"assistant","content":"Hé","tool_calls":[{"index":0,"id":"call_1","function":{"name":"bash","arguments":"{}"}}],"images":[{"type":"image_url","image_url":{"url":"data:image/png;base64,iVBO"}}]}}]}
+
+"#;
+        let (start, event) = sse.split_at(sse.find("data:").unwrap());
+        let event = event.trim_end();
+        // Every cut of the event is an error, never a panic
+        for end in (6..event.len()).filter(|&i| event.is_char_boundary(i)) {
+            let cut = start.to_string() + &event[..end] + "\n\n";
+            let Replayed { errors, result, .. } = replay(&Cfg::default(), cut.as_bytes(), false);
+            assert!(result.is_ok(), "{cut}");
+            assert_eq!(errors.len(), 1, "{cut}");
+            assert!(errors[0].starts_with("Malformed event: "), "{cut}");
+        }
+        let Replayed {
+            errors,
+            content,
+            result,
+        } = replay(&Cfg::default(), sse.as_bytes(), false);
+        assert!(result.is_ok());
+        assert!(errors.is_empty());
+        assert_eq!(content, "Hé");
+
+        // A bad event with good ones after it is skipped
+        let event = |content: &str| {
+            r#"data: {"id":"gen-1","choices":[{"index":0,"delta":{"content":""#.to_string()
+                + content
+                + "\"}}]}\n\n"
+        };
+        let sse = start.to_string() + &event("a") + "data: {\"id\":\n\n" + &event("b");
+        let Replayed {
+            errors,
+            content,
+            result,
+        } = replay(&Cfg::default(), sse.as_bytes(), false);
+        assert!(result.is_ok());
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(content, "ab");
+    }
+
+    #[test]
+    fn test_long_lines() {
+        let event = |content: &str| {
+            r#"data: {"id":"gen-1","choices":[{"index":0,"delta":{"content":""#.to_string()
+                + content
+                + "\"}}]}\n\n"
+        };
+        let big = "é".repeat(100_000);
+        let sse = ": PROCESSING\n\n".to_string() + &event(&big) + &event("end");
+        let Replayed {
+            content, result, ..
+        } = replay(&Cfg::default(), sse.as_bytes(), false);
+        assert_eq!(content, big.clone() + "end");
+        assert!(result.is_ok());
+
+        // An event split over two HTTP chunks
+        let small = event("Hi");
+        let (a, b) = small.split_at(20);
+        let mut chunked = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_string();
+        for part in [": PROCESSING\n\n", a, b] {
+            chunked += &(alloc::format!("{:x}\r\n", part.len()) + part + "\r\n");
+        }
+        chunked += "0\r\n\r\n";
+        let Replayed {
+            content, result, ..
+        } = replay(&Cfg::default(), chunked.as_bytes(), true);
+        assert_eq!(content, "Hi");
+        assert!(result.is_ok());
+
+        // Too big is skipped, the stream goes on
+        let cfg = Cfg {
+            max_line_bytes: Some(100_000),
+            ..Cfg::default()
+        };
+        let Replayed {
+            content, result, ..
+        } = replay(&cfg, sse.as_bytes(), false);
+        assert_eq!(content, "end");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_deadline_keeps_partial_answer() {
+        let sse = br#": OPENROUTER PROCESSING
+
+data: {"id":"gen-1","provider":"Chutes","choices":[{"index":0,"delta":{"content":"Hel"}}]}
+
+data: {"id":"gen-1","choices":[{"index":0,"delta":{"content":"lo"}}]}
+"#;
+        let cfg = Cfg::default();
+        let opts = PromptOpts::default();
+        let mut active_prompt =
+            ActivePrompt::new(String::new(), &cfg, opts, vec![], vec![], 0, None).unwrap();
+        let source = ReplaySource::new(sse.to_vec(), vec![], true);
+        active_prompt.start_replay(source, false).unwrap();
+        let mut content = String::new();
+        for _ in 0..2 {
+            for event in active_prompt.next().unwrap().unwrap() {
+                if let Response::Content(c) = event {
+                    content.push_str(&c);
+                }
+            }
+        }
+        // Time's up before the rest is read
+        active_prompt.deadline = Some(0);
+        let Err(err) = active_prompt.next() else {
+            panic!("expected the deadline to pass");
+        };
+        assert!(matches!(err.kind, ErrorKind::DeadlineExceeded));
+        assert_eq!(err.kind.exit_code(), crate::common::error::EXIT_DEADLINE);
+        assert_eq!(content, "Hel");
+        assert_eq!(active_prompt.stop().provider, "Chutes");
+    }
+}
@@ -0,0 +1,422 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `ort review`: review a diff one file at a time, all files at once, then
+//! print the findings grouped by file, worst first.
+
+extern crate alloc;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::common::config::Cfg;
+use crate::common::secrets;
+use crate::common::term::Term;
+use crate::common::utils;
+use crate::input::args::{ReviewOpts, ReviewSource};
+use crate::input::{cli, prompt};
+use crate::{
+    DEFAULT_MODEL, ErrorKind, OrtResult, PromptOpts, Verbosity, Write, ort_error, syscall,
+};
+
+/// Most requests in flight at once
+const MAX_PARALLEL: usize = 10;
+
+/// A file's diff bigger than this is split between its hunks. About 12k tokens.
+const MAX_CHUNK_BYTES: usize = 48 * 1024;
+
+const DEFAULT_REVIEW_PROMPT: &str = "You are reviewing a code change. Report only real problems: bugs, security issues, missing error handling, and confusing code. One finding per line, starting with [high], [medium] or [low], then the line number in the new file if there is one, e.g. `[high] L42: off by one, the loop skips the last item`. No praise and no summary. If there is nothing to report, answer `No issues.`";
+
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    High,
+    Medium,
+    Low,
+    // The model didn't follow the format, show what it said
+    Note,
+}
+
+impl Severity {
+    const MARKED: [Severity; 3] = [Severity::High, Severity::Medium, Severity::Low];
+
+    fn marker(&self) -> &'static str {
+        match self {
+            Severity::High => "[high]",
+            Severity::Medium => "[medium]",
+            Severity::Low => "[low]",
+            Severity::Note => "",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::High => "\x1b[31m",
+            Severity::Medium => "\x1b[33m",
+            Severity::Low => "\x1b[2m",
+            Severity::Note => "",
+        }
+    }
+}
+
+struct Chunk {
+    file: String,
+    diff: String,
+}
+
+pub fn run<W: Write>(
+    api_key: &str,
+    cfg: &Cfg,
+    opts: ReviewOpts,
+    is_offline: bool,
+    term: Term,
+    w: &mut W,
+) -> OrtResult<()> {
+    let diff = read_diff(&opts.source)?;
+    let chunks = split_diff(&diff, MAX_CHUNK_BYTES);
+    if chunks.is_empty() {
+        return Err(ort_error(
+            ErrorKind::InvalidArguments,
+            "Nothing to review, the diff has no changes",
+        ));
+    }
+    let name = match &opts.source {
+        ReviewSource::PatchFile(path) => path.as_str(),
+        _ => "diff",
+    };
+    secrets::check(&[(name, &diff)], opts.allow_secrets)?;
+    let model = opts
+        .model
+        .or_else(|| cfg.models.first().cloned())
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    let num_files = count_files(&chunks);
+
+    let mut msg = "Reviewing ".to_string()
+        + &utils::num_to_string(num_files)
+        + " file(s) in "
+        + &utils::num_to_string(chunks.len())
+        + " request(s) with "
+        + &model
+        + "\n";
+    let _ = w.write_str(&msg);
+    let _ = w.flush();
+    cli::require_network(is_offline)?;
+
+    let system = cfg
+        .review_prompt
+        .clone()
+        .unwrap_or_else(|| DEFAULT_REVIEW_PROMPT.to_string());
+    let mut answers: Vec<Option<String>> = vec![None; chunks.len()];
+    let mut start = 0;
+    while start < chunks.len() {
+        let end = (start + MAX_PARALLEL).min(chunks.len());
+        let mut prompts = Vec::with_capacity(end - start);
+        for idx in start..end {
+            let mut opts = PromptOpts {
+                prompt: Some(user_message(&chunks, idx)),
+                models: vec![model.clone()],
+                system: Some(system.clone()),
+                provider: cfg.provider.clone(),
+                priority: cfg.priority,
+                verbosity: Some(Verbosity::Quiet),
+                user: cfg.user_id.clone(),
+                ..Default::default()
+            };
+            if cfg.effort.is_some() {
+                opts.effort = cfg.effort;
+            }
+            let messages = opts.messages()?;
+            prompts.push((opts, messages, 0));
+        }
+        prompt::run_parallel(api_key, cfg, prompts, None, |i, out| {
+            if out.error().is_none() {
+                answers[start + i] = Some(out.contents().to_string());
+            }
+        })?;
+        start = end;
+    }
+
+    msg.clear();
+    write_report(&mut msg, &chunks, &answers, term.has_ansi());
+    w.write_str(&msg)?;
+    w.flush()?;
+    Ok(())
+}
+
+fn read_diff(source: &ReviewSource) -> OrtResult<String> {
+    let cmd = match source {
+        ReviewSource::PatchFile(filename) => {
+            return utils::filename_read_to_string(filename)
+                .map_err(|err| ort_error(ErrorKind::FileReadFailed, err));
+        }
+        ReviewSource::WorkingTree => "git diff HEAD".to_string(),
+        ReviewSource::Staged => "git diff --staged".to_string(),
+        ReviewSource::Range(range) => "git diff ".to_string() + &shell_quote(range),
+    };
+    let out = syscall::system(&cmd)?;
+    if out.exit_code != 0 {
+        utils::print_string(c"", &out.stderr);
+        return Err(ort_error(ErrorKind::InvalidArguments, "git diff failed"));
+    }
+    Ok(out.stdout)
+}
+
+fn shell_quote(s: &str) -> String {
+    "'".to_string() + &s.replace('\'', "'\\''") + "'"
+}
+
+/// One chunk per changed file, or several for a big one. Sections with no
+/// hunks (binary files, pure renames) are skipped.
+fn split_diff(diff: &str, max_bytes: usize) -> Vec<Chunk> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let file = rest
+                .split_once(" b/")
+                .map(|(_, b)| b)
+                .unwrap_or(rest)
+                .trim_end();
+            sections.push((file.to_string(), String::new()));
+        } else if sections.is_empty() {
+            // Not from git, or the email header of a `git format-patch` file
+            if !line.starts_with("--- ") {
+                continue;
+            }
+            sections.push(("patch".to_string(), String::new()));
+        }
+        sections.last_mut().unwrap().1.push_str(line);
+    }
+
+    let mut chunks = Vec::new();
+    for (file, section) in sections {
+        let Some(first_hunk) = section.find("\n@@ ").map(|i| i + 1) else {
+            continue;
+        };
+        let (header, hunks) = section.split_at(first_hunk);
+        let mut current = String::new();
+        for hunk in split_hunks(hunks) {
+            if !current.is_empty() && header.len() + current.len() + hunk.len() > max_bytes {
+                chunks.push(Chunk {
+                    file: file.clone(),
+                    diff: header.to_string() + &current,
+                });
+                current.clear();
+            }
+            current.push_str(hunk);
+        }
+        chunks.push(Chunk {
+            file,
+            diff: header.to_string() + &current,
+        });
+    }
+    chunks
+}
+
+// `s` starts with "@@ "
+fn split_hunks(s: &str) -> Vec<&str> {
+    let mut hunks = Vec::new();
+    let mut start = 0;
+    while let Some(i) = s[start + 1..].find("\n@@ ") {
+        let end = start + 1 + i + 1;
+        hunks.push(&s[start..end]);
+        start = end;
+    }
+    hunks.push(&s[start..]);
+    hunks
+}
+
+fn count_files(chunks: &[Chunk]) -> usize {
+    let mut n = 0;
+    for (i, c) in chunks.iter().enumerate() {
+        if i == 0 || chunks[i - 1].file != c.file {
+            n += 1;
+        }
+    }
+    n
+}
+
+fn user_message(chunks: &[Chunk], idx: usize) -> String {
+    let chunk = &chunks[idx];
+    let parts: Vec<usize> = (0..chunks.len())
+        .filter(|i| chunks[*i].file == chunk.file)
+        .collect();
+    let mut s = "Review this change to ".to_string() + &chunk.file;
+    if parts.len() > 1 {
+        let part = parts.iter().position(|i| *i == idx).unwrap_or_default() + 1;
+        s.push_str(" (part ");
+        s.push_str(&utils::num_to_string(part));
+        s.push_str(" of ");
+        s.push_str(&utils::num_to_string(parts.len()));
+        s.push(')');
+    }
+    s.push_str(":\n\n```diff\n");
+    s.push_str(&chunk.diff);
+    s.push_str("```\n");
+    s
+}
+
+fn parse_findings(answer: &str) -> Vec<(Severity, String)> {
+    let mut findings = Vec::new();
+    for line in answer.lines() {
+        let line = line.trim().trim_start_matches(['-', '*']).trim_start();
+        for sev in Severity::MARKED {
+            if let Some(rest) = line.strip_prefix(sev.marker()) {
+                findings.push((sev, rest.trim().to_string()));
+            }
+        }
+    }
+    let trimmed = answer.trim();
+    let is_clean = trimmed.is_empty() || trimmed.to_lowercase().starts_with("no issues");
+    if findings.is_empty() && !is_clean {
+        findings.push((Severity::Note, trimmed.to_string()));
+    }
+    findings.sort_by_key(|(sev, _)| *sev);
+    findings
+}
+
+fn write_report(out: &mut String, chunks: &[Chunk], answers: &[Option<String>], is_ansi: bool) {
+    let mut counts = [0usize; 3];
+    let mut i = 0;
+    while i < chunks.len() {
+        let file = &chunks[i].file;
+        let mut findings = Vec::new();
+        let mut is_failed = false;
+        while i < chunks.len() && &chunks[i].file == file {
+            match answers[i].as_deref() {
+                Some(answer) => findings.extend(parse_findings(answer)),
+                None => is_failed = true,
+            }
+            i += 1;
+        }
+        findings.sort_by_key(|(sev, _)| *sev);
+
+        if is_ansi {
+            out.push_str(BOLD);
+        }
+        out.push('\n');
+        out.push_str(file);
+        if is_ansi {
+            out.push_str(RESET);
+        }
+        out.push('\n');
+        if is_failed {
+            out.push_str("  request failed, not reviewed\n");
+        } else if findings.is_empty() {
+            out.push_str("  no issues\n");
+        }
+        for (sev, text) in findings {
+            if let Some(pos) = Severity::MARKED.iter().position(|s| *s == sev) {
+                counts[pos] += 1;
+            }
+            out.push_str("  ");
+            if is_ansi {
+                out.push_str(sev.color());
+            }
+            if sev != Severity::Note {
+                out.push_str(sev.marker());
+                out.push(' ');
+            }
+            out.push_str(&text);
+            if is_ansi {
+                out.push_str(RESET);
+            }
+            out.push('\n');
+        }
+    }
+
+    out.push_str("\nReview: ");
+    out.push_str(&utils::num_to_string(counts[0]));
+    out.push_str(" high, ");
+    out.push_str(&utils::num_to_string(counts[1]));
+    out.push_str(" medium, ");
+    out.push_str(&utils::num_to_string(counts[2]));
+    out.push_str(" low\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/a.rs b/src/a.rs
+index 1..2 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,2 +1,2 @@
+-let x = 1;
++let x = 2;
+@@ -10,2 +10,2 @@
+-let y = 1;
++let y = 2;
+diff --git a/logo.png b/logo.png
+Binary files a/logo.png and b/logo.png differ
+diff --git a/README.md b/README.md
+--- a/README.md
++++ b/README.md
+@@ -1 +1 @@
+-old
++new
+";
+
+    #[test]
+    fn split_diff_by_file_then_hunk() {
+        let chunks = split_diff(DIFF, MAX_CHUNK_BYTES);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].file, "src/a.rs");
+        assert!(chunks[0].diff.starts_with("diff --git"));
+        assert!(chunks[0].diff.ends_with("+let y = 2;\n"));
+        assert_eq!(chunks[1].file, "README.md");
+
+        // Too big for one, each hunk gets the file header
+        let chunks = split_diff(DIFF, 150);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(count_files(&chunks), 2);
+        assert!(chunks[1].diff.starts_with("diff --git a/src/a.rs"));
+        assert!(chunks[1].diff.contains("@@ -10,2 +10,2 @@\n-let y = 1;"));
+        assert!(!chunks[1].diff.contains("let x"));
+        assert!(
+            user_message(&chunks, 1).starts_with("Review this change to src/a.rs (part 2 of 2)")
+        );
+    }
+
+    #[test]
+    fn parse_findings_sorted_by_severity() {
+        let answer = "- [low] L3: typo\n[high] L1: panics on empty input\nSome chatter\n";
+        let findings = parse_findings(answer);
+        assert_eq!(
+            findings[0],
+            (Severity::High, "L1: panics on empty input".to_string())
+        );
+        assert_eq!(findings[1], (Severity::Low, "L3: typo".to_string()));
+        assert_eq!(findings.len(), 2);
+
+        assert!(parse_findings("No issues.").is_empty());
+        assert_eq!(parse_findings("Looks risky")[0].0, Severity::Note);
+    }
+
+    #[test]
+    fn report_grouped_by_file() {
+        let chunks = split_diff(DIFF, 150);
+        let answers = vec![
+            Some("[low] L1: magic number".to_string()),
+            Some("[high] L10: y is unused".to_string()),
+            None,
+        ];
+        let mut out = String::new();
+        write_report(&mut out, &chunks, &answers, false);
+        assert_eq!(
+            out,
+            "\nsrc/a.rs\n  [high] L10: y is unused\n  [low] L1: magic number\n\nREADME.md\n  request failed, not reviewed\n\nReview: 1 high, 0 medium, 1 low\n"
+        );
+    }
+
+    #[test]
+    fn quote_range() {
+        assert_eq!(shell_quote("main..HEAD"), "'main..HEAD'");
+        assert_eq!(shell_quote("a'b"), "'a'\\''b'");
+    }
+}
@@ -0,0 +1,481 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `ort serve-openai`: a local OpenAI compatible endpoint.
+//! Forwards `/v1/chat/completions` and `/v1/models` to OpenRouter using ort's
+//! key and config, so other tools don't each need their own setup.
+//! One connection at a time, closed after each response. Only for tools on
+//! this machine: a request must name 127.0.0.1 or localhost as its Host, and
+//! one with an Origin came from a web page, so both are refused.
+
+use core::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+
+extern crate alloc;
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::common::buf_read::OrtBufReader;
+use crate::common::config::Cfg;
+use crate::common::io::ReadLine;
+use crate::common::json_parser::{JsonField, autoparser};
+use crate::common::request_id;
+use crate::common::resolver;
+use crate::common::utils;
+use crate::input::args::ServeOpts;
+use crate::input::to_json::{write_json_str, write_provider_prefs};
+use crate::transport::AsFd as _;
+use crate::transport::socket::{self, TcpListener};
+use crate::utils::print_string;
+use crate::{Context, DEFAULT_MODEL, ErrorKind, OrtResult, Read, Write, http, ort_error};
+use crate::{TcpSocket, TlsStream, chunked};
+
+/// Clients send the whole conversation each time, but this is still generous.
+const MAX_REQUEST_BODY: usize = 32 * 1024 * 1024;
+
+/// Same as input/prompt.rs so the chunked reader generic is shared
+const MAX_CHUNK_SIZE: usize = 128 * 1024;
+
+/// One connection at a time, so a client that goes quiet can't hold it
+const CLIENT_READ_TIMEOUT_MS: u64 = 10_000;
+
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    pub(crate) body: String,
+    pub(crate) host: Option<String>,
+    /// Only a browser sends one, and no web page should spend our key
+    pub(crate) has_origin: bool,
+}
+
+impl Request {
+    /// Addressed to us by name, not by a DNS rebinding hostname, and not
+    /// from a web page
+    fn is_local(&self, port: u16) -> bool {
+        let port = utils::num_to_string(port);
+        let is_our_host = self.host.as_deref().is_some_and(|host| {
+            host.rsplit_once(':').is_some_and(|(name, p)| {
+                p == port && (name == "127.0.0.1" || name.eq_ignore_ascii_case("localhost"))
+            })
+        });
+        is_our_host && !self.has_origin
+    }
+}
+
+pub fn run(api_key: &str, cfg: &Cfg, opts: ServeOpts) -> OrtResult<()> {
+    let (host, port, _) = http::split_url(&cfg.base_url);
+    let upstream_addrs: Vec<SocketAddr> = if cfg.dns.is_empty() {
+        let ips = unsafe { resolver::resolve(host)? };
+        ips.into_iter()
+            .map(|ip| SocketAddr::new(IpAddr::V4(ip), port))
+            .collect()
+    } else {
+        cfg.dns
+            .iter()
+            .map(|a| {
+                let ip_addr = a.parse::<Ipv4Addr>().unwrap();
+                SocketAddr::new(IpAddr::V4(ip_addr), port)
+            })
+            .collect()
+    };
+
+    let listener = TcpListener::bind(&SocketAddrV4::new(Ipv4Addr::LOCALHOST, opts.port))
+        .context("Is the port already in use?")?;
+    let mut msg = "http://127.0.0.1:".to_string();
+    msg.push_str(&utils::num_to_string(opts.port));
+    msg.push_str("/v1");
+    print_string(c"Listening on ", &msg);
+
+    loop {
+        let conn = match listener.accept() {
+            Ok(conn) => conn,
+            Err(err) => {
+                print_string(c"serve-openai accept: ", &err.as_string());
+                continue;
+            }
+        };
+        socket::set_read_timeout(conn.as_fd(), CLIENT_READ_TIMEOUT_MS);
+        if let Err(err) = handle(api_key, cfg, &upstream_addrs, opts.port, conn) {
+            print_string(c"serve-openai: ", &err.as_string());
+        }
+    }
+}
+
+fn handle(
+    api_key: &str,
+    cfg: &Cfg,
+    upstream_addrs: &[SocketAddr],
+    port: u16,
+    conn: TcpSocket,
+) -> OrtResult<()> {
+    let mut reader = OrtBufReader::new(conn);
+    let req = match read_request(&mut reader) {
+        Ok(req) => req,
+        Err(err) => {
+            let client = reader.get_mut();
+            write_error(client, "400 Bad Request", err.context)?;
+            return Err(err);
+        }
+    };
+    let client = reader.get_mut();
+    if !req.is_local(port) {
+        return write_error(client, "403 Forbidden", "Only local clients, not web pages");
+    }
+    let (host, _, base_path) = http::split_url(&cfg.base_url);
+
+    let upstream = match (req.method.as_str(), req.path.as_str()) {
+        ("POST", p) if p.ends_with("/chat/completions") => {
+            let body = match rewrite_body(&req.body, cfg) {
+                Ok(body) => body,
+                Err(err) => {
+                    print_string(c"serve-openai request body: ", &err);
+                    return write_error(client, "400 Bad Request", "Invalid JSON body");
+                }
+            };
+            http::chat_completions(
+                api_key,
+                host,
+                base_path,
+                upstream_addrs.to_vec(),
+                &body,
+                cfg.is_gzip(body.len()),
+                None,
+                None,
+                &request_id::new(),
+            )
+        }
+        ("GET", p) if p.ends_with("/models") => {
+            http::list_models(api_key, host, base_path, upstream_addrs.to_vec())
+                .map(OrtBufReader::new)
+        }
+        _ => {
+            return write_error(client, "404 Not Found", "Unknown endpoint");
+        }
+    };
+    let upstream = match upstream {
+        Ok(u) => u,
+        Err(err) => {
+            write_error(client, "502 Bad Gateway", "Could not reach upstream")?;
+            return Err(err);
+        }
+    };
+    relay(upstream, client)
+}
+
+/// Read the request line, headers and body of an HTTP/1.1 request
+pub(crate) fn read_request<R: Read>(r: &mut OrtBufReader<R>) -> OrtResult<Request> {
+    let mut line = String::with_capacity(256);
+    if r.read_line(&mut line)? == 0 {
+        return Err(ort_error(ErrorKind::UnexpectedEof, "Missing request line"));
+    }
+    let mut parts = line.split_ascii_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(ort_error(ErrorKind::FormatError, "Invalid request line"));
+    };
+    let method = method.to_string();
+    let path = path.to_string();
+
+    let mut content_length = 0;
+    let mut host = None;
+    let mut has_origin = false;
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Err(ort_error(ErrorKind::UnexpectedEof, "Truncated headers"));
+        }
+        let header = line.trim();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = value
+                .parse::<usize>()
+                .map_err(|_| ort_error(ErrorKind::FormatError, "Invalid Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("Host") {
+            host = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("Origin") {
+            has_origin = true;
+        } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            return Err(ort_error(
+                ErrorKind::FormatError,
+                "Chunked requests not supported, send Content-Length",
+            ));
+        }
+    }
+    if content_length > MAX_REQUEST_BODY {
+        return Err(ort_error(ErrorKind::FormatError, "Request body too large"));
+    }
+
+    let mut body = vec![0u8; content_length];
+    r.read_exact(&mut body)?;
+    let body = String::from_utf8(body)
+        .map_err(|_| ort_error(ErrorKind::FormatError, "Request body is not UTF-8"))?;
+
+    Ok(Request {
+        method,
+        path,
+        body,
+        host,
+        has_origin,
+    })
+}
+
+/// Fill in what the client did not set from ort's config: the model, and
+/// the provider routing preferences.
+fn rewrite_body(body: &str, cfg: &Cfg) -> Result<String, Cow<'static, str>> {
+    let mut fields = [
+        JsonField::new_string("model"),
+        JsonField::new_raw("provider"),
+    ];
+    autoparser(body, &mut fields)?;
+    let has_model = fields[0]
+        .get_string()
+        .map(|m| !m.is_empty())
+        .unwrap_or(false);
+    let has_provider = fields[1].get_raw().is_some();
+
+    let mut extra: Vec<u8> = Vec::with_capacity(128);
+    if !has_model {
+        let model = cfg
+            .models
+            .first()
+            .map(|m| m.as_str())
+            .unwrap_or(DEFAULT_MODEL);
+        extra.write_str("\"model\": ").map_err(|e| e.as_string())?;
+        write_json_str(&mut extra, model).map_err(|e| e.as_string())?;
+    }
+    if !has_provider && (cfg.priority.is_some() || cfg.provider.is_some()) {
+        if !extra.is_empty() {
+            extra.write_str(", ").map_err(|e| e.as_string())?;
+        }
+        write_provider_prefs(
+            &mut extra,
+            cfg.priority,
+            cfg.provider.as_deref(),
+            false,
+            &[],
+            None,
+        )
+        .map_err(|e| e.as_string())?;
+    }
+    if extra.is_empty() {
+        return Ok(body.to_string());
+    }
+    let extra = unsafe { String::from_utf8_unchecked(extra) };
+
+    // autoparser already checked it's an object, so it starts with '{'
+    let rest = body.trim_start()[1..].trim_start();
+    let mut out = String::with_capacity(body.len() + extra.len() + 4);
+    out.push('{');
+    out.push_str(&extra);
+    if !rest.starts_with('}') {
+        out.push_str(", ");
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Copy upstream's response to the client.
+/// We always send our own headers, with upstream's Content-Type, and
+/// re-chunk streamed bodies.
+fn relay(
+    mut upstream: OrtBufReader<TlsStream<TcpSocket>>,
+    client: &mut TcpSocket,
+) -> OrtResult<()> {
+    let header = match http::read_header(&mut upstream) {
+        Ok(header) => header,
+        Err(http_err) => {
+            // "HTTP/1.1 400 Bad Request" -> "400 Bad Request"
+            let status = http_err
+                .status_line()
+                .strip_prefix("HTTP/1.1 ")
+                .unwrap_or("502 Bad Gateway");
+            let mut msg = String::with_capacity(64);
+            msg.push_str("HTTP/1.1 ");
+            msg.push_str(status);
+            msg.push_str("\r\nContent-Type: application/json\r\nContent-Length: ");
+            msg.push_str(&utils::num_to_string(http_err.body().len()));
+            msg.push_str("\r\nConnection: close\r\n\r\n");
+            msg.push_str(http_err.body());
+            return client
+                .write_all(msg.as_bytes())
+                .context("write error response");
+        }
+    };
+
+    let content_type = header.content_type().unwrap_or("application/json");
+    match header.body() {
+        http::ResponseBody::Chunked => {
+            let mut msg = String::with_capacity(128);
+            msg.push_str("HTTP/1.1 200 OK\r\nContent-Type: ");
+            msg.push_str(content_type);
+            msg.push_str("\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n");
+            client
+                .write_all(msg.as_bytes())
+                .context("write response header")?;
+            let mut chunks = chunked::read::<_, MAX_CHUNK_SIZE>(upstream);
+            while let Some(chunk) = chunks.next_chunk() {
+                let chunk = chunk?;
+                let mut size_line = hex(chunk.len());
+                size_line.push_str("\r\n");
+                client
+                    .write_all(size_line.as_bytes())
+                    .context("write chunk")?;
+                client.write_all(chunk.as_bytes()).context("write chunk")?;
+                client.write_all(b"\r\n").context("write chunk")?;
+            }
+            client.write_all(b"0\r\n\r\n").context("write last chunk")?;
+        }
+        other => {
+            let body = http::read_body(upstream, other)?;
+            let mut msg = String::with_capacity(128 + body.len());
+            msg.push_str("HTTP/1.1 200 OK\r\nContent-Type: ");
+            msg.push_str(content_type);
+            msg.push_str("\r\nContent-Length: ");
+            msg.push_str(&utils::num_to_string(body.len()));
+            msg.push_str("\r\nConnection: close\r\n\r\n");
+            msg.push_str(&body);
+            client.write_all(msg.as_bytes()).context("write response")?;
+        }
+    }
+    Ok(())
+}
+
+/// An OpenAI shaped error response
+fn write_error(client: &mut TcpSocket, status: &str, message: &str) -> OrtResult<()> {
+    let mut body: Vec<u8> = Vec::with_capacity(64 + message.len());
+    body.write_str("{\"error\":{\"message\":")?;
+    write_json_str(&mut body, message)?;
+    body.write_str("}}")?;
+
+    let mut msg = String::with_capacity(128);
+    msg.push_str("HTTP/1.1 ");
+    msg.push_str(status);
+    msg.push_str("\r\nContent-Type: application/json\r\nContent-Length: ");
+    msg.push_str(&utils::num_to_string(body.len()));
+    msg.push_str("\r\nConnection: close\r\n\r\n");
+    client
+        .write_all(msg.as_bytes())
+        .context("write error header")?;
+    client.write_all(&body).context("write error body")
+}
+
+/// Lower case hex, for chunk sizes
+pub(crate) fn hex(mut n: usize) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    if n == 0 {
+        return "0".to_string();
+    }
+    let mut out = Vec::with_capacity(16);
+    while n > 0 {
+        out.push(DIGITS[n % 16]);
+        n /= 16;
+    }
+    out.reverse();
+    unsafe { String::from_utf8_unchecked(out) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    struct BytesReader {
+        data: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Read for BytesReader {
+        fn read(&mut self, buf: &mut [u8]) -> OrtResult<usize> {
+            let count = (self.data.len() - self.pos).min(buf.len());
+            buf[..count].copy_from_slice(&self.data[self.pos..self.pos + count]);
+            self.pos += count;
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn read_request_with_body() {
+        let body = r#"{"messages":[]}"#;
+        let raw = "POST /v1/chat/completions HTTP/1.1\r\nHost: localhost\r\nContent-Length: "
+            .to_string()
+            + &utils::num_to_string(body.len())
+            + "\r\n\r\n"
+            + body;
+        let mut r = OrtBufReader::new(BytesReader {
+            data: raw.into_bytes(),
+            pos: 0,
+        });
+        let req = read_request(&mut r).unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/v1/chat/completions");
+        assert_eq!(req.body, body);
+        // Without the port
+        assert!(!req.is_local(8080));
+    }
+
+    #[test]
+    fn only_local_requests() {
+        let request = |headers: &str| {
+            let raw = "GET /v1/models HTTP/1.1\r\n".to_string() + headers + "\r\n";
+            let mut r = OrtBufReader::new(BytesReader {
+                data: raw.into_bytes(),
+                pos: 0,
+            });
+            read_request(&mut r).unwrap()
+        };
+        assert!(request("Host: 127.0.0.1:8080\r\n").is_local(8080));
+        assert!(request("Host: LocalHost:8080\r\n").is_local(8080));
+        assert!(!request("Host: localhost:9090\r\n").is_local(8080));
+        assert!(!request("Host: evil.example:8080\r\n").is_local(8080));
+        assert!(!request("").is_local(8080));
+        let cross_site = "Host: localhost:8080\r\nOrigin: https://evil.example\r\n";
+        assert!(!request(cross_site).is_local(8080));
+    }
+
+    #[test]
+    fn rewrite_body_adds_model_and_provider() {
+        let cfg = Cfg {
+            models: vec!["openai/gpt-oss-20b".to_string()],
+            priority: Some(Priority::Latency),
+            ..Cfg::default()
+        };
+        let got = rewrite_body(r#"{"stream":true}"#, &cfg).unwrap();
+        assert_eq!(
+            got,
+            r#"{"model": "openai/gpt-oss-20b", "provider": {"sort":"latency"}, "stream":true}"#
+        );
+
+        let got = rewrite_body("{}", &cfg).unwrap();
+        assert_eq!(
+            got,
+            r#"{"model": "openai/gpt-oss-20b", "provider": {"sort":"latency"}}"#
+        );
+    }
+
+    #[test]
+    fn rewrite_body_keeps_client_choices() {
+        let cfg = Cfg {
+            models: vec!["openai/gpt-oss-20b".to_string()],
+            provider: Some("groq".to_string()),
+            ..Cfg::default()
+        };
+        let body = r#"{"model":"x/y","provider":{"order":["z"]}}"#;
+        assert_eq!(rewrite_body(body, &cfg).unwrap(), body);
+    }
+
+    #[test]
+    fn hex_chunk_size() {
+        assert_eq!(hex(0), "0");
+        assert_eq!(hex(255), "ff");
+        assert_eq!(hex(4096), "1000");
+    }
+}
@@ -0,0 +1,136 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Reading what was piped in. A GUI launcher or a CI runner can leave stdin
+//! open with nothing ever written to it, or attached to binary data. So
+//! unless it is a pipe or a file wait only so long for the first byte,
+//! ignore binary input with a warning, and with `--stdin-null` don't read it
+//! at all.
+
+extern crate alloc;
+use alloc::string::{String, ToString};
+
+use core::ffi::c_int;
+use core::mem::MaybeUninit;
+
+use crate::common::{buf_read, utils};
+use crate::{ErrorKind, OrtResult, ort_error, syscall};
+
+const STDIN_FILENO: c_int = 0;
+
+/// How long to wait for the first byte of anything but a pipe or a file,
+/// unless `--stdin-timeout`
+const DEFAULT_TIMEOUT_MS: c_int = 10_000;
+
+/// `st_mode` file types
+const S_IFMT: u32 = 0o170000;
+const S_IFIFO: u32 = 0o010000;
+const S_IFREG: u32 = 0o100000;
+
+/// Only the start is checked, as git does
+const BINARY_CHECK_BYTES: usize = 8000;
+
+// Only written once at startup, before anything reads stdin
+static mut IS_NULL: bool = false;
+static mut TIMEOUT_MS: Option<c_int> = None;
+static mut IS_LOSSY: bool = false;
+
+/// `--stdin-null`: never read stdin, as if nothing was piped in
+pub fn set_null() {
+    unsafe { IS_NULL = true };
+}
+
+/// `--stdin-timeout`, 0 waits forever
+pub fn set_timeout_ms(ms: u32) {
+    unsafe { TIMEOUT_MS = Some(ms.min(c_int::MAX as u32) as c_int) };
+}
+
+/// `--clean-input`: keep text that isn't all UTF-8, the rest becomes U+FFFD
+pub fn set_lossy() {
+    unsafe { IS_LOSSY = true };
+}
+
+pub fn is_null() -> bool {
+    unsafe { IS_NULL }
+}
+
+/// Everything on stdin. None if it was empty, binary, nothing arrived
+/// within the default wait, or `--stdin-null`. An error if nothing arrived
+/// within `--stdin-timeout`, the caller asked for it.
+pub fn read() -> OrtResult<Option<String>> {
+    if is_null() {
+        return Ok(None);
+    }
+    let explicit_ms = unsafe { TIMEOUT_MS };
+    // A pipe can take its time, the command writing it may be slow
+    let timeout_ms = explicit_ms.unwrap_or(if is_pipe_or_file() {
+        0
+    } else {
+        DEFAULT_TIMEOUT_MS
+    });
+    // poll says a file or /dev/null is ready at once, and a closed pipe too
+    if timeout_ms > 0 && syscall::poll_read(STDIN_FILENO, timeout_ms) == 0 {
+        if explicit_ms.is_some() {
+            return Err(ort_error(
+                ErrorKind::StdinTimeout,
+                "Nothing on stdin within --stdin-timeout",
+            ));
+        }
+        let msg = "Nothing on stdin after ".to_string()
+            + &utils::num_to_string(DEFAULT_TIMEOUT_MS / 1000)
+            + "s, carrying on without it. Use --stdin-null to skip it, or --stdin-timeout 0 to wait.\n";
+        utils::warn(&msg);
+        return Ok(None);
+    }
+    let mut buffer = String::with_capacity(8 * 1024);
+    if !buf_read::fd_read_to_string(STDIN_FILENO, &mut buffer, unsafe { IS_LOSSY })
+        && buffer.is_empty()
+    {
+        utils::warn("Ignoring stdin, it isn't UTF-8. Use --clean-input to keep its text.\n");
+        return Ok(None);
+    }
+    if buffer.is_empty() {
+        return Ok(None);
+    }
+    if is_binary(&buffer) {
+        utils::warn("Ignoring stdin, it looks binary. Use --stdin-null to skip it.\n");
+        return Ok(None);
+    }
+    Ok(Some(buffer))
+}
+
+/// A pipe, FIFO or regular file, not a socket or a device
+fn is_pipe_or_file() -> bool {
+    let mut st = MaybeUninit::<syscall::Stat>::uninit();
+    // The link follows to whatever stdin is
+    if syscall::stat(c"/proc/self/fd/0".as_ptr(), &mut st).is_err() {
+        return false;
+    }
+    let file_type = unsafe { st.assume_init() }.st_mode & S_IFMT;
+    file_type == S_IFIFO || file_type == S_IFREG
+}
+
+/// A nul byte near the start. Text never has one, most binary formats do.
+fn is_binary(s: &str) -> bool {
+    s.as_bytes()
+        .iter()
+        .take(BINARY_CHECK_BYTES)
+        .any(|b| *b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_binary;
+
+    #[test]
+    fn binary() {
+        assert!(!is_binary("fn main() {}\n"));
+        assert!(!is_binary("caf\u{e9} \u{fffd}\n"));
+        assert!(is_binary("\u{7f}ELF\u{2}\u{1}\u{1}\0\0\0"));
+        let late_nul = "a".repeat(super::BINARY_CHECK_BYTES) + "\0";
+        assert!(!is_binary(&late_nul));
+    }
+}
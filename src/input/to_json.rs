@@ -5,11 +5,16 @@
 //! Copyright (c) 2025 Graham King
 
 extern crate alloc;
-use alloc::string::String;
+use alloc::string::{String, ToString};
 
 use crate::{
-    ErrorKind, Message, OrtResult, PromptOpts, ReasoningEffort, Write,
-    common::data::{Content, Tool, ToolCall, ToolParameter},
+    ErrorKind, LastData, Message, OrtResult, Priority, PromptOpts, ReasoningEffort, Verbosity,
+    Write,
+    common::data::{Content, ONELINE_INSTRUCTION, Role, Tool, ToolCall, ToolParameter},
+    common::json_parser::Parser,
+    common::stats::Stats,
+    common::tokens::{self, Family},
+    common::utils,
     ort_error,
 };
 
@@ -30,42 +35,97 @@ pub fn build_body(
     w.write_str("{\"stream\": true, \"model\": ")?;
     write_json_str(w, opts.models.get(idx).expect("Missing model"))?;
 
-    if opts.priority.is_some() || opts.provider.is_some() {
-        w.write_str(", \"provider\": {")?;
-        let mut is_first = true;
-        if let Some(p) = opts.priority {
-            w.write_str("\"sort\":")?;
-            write_json_str_simple(w, p.as_str())?;
-            is_first = false;
-        }
-        if let Some(pr) = &opts.provider {
-            if !is_first {
-                w.write_str(", ")?;
-            }
-            w.write_str("\"order\": [")?;
-            write_json_str(w, pr)?;
-            w.write_char(']')?;
-        }
-        w.write_char('}')?;
+    // OpenRouter always sends usage, other OpenAI compatible servers need asking.
+    w.write_str(", \"stream_options\": {\"include_usage\": true}")?;
+
+    let max_price = opts.max_cost.map(|cost| {
+        let family = Family::from_model(&opts.models[idx]);
+        MaxPrice::new(cost, tokens::estimate_messages(messages, family))
+    });
+    if opts.priority.is_some()
+        || opts.provider.is_some()
+        || !opts.ignore_providers.is_empty()
+        || max_price.is_some()
+    {
+        w.write_str(", ")?;
+        write_provider_prefs(
+            w,
+            opts.priority,
+            opts.provider.as_deref(),
+            opts.pin_provider,
+            &opts.ignore_providers,
+            max_price,
+        )?;
     }
 
     w.write_str(", \"reasoning\": ")?;
-    match &opts.effort {
-        // No -r and nothing in config file
+    match (&opts.effort, opts.reasoning_tokens) {
         // cli "-r off" or config file '"enabled": false'
-        None | Some(ReasoningEffort::None) => {
+        (Some(ReasoningEffort::None), _) => {
+            w.write_str("{\"enabled\": false}")?;
+        }
+        // --reasoning-tokens, a budget instead of an effort
+        (_, Some(budget)) => {
+            w.write_str("{\"exclude\": false, \"enabled\": true, \"max_tokens\": ")?;
+            w.write_str(&utils::num_to_string(budget))?;
+            w.write_char('}')?;
+        }
+        // No -r and nothing in config file
+        (None, None) => {
             w.write_str("{\"enabled\": false}")?;
         }
         // Reasoning on
-        Some(effort) => {
+        (Some(effort), None) => {
             w.write_str("{\"exclude\": false, \"enabled\": true, \"effort\":")?;
             write_json_str_simple(w, effort.as_str())?;
             w.write_char('}')?;
         }
     };
 
+    if let Some(n) = opts.n.filter(|n| *n > 1) {
+        w.write_str(", \"n\": ")?;
+        w.write_str(&crate::utils::num_to_string(n))?;
+    }
+
+    if let Some(user) = opts.user.as_deref() {
+        w.write_str(", \"user\": ")?;
+        write_json_str(w, user)?;
+    }
+    if !opts.labels.is_empty() {
+        w.write_str(", \"metadata\": {")?;
+        for (i, (k, v)) in opts.labels.iter().enumerate() {
+            if i != 0 {
+                w.write_str(", ")?;
+            }
+            write_json_str(w, k)?;
+            w.write_char(':')?;
+            write_json_str(w, v)?;
+        }
+        w.write_char('}')?;
+    }
+
+    if opts.output_image.is_some() {
+        w.write_str(", \"modalities\": [\"image\", \"text\"]")?;
+    }
+
+    // Values were checked by json_scalar when parsing the args
+    for (k, v) in &opts.params {
+        w.write_str(", ")?;
+        write_json_str(w, k)?;
+        w.write_str(": ")?;
+        w.write_str(v)?;
+    }
+
     w.write_str(", \"messages\":")?;
-    Message::write_json_array(messages, w)?;
+    if opts.oneline {
+        // Just before the prompt, and only in this request
+        let mut with_instruction = messages.to_vec();
+        let at = with_instruction.len().saturating_sub(1);
+        with_instruction.insert(at, Message::system(ONELINE_INSTRUCTION.to_string()));
+        Message::write_json_array_for(&with_instruction, &opts.models[idx], w)?;
+    } else {
+        Message::write_json_array_for(messages, &opts.models[idx], w)?;
+    }
 
     w.write_str(", \"tools\":")?;
     Tool::write_json_array(client_tools, opts.include_web_tools.unwrap_or_default(), w)?;
@@ -79,6 +139,154 @@ pub fn build_body(
     Ok(string_buf)
 }
 
+/// Top level fields `build_body` may write. `--param` can't set these.
+pub(crate) const BODY_FIELDS: [&str; 11] = [
+    "stream",
+    "model",
+    "stream_options",
+    "provider",
+    "reasoning",
+    "n",
+    "user",
+    "metadata",
+    "modalities",
+    "messages",
+    "tools",
+];
+
+/// A `--param` value as JSON text: a number, true, false, null or a
+/// "quoted string". A string is decoded and escaped again, so whatever was
+/// inside the quotes can't break the body.
+pub(crate) fn json_scalar(s: &str) -> Option<String> {
+    if matches!(s, "true" | "false" | "null") || is_json_number(s) {
+        return Some(s.to_string());
+    }
+    if !s.starts_with('"') {
+        return None;
+    }
+    let mut p = Parser::new(s);
+    let decoded = p.parse_string().ok()?;
+    p.skip_ws();
+    if p.peek().is_some() {
+        return None;
+    }
+    let mut out = String::with_capacity(s.len());
+    write_json_str(unsafe { out.as_mut_vec() }, &decoded).ok()?;
+    Some(out)
+}
+
+/// -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+fn is_json_number(s: &str) -> bool {
+    let b = s.as_bytes();
+    let mut i = usize::from(b.first() == Some(&b'-'));
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while b.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        *i - start
+    };
+    match digits(&mut i) {
+        0 => return false,
+        n if n > 1 && b[i - n] == b'0' => return false,
+        _ => {}
+    }
+    if b.get(i) == Some(&b'.') {
+        i += 1;
+        if digits(&mut i) == 0 {
+            return false;
+        }
+    }
+    if matches!(b.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(b.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        if digits(&mut i) == 0 {
+            return false;
+        }
+    }
+    i == b.len()
+}
+
+/// OpenRouter's `max_price` routing preference for a --max-cost request.
+/// Providers whose prompt price alone would go over are skipped. What the
+/// answer costs isn't known up front, the stream loop watches that.
+#[derive(Clone, Copy)]
+pub(crate) struct MaxPrice {
+    // Dollars per million prompt tokens
+    prompt: f64,
+    // Dollars per request
+    request: f64,
+}
+
+impl MaxPrice {
+    fn new(max_cost: f64, prompt_tokens: usize) -> Self {
+        MaxPrice {
+            prompt: max_cost * 1_000_000.0 / prompt_tokens.max(1) as f64,
+            request: max_cost,
+        }
+    }
+}
+
+/// The "provider" routing preferences field.
+/// Caller must check at least one of priority, provider, ignore or max_price is set.
+pub(crate) fn write_provider_prefs<W: Write>(
+    w: &mut W,
+    priority: Option<Priority>,
+    provider: Option<&str>,
+    // No other provider if that one fails
+    is_pinned: bool,
+    ignore: &[String],
+    max_price: Option<MaxPrice>,
+) -> OrtResult<()> {
+    w.write_str("\"provider\": {")?;
+    let mut is_first = true;
+    if let Some(p) = priority {
+        w.write_str("\"sort\":")?;
+        write_json_str_simple(w, p.as_str())?;
+        is_first = false;
+    }
+    if let Some(pr) = provider {
+        if !is_first {
+            w.write_str(", ")?;
+        }
+        w.write_str("\"order\": [")?;
+        write_json_str(w, pr)?;
+        w.write_char(']')?;
+        if is_pinned {
+            w.write_str(", \"allow_fallbacks\": false")?;
+        }
+        is_first = false;
+    }
+    if !ignore.is_empty() {
+        if !is_first {
+            w.write_str(", ")?;
+        }
+        w.write_str("\"ignore\": [")?;
+        for (i, pr) in ignore.iter().enumerate() {
+            if i != 0 {
+                w.write_str(", ")?;
+            }
+            write_json_str(w, pr)?;
+        }
+        w.write_char(']')?;
+        is_first = false;
+    }
+    if let Some(mp) = max_price {
+        if !is_first {
+            w.write_str(", ")?;
+        }
+        w.write_str("\"max_price\": {\"prompt\": ")?;
+        w.write_str(&utils::float_to_string(mp.prompt, 6))?;
+        w.write_str(", \"request\": ")?;
+        w.write_str(&utils::float_to_string(mp.request, 6))?;
+        w.write_char('}')?;
+    }
+    w.write_char('}')?;
+    Ok(())
+}
+
 impl PromptOpts {
     pub fn to_json_writer<W: Write>(&self, writer: &mut W) -> OrtResult<()> {
         let w = writer;
@@ -141,6 +349,15 @@ impl PromptOpts {
             w.write_str("\"effort\":")?;
             write_json_str_simple(w, eff.as_str())?;
         }
+        if let Some(budget) = self.reasoning_tokens {
+            if !first {
+                w.write_char(',')?;
+            } else {
+                first = false;
+            }
+            w.write_str("\"reasoning_tokens\":")?;
+            w.write_str(&utils::num_to_string(budget))?;
+        }
         if let Some(show) = self.show_reasoning {
             if !first {
                 w.write_char(',')?;
@@ -150,14 +367,14 @@ impl PromptOpts {
             w.write_str("\"show_reasoning\":")?;
             write_bool(w, show)?;
         }
-        if let Some(quiet) = self.quiet {
+        if let Some(verbosity) = self.verbosity {
             if !first {
                 w.write_char(',')?;
             } else {
                 //first = false;
             }
             w.write_str("\"quiet\":")?;
-            write_bool(w, quiet)?;
+            write_bool(w, verbosity != Verbosity::Normal)?;
         }
 
         // merge_config
@@ -208,6 +425,37 @@ impl Message {
         w.write_char(']')?;
         Ok(())
     }
+
+    /// For a request body: system and developer roles as this model wants them
+    pub fn write_json_array_for<W: Write>(
+        msgs: &[Message],
+        model: &str,
+        w: &mut W,
+    ) -> OrtResult<()> {
+        w.write_char('[')?;
+        for (i, msg) in msgs.iter().enumerate() {
+            if i != 0 {
+                w.write_char(',')?;
+            }
+            write_json_message_as(msg, msg.role.for_model(model), w)?;
+        }
+        w.write_char(']')?;
+        Ok(())
+    }
+}
+
+impl LastData {
+    /// The same shape LastWriter streams out, for a conversation we already have whole
+    pub fn to_json_writer<W: Write>(&self, w: &mut W) -> OrtResult<()> {
+        w.write_str(r#"{"tools": "#)?;
+        Tool::write_json_array(&self.tools, false, w)?;
+        w.write_str(r#", "messages":"#)?;
+        Message::write_json_array(&self.messages, w)?;
+        w.write_str(",\"opts\":")?;
+        self.opts.to_json_writer(w)?;
+        w.write_char('}')?;
+        Ok(())
+    }
 }
 
 const WEB_TOOLS: &str = r#"{"type": "openrouter:web_search"}, {"type": "openrouter:web_fetch"}"#;
@@ -305,11 +553,15 @@ impl ToolCall {
 }
 
 pub fn write_json_message<W: Write>(data: &Message, w: &mut W) -> OrtResult<()> {
+    write_json_message_as(data, data.role, w)
+}
+
+fn write_json_message_as<W: Write>(data: &Message, role: Role, w: &mut W) -> OrtResult<()> {
     if data.content.is_empty() && data.reasoning.is_none() && data.tool_calls.is_empty() {
         return Ok(());
     }
     w.write_str("{\"role\":")?;
-    write_json_str_simple(w, data.role.as_str())?;
+    write_json_str_simple(w, role.as_str())?;
     if let Some(tool_call_id) = &data.tool_call_id {
         w.write_str(",\"tool_call_id\":")?;
         write_json_str_simple(w, tool_call_id)?;
@@ -403,6 +655,48 @@ impl Content {
     }
 }
 
+/// Stats as JSON for `--json`. Times are in milliseconds, unknowns are null.
+pub fn write_stats_json<W: Write>(w: &mut W, stats: &Stats) -> OrtResult<()> {
+    w.write_str("{\"model\":")?;
+    write_json_str(w, &stats.used_model)?;
+    w.write_str(",\"provider\":")?;
+    write_json_str(w, &stats.provider)?;
+    w.write_str(",\"cost_cents\":")?;
+    write_opt_num(w, stats.cost_in_cents.map(|c| utils::float_to_string(c, 6)))?;
+    w.write_str(",\"is_byok\":")?;
+    write_bool(w, stats.is_byok)?;
+    w.write_str(",\"is_cancelled\":")?;
+    write_bool(w, stats.is_cancelled)?;
+    w.write_str(",\"elapsed_ms\":")?;
+    w.write_str(&utils::num_to_string(stats.elapsed_time.as_millis()))?;
+    w.write_str(",\"time_to_first_token_ms\":")?;
+    write_opt_num(
+        w,
+        stats
+            .time_to_first_token
+            .map(|t| utils::num_to_string(t.as_millis())),
+    )?;
+    w.write_str(",\"completion_tokens\":")?;
+    write_opt_num(w, stats.completion_tokens.map(utils::num_to_string))?;
+    w.write_str(",\"reasoning_tokens\":")?;
+    write_opt_num(w, stats.reasoning_tokens.map(utils::num_to_string))?;
+    w.write_str(",\"tokens_per_sec\":")?;
+    write_opt_num(
+        w,
+        stats.tokens_per_sec().map(|t| utils::float_to_string(t, 1)),
+    )?;
+    w.write_str(",\"inter_token_latency_ms\":")?;
+    w.write_str(&utils::num_to_string(stats.inter_token_latency_ms))?;
+    w.write_str(",\"web_search_requests\":")?;
+    write_opt_num(w, stats.web_search_requests.map(utils::num_to_string))?;
+    w.write_char('}')?;
+    Ok(())
+}
+
+fn write_opt_num<W: Write>(w: &mut W, num: Option<String>) -> OrtResult<usize> {
+    w.write_str(num.as_deref().unwrap_or("null"))
+}
+
 /// No escapes or special characters, just write the bytes
 pub(crate) fn write_json_str_simple<W: Write>(w: &mut W, s: &str) -> OrtResult<()> {
     w.write_char('"')?;
@@ -469,8 +763,10 @@ mod tests {
     extern crate alloc;
     use alloc::string::ToString;
     use alloc::vec;
+    use alloc::vec::Vec;
 
     use super::*;
+    use crate::common::data::{Flush, StdinPos};
     use crate::common::tools::ALL_TOOLS;
 
     #[test]
@@ -481,14 +777,49 @@ mod tests {
             models: vec!["google/gemma-3n-e4b-it:free".to_string()],
             provider: Some("google-ai-studio".to_string()),
             system: Some("System prompt here".to_string()),
+            append_system: None,
             priority: None,
             effort: None,
             show_reasoning: Some(false),
-            quiet: None,
+            verbosity: None,
             merge_config: false,
             prompt_filename: None,
             files: vec![], // TODO
             include_web_tools: Some(true),
+            output_image: None,
+            first_token_deadline_ms: None,
+            deadline_ms: None,
+            ignore_providers: vec![],
+            stdin: None,
+            n: None,
+            raw: false,
+            max_steps: None,
+            labels: vec![],
+            params: vec![],
+            user: None,
+            extract: None,
+            is_json: false,
+            out_dir: None,
+            max_cost: None,
+            assume_yes: false,
+            flush: Flush::Chunk,
+            verbose: false,
+            ctx: vec![],
+            stdin_pos: StdinPos::After,
+            template: None,
+            allow_secrets: false,
+            clean_input: false,
+            oneline: false,
+            model_suffix: None,
+            tee: None,
+            tee_all: false,
+            model_source: Default::default(),
+            queue_on_fail: false,
+            save_code: None,
+            reasoning_tokens: None,
+            strict_reasoning: false,
+            pin_provider: false,
+            retries: None,
         };
         let messages = vec![
             Message::user("Hello".to_string()),
@@ -501,8 +832,180 @@ mod tests {
             }
         };
 
-        let expected = r#"{"stream": true, "model": "google/gemma-3n-e4b-it:free", "provider": {"order": ["google-ai-studio"]}, "reasoning": {"enabled": false}, "messages":[{"role":"user","content":"Hello"},{"role":"assistant","content":"Hello there!"}], "tools":[{"type": "openrouter:web_search"}, {"type": "openrouter:web_fetch"},{"type": "function", "function": {"name": "read", "description": "Read the contents of a text file.", "parameters": {"type": "object", "properties": {"path": {"type": "string", "description": "Path to the file to read (relative or absolute)"},"offset": {"type": "number", "description": "Line number to start reading from (1-indexed)"},"limit": {"type": "number", "description": "Maximum number of lines to read"}}, "required": ["path"]}}}]}"#;
+        let expected = r#"{"stream": true, "model": "google/gemma-3n-e4b-it:free", "stream_options": {"include_usage": true}, "provider": {"order": ["google-ai-studio"]}, "reasoning": {"enabled": false}, "messages":[{"role":"user","content":"Hello"},{"role":"assistant","content":"Hello there!"}], "tools":[{"type": "openrouter:web_search"}, {"type": "openrouter:web_fetch"},{"type": "function", "function": {"name": "read", "description": "Read the contents of a text file.", "parameters": {"type": "object", "properties": {"path": {"type": "string", "description": "Path to the file to read (relative or absolute)"},"offset": {"type": "number", "description": "Line number to start reading from (1-indexed)"},"limit": {"type": "number", "description": "Maximum number of lines to read"}}, "required": ["path"]}}}]}"#;
 
         assert_eq!(got, expected);
     }
+
+    #[test]
+    fn test_build_body_output_image() {
+        let opts = PromptOpts {
+            models: vec!["google/gemini-2.5-flash-image".to_string()],
+            effort: None,
+            output_image: Some("out.png".to_string()),
+            ..PromptOpts::default()
+        };
+        let messages = vec![Message::user("A cat".to_string())];
+        let got = build_body(0, &opts, &messages, &[]).unwrap();
+        assert!(got.contains(r#", "modalities": ["image", "text"], "messages":"#));
+        assert!(!got.contains(r#""n":"#));
+
+        let opts = PromptOpts { n: Some(3), ..opts };
+        let got = build_body(0, &opts, &messages, &[]).unwrap();
+        assert!(got.contains(r#", "n": 3, "modalities""#));
+    }
+
+    #[test]
+    fn test_build_body_user_and_labels() {
+        let opts = PromptOpts {
+            effort: None,
+            user: Some("team-42".to_string()),
+            labels: vec![
+                ("project".to_string(), "ort".to_string()),
+                ("note".to_string(), "a \"b\"".to_string()),
+            ],
+            ..PromptOpts::default()
+        };
+        let messages = vec![Message::user("Hi".to_string())];
+        let got = build_body(0, &opts, &messages, &[]).unwrap();
+        assert!(got.contains(
+            r#", "user": "team-42", "metadata": {"project":"ort", "note":"a \"b\""}, "messages":"#
+        ));
+    }
+
+    #[test]
+    fn test_build_body_params() {
+        let opts = PromptOpts {
+            effort: None,
+            params: vec![
+                ("top_k".to_string(), "40".to_string()),
+                ("repetition_penalty".to_string(), "1.1".to_string()),
+            ],
+            ..PromptOpts::default()
+        };
+        let messages = vec![Message::user("Hi".to_string())];
+        let got = build_body(0, &opts, &messages, &[]).unwrap();
+        assert!(got.contains(r#", "top_k": 40, "repetition_penalty": 1.1, "messages":"#));
+    }
+
+    #[test]
+    fn test_json_scalar() {
+        for ok in [
+            "0", "-1", "40", "1.1", "-0.5e-3", "2E10", "true", "false", "null",
+        ] {
+            assert_eq!(json_scalar(ok).as_deref(), Some(ok));
+        }
+        for bad in [
+            "", "-", "01", "1.", ".5", "1e", "+1", "NaN", "True", "END", "\"END", "\"a\" 1", "[]",
+        ] {
+            assert!(json_scalar(bad).is_none(), "{bad}");
+        }
+        assert_eq!(json_scalar(r#""END""#).as_deref(), Some(r#""END""#));
+        // Decoded and escaped again
+        assert_eq!(json_scalar(r#""a\u0022\n""#).as_deref(), Some(r#""a\"\n""#));
+    }
+
+    #[test]
+    fn test_build_body_oneline() {
+        let messages = vec![
+            Message::system("Be brief".to_string()),
+            Message::user("untar a .tar.zst".to_string()),
+        ];
+        let opts = PromptOpts {
+            models: vec!["a/b".to_string()],
+            oneline: true,
+            ..PromptOpts::default()
+        };
+        let got = build_body(0, &opts, &messages, &[]).unwrap();
+        let expected =
+            r#""messages":[{"role":"system","content":"Be brief"},{"role":"system","content":""#
+                .to_string()
+                + ONELINE_INSTRUCTION
+                + r#""},{"role":"user","content":"untar a .tar.zst"}]"#;
+        assert!(got.contains(&expected), "{got}");
+    }
+
+    #[test]
+    fn test_build_body_developer_role() {
+        let messages = vec![
+            Message::system("Be brief".to_string()),
+            Message::new(Role::Developer, Some("No emoji".to_string()), None),
+            Message::user("Hi".to_string()),
+        ];
+        let body = |model: &str| {
+            let opts = PromptOpts {
+                models: vec![model.to_string()],
+                ..PromptOpts::default()
+            };
+            build_body(0, &opts, &messages, &[]).unwrap()
+        };
+        let got = body("openai/gpt-5-mini");
+        assert!(got.contains(
+            r#"[{"role":"developer","content":"Be brief"},{"role":"developer","content":"No emoji"},{"role":"user""#
+        ));
+        assert!(body("openai/o4-mini").contains(r#"[{"role":"developer","#));
+        for model in ["openai/gpt-4o", "anthropic/claude-sonnet-4"] {
+            let got = body(model);
+            assert!(got.contains(
+                r#"[{"role":"system","content":"Be brief"},{"role":"system","content":"No emoji"},"#
+            ));
+        }
+    }
+
+    #[test]
+    fn test_build_body_reasoning_tokens() {
+        let messages = vec![Message::user("Hi".to_string())];
+        for effort in [None, Some(ReasoningEffort::High)] {
+            let opts = PromptOpts {
+                effort,
+                reasoning_tokens: Some(2000),
+                ..PromptOpts::default()
+            };
+            let got = build_body(0, &opts, &messages, &[]).unwrap();
+            assert!(got.contains(
+                r#""reasoning": {"exclude": false, "enabled": true, "max_tokens": 2000}"#
+            ));
+        }
+    }
+
+    #[test]
+    fn test_build_body_max_cost() {
+        let opts = PromptOpts {
+            models: vec!["openai/gpt-5".to_string()],
+            effort: None,
+            max_cost: Some(0.5),
+            ..PromptOpts::default()
+        };
+        // About 2 tokens
+        let messages = vec![Message::user("Hello world".to_string())];
+        let got = build_body(0, &opts, &messages, &[]).unwrap();
+        assert!(got.contains(
+            r#", "provider": {"max_price": {"prompt": 250000.000000, "request": 0.500000}}, "#
+        ));
+    }
+
+    #[test]
+    fn test_provider_prefs_ignore() {
+        let mut got: Vec<u8> = Vec::new();
+        write_provider_prefs(
+            &mut got,
+            Some(Priority::Price),
+            None,
+            false,
+            &["deepinfra".to_string(), "novita".to_string()],
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            str::from_utf8(&got).unwrap(),
+            r#""provider": {"sort":"price", "ignore": ["deepinfra", "novita"]}"#
+        );
+
+        got.clear();
+        write_provider_prefs(&mut got, None, Some("chutes"), true, &[], None).unwrap();
+        assert_eq!(
+            str::from_utf8(&got).unwrap(),
+            r#""provider": {"order": ["chutes"], "allow_fallbacks": false}"#
+        );
+    }
 }
@@ -0,0 +1,42 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `ort tokens [file|-] [-m model]`: estimated token count, without calling the API.
+
+extern crate alloc;
+use alloc::string::String;
+
+use crate::common::tokens::{self, Family};
+use crate::input::args::TokensOpts;
+use crate::input::stdin;
+use crate::{ErrorKind, OrtResult, Write, ort_error, utils};
+
+pub fn run<W: Write>(opts: TokensOpts, w: &mut W) -> OrtResult<()> {
+    let text = match opts.source.as_deref() {
+        None | Some("-") => stdin::read()?.unwrap_or_default(),
+        Some(filename) => utils::filename_read_to_string(filename)
+            .map_err(|err| ort_error(ErrorKind::FileReadFailed, err))?,
+    };
+
+    let mut out = String::with_capacity(128);
+    match opts.model.as_deref() {
+        Some(model) => {
+            let count = tokens::estimate(&text, Family::from_model(model));
+            out.push_str(&utils::num_to_string(count));
+            out.push('\n');
+        }
+        None => {
+            for family in Family::ALL {
+                out.push_str(family.as_str());
+                out.push('\t');
+                out.push_str(&utils::num_to_string(tokens::estimate(&text, family)));
+                out.push('\n');
+            }
+        }
+    }
+    w.write_str(&out)?;
+    w.flush()
+}
@@ -0,0 +1,184 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Config `update_check: true`: at most once a day, ask the crates.io index
+//! for the newest ort and say so if this one is older. The request carries
+//! nothing about you or this install, not even our version. Any failure is
+//! silent, it never gets in the way of the prompt.
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use core::net::{IpAddr, SocketAddr};
+
+use crate::cli::Env;
+use crate::common::buf_read::OrtBufReader;
+use crate::common::json_parser::{JsonField, autoparser};
+use crate::common::{file, paths, resolver, utils};
+use crate::{ErrorKind, OrtResult, TlsStream, Write, http, ort_error, syscall};
+
+const STATE_FILENAME: &str = "update-check";
+const CHECK_EVERY_SECS: u64 = 24 * 60 * 60;
+const TIMEOUT_MS: u64 = 2000;
+
+const INDEX_HOST: &str = "index.crates.io";
+// The sparse index file for our crate, one JSON line per published version
+const INDEX_REQ: &[u8] = concat!(
+    "GET /or/t-/",
+    env!("CARGO_PKG_NAME"),
+    " HTTP/1.1\r\n",
+    "Host: index.crates.io\r\n",
+    "User-Agent: ort\r\n",
+    "Connection: close\r\n",
+    "\r\n"
+)
+.as_bytes();
+
+/// Print a one line notice on stderr if a newer ort is out. Only goes to the
+/// network if the last check was a day or more ago.
+pub fn check(env: &Env) {
+    let Ok(path) = state_path(env) else {
+        return;
+    };
+    let now = syscall::unix_time();
+    let state = utils::filename_read_to_string(&path).unwrap_or_default();
+    let (last_checked, mut latest) = parse_state(&state);
+    if now.saturating_sub(last_checked) < CHECK_EVERY_SECS {
+        return;
+    }
+    // Offline or blocked, keep what we knew and try again tomorrow
+    if let Ok(Some(version)) = fetch_latest() {
+        latest = version;
+    }
+    let _ = save_state(&path, now, &latest);
+
+    let current = env!("CARGO_PKG_VERSION");
+    if is_newer(&latest, current) {
+        let msg = "ort ".to_string()
+            + &latest
+            + " is available, you have "
+            + current
+            + ". See https://github.com/grahamking/ort#install\n";
+        utils::warn(&msg);
+    }
+}
+
+fn state_path(env: &Env) -> OrtResult<String> {
+    paths::cache_file(env, STATE_FILENAME)
+}
+
+/// "<unix seconds>\t<latest version>"
+fn parse_state(state: &str) -> (u64, String) {
+    let (when, version) = state.trim().split_once('\t').unwrap_or((state.trim(), ""));
+    (when.parse().unwrap_or(0), version.to_string())
+}
+
+fn save_state(path: &str, now: u64, latest: &str) -> OrtResult<()> {
+    let path = CString::new(path)
+        .map_err(|_| ort_error(ErrorKind::Other, "Cache path contains nul byte"))?;
+    let mut f = unsafe { file::File::create(path.as_bytes_with_nul())? };
+    let line = utils::num_to_string(now) + "\t" + latest + "\n";
+    f.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+fn fetch_latest() -> OrtResult<Option<String>> {
+    let ips = unsafe { resolver::resolve(INDEX_HOST)? };
+    let addrs: Vec<SocketAddr> = ips
+        .into_iter()
+        .map(|ip| SocketAddr::new(IpAddr::V4(ip), 443))
+        .collect();
+    let deadline = syscall::monotonic_ms() + TIMEOUT_MS;
+    let tcp = http::connect(addrs, Some(deadline))?;
+    let mut tls = TlsStream::connect(tcp, INDEX_HOST)?;
+    tls.write_all(INDEX_REQ)?;
+    tls.flush()?;
+    let mut reader = OrtBufReader::new(tls);
+    let header = http::read_header(&mut reader)?;
+    let body = http::read_body(reader, header.body())?;
+    Ok(latest_version(&body))
+}
+
+/// The highest version in the index file that isn't yanked or a pre-release
+fn latest_version(index: &str) -> Option<String> {
+    let mut best: Option<([u32; 3], String)> = None;
+    for line in index.lines() {
+        let mut fields = [
+            JsonField::new_simple_string("vers"),
+            JsonField::new_bool("yanked"),
+        ];
+        if autoparser(line, &mut fields).is_err() || fields[1].get_bool() == Some(true) {
+            continue;
+        }
+        let Some(vers) = fields[0].get_string() else {
+            continue;
+        };
+        let Some(parsed) = parse_version(&vers) else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|(b, _)| parsed > *b) {
+            best = Some((parsed, vers));
+        }
+    }
+    best.map(|(_, v)| v)
+}
+
+/// "1.2.3" or "v1.2.3". None for pre-releases like "1.3.0-rc1".
+fn parse_version(v: &str) -> Option<[u32; 3]> {
+    let mut parts = v.strip_prefix('v').unwrap_or(v).split('.');
+    let mut out = [0u32; 3];
+    for slot in out.iter_mut() {
+        *slot = parts.next()?.parse().ok()?;
+    }
+    parts.next().is_none().then_some(out)
+}
+
+fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(l), Some(c)) => l > c,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_from_index() {
+        let index = r#"{"name":"ort-openrouter-cli","vers":"0.4.0","deps":[],"cksum":"a","features":{},"yanked":false}
+{"name":"ort-openrouter-cli","vers":"0.10.1","deps":[{"name":"x","req":"^1"}],"cksum":"b","features":{},"yanked":false}
+{"name":"ort-openrouter-cli","vers":"0.11.0","deps":[],"cksum":"c","features":{},"yanked":true}
+{"name":"ort-openrouter-cli","vers":"0.12.0-rc1","deps":[],"cksum":"d","features":{},"yanked":false}
+{"name":"ort-openrouter-cli","vers":"0.9.0","deps":[],"cksum":"e","features":{},"yanked":false}
+"#;
+        assert_eq!(latest_version(index).as_deref(), Some("0.10.1"));
+        assert_eq!(latest_version(""), None);
+    }
+
+    #[test]
+    fn compares_versions() {
+        assert!(is_newer("0.10.0", "0.5.1"));
+        assert!(is_newer("v1.0.0", "0.5.1"));
+        assert!(!is_newer("0.5.1", "0.5.1"));
+        assert!(!is_newer("0.5.0", "0.5.1"));
+        assert!(!is_newer("", "0.5.1"));
+        assert!(!is_newer("0.6.0-beta", "0.5.1"));
+    }
+
+    #[test]
+    fn state_round_trip() {
+        assert_eq!(
+            parse_state("1760000000\t0.6.0\n"),
+            (1760000000, "0.6.0".to_string())
+        );
+        assert_eq!(parse_state(""), (0, String::new()));
+        // From a check that never got an answer
+        assert_eq!(parse_state("1760000000\t\n"), (1760000000, String::new()));
+    }
+}
@@ -3,31 +3,63 @@
 //!
 //! MIT License
 //! Copyright (c) 2025 Graham King
+//!
+//! The `ort` binary, and a library for embedding it. The public API is `cli`,
+//! the data types at the top level, `io` and `net`, and it follows semver.
+//! Anything `#[doc(hidden)]` is internal and can change in any release.
 
 #![no_std]
 // feature(test) for benchmarking
 #![cfg_attr(test, feature(test))]
 
+// Raw Linux syscalls and x86_64 intrinsics throughout, there is no other port.
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+compile_error!(
+    "ort only builds for x86_64 Linux (target_os = \"linux\", target_arch = \"x86_64\")"
+);
+
 mod common;
 mod input;
-mod net;
 mod output;
+#[doc(hidden)]
 pub mod syscall;
+mod transport;
+
+/// Reads and writes on raw file descriptors, without std
+pub mod io {
+    pub use crate::common::buf_read::OrtBufReader;
+    pub use crate::common::io::{Read, ReadLine, Write};
+}
+
+/// TCP, TLS 1.3 and HTTP/1.1 straight on Linux syscalls
+pub mod net {
+    pub use crate::transport::{AsFd, TcpSocket, TlsStream, chunked, http};
+}
 
 pub use common::alloc::ArenaAlloc;
 pub use common::data::{
     ChatCompletionsResponse, Choice, DEFAULT_MODEL, LastData, Message, Priority, PromptOpts,
-    ReasoningEffort, Response, Role, ThinkEvent, Usage,
+    ReasoningEffort, Response, Role, ThinkEvent, Usage, Verbosity,
 };
 pub use common::error::{Context, ErrorKind, OrtError, OrtResult, ort_error};
+pub use common::term::Term;
+#[doc(hidden)]
 pub use common::utils;
+#[doc(hidden)]
 pub use common::{io::Read, io::Write};
 
 pub use input::cli;
+#[cfg(feature = "ffi")]
+pub use input::ffi;
+#[cfg(feature = "fuzz")]
+pub use input::fuzz;
 pub use input::to_json::build_body;
 
-pub use net::socket::TcpSocket;
-pub use net::tls::TlsStream;
-pub use net::{chunked, http};
+// Moved to `net`, kept here for older callers
+#[cfg(feature = "mock-server")]
+#[doc(hidden)]
+pub use transport::mock_server;
+#[doc(hidden)]
+pub use transport::{TcpSocket, TlsStream, chunked, http};
 
 pub use output::writer::StdoutWriter;
@@ -32,7 +32,7 @@ use core::{
 };
 
 use ort_openrouter_cli::ArenaAlloc;
-use ort_openrouter_cli::{StdoutWriter, cli, syscall};
+use ort_openrouter_cli::{StdoutWriter, Term, cli, syscall};
 
 #[cfg(not(debug_assertions))]
 mod panic_handler;
@@ -63,16 +63,16 @@ pub unsafe extern "C" fn main(
     }
     let env = collect_env(envp);
 
-    // Check stdout for redirection
-    let is_terminal = syscall::isatty(1);
+    // Check stdout for redirection, and what the terminal can do
+    let term = Term::detect(1, env.TERM);
 
-    match cli::main(&args, env, is_terminal, &mut StdoutWriter {}) {
+    match cli::main(&args, env, term, &mut StdoutWriter {}) {
         Ok(exit_code) => exit_code as c_int,
         Err(err) => {
             let err_msg = CString::new(err.as_string()).unwrap();
             let _ = syscall::write(2, c"ERROR: ".as_ptr().cast(), c"ERROR: ".count_bytes());
             let _ = syscall::write(2, err_msg.as_ptr().cast(), err_msg.count_bytes());
-            1
+            err.kind.exit_code()
         }
     }
 }
@@ -104,22 +104,31 @@ fn main() -> std::process::ExitCode {
         HOME: env_str!("HOME"),
         PWD: env_str!("PWD"),
         TMUX_PANE: env_str!("TMUX_PANE"),
+        ORT_SESSION: env_str!("ORT_SESSION"),
+        ORT_PROFILE: env_str!("ORT_PROFILE"),
         XDG_CONFIG_HOME: env_str!("XDG_CONFIG_HOME"),
         XDG_CACHE_HOME: env_str!("XDG_CACHE_HOME"),
+        XDG_STATE_HOME: env_str!("XDG_STATE_HOME"),
+        ORT_CONFIG_DIR: env_str!("ORT_CONFIG_DIR"),
+        ORT_CACHE_DIR: env_str!("ORT_CACHE_DIR"),
+        ORT_STATE_DIR: env_str!("ORT_STATE_DIR"),
+        ORT_HISTORY_FILE: env_str!("ORT_HISTORY_FILE"),
         OPENROUTER_API_KEY: env_str!("OPENROUTER_API_KEY"),
         NVIDIA_API_KEY: env_str!("NVIDIA_API_KEY"),
+        SSLKEYLOGFILE: env_str!("SSLKEYLOGFILE"),
+        TERM: env_str!("TERM"),
     };
 
-    // Check stdout for redirection
-    let is_terminal = syscall::isatty(1);
+    // Check stdout for redirection, and what the terminal can do
+    let term = Term::detect(1, env.TERM);
 
-    match cli::main(&args, env, is_terminal, &mut StdoutWriter {}) {
+    match cli::main(&args, env, term, &mut StdoutWriter {}) {
         Ok(exit_code) => (exit_code as u8).into(),
         Err(err) => {
             let err_msg = CString::new(err.as_string()).unwrap();
             let _ = syscall::write(2, c"ERROR: ".as_ptr().cast(), c"ERROR: ".count_bytes());
             let _ = syscall::write(2, err_msg.as_ptr().cast(), err_msg.count_bytes());
-            1.into()
+            (err.kind.exit_code() as u8).into()
         }
     }
 }
@@ -151,8 +160,9 @@ fn collect_args(argc: core::ffi::c_int, argv: *const *const core::ffi::c_char) -
 
 /// Collect env vars we want from above stack (release mode)
 ///
-/// HOME, TMUX_PANE, XDG_CONFIG_HOME, XDG_CACHE_HOME,
-/// OPENROUTER_API_KEY, NVIDIA_API_KEY
+/// HOME, TMUX_PANE, ORT_SESSION, ORT_PROFILE, XDG_CONFIG_HOME, XDG_CACHE_HOME,
+/// XDG_STATE_HOME, ORT_CONFIG_DIR, ORT_CACHE_DIR, ORT_STATE_DIR, ORT_HISTORY_FILE,
+/// OPENROUTER_API_KEY, NVIDIA_API_KEY, SSLKEYLOGFILE, TERM
 #[allow(unused)]
 fn collect_env(mut envp: *const *const core::ffi::c_char) -> cli::Env {
     use core::ffi::CStr;
@@ -169,10 +179,19 @@ fn collect_env(mut envp: *const *const core::ffi::c_char) -> cli::Env {
                 "HOME" => env.HOME = Some(value),
                 "PWD" => env.PWD = Some(value),
                 "TMUX_PANE" => env.TMUX_PANE = Some(value),
+                "ORT_SESSION" => env.ORT_SESSION = Some(value),
+                "ORT_PROFILE" => env.ORT_PROFILE = Some(value),
                 "XDG_CONFIG_HOME" => env.XDG_CONFIG_HOME = Some(value),
                 "XDG_CACHE_HOME" => env.XDG_CACHE_HOME = Some(value),
+                "XDG_STATE_HOME" => env.XDG_STATE_HOME = Some(value),
+                "ORT_CONFIG_DIR" => env.ORT_CONFIG_DIR = Some(value),
+                "ORT_CACHE_DIR" => env.ORT_CACHE_DIR = Some(value),
+                "ORT_STATE_DIR" => env.ORT_STATE_DIR = Some(value),
+                "ORT_HISTORY_FILE" => env.ORT_HISTORY_FILE = Some(value),
                 "OPENROUTER_API_KEY" => env.OPENROUTER_API_KEY = Some(value),
                 "NVIDIA_API_KEY" => env.NVIDIA_API_KEY = Some(value),
+                "SSLKEYLOGFILE" => env.SSLKEYLOGFILE = Some(value),
+                "TERM" => env.TERM = Some(value),
                 _ => {}
             }
             //let env_val = String::from_utf8_lossy(env_cstr.to_bytes()).into_owned();
@@ -12,8 +12,17 @@ use crate::OrtResult;
 use crate::common::data::Response;
 
 pub mod agent;
+pub mod ansi;
+pub mod answer_writer;
+pub mod extract;
+pub mod from_json;
 pub mod last_writer;
 pub mod logger;
+pub mod metrics;
+pub mod oneline;
+pub mod pager;
+pub mod save_code;
+pub mod tee;
 pub mod writer;
 
 pub const CURSOR_ON: &[u8] = "\x1b[?25h".as_bytes();
@@ -52,6 +61,8 @@ pub const TOOL_CALL_ARGUMENT_START: &[u8] = "\x1b[96m".as_bytes();
 pub const TOOL_CALL_END: &[u8] = "\x1b[0m\n".as_bytes();
 
 pub const AGENT_STATS_START: &[u8] = "\n\x1b[35m".as_bytes();
+pub const AGENT_STEP_START: &[u8] = "\n\x1b[2m── step ".as_bytes();
+pub const AGENT_STEP_END: &[u8] = " ──\x1b[0m\n".as_bytes();
 pub const AGENT_STATS_END: &[u8] = "\x1b[0m\n".as_bytes();
 
 pub const ERR_RATE_LIMITED: &str = "429 Too Many Requests";
@@ -59,4 +70,10 @@ pub const ERR_RATE_LIMITED: &str = "429 Too Many Requests";
 pub trait OutputWriter {
     fn write(&mut self, data: Response) -> OrtResult<()>;
     fn stop(&mut self, include_stats: bool) -> OrtResult<()>;
+
+    /// A new attempt at the answer is starting. Only a writer that can take
+    /// back what it wrote does anything, what's on the console stays.
+    fn restart(&mut self) -> OrtResult<()> {
+        Ok(())
+    }
 }
@@ -13,22 +13,47 @@ use crate::OrtResult;
 use crate::ThinkEvent;
 use crate::Write;
 use crate::common::data::Response;
+use crate::common::term::Term;
 use crate::ort_error;
+use crate::output::ansi::AnsiFilter;
 use crate::syscall;
-use crate::utils::zclean;
+use crate::utils::{self, zclean};
 
 pub struct AgentWriter<'a, W: Write + Send> {
     pub writer: &'a mut W,
     pub show_reasoning: bool,
+    pub term: Term,
 }
 
 impl<'a, W: Write + Send> AgentWriter<'a, W> {
-    pub fn new(writer: &'a mut W, show_reasoning: bool) -> AgentWriter<'a, W> {
+    pub fn new(writer: &'a mut W, show_reasoning: bool, term: Term) -> AgentWriter<'a, W> {
         Self {
             writer,
             show_reasoning,
+            term,
         }
     }
+
+    /// One of the styled constants, without its escape codes if the terminal
+    /// can't do them.
+    fn write_styled(&mut self, styled: &[u8]) {
+        if self.term.has_ansi() {
+            let _ = self.writer.write(styled);
+        } else if let Ok(s) = str::from_utf8(styled) {
+            let plain = AnsiFilter::default().filter(s.to_string());
+            let _ = self.writer.write_str(&plain);
+        }
+    }
+
+    /// Header before each model call in the tool loop, e.g. "step 2/50"
+    pub fn step(&mut self, step: u32, max_steps: u32) {
+        self.write_styled(super::AGENT_STEP_START);
+        let _ = self.writer.write_str(&utils::num_to_string(step));
+        let _ = self.writer.write_char('/');
+        let _ = self.writer.write_str(&utils::num_to_string(max_steps));
+        self.write_styled(super::AGENT_STEP_END);
+        let _ = self.writer.flush();
+    }
 }
 
 impl<'a, W: Write + Send> super::OutputWriter for AgentWriter<'a, W> {
@@ -39,7 +64,7 @@ impl<'a, W: Write + Send> super::OutputWriter for AgentWriter<'a, W> {
                 if self.show_reasoning {
                     match think {
                         ThinkEvent::Start => {
-                            let _ = self.writer.write(super::MSG_THINK_START);
+                            self.write_styled(super::MSG_THINK_START);
                             let _ = self.writer.flush();
                         }
                         ThinkEvent::Content(s) => {
@@ -47,7 +72,7 @@ impl<'a, W: Write + Send> super::OutputWriter for AgentWriter<'a, W> {
                             let _ = self.writer.flush();
                         }
                         ThinkEvent::Stop => {
-                            let _ = self.writer.write(super::MSG_THINK_END);
+                            self.write_styled(super::MSG_THINK_END);
                             let _ = self.writer.write_char('\n');
                         }
                     }
@@ -60,11 +85,11 @@ impl<'a, W: Write + Send> super::OutputWriter for AgentWriter<'a, W> {
                 // We use ToolDisplay instead
             }
             Response::ToolDisplay(tool) => {
-                let _ = self.writer.write(super::TOOL_CALL_START);
+                self.write_styled(super::TOOL_CALL_START);
                 let _ = self.writer.write(tool.name.as_bytes());
-                let _ = self.writer.write(super::TOOL_CALL_ARGUMENT_START);
+                self.write_styled(super::TOOL_CALL_ARGUMENT_START);
                 let _ = self.writer.write(tool.arguments.trim().as_bytes());
-                let _ = self.writer.write(super::TOOL_CALL_END);
+                self.write_styled(super::TOOL_CALL_END);
                 let _ = self.writer.flush();
             }
             Response::Stats(mut stats) => {
@@ -72,15 +97,15 @@ impl<'a, W: Write + Send> super::OutputWriter for AgentWriter<'a, W> {
                 stats.time_to_first_token = None;
 
                 // TODO: Align flush right
-                let _ = self.writer.write(super::AGENT_STATS_START);
+                self.write_styled(super::AGENT_STATS_START);
                 let _ = self.writer.write(stats.as_string().as_bytes());
-                let _ = self.writer.write(super::AGENT_STATS_END);
+                self.write_styled(super::AGENT_STATS_END);
                 let _ = self.writer.flush();
             }
             Response::Prompt(prompt) => {
-                let _ = self.writer.write(super::PROMPT_START);
+                self.write_styled(super::PROMPT_START);
                 let _ = self.writer.write(prompt.as_bytes());
-                let _ = self.writer.write(super::RESET);
+                self.write_styled(super::RESET);
                 let _ = self.writer.write(b"\n");
                 let _ = self.writer.flush();
             }
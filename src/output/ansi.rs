@@ -0,0 +1,124 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Strip terminal control sequences from model output before it reaches the
+//! user's terminal, so a model can't change the title, move the cursor, etc.
+
+extern crate alloc;
+use alloc::string::String;
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+enum State {
+    #[default]
+    Text,
+    // After ESC
+    Esc,
+    // Control Sequence: ESC [ ... final byte
+    Csi,
+    // Operating System Command and friends: ESC ] ... BEL or ESC \
+    Osc,
+    // ESC inside an OSC, probably the start of the ESC \ terminator
+    OscEsc,
+}
+
+/// Keeps state between calls because a sequence can be split over two chunks.
+#[derive(Default)]
+pub struct AnsiFilter {
+    state: State,
+}
+
+impl AnsiFilter {
+    /// Remove escape sequences and control characters, except newline and tab.
+    pub fn filter(&mut self, s: String) -> String {
+        if self.state == State::Text && !s.chars().any(is_unsafe) {
+            return s;
+        }
+        let mut out = String::with_capacity(s.len());
+        for c in s.chars() {
+            self.state = match self.state {
+                State::Text => match c {
+                    '\x1b' => State::Esc,
+                    '\u{9b}' => State::Csi,
+                    '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => State::Osc,
+                    c if is_unsafe(c) => State::Text,
+                    c => {
+                        out.push(c);
+                        State::Text
+                    }
+                },
+                State::Esc => match c {
+                    '[' => State::Csi,
+                    // OSC, DCS, SOS, PM, APC are all terminated the same way
+                    ']' | 'P' | 'X' | '^' | '_' => State::Osc,
+                    // Two character sequence, drop it
+                    _ => State::Text,
+                },
+                State::Csi => match c {
+                    '\x40'..='\x7e' => State::Text,
+                    _ => State::Csi,
+                },
+                State::Osc => match c {
+                    '\x07' | '\u{9c}' => State::Text,
+                    '\x1b' => State::OscEsc,
+                    // Unterminated, don't swallow the rest of the answer
+                    '\n' => {
+                        out.push(c);
+                        State::Text
+                    }
+                    _ => State::Osc,
+                },
+                State::OscEsc => match c {
+                    '\\' => State::Text,
+                    _ => State::Osc,
+                },
+            };
+        }
+        out
+    }
+}
+
+fn is_unsafe(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn f(s: &str) -> String {
+        AnsiFilter::default().filter(s.to_string())
+    }
+
+    #[test]
+    fn plain_text_unchanged() {
+        assert_eq!(f("Hello\n\tworld é"), "Hello\n\tworld é");
+    }
+
+    #[test]
+    fn strips_sequences() {
+        assert_eq!(f("\x1b[31mred\x1b[0m"), "red");
+        assert_eq!(f("a\x1b]0;pwned\x07b"), "ab");
+        assert_eq!(f("a\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\"), "alink");
+        assert_eq!(f("a\x1b7b\x1b8"), "ab");
+        assert_eq!(f("over\rwrite\x08"), "overwrite");
+        assert_eq!(f("\u{9b}2Jclear"), "clear");
+    }
+
+    #[test]
+    fn split_across_chunks() {
+        let mut filter = AnsiFilter::default();
+        let mut out = filter.filter("one\x1b[".to_string());
+        out.push_str(&filter.filter("1;31mtwo\x1b]0;ti".to_string()));
+        out.push_str(&filter.filter("tle\x07three".to_string()));
+        assert_eq!(out, "onetwothree");
+    }
+
+    #[test]
+    fn unterminated_osc_stops_at_newline() {
+        assert_eq!(f("a\x1b]0;title\nrest"), "a\nrest");
+    }
+}
@@ -0,0 +1,185 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+
+extern crate alloc;
+
+use alloc::ffi::CString;
+use alloc::string::String;
+
+use crate::cli::Env;
+use crate::common::{file, paths, time, utils};
+use crate::output::OutputWriter;
+use crate::{ErrorKind, OrtResult, Response, Write, ort_error, syscall};
+
+/// Room left in the frontmatter for the cost, filled in when we have it
+const COST_WIDTH: usize = 16;
+
+/// Answers of the same model in the same second get `-2`, `-3`.. up to this
+const MAX_SAME_NAME: usize = 100;
+
+/// AnswerWriter streams the model's answer to a markdown file, with the
+/// model, date and cost in YAML frontmatter.
+pub struct AnswerWriter {
+    w: file::File,
+    path: String,
+    // Where in the file the cost value goes
+    cost_offset: usize,
+    cost_in_cents: Option<f64>,
+}
+
+impl AnswerWriter {
+    pub fn new(env: &Env, save_dir: Option<&str>, model: &str) -> OrtResult<Self> {
+        let mut path = match save_dir {
+            Some(dir) => {
+                utils::ensure_dir_exists(dir);
+                String::from(dir)
+            }
+            None => paths::state_dir(env)?,
+        };
+        let date = time::utc_timestamp(syscall::unix_time());
+        path.push('/');
+        path.push_str(&utils::slug(model));
+        path.push('-');
+        path.extend(date.chars().filter(|c| *c != '-' && *c != ':'));
+        let (path, mut w) = create_unique(path)?;
+
+        let (header, cost_offset) = frontmatter(model, &date);
+        w.write_all(header.as_bytes())?;
+
+        Ok(AnswerWriter {
+            w,
+            path,
+            cost_offset,
+            cost_in_cents: None,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// A new `<stem>.md`, or `<stem>-2.md` and so on if that is taken, never
+/// replacing an earlier answer
+fn create_unique(stem: String) -> OrtResult<(String, file::File)> {
+    for n in 1..=MAX_SAME_NAME {
+        let mut path = stem.clone();
+        if n > 1 {
+            path.push('-');
+            path.push_str(&utils::num_to_string(n));
+        }
+        path.push_str(".md");
+        let c_path = CString::new(path.as_str())
+            .map_err(|_| ort_error(ErrorKind::Other, "Answer path contains nul byte"))?;
+        if let Some(w) = unsafe { file::File::create_new(c_path.as_bytes_with_nul())? } {
+            return Ok((path, w));
+        }
+    }
+    Err(ort_error(
+        ErrorKind::FileCreateFailed,
+        "Too many answers with the same name",
+    ))
+}
+
+/// The YAML header, and the offset of the blank cost value within it
+fn frontmatter(model: &str, date: &str) -> (String, usize) {
+    let mut s = String::with_capacity(64 + model.len() + COST_WIDTH);
+    s.push_str("---\nmodel: ");
+    s.push_str(model);
+    s.push_str("\ndate: ");
+    s.push_str(date);
+    s.push_str("\ncost_cents: ");
+    let cost_offset = s.len();
+    for _ in 0..COST_WIDTH {
+        s.push(' ');
+    }
+    s.push_str("\n---\n\n");
+    (s, cost_offset)
+}
+
+impl OutputWriter for AnswerWriter {
+    fn write(&mut self, data: Response) -> OrtResult<()> {
+        match data {
+            Response::Content(content) => {
+                self.w.write_all(content.as_bytes())?;
+            }
+            Response::Stats(stats) => {
+                self.cost_in_cents = stats.cost_in_cents;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self, _include_stats: bool) -> OrtResult<()> {
+        self.w.write_char('\n')?;
+        if let Some(cost) = self.cost_in_cents {
+            let cost = utils::float_to_string(cost, 4);
+            let len = cost.len().min(COST_WIDTH);
+            self.w.write_at(&cost.as_bytes()[..len], self.cost_offset)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::common::stats::Stats;
+
+    #[test]
+    fn test_frontmatter_cost() {
+        const TEST_PATH_C: &[u8] = b"/tmp/ort-answer-writer-test.md\0";
+        const TEST_PATH: &str = "/tmp/ort-answer-writer-test.md";
+
+        let mut w = unsafe { file::File::create(TEST_PATH_C) }.unwrap();
+        let (header, cost_offset) = frontmatter("openai/gpt-5", "2026-10-17T16:03:41Z");
+        w.write_all(header.as_bytes()).unwrap();
+        let mut writer = AnswerWriter {
+            w,
+            path: TEST_PATH.to_string(),
+            cost_offset,
+            cost_in_cents: None,
+        };
+        for event in [
+            Response::Start,
+            Response::Content("Hello ".to_string()),
+            Response::Content("world".to_string()),
+            Response::Stats(Box::new(Stats {
+                cost_in_cents: Some(0.125),
+                ..Default::default()
+            })),
+        ] {
+            writer.write(event).unwrap();
+        }
+        writer.stop(true).unwrap();
+
+        let md = utils::filename_read_to_string(TEST_PATH).unwrap();
+        let mut lines = md.lines();
+        assert_eq!(lines.next(), Some("---"));
+        assert_eq!(lines.next(), Some("model: openai/gpt-5"));
+        assert_eq!(lines.next(), Some("date: 2026-10-17T16:03:41Z"));
+        assert_eq!(lines.next().map(str::trim_end), Some("cost_cents: 0.1250"));
+        assert_eq!(lines.next(), Some("---"));
+        assert!(md.ends_with("\n\nHello world\n"));
+    }
+
+    #[test]
+    fn test_create_unique() {
+        let stem = "/tmp/ort-answer-unique-".to_string() + &utils::num_to_string(syscall::getpid());
+        let (first, _) = create_unique(stem.clone()).unwrap();
+        let (second, _) = create_unique(stem.clone()).unwrap();
+        assert_eq!(first, stem.clone() + ".md");
+        assert_eq!(second, stem + "-2.md");
+        for path in [first, second] {
+            let c_path = CString::new(path).unwrap();
+            syscall::unlink(c_path.as_ptr());
+        }
+    }
+}
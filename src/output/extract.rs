@@ -0,0 +1,182 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--extract code`: keep only the code of the answer, so it can be piped
+//! straight into a file or a shell. Wraps the real output writer, collects the
+//! content, and passes the code on at the end.
+
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::common::data::Extract;
+use crate::{OrtResult, Response};
+
+const FENCE: &str = "```";
+
+// Lines like "Sure! Here's the code:" before unfenced code
+const PREAMBLE_STARTS: &[&str] = &[
+    "Sure",
+    "Certainly",
+    "Of course",
+    "Absolutely",
+    "Okay",
+    "OK,",
+    "Here",
+    "Below",
+];
+
+// Paragraphs like "This script ..." after unfenced code
+const EXPLANATION_STARTS: &[&str] = &[
+    "This ",
+    "The above",
+    "The code",
+    "Note",
+    "Explanation",
+    "How it works",
+    "In this",
+    "Let me know",
+    "Feel free",
+    "I hope",
+    "Hope this",
+];
+
+pub struct ExtractWriter<'a> {
+    inner: Box<dyn super::OutputWriter + 'a>,
+    mode: Extract,
+    content: String,
+}
+
+impl<'a> ExtractWriter<'a> {
+    pub fn new(inner: Box<dyn super::OutputWriter + 'a>, mode: Extract) -> Self {
+        ExtractWriter {
+            inner,
+            mode,
+            content: String::new(),
+        }
+    }
+}
+
+impl<'a> super::OutputWriter for ExtractWriter<'a> {
+    fn write(&mut self, data: Response) -> OrtResult<()> {
+        match data {
+            Response::Content(content) => {
+                self.content.push_str(&content);
+                Ok(())
+            }
+            other => self.inner.write(other),
+        }
+    }
+
+    fn stop(&mut self, include_stats: bool) -> OrtResult<()> {
+        let code = extract_code(&self.content, self.mode == Extract::AllCode);
+        if !code.is_empty() {
+            self.inner.write(Response::Content(code))?;
+        }
+        self.inner.stop(include_stats)
+    }
+}
+
+/// The fenced code blocks of `text`, the first one or all of them.
+/// With no fences, `text` without any chatter before or after the code.
+pub fn extract_code(text: &str, is_all: bool) -> String {
+    let blocks = fenced_blocks(text);
+    if blocks.is_empty() {
+        return strip_chatter(text);
+    }
+    let take = if is_all { blocks.len() } else { 1 };
+    blocks[..take].join("\n")
+}
+
+// An unclosed block runs to the end, the answer may have been cut off
+fn fenced_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<String> = None;
+    for line in text.lines() {
+        let is_fence = line.trim_start().starts_with(FENCE);
+        match current.as_mut() {
+            None if is_fence => current = Some(String::new()),
+            None => {}
+            Some(_) if is_fence && line.trim() == FENCE => {
+                blocks.extend(current.take());
+            }
+            Some(block) => {
+                block.push_str(line);
+                block.push('\n');
+            }
+        }
+    }
+    blocks.extend(current);
+    blocks
+}
+
+fn strip_chatter(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut start = 0;
+    while start < lines.len() && (lines[start].trim().is_empty() || is_preamble(lines[start])) {
+        start += 1;
+    }
+
+    // Drop whole trailing paragraphs that read like an explanation
+    let mut end = lines.len();
+    loop {
+        while end > start && lines[end - 1].trim().is_empty() {
+            end -= 1;
+        }
+        let mut para_start = end;
+        while para_start > start && !lines[para_start - 1].trim().is_empty() {
+            para_start -= 1;
+        }
+        if para_start == start || !is_explanation(lines[para_start]) {
+            break;
+        }
+        end = para_start;
+    }
+
+    let mut out = lines[start..end].join("\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+fn is_preamble(line: &str) -> bool {
+    let line = line.trim();
+    PREAMBLE_STARTS.iter().any(|p| line.starts_with(p))
+        || (line.ends_with(':') && line.contains(' ') && line.starts_with(char::is_uppercase))
+}
+
+fn is_explanation(line: &str) -> bool {
+    let line = line.trim_start();
+    EXPLANATION_STARTS.iter().any(|p| line.starts_with(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_code;
+
+    #[test]
+    fn first_block_or_all() {
+        let answer = "Sure! Here's the code:\n\n```python\nprint(1)\n```\n\nAnd a test:\n```\nassert True\n```\nThis prints 1.\n";
+        assert_eq!(extract_code(answer, false), "print(1)\n");
+        assert_eq!(extract_code(answer, true), "print(1)\n\nassert True\n");
+    }
+
+    #[test]
+    fn unclosed_block() {
+        assert_eq!(extract_code("```sh\nls -l\n", false), "ls -l\n");
+    }
+
+    #[test]
+    fn no_fences_strips_chatter() {
+        let answer = "Certainly! Here is the script:\n\n#!/bin/sh\necho hi\n\nThis script prints hi.\nRun it with sh.\n";
+        assert_eq!(extract_code(answer, false), "#!/bin/sh\necho hi\n");
+        // Nothing to strip
+        assert_eq!(extract_code("ls -l\n", false), "ls -l\n");
+    }
+}
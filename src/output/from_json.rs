@@ -0,0 +1,396 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Conversations started somewhere else, so `ort -c` can carry them on.
+//! Reads the OpenAI messages format and the ChatGPT data export, and writes
+//! the messages format back out.
+
+extern crate alloc;
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::common::json_parser::{self, JsonField, Parser, autoparser};
+use crate::{LastData, Message, OrtResult, PromptOpts, Role, Write};
+
+#[derive(Debug, PartialEq)]
+pub enum ChatFormat {
+    /// Our own last-<pane>.json
+    Ort,
+    /// `[{"role": "user", "content": "..."}, ...]`
+    OpenAiMessages,
+    /// A chat completions request body, `{"model": "..", "messages": [..]}`
+    OpenAiRequest,
+    /// ChatGPT's conversations.json, one conversation or an array of them
+    ChatGpt,
+}
+
+pub fn detect(json: &str) -> Option<ChatFormat> {
+    let json = json.trim_start();
+    let obj = match json.as_bytes().first()? {
+        b'{' => json,
+        b'[' => {
+            let first = array_items(json).ok()?.into_iter().next()?;
+            if !first.starts_with('{') {
+                return None;
+            }
+            if has_key(first, "mapping") {
+                return Some(ChatFormat::ChatGpt);
+            }
+            return has_key(first, "role").then_some(ChatFormat::OpenAiMessages);
+        }
+        _ => return None,
+    };
+    if has_key(obj, "mapping") {
+        Some(ChatFormat::ChatGpt)
+    } else if has_key(obj, "opts") {
+        Some(ChatFormat::Ort)
+    } else if has_key(obj, "messages") {
+        Some(ChatFormat::OpenAiRequest)
+    } else {
+        None
+    }
+}
+
+/// Convert any of the [`ChatFormat`]s to a conversation we can continue.
+/// The opts only carry a model if the file named an OpenRouter one.
+pub fn to_last_data(json: &str) -> Result<LastData, Cow<'static, str>> {
+    // These come from other tools or hand edits, and the parser is lenient,
+    // so check strictly first and say where it's broken.
+    json_parser::check(json).map_err(|err| err.as_string())?;
+    convert(json)
+}
+
+fn convert(json: &str) -> Result<LastData, Cow<'static, str>> {
+    let messages = match detect(json) {
+        Some(ChatFormat::Ort) => return LastData::from_json(json),
+        Some(ChatFormat::OpenAiMessages) => {
+            return Ok(last_data(openai_messages(&array_items(json)?)?, None));
+        }
+        Some(ChatFormat::OpenAiRequest) => {
+            let mut fields = [
+                JsonField::new_vec_raw("messages"),
+                JsonField::new_string("model"),
+            ];
+            autoparser(json, &mut fields)?;
+            let msgs = fields[0].get_vec_raw().unwrap_or_default();
+            let msgs: Vec<&str> = msgs.iter().map(String::as_str).collect();
+            return Ok(last_data(openai_messages(&msgs)?, fields[1].get_string()));
+        }
+        Some(ChatFormat::ChatGpt) => chatgpt(json)?,
+        None => return Err("Unrecognized conversation format".into()),
+    };
+    Ok(last_data(messages, None))
+}
+
+/// The conversation as an OpenAI messages array. Reasoning is dropped,
+/// that format has nowhere to put it.
+pub fn write_openai_messages<W: Write>(messages: &[Message], w: &mut W) -> OrtResult<()> {
+    w.write_char('[')?;
+    let mut is_first = true;
+    for m in messages {
+        if m.content.is_empty() && m.tool_calls.is_empty() {
+            continue;
+        }
+        if !is_first {
+            w.write_char(',')?;
+        }
+        is_first = false;
+        let mut m = m.clone();
+        m.reasoning = None;
+        crate::input::to_json::write_json_message(&m, w)?;
+    }
+    w.write_char(']')?;
+    Ok(())
+}
+
+fn last_data(messages: Vec<Message>, model: Option<String>) -> LastData {
+    // "gpt-4o" is an OpenAI name, only "openai/gpt-4o" means anything to us.
+    // Without one the config default is merged in later.
+    let models = model.filter(|m| m.contains('/')).into_iter().collect();
+    LastData {
+        opts: PromptOpts {
+            models,
+            ..Default::default()
+        },
+        messages,
+        tools: vec![],
+    }
+}
+
+fn openai_messages(items: &[&str]) -> Result<Vec<Message>, Cow<'static, str>> {
+    let mut messages = Vec::with_capacity(items.len());
+    for m in items {
+        let m = Message::from_json(m)?;
+        if !m.content.is_empty() || !m.tool_calls.is_empty() {
+            messages.push(m);
+        }
+    }
+    Ok(messages)
+}
+
+/// ChatGPT stores a tree of messages, because of edits and regenerations.
+/// We take the branch that ends at `current_node`, the one the user last saw.
+fn chatgpt(json: &str) -> Result<Vec<Message>, Cow<'static, str>> {
+    let conversation = if json.trim_start().starts_with('[') {
+        // The whole export, take the most recently updated conversation
+        let mut latest = None;
+        let mut latest_time = 0;
+        for c in array_items(json)? {
+            let mut fields = [JsonField::new_raw("update_time")];
+            autoparser(c, &mut fields)?;
+            let t = fields[0]
+                .get_raw()
+                .and_then(|t| crate::utils::parse_u32(t.as_bytes()).ok())
+                .unwrap_or(0);
+            if latest.is_none() || t > latest_time {
+                (latest, latest_time) = (Some(c), t);
+            }
+        }
+        latest.ok_or("No conversations in ChatGPT export")?
+    } else {
+        json
+    };
+
+    let mut fields = [
+        JsonField::new_raw("mapping"),
+        JsonField::new_string("current_node"),
+    ];
+    autoparser(conversation, &mut fields)?;
+    let mapping = fields[0]
+        .get_raw()
+        .ok_or("ChatGPT export missing mapping")?;
+    let current = fields[1]
+        .get_string()
+        .ok_or("ChatGPT export missing current_node")?;
+
+    // (id, parent, node)
+    let mut nodes: Vec<(String, Option<String>, String)> = vec![];
+    let mut p = Parser::new(&mapping);
+    p.expect(b'{')?;
+    loop {
+        p.skip_ws();
+        if p.try_consume(b'}') {
+            break;
+        }
+        let id = p.parse_string()?;
+        p.skip_ws();
+        p.expect(b':')?;
+        let node = p.value_slice()?;
+        let mut fields = [JsonField::new_string("parent")];
+        autoparser(node, &mut fields)?;
+        nodes.push((id, fields[0].get_string(), node.to_string()));
+        p.skip_ws();
+        p.try_consume(b',');
+    }
+
+    let mut messages = vec![];
+    let mut next = Some(current);
+    while let Some(id) = next {
+        let Some(pos) = nodes.iter().position(|n| n.0 == id) else {
+            return Err(("ChatGPT export missing node: ".to_string() + &id).into());
+        };
+        // Removing it means a loop in the parents ends instead of spinning
+        let (_, parent, node) = nodes.swap_remove(pos);
+        if let Some(m) = chatgpt_message(&node)? {
+            messages.push(m);
+        }
+        next = parent;
+    }
+    messages.reverse();
+    Ok(messages)
+}
+
+/// The text of one mapping node. None for the hidden and tool ones, which
+/// don't fit a plain conversation.
+fn chatgpt_message(node: &str) -> Result<Option<Message>, Cow<'static, str>> {
+    let mut fields = [JsonField::new_raw("message")];
+    autoparser(node, &mut fields)?;
+    let Some(message) = fields[0].get_raw() else {
+        return Ok(None);
+    };
+
+    let mut fields = [
+        JsonField::new_raw("author"),
+        JsonField::new_raw("content"),
+        JsonField::new_string("recipient"),
+    ];
+    autoparser(&message, &mut fields)?;
+    let (Some(author), Some(content)) = (fields[0].get_raw(), fields[1].get_raw()) else {
+        return Ok(None);
+    };
+    // Assistant messages to anyone else are tool calls, e.g. to the browser
+    if fields[2].get_string().is_some_and(|r| r != "all") {
+        return Ok(None);
+    }
+
+    let mut fields = [JsonField::new_string("role")];
+    autoparser(&author, &mut fields)?;
+    let role = match fields[0].get_string().as_deref() {
+        Some("user") => Role::User,
+        Some("assistant") => Role::Assistant,
+        Some("system") => Role::System,
+        Some("developer") => Role::Developer,
+        _ => return Ok(None),
+    };
+
+    let mut fields = [
+        JsonField::new_string("content_type"),
+        JsonField::new_vec_raw("parts"),
+    ];
+    autoparser(&content, &mut fields)?;
+    if !matches!(
+        fields[0].get_string().as_deref(),
+        Some("text" | "multimodal_text")
+    ) {
+        return Ok(None);
+    }
+    // Images in the parts are pointers into the export's files, skip those
+    let mut text = String::new();
+    for part in fields[1].get_vec_raw().unwrap_or_default() {
+        if part.starts_with('"') {
+            text.push_str(&Parser::new(&part).parse_string()?);
+        }
+    }
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Message::new(role, Some(text), None)))
+}
+
+fn array_items(json: &str) -> Result<Vec<&str>, Cow<'static, str>> {
+    let mut items = vec![];
+    let mut p = Parser::new(json);
+    p.skip_ws();
+    p.expect(b'[')?;
+    loop {
+        p.skip_ws();
+        if p.try_consume(b']') {
+            break;
+        }
+        items.push(p.value_slice()?);
+        p.skip_ws();
+        if !p.try_consume(b',') {
+            p.skip_ws();
+            p.expect(b']')?;
+            break;
+        }
+    }
+    Ok(items)
+}
+
+fn has_key(obj: &str, key: &'static str) -> bool {
+    let mut fields = [JsonField::new_raw(key)];
+    autoparser(obj, &mut fields).is_ok() && !fields[0].is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHATGPT: &str = r#"{
+        "title": "Rust",
+        "update_time": 1700000100.5,
+        "current_node": "c",
+        "mapping": {
+            "root": {"id": "root", "message": null, "parent": null, "children": ["a"]},
+            "a": {"id": "a", "parent": "root", "children": ["b", "b2"], "message": {
+                "author": {"role": "user"}, "recipient": "all",
+                "content": {"content_type": "text", "parts": ["Why \"Rust\"?"]}}},
+            "b2": {"id": "b2", "parent": "a", "children": [], "message": {
+                "author": {"role": "assistant"}, "recipient": "all",
+                "content": {"content_type": "text", "parts": ["Regenerated away"]}}},
+            "b": {"id": "b", "parent": "a", "children": ["c"], "message": {
+                "author": {"role": "assistant"}, "recipient": "browser",
+                "content": {"content_type": "code", "text": "search(\"rust\")"}}},
+            "c": {"id": "c", "parent": "b", "children": [], "message": {
+                "author": {"role": "assistant"}, "recipient": "all",
+                "content": {"content_type": "multimodal_text", "parts": [{"asset_pointer": "x"}, "Safety."]}}}
+        }
+    }"#;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            detect(r#" [{"role": "user", "content": "hi"}]"#),
+            Some(ChatFormat::OpenAiMessages)
+        );
+        assert_eq!(
+            detect(r#"{"model": "x", "messages": []}"#),
+            Some(ChatFormat::OpenAiRequest)
+        );
+        assert_eq!(
+            detect(r#"{"tools": [], "messages": [], "opts": {}}"#),
+            Some(ChatFormat::Ort)
+        );
+        assert_eq!(detect(CHATGPT), Some(ChatFormat::ChatGpt));
+        assert_eq!(
+            detect(&("[".to_string() + CHATGPT + "]")),
+            Some(ChatFormat::ChatGpt)
+        );
+        assert_eq!(detect(r#"{"hello": 1}"#), None);
+        assert_eq!(detect("[1, 2]"), None);
+        assert_eq!(detect("nope"), None);
+    }
+
+    #[test]
+    fn test_openai_messages() {
+        let json = r#"[
+            {"role": "developer", "content": "Be brief"},
+            {"role": "user", "content": [{"type": "text", "text": "Hi"}]},
+            {"role": "assistant", "content": "Hello"}
+        ]"#;
+        let last = to_last_data(json).unwrap();
+        assert_eq!(last.messages.len(), 3);
+        assert!(matches!(last.messages[0].role, Role::Developer));
+        assert_eq!(last.messages[1].text(), Some("Hi"));
+        assert_eq!(last.messages[2].text(), Some("Hello"));
+        assert!(last.opts.models.is_empty());
+
+        let mut out = String::new();
+        write_openai_messages(&last.messages, &mut out).unwrap();
+        assert_eq!(
+            out,
+            r#"[{"role":"developer","content":"Be brief"},{"role":"user","content":"Hi"},{"role":"assistant","content":"Hello"}]"#
+        );
+        // And back again
+        assert_eq!(to_last_data(&out).unwrap().messages.len(), 3);
+    }
+
+    #[test]
+    fn test_openai_request_model() {
+        let json = r#"{"model": "openai/gpt-5", "messages": [{"role": "user", "content": "Hi"}]}"#;
+        assert_eq!(to_last_data(json).unwrap().opts.models, ["openai/gpt-5"]);
+        let json = r#"{"model": "gpt-5", "messages": [{"role": "user", "content": "Hi"}]}"#;
+        assert!(to_last_data(json).unwrap().opts.models.is_empty());
+    }
+
+    #[test]
+    fn test_chatgpt() {
+        let last = to_last_data(CHATGPT).unwrap();
+        let texts: Vec<_> = last.messages.iter().map(|m| m.text().unwrap()).collect();
+        assert_eq!(texts, ["Why \"Rust\"?", "Safety."]);
+        assert!(matches!(last.messages[1].role, Role::Assistant));
+
+        // The export is an array, newest conversation wins
+        let old = CHATGPT
+            .replace("1700000100.5", "1600000000")
+            .replace("Safety.", "Old");
+        let export = "[".to_string() + CHATGPT + "," + &old + "]";
+        let last = to_last_data(&export).unwrap();
+        assert_eq!(last.messages[1].text(), Some("Safety."));
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(to_last_data(r#"{"hello": 1}"#).is_err());
+        let Err(err) = to_last_data("[\n  {\"role\": \"user\" \"content\": \"hi\"}\n]") else {
+            panic!("expected error");
+        };
+        assert_eq!(err, "expected ',' or '}' at line 2 col 19");
+    }
+}
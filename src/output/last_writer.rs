@@ -6,16 +6,19 @@
 
 extern crate alloc;
 
+use alloc::ffi::CString;
+use alloc::string::String;
 use alloc::vec::Vec;
+use core::ffi::c_int;
 
 use crate::cli::Env;
 use crate::common::data::Tool;
 use crate::output::OutputWriter;
 use crate::{
-    Context, ErrorKind, LastData, Message, OrtResult, PromptOpts, Response, Write, common::config,
-    common::file, common::utils,
+    Context, ErrorKind, LastData, Message, OrtResult, PromptOpts, Response, Write, common::file,
+    common::paths, common::utils,
 };
-use crate::{Role, ort_error};
+use crate::{Role, ThinkEvent, ort_error, syscall};
 
 /// How many bytes of content tokens to buffer before streaming to disk.
 /// This limits max memory, but also the biggest message we can handle.
@@ -24,10 +27,12 @@ const TOKEN_MEM_BUFFER: usize = 4096;
 /// LastWriter saves to disk the model response and enough information so that we can
 /// continue the conversation with `ort -c "next prompt"` later.
 pub struct LastWriter {
-    w: file::File,
+    w: LastFile,
     data: LastData,
     buffer: [u8; TOKEN_MEM_BUFFER],
     buf_idx: usize,
+    // Only collected if config save_reasoning is on
+    reasoning: Option<String>,
 }
 
 impl LastWriter {
@@ -36,29 +41,151 @@ impl LastWriter {
         messages: Vec<Message>,
         tools: Vec<&'static Tool>,
         env: &Env,
+        save_reasoning: bool,
     ) -> OrtResult<Self> {
-        let mut last_path = [0u8; 128];
-        let idx = config::cache_dir(env, &mut last_path)?;
-        last_path[idx] = b'/';
-        let last_filename = utils::last_filename(env);
-        let start = idx + 1;
-        let end = start + last_filename.len();
-        last_path[start..end].copy_from_slice(last_filename.as_bytes());
-        // end + 1 to add a null byte on the end
-        let last_file =
-            unsafe { file::File::create(&last_path[..end + 1]).context("create last file")? };
+        Ok(Self::with_file(
+            create_last_file(env)?,
+            opts,
+            messages,
+            tools,
+            save_reasoning,
+        ))
+    }
+
+    /// Save to `last_file` instead of this session's, e.g. `ort flush-queue`
+    /// saving to the session that queued the prompt
+    pub(crate) fn with_file(
+        last_file: LastFile,
+        opts: PromptOpts,
+        messages: Vec<Message>,
+        tools: Vec<&'static Tool>,
+        save_reasoning: bool,
+    ) -> Self {
         let data = LastData {
             opts,
             messages,
             tools,
         };
-        Ok(LastWriter {
+        LastWriter {
             data,
             w: last_file,
             buffer: [0u8; TOKEN_MEM_BUFFER],
             buf_idx: 0,
+            reasoning: save_reasoning.then(String::new),
+        }
+    }
+}
+
+/// Start a new last-<session>.json. It replaces the old one on `persist`.
+pub(crate) fn create_last_file(env: &Env) -> OrtResult<LastFile> {
+    LastFile::create(state_path(env, &utils::last_filename(env))?)
+}
+
+pub(crate) fn state_path(env: &Env, filename: &str) -> OrtResult<String> {
+    paths::state_file(env, filename)
+}
+
+/// A last file being written. It goes to a temporary file next to it, which
+/// `persist` renames into place, so concurrent runs in one session don't
+/// interleave and `-c` never reads half a file. The last to finish wins.
+pub(crate) struct LastFile {
+    f: file::File,
+    tmp: CString,
+    path: CString,
+    is_persisted: bool,
+}
+
+impl LastFile {
+    pub(crate) fn create(path: String) -> OrtResult<Self> {
+        let mut tmp = path.clone();
+        tmp.push('.');
+        tmp.push_str(&utils::num_to_string(syscall::getpid()));
+        tmp.push_str(".tmp");
+        let (Ok(tmp), Ok(path)) = (CString::new(tmp), CString::new(path)) else {
+            return Err(ort_error(
+                ErrorKind::FileCreateFailed,
+                "Null byte in last file path",
+            ));
+        };
+        let f =
+            unsafe { file::File::create(tmp.as_bytes_with_nul()) }.context("create last file")?;
+        Ok(LastFile {
+            f,
+            tmp,
+            path,
+            is_persisted: false,
         })
     }
+
+    /// Replace the session's last file with what we wrote
+    pub(crate) fn persist(&mut self) -> OrtResult<()> {
+        self.f.flush()?;
+        if syscall::rename(self.tmp.as_ptr(), self.path.as_ptr()) < 0 {
+            return Err(ort_error(
+                ErrorKind::FileWriteFailed,
+                "rename last file into place",
+            ));
+        }
+        self.is_persisted = true;
+        Ok(())
+    }
+}
+
+impl Write for LastFile {
+    fn write(&mut self, buf: &[u8]) -> OrtResult<usize> {
+        self.f.write(buf)
+    }
+
+    fn flush(&mut self) -> OrtResult<()> {
+        self.f.flush()
+    }
+}
+
+impl Drop for LastFile {
+    fn drop(&mut self) {
+        if !self.is_persisted {
+            syscall::unlink(self.tmp.as_ptr());
+        }
+    }
+}
+
+/// Held by `-c` from reading the session's last conversation until the
+/// next one is saved, so two continuations in one session take turns
+/// instead of both answering the same turn. Released on drop.
+pub(crate) struct SessionLock {
+    fd: c_int,
+}
+
+impl SessionLock {
+    pub(crate) fn acquire(env: &Env) -> OrtResult<Self> {
+        Self::acquire_for(env, &utils::session_key(env))
+    }
+
+    /// The lock of another `session`, e.g. the one a queued prompt came from
+    pub(crate) fn acquire_for(env: &Env, session: &str) -> OrtResult<Self> {
+        let mut name = String::from("last-");
+        name.push_str(session);
+        name.push_str(".lock");
+        let path = CString::new(state_path(env, &name)?)
+            .map_err(|_| ort_error(ErrorKind::FileCreateFailed, "Null byte in lock path"))?;
+        let flags = syscall::O_CLOEXEC | syscall::O_RDWR | syscall::O_CREAT;
+        let fd = syscall::open(path.as_ptr(), flags, 0o660)
+            .map_err(|e| ort_error(ErrorKind::FileCreateFailed, e))?;
+        let lock = SessionLock { fd };
+        if syscall::flock(fd, syscall::LOCK_EX | syscall::LOCK_NB) < 0 {
+            crate::utils::warn("Waiting for the other ort -c in this session to finish\n");
+            if syscall::flock(fd, syscall::LOCK_EX) < 0 {
+                return Err(ort_error(ErrorKind::FileCreateFailed, "flock last file"));
+            }
+        }
+        Ok(lock)
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        syscall::close(self.fd);
+    }
 }
 
 impl OutputWriter for LastWriter {
@@ -90,6 +217,11 @@ impl OutputWriter for LastWriter {
                 )?;
                 self.w.write_str(",\"content\":\"")?;
             }
+            Response::Think(ThinkEvent::Content(thought)) => {
+                if let Some(reasoning) = self.reasoning.as_mut() {
+                    reasoning.push_str(&thought);
+                }
+            }
             Response::Think(_) => {}
             Response::Content(content) => {
                 let b = content.as_bytes();
@@ -145,14 +277,24 @@ impl OutputWriter for LastWriter {
         // Write final contents
         crate::input::to_json::write_encoded_bytes(&mut self.w, &self.buffer[..self.buf_idx])?;
 
-        // close the contents message and messages array
-        self.w.write_str("\"}]")?;
+        // close the contents
+        self.w.write_char('"')?;
+
+        if let Some(reasoning) = self.reasoning.as_deref()
+            && !reasoning.is_empty()
+        {
+            self.w.write_str(",\"reasoning\":")?;
+            crate::input::to_json::write_json_str(&mut self.w, reasoning)?;
+        }
+
+        // close the message and messages array
+        self.w.write_str("}]")?;
 
         self.w.write_str(",\"opts\":")?;
         self.data.opts.to_json_writer(&mut self.w)?;
 
         self.w.write_char('}')?; // End of whole object
-        let _ = self.w.flush();
+        self.w.persist()?;
 
         Ok(())
     }
@@ -162,19 +304,19 @@ impl OutputWriter for LastWriter {
 mod tests {
     extern crate alloc;
 
+    use alloc::boxed::Box;
     use alloc::string::ToString;
     use alloc::vec;
 
     use super::*;
     use crate::{
-        LastData, ThinkEvent,
+        LastData,
         common::{stats, tools::ALL_TOOLS},
         utils::num_to_string,
     };
 
     #[test]
     fn test_run_success() {
-        const TEST_PATH_C: &[u8] = b"/tmp/ort-last-writer-test.json\0";
         const TEST_PATH: &str = "/tmp/ort-last-writer-test.json";
 
         let opts = PromptOpts::default();
@@ -182,7 +324,7 @@ mod tests {
             Message::system("system prompt".to_string()),
             Message::user("user prompt".to_string()),
         ];
-        let file = match unsafe { file::File::create(TEST_PATH_C) } {
+        let file = match LastFile::create(TEST_PATH.to_string()) {
             Ok(file) => file,
             Err(err) => panic!("{}", err.as_string()),
         };
@@ -196,6 +338,7 @@ mod tests {
             data,
             buffer: [0u8; TOKEN_MEM_BUFFER],
             buf_idx: 0,
+            reasoning: None,
         };
 
         let mut q = vec![
@@ -210,10 +353,10 @@ mod tests {
             q.push(Response::Content(num_to_string(i)));
             q.push(Response::Content(". ".to_string()));
         }
-        q.push(Response::Stats(stats::Stats {
+        q.push(Response::Stats(Box::new(stats::Stats {
             provider: "OpenRouter AI".to_string(),
             ..Default::default()
-        }));
+        })));
 
         for event in q {
             writer
@@ -240,4 +383,41 @@ mod tests {
         assert!(content.ends_with("Hello world 99. "));
         assert_eq!(data.tools.len(), 1);
     }
+
+    #[test]
+    fn test_save_reasoning() {
+        const TEST_PATH: &str = "/tmp/ort-last-writer-reasoning-test.json";
+
+        let file = LastFile::create(TEST_PATH.to_string()).unwrap();
+        let mut writer = LastWriter {
+            w: file,
+            data: LastData {
+                opts: PromptOpts::default(),
+                messages: vec![Message::user("user prompt".to_string())],
+                tools: vec![],
+            },
+            buffer: [0u8; TOKEN_MEM_BUFFER],
+            buf_idx: 0,
+            reasoning: Some(String::new()),
+        };
+        for event in [
+            Response::Start,
+            Response::Think(ThinkEvent::Start),
+            Response::Think(ThinkEvent::Content("Hmm, ".to_string())),
+            Response::Think(ThinkEvent::Content("\"hello\"".to_string())),
+            Response::Think(ThinkEvent::Stop),
+            Response::Content("Hello".to_string()),
+        ] {
+            writer.write(event).unwrap();
+        }
+        writer.stop(true).unwrap();
+
+        let json = utils::filename_read_to_string(TEST_PATH).unwrap();
+        let data = LastData::from_json(&json).unwrap();
+        assert_eq!(data.messages[1].text(), Some("Hello"));
+        assert_eq!(
+            data.messages[1].reasoning.as_deref(),
+            Some("Hmm, \"hello\"")
+        );
+    }
 }
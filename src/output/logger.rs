@@ -4,14 +4,22 @@
 //! MIT License
 //! Copyright (c) 2026 Graham King
 
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
 use crate::{
-    Context as _, OrtResult, Write as _,
+    Context as _, ErrorKind, OrtResult, Write as _,
     cli::Env,
-    common::{config, file},
+    common::{file, paths, utils},
+    ort_error,
 };
 
+/// All debug output goes through here, so it is the one place that masks secrets.
 pub struct Logger {
     w: file::File,
+    secrets: Vec<String>,
 }
 
 const LOG_FILENAME: &str = "log.jsonl";
@@ -20,18 +28,25 @@ impl Logger {
     /// Only make one!
     /// TODO: Probably make it a singleton
     pub fn new(env: &Env) -> OrtResult<Self> {
-        let mut log_path = [0u8; 128];
-        let idx = config::cache_dir(env, &mut log_path)?;
-        log_path[idx] = b'/';
-        let start = idx + 1;
-        let end = start + LOG_FILENAME.len();
-        log_path[start..end].copy_from_slice(LOG_FILENAME.as_bytes());
-        // end + 1 to add a null byte on the end
-        let log = unsafe { file::File::create(&log_path[..end + 1]).context("create log file")? };
-        Ok(Logger { w: log })
+        let log_path = CString::new(paths::state_file(env, LOG_FILENAME)?)
+            .map_err(|_| ort_error(ErrorKind::Other, "Null byte in log path"))?;
+        let log =
+            unsafe { file::File::create(log_path.as_bytes_with_nul()).context("create log file")? };
+        Ok(Logger {
+            w: log,
+            secrets: Vec::new(),
+        })
+    }
+
+    /// Never write this value, log a masked version instead.
+    pub fn add_secret(&mut self, secret: &str) {
+        if !secret.is_empty() && !self.secrets.iter().any(|s| s == secret) {
+            self.secrets.push(secret.to_string());
+        }
     }
 
     pub fn log(&mut self, msg: &str) {
+        let msg = utils::redact(msg, &self.secrets);
         let _ = self.w.write(msg.as_bytes());
         let _ = self.w.write_char('\n');
     }
@@ -0,0 +1,387 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Config `metrics`: after each request send its timings, tokens and cost to
+//! a statsd server over UDP, or to an OpenTelemetry collector as OTLP/HTTP
+//! JSON. A metrics failure is reported on stderr, it never fails the prompt.
+
+extern crate alloc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use crate::common::config::Cfg;
+use crate::common::data::strip_model_suffix;
+use crate::common::resolver;
+use crate::common::stats::Stats;
+use crate::input::to_json::write_json_str;
+use crate::transport::AsFd as _;
+use crate::transport::socket::{self, TcpSocket, UdpSocket};
+use crate::{ErrorKind, OrtError, OrtResult, Read as _, Write as _, ort_error, syscall, utils};
+
+const STATSD_PORT: u16 = 8125;
+const OTLP_PORT: u16 = 4318;
+const OTLP_PATH: &str = "/v1/metrics";
+const CONNECT_TIMEOUT_MS: i32 = 1000;
+const READ_TIMEOUT_MS: u64 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// Adds up across requests, a statsd counter
+    Sum,
+    /// One reading, a statsd timer for "ms" or gauge otherwise
+    Gauge,
+}
+
+struct Metric {
+    name: &'static str,
+    unit: &'static str,
+    value: f64,
+    kind: Kind,
+}
+
+/// Where config `metrics` points
+#[derive(Debug, PartialEq)]
+enum Sink<'a> {
+    Statsd {
+        host: &'a str,
+        port: u16,
+    },
+    Otlp {
+        host: &'a str,
+        port: u16,
+        path: &'a str,
+    },
+}
+
+impl<'a> Sink<'a> {
+    fn parse(url: &'a str) -> Option<Self> {
+        let (is_statsd, rest) = match url.strip_prefix("statsd://") {
+            Some(rest) => (true, rest),
+            None => (false, url.strip_prefix("http://")?),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None if is_statsd => (authority, STATSD_PORT),
+            None => (authority, OTLP_PORT),
+        };
+        if host.is_empty() {
+            return None;
+        }
+        Some(if is_statsd {
+            Sink::Statsd { host, port }
+        } else {
+            let path = if path.is_empty() || path == "/" {
+                OTLP_PATH
+            } else {
+                path
+            };
+            Sink::Otlp { host, port, path }
+        })
+    }
+}
+
+/// A request finished. `x:nitro` and `x` are the same model here.
+pub fn record(cfg: &Cfg, stats: &Stats) {
+    let Some(url) = cfg.metrics.as_deref() else {
+        return;
+    };
+    let attrs = [
+        ("model", strip_model_suffix(&stats.used_model)),
+        ("provider", stats.provider.as_str()),
+    ];
+    send(url, &from_stats(stats), &attrs);
+}
+
+/// A request failed
+pub fn record_error(cfg: &Cfg, model: &str, err: &OrtError) {
+    let Some(url) = cfg.metrics.as_deref() else {
+        return;
+    };
+    let metrics = [Metric {
+        name: "ort.errors",
+        unit: "1",
+        value: 1.0,
+        kind: Kind::Sum,
+    }];
+    send(
+        url,
+        &metrics,
+        &[
+            ("model", strip_model_suffix(model)),
+            ("error", err.kind.as_string()),
+        ],
+    );
+}
+
+fn from_stats(stats: &Stats) -> Vec<Metric> {
+    let metric = |name, unit, value, kind| Metric {
+        name,
+        unit,
+        value,
+        kind,
+    };
+    let mut out = Vec::with_capacity(6);
+    out.push(metric("ort.requests", "1", 1.0, Kind::Sum));
+    out.push(metric(
+        "ort.elapsed_ms",
+        "ms",
+        stats.elapsed_time.as_millis() as f64,
+        Kind::Gauge,
+    ));
+    if let Some(ttft) = stats.time_to_first_token {
+        out.push(metric(
+            "ort.ttft_ms",
+            "ms",
+            ttft.as_millis() as f64,
+            Kind::Gauge,
+        ));
+    }
+    if let Some(tps) = stats.tokens_per_sec() {
+        out.push(metric("ort.tokens_per_sec", "{token}/s", tps, Kind::Gauge));
+    }
+    if let Some(tokens) = stats.completion_tokens {
+        out.push(metric(
+            "ort.completion_tokens",
+            "{token}",
+            tokens as f64,
+            Kind::Sum,
+        ));
+    }
+    if let Some(cents) = stats.cost_in_cents {
+        out.push(metric("ort.cost_cents", "{cent}", cents, Kind::Sum));
+    }
+    out
+}
+
+fn send(url: &str, metrics: &[Metric], attrs: &[(&str, &str)]) {
+    let res = match Sink::parse(url) {
+        Some(Sink::Statsd { host, port }) => resolve(host, port)
+            .and_then(|addr| UdpSocket::connect(&addr)?.send(statsd_lines(metrics).as_bytes())),
+        Some(Sink::Otlp { host, port, path }) => resolve(host, port).and_then(|addr| {
+            let body = otlp_json(metrics, attrs, syscall::unix_time());
+            post(&addr, host, path, &body)
+        }),
+        None => Err(ort_error(
+            ErrorKind::InvalidArguments,
+            "config metrics is not a valid URL",
+        )),
+    };
+    if let Err(err) = res {
+        utils::warn(&("metrics: ".to_string() + &err.as_string() + "\n"));
+    }
+}
+
+fn resolve(host: &str, port: u16) -> OrtResult<SocketAddrV4> {
+    let ip = if host == "localhost" {
+        Ipv4Addr::LOCALHOST
+    } else if let Ok(ip) = host.parse() {
+        ip
+    } else {
+        unsafe { resolver::resolve(host) }?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ort_error(ErrorKind::DnsResolveFailed, "no address"))?
+    };
+    Ok(SocketAddrV4::new(ip, port))
+}
+
+/// One datagram, a line per metric: "ort.ttft_ms:312|ms"
+fn statsd_lines(metrics: &[Metric]) -> String {
+    let mut s = String::with_capacity(metrics.len() * 32);
+    for m in metrics {
+        s.push_str(m.name);
+        s.push(':');
+        s.push_str(&number(m.value));
+        s.push_str(match (m.kind, m.unit) {
+            (Kind::Sum, _) => "|c",
+            (Kind::Gauge, "ms") => "|ms",
+            (Kind::Gauge, _) => "|g",
+        });
+        s.push('\n');
+    }
+    s
+}
+
+/// An OTLP ExportMetricsServiceRequest, JSON encoded
+fn otlp_json(metrics: &[Metric], attrs: &[(&str, &str)], unix_secs: u64) -> String {
+    let mut points = String::with_capacity(128);
+    points.push_str("\"timeUnixNano\":\"");
+    points.push_str(&utils::num_to_string(unix_secs));
+    points.push_str("000000000\",\"attributes\":");
+    write_attributes(&mut points, attrs.iter().filter(|(_, v)| !v.is_empty()));
+
+    let mut s = String::with_capacity(1024);
+    s.push_str("{\"resourceMetrics\":[{\"resource\":{\"attributes\":");
+    write_attributes(&mut s, [("service.name", "ort")].iter());
+    s.push_str("},\"scopeMetrics\":[{\"scope\":{\"name\":\"ort\"},\"metrics\":[");
+    for (i, m) in metrics.iter().enumerate() {
+        if i != 0 {
+            s.push(',');
+        }
+        s.push_str("{\"name\":\"");
+        s.push_str(m.name);
+        s.push_str("\",\"unit\":\"");
+        s.push_str(m.unit);
+        s.push_str(match m.kind {
+            // Delta, each request reports only itself
+            Kind::Sum => {
+                "\",\"sum\":{\"aggregationTemporality\":1,\"isMonotonic\":true,\"dataPoints\":[{"
+            }
+            Kind::Gauge => "\",\"gauge\":{\"dataPoints\":[{",
+        });
+        s.push_str("\"asDouble\":");
+        s.push_str(&number(m.value));
+        s.push(',');
+        s.push_str(&points);
+        s.push_str("}]}}");
+    }
+    s.push_str("]}]}]}");
+    s
+}
+
+fn write_attributes<'a, I: Iterator<Item = &'a (&'a str, &'a str)>>(s: &mut String, attrs: I) {
+    s.push('[');
+    for (i, (key, value)) in attrs.enumerate() {
+        if i != 0 {
+            s.push(',');
+        }
+        s.push_str("{\"key\":\"");
+        s.push_str(key);
+        s.push_str("\",\"value\":{\"stringValue\":");
+        let _ = write_json_str(s, value);
+        s.push_str("}}");
+    }
+    s.push(']');
+}
+
+/// Whole numbers without a point, others to 4 places
+fn number(v: f64) -> String {
+    if v == (v as u64) as f64 {
+        utils::num_to_string(v as u64)
+    } else {
+        utils::float_to_string(v, 4)
+    }
+}
+
+/// Plain HTTP, collectors are usually on localhost or a sidecar
+fn post(addr: &SocketAddrV4, host: &str, path: &str, body: &str) -> OrtResult<()> {
+    let mut sock = TcpSocket::new()?;
+    sock.connect(addr, CONNECT_TIMEOUT_MS)?;
+    let mut req = String::with_capacity(body.len() + 160);
+    req.push_str("POST ");
+    req.push_str(path);
+    req.push_str(" HTTP/1.1\r\nHost: ");
+    req.push_str(host);
+    req.push_str("\r\nContent-Type: application/json\r\nContent-Length: ");
+    req.push_str(&utils::num_to_string(body.len()));
+    req.push_str("\r\nConnection: close\r\n\r\n");
+    req.push_str(body);
+    sock.write_all(req.as_bytes())?;
+
+    socket::set_read_timeout(sock.as_fd(), READ_TIMEOUT_MS);
+    // "HTTP/1.1 200"
+    let mut status = [0u8; 12];
+    let mut got = 0;
+    while got < status.len() {
+        match sock.read(&mut status[got..])? {
+            0 => break,
+            n => got += n,
+        }
+    }
+    if got < status.len() || status[9] != b'2' {
+        let line = String::from_utf8_lossy(&status[..got]);
+        let msg = "collector answered ".to_string() + line.trim();
+        return Err(ort_error(ErrorKind::HttpStatusError, msg.leak()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+
+    #[test]
+    fn parse_sink() {
+        assert_eq!(
+            Sink::parse("statsd://10.0.0.5"),
+            Some(Sink::Statsd {
+                host: "10.0.0.5",
+                port: 8125
+            })
+        );
+        assert_eq!(
+            Sink::parse("http://localhost:4318/"),
+            Some(Sink::Otlp {
+                host: "localhost",
+                port: 4318,
+                path: "/v1/metrics"
+            })
+        );
+        assert_eq!(
+            Sink::parse("http://otel:9000/otlp/v1/metrics"),
+            Some(Sink::Otlp {
+                host: "otel",
+                port: 9000,
+                path: "/otlp/v1/metrics"
+            })
+        );
+        assert_eq!(Sink::parse("statsd://:8125"), None);
+        assert_eq!(Sink::parse("statsd://host:port"), None);
+    }
+
+    #[test]
+    fn statsd_from_stats() {
+        let stats = Stats {
+            elapsed_time: Duration::from_millis(2500),
+            time_to_first_token: Some(Duration::from_millis(312)),
+            completion_tokens: Some(200),
+            content_time: Some(Duration::from_secs(2)),
+            cost_in_cents: Some(0.06),
+            ..Default::default()
+        };
+        assert_eq!(
+            statsd_lines(&from_stats(&stats)),
+            "ort.requests:1|c\nort.elapsed_ms:2500|ms\nort.ttft_ms:312|ms\n\
+             ort.tokens_per_sec:100|g\nort.completion_tokens:200|c\nort.cost_cents:0.0600|c\n"
+        );
+    }
+
+    #[test]
+    fn otlp_body() {
+        let metrics = [
+            Metric {
+                name: "ort.requests",
+                unit: "1",
+                value: 1.0,
+                kind: Kind::Sum,
+            },
+            Metric {
+                name: "ort.ttft_ms",
+                unit: "ms",
+                value: 312.0,
+                kind: Kind::Gauge,
+            },
+        ];
+        let attrs = [("model", "openai/gpt-5"), ("provider", "")];
+        assert_eq!(
+            otlp_json(&metrics, &attrs, 1_700_000_000),
+            concat!(
+                r#"{"resourceMetrics":[{"resource":{"attributes":[{"key":"service.name","value":{"stringValue":"ort"}}]},"#,
+                r#""scopeMetrics":[{"scope":{"name":"ort"},"metrics":["#,
+                r#"{"name":"ort.requests","unit":"1","sum":{"aggregationTemporality":1,"isMonotonic":true,"dataPoints":[{"asDouble":1,"timeUnixNano":"1700000000000000000","attributes":[{"key":"model","value":{"stringValue":"openai/gpt-5"}}]}]}},"#,
+                r#"{"name":"ort.ttft_ms","unit":"ms","gauge":{"dataPoints":[{"asDouble":312,"timeUnixNano":"1700000000000000000","attributes":[{"key":"model","value":{"stringValue":"openai/gpt-5"}}]}]}}"#,
+                r#"]}]}]}"#
+            )
+        );
+    }
+}
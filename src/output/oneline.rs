@@ -0,0 +1,92 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--oneline`: the answer as a single line, for `$(ort --oneline ...)` in
+//! shell functions. Wraps the real output writer, collects the content, and
+//! passes it on at the end with the whitespace collapsed.
+
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::string::String;
+
+use super::extract::extract_code;
+use crate::{OrtResult, Response};
+
+pub struct OneLineWriter<'a> {
+    inner: Box<dyn super::OutputWriter + 'a>,
+    content: String,
+}
+
+impl<'a> OneLineWriter<'a> {
+    pub fn new(inner: Box<dyn super::OutputWriter + 'a>) -> Self {
+        OneLineWriter {
+            inner,
+            content: String::new(),
+        }
+    }
+}
+
+impl<'a> super::OutputWriter for OneLineWriter<'a> {
+    fn write(&mut self, data: Response) -> OrtResult<()> {
+        match data {
+            Response::Content(content) => {
+                self.content.push_str(&content);
+                Ok(())
+            }
+            other => self.inner.write(other),
+        }
+    }
+
+    fn stop(&mut self, include_stats: bool) -> OrtResult<()> {
+        let line = one_line(&self.content);
+        if !line.is_empty() {
+            self.inner.write(Response::Content(line))?;
+        }
+        self.inner.stop(include_stats)
+    }
+}
+
+/// `text` on one line, without code fences, chatter or inline backticks
+/// around the whole thing. The writer adds the newline.
+pub fn one_line(text: &str) -> String {
+    let code = extract_code(text, false);
+    let src = if code.trim().is_empty() { text } else { &code };
+    let mut out = String::with_capacity(src.len());
+    for word in src.split_whitespace() {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    let is_quoted = out.len() >= 2 && out.starts_with('`') && out.ends_with('`');
+    if is_quoted && !out[1..out.len() - 1].contains('`') {
+        out = out[1..out.len() - 1].trim().into();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::one_line;
+
+    #[test]
+    fn collapses_whitespace() {
+        assert_eq!(
+            one_line("\n  tar --zstd\n\t-xf  archive.tar.zst \n"),
+            "tar --zstd -xf archive.tar.zst"
+        );
+        assert_eq!(one_line(""), "");
+    }
+
+    #[test]
+    fn unwraps_code() {
+        let answer = "Sure! Here's the command:\n\n```bash\ntar --zstd -xf a.tar.zst\n```\nThis extracts it.\n";
+        assert_eq!(one_line(answer), "tar --zstd -xf a.tar.zst");
+        assert_eq!(one_line("`ls -la`\n"), "ls -la");
+        // Backticks inside the answer stay
+        assert_eq!(one_line("`a` or `b`"), "`a` or `b`");
+    }
+}
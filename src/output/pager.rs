@@ -0,0 +1,178 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! With `use_pager`, an answer longer than the terminal goes to `$PAGER`.
+//! It prints as usual until it fills the screen, then the pager starts with
+//! everything so far and gets the rest as it streams.
+
+extern crate alloc;
+use alloc::string::String;
+
+use crate::{Write, syscall};
+
+/// bash expands this in the child, so it sees our environment
+const PAGER_CMD: &str = "exec ${PAGER:-less -R}";
+
+enum State {
+    // Writing to the terminal, keeping a copy for the pager
+    Counting(String),
+    Paging(syscall::Child),
+    // The user quit the pager, the rest of the answer isn't shown
+    Quit,
+    // The pager failed to start
+    Off,
+}
+
+pub struct Pager {
+    rows: usize,
+    cols: usize,
+    line: usize,
+    col: usize,
+    state: State,
+}
+
+impl Pager {
+    /// None if stdout is not a terminal
+    pub fn new() -> Option<Pager> {
+        let (rows, cols) = syscall::terminal_size(1)?;
+        Some(Pager::with_size(rows as usize, cols as usize))
+    }
+
+    fn with_size(rows: usize, cols: usize) -> Pager {
+        Pager {
+            rows,
+            cols,
+            line: 0,
+            col: 0,
+            state: State::Counting(String::new()),
+        }
+    }
+
+    pub fn write<W: Write>(&mut self, terminal: &mut W, s: &str) {
+        if let State::Counting(seen) = &mut self.state {
+            seen.push_str(s);
+            if self.fills_screen(s) {
+                self.start(terminal, s);
+                return;
+            }
+        }
+        match self.state {
+            State::Paging(_) => self.send(s.as_bytes()),
+            State::Quit => {}
+            State::Counting(_) | State::Off => {
+                let _ = terminal.write_all(s.as_bytes());
+                let _ = terminal.flush();
+            }
+        }
+    }
+
+    // Start the pager and give it everything so far. The screen already has
+    // all but the last chunk, the pager redraws it.
+    fn start<W: Write>(&mut self, terminal: &mut W, s: &str) {
+        let State::Counting(seen) = core::mem::replace(&mut self.state, State::Off) else {
+            return;
+        };
+        match syscall::spawn_with_stdin(PAGER_CMD) {
+            Ok(child) => {
+                self.state = State::Paging(child);
+                self.send(seen.as_bytes());
+            }
+            Err(_) => {
+                // No pager, carry on without one
+                let _ = terminal.write_all(s.as_bytes());
+                let _ = terminal.flush();
+            }
+        }
+    }
+
+    /// Wait for the user to quit the pager. Returns true if it was used.
+    pub fn finish(&mut self) -> bool {
+        match core::mem::replace(&mut self.state, State::Quit) {
+            State::Paging(child) => {
+                let _ = child.wait();
+                true
+            }
+            State::Quit => true,
+            other => {
+                self.state = other;
+                false
+            }
+        }
+    }
+
+    fn send(&mut self, mut buf: &[u8]) {
+        let State::Paging(child) = &self.state else {
+            return;
+        };
+        while !buf.is_empty() {
+            let n = syscall::write(child.stdin, buf.as_ptr().cast(), buf.len());
+            if n <= 0 {
+                // The pager exited. Keep streaming, the answer is still
+                // saved, but don't print the rest under the shell prompt.
+                self.finish();
+                return;
+            }
+            buf = &buf[n as usize..];
+        }
+    }
+
+    /// Track the cursor through `s`, wrapping at the terminal width.
+    /// True once the output needs more than a screen, keeping a line for the prompt.
+    fn fills_screen(&mut self, s: &str) -> bool {
+        for c in s.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.col = 0;
+            } else {
+                self.col += 1;
+                if self.col == self.cols {
+                    self.line += 1;
+                    self.col = 0;
+                }
+            }
+        }
+        self.line + 1 >= self.rows
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    use alloc::string::String;
+
+    use super::{Pager, State};
+
+    #[test]
+    fn fills_screen_counts_wrapped_lines() {
+        let mut p = Pager::with_size(5, 10);
+        assert!(!p.fills_screen("one\ntwo\n"));
+        // A full width line wraps
+        assert!(!p.fills_screen("abcdefghij"));
+        assert!(p.fills_screen("abcdefghijklmno\n"));
+    }
+
+    #[test]
+    fn nothing_after_quit() {
+        let mut p = Pager::with_size(5, 10);
+        p.state = State::Quit;
+        let mut terminal = String::new();
+        p.write(&mut terminal, "rest of the answer\n");
+        assert!(terminal.is_empty());
+        assert!(p.finish());
+
+        let mut p = Pager::with_size(5, 10);
+        p.state = State::Off;
+        p.write(&mut terminal, "no pager\n");
+        assert_eq!(terminal, "no pager\n");
+        assert!(!p.finish());
+    }
+}
@@ -0,0 +1,261 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--save-code dir`: the answer prints as usual, and each fenced code block
+//! in it is written to `dir/block-N.<ext>` as soon as its closing fence
+//! arrives. The saved filenames go to stderr at the end.
+
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::common::{file, utils};
+use crate::{ErrorKind, OrtResult, Response, Write, ort_error};
+
+const FENCE: char = '`';
+
+/// Fewer backticks than this is inline code, not a fence
+const MIN_FENCE: usize = 3;
+
+/// Info strings that aren't their own file extension
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("bash", "sh"),
+    ("c++", "cpp"),
+    ("csharp", "cs"),
+    ("c#", "cs"),
+    ("golang", "go"),
+    ("javascript", "js"),
+    ("kotlin", "kt"),
+    ("markdown", "md"),
+    ("python", "py"),
+    ("python3", "py"),
+    ("ruby", "rb"),
+    ("rust", "rs"),
+    ("shell", "sh"),
+    ("text", "txt"),
+    ("typescript", "ts"),
+    ("yaml", "yml"),
+    ("zsh", "sh"),
+];
+
+/// Longer than this and the info string isn't a language
+const MAX_EXT_LEN: usize = 10;
+
+pub struct SaveCodeWriter<'a> {
+    inner: Box<dyn super::OutputWriter + 'a>,
+    dir: String,
+    fences: FenceParser,
+    saved: Vec<String>,
+}
+
+impl<'a> SaveCodeWriter<'a> {
+    pub fn new(inner: Box<dyn super::OutputWriter + 'a>, dir: &str) -> Self {
+        let dir = dir.trim_end_matches('/');
+        SaveCodeWriter {
+            inner,
+            dir: if dir.is_empty() { "/" } else { dir }.to_string(),
+            fences: FenceParser::default(),
+            saved: Vec::new(),
+        }
+    }
+
+    fn save(&mut self, block: CodeBlock) -> OrtResult<()> {
+        if self.saved.is_empty() {
+            utils::ensure_dir_all_exist(&self.dir);
+        }
+        let path = self.dir.clone()
+            + "/block-"
+            + &utils::num_to_string(self.saved.len() + 1)
+            + "."
+            + extension(&block.lang);
+        let c_path = CString::new(path.as_str())
+            .map_err(|_| ort_error(ErrorKind::Other, "--save-code path contains nul byte"))?;
+        let mut f = unsafe { file::File::create(c_path.as_bytes_with_nul())? };
+        f.write_all(block.code.as_bytes())?;
+        self.saved.push(path);
+        Ok(())
+    }
+}
+
+impl<'a> super::OutputWriter for SaveCodeWriter<'a> {
+    fn write(&mut self, data: Response) -> OrtResult<()> {
+        if let Response::Content(content) = &data {
+            for block in self.fences.push(content) {
+                self.save(block)?;
+            }
+        }
+        self.inner.write(data)
+    }
+
+    fn stop(&mut self, include_stats: bool) -> OrtResult<()> {
+        // An unclosed block runs to the end, the answer may have been cut off
+        let last = self.fences.finish();
+        let saved = match last {
+            Some(block) => self.save(block),
+            None => Ok(()),
+        };
+        self.inner.stop(include_stats)?;
+        for path in &self.saved {
+            utils::warn(&("Saved ".to_string() + path + "\n"));
+        }
+        saved
+    }
+}
+
+/// A fenced code block: the first word of the info string and the lines
+/// between the fences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    pub lang: String,
+    pub code: String,
+}
+
+/// Finds fenced code blocks in text that arrives in pieces, split anywhere,
+/// even in the middle of a fence. A block is ready once its closing fence
+/// line is complete.
+#[derive(Default)]
+pub struct FenceParser {
+    /// The start of a line whose end hasn't arrived yet
+    partial: String,
+    /// The block we're in, and how many backticks close it
+    open: Option<(CodeBlock, usize)>,
+}
+
+impl FenceParser {
+    /// The next piece of the answer. Returns the blocks it closed.
+    pub fn push(&mut self, text: &str) -> Vec<CodeBlock> {
+        let mut done = Vec::new();
+        self.partial.push_str(text);
+        while let Some(pos) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=pos).collect();
+            if let Some(block) = self.line(&line) {
+                done.push(block);
+            }
+        }
+        done
+    }
+
+    /// The end of the answer. Returns the block it was in, if any.
+    pub fn finish(&mut self) -> Option<CodeBlock> {
+        let rest = core::mem::take(&mut self.partial);
+        if !rest.is_empty()
+            && let Some(block) = self.line(&rest)
+        {
+            return Some(block);
+        }
+        self.open.take().map(|(block, _)| block)
+    }
+
+    /// One whole line, with its newline if it had one
+    fn line(&mut self, line: &str) -> Option<CodeBlock> {
+        let trimmed = line.trim();
+        let ticks = trimmed.chars().take_while(|c| *c == FENCE).count();
+        match self.open.as_mut() {
+            None => {
+                if ticks >= MIN_FENCE {
+                    let info = &trimmed[ticks..];
+                    let lang = info
+                        .split(|c: char| c.is_whitespace() || c == '{' || c == ':')
+                        .next()
+                        .unwrap_or_default();
+                    let block = CodeBlock {
+                        lang: lang.to_string(),
+                        code: String::new(),
+                    };
+                    self.open = Some((block, ticks));
+                }
+                None
+            }
+            Some((block, open_ticks)) => {
+                // A closing fence is only backticks, at least as many as opened it
+                if ticks >= *open_ticks && ticks == trimmed.len() {
+                    return self.open.take().map(|(block, _)| block);
+                }
+                block.code.push_str(line);
+                None
+            }
+        }
+    }
+}
+
+/// The file extension for a fence's info string
+fn extension(lang: &str) -> &str {
+    let lang_lower = lang.to_ascii_lowercase();
+    if let Some((_, ext)) = EXTENSIONS.iter().find(|(name, _)| *name == lang_lower) {
+        return ext;
+    }
+    let is_ext = !lang.is_empty()
+        && lang.len() <= MAX_EXT_LEN
+        && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '+');
+    if is_ext { lang } else { "txt" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = "Here are the files.\n\n```python\nprint('hi')\n```\n\nAnd:\n\n````markdown\n# Title\n```sh\nls\n```\n````\n\n```\nplain\n";
+
+    fn blocks(pieces: &[&str]) -> Vec<CodeBlock> {
+        let mut p = FenceParser::default();
+        let mut out = Vec::new();
+        for piece in pieces {
+            out.extend(p.push(piece));
+        }
+        out.extend(p.finish());
+        out
+    }
+
+    #[test]
+    fn whole_answer() {
+        let got = blocks(&[ANSWER]);
+        let want = [
+            ("python", "print('hi')\n"),
+            ("markdown", "# Title\n```sh\nls\n```\n"),
+            ("", "plain\n"),
+        ];
+        assert_eq!(got.len(), want.len());
+        for (block, (lang, code)) in got.iter().zip(want) {
+            assert_eq!(block.lang, lang);
+            assert_eq!(block.code, code);
+        }
+    }
+
+    #[test]
+    fn split_anywhere() {
+        let want = blocks(&[ANSWER]);
+        for size in 1..8 {
+            let pieces: Vec<&str> = ANSWER
+                .as_bytes()
+                .chunks(size)
+                .map(|c| core::str::from_utf8(c).unwrap())
+                .collect();
+            assert_eq!(blocks(&pieces), want, "pieces of {size}");
+        }
+    }
+
+    #[test]
+    fn ready_at_the_closing_fence() {
+        let mut p = FenceParser::default();
+        assert!(p.push("```rust\nfn main() {}\n``").is_empty());
+        let done = p.push("`\nmore");
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].code, "fn main() {}\n");
+        assert!(p.finish().is_none());
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(extension("Python"), "py");
+        assert_eq!(extension("rust"), "rs");
+        assert_eq!(extension("toml"), "toml");
+        assert_eq!(extension(""), "txt");
+        assert_eq!(extension("../etc"), "txt");
+        assert_eq!(extension("averyverylonglanguage"), "txt");
+    }
+}
@@ -0,0 +1,118 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--tee`: the answer goes to the console and, as it arrives, to a file.
+//! Wraps the real output writer and a FileWriter for the copy. Each attempt
+//! at the answer, after a retry or failing over, starts the file again.
+
+extern crate alloc;
+use alloc::boxed::Box;
+
+use super::writer::FileWriter;
+use crate::{OrtResult, Response, Write};
+
+pub struct TeeWriter<'a, W: Write + Send> {
+    inner: Box<dyn super::OutputWriter + 'a>,
+    copy: FileWriter<'a, W>,
+}
+
+impl<'a, W: Write + Send> TeeWriter<'a, W> {
+    pub fn new(inner: Box<dyn super::OutputWriter + 'a>, copy: FileWriter<'a, W>) -> Self {
+        TeeWriter { inner, copy }
+    }
+}
+
+impl<'a, W: Write + Send> super::OutputWriter for TeeWriter<'a, W> {
+    fn write(&mut self, data: Response) -> OrtResult<()> {
+        match data {
+            // The copy decides for itself whether to keep the reasoning
+            Response::Start | Response::Think(_) | Response::Content(_) => {
+                self.copy.write(data.clone())?;
+                self.inner.write(data)
+            }
+            // Errors, stats and tool calls are for the console only
+            other => self.inner.write(other),
+        }
+    }
+
+    fn stop(&mut self, include_stats: bool) -> OrtResult<()> {
+        let copied = self.copy.stop(false);
+        self.inner.stop(include_stats)?;
+        copied
+    }
+
+    /// The file starts again empty, so it only has the answer that worked
+    fn restart(&mut self) -> OrtResult<()> {
+        self.copy.truncate()?;
+        self.inner.restart()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    use alloc::boxed::Box;
+    use alloc::string::{String, ToString};
+
+    use super::TeeWriter;
+    use crate::common::data::{Flush, Verbosity};
+    use crate::output::OutputWriter;
+    use crate::output::writer::FileWriter;
+    use crate::{Response, ThinkEvent};
+
+    fn tee(tee_all: bool) -> (String, String) {
+        let mut console = String::new();
+        let mut file = String::new();
+        {
+            let inner = FileWriter::new(&mut console, true, Verbosity::Quiet, Flush::Chunk);
+            let copy = FileWriter::new(&mut file, tee_all, Verbosity::Quiet, Flush::Chunk);
+            let mut w = TeeWriter::new(Box::new(inner), copy);
+            for event in [
+                Response::Start,
+                Response::Think(ThinkEvent::Start),
+                Response::Think(ThinkEvent::Content("hmm".to_string())),
+                Response::Think(ThinkEvent::Stop),
+                Response::Content("Hello ".to_string()),
+                Response::Content("world".to_string()),
+            ] {
+                w.write(event).unwrap();
+            }
+            w.stop(true).unwrap();
+        }
+        (console, file)
+    }
+
+    #[test]
+    fn copies_the_answer() {
+        let (console, file) = tee(false);
+        assert_eq!(console, "<think>hmm</think>\n\nHello world\n");
+        assert_eq!(file, "Hello world\n");
+    }
+
+    #[test]
+    fn restart_empties_the_copy() {
+        let mut console = String::new();
+        let mut file = String::new();
+        {
+            let inner = FileWriter::new(&mut console, false, Verbosity::Quiet, Flush::Line);
+            let copy = FileWriter::new(&mut file, false, Verbosity::Quiet, Flush::Line);
+            let mut w = TeeWriter::new(Box::new(inner), copy);
+            w.write(Response::Content("First\ntry".to_string()))
+                .unwrap();
+            w.restart().unwrap();
+            w.write(Response::Content("Second".to_string())).unwrap();
+            w.stop(true).unwrap();
+        }
+        assert_eq!(console, "First\ntrySecond\n");
+        assert_eq!(file, "Second\n");
+    }
+
+    #[test]
+    fn copies_the_reasoning_with_tee_all() {
+        let (console, file) = tee(true);
+        assert_eq!(file, console);
+    }
+}
@@ -9,7 +9,11 @@ use core::ffi::c_void;
 
 use alloc::ffi::CString;
 use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
+use crate::common::data::{Flush, Verbosity};
+use crate::output::ansi::AnsiFilter;
+use crate::output::pager::Pager;
 use crate::utils::zclean;
 use crate::{ErrorKind, OrtResult, Response, ThinkEvent, Write, common::stats, common::utils};
 use crate::{ort_error, syscall};
@@ -17,33 +21,60 @@ use crate::{ort_error, syscall};
 pub struct ConsoleWriter<'a, W: Write + Send> {
     pub writer: &'a mut W, // Must handle ANSI control chars
     pub show_reasoning: bool,
-    pub is_quiet: bool,
+    pub verbosity: Verbosity,
     pub is_running: bool,
     pub is_first_content: bool,
     pub spindx: usize,
     pub stats_out: Option<stats::Stats>,
+    // None with --raw
+    pub ansi_filter: Option<AnsiFilter>,
+    // Config use_pager, and stdout is a terminal
+    pub pager: Option<Pager>,
+    // Reasoning and answer text, held back per --flush
+    pub out: FlushBuffer,
 }
 
 impl<'a, W: Write + Send> ConsoleWriter<'a, W> {
-    pub fn new(writer: &'a mut W, show_reasoning: bool, is_quiet: bool) -> ConsoleWriter<'a, W> {
+    pub fn new(
+        writer: &'a mut W,
+        show_reasoning: bool,
+        verbosity: Verbosity,
+        is_raw: bool,
+        flush: Flush,
+    ) -> ConsoleWriter<'a, W> {
         ConsoleWriter {
             writer,
             show_reasoning,
-            is_quiet,
+            verbosity,
             is_running: false,
             is_first_content: true,
             spindx: 0,
             stats_out: None,
+            ansi_filter: (!is_raw).then(AnsiFilter::default),
+            pager: None,
+            out: FlushBuffer::new(flush),
+        }
+    }
+
+    fn sanitize(&mut self, s: String) -> String {
+        match self.ansi_filter.as_mut() {
+            Some(filter) => filter.filter(s),
+            None => s,
         }
     }
 }
 
 impl<'a, W: Write + Send> super::OutputWriter for ConsoleWriter<'a, W> {
     fn stop(&mut self, include_stats: bool) -> OrtResult<()> {
+        self.out.drain(self.writer);
+        // The pager shows the end of the answer, the stats go after it
+        let was_paged = self.pager.as_mut().is_some_and(|p| p.finish());
         let _ = self.writer.write(super::CURSOR_ON);
-        let _ = self.writer.write(b"\n");
+        if !was_paged {
+            let _ = self.writer.write(b"\n");
+        }
         let _ = self.writer.flush();
-        if !include_stats || self.is_quiet {
+        if !include_stats || !self.verbosity.show_stats() {
             return Ok(());
         }
 
@@ -58,16 +89,21 @@ impl<'a, W: Write + Send> super::OutputWriter for ConsoleWriter<'a, W> {
     }
 
     fn write(&mut self, data: Response) -> OrtResult<()> {
+        let show_progress = self.verbosity.show_progress();
         if !self.is_running {
-            let _ = self.writer.write(super::MSG_CONNECTING);
-            let _ = self.writer.flush();
+            if show_progress {
+                let _ = self.writer.write(super::MSG_CONNECTING);
+                let _ = self.writer.flush();
+            }
             self.is_running = true;
         }
 
         match data {
             Response::Start => {
-                let _ = self.writer.write(super::MSG_PROCESSING);
-                let _ = self.writer.flush();
+                if show_progress {
+                    let _ = self.writer.write(super::MSG_PROCESSING);
+                    let _ = self.writer.flush();
+                }
             }
             Response::Think(think) => {
                 if !self.is_first_content {
@@ -81,14 +117,15 @@ impl<'a, W: Write + Send> super::OutputWriter for ConsoleWriter<'a, W> {
                             let _ = self.writer.write(super::MSG_THINK_START);
                         }
                         ThinkEvent::Content(s) => {
-                            let _ = self.writer.write_all(s.as_bytes());
-                            let _ = self.writer.flush();
+                            let s = self.sanitize(s);
+                            self.out.write(self.writer, &s);
                         }
                         ThinkEvent::Stop => {
+                            self.out.drain(self.writer);
                             let _ = self.writer.write(super::MSG_THINK_END);
                         }
                     }
-                } else {
+                } else if show_progress {
                     match think {
                         ThinkEvent::Start => {
                             let _ = self.writer.write(super::MSG_THINKING);
@@ -108,28 +145,38 @@ impl<'a, W: Write + Send> super::OutputWriter for ConsoleWriter<'a, W> {
             Response::Content(content) => {
                 if self.is_first_content {
                     // Erase the Processing or Thinking line
-                    let _ = self.writer.write(super::MSG_CLEAR_LINE);
+                    self.out.drain(self.writer);
+                    if show_progress {
+                        let _ = self.writer.write(super::MSG_CLEAR_LINE);
+                    }
                     self.is_first_content = false;
                 }
-                let _ = self.writer.write_all(content.as_bytes());
-                let _ = self.writer.flush();
+                let content = self.sanitize(content);
+                match self.pager.as_mut() {
+                    Some(pager) => pager.write(self.writer, &content),
+                    None => self.out.write(self.writer, &content),
+                }
             }
             Response::ToolCalls(_) | Response::ToolDisplay(_) => {
                 // No tool calls in chat mode
             }
             Response::Stats(stats) => {
-                self.stats_out = Some(stats);
+                self.stats_out = Some(*stats);
             }
             Response::Prompt(_prompt) => {
                 // Prompt not displayed in chat mode
             }
             Response::Error(err_string) => {
+                self.out.drain(self.writer);
+                if let Some(pager) = self.pager.as_mut() {
+                    pager.finish();
+                }
                 let _ = self.writer.write(super::CURSOR_ON);
                 let _ = self.writer.flush();
                 if err_string.contains(super::ERR_RATE_LIMITED) {
                     return Err(ort_error(ErrorKind::RateLimited, ""));
                 }
-                utils::print_string(c"\nERROR: ", &err_string);
+                utils::print_error(c"\nERROR: ", &err_string);
                 return Err(ort_error(
                     ErrorKind::ResponseStreamError,
                     "Remote returned an error",
@@ -148,19 +195,34 @@ impl<'a, W: Write + Send> super::OutputWriter for ConsoleWriter<'a, W> {
 pub struct FileWriter<'a, W: Write + Send> {
     pub writer: &'a mut W,
     pub show_reasoning: bool,
-    pub is_quiet: bool,
+    pub verbosity: Verbosity,
     pub stats_out: Option<stats::Stats>,
+    // Everything before the stats, held back per --flush
+    pub out: FlushBuffer,
 }
 
 impl<'a, W: Write + Send> FileWriter<'a, W> {
-    pub fn new(writer: &'a mut W, show_reasoning: bool, is_quiet: bool) -> FileWriter<'a, W> {
+    pub fn new(
+        writer: &'a mut W,
+        show_reasoning: bool,
+        verbosity: Verbosity,
+        flush: Flush,
+    ) -> FileWriter<'a, W> {
         FileWriter {
             writer,
             show_reasoning,
-            is_quiet,
+            verbosity,
             stats_out: None,
+            out: FlushBuffer::new(flush),
         }
     }
+
+    /// Throw away everything written, for a file that gets a second go
+    pub fn truncate(&mut self) -> OrtResult<()> {
+        self.out.clear();
+        self.stats_out = None;
+        self.writer.truncate()
+    }
 }
 
 impl<'a, W: Write + Send> super::OutputWriter for FileWriter<'a, W> {
@@ -170,34 +232,27 @@ impl<'a, W: Write + Send> super::OutputWriter for FileWriter<'a, W> {
             Response::Think(think) => {
                 if self.show_reasoning {
                     match think {
-                        ThinkEvent::Start => {
-                            let _ = self.writer.write("<think>".as_bytes());
-                        }
-                        ThinkEvent::Content(s) => {
-                            let _ = self.writer.write_all(s.as_bytes());
-                        }
-                        ThinkEvent::Stop => {
-                            let _ = self.writer.write("</think>\n\n".as_bytes());
-                        }
+                        ThinkEvent::Start => self.out.write(self.writer, "<think>"),
+                        ThinkEvent::Content(s) => self.out.write(self.writer, &s),
+                        ThinkEvent::Stop => self.out.write(self.writer, "</think>\n\n"),
                     }
                 }
             }
-            Response::Content(content) => {
-                let _ = self.writer.write_all(content.as_bytes());
-            }
+            Response::Content(content) => self.out.write(self.writer, &content),
             Response::ToolCalls(_) | Response::ToolDisplay(_) => {
                 // TODO
             }
             Response::Stats(stats) => {
-                self.stats_out = Some(stats);
+                self.stats_out = Some(*stats);
             }
             Response::Prompt(prompt) => {
-                let _ = self.writer.write("> ".as_bytes());
-                let _ = self.writer.write(prompt.as_bytes());
-                let _ = self.writer.write(b"\n");
-                let _ = self.writer.flush();
+                self.out.write(self.writer, "> ");
+                self.out.write(self.writer, &prompt);
+                self.out.write(self.writer, "\n");
+                self.out.drain(self.writer);
             }
             Response::Error(mut err_string) => {
+                self.out.drain(self.writer);
                 if err_string.contains(super::ERR_RATE_LIMITED) {
                     return Err(ort_error(ErrorKind::RateLimited, ""));
                 }
@@ -217,8 +272,9 @@ impl<'a, W: Write + Send> super::OutputWriter for FileWriter<'a, W> {
     }
 
     fn stop(&mut self, include_stats: bool) -> OrtResult<()> {
-        let _ = self.writer.write(b"\n");
-        if !include_stats || self.is_quiet {
+        self.out.write(self.writer, "\n");
+        self.out.drain(self.writer);
+        if !include_stats || !self.verbosity.show_stats() {
             return Ok(());
         }
 
@@ -232,9 +288,61 @@ impl<'a, W: Write + Send> super::OutputWriter for FileWriter<'a, W> {
     }
 }
 
+/// Holds back text until the `Flush` policy says it can go out
+pub struct FlushBuffer {
+    policy: Flush,
+    pending: String,
+}
+
+impl FlushBuffer {
+    pub fn new(policy: Flush) -> Self {
+        FlushBuffer {
+            policy,
+            pending: String::new(),
+        }
+    }
+
+    /// Add `s`, then write and flush as much as the policy allows
+    pub fn write<W: Write + ?Sized>(&mut self, w: &mut W, s: &str) {
+        self.pending.push_str(s);
+        let end = match self.policy {
+            Flush::Chunk => self.pending.len(),
+            // Only `s` can have a newline, the rest was held back for lack of one
+            Flush::Line => s
+                .rfind('\n')
+                .map_or(0, |i| self.pending.len() - s.len() + i + 1),
+            Flush::Bytes(n) if self.pending.len() >= n => self.pending.len(),
+            Flush::Bytes(_) => 0,
+        };
+        if end > 0 {
+            let _ = w.write_all(&self.pending.as_bytes()[..end]);
+            let _ = w.flush();
+            self.pending.drain(..end);
+        }
+    }
+
+    /// Drop whatever is held back
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Write out whatever is held back, before other output or at the end
+    pub fn drain<W: Write + ?Sized>(&mut self, w: &mut W) {
+        if !self.pending.is_empty() {
+            let _ = w.write_all(self.pending.as_bytes());
+            self.pending.clear();
+        }
+        let _ = w.flush();
+    }
+}
+
+#[derive(Clone)]
 pub struct CollectedWriter {
     contents: String,
     got_stats: Option<stats::Stats>,
+    error: Option<String>,
+    /// --samples: the second and later completions, `contents` is the first
+    pub samples: Vec<String>,
     pub output: Option<String>,
 }
 
@@ -243,9 +351,25 @@ impl CollectedWriter {
         Self {
             got_stats: None,
             contents: String::with_capacity(4096),
+            error: None,
+            samples: Vec::new(),
             output: None,
         }
     }
+
+    /// The answer, without the stats header that `output` has
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    pub fn stats(&self) -> Option<&stats::Stats> {
+        self.got_stats.as_ref()
+    }
+
+    /// Why this prompt failed, if it did
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
 }
 
 impl super::OutputWriter for CollectedWriter {
@@ -260,15 +384,12 @@ impl super::OutputWriter for CollectedWriter {
                 // No ToolCalls when using CollectedWriter
             }
             Response::Stats(stats) => {
-                self.got_stats = Some(stats);
+                self.got_stats = Some(*stats);
             }
             Response::Prompt(_) => {}
-            Response::Error(_err) => {
-                // Original message: CollectedWriter + err detail
-                return Err(ort_error(
-                    ErrorKind::ResponseStreamError,
-                    "CollectedWriter response error",
-                ));
+            Response::Error(err) => {
+                // Kept for the caller, the other prompts carry on
+                self.error = Some(err);
             }
             Response::None => {
                 // TODO: Can this still happen?
@@ -279,18 +400,35 @@ impl super::OutputWriter for CollectedWriter {
     }
 
     fn stop(&mut self, _include_stats: bool) -> OrtResult<()> {
-        let stat_string = self.got_stats.take().unwrap().as_string();
+        let stat_string = match (&self.error, &self.got_stats) {
+            (Some(err), _) => "Error: ".to_string() + err,
+            (None, Some(stats)) => stats.as_string(),
+            (None, None) => String::new(),
+        };
         let mut out = String::with_capacity(stat_string.len() + self.contents.len() + 9);
         out.push_str("--- ");
         out.push_str(&stat_string);
         out.push_str(" ---\n");
+        if !self.samples.is_empty() {
+            out.push_str(&sample_label(1));
+        }
         out.push_str(&self.contents);
+        for (i, sample) in self.samples.iter().enumerate() {
+            out.push_str("\n\n");
+            out.push_str(&sample_label(i + 2));
+            out.push_str(sample);
+        }
 
         self.output = Some(out);
         Ok(())
     }
 }
 
+/// The header of a --samples completion, counting from 1
+pub fn sample_label(n: usize) -> String {
+    "[sample ".to_string() + &utils::num_to_string(n) + "]\n"
+}
+
 pub struct StdoutWriter {}
 
 impl Write for StdoutWriter {
@@ -307,3 +445,94 @@ impl Write for StdoutWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::OutputWriter as _;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_flush_line() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut buf = FlushBuffer::new(Flush::Line);
+        buf.write(&mut out, "Hel");
+        assert!(out.is_empty());
+        buf.write(&mut out, "lo\nwor");
+        assert_eq!(out, b"Hello\n");
+        buf.write(&mut out, "ld");
+        assert_eq!(out, b"Hello\n");
+        buf.drain(&mut out);
+        assert_eq!(out, b"Hello\nworld");
+    }
+
+    #[test]
+    fn test_flush_bytes() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut buf = FlushBuffer::new(Flush::Bytes(4));
+        buf.write(&mut out, "ab");
+        assert!(out.is_empty());
+        buf.write(&mut out, "cde");
+        assert_eq!(out, b"abcde");
+
+        let mut buf = FlushBuffer::new(Flush::Chunk);
+        buf.write(&mut out, "f");
+        assert_eq!(out, b"abcdef");
+    }
+
+    #[test]
+    fn test_file_writer_line() {
+        let mut out: Vec<u8> = Vec::new();
+        let mut w = FileWriter::new(&mut out, true, Verbosity::Quiet, Flush::Line);
+        w.write(Response::Think(ThinkEvent::Start)).unwrap();
+        w.write(Response::Think(ThinkEvent::Content("Hmm".into())))
+            .unwrap();
+        w.write(Response::Think(ThinkEvent::Stop)).unwrap();
+        w.write(Response::Content("One\nTw".into())).unwrap();
+        assert_eq!(w.out.pending, "Tw");
+        w.write(Response::Content("o".into())).unwrap();
+        w.stop(false).unwrap();
+        assert_eq!(out, b"<think>Hmm</think>\n\nOne\nTwo\n");
+    }
+
+    #[test]
+    fn test_console_writer_verbosity() {
+        let run = |verbosity| {
+            let mut out: Vec<u8> = Vec::new();
+            let mut w = ConsoleWriter::new(&mut out, false, verbosity, true, Flush::Chunk);
+            w.write(Response::Start).unwrap();
+            w.write(Response::Think(ThinkEvent::Start)).unwrap();
+            w.write(Response::Think(ThinkEvent::Content("Hmm".into())))
+                .unwrap();
+            w.write(Response::Think(ThinkEvent::Stop)).unwrap();
+            w.write(Response::Content("Hi".into())).unwrap();
+            w.write(Response::Stats(Default::default())).unwrap();
+            w.stop(true).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let normal = run(Verbosity::Normal);
+        assert!(normal.contains("Thinking") && normal.contains("\nStats: "));
+
+        let quiet = run(Verbosity::Quiet);
+        assert!(quiet.contains("Thinking") && !quiet.contains("Stats"));
+
+        // The answer and nothing else
+        let silent = run(Verbosity::Silent);
+        assert_eq!(
+            silent.as_bytes(),
+            [b"Hi", super::super::CURSOR_ON, b"\n"].concat()
+        );
+    }
+
+    #[test]
+    fn test_collected_samples() {
+        let mut w = CollectedWriter::new();
+        w.write(Response::Content("One".into())).unwrap();
+        w.samples = Vec::from(["Two".into()]);
+        w.stop(true).unwrap();
+        assert_eq!(
+            w.output.as_deref(),
+            Some("---  ---\n[sample 1]\nOne\n\n[sample 2]\nTwo")
+        );
+    }
+}
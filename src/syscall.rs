@@ -14,6 +14,7 @@ use alloc::{ffi::CString, string::String, vec::Vec};
 use crate::{ErrorKind, OrtResult, ort_error};
 use core::{
     arch::asm,
+    cmp::min,
     ffi::{CStr, c_char, c_int, c_long, c_short, c_uchar, c_ushort, c_void},
     mem::MaybeUninit,
 };
@@ -47,33 +48,58 @@ const SYS_OPEN: u32 = 2;
 const SYS_CLOSE: u32 = 3;
 const SYS_FSTAT: u32 = 5;
 const SYS_POLL: u32 = 7;
+const SYS_LSEEK: u32 = 8;
 const SYS_MMAP: u32 = 9;
 const SYS_MPROTECT: u32 = 10;
 const SYS_IOCTL: u32 = 16;
+const SYS_PWRITE64: u32 = 18;
 const SYS_ACCESS: u32 = 21;
 const SYS_DUP2: i32 = 33;
+const SYS_NANOSLEEP: i32 = 35;
+const SYS_GETPID: i32 = 39;
 const SYS_SOCKET: u32 = 41;
 const SYS_CONNECT: u32 = 42;
+const SYS_SENDTO: u32 = 44;
+const SYS_SHUTDOWN: i32 = 48;
+const SYS_ACCEPT4: i32 = 288;
+const SYS_BIND: i32 = 49;
+const SYS_LISTEN: i32 = 50;
+const SYS_GETSOCKNAME: i32 = 51;
+const SYS_GETPEERNAME: i32 = 52;
 const SYS_SETSOCKOPT: i32 = 54;
 const SYS_GETSOCKOPT: i32 = 55;
 const SYS_FORK: i32 = 57;
 const SYS_EXECVE: i32 = 59;
 const SYS_EXIT: i32 = 60;
 const SYS_WAIT4: i32 = 61;
+const SYS_KILL: i32 = 62;
 const SYS_FCNTL: i32 = 72;
+const SYS_FLOCK: i32 = 73;
+const SYS_FTRUNCATE: i32 = 77;
+const SYS_RENAME: u32 = 82;
 const SYS_MKDIR: u32 = 83;
+const SYS_UNLINK: u32 = 87;
+const SYS_READLINK: u32 = 89;
+const SYS_GETRUSAGE: i32 = 98;
+const SYS_SETPGID: i32 = 109;
+const SYS_GETPPID: i32 = 110;
 const SYS_EPOLL_CREATE: i32 = 213;
+const SYS_CLOCK_GETTIME: i32 = 228;
 const SYS_INOTIFY_ADD_WATCH: i32 = 254;
 const SYS_EPOLL_WAIT: i32 = 232;
 const SYS_EPOLL_CTL: i32 = 233;
 const SYS_GETDENTS64: u32 = 217;
+const SYS_RT_SIGACTION: i32 = 13;
 const SYS_PIPE2: i32 = 293;
 const SYS_INOTIFY_INIT1: i32 = 294;
 
 pub const EAGAIN: i32 = -11; // Operation would block, try again
+pub const EPIPE: i32 = -32; // Broken pipe
 const EINTR: i32 = -4; // Interrupted system call
 const EACCES: i32 = -13; // Permission denied
+const EEXIST: i32 = -17; // File exists
 const ENOTTY: i32 = -25; // Not a typewriter / inappropriate ioctl for device
+pub const ENETUNREACH: i32 = -101; // Network is unreachable
 pub const EINPROGRESS: i32 = -115; // Operation now in progress
 
 // TODO check these two, might be wrong values, and convert to decimal
@@ -82,12 +108,21 @@ pub const O_DIRECTORY: c_int = 0x10000;
 
 pub const O_RDONLY: c_int = 0;
 pub const O_WRONLY: c_int = 1;
-//const O_RDWR: c_int = 2;
+pub const O_RDWR: c_int = 2;
 pub const O_CREAT: c_int = 64;
+pub const O_EXCL: c_int = 128;
 pub const O_TRUNC: c_int = 512;
+pub const O_APPEND: c_int = 1024;
 pub const O_NONBLOCK: c_int = 2048;
+pub const O_PATH: c_int = 0x200000;
+
+pub const SEEK_SET: c_int = 0;
+
+pub const LOCK_EX: c_int = 2;
+pub const LOCK_NB: c_int = 4;
 
 pub const F_OK: i32 = 0;
+pub const W_OK: i32 = 2;
 
 pub const SOCK_STREAM: c_int = 1;
 pub const SOCK_DGRAM: c_int = 2;
@@ -95,10 +130,15 @@ pub const SOCK_CLOEXEC: c_int = O_CLOEXEC;
 pub const AF_INET: c_int = 2;
 pub const SOL_SOCKET: c_int = 1;
 pub const SO_ERROR: c_int = 4;
+pub const SHUT_RDWR: c_int = 2;
+pub const MSG_NOSIGNAL: c_int = 0x4000;
+pub const SO_REUSEADDR: c_int = 2;
+pub const SO_RCVTIMEO: c_int = 20;
 pub const IPPROTO_TCP: i32 = 6;
 pub const TCP_FASTOPEN_CONNECT: i32 = 30;
 pub const EPOLLIN: u32 = 0x001;
 pub const EPOLL_CTL_ADD: c_int = 1;
+pub const EPOLL_CTL_DEL: c_int = 2;
 pub const IN_MOVED_TO: u32 = 0x00000080;
 //pub const IN_MODIFY: u32 = 0x00000002; // File was modified
 pub const IN_CLOSE_WRITE: u32 = 0x00000008; // Writable file was closed
@@ -116,7 +156,41 @@ pub const MAP_STACK: c_int = 0x020000;
 
 pub const F_GETFL: c_int = 3;
 pub const F_SETFL: c_int = 4;
+pub const F_DUPFD_CLOEXEC: c_int = 1030;
 const TCGETS: usize = 0x5401;
+const TIOCGWINSZ: usize = 0x5413;
+const TCSETS: usize = 0x5402;
+
+// termios c_lflag bits
+pub const ISIG: u32 = 0o1;
+pub const ICANON: u32 = 0o2;
+pub const ECHO: u32 = 0o10;
+pub const IEXTEN: u32 = 0o100000;
+// termios c_iflag bits
+pub const ICRNL: u32 = 0o400;
+pub const IXON: u32 = 0o2000;
+// termios c_cc indexes
+pub const VTIME: usize = 5;
+pub const VMIN: usize = 6;
+
+/// The kernel's struct termios, what TCGETS and TCSETS take
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct termios {
+    pub c_iflag: u32,
+    pub c_oflag: u32,
+    pub c_cflag: u32,
+    pub c_lflag: u32,
+    pub c_line: u8,
+    pub c_cc: [u8; 19],
+}
+const CLOCK_REALTIME: c_int = 0;
+const CLOCK_MONOTONIC: c_int = 1;
+const SIGINT: c_int = 2;
+const SIGKILL: c_int = 9;
+const SIGPIPE: c_int = 13;
+const SIG_DFL: usize = 0;
+const SIG_IGN: usize = 1;
 const POLLIN: c_short = 0x001;
 const POLLOUT: c_short = 0x004;
 
@@ -195,6 +269,39 @@ pub struct inotify_event {
     pub name: [c_char; NAME_MAX], // Optional null-terminated name
 }
 
+#[repr(C)]
+pub struct timeval {
+    pub tv_sec: time_t,
+    pub tv_usec: i64,
+}
+
+#[repr(C)]
+pub struct timespec {
+    pub tv_sec: time_t,
+    pub tv_nsec: i64,
+}
+
+#[repr(C)]
+pub struct rusage {
+    pub ru_utime: timeval,
+    pub ru_stime: timeval,
+    /// Peak resident set size, KiB
+    pub ru_maxrss: i64,
+    // ixrss through nivcsw, unused
+    _rest: [i64; 13],
+}
+
+const RUSAGE_SELF: c_int = 0;
+
+// The kernel's struct sigaction, not glibc's
+#[repr(C)]
+struct kernel_sigaction {
+    handler: usize,
+    flags: u64,
+    restorer: usize,
+    mask: u64,
+}
+
 #[repr(C)]
 struct pollfd {
     fd: c_int,
@@ -255,6 +362,24 @@ pub fn write(fd: c_int, buf: *const c_void, count: size_t) -> i32 {
     ret
 }
 
+/// Write at `offset` without moving the file position
+pub fn pwrite(fd: c_int, buf: *const c_void, count: size_t, offset: i64) -> i32 {
+    let mut ret: i32;
+    unsafe {
+        asm!("syscall",
+            inlateout("eax") SYS_PWRITE64 as i32 => ret,
+            in("edi") fd,
+            in("rsi") buf,
+            in("rdx") count,
+            in("r10") offset,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack)
+        );
+    }
+    ret
+}
+
 pub fn mmap(
     addr: *mut c_void,
     len: size_t,
@@ -337,6 +462,49 @@ pub fn isatty(fd: c_int) -> bool {
     }
 }
 
+pub fn tcgetattr(fd: c_int, t: &mut termios) -> c_int {
+    ioctl_termios(fd, TCGETS, t)
+}
+
+pub fn tcsetattr(fd: c_int, t: &termios) -> c_int {
+    ioctl_termios(fd, TCSETS, t as *const termios as *mut termios)
+}
+
+fn ioctl_termios(fd: c_int, request: usize, t: *mut termios) -> c_int {
+    let mut ret: c_long;
+    unsafe {
+        asm!("syscall",
+            inlateout("rax") SYS_IOCTL as c_long => ret,
+            in("edi") fd,
+            in("rsi") request,
+            in("rdx") t,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack)
+        );
+    }
+    if ret < 0 { -1 } else { 0 }
+}
+
+/// Rows and columns of the terminal on `fd`, None if it isn't one.
+pub fn terminal_size(fd: c_int) -> Option<(u16, u16)> {
+    let mut ret: c_long;
+    // struct winsize: rows, cols, xpixel, ypixel
+    let mut ws = [0u16; 4];
+    unsafe {
+        asm!("syscall",
+            inlateout("rax") SYS_IOCTL as c_long => ret,
+            in("edi") fd,
+            in("rsi") TIOCGWINSZ,
+            in("rdx") ws.as_mut_ptr(),
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack)
+        );
+    }
+    (ret == 0 && ws[0] > 0 && ws[1] > 0).then_some((ws[0], ws[1]))
+}
+
 pub fn mkdir(path: *const c_char, mode: u32) -> i32 {
     let mut ret: i32;
     unsafe {
@@ -352,6 +520,35 @@ pub fn mkdir(path: *const c_char, mode: u32) -> i32 {
     ret
 }
 
+pub fn rename(old_path: *const c_char, new_path: *const c_char) -> i32 {
+    let mut ret: i32;
+    unsafe {
+        asm!("syscall",
+             inout("eax") SYS_RENAME => ret,
+             in("rdi") old_path,
+             in("rsi") new_path,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack),
+        );
+    }
+    ret
+}
+
+pub fn unlink(path: *const c_char) -> i32 {
+    let mut ret: i32;
+    unsafe {
+        asm!("syscall",
+             inout("eax") SYS_UNLINK => ret,
+             in("rdi") path,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack),
+        );
+    }
+    ret
+}
+
 pub fn getdents64(fd: c_int, dirp: *mut c_void, count: size_t) -> ssize_t {
     let mut ret: ssize_t;
     unsafe {
@@ -383,6 +580,8 @@ pub fn open(path: *const c_char, flags: i32, mode: i32) -> Result<i32, &'static
     }
     if result == EACCES {
         Err("Permission denied")
+    } else if result == EEXIST {
+        Err("File exists")
     } else if result < 0 {
         Err("SYS_OPEN error")
     } else {
@@ -404,6 +603,37 @@ pub fn close(fd: i32) -> i32 {
     ret
 }
 
+pub fn lseek(fd: c_int, offset: i64, whence: c_int) -> i64 {
+    let mut ret: i64;
+    unsafe {
+        asm!("syscall",
+             inlateout("rax") SYS_LSEEK as i64 => ret,
+             in("edi") fd,
+             in("rsi") offset,
+             in("edx") whence,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack, nomem),
+        );
+    }
+    ret
+}
+
+pub fn ftruncate(fd: c_int, len: i64) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+             inout("eax") SYS_FTRUNCATE => ret,
+             in("edi") fd,
+             in("rsi") len,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack, nomem),
+        );
+    }
+    ret
+}
+
 fn poll(fds: *mut pollfd, nfds: size_t, timeout: c_int) -> c_int {
     let mut ret: c_int;
     unsafe {
@@ -429,6 +659,15 @@ pub fn poll_write(fd: c_int, timeout_ms: c_int) -> c_int {
     poll(fds.as_mut_ptr(), 1, timeout_ms)
 }
 
+pub fn poll_read(fd: c_int, timeout_ms: c_int) -> c_int {
+    let mut fds = [pollfd {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    }];
+    poll(fds.as_mut_ptr(), 1, timeout_ms)
+}
+
 /// open + fstat + close
 pub fn stat(path: *const c_char, sb: &mut MaybeUninit<Stat>) -> Result<(), &'static str> {
     let fd = open(path, O_RDONLY, 0)?;
@@ -467,6 +706,27 @@ pub fn socket(domain: c_int, ty: c_int, protocol: c_int) -> i32 {
     ret
 }
 
+/// Write to a connected socket. With MSG_NOSIGNAL a closed connection is
+/// an EPIPE error instead of a SIGPIPE that kills us.
+pub fn send(fd: c_int, buf: *const c_void, count: size_t, flags: c_int) -> i32 {
+    let mut ret: i32;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_SENDTO => ret,
+            in("edi") fd,
+            in("rsi") buf,
+            in("rdx") count,
+            in("r10") flags as isize,
+            in("r8") 0usize, // no address, already connected
+            in("r9") 0usize,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
 pub fn connect(socket: c_int, address: *const sockaddr, len: socklen_t) -> c_int {
     let mut ret: c_int;
     unsafe {
@@ -531,6 +791,103 @@ pub fn getsockopt(
     ret
 }
 
+pub fn getpeername(socket: c_int, address: *mut sockaddr, len: *mut socklen_t) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_GETPEERNAME => ret,
+            in("edi") socket,
+            in("rsi") address,
+            in("rdx") len,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+pub fn getsockname(socket: c_int, address: *mut sockaddr, len: *mut socklen_t) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_GETSOCKNAME => ret,
+            in("edi") socket,
+            in("rsi") address,
+            in("rdx") len,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+pub fn bind(socket: c_int, address: *const sockaddr, len: socklen_t) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_BIND => ret,
+            in("edi") socket,
+            in("rsi") address,
+            in("edx") len,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+pub fn listen(socket: c_int, backlog: c_int) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_LISTEN => ret,
+            in("edi") socket,
+            in("esi") backlog,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// Safe from another thread, a blocked read on `socket` returns 0
+pub fn shutdown(socket: c_int, how: c_int) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_SHUTDOWN => ret,
+            in("edi") socket,
+            in("esi") how,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// We don't need the peer address, so no sockaddr out-params.
+pub fn accept4(socket: c_int, flags: c_int) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_ACCEPT4 => ret,
+            in("edi") socket,
+            in("rsi") core::ptr::null_mut::<sockaddr>(),
+            in("rdx") core::ptr::null_mut::<socklen_t>(),
+            in("r10d") flags,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
 pub fn fcntl(fd: c_int, op: c_int, flags: c_int) -> c_int {
     let mut ret: c_int;
     unsafe {
@@ -736,7 +1093,254 @@ pub fn waitpid(pid: pid_t, status: *mut c_int, options: c_int) -> pid_t {
     ret
 }
 
+pub fn kill(pid: pid_t, sig: c_int) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_KILL => ret,
+            in("edi") pid,
+            in("esi") sig,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// Move `pid` (0 is us) into process group `pgid` (0 is a new one led by it)
+fn setpgid(pid: pid_t, pgid: pid_t) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_SETPGID => ret,
+            in("edi") pid,
+            in("esi") pgid,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack, nomem),
+        );
+    }
+    ret
+}
+
+pub fn getpid() -> pid_t {
+    let mut ret: pid_t;
+    unsafe {
+        asm!("syscall",
+             inout("eax") SYS_GETPID => ret,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack),
+        );
+    }
+    ret
+}
+
+pub fn getppid() -> pid_t {
+    let mut ret: pid_t;
+    unsafe {
+        asm!("syscall",
+             inout("eax") SYS_GETPPID => ret,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack),
+        );
+    }
+    ret
+}
+
+/// Advisory lock on the whole file. Released when the fd is closed.
+pub fn flock(fd: c_int, op: c_int) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+             inout("eax") SYS_FLOCK => ret,
+             in("edi") fd,
+             in("esi") op,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack),
+        );
+    }
+    ret
+}
+
+/// Returns the number of bytes placed in `buf`, which is not null terminated.
+pub fn readlink(path: *const c_char, buf: *mut c_char, len: size_t) -> ssize_t {
+    let mut ret: ssize_t;
+    unsafe {
+        asm!("syscall",
+             inout("rax") SYS_READLINK as ssize_t => ret,
+             in("rdi") path,
+             in("rsi") buf,
+             in("rdx") len,
+             lateout("rcx") _,
+             lateout("r11") _,
+             options(nostack),
+        );
+    }
+    ret
+}
+
+pub fn clock_gettime(clock_id: c_int, tp: *mut timespec) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_CLOCK_GETTIME => ret,
+            in("edi") clock_id,
+            in("rsi") tp,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+pub fn nanosleep(req: *const timespec) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_NANOSLEEP => ret,
+            in("rdi") req,
+            in("rsi") 0usize,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// Block for `ms` milliseconds. A signal cuts it short.
+pub fn sleep_ms(ms: u64) {
+    let ts = timespec {
+        tv_sec: (ms / 1000) as time_t,
+        tv_nsec: (ms % 1000 * 1_000_000) as i64,
+    };
+    nanosleep(&ts);
+}
+
+pub fn getrusage(who: c_int, usage: *mut rusage) -> c_int {
+    let mut ret: c_int;
+    unsafe {
+        asm!("syscall",
+            inout("eax") SYS_GETRUSAGE => ret,
+            in("edi") who,
+            in("rsi") usage,
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// Most memory this process has had resident, in KiB
+pub fn peak_rss_kib() -> Option<u64> {
+    let mut usage = rusage {
+        ru_utime: timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        },
+        ru_stime: timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        },
+        ru_maxrss: 0,
+        _rest: [0; 13],
+    };
+    (getrusage(RUSAGE_SELF, &mut usage) == 0).then_some(usage.ru_maxrss as u64)
+}
+
+/// Milliseconds on the monotonic clock. Only useful for differences.
+pub fn monotonic_ms() -> u64 {
+    let mut ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    clock_gettime(CLOCK_MONOTONIC, &mut ts);
+    ts.tv_sec as u64 * 1000 + ts.tv_nsec as u64 / 1_000_000
+}
+
+/// Microseconds on the monotonic clock. Only useful for differences.
+pub fn monotonic_us() -> u64 {
+    let mut ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    clock_gettime(CLOCK_MONOTONIC, &mut ts);
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1000
+}
+
+/// Milliseconds since the Unix epoch, wall clock.
+pub fn unix_time_ms() -> u64 {
+    let mut ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    clock_gettime(CLOCK_REALTIME, &mut ts);
+    ts.tv_sec as u64 * 1000 + ts.tv_nsec as u64 / 1_000_000
+}
+
+/// Seconds since the Unix epoch, wall clock.
+pub fn unix_time() -> u64 {
+    let mut ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    clock_gettime(CLOCK_REALTIME, &mut ts);
+    ts.tv_sec as u64
+}
+
+/// Set the handler for `signum` to SIG_DFL or SIG_IGN, returning the old one.
+fn signal(signum: c_int, handler: usize) -> usize {
+    let act = kernel_sigaction {
+        handler,
+        flags: 0,
+        restorer: 0,
+        mask: 0,
+    };
+    let mut old = kernel_sigaction {
+        handler: SIG_DFL,
+        flags: 0,
+        restorer: 0,
+        mask: 0,
+    };
+    let mut ret: c_long;
+    unsafe {
+        asm!("syscall",
+            inlateout("rax") SYS_RT_SIGACTION as c_long => ret,
+            in("rdi") signum,
+            in("rsi") &act as *const kernel_sigaction,
+            in("rdx") &mut old as *mut kernel_sigaction,
+            in("r10") core::mem::size_of::<u64>(),
+            lateout("rcx") _,
+            lateout("r11") _,
+            options(nostack),
+        );
+    }
+    if ret < 0 { SIG_DFL } else { old.handler }
+}
+
 pub fn system(command: &str) -> OrtResult<ProcessOutput> {
+    run_command(command, None, None)
+}
+
+/// Run `command` with bash, writing `input` to its stdin.
+/// The child and anything it started are killed if it has not finished after
+/// `timeout_ms`.
+pub fn system_with_input(command: &str, input: &[u8], timeout_ms: u32) -> OrtResult<ProcessOutput> {
+    run_command(command, Some(input), Some(timeout_ms))
+}
+
+fn run_command(
+    command: &str,
+    input: Option<&[u8]>,
+    timeout_ms: Option<u32>,
+) -> OrtResult<ProcessOutput> {
+    const STDIN_FILENO: c_int = 0;
     const STDOUT_FILENO: c_int = 1;
     const STDERR_FILENO: c_int = 2;
 
@@ -745,30 +1349,48 @@ pub fn system(command: &str) -> OrtResult<ProcessOutput> {
     let (env_bytes, envp) = current_envp();
     let bash_path = find_bash(envp.as_ptr())?;
 
+    // Without input the child shares our stdin
+    let mut stdin_pipe = [-1 as c_int; 2];
+    if input.is_some() && pipe2(stdin_pipe.as_mut_ptr(), O_CLOEXEC) < 0 {
+        return Err(ort_error(ErrorKind::Other, "system pipe2 failed"));
+    }
+
     let mut stdout_pipe = [0 as c_int; 2];
     if pipe2(stdout_pipe.as_mut_ptr(), O_CLOEXEC) < 0 {
+        close_pipe(&stdin_pipe);
         return Err(ort_error(ErrorKind::Other, "system pipe2 failed"));
     }
 
     let mut stderr_pipe = [0 as c_int; 2];
     if pipe2(stderr_pipe.as_mut_ptr(), O_CLOEXEC) < 0 {
-        let _ = close(stdout_pipe[0]);
-        let _ = close(stdout_pipe[1]);
+        close_pipe(&stdin_pipe);
+        close_pipe(&stdout_pipe);
         return Err(ort_error(ErrorKind::Other, "system pipe2 failed"));
     }
 
     let pid = fork();
     if pid < 0 {
-        let _ = close(stdout_pipe[0]);
-        let _ = close(stdout_pipe[1]);
-        let _ = close(stderr_pipe[0]);
-        let _ = close(stderr_pipe[1]);
+        close_pipe(&stdin_pipe);
+        close_pipe(&stdout_pipe);
+        close_pipe(&stderr_pipe);
         return Err(ort_error(ErrorKind::Other, "system fork failed"));
     }
 
+    // With a timeout the child leads its own process group, so killing
+    // that gets the commands of a pipeline too. Both sides set it, so it is
+    // in place whichever runs first.
     if pid == 0 {
+        if timeout_ms.is_some() {
+            let _ = setpgid(0, 0);
+        }
         let _ = close(stdout_pipe[0]);
         let _ = close(stderr_pipe[0]);
+        if stdin_pipe[0] >= 0 {
+            let _ = close(stdin_pipe[1]);
+            if dup2(stdin_pipe[0], STDIN_FILENO) < 0 {
+                exit(127);
+            }
+        }
         if dup2(stdout_pipe[1], STDOUT_FILENO) < 0 {
             exit(127);
         }
@@ -791,15 +1413,38 @@ pub fn system(command: &str) -> OrtResult<ProcessOutput> {
         let _ = execve(bash_path.as_ptr(), argv.as_ptr(), envp.as_ptr());
         exit(127);
     }
+    if timeout_ms.is_some() {
+        let _ = setpgid(pid, pid);
+    }
 
     let _ = close(stdout_pipe[1]);
     let _ = close(stderr_pipe[1]);
+    if stdin_pipe[0] >= 0 {
+        let _ = close(stdin_pipe[0]);
+    }
 
-    // The child stays in the parent's foreground process group, so terminal
-    // Ctrl-C is delivered to the shell by the kernel's default job control.
-    let (stdout, stderr) = match read_child_pipes(stdout_pipe[0], stderr_pipe[0]) {
+    // Without a timeout the child stays in the parent's foreground process
+    // group, so terminal Ctrl-C is delivered to the shell by the kernel's
+    // default job control.
+    // If the child exits without reading its input, don't die of SIGPIPE.
+    let prev_sigpipe = input.map(|_| signal(SIGPIPE, SIG_IGN));
+    let deadline = timeout_ms.map(|t| monotonic_ms() + t as u64);
+    let pipes = read_child_pipes(
+        stdin_pipe[1],
+        input.unwrap_or_default(),
+        stdout_pipe[0],
+        stderr_pipe[0],
+        deadline,
+    );
+    if let Some(prev) = prev_sigpipe {
+        signal(SIGPIPE, prev);
+    }
+    let (stdout, stderr) = match pipes {
         Ok(output) => output,
         Err(err) => {
+            if matches!(err.kind, ErrorKind::CommandTimeout) {
+                kill(-pid, SIGKILL);
+            }
             let _ = wait_for_child(pid);
             return Err(err);
         }
@@ -818,7 +1463,92 @@ pub fn system(command: &str) -> OrtResult<ProcessOutput> {
     })
 }
 
-fn read_child_pipes(stdout_fd: c_int, stderr_fd: c_int) -> OrtResult<(Vec<u8>, Vec<u8>)> {
+/// A child started by `spawn_with_stdin`
+pub struct Child {
+    pid: pid_t,
+    /// Write end of the child's stdin
+    pub stdin: c_int,
+    prev_sigint: usize,
+    prev_sigpipe: usize,
+}
+
+impl Child {
+    /// Close the child's stdin and wait for it to exit.
+    pub fn wait(self) -> OrtResult<u32> {
+        let _ = close(self.stdin);
+        let exit_code = wait_for_child(self.pid);
+        signal(SIGINT, self.prev_sigint);
+        signal(SIGPIPE, self.prev_sigpipe);
+        exit_code
+    }
+}
+
+/// Start `command` with bash, sharing our stdout and stderr, with a pipe to its stdin.
+/// Until `Child::wait` we ignore Ctrl-C, the child handles it, and we don't die
+/// of SIGPIPE if the child exits early, writes to `stdin` fail instead.
+pub fn spawn_with_stdin(command: &str) -> OrtResult<Child> {
+    const STDIN_FILENO: c_int = 0;
+
+    let command = CString::new(command)
+        .map_err(|_| ort_error(ErrorKind::Other, "spawn command contains nul byte"))?;
+    let (env_bytes, envp) = current_envp();
+    let bash_path = find_bash(envp.as_ptr())?;
+
+    let mut stdin_pipe = [0 as c_int; 2];
+    if pipe2(stdin_pipe.as_mut_ptr(), O_CLOEXEC) < 0 {
+        return Err(ort_error(ErrorKind::Other, "spawn pipe2 failed"));
+    }
+
+    let pid = fork();
+    if pid < 0 {
+        close_pipe(&stdin_pipe);
+        return Err(ort_error(ErrorKind::Other, "spawn fork failed"));
+    }
+
+    if pid == 0 {
+        let _ = close(stdin_pipe[1]);
+        if dup2(stdin_pipe[0], STDIN_FILENO) < 0 {
+            exit(127);
+        }
+        let argv = [
+            c"bash".as_ptr(),
+            c"-c".as_ptr(),
+            command.as_ptr(),
+            core::ptr::null(),
+        ];
+        let _ = execve(bash_path.as_ptr(), argv.as_ptr(), envp.as_ptr());
+        exit(127);
+    }
+
+    let _ = close(stdin_pipe[0]);
+    // Keep the environment backing storage alive until after the fork.
+    let _ = env_bytes.len();
+
+    Ok(Child {
+        pid,
+        stdin: stdin_pipe[1],
+        prev_sigint: signal(SIGINT, SIG_IGN),
+        prev_sigpipe: signal(SIGPIPE, SIG_IGN),
+    })
+}
+
+fn close_pipe(pipe: &[c_int; 2]) {
+    for fd in pipe {
+        if *fd >= 0 {
+            let _ = close(*fd);
+        }
+    }
+}
+
+/// Write `input` to the child's stdin and gather its stdout and stderr,
+/// until both output pipes close or we pass `deadline` (monotonic ms).
+fn read_child_pipes(
+    stdin_fd: c_int,
+    input: &[u8],
+    stdout_fd: c_int,
+    stderr_fd: c_int,
+    deadline: Option<u64>,
+) -> OrtResult<(Vec<u8>, Vec<u8>)> {
     let mut fds = [
         pollfd {
             fd: stdout_fd,
@@ -830,14 +1560,36 @@ fn read_child_pipes(stdout_fd: c_int, stderr_fd: c_int) -> OrtResult<(Vec<u8>, V
             events: POLLIN,
             revents: 0,
         },
+        // poll ignores negative fds, so this is skipped when there's no input
+        pollfd {
+            fd: stdin_fd,
+            events: POLLOUT,
+            revents: 0,
+        },
     ];
+    if stdin_fd >= 0 && input.is_empty() {
+        let _ = close(stdin_fd);
+        fds[2].fd = -1;
+    }
+    let mut written = 0;
     let mut stdout = Vec::new();
     let mut stderr = Vec::new();
     let mut buf = [0u8; 4096];
     let mut open_fds = 2;
 
     while open_fds > 0 {
-        let num_ready = poll(fds.as_mut_ptr(), fds.len(), -1);
+        let timeout = match deadline {
+            Some(d) => {
+                let now = monotonic_ms();
+                if now >= d {
+                    close_poll_fds(&mut fds);
+                    return Err(ort_error(ErrorKind::CommandTimeout, "system"));
+                }
+                (d - now) as c_int
+            }
+            None => -1,
+        };
+        let num_ready = poll(fds.as_mut_ptr(), fds.len(), timeout);
         if num_ready < 0 {
             if num_ready == EINTR {
                 continue;
@@ -846,7 +1598,26 @@ fn read_child_pipes(stdout_fd: c_int, stderr_fd: c_int) -> OrtResult<(Vec<u8>, V
             return Err(ort_error(ErrorKind::Other, "system poll failed"));
         }
 
-        for idx in 0..fds.len() {
+        if fds[2].fd >= 0 && fds[2].revents != 0 {
+            // A pipe write of up to PIPE_BUF (4096) bytes doesn't block once poll says ready
+            let end = min(written + 4096, input.len());
+            let ret = write(
+                fds[2].fd,
+                input[written..end].as_ptr().cast(),
+                end - written,
+            );
+            if ret > 0 {
+                written += ret as usize;
+            }
+            if written == input.len() || (ret < 0 && ret != EINTR && ret != EAGAIN) {
+                // Done, or the child closed its stdin (EPIPE). Either way it gets EOF.
+                let _ = close(fds[2].fd);
+                fds[2].fd = -1;
+            }
+            fds[2].revents = 0;
+        }
+
+        for idx in 0..2 {
             if fds[idx].fd < 0 || fds[idx].revents == 0 {
                 continue;
             }
@@ -868,6 +1639,9 @@ fn read_child_pipes(stdout_fd: c_int, stderr_fd: c_int) -> OrtResult<(Vec<u8>, V
             }
         }
     }
+    if fds[2].fd >= 0 {
+        let _ = close(fds[2].fd);
+    }
 
     Ok((stdout, stderr))
 }
@@ -886,7 +1660,7 @@ fn read_ready_fd(fd: c_int, output: &mut Vec<u8>, buf: &mut [u8; 4096]) -> OrtRe
     }
 }
 
-fn close_poll_fds(fds: &mut [pollfd; 2]) {
+fn close_poll_fds(fds: &mut [pollfd; 3]) {
     for fd in fds {
         if fd.fd >= 0 {
             let _ = close(fd.fd);
@@ -1006,6 +1780,63 @@ mod tests {
         assert_eq!(out.exit_code, 7);
     }
 
+    #[test]
+    fn system_with_input_feeds_stdin() {
+        let out = match super::system_with_input("tr a-z A-Z", b"hook payload", 5000) {
+            Ok(out) => out,
+            Err(err) => panic!("{}", err.as_string()),
+        };
+        assert_eq!(out.stdout, "HOOK PAYLOAD");
+        assert_eq!(out.exit_code, 0);
+    }
+
+    #[test]
+    fn spawn_with_stdin_exit_code() {
+        let child = match super::spawn_with_stdin("grep -q pager") {
+            Ok(child) => child,
+            Err(err) => panic!("{}", err.as_string()),
+        };
+        let msg = b"to the pager\n";
+        assert_eq!(
+            super::write(child.stdin, msg.as_ptr().cast(), msg.len()),
+            msg.len() as i32
+        );
+        assert_eq!(child.wait().unwrap(), 0);
+    }
+
+    #[test]
+    fn system_with_input_times_out() {
+        let res = super::system_with_input("sleep 5", b"", 100);
+        assert!(matches!(
+            res,
+            Err(crate::OrtError {
+                kind: crate::ErrorKind::CommandTimeout,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn system_with_input_timeout_kills_the_pipeline() {
+        extern crate std;
+        let pid_file =
+            std::env::temp_dir().join(std::format!("ort-test-{}-hook-pid", std::process::id()));
+        let command = std::format!(
+            "sh -c 'echo $$ > {}; exec sleep 30' | cat",
+            pid_file.display()
+        );
+        let res = super::system_with_input(&command, b"", 300);
+        assert!(res.is_err());
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let _ = std::fs::remove_file(&pid_file);
+        // Gone, or dead and waiting to be reaped
+        let stat = std::fs::read_to_string(std::format!("/proc/{}/stat", pid.trim()));
+        assert!(
+            stat.as_deref().map_or(true, |s| s.contains(") Z ")),
+            "{stat:?}"
+        );
+    }
+
     /*
     fn test_mkdir() {
         let ret = super::mkdir(c"/home/graham/Temp/HERE_gk_test".as_ptr(), 0o755);
@@ -0,0 +1,28 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2025 Graham King
+//!
+//! TCP, TLS 1.3 and HTTP/1.1 straight on Linux syscalls. The public parts
+//! are re-exported as `net`.
+
+pub mod chunked;
+pub mod deflate;
+pub mod event_loop;
+pub mod http;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+pub mod pool;
+pub mod socket;
+pub mod tls;
+pub mod trace;
+pub mod wire_dump;
+
+pub use socket::TcpSocket;
+pub use tls::TlsStream;
+
+/// The official one is in std
+pub trait AsFd {
+    fn as_fd(&self) -> i32;
+}
@@ -5,14 +5,27 @@
 //! Copyright (c) 2025 Graham King
 
 extern crate alloc;
+use alloc::boxed::Box;
 use alloc::ffi::CString;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
 use crate::common::io::ReadLine;
+use crate::common::utils::zclean;
 use crate::input::prompt::PromptReader;
-use crate::net::AsFd;
-use crate::{ErrorKind, OrtResult, Read, common::buf_read, ort_error, syscall};
+use crate::transport::{AsFd, socket};
+use crate::{
+    ErrorKind, OrtResult, Read, TcpSocket, TlsStream, common::buf_read, ort_error, syscall,
+};
+
+/// A single chunk bigger than this is an error. Images arrive whole, so it's generous.
+const MAX_CHUNK_BYTES: usize = 64 * 1024 * 1024;
+
+/// After `[DONE]` the server only has the last, empty, chunk to send.
+/// How long we wait for it, and how many chunks we skip looking for it,
+/// before giving up on reusing the connection.
+const DRAIN_TIMEOUT_MS: u64 = 300;
+const MAX_DRAIN_CHUNKS: usize = 4;
 
 /// Read a transfer encoding chunked body, chunk by chunk.
 ///
@@ -29,6 +42,9 @@ pub struct ChunkedIterator<R: Read, const MAX_CHUNK_SIZE: usize> {
     size_buf: String,
     data_buf: Vec<u8>,
     pending_lines: Vec<String>,
+    // Read the last chunk and the trailer, the connection is ready for
+    // another response
+    is_done: bool,
 }
 
 impl<R: Read, const MAX_CHUNK_SIZE: usize> ReadLine for ChunkedIterator<R, MAX_CHUNK_SIZE> {
@@ -44,7 +60,8 @@ impl<R: Read, const MAX_CHUNK_SIZE: usize> ReadLine for ChunkedIterator<R, MAX_C
             None => return Ok(0),
         };
 
-        let mut lines_iter = chunk.lines();
+        // Keep the newlines. A line without one continues in the next chunk.
+        let mut lines_iter = chunk.split_inclusive('\n');
         let Some(first) = lines_iter.next() else {
             return Ok(0);
         };
@@ -53,11 +70,7 @@ impl<R: Read, const MAX_CHUNK_SIZE: usize> ReadLine for ChunkedIterator<R, MAX_C
 
         // `rev` to store the lines in reverse order, allowing us to `pop` the back one.
         // This is much more memory efficient that removing the front one and move the rest.
-        self.pending_lines = lines_iter
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .rev()
-            .collect();
+        self.pending_lines = lines_iter.map(|s| s.to_string()).rev().collect();
 
         Ok(first_len)
     }
@@ -69,9 +82,27 @@ impl<T: Read + AsFd, const MAX_CHUNK_SIZE: usize> AsFd for ChunkedIterator<T, MA
     }
 }
 
-impl<T: Read + AsFd, const MAX_CHUNK_SIZE: usize> PromptReader
+impl<T: Read + AsFd + 'static, const MAX_CHUNK_SIZE: usize> PromptReader
     for ChunkedIterator<T, MAX_CHUNK_SIZE>
 {
+    fn into_idle(self: Box<Self>) -> Option<TlsStream<TcpSocket>> {
+        let any: Box<dyn core::any::Any> = self;
+        let mut chunked = any
+            .downcast::<ChunkedIterator<TlsStream<TcpSocket>, MAX_CHUNK_SIZE>>()
+            .ok()?;
+        // The SSE stream ended at `[DONE]`, before the HTTP body did
+        let fd = chunked.as_fd();
+        syscall::fcntl(fd, syscall::F_SETFL, 0);
+        socket::set_read_timeout(fd, DRAIN_TIMEOUT_MS);
+        for _ in 0..MAX_DRAIN_CHUNKS {
+            match chunked.next_chunk() {
+                Some(Ok(_)) => continue,
+                Some(Err(_)) => return None,
+                None => break,
+            }
+        }
+        chunked.into_inner()?.into_inner()
+    }
 }
 
 /// Lending Iterator. This doesn't implement Iterator because that doesn't allow the Item
@@ -86,10 +117,19 @@ impl<R: Read, const MAX_CHUNK_SIZE: usize> ChunkedIterator<R, MAX_CHUNK_SIZE> {
             size_buf: String::with_capacity(16),
             data_buf: Vec::with_capacity(MAX_CHUNK_SIZE),
             pending_lines: Vec::new(),
+            is_done: false,
         }
     }
 
+    /// The reader, once the whole body has been read
+    pub fn into_inner(self) -> Option<buf_read::OrtBufReader<R>> {
+        self.is_done.then_some(self.r)
+    }
+
     pub fn next_chunk(&mut self) -> Option<OrtResult<&str>> {
+        if self.is_done {
+            return None;
+        }
         let mut bytes_read = 0;
         // Usually we only go through the loop once per call.
         // Exceptions are the initial blank line, and splitting a multi-byte char.
@@ -115,16 +155,30 @@ impl<R: Read, const MAX_CHUNK_SIZE: usize> ChunkedIterator<R, MAX_CHUNK_SIZE> {
             let size = match usize::from_str_radix(size_str, 16) {
                 Ok(n) => n,
                 Err(_err) => {
-                    let c_s = CString::new("ERROR invalid chunked size: ".to_string() + size_str)
-                        .unwrap();
+                    let mut msg = "ERROR invalid chunked size: ".to_string() + size_str;
+                    let c_s = CString::new(zclean(&mut msg)).unwrap();
                     syscall::write(2, c_s.as_ptr().cast(), c_s.count_bytes());
                     return Some(Err(ort_error(ErrorKind::ChunkedInvalidSize, "")));
                 }
             };
             if size == 0 {
-                // How transfer-encoding chunked signals EOF
+                // How transfer-encoding chunked signals EOF.
+                // Trailer fields, if any, then a blank line.
+                loop {
+                    self.size_buf.clear();
+                    match self.r.read_line(&mut self.size_buf) {
+                        Ok(0) | Err(_) => return None,
+                        Ok(_) if self.size_buf.trim().is_empty() => break,
+                        Ok(_) => {}
+                    }
+                }
+                self.is_done = true;
                 return None;
             }
+            // Don't let a bad size line make us allocate it
+            if size > MAX_CHUNK_BYTES {
+                return Some(Err(ort_error(ErrorKind::ChunkedInvalidSize, "too big")));
+            }
 
             // Ensure buffer capacity (do not shrink)
             if bytes_read == 0 {
@@ -140,15 +194,18 @@ impl<R: Read, const MAX_CHUNK_SIZE: usize> ChunkedIterator<R, MAX_CHUNK_SIZE> {
             };
             bytes_read += size;
 
-            // If we split a UTF-8 multi-byte character on the end of the chunk,
-            // fetch the next chunk. This really happens.
-            let last_byte = self.data_buf[self.data_buf.len() - 1];
-            if (last_byte & 0b1000_0000) != 0 {
-                //let c_s = CString::new("SPLIT MULTI-BYTE CHAR\n").unwrap();
-                //unsafe { libc::write(2, c_s.as_ptr().cast(), c_s.count_bytes()) };
-                continue;
+            match str::from_utf8(&self.data_buf) {
+                Ok(_) => break,
+                // If we split a UTF-8 multi-byte character on the end of the chunk,
+                // fetch the next chunk. This really happens.
+                Err(err) if err.error_len().is_none() => continue,
+                Err(_) => {
+                    return Some(Err(ort_error(
+                        ErrorKind::ChunkedDataReadError,
+                        "invalid UTF-8",
+                    )));
+                }
             }
-            break;
         }
         Some(Ok(unsafe { str::from_utf8_unchecked(&self.data_buf) }))
     }
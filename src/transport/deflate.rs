@@ -0,0 +1,257 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! gzip (RFC 1952) for big request bodies. One DEFLATE block with the fixed
+//! Huffman codes (RFC 1951 3.2.6) and hash chain LZ77 matching. JSON prompts
+//! compress mostly from the repeats, so that's most of the gain for little code.
+
+extern crate alloc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const WINDOW: usize = 32 * 1024;
+const HASH_BITS: usize = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// How many earlier positions to try for a match. Higher is smaller but slower.
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// `data` as a gzip file
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // ID1 ID2, deflate, no flags, no mtime, no extra flags, OS unix
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+    let mut bits = BitWriter {
+        out: &mut out,
+        acc: 0,
+        n: 0,
+    };
+    deflate(data, &mut bits);
+    bits.flush();
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+fn deflate(data: &[u8], bits: &mut BitWriter) {
+    // Last block, fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    // Most recent position+1 for each hash, 0 for none, and the one before it
+    let mut head = vec![0u32; 1 << HASH_BITS];
+    let mut prev = vec![0u32; WINDOW];
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(&data[i..i + MIN_MATCH])] as usize;
+            let mut chain = MAX_CHAIN;
+            while candidate != 0 && chain != 0 {
+                let pos = candidate - 1;
+                let dist = i - pos;
+                if dist >= WINDOW {
+                    break;
+                }
+                let len = match_len(&data[pos..], &data[i..], max_len);
+                if len > best_len {
+                    (best_len, best_dist) = (len, dist);
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[pos % WINDOW] as usize;
+                if next == 0 || next > pos {
+                    // Slot reused by a newer position, the chain ends here
+                    break;
+                }
+                candidate = next;
+                chain -= 1;
+            }
+            insert(data, &mut head, &mut prev, i);
+        }
+
+        if best_len >= MIN_MATCH {
+            write_length(bits, best_len);
+            write_distance(bits, best_dist);
+            for pos in i + 1..(i + best_len).min(data.len() + 1 - MIN_MATCH) {
+                insert(data, &mut head, &mut prev, pos);
+            }
+            i += best_len;
+        } else {
+            write_literal(bits, data[i] as u16);
+            i += 1;
+        }
+    }
+    // End of block
+    write_literal(bits, 256);
+}
+
+fn insert(data: &[u8], head: &mut [u32], prev: &mut [u32], pos: usize) {
+    let h = hash(&data[pos..pos + MIN_MATCH]);
+    prev[pos % WINDOW] = head[h];
+    head[h] = pos as u32 + 1;
+}
+
+fn hash(b: &[u8]) -> usize {
+    let h = ((b[0] as usize) << 10) ^ ((b[1] as usize) << 5) ^ (b[2] as usize);
+    h & ((1 << HASH_BITS) - 1)
+}
+
+fn match_len(a: &[u8], b: &[u8], max: usize) -> usize {
+    a.iter().zip(&b[..max]).take_while(|(x, y)| x == y).count()
+}
+
+// Fixed Huffman code for a literal/length symbol, 0-287
+fn write_literal(bits: &mut BitWriter, sym: u16) {
+    let (code, len) = match sym {
+        0..=143 => (0x30 + sym, 8),
+        144..=255 => (0x190 + sym - 144, 9),
+        256..=279 => (sym - 256, 7),
+        _ => (0xc0 + sym - 280, 8),
+    };
+    bits.write_huffman(code as u32, len);
+}
+
+fn write_length(bits: &mut BitWriter, len: usize) {
+    let idx = LENGTH_BASE
+        .iter()
+        .rposition(|b| *b as usize <= len)
+        .unwrap();
+    write_literal(bits, 257 + idx as u16);
+    bits.write(
+        (len - LENGTH_BASE[idx] as usize) as u32,
+        LENGTH_EXTRA[idx] as u32,
+    );
+}
+
+fn write_distance(bits: &mut BitWriter, dist: usize) {
+    let idx = DIST_BASE.iter().rposition(|b| *b as usize <= dist).unwrap();
+    bits.write_huffman(idx as u32, 5);
+    bits.write(
+        (dist - DIST_BASE[idx] as usize) as u32,
+        DIST_EXTRA[idx] as u32,
+    );
+}
+
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    acc: u64,
+    n: u32,
+}
+
+impl BitWriter<'_> {
+    /// Least significant bit first, for headers and extra bits
+    fn write(&mut self, value: u32, len: u32) {
+        self.acc |= (value as u64) << self.n;
+        self.n += len;
+        while self.n >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    /// Huffman codes go most significant bit first
+    fn write_huffman(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn flush(&mut self) {
+        if self.n > 0 {
+            self.out.push(self.acc as u8);
+            self.acc = 0;
+            self.n = 0;
+        }
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc = CRC_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::syscall;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let mut body = String::from(r#"{"stream": true, "messages": ["#);
+        for i in 0..2000 {
+            body.push_str(r#"{"role": "user", "content": "line "#);
+            body.push_str(&crate::utils::num_to_string(i));
+            body.push_str("\"},");
+        }
+        body.push_str("\"é\"]}");
+        let gz = gzip(body.as_bytes());
+        assert!(gz.len() * 4 < body.len());
+
+        let out = syscall::system_with_input("gzip -dc", &gz, 5000).unwrap();
+        assert_eq!(out.exit_code, 0);
+        assert_eq!(out.stdout, body);
+
+        // Bigger than the window
+        let src = include_str!("tls.rs");
+        assert!(src.len() > WINDOW);
+        let out = syscall::system_with_input("gzip -dc", &gzip(src.as_bytes()), 5000).unwrap();
+        assert_eq!(out.stdout, src);
+
+        // Too short to match anything
+        let out = syscall::system_with_input("gzip -dc", &gzip(b"ab"), 5000).unwrap();
+        assert_eq!(out.stdout, "ab");
+    }
+}
@@ -0,0 +1,172 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! One thread waiting on many sockets and timers with epoll. Each socket and
+//! timer has a caller chosen token, usually an index into the caller's Vec.
+
+extern crate alloc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{ErrorKind, OrtResult, ort_error, syscall};
+
+/// Why `wait` returned for this token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// The socket has data
+    Readable(u64),
+    /// The timer's deadline passed. Timers fire once.
+    Timer(u64),
+}
+
+pub struct EventLoop {
+    epoll_fd: i32,
+    // (token, deadline in syscall::monotonic_ms)
+    timers: Vec<(u64, u64)>,
+    ready: Vec<syscall::epoll_event>,
+}
+
+impl EventLoop {
+    /// `capacity` is the most sockets we expect, it is only a hint
+    pub fn new(capacity: usize) -> OrtResult<Self> {
+        let capacity = capacity.max(1);
+        let epoll_fd = syscall::epoll_create(capacity as i32);
+        if epoll_fd < 0 {
+            return Err(ort_error(ErrorKind::Other, "epoll_create"));
+        }
+        Ok(EventLoop {
+            epoll_fd,
+            timers: Vec::new(),
+            ready: vec![syscall::epoll_event { events: 0, data: 0 }; capacity],
+        })
+    }
+
+    /// Wake for `fd` when it is readable. The socket should be non-blocking.
+    pub fn add(&mut self, fd: i32, token: u64) -> OrtResult<()> {
+        let mut event = syscall::epoll_event {
+            events: syscall::EPOLLIN,
+            data: token,
+        };
+        if syscall::epoll_ctl(self.epoll_fd, syscall::EPOLL_CTL_ADD, fd, &mut event) < 0 {
+            return Err(ort_error(ErrorKind::Other, "epoll_ctl"));
+        }
+        Ok(())
+    }
+
+    /// Stop watching `fd`. A finished socket stays readable, so remove it.
+    pub fn remove(&mut self, fd: i32) {
+        let mut event = syscall::epoll_event { events: 0, data: 0 };
+        syscall::epoll_ctl(self.epoll_fd, syscall::EPOLL_CTL_DEL, fd, &mut event);
+    }
+
+    /// Fire `Event::Timer(token)` at `deadline_ms`, in `syscall::monotonic_ms`.
+    /// Replaces the token's previous timer.
+    pub fn set_timer(&mut self, token: u64, deadline_ms: u64) {
+        self.clear_timer(token);
+        self.timers.push((token, deadline_ms));
+    }
+
+    pub fn clear_timer(&mut self, token: u64) {
+        self.timers.retain(|(t, _)| *t != token);
+    }
+
+    /// Until the next timer is due, -1 for none. A timer too far out for
+    /// epoll waits as long as it can, and is looked at again after.
+    fn timeout_ms(&self) -> i32 {
+        match self.timers.iter().map(|(_, d)| *d).min() {
+            Some(deadline) => deadline
+                .saturating_sub(syscall::monotonic_ms())
+                .min(i32::MAX as u64) as i32,
+            None => -1,
+        }
+    }
+
+    /// Sleep until a socket is readable or a timer is due, then put what
+    /// happened in `events`. With no timers it sleeps as long as it takes.
+    /// Returns false if a signal interrupted it, e.g. Ctrl-C.
+    pub fn wait(&mut self, events: &mut Vec<Event>) -> bool {
+        events.clear();
+        let num_ready = syscall::epoll_wait(
+            self.epoll_fd,
+            self.ready.as_mut_ptr(),
+            self.ready.len() as i32,
+            self.timeout_ms(),
+        );
+        if num_ready < 0 {
+            return false;
+        }
+        for evt in &self.ready[..num_ready as usize] {
+            events.push(Event::Readable(evt.data));
+        }
+        self.fire_timers(syscall::monotonic_ms(), events);
+        true
+    }
+
+    fn fire_timers(&mut self, now: u64, events: &mut Vec<Event>) {
+        self.timers.retain(|(token, deadline)| {
+            if *deadline <= now {
+                events.push(Event::Timer(*token));
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        let _ = syscall::close(self.epoll_fd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timers() {
+        let mut el = EventLoop::new(1).unwrap();
+        let now = syscall::monotonic_ms();
+        el.set_timer(1, now + 60_000);
+        el.set_timer(2, now);
+        el.set_timer(3, now);
+        el.clear_timer(3);
+
+        let mut events = Vec::new();
+        assert!(el.wait(&mut events));
+        assert_eq!(events, [Event::Timer(2)]);
+
+        // Replaced, so it doesn't wait a minute
+        el.set_timer(1, now);
+        assert!(el.wait(&mut events));
+        assert_eq!(events, [Event::Timer(1)]);
+        assert!(el.timers.is_empty());
+
+        assert_eq!(el.timeout_ms(), -1);
+        // Past what epoll can wait, about 25 days
+        el.set_timer(4, now + 30 * 24 * 60 * 60 * 1000);
+        assert_eq!(el.timeout_ms(), i32::MAX);
+    }
+
+    #[test]
+    fn test_readable() {
+        let mut fds = [0i32; 2];
+        assert_eq!(syscall::pipe2(fds.as_mut_ptr(), syscall::O_CLOEXEC), 0);
+        let mut el = EventLoop::new(1).unwrap();
+        el.add(fds[0], 7).unwrap();
+        syscall::write(fds[1], b"x".as_ptr().cast(), 1);
+        el.set_timer(8, syscall::monotonic_ms() + 60_000);
+
+        let mut events = Vec::new();
+        assert!(el.wait(&mut events));
+        assert_eq!(events, [Event::Readable(7)]);
+
+        el.remove(fds[0]);
+        syscall::close(fds[0]);
+        syscall::close(fds[1]);
+    }
+}
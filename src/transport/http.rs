@@ -0,0 +1,1135 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2025,2026 Graham King
+
+use core::cmp::min;
+use core::net::SocketAddr;
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::transport::AsFd;
+use crate::transport::wire_dump::WireDump;
+use crate::transport::{deflate, socket, trace};
+use crate::{
+    Context, ErrorKind, OrtError, OrtResult, Read, TcpSocket, TlsStream, Write, common::buf_read,
+    common::io::ReadLine, ort_error,
+};
+use crate::{syscall, utils};
+
+/// How long to wait for 'connect' syscall.
+/// Everyone blackholes the initial SYN so we can't wait for a rejection.
+/// Default timeout is about 2 minutes. Change to 2 seconds.
+const SOCKET_CONNECT_TIMEOUT_MS: i32 = 2000;
+
+/// A status line and headers bigger than this is an error, not something to buffer
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const TRANSFER_ENCODING_HEADER: &str = "Transfer-Encoding";
+const CONTENT_LENGTH_HEADER_NAME: &str = "Content-Length";
+
+const POST: &[u8] = "POST ".as_bytes();
+const GET: &[u8] = "GET ".as_bytes();
+const HTTP_1_1: &[u8] = " HTTP/1.1\r\n".as_bytes();
+const HOST_HEADER: &[u8] = "Host: ".as_bytes();
+const CLIENT_REQUEST_ID_HEADER: &[u8] = "X-Client-Request-Id: ".as_bytes();
+const CONTENT_LENGTH_HEADER: &[u8] = "Content-Length: ".as_bytes();
+const CONTENT_ENCODING_GZIP: &[u8] = "Content-Encoding: gzip\r\n".as_bytes();
+const CRLF: &[u8] = "\r\n".as_bytes();
+
+pub enum ResponseBody {
+    Chunked,
+    ContentLength(usize),
+    UntilEof,
+}
+
+pub struct ContentLengthReader<R: Read> {
+    inner: R,
+    remaining: usize,
+}
+
+impl<R: Read> ContentLengthReader<R> {
+    pub fn new(inner: R, len: usize) -> Self {
+        Self {
+            inner,
+            remaining: len,
+        }
+    }
+}
+
+impl<R: Read> Read for ContentLengthReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> OrtResult<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let to_read = min(buf.len(), self.remaining);
+        let n = self.inner.read(&mut buf[..to_read])?;
+        self.remaining -= n;
+        Ok(n)
+    }
+}
+
+impl<R: Read + AsFd> AsFd for ContentLengthReader<R> {
+    fn as_fd(&self) -> i32 {
+        self.inner.as_fd()
+    }
+}
+
+// The constant part of the list request headers
+const LIST_REQ_MIDDLE: &[u8] = concat!(
+    "Accept: application/json\r\n",
+    "User-Agent: ",
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    "\r\n",
+    "HTTP-Referer: https://github.com/grahamking/ort\r\n",
+    "X-Title: ort\r\n",
+    "Authorization: Bearer "
+)
+.as_bytes();
+
+pub fn list_models(
+    api_key: &str,
+    host: &str,
+    base_path: &str,
+    addrs: Vec<SocketAddr>,
+) -> OrtResult<TlsStream<TcpSocket>> {
+    let list_url = base_path.to_string() + "/models";
+    get(api_key, host, &list_url, addrs, None, None).context("list_models")
+}
+
+/// Fetch the stats for a finished request.
+/// `generation_id` is the `id` field of the streamed chunks.
+/// `deadline` as for `connect`. `conn` as for `chat_completions`.
+pub fn generation(
+    api_key: &str,
+    host: &str,
+    base_path: &str,
+    addrs: Vec<SocketAddr>,
+    generation_id: &str,
+    deadline: Option<u64>,
+    conn: Option<TlsStream<TcpSocket>>,
+) -> OrtResult<TlsStream<TcpSocket>> {
+    let generation_url = base_path.to_string() + "/generation?id=" + generation_id;
+    get(api_key, host, &generation_url, addrs, deadline, conn).context("generation")
+}
+
+/// What the key has bought and used, `{"data":{"total_credits":..,"total_usage":..}}`.
+/// `deadline` as for `connect`.
+pub fn credits(
+    api_key: &str,
+    host: &str,
+    base_path: &str,
+    addrs: Vec<SocketAddr>,
+    deadline: Option<u64>,
+) -> OrtResult<TlsStream<TcpSocket>> {
+    let credits_url = base_path.to_string() + "/credits";
+    get(api_key, host, &credits_url, addrs, deadline, None).context("credits")
+}
+
+/// Send a GET request for `url_path` with our standard JSON headers.
+fn get(
+    api_key: &str,
+    host: &str,
+    url_path: &str,
+    addrs: Vec<SocketAddr>,
+    deadline: Option<u64>,
+    conn: Option<TlsStream<TcpSocket>>,
+) -> OrtResult<TlsStream<TcpSocket>> {
+    let mut tls = match conn {
+        Some(tls) => tls,
+        None => TlsStream::connect(connect(addrs, deadline)?, host)?,
+    };
+    send_get(&mut tls, api_key, host, url_path)?;
+    Ok(tls)
+}
+
+/// Write the GET request for `url_path` on an open connection.
+pub(crate) fn send_get(
+    tls: &mut TlsStream<TcpSocket>,
+    api_key: &str,
+    host: &str,
+    url_path: &str,
+) -> OrtResult<()> {
+    // Built request on the stack, zero alloc
+    // List request is about 276 bytes right now, generation a little longer.
+    let mut req = [0u8; 512];
+    let req_len = GET.len()
+        + url_path.len()
+        + HTTP_1_1.len()
+        + HOST_HEADER.len()
+        + host.len()
+        + LIST_REQ_MIDDLE.len()
+        + api_key.len()
+        + CRLF.len() * 3;
+    if req_len > req.len() {
+        return Err(ort_error(ErrorKind::Other, "GET request too long"));
+    }
+
+    // GET <url_path> HTTP/1.1\r\n
+    let mut start = 0;
+    let mut end = GET.len();
+    req[start..end].copy_from_slice(GET);
+    start = end;
+    end += url_path.len();
+    req[start..end].copy_from_slice(url_path.as_bytes());
+    start = end;
+    end += HTTP_1_1.len();
+    req[start..end].copy_from_slice(HTTP_1_1);
+
+    // Host: <host>\r\n
+    start = end;
+    end += HOST_HEADER.len();
+    req[start..end].copy_from_slice(HOST_HEADER);
+    start = end;
+    end += host.len();
+    req[start..end].copy_from_slice(host.as_bytes());
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+
+    // Rest of the HTTP headers
+    start = end;
+    end += LIST_REQ_MIDDLE.len();
+    req[start..end].copy_from_slice(LIST_REQ_MIDDLE);
+
+    // The constant part finished with "Authorization: Bearer ".
+    // Append the API key and the final double CRLF.
+    start = end;
+    end += api_key.len();
+    req[start..end].copy_from_slice(api_key.as_bytes());
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+
+    tls.write_all(&req[..end]).context("write GET request")?;
+    tls.flush().context("flush GET request")?;
+    Ok(())
+}
+
+/// Read a whole response body into a String.
+/// For small JSON responses, `list` streams its larger body instead.
+pub fn read_body<R: Read>(
+    reader: buf_read::OrtBufReader<R>,
+    response_body: ResponseBody,
+) -> OrtResult<String> {
+    read_body_keep(reader, response_body).map(|(body, _)| body)
+}
+
+/// `read_body`, also giving back the reader if the body had a known end,
+/// so the connection can carry another request.
+pub fn read_body_keep<R: Read>(
+    reader: buf_read::OrtBufReader<R>,
+    response_body: ResponseBody,
+) -> OrtResult<(String, Option<buf_read::OrtBufReader<R>>)> {
+    const MAX_CHUNK_SIZE: usize = 128 * 1024;
+    let mut out = String::with_capacity(1024);
+    let rest = match response_body {
+        ResponseBody::Chunked => {
+            let mut chunked = crate::chunked::read::<_, MAX_CHUNK_SIZE>(reader);
+            while let Some(chunk) = chunked.next_chunk() {
+                out.push_str(chunk?);
+            }
+            chunked.into_inner()
+        }
+        ResponseBody::ContentLength(len) => {
+            let mut reader = reader;
+            read_content_length_body(&mut reader, len, &mut out)?;
+            Some(reader)
+        }
+        ResponseBody::UntilEof => {
+            let mut reader = reader;
+            let mut body = Vec::with_capacity(1024);
+            let mut buf = [0u8; 4096];
+            loop {
+                let bytes_read = reader.read(&mut buf).context("read body")?;
+                if bytes_read == 0 {
+                    break;
+                }
+                body.extend_from_slice(&buf[..bytes_read]);
+            }
+            let body_str = str::from_utf8(&body)
+                .map_err(|_| ort_error(ErrorKind::FormatError, "http body utf8"))?;
+            out.push_str(body_str);
+            None
+        }
+    };
+    Ok((out, rest))
+}
+
+const CHAT_REQ_MIDDLE: &[u8] = concat!(
+    "Content-Type: application/json\r\n",
+    // A server that won't stream may answer in one piece, `ActivePrompt` reads both
+    "Accept: text/event-stream, application/json;q=0.9\r\n",
+    "User-Agent: ",
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    "\r\n",
+    // ID for openrouter.ai App rankings
+    "HTTP-Referer: https://github.com/grahamking/ort\r\n",
+    // Name to appear in openrouter.ai App rankings
+    "X-Title: ort\r\n",
+    "Authorization: Bearer "
+)
+.as_bytes();
+
+/// Send the request. On `conn` if given, a keep-alive connection from an
+/// earlier request, otherwise on a new one. --dump-wire always uses a new one.
+/// `client_request_id` is ours, from `request_id`, sent as a header.
+#[allow(clippy::too_many_arguments)]
+pub fn chat_completions(
+    api_key: &str,
+    host: &str,
+    base_path: &str,
+    addrs: Vec<SocketAddr>,
+    json_body: &str,
+    is_gzip: bool,
+    deadline: Option<u64>,
+    conn: Option<TlsStream<TcpSocket>>,
+    client_request_id: &str,
+) -> OrtResult<buf_read::OrtBufReader<TlsStream<TcpSocket>>> {
+    let compressed;
+    let body = if is_gzip {
+        compressed = deflate::gzip(json_body.as_bytes());
+        &compressed[..]
+    } else {
+        json_body.as_bytes()
+    };
+
+    // Built HTTP request header on the stack.
+    // With gzip and an OpenRouter key the headers are about 460 bytes.
+    let mut req = [0u8; 640];
+    let chat_completions_url = base_path.to_string() + "/chat/completions";
+    let mut body_len_buf: [u8; 16] = [0; 16];
+    // Subtract two to strip the \n and \0 that to_ascii adds
+    let body_len_len = utils::to_ascii(body.len(), &mut body_len_buf[..]) - 2;
+    let req_len = POST.len()
+        + chat_completions_url.len()
+        + HTTP_1_1.len()
+        + HOST_HEADER.len()
+        + host.len()
+        + CLIENT_REQUEST_ID_HEADER.len()
+        + client_request_id.len()
+        + CONTENT_LENGTH_HEADER.len()
+        + body_len_len
+        + if is_gzip {
+            CONTENT_ENCODING_GZIP.len()
+        } else {
+            0
+        }
+        + CHAT_REQ_MIDDLE.len()
+        + api_key.len()
+        + CRLF.len() * 5;
+    if req_len > req.len() {
+        // Before connecting, it would only fail again on a retry
+        return Err(ort_error(
+            ErrorKind::HttpClientError,
+            "Request header too long, check base_url and the API key",
+        ));
+    }
+
+    let mut dump = WireDump::start()?;
+    let mut tls = match conn.filter(|_| dump.is_none()) {
+        Some(tls) => tls,
+        None => {
+            let tcp = connect(addrs, deadline)?;
+            if let Some(d) = dump.as_mut() {
+                d.event("connected");
+            }
+            let tls = TlsStream::connect(tcp, host)?;
+            if let Some(d) = dump.as_mut() {
+                d.event("tls handshake done");
+            }
+            tls
+        }
+    };
+
+    // POST <chat_completions_url> HTTP/1.1\r\n
+    let mut start = 0;
+    let mut end = POST.len();
+    req[start..end].copy_from_slice(POST);
+    start = end;
+    end += chat_completions_url.len();
+    req[start..end].copy_from_slice(chat_completions_url.as_bytes());
+    start = end;
+    end += HTTP_1_1.len();
+    req[start..end].copy_from_slice(HTTP_1_1);
+
+    // Host: <host>\r\n
+    start = end;
+    end += HOST_HEADER.len();
+    req[start..end].copy_from_slice(HOST_HEADER);
+    start = end;
+    end += host.len();
+    req[start..end].copy_from_slice(host.as_bytes());
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+
+    // X-Client-Request-Id: <ulid>\r\n
+    start = end;
+    end += CLIENT_REQUEST_ID_HEADER.len();
+    req[start..end].copy_from_slice(CLIENT_REQUEST_ID_HEADER);
+    start = end;
+    end += client_request_id.len();
+    req[start..end].copy_from_slice(client_request_id.as_bytes());
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+
+    // Content-Length: <body-len>\r\n
+    start = end;
+    end += CONTENT_LENGTH_HEADER.len();
+    req[start..end].copy_from_slice(CONTENT_LENGTH_HEADER);
+    start = end;
+    end += body_len_len;
+    req[start..end].copy_from_slice(&body_len_buf[..body_len_len]);
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+
+    if is_gzip {
+        start = end;
+        end += CONTENT_ENCODING_GZIP.len();
+        req[start..end].copy_from_slice(CONTENT_ENCODING_GZIP);
+    }
+
+    // Rest of the HTTP headers
+    start = end;
+    end += CHAT_REQ_MIDDLE.len();
+    req[start..end].copy_from_slice(CHAT_REQ_MIDDLE);
+
+    // The constant part finished with "Authorization: Bearer ".
+    // Append the API key and the final double CRLF.
+    start = end;
+    end += api_key.len();
+    req[start..end].copy_from_slice(api_key.as_bytes());
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+    start = end;
+    end += CRLF.len();
+    req[start..end].copy_from_slice(CRLF);
+    debug_assert_eq!(end, req_len);
+
+    //let end_str = utils::num_to_string(end);
+    //utils::print_string(c"REQ LEN ", &end_str);
+
+    tls.write_all(&req[..end])
+        .context("write chat_completions header")?;
+    tls.write_all(body).context("write chat_completions body")?;
+    tls.flush().context("flush chat_completions")?;
+    trace::mark("request_write");
+    if let Some(mut d) = dump {
+        // The JSON, not the gzip of it, so the dump stays readable
+        d.request(&req[..end], json_body.as_bytes(), api_key);
+        tls.wire_dump = Some(d);
+    }
+
+    Ok(buf_read::OrtBufReader::new(tls))
+}
+
+/// The status and headers of a response
+#[derive(Debug, Default)]
+pub struct ResponseHeader {
+    /// e.g. 200
+    pub status: u16,
+    /// e.g. "HTTP/1.1 200 OK"
+    pub status_line: String,
+    /// Every header, names lower case, in the order received
+    pub headers: Vec<(String, String)>,
+}
+
+impl ResponseHeader {
+    /// The first header with this name, any case
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the server will take another request on this connection
+    pub fn is_keep_alive(&self) -> bool {
+        self.status_line.starts_with("HTTP/1.1")
+            && !self.get("connection").is_some_and(|v| {
+                v.split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("close"))
+            })
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.get("content-type")
+    }
+
+    /// What the body holds, from the Content-Type's media type
+    pub fn body_kind(&self) -> BodyKind {
+        let Some(content_type) = self.content_type() else {
+            return BodyKind::EventStream;
+        };
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if media_type == "text/event-stream" {
+            BodyKind::EventStream
+        } else if media_type == "application/json" || media_type.ends_with("+json") {
+            BodyKind::Json
+        } else {
+            BodyKind::Other
+        }
+    }
+
+    /// OpenRouter's id for the request, quote it in bug reports
+    pub fn request_id(&self) -> Option<&str> {
+        self.get("x-request-id")
+    }
+
+    /// The Cloudflare data center that answered, e.g. "SJC" from a
+    /// `cf-ray: 8f1a2b3c4d5e6f70-SJC` header
+    pub fn cf_colo(&self) -> Option<&str> {
+        self.get("cf-ray")?.rsplit_once('-').map(|(_, colo)| colo)
+    }
+
+    /// The x-ratelimit-* headers, e.g. ("x-ratelimit-remaining", "19")
+    pub fn ratelimit(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n.starts_with("x-ratelimit-"))
+            .map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// How the body is framed
+    pub fn body(&self) -> ResponseBody {
+        let is_chunked = self
+            .get(TRANSFER_ENCODING_HEADER)
+            .is_some_and(|v| v.eq_ignore_ascii_case("chunked"));
+        if is_chunked {
+            ResponseBody::Chunked
+        } else if let Some(len) = self
+            .get(CONTENT_LENGTH_HEADER_NAME)
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            ResponseBody::ContentLength(len)
+        } else {
+            ResponseBody::UntilEof
+        }
+    }
+}
+
+/// What a response body holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyKind {
+    /// `text/event-stream`, or no Content-Type at all, as local servers and
+    /// captures without a header do
+    EventStream,
+    /// One JSON document: a non-streamed completion or an error
+    Json,
+    /// Anything else, e.g. the HTML page of a proxy or captive portal
+    Other,
+}
+
+#[derive(Debug)]
+pub struct HttpError {
+    status_line: String,
+    body: String,
+    request_id: Option<String>,
+    // Not a byte of the response arrived
+    is_no_response: bool,
+}
+
+impl HttpError {
+    pub(crate) fn as_string(&self) -> String {
+        let mut msg = String::with_capacity(64 + self.status_line.len() + self.body.len());
+        msg.push_str("\nHTTP ERROR: ");
+        msg.push_str(&self.status_line);
+        msg.push_str(", ");
+        msg.push_str(&self.body);
+        if let Some(id) = self.request_id.as_deref() {
+            msg.push_str("\nx-request-id: ");
+            msg.push_str(id);
+        }
+        msg.push('\0');
+        msg
+    }
+
+    pub(crate) fn status_line(&self) -> &str {
+        &self.status_line
+    }
+
+    /// e.g. 502 from "HTTP/1.1 502 Bad Gateway"
+    pub(crate) fn status_code(&self) -> Option<u16> {
+        self.status_line.split(' ').nth(1)?.parse().ok()
+    }
+
+    pub(crate) fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// The server closed without answering. On a reused keep-alive
+    /// connection that means it timed out while idle, so sending the request
+    /// again on a new connection is safe.
+    pub(crate) fn is_no_response(&self) -> bool {
+        self.is_no_response
+    }
+
+    fn new(header: &ResponseHeader, body: String) -> Self {
+        HttpError {
+            status_line: header.status_line.clone(),
+            body,
+            request_id: header.request_id().map(|s| s.to_string()),
+            is_no_response: false,
+        }
+    }
+
+    fn status(status_line: String) -> Self {
+        HttpError {
+            status_line,
+            body: "".to_string(),
+            request_id: None,
+            is_no_response: false,
+        }
+    }
+}
+
+impl From<HttpError> for OrtError {
+    fn from(err: HttpError) -> OrtError {
+        let c_s = unsafe { CString::from_vec_with_nul_unchecked(err.as_string().into_bytes()) };
+        syscall::write(2, c_s.as_ptr().cast(), c_s.count_bytes());
+        ort_error(ErrorKind::HttpStatusError, "")
+    }
+}
+
+/// Read the status line and headers, leaving the reader at the start of
+/// the body. Whatever the status. Interim responses (100 Continue, 103 Early
+/// Hints) are skipped, the final one follows them.
+pub fn parse_header<R: Read>(
+    reader: &mut buf_read::OrtBufReader<R>,
+) -> Result<ResponseHeader, HttpError> {
+    let mut line = Vec::with_capacity(512);
+    loop {
+        let header = parse_one_header(reader, &mut line)?;
+        // 101 Switching Protocols is final, the connection is something else now
+        if !(100..200).contains(&header.status) || header.status == 101 {
+            trace::mark("headers_parsed");
+            return Ok(header);
+        }
+    }
+}
+
+fn parse_one_header<R: Read>(
+    reader: &mut buf_read::OrtBufReader<R>,
+    line: &mut Vec<u8>,
+) -> Result<ResponseHeader, HttpError> {
+    let mut size = 0;
+    let mut next_line = |line: &mut Vec<u8>| -> Result<usize, HttpError> {
+        line.clear();
+        let n = reader.read_line_bytes(line).map_err(|err| {
+            HttpError::status("Reading response header: ".to_string() + &err.as_string())
+        })?;
+        size += n;
+        if size > MAX_HEADER_BYTES {
+            return Err(HttpError::status("Response header too large".to_string()));
+        }
+        Ok(n)
+    };
+
+    let no_response = |mut err: HttpError| {
+        err.is_no_response = true;
+        err
+    };
+    if next_line(line).map_err(no_response)? == 0 {
+        return Err(no_response(HttpError::status(
+            "Missing initial status line".to_string(),
+        )));
+    }
+    trace::mark("first_byte");
+    let status_line = String::from_utf8_lossy(line).trim().to_string();
+    let status = status_line
+        .split_ascii_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| HttpError::status("Invalid status line: ".to_string() + &status_line))?;
+    let mut header = ResponseHeader {
+        status,
+        status_line,
+        headers: Vec::with_capacity(16),
+    };
+
+    loop {
+        if next_line(line)? == 0 {
+            return Err(HttpError::status(
+                "Connection closed in the response header".to_string(),
+            ));
+        }
+        // Values are ASCII, anything else (obs-text) is kept as best we can
+        let text = String::from_utf8_lossy(line);
+        let is_continuation = text.starts_with([' ', '\t']);
+        let text = text.trim();
+        if text.is_empty() {
+            // end of headers
+            break;
+        }
+        if is_continuation {
+            // Obsolete line folding, more of the previous header's value
+            if let Some((_, value)) = header.headers.last_mut() {
+                value.push(' ');
+                value.push_str(text);
+            }
+        } else if let Some((name, value)) = text.split_once(':') {
+            header
+                .headers
+                .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    Ok(header)
+}
+
+/// Read the status line and headers, leaving the reader at the start of
+/// the body. Anything but a 200 is an error, with the body if there is one.
+pub fn read_header<R: Read>(
+    reader: &mut buf_read::OrtBufReader<R>,
+) -> Result<ResponseHeader, HttpError> {
+    let header = parse_header(reader)?;
+    if header.status == 200 {
+        return Ok(header);
+    }
+
+    // Usually the body explains the error so gather that.
+    let mut buffer = String::with_capacity(512);
+    match header.body() {
+        ResponseBody::ContentLength(0) => Err(HttpError::new(&header, String::new())),
+        ResponseBody::ContentLength(len) => {
+            match read_content_length_body(reader, len, &mut buffer) {
+                Ok(()) => Err(HttpError::new(&header, buffer.trim().to_string())),
+                Err(err) => Err(HttpError::status(
+                    "Reading response body: ".to_string() + &err.as_string(),
+                )),
+            }
+        }
+        body => {
+            if matches!(body, ResponseBody::Chunked) {
+                // Skip the size line, the header said transfer encoding chunked
+                // so even an HTTP 400 has to respect that.
+                let _ = reader.read_line(&mut buffer);
+                buffer.clear();
+            }
+            // TODO parse JSON. It looks like this:
+            // {"error":{"message":"openai/gpt-oss-90b is not a valid model ID","code":400},"user_id":"user_30mJ0GpP57Kj9wLQ4mDCfMS5nk0"}
+            match reader.read_line(&mut buffer) {
+                Ok(_) => Err(HttpError::new(&header, buffer.trim().to_string())),
+                _ => Err(HttpError::new(&header, String::new())),
+            }
+        }
+    }
+}
+
+fn read_content_length_body<R: Read>(
+    reader: &mut R,
+    len: usize,
+    buffer: &mut String,
+) -> OrtResult<()> {
+    // Grow as the bytes arrive, so a bad Content-Length can't make us allocate it
+    let mut body = Vec::with_capacity(len.min(16 * 1024));
+    let mut buf = [0u8; 4096];
+    while body.len() < len {
+        let want = buf.len().min(len - body.len());
+        let n = reader.read(&mut buf[..want])?;
+        if n == 0 {
+            return Err(ort_error(ErrorKind::UnexpectedEof, ""));
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+
+    let body_str =
+        str::from_utf8(&body).map_err(|_| ort_error(ErrorKind::FormatError, "http body utf8"))?;
+    buffer.push_str(body_str);
+    Ok(())
+}
+
+/// Extract host, port and path components from a URL.
+/// The port is optional and defaults to 443.
+/// "https://openrouter.ai:443/api/v1" => ("openrouter.ai", 443, "/api/v1")
+pub fn split_url(url: &str) -> (&str, u16, &str) {
+    let mut host_start = 0;
+    let mut host_end = url.len();
+
+    let mut port_start = 0;
+    let mut port_end = 0;
+
+    let mut path_start = 0;
+    let path_end = url.len();
+
+    if url.starts_with("https://") {
+        host_start += "https://".len();
+        path_start = host_start;
+    }
+    if let Some(port_s) = url[host_start..host_end].find(":") {
+        host_end = host_start + port_s;
+        port_start = host_start + port_s + 1;
+    }
+    if let Some(path_s) = url[path_start..path_end].find("/") {
+        if port_start == 0 {
+            // If there's no port, the host stops where the path starts
+            host_end = path_start + path_s;
+        }
+        port_end = path_start + path_s;
+        path_start += path_s;
+    }
+
+    let host = &url[host_start..host_end];
+    let port = utils::parse_u32(&url.as_bytes()[port_start..port_end]).unwrap_or(443);
+    let base_path = &url[path_start..path_end];
+    (host, port as u16, base_path)
+}
+
+/// Attempt to connect to all the SocketAddr in order.
+/// The addresses come from the system resolver or `${XDG_CONFIG_HOME}/ort.json`
+/// in settings/dns.
+/// With a `deadline` (in `syscall::monotonic_ms` time) connecting stops there,
+/// and reads on the socket, the TLS handshake included, time out there.
+pub(crate) fn connect(addrs: Vec<SocketAddr>, deadline: Option<u64>) -> OrtResult<TcpSocket> {
+    for addr in addrs {
+        let addr_v4 = match addr {
+            SocketAddr::V4(v4) => v4,
+            _ => continue,
+        };
+        let mut timeout_ms = SOCKET_CONNECT_TIMEOUT_MS;
+        if let Some(deadline) = deadline {
+            let left = deadline.saturating_sub(syscall::monotonic_ms());
+            if left == 0 {
+                return Err(ort_error(ErrorKind::DeadlineExceeded, "connecting"));
+            }
+            timeout_ms = timeout_ms.min(left.min(i32::MAX as u64) as i32);
+        }
+        let sock = TcpSocket::new()?;
+        if sock.connect(&addr_v4, timeout_ms).is_ok() {
+            if let Some(deadline) = deadline {
+                let left = deadline.saturating_sub(syscall::monotonic_ms());
+                socket::set_read_timeout(sock.as_fd(), left.max(1));
+            }
+            return Ok(sock);
+        }
+    }
+    if deadline.is_some_and(|d| syscall::monotonic_ms() >= d) {
+        return Err(ort_error(ErrorKind::DeadlineExceeded, "connecting"));
+    }
+    Err(ort_error(
+        ErrorKind::HttpConnectError,
+        "'connect' failed on all of the IP addresses",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    struct BytesReader {
+        data: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Read for BytesReader {
+        fn read(&mut self, buf: &mut [u8]) -> OrtResult<usize> {
+            let remaining = self.data.len() - self.pos;
+            let count = remaining.min(buf.len());
+
+            if count == 0 {
+                return Ok(0);
+            }
+
+            buf[..count].copy_from_slice(&self.data[self.pos..self.pos + count]);
+            self.pos += count;
+            Ok(count)
+        }
+    }
+
+    #[test]
+    pub fn test_split_url() {
+        let input = "openrouter.ai/api/v1";
+        let (host, port, base) = super::split_url(input);
+        assert_eq!(host, "openrouter.ai");
+        assert_eq!(port, 443);
+        assert_eq!(base, "/api/v1");
+
+        let input = "https://openrouter.ai/api/v1";
+        let (host, port, base) = super::split_url(input);
+        assert_eq!(host, "openrouter.ai");
+        assert_eq!(port, 443);
+        assert_eq!(base, "/api/v1");
+
+        let input = "https://openrouter.ai:8443/api/v1";
+        let (host, port, base) = super::split_url(input);
+        assert_eq!(host, "openrouter.ai");
+        assert_eq!(port, 8443);
+        assert_eq!(base, "/api/v1");
+    }
+
+    #[test]
+    pub fn content_length_reader_returns_eof_at_declared_len() {
+        let body = "First\nSecond";
+        let reader = BytesReader {
+            data: (body.to_string() + "EXTRA").into_bytes(),
+            pos: 0,
+        };
+        let limited = ContentLengthReader::new(reader, body.len());
+        let mut buffered = buf_read::OrtBufReader::new(limited);
+
+        let mut line = String::new();
+        assert_eq!(buffered.read_line(&mut line).unwrap(), "First\n".len());
+        assert_eq!(line, "First\n");
+
+        line.clear();
+        assert_eq!(buffered.read_line(&mut line).unwrap(), "Second".len());
+        assert_eq!(line, "Second");
+
+        line.clear();
+        assert_eq!(buffered.read_line(&mut line).unwrap(), 0);
+        assert!(line.is_empty());
+    }
+
+    #[test]
+    pub fn read_content_length_body_does_not_wait_for_newline() {
+        let body = r#"{"error":"bad request"}"#;
+        let mut reader = BytesReader {
+            data: (body.to_string() + "EXTRA").into_bytes(),
+            pos: 0,
+        };
+        let mut out = String::new();
+
+        read_content_length_body(&mut reader, body.len(), &mut out).unwrap();
+
+        assert_eq!(out, body);
+        assert_eq!(reader.pos, body.len());
+    }
+
+    #[test]
+    pub fn read_content_length_body_short() {
+        let mut reader = BytesReader {
+            data: b"{}".to_vec(),
+            pos: 0,
+        };
+        let mut out = String::new();
+        // Found by fuzz/, this used to allocate the whole length first
+        let err = read_content_length_body(&mut reader, usize::MAX / 2, &mut out).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    pub fn read_header_parses_headers() {
+        let response = "HTTP/1.1 200 OK\r\n\
+            Date: Sat, 17 Oct 2026 16:03:41 GMT\r\n\
+            Content-Type: text/event-stream\r\n\
+            Transfer-Encoding: chunked\r\n\
+            X-RateLimit-Limit: 20\r\n\
+            X-RateLimit-Remaining: 19\r\n\
+            X-Request-Id: req-123\r\n\
+            CF-RAY: 8f1a2b3c4d5e6f70-SJC\r\n\
+            \r\n\
+            body";
+        let mut reader = buf_read::OrtBufReader::new(BytesReader {
+            data: response.as_bytes().to_vec(),
+            pos: 0,
+        });
+        let header = read_header(&mut reader).unwrap();
+        assert_eq!(header.status, 200);
+        assert_eq!(header.content_type(), Some("text/event-stream"));
+        assert_eq!(header.request_id(), Some("req-123"));
+        assert_eq!(header.cf_colo(), Some("SJC"));
+        assert!(matches!(header.body(), ResponseBody::Chunked));
+        let limits: Vec<_> = header.ratelimit().collect();
+        assert_eq!(
+            limits,
+            [("x-ratelimit-limit", "20"), ("x-ratelimit-remaining", "19")]
+        );
+
+        let mut rest = String::new();
+        reader.read_line(&mut rest).unwrap();
+        assert_eq!(rest, "body");
+    }
+
+    #[test]
+    pub fn keep_alive_reads_the_next_response() {
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nHello\r\n0\r\nX-Trailer: yes\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+        let mut reader = buf_read::OrtBufReader::new(BytesReader {
+            data: response.as_bytes().to_vec(),
+            pos: 0,
+        });
+        let header = read_header(&mut reader).unwrap();
+        assert!(header.is_keep_alive());
+        let (body, rest) = read_body_keep(reader, header.body()).unwrap();
+        assert_eq!(body, "Hello");
+
+        let mut reader = rest.unwrap();
+        let header = read_header(&mut reader).unwrap();
+        assert!(!header.is_keep_alive());
+        let (body, _) = read_body_keep(reader, header.body()).unwrap();
+        assert_eq!(body, "{}");
+    }
+
+    #[test]
+    pub fn body_kind_from_content_type() {
+        let header = |content_type: Option<&str>| ResponseHeader {
+            status: 200,
+            status_line: "HTTP/1.1 200 OK".to_string(),
+            headers: content_type
+                .map(|ct| vec![("content-type".to_string(), ct.to_string())])
+                .unwrap_or_default(),
+        };
+        for (content_type, kind) in [
+            (None, BodyKind::EventStream),
+            (Some("text/event-stream"), BodyKind::EventStream),
+            (
+                Some("text/event-stream; charset=utf-8"),
+                BodyKind::EventStream,
+            ),
+            (Some("Application/JSON; charset=utf-8"), BodyKind::Json),
+            (Some("application/problem+json"), BodyKind::Json),
+            (Some("text/html"), BodyKind::Other),
+        ] {
+            assert_eq!(header(content_type).body_kind(), kind, "{content_type:?}");
+        }
+    }
+
+    #[test]
+    pub fn read_header_error_keeps_request_id() {
+        let body = r#"{"error":{"message":"No auth","code":401}}"#;
+        let response = "HTTP/1.1 401 Unauthorized\r\nContent-Length: ".to_string()
+            + &utils::num_to_string(body.len())
+            + "\r\nX-Request-Id: req-456\r\n\r\n"
+            + body;
+        let mut reader = buf_read::OrtBufReader::new(BytesReader {
+            data: response.into_bytes(),
+            pos: 0,
+        });
+        let err = read_header(&mut reader).unwrap_err();
+        assert_eq!(err.status_line(), "HTTP/1.1 401 Unauthorized");
+        assert_eq!(err.body(), body);
+        assert!(err.as_string().contains("x-request-id: req-456"));
+    }
+
+    /// Response headers as servers send them, with the (status, header count) they parse to
+    const CORPUS: [(&str, u16, usize); 5] = [
+        (
+            "HTTP/1.1 200 OK\r\nDate: Sat, 17 Oct 2026 16:03:41 GMT\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\nConnection: keep-alive\r\nAccess-Control-Allow-Origin: *\r\nCache-Control: no-cache\r\nX-Request-Id: req-123\r\nServer: cloudflare\r\nCF-RAY: 9a1b2c3d4e5f6a7b-LHR\r\n\r\n",
+            200,
+            9,
+        ),
+        (
+            "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 401 Unauthorized\r\nContent-Type: application/json\r\nContent-Length: 42\r\n\r\n",
+            401,
+            2,
+        ),
+        (
+            "HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload; as=style\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            200,
+            1,
+        ),
+        (
+            "HTTP/1.1   200   OK  \r\nX-Folded: first\r\n  second\r\n\tthird\r\nX-Spaces:    padded   \r\n\r\n",
+            200,
+            2,
+        ),
+        // Bare \n line endings, and a header that is not UTF-8
+        (
+            "HTTP/1.1 429 Too Many Requests\nRetry-After: 7\nX-Name: caf\u{e9}\n\n",
+            429,
+            2,
+        ),
+    ];
+
+    /// Hands out a few bytes at a time, like TLS records cut anywhere
+    struct SplitReader {
+        data: Vec<u8>,
+        pos: usize,
+        seed: u32,
+        max: usize,
+    }
+
+    impl Read for SplitReader {
+        fn read(&mut self, buf: &mut [u8]) -> OrtResult<usize> {
+            self.seed = self.seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let want = 1 + (self.seed >> 16) as usize % self.max;
+            let count = want.min(buf.len()).min(self.data.len() - self.pos);
+            buf[..count].copy_from_slice(&self.data[self.pos..self.pos + count]);
+            self.pos += count;
+            Ok(count)
+        }
+    }
+
+    fn corpus_bytes(text: &str) -> Vec<u8> {
+        // Latin-1, so \u{e9} above is the one byte 0xe9, not its UTF-8
+        text.chars().map(|c| c as u32 as u8).collect()
+    }
+
+    #[test]
+    pub fn parse_header_corpus_any_split() {
+        for (text, status, count) in CORPUS {
+            for max in [1, 2, 3, 7, 16, 64, 1000] {
+                for seed in 0..20 {
+                    let mut data = corpus_bytes(text);
+                    data.extend_from_slice(b"BODY");
+                    let mut reader = buf_read::OrtBufReader::new(SplitReader {
+                        data,
+                        pos: 0,
+                        seed,
+                        max,
+                    });
+                    let header = parse_header(&mut reader).unwrap();
+                    assert_eq!(header.status, status, "{text}");
+                    assert_eq!(header.headers.len(), count, "{text}");
+                    let mut rest = [0u8; 4];
+                    reader.read_exact(&mut rest).unwrap();
+                    assert_eq!(&rest, b"BODY");
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn parse_header_edge_cases() {
+        let parse = |text: &str| {
+            let mut reader = buf_read::OrtBufReader::new(BytesReader {
+                data: corpus_bytes(text),
+                pos: 0,
+            });
+            parse_header(&mut reader)
+        };
+        let header = parse(CORPUS[3].0).unwrap();
+        assert_eq!(header.status_line, "HTTP/1.1   200   OK");
+        assert_eq!(header.get("x-folded"), Some("first second third"));
+        assert_eq!(header.get("x-spaces"), Some("padded"));
+        assert_eq!(
+            parse(CORPUS[4].0).unwrap().get("x-name"),
+            Some("caf\u{fffd}")
+        );
+
+        let err = parse("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n").unwrap_err();
+        assert_eq!(
+            err.status_line(),
+            "Connection closed in the response header"
+        );
+
+        let huge = "HTTP/1.1 200 OK\r\n".to_string()
+            + &"X-Padding: 0123456789abcdef\r\n".repeat(MAX_HEADER_BYTES / 16);
+        let err = parse(&huge).unwrap_err();
+        assert_eq!(err.status_line(), "Response header too large");
+    }
+}
@@ -0,0 +1,139 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! A stand-in for the OpenRouter API, for the end to end tests in tests/mock.rs.
+//! It speaks TLS with our own server handshake and sends back whatever the test
+//! scripted, so DNS override, TLS, HTTP, SSE parsing and the writers all run
+//! for real without the network.
+
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::common::buf_read::OrtBufReader;
+use crate::input::serve::{hex, read_request};
+use crate::transport::AsFd;
+use crate::transport::socket::{self, TcpListener};
+use crate::{Context, ErrorKind, OrtResult, TlsStream, Write, ort_error, utils};
+
+/// How long a keep-alive connection waits for the next request
+const KEEP_ALIVE_TIMEOUT_MS: u64 = 5000;
+
+pub struct MockServer {
+    listener: TcpListener,
+    port: u16,
+}
+
+/// What the client sent
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+impl MockServer {
+    /// Listen on a free port on 127.0.0.1
+    pub fn bind() -> OrtResult<Self> {
+        let listener = TcpListener::bind(&SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0))?;
+        let port = listener
+            .local_addr()
+            .ok_or_else(|| ort_error(ErrorKind::SocketBindFailed, "getsockname"))?
+            .port();
+        Ok(MockServer { listener, port })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Take one connection, read its request, send `response` exactly as
+    /// given and close.
+    pub fn serve(&self, response: &[u8]) -> OrtResult<MockRequest> {
+        let conn = self.listener.accept()?;
+        let tls = TlsStream::accept(conn).context("mock server handshake")?;
+        let mut reader = OrtBufReader::new(tls);
+        let req = read_request(&mut reader)?;
+        let tls = reader.get_mut();
+        tls.write_all(response).context("mock server response")?;
+        tls.shutdown()?;
+        Ok(MockRequest {
+            method: req.method,
+            path: req.path,
+            body: req.body,
+        })
+    }
+
+    /// Take one connection and answer each of `responses` in turn on it,
+    /// as a keep-alive server does. A request that doesn't come, because
+    /// the client opened another connection, is an error.
+    pub fn serve_keep_alive(&self, responses: &[Vec<u8>]) -> OrtResult<Vec<MockRequest>> {
+        let conn = self.listener.accept()?;
+        socket::set_read_timeout(conn.as_fd(), KEEP_ALIVE_TIMEOUT_MS);
+        let tls = TlsStream::accept(conn).context("mock server handshake")?;
+        let mut reader = OrtBufReader::new(tls);
+        let mut requests = Vec::with_capacity(responses.len());
+        for response in responses {
+            let req = read_request(&mut reader).context("mock server keep-alive")?;
+            let tls = reader.get_mut();
+            tls.write_all(response).context("mock server response")?;
+            tls.flush()?;
+            requests.push(MockRequest {
+                method: req.method,
+                path: req.path,
+                body: req.body,
+            });
+        }
+        reader.get_mut().shutdown()?;
+        Ok(requests)
+    }
+}
+
+/// A streaming 200 response, one HTTP chunk per entry of `chunks`.
+/// Chunks don't have to line up with SSE events.
+pub fn sse_response(chunks: &[&str]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(256);
+    out.extend_from_slice(b"HTTP/1.1 200 OK\r\nConnection: close\r\n");
+    sse_body(chunks, &mut out);
+    out
+}
+
+/// `sse_response` leaving the connection open for another request
+pub fn sse_response_keep_alive(chunks: &[&str]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(256);
+    out.extend_from_slice(b"HTTP/1.1 200 OK\r\n");
+    sse_body(chunks, &mut out);
+    out
+}
+
+/// A 200 response with a JSON body, keep-alive
+pub fn json_response(body: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(128 + body.len());
+    out.extend_from_slice(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n");
+    out.extend_from_slice(b"Content-Length: ");
+    out.extend_from_slice(utils::num_to_string(body.len()).as_bytes());
+    out.extend_from_slice(b"\r\n\r\n");
+    out.extend_from_slice(body.as_bytes());
+    out
+}
+
+fn sse_body(chunks: &[&str], out: &mut Vec<u8>) {
+    out.extend_from_slice(
+        concat!(
+            "Content-Type: text/event-stream\r\n",
+            "Transfer-Encoding: chunked\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    for chunk in chunks {
+        out.extend_from_slice(hex(chunk.len()).as_bytes());
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(chunk.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(b"0\r\n\r\n");
+}
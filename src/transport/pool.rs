@@ -0,0 +1,96 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Idle HTTP/1.1 keep-alive connections. A follow-up request in the same run
+//! (the generation stats, a failover, the next agent turn) takes one instead
+//! of paying for the TCP connect and the TLS handshake again.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::transport::AsFd;
+use crate::transport::socket;
+use crate::{TcpSocket, TlsStream, syscall, utils};
+
+/// Most idle connections we keep, the rest are closed
+const MAX_IDLE: usize = 4;
+
+type Conn = TlsStream<TcpSocket>;
+
+struct Pool {
+    is_locked: AtomicBool,
+    // (host:port, connection)
+    idle: UnsafeCell<Vec<(String, Conn)>>,
+}
+
+// Only touched with `is_locked` held
+unsafe impl Sync for Pool {}
+
+static POOL: Pool = Pool {
+    is_locked: AtomicBool::new(false),
+    idle: UnsafeCell::new(Vec::new()),
+};
+
+fn with_idle<F: FnOnce(&mut Vec<(String, Conn)>) -> R, R>(f: F) -> R {
+    while POOL
+        .is_locked
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    let out = f(unsafe { &mut *POOL.idle.get() });
+    POOL.is_locked.store(false, Ordering::Release);
+    out
+}
+
+fn key(host: &str, port: u16) -> String {
+    String::from(host) + ":" + &utils::num_to_string(port)
+}
+
+/// Keep `conn` for the next request to `host`. Only call this once the
+/// response was read to its end, and the server didn't say
+/// `Connection: close`.
+pub fn put(host: &str, port: u16, conn: Conn) {
+    if !conn.is_reusable() {
+        return;
+    }
+    // No read timeout, whoever takes it sets their own
+    socket::set_read_timeout(conn.as_fd(), 0);
+    let key = key(host, port);
+    with_idle(move |idle| {
+        if idle.len() < MAX_IDLE {
+            idle.push((key, conn));
+        }
+    });
+}
+
+/// An idle connection to `host`, if there is one the server hasn't closed.
+/// With a `deadline`, in `syscall::monotonic_ms` time, reads time out there
+/// as they do on a new connection.
+pub fn take(host: &str, port: u16, deadline: Option<u64>) -> Option<Conn> {
+    let key = key(host, port);
+    loop {
+        let conn = with_idle(|idle| {
+            let pos = idle.iter().rposition(|(k, _)| *k == key)?;
+            Some(idle.swap_remove(pos).1)
+        })?;
+        // Anything to read on an idle connection means the server hung up,
+        // or sent close_notify which is the same thing
+        if syscall::poll_read(conn.as_fd(), 0) != 0 {
+            continue;
+        }
+        if let Some(deadline) = deadline {
+            let left = deadline.saturating_sub(syscall::monotonic_ms());
+            socket::set_read_timeout(conn.as_fd(), left.max(1));
+        }
+        return Some(conn);
+    }
+}
@@ -0,0 +1,359 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2025 Graham King
+
+use core::ffi::{c_int, c_void};
+use core::mem::size_of;
+use core::net::{Ipv4Addr, SocketAddrV4};
+
+use crate::transport::trace;
+use crate::{ErrorKind, OrtResult, Read, Write, ort_error, syscall, utils};
+
+pub struct TcpSocket {
+    fd: i32,
+}
+
+impl TcpSocket {
+    pub fn new() -> OrtResult<Self> {
+        let fd = syscall::socket(
+            syscall::AF_INET,
+            syscall::SOCK_STREAM | syscall::SOCK_CLOEXEC,
+            0,
+        );
+        if fd == -1 {
+            return Err(ort_error(ErrorKind::SocketCreateFailed, ""));
+        }
+        set_tcp_fastopen(fd);
+        Ok(TcpSocket { fd })
+    }
+
+    pub fn connect(&self, addr: &SocketAddrV4, timeout_ms: c_int) -> OrtResult<()> {
+        let c_addr = socket_addr_v4_to_c(addr);
+        let len = size_of::<syscall::sockaddr_in>() as syscall::socklen_t;
+
+        // Get the current flags
+        let flags = syscall::fcntl(self.fd, syscall::F_GETFL, 0);
+        if flags < 0 {
+            return Err(ort_error(ErrorKind::SocketConnectFailed, ""));
+        }
+        // Set socket to non blocking by adding to current flags
+        syscall::fcntl(self.fd, syscall::F_SETFL, flags | syscall::O_NONBLOCK);
+
+        let res = syscall::connect(
+            self.fd,
+            &c_addr as *const _ as *const syscall::sockaddr,
+            len,
+        );
+        if res == syscall::ENETUNREACH {
+            syscall::fcntl(self.fd, syscall::F_SETFL, flags);
+            return Err(ort_error(ErrorKind::NoNetwork, "network unreachable"));
+        }
+        // connect failed before we even started, not sure when this can happen
+        if res < 0 && res != syscall::EINPROGRESS {
+            syscall::fcntl(self.fd, syscall::F_SETFL, flags);
+            return Err(ort_error(
+                ErrorKind::SocketConnectFailed,
+                "non-blocking connect failed",
+            ));
+        }
+        // Normal case, we are EINPROGRESS
+        if res < 0 {
+            let mut err: c_int = 0;
+            let mut err_len = size_of::<c_int>() as syscall::socklen_t;
+            // poll syscall has built-in timeout
+            // getsockopt after poll succeeds to check for socket error
+            if syscall::poll_write(self.fd, timeout_ms) <= 0
+                || syscall::getsockopt(
+                    self.fd,
+                    syscall::SOL_SOCKET,
+                    syscall::SO_ERROR,
+                    &mut err as *mut _ as *mut c_void,
+                    &mut err_len,
+                ) < 0
+                || err != 0
+            {
+                syscall::fcntl(self.fd, syscall::F_SETFL, flags);
+                if err == -syscall::ENETUNREACH {
+                    return Err(ort_error(ErrorKind::NoNetwork, "network unreachable"));
+                }
+                return Err(ort_error(ErrorKind::SocketConnectFailed, "timed out"));
+            }
+        }
+        // Success. Set socket back to blocking mode by restoring flags
+        syscall::fcntl(self.fd, syscall::F_SETFL, flags);
+        trace::mark("tcp_connect");
+        Ok(())
+    }
+}
+
+impl Drop for TcpSocket {
+    fn drop(&mut self) {
+        let _ = syscall::close(self.fd);
+    }
+}
+
+/// Connected UDP, to send statsd metrics.
+pub struct UdpSocket {
+    fd: i32,
+}
+
+impl UdpSocket {
+    /// UDP has no handshake, this only sets where `send` goes
+    pub fn connect(addr: &SocketAddrV4) -> OrtResult<Self> {
+        let fd = syscall::socket(
+            syscall::AF_INET,
+            syscall::SOCK_DGRAM | syscall::SOCK_CLOEXEC,
+            0,
+        );
+        if fd < 0 {
+            return Err(ort_error(ErrorKind::SocketCreateFailed, ""));
+        }
+        let sock = UdpSocket { fd };
+        let c_addr = socket_addr_v4_to_c(addr);
+        let len = size_of::<syscall::sockaddr_in>() as syscall::socklen_t;
+        if syscall::connect(fd, &c_addr as *const _ as *const syscall::sockaddr, len) < 0 {
+            return Err(ort_error(ErrorKind::SocketConnectFailed, "udp"));
+        }
+        Ok(sock)
+    }
+
+    /// One datagram
+    pub fn send(&self, buf: &[u8]) -> OrtResult<()> {
+        let sent = syscall::send(
+            self.fd,
+            buf.as_ptr() as *const c_void,
+            buf.len(),
+            syscall::MSG_NOSIGNAL,
+        );
+        if sent < 0 {
+            return Err(ort_error(ErrorKind::SocketWriteFailed, "udp send"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        let _ = syscall::close(self.fd);
+    }
+}
+
+/// A listening socket, for `ort serve-openai`.
+pub struct TcpListener {
+    fd: i32,
+}
+
+impl TcpListener {
+    pub fn bind(addr: &SocketAddrV4) -> OrtResult<Self> {
+        let fd = syscall::socket(
+            syscall::AF_INET,
+            syscall::SOCK_STREAM | syscall::SOCK_CLOEXEC,
+            0,
+        );
+        if fd < 0 {
+            return Err(ort_error(ErrorKind::SocketCreateFailed, ""));
+        }
+        let listener = TcpListener { fd };
+
+        // Allow restarting straight away while old connections are in TIME_WAIT
+        let optval: c_int = 1;
+        syscall::setsockopt(
+            fd,
+            syscall::SOL_SOCKET,
+            syscall::SO_REUSEADDR,
+            &optval as *const _ as *const c_void,
+            size_of::<c_int>() as u32,
+        );
+
+        let c_addr = socket_addr_v4_to_c(addr);
+        let len = size_of::<syscall::sockaddr_in>() as syscall::socklen_t;
+        if syscall::bind(fd, &c_addr as *const _ as *const syscall::sockaddr, len) < 0 {
+            return Err(ort_error(ErrorKind::SocketBindFailed, "bind"));
+        }
+        if syscall::listen(fd, 16) < 0 {
+            return Err(ort_error(ErrorKind::SocketBindFailed, "listen"));
+        }
+        Ok(listener)
+    }
+
+    /// Block until a client connects
+    pub fn accept(&self) -> OrtResult<TcpSocket> {
+        let fd = syscall::accept4(self.fd, syscall::SOCK_CLOEXEC);
+        if fd < 0 {
+            return Err(ort_error(ErrorKind::SocketAcceptFailed, ""));
+        }
+        Ok(TcpSocket { fd })
+    }
+
+    /// The address we are listening on, for finding the port after binding to 0
+    #[cfg(any(test, feature = "mock-server"))]
+    pub fn local_addr(&self) -> Option<SocketAddrV4> {
+        sockaddr_v4(self.fd, syscall::getsockname)
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        let _ = syscall::close(self.fd);
+    }
+}
+
+impl super::AsFd for TcpSocket {
+    fn as_fd(&self) -> i32 {
+        self.fd
+    }
+}
+
+impl Read for TcpSocket {
+    fn read(&mut self, buf: &mut [u8]) -> OrtResult<usize> {
+        let bytes_read = syscall::read(self.fd, buf.as_mut_ptr() as *mut c_void, buf.len());
+        if bytes_read < 0 {
+            if bytes_read == syscall::EAGAIN {
+                return Err(ort_error(ErrorKind::WouldBlock, ""));
+            }
+            // see /usr/include/asm-generic/errno.h to translate the codes
+            let err_code = utils::num_to_string(-bytes_read);
+            utils::print_string(c"socket read err: ", &err_code);
+            Err(ort_error(ErrorKind::SocketReadFailed, "syscall read error"))
+        } else {
+            Ok(bytes_read as usize)
+        }
+    }
+}
+
+impl Write for TcpSocket {
+    fn write(&mut self, buf: &[u8]) -> OrtResult<usize> {
+        let bytes_written = syscall::send(
+            self.fd,
+            buf.as_ptr() as *const c_void,
+            buf.len(),
+            syscall::MSG_NOSIGNAL,
+        );
+        if bytes_written == syscall::EPIPE {
+            // The server closed the connection. Expected when our close_notify
+            // goes to a server that already hung up, the caller decides.
+            Err(ort_error(ErrorKind::SocketWriteFailed, "connection closed"))
+        } else if bytes_written < 0 {
+            // see /usr/include/asm-generic/errno.h to translate the codes
+            let err_code = utils::num_to_string(-bytes_written);
+            utils::print_string(c"socket write err: ", &err_code);
+            Err(ort_error(
+                ErrorKind::SocketWriteFailed,
+                "syscall write error",
+            ))
+        } else {
+            Ok(bytes_written as usize)
+        }
+    }
+
+    fn flush(&mut self) -> OrtResult<()> {
+        Ok(())
+    }
+}
+
+/// Make blocking reads on `fd` give up with WouldBlock after `timeout_ms`.
+/// Zero means wait forever.
+pub fn set_read_timeout(fd: i32, timeout_ms: u64) {
+    let tv = syscall::timeval {
+        tv_sec: (timeout_ms / 1000) as i64,
+        tv_usec: ((timeout_ms % 1000) * 1000) as i64,
+    };
+    syscall::setsockopt(
+        fd,
+        syscall::SOL_SOCKET,
+        syscall::SO_RCVTIMEO,
+        &tv as *const _ as *const c_void,
+        size_of::<syscall::timeval>() as u32,
+    );
+}
+
+/// The address `fd` is connected to. None if it isn't a connected IPv4 socket.
+pub fn peer_addr(fd: i32) -> Option<SocketAddrV4> {
+    sockaddr_v4(fd, syscall::getpeername)
+}
+
+/// Ask `getname` (getpeername or getsockname) for an IPv4 address of `fd`
+fn sockaddr_v4(
+    fd: i32,
+    getname: fn(c_int, *mut syscall::sockaddr, *mut syscall::socklen_t) -> c_int,
+) -> Option<SocketAddrV4> {
+    let mut c_addr: syscall::sockaddr_in = unsafe { core::mem::zeroed() };
+    let mut len = size_of::<syscall::sockaddr_in>() as syscall::socklen_t;
+    let res = getname(
+        fd,
+        &mut c_addr as *mut _ as *mut syscall::sockaddr,
+        &mut len,
+    );
+    if res < 0 || c_addr.sin_family != syscall::AF_INET as syscall::sa_family_t {
+        return None;
+    }
+    Some(SocketAddrV4::new(
+        Ipv4Addr::from(c_addr.sin_addr.s_addr.to_ne_bytes()),
+        u16::from_be(c_addr.sin_port),
+    ))
+}
+
+/// Must be called before 'connect'.
+fn set_tcp_fastopen(fd: i32) {
+    let optval: c_int = 1; // Enable
+    syscall::setsockopt(
+        fd,
+        syscall::IPPROTO_TCP,
+        syscall::TCP_FASTOPEN_CONNECT,
+        &optval as *const _ as *const core::ffi::c_void,
+        size_of::<i32>() as u32,
+    );
+}
+
+fn socket_addr_v4_to_c(addr: &SocketAddrV4) -> syscall::sockaddr_in {
+    syscall::sockaddr_in {
+        sin_family: syscall::AF_INET as syscall::sa_family_t,
+        sin_port: addr.port().to_be(),
+        sin_addr: ip_v4_addr_to_c(addr.ip()),
+        ..unsafe { core::mem::zeroed() }
+    }
+}
+fn ip_v4_addr_to_c(addr: &Ipv4Addr) -> syscall::in_addr {
+    // `s_addr` is stored as BE on all machines and the array is in BE order.
+    // So the native endian conversion method is used so that it's never swapped.
+    syscall::in_addr {
+        s_addr: u32::from_ne_bytes(addr.octets()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_timeout_gives_would_block() {
+        let listener = TcpListener::bind(&SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = TcpSocket::new().unwrap();
+        client.connect(&addr, 1000).unwrap();
+        set_read_timeout(client.fd, 20);
+        let mut buf = [0u8; 8];
+        let err = client.read(&mut buf).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::WouldBlock));
+    }
+
+    #[test]
+    fn peer_addr_of_connected() {
+        let listener = TcpListener::bind(&SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpSocket::new().unwrap();
+        assert_eq!(peer_addr(client.fd), None);
+        client.connect(&addr, 1000).unwrap();
+        assert_eq!(peer_addr(client.fd), Some(addr));
+    }
+
+    #[test]
+    fn local_addr_of_port_zero() {
+        let listener = TcpListener::bind(&SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_eq!(*addr.ip(), Ipv4Addr::LOCALHOST);
+        assert_ne!(addr.port(), 0);
+    }
+}
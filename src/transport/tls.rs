@@ -15,16 +15,23 @@ use alloc::vec;
 use alloc::vec::Vec;
 
 use crate::{
-    Context, ErrorKind, OrtResult, Read, Write, common::utils::to_ascii, net::AsFd, ort_error,
-    syscall,
+    Context, ErrorKind, OrtResult, Read, Write, common::utils::to_ascii, ort_error, syscall,
+    transport::AsFd, transport::trace, transport::wire_dump::WireDump,
 };
 
 mod aead;
 mod ecdh;
 mod hkdf;
 mod hmac;
+#[cfg(feature = "sslkeylog")]
+mod keylog;
+#[cfg(feature = "mock-server")]
+mod server;
 mod sha2;
 
+#[cfg(feature = "sslkeylog")]
+pub use keylog::set_key_log_file;
+
 #[allow(unused)]
 const DEBUG_LOG: bool = false;
 
@@ -37,6 +44,10 @@ const REC_TYPE_HANDSHAKE: u8 = 22; // 0x16
 const REC_TYPE_APPDATA: u8 = 23; // 0x17
 const LEGACY_REC_VER: u16 = 0x0303;
 
+const ALERT_LEVEL_WARNING: u8 = 1;
+const ALERT_CLOSE_NOTIFY: u8 = 0;
+const ALERT_USER_CANCELED: u8 = 90;
+
 const HS_CLIENT_HELLO: u8 = 1;
 const HS_SERVER_HELLO: u8 = 2;
 //const HS_NEW_SESSION_TICKET: u8 = 4;
@@ -124,6 +135,12 @@ pub struct TlsStream<T: Read + Write> {
     // read buffer for decrypted application data
     rbuf: Vec<u8>,
     rpos: usize,
+    // --dump-wire records everything we read
+    pub(crate) wire_dump: Option<WireDump>,
+    // The server sent close_notify, reads are EOF from now on
+    is_read_closed: bool,
+    // We sent close_notify
+    is_write_closed: bool,
 }
 
 fn client_hello_body(sni_host: &str, client_pub: &[u8]) -> Vec<u8> {
@@ -329,6 +346,20 @@ impl<T: Read + Write> TlsStream<T> {
         let sh_body = Self::receive_server_hello(&mut io, &mut transcript)?;
 
         let handshake = Self::derive_handshake_keys(&client_private_key, &sh_body, &transcript)?;
+        #[cfg(feature = "sslkeylog")]
+        {
+            let client_random = keylog::client_random(&transcript);
+            keylog::log(
+                "CLIENT_HANDSHAKE_TRAFFIC_SECRET",
+                client_random,
+                &handshake.client_hs_ts,
+            );
+            keylog::log(
+                "SERVER_HANDSHAKE_TRAFFIC_SECRET",
+                client_random,
+                &handshake.server_hs_ts,
+            );
+        }
 
         let mut first_encrypted_record = {
             debug_print("MSG <- ChangeCipherSpec (dummy, optional)", &[]);
@@ -371,6 +402,7 @@ impl<T: Read + Write> TlsStream<T> {
         Self::send_client_finished(&mut io, &handshake, &mut transcript, &mut seq_enc_hs)?;
 
         debug_print("TLS connect done", &[]);
+        trace::mark("tls_handshake");
         Ok(TlsStream {
             io,
             aead_enc: aead_app_enc,
@@ -381,6 +413,9 @@ impl<T: Read + Write> TlsStream<T> {
             seq_dec: seq_app_dec,
             rbuf: Vec::with_capacity(16 * 1024),
             rpos: 0,
+            wire_dump: None,
+            is_read_closed: false,
+            is_write_closed: false,
         })
     }
 
@@ -511,6 +546,12 @@ impl<T: Read + Write> TlsStream<T> {
         let hs_shared_secret = ecdh::x25519_agreement(client_private_key, &server_public_key_bytes);
         debug_print("hs shared secret", &hs_shared_secret);
 
+        Ok(Self::handshake_keys(&hs_shared_secret, transcript))
+    }
+
+    /// The handshake key schedule, from the ECDH secret and the ClientHello +
+    /// ServerHello transcript. Keys are named from the client's side.
+    fn handshake_keys(hs_shared_secret: &[u8], transcript: &[u8]) -> HandshakeState {
         // Same as: `echo -n "" | openssl sha256`
         let empty_hash = digest_bytes(&[]);
         debug_print("empty_hash", &empty_hash);
@@ -521,7 +562,7 @@ impl<T: Read + Write> TlsStream<T> {
         let derived_secret_bytes = hkdf_expand_label::<32>(&early_secret, "derived", &empty_hash);
         debug_print("derived", &derived_secret_bytes);
 
-        let handshake_secret = hkdf::hkdf_extract(&derived_secret_bytes, &hs_shared_secret);
+        let handshake_secret = hkdf::hkdf_extract(&derived_secret_bytes, hs_shared_secret);
         debug_print("handshake_secret", &handshake_secret);
 
         let ch_sh_hash = digest_bytes(transcript);
@@ -556,7 +597,7 @@ impl<T: Read + Write> TlsStream<T> {
             .unwrap();
         debug_print("server_handshake_iv", &server_handshake_iv);
 
-        Ok(HandshakeState {
+        HandshakeState {
             handshake_secret,
             client_hs_ts: c_hs_ts,
             server_hs_ts: s_hs_ts,
@@ -565,7 +606,7 @@ impl<T: Read + Write> TlsStream<T> {
             aead_enc_hs: client_handshake_key,
             aead_dec_hs: server_handshake_key,
             empty_hash,
-        })
+        }
     }
 
     fn derive_application_keys(
@@ -584,6 +625,12 @@ impl<T: Read + Write> TlsStream<T> {
         let s_ap_ts = hkdf_expand_label::<32>(&master_secret, "s ap traffic", &thash_srv_fin);
         debug_print("c_ap_ts", &c_ap_ts);
         debug_print("s_ap_ts", &s_ap_ts);
+        #[cfg(feature = "sslkeylog")]
+        {
+            let client_random = keylog::client_random(transcript);
+            keylog::log("CLIENT_TRAFFIC_SECRET_0", client_random, &c_ap_ts);
+            keylog::log("SERVER_TRAFFIC_SECRET_0", client_random, &s_ap_ts);
+        }
 
         let cak: [u8; 16] = hkdf_expand_label::<16>(&c_ap_ts, "key", &[]).as_slice()[..16]
             .try_into()
@@ -667,8 +714,54 @@ impl<T: Read + Write> Write for TlsStream<T> {
     }
 }
 
+impl<T: Read + Write> TlsStream<T> {
+    /// Open both ways with nothing left unread, so it can carry another
+    /// request. Not while --dump-wire is recording it.
+    pub(crate) fn is_reusable(&self) -> bool {
+        !self.is_read_closed
+            && !self.is_write_closed
+            && self.rbuf.is_empty()
+            && self.wire_dump.is_none()
+    }
+
+    /// Tell the server we are done with a close_notify alert, so it knows
+    /// we didn't just lose the connection. Only sent once. Drop calls this.
+    pub fn shutdown(&mut self) -> OrtResult<()> {
+        if self.is_write_closed {
+            return Ok(());
+        }
+        self.is_write_closed = true;
+        write_record_cipher(
+            &mut self.io,
+            REC_TYPE_ALERT,
+            &[ALERT_LEVEL_WARNING, ALERT_CLOSE_NOTIFY],
+            &self.aead_enc,
+            &self.iv_enc,
+            &mut self.seq_enc,
+        )?;
+        self.io.flush()
+    }
+}
+
+impl<T: Read + Write> Drop for TlsStream<T> {
+    fn drop(&mut self) {
+        // The server may be gone already, nothing to do about that
+        let _ = self.shutdown();
+    }
+}
+
 impl<T: Read + Write> Read for TlsStream<T> {
     fn read(&mut self, out: &mut [u8]) -> OrtResult<usize> {
+        let n = self.read_plaintext(out)?;
+        if let Some(dump) = self.wire_dump.as_mut() {
+            dump.response(&out[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl<T: Read + Write> TlsStream<T> {
+    fn read_plaintext(&mut self, out: &mut [u8]) -> OrtResult<usize> {
         if self.rpos < self.rbuf.len() {
             debug_print("TlsStream.read using buf", &[]);
 
@@ -681,6 +774,9 @@ impl<T: Read + Write> Read for TlsStream<T> {
             }
             return Ok(n);
         }
+        if self.is_read_closed {
+            return Ok(0);
+        }
         loop {
             let (typ, plaintext, inner_type) = read_record_cipher(
                 &mut self.io,
@@ -701,6 +797,19 @@ impl<T: Read + Write> Read for TlsStream<T> {
                 continue;
             }
             if inner_type == REC_TYPE_ALERT {
+                // A clean end. A connection that just drops is still an error,
+                // it might have been cut short.
+                if plaintext.get(1) == Some(&ALERT_CLOSE_NOTIFY) {
+                    self.is_read_closed = true;
+                    return Ok(0);
+                }
+                // Always followed by a close_notify
+                if plaintext.get(1) == Some(&ALERT_USER_CANCELED) {
+                    continue;
+                }
+                if plaintext.len() < 2 {
+                    return Err(ort_error(ErrorKind::TlsAlertReceived, "short alert"));
+                }
                 let level = match plaintext[0] {
                     1 => "warning",
                     2 => "fatal",
@@ -1011,11 +1120,16 @@ pub mod tests {
     struct TestIo {
         bytes: Vec<u8>,
         pos: usize,
+        written: Vec<u8>,
     }
 
     impl TestIo {
         fn new(bytes: Vec<u8>) -> Self {
-            Self { bytes, pos: 0 }
+            Self {
+                bytes,
+                pos: 0,
+                written: Vec::new(),
+            }
         }
     }
 
@@ -1031,6 +1145,7 @@ pub mod tests {
 
     impl Write for TestIo {
         fn write(&mut self, buf: &[u8]) -> OrtResult<usize> {
+            self.written.extend_from_slice(buf);
             Ok(buf.len())
         }
 
@@ -1048,6 +1163,33 @@ pub mod tests {
         record
     }
 
+    const TEST_KEY: [u8; 16] = [7; 16];
+    const TEST_IV: [u8; 12] = [9; 12];
+
+    // Same keys both ways so we can decrypt what we wrote
+    fn test_stream(bytes: Vec<u8>) -> TlsStream<TestIo> {
+        TlsStream {
+            io: TestIo::new(bytes),
+            aead_enc: TEST_KEY,
+            aead_dec: TEST_KEY,
+            iv_enc: TEST_IV,
+            iv_dec: TEST_IV,
+            seq_enc: 0,
+            seq_dec: 0,
+            rbuf: Vec::new(),
+            rpos: 0,
+            wire_dump: None,
+            is_read_closed: false,
+            is_write_closed: false,
+        }
+    }
+
+    fn cipher_record(inner_type: u8, body: &[u8], seq: &mut u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_record_cipher(&mut out, inner_type, body, &TEST_KEY, &TEST_IV, seq).unwrap();
+        out
+    }
+
     pub fn string_to_bytes(s: &str) -> [u8; 32] {
         let mut bytes = s.as_bytes();
         if bytes.len() >= 2 && bytes[0] == b'0' && (bytes[1] == b'x' || bytes[1] == b'X') {
@@ -1138,4 +1280,58 @@ pub mod tests {
         assert_eq!(inner_type, REC_TYPE_HANDSHAKE);
         assert_eq!(plaintext, alloc::vec![1, 2, 3]);
     }
+
+    #[test]
+    fn close_notify_is_eof() {
+        let mut seq = 0;
+        let mut bytes = cipher_record(REC_TYPE_APPDATA, b"hello", &mut seq);
+        bytes.extend_from_slice(&cipher_record(
+            REC_TYPE_ALERT,
+            &[ALERT_LEVEL_WARNING, ALERT_CLOSE_NOTIFY],
+            &mut seq,
+        ));
+        let mut stream = test_stream(bytes);
+
+        let mut buf = [0u8; 16];
+        assert_eq!(stream.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        // Nothing left on the wire, must not try to read another record
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn other_alerts_are_errors() {
+        let mut seq = 0;
+        // fatal handshake_failure
+        let bytes = cipher_record(REC_TYPE_ALERT, &[2, 40], &mut seq);
+        let mut stream = test_stream(bytes);
+
+        let mut buf = [0u8; 16];
+        match stream.read(&mut buf) {
+            Ok(_) => panic!("fatal alert should fail"),
+            Err(err) => assert!(matches!(err.kind, ErrorKind::TlsAlertReceived)),
+        }
+    }
+
+    #[test]
+    fn shutdown_sends_one_close_notify() {
+        let mut stream = test_stream(Vec::new());
+        stream.shutdown().unwrap();
+        stream.shutdown().unwrap();
+        let mut written = core::mem::take(&mut stream.io.written);
+        drop(stream);
+
+        let mut seq = 0;
+        let mut r = TestIo::new(core::mem::take(&mut written));
+        let (typ, plaintext, inner_type) =
+            read_record_cipher(&mut r, &TEST_KEY, &TEST_IV, &mut seq).unwrap();
+        assert_eq!(typ, REC_TYPE_APPDATA);
+        assert_eq!(inner_type, REC_TYPE_ALERT);
+        assert_eq!(
+            plaintext,
+            alloc::vec![ALERT_LEVEL_WARNING, ALERT_CLOSE_NOTIFY]
+        );
+        assert_eq!(r.pos, r.bytes.len(), "only one record");
+    }
 }
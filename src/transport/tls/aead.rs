@@ -9,6 +9,8 @@
 extern crate alloc;
 use alloc::vec::Vec;
 
+use crate::common::cpu;
+
 /// AES-128 GCM encryption. Returns ciphertext || 16-byte tag.
 /// `aad` is additional authenticated data (can be empty) that is authenticated
 /// but not encrypted.
@@ -138,9 +140,12 @@ pub fn aes_128_gcm_decrypt(
 // ================= AES PRIMITIVES ================= //
 
 // Generate 11 round keys (11 * 16 bytes)
-// Target assumption: modern x86_64 CPUs with AES-NI support.
 fn key_expansion(key: &[u8]) -> [[u8; 16]; 11] {
-    unsafe { key_expansion_aesni(key) }
+    if cpu::has_aes() {
+        unsafe { key_expansion_aesni(key) }
+    } else {
+        key_expansion_soft(key)
+    }
 }
 
 unsafe fn key_expansion_aesni(key: &[u8]) -> [[u8; 16]; 11] {
@@ -197,9 +202,12 @@ unsafe fn key_expansion_aesni(key: &[u8]) -> [[u8; 16]; 11] {
     }
 }
 
-// Target assumption: modern x86_64 CPUs with AES-NI support.
 fn aes_encrypt_block(input: &[u8; 16], round_keys: &[[u8; 16]; 11]) -> [u8; 16] {
-    unsafe { aes_encrypt_block_aesni(input, round_keys) }
+    if cpu::has_aes() {
+        unsafe { aes_encrypt_block_aesni(input, round_keys) }
+    } else {
+        aes_encrypt_block_soft(input, round_keys)
+    }
 }
 
 unsafe fn aes_encrypt_block_aesni(input: &[u8; 16], round_keys: &[[u8; 16]; 11]) -> [u8; 16] {
@@ -229,6 +237,87 @@ unsafe fn aes_encrypt_block_aesni(input: &[u8; 16], round_keys: &[[u8; 16]; 11])
     }
 }
 
+// Portable AES for CPUs without AES-NI. The S-box lookups are not
+// constant time, it's only here so those CPUs work at all.
+
+#[rustfmt::skip]
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+fn key_expansion_soft(key: &[u8]) -> [[u8; 16]; 11] {
+    const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+    let mut round_keys = [[0u8; 16]; 11];
+    round_keys[0].copy_from_slice(&key[..16]);
+    for i in 1..11 {
+        let prev = round_keys[i - 1];
+        // RotWord, SubWord and Rcon of the previous key's last word
+        let mut t = [
+            SBOX[prev[13] as usize] ^ RCON[i - 1],
+            SBOX[prev[14] as usize],
+            SBOX[prev[15] as usize],
+            SBOX[prev[12] as usize],
+        ];
+        for j in 0..16 {
+            round_keys[i][j] = prev[j] ^ t[j % 4];
+            t[j % 4] = round_keys[i][j];
+        }
+    }
+    round_keys
+}
+
+#[inline(always)]
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ ((b >> 7) * 0x1b)
+}
+
+fn aes_encrypt_block_soft(input: &[u8; 16], round_keys: &[[u8; 16]; 11]) -> [u8; 16] {
+    // Column major, byte `r + 4 * c` is row r column c
+    let mut state = *input;
+    for (s, k) in state.iter_mut().zip(round_keys[0]) {
+        *s ^= k;
+    }
+    for (round, round_key) in round_keys.iter().enumerate().skip(1) {
+        // SubBytes and ShiftRows
+        let mut t = [0u8; 16];
+        for c in 0..4 {
+            for r in 0..4 {
+                t[r + 4 * c] = SBOX[state[r + 4 * ((c + r) % 4)] as usize];
+            }
+        }
+        if round != 10 {
+            // MixColumns
+            for col in t.chunks_exact_mut(4) {
+                let a = [col[0], col[1], col[2], col[3]];
+                let all = a[0] ^ a[1] ^ a[2] ^ a[3];
+                for r in 0..4 {
+                    col[r] = a[r] ^ all ^ xtime(a[r] ^ a[(r + 1) % 4]);
+                }
+            }
+        }
+        for (s, (t, k)) in state.iter_mut().zip(t.iter().zip(round_key)) {
+            *s = t ^ k;
+        }
+    }
+    state
+}
+
 // ================= GCM SUPPORT ================= //
 
 #[inline(always)]
@@ -246,14 +335,14 @@ fn ghash(h: u128, aad: &[u8], ciphertext: &[u8]) -> u128 {
         let mut b = [0u8; 16];
         b[..block.len()].copy_from_slice(block);
         let x = u128::from_be_bytes(b);
-        y = unsafe { gf_mul_pclmul(y ^ x, h) };
+        y = gf_mul(y ^ x, h);
     }
 
     for block in ciphertext.chunks(16) {
         let mut b = [0u8; 16];
         b[..block.len()].copy_from_slice(block);
         let x = u128::from_be_bytes(b);
-        y = unsafe { gf_mul_pclmul(y ^ x, h) };
+        y = gf_mul(y ^ x, h);
     }
 
     let aad_bits = (aad.len() as u128) * 8;
@@ -262,7 +351,28 @@ fn ghash(h: u128, aad: &[u8], ciphertext: &[u8]) -> u128 {
     len_block[..8].copy_from_slice(&(aad_bits as u64).to_be_bytes());
     len_block[8..].copy_from_slice(&(ct_bits as u64).to_be_bytes());
     let x = u128::from_be_bytes(len_block);
-    unsafe { gf_mul_pclmul(y ^ x, h) }
+    gf_mul(y ^ x, h)
+}
+
+fn gf_mul(x: u128, h: u128) -> u128 {
+    if cpu::has_pclmul() {
+        unsafe { gf_mul_pclmul(x, h) }
+    } else {
+        gf_mul_soft(x, h)
+    }
+}
+
+/// Bit at a time GF(2^128) multiply, for CPUs without PCLMULQDQ
+fn gf_mul_soft(x: u128, h: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut z = 0u128;
+    let mut v = h;
+    for i in (0..128).rev() {
+        // Masks instead of branches, so the time doesn't depend on the key
+        z ^= v & ((x >> i) & 1).wrapping_neg();
+        v = (v >> 1) ^ (R & (v & 1).wrapping_neg());
+    }
+    z
 }
 
 #[inline(always)]
@@ -643,6 +753,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_soft_matches_hardware() {
+        let key = hex_to_bytes("000102030405060708090a0b0c0d0e0f");
+        let round_keys = key_expansion_soft(&key);
+        let block: [u8; 16] = hex_to_bytes("00112233445566778899aabbccddeeff")
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            aes_encrypt_block_soft(&block, &round_keys).to_vec(),
+            hex_to_bytes("69c4e0d86a7b0430d8cdb78070b4c55a")
+        );
+        if cpu::has_aes() {
+            assert_eq!(round_keys, unsafe { key_expansion_aesni(&key) });
+        }
+
+        let h = 0x66e94bd4ef8a2c3b884cfa59ca342b2eu128;
+        let x = 0x0388dace60b6a392f328c2b971b2fe78u128;
+        let expected = 0x5e2ec746917062882c85b0685353deb7u128;
+        assert_eq!(gf_mul_soft(x, h), expected);
+        if cpu::has_pclmul() {
+            assert_eq!(unsafe { gf_mul_pclmul(x, h) }, expected);
+        }
+    }
+
     /// Test Case 9: Very short plaintext (1 byte)
     #[test]
     fn test_aes_128_gcm_one_byte_plaintext() {
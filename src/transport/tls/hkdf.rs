@@ -57,7 +57,7 @@ pub fn hkdf_expand(prk: &[u8], info: &[u8], len: usize) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use crate::net::tls::tests::{hex_to_vec, string_to_bytes};
+    use crate::transport::tls::tests::{hex_to_vec, string_to_bytes};
 
     #[test]
     fn test_hkdf_extract_rfc5869_case1() {
@@ -88,7 +88,7 @@ mod tests {
         let ikm = b"handshake secret";
         let prk = super::hkdf_extract(&[], ikm);
         let zero_salt = [0u8; 32];
-        let expected = crate::net::tls::hmac::sign(&zero_salt, ikm);
+        let expected = crate::transport::tls::hmac::sign(&zero_salt, ikm);
         assert_eq!(prk, expected);
     }
 
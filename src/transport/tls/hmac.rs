@@ -51,7 +51,7 @@ pub fn sign(key: &[u8], data: &[u8]) -> [u8; 32] {
 mod tests {
     extern crate test;
 
-    use crate::net::tls::tests::string_to_bytes;
+    use crate::transport::tls::tests::string_to_bytes;
     use test::{Bencher, black_box};
 
     #[test]
@@ -0,0 +1,81 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! SSLKEYLOGFILE support, so Wireshark can decrypt our own sessions.
+//! NSS key log format: `<label> <client_random hex> <secret hex>`
+
+use core::ffi::{c_char, c_void};
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::String;
+
+use crate::syscall;
+
+// Only written once at startup, before any connection
+static mut KEY_LOG_FILE: Option<&'static str> = None;
+
+/// Append TLS secrets to this file from now on.
+pub fn set_key_log_file(path: &'static str) {
+    unsafe { KEY_LOG_FILE = Some(path) };
+}
+
+/// The ClientHello random. The transcript starts with the ClientHello:
+/// type (1), length (3), legacy_version (2), then 32 random bytes.
+pub(super) fn client_random(transcript: &[u8]) -> &[u8] {
+    &transcript[6..38]
+}
+
+/// Append one secret to the key log file, if there is one.
+/// Failures are ignored, this is a debugging aid.
+pub(super) fn log(label: &str, client_random: &[u8], secret: &[u8]) {
+    let Some(path) = (unsafe { KEY_LOG_FILE }) else {
+        return;
+    };
+    let Ok(c_path) = CString::new(path) else {
+        return;
+    };
+    let flags = syscall::O_CLOEXEC | syscall::O_WRONLY | syscall::O_CREAT | syscall::O_APPEND;
+    let Ok(fd) = syscall::open(c_path.as_ptr() as *const c_char, flags, 0o600) else {
+        return;
+    };
+    if fd < 0 {
+        return;
+    }
+    let l = line(label, client_random, secret);
+    syscall::write(fd, l.as_ptr() as *const c_void, l.len());
+    let _ = syscall::close(fd);
+}
+
+fn line(label: &str, client_random: &[u8], secret: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(label.len() + 3 + 2 * (client_random.len() + secret.len()));
+    out.push_str(label);
+    for (i, bytes) in [client_random, secret].into_iter().enumerate() {
+        out.push(' ');
+        for b in bytes {
+            out.push(HEX[(b >> 4) as usize] as char);
+            out.push(HEX[(b & 0xf) as usize] as char);
+        }
+        if i == 1 {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_log_line() {
+        assert_eq!(
+            line("CLIENT_TRAFFIC_SECRET_0", &[0x01, 0xab], &[0xff, 0x00]),
+            "CLIENT_TRAFFIC_SECRET_0 01ab ff00\n"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! Server side of the TLS 1.3 handshake, for the mock server in the
+//! integration tests. It speaks just enough for our own client: one cipher,
+//! X25519, and no Certificate because the client does not check it.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use super::{
+    ApplicationKeys, CIPHER_TLS_AES_128_GCM_SHA256, EXT_KEY_SHARE, EXT_SUPPORTED_VERSIONS,
+    GROUP_X25519, HS_CLIENT_HELLO, HS_FINISHED, HS_SERVER_HELLO, REC_TYPE_HANDSHAKE, TLS13,
+    TlsStream, digest_bytes, ecdh, hkdf_expand_label, hmac, put_u16, put_u24,
+    read_handshake_message, read_record_cipher_from_record, read_record_plain, write_record_cipher,
+    write_record_plain,
+};
+use crate::{Context, ErrorKind, OrtResult, Read, Write, ort_error, syscall};
+
+const HS_ENCRYPTED_EXTENSIONS: u8 = 8;
+
+impl<T: Read + Write> TlsStream<T> {
+    /// Handshake as the server on a freshly accepted connection
+    pub fn accept(mut io: T) -> OrtResult<Self> {
+        let mut transcript = Vec::with_capacity(1024);
+
+        let (typ, ch_buf) = read_record_plain(&mut io).context("read ClientHello")?;
+        if typ != REC_TYPE_HANDSHAKE {
+            return Err(ort_error(ErrorKind::TlsExpectedHandshakeRecord, ""));
+        }
+        let mut rd = &ch_buf[..];
+        let (ch_typ, ch_body, ch_full) = read_handshake_message(&mut rd)?;
+        if ch_typ != HS_CLIENT_HELLO {
+            return Err(ort_error(
+                ErrorKind::TlsBadClientHello,
+                "Expected handshake type 1",
+            ));
+        }
+        let (session_id, client_pub) = parse_client_hello(ch_body)?;
+        transcript.extend_from_slice(ch_full);
+
+        let mut server_private_key = [0u8; 32];
+        syscall::getrandom(&mut server_private_key);
+        let sh_msg = server_hello_msg(session_id, &ecdh::x25519_public_key(&server_private_key));
+        write_record_plain(&mut io, REC_TYPE_HANDSHAKE, &sh_msg).context("write ServerHello")?;
+        transcript.extend_from_slice(&sh_msg);
+
+        let hs_shared_secret = ecdh::x25519_agreement(&server_private_key, &client_pub);
+        let handshake = Self::handshake_keys(&hs_shared_secret, &transcript);
+
+        // EncryptedExtensions (empty) and Finished, in one record
+        let mut flight = Vec::with_capacity(6 + 4 + 32);
+        flight.push(HS_ENCRYPTED_EXTENSIONS);
+        put_u24(&mut flight, 2);
+        put_u16(&mut flight, 0);
+        transcript.extend_from_slice(&flight);
+
+        let s_finished_key = hkdf_expand_label::<32>(&handshake.server_hs_ts, "finished", &[]);
+        let verify_data = hmac::sign(&s_finished_key, &digest_bytes(&transcript));
+        let mut fin = Vec::with_capacity(4 + verify_data.len());
+        fin.push(HS_FINISHED);
+        put_u24(&mut fin, verify_data.len());
+        fin.extend_from_slice(&verify_data);
+        transcript.extend_from_slice(&fin);
+        flight.extend_from_slice(&fin);
+
+        // The client's keys are our dec, and the other way round
+        let mut seq_enc_hs = 0u64;
+        write_record_cipher(
+            &mut io,
+            REC_TYPE_HANDSHAKE,
+            &flight,
+            &handshake.aead_dec_hs,
+            &handshake.server_handshake_iv,
+            &mut seq_enc_hs,
+        )
+        .context("write server Finished")?;
+        io.flush()?;
+
+        // Application keys come from the transcript up to the server Finished
+        let ApplicationKeys {
+            aead_app_enc: cak,
+            aead_app_dec: sak,
+            iv_enc: caiv,
+            iv_dec: saiv,
+        } = Self::derive_application_keys(
+            &handshake.handshake_secret,
+            &handshake.empty_hash,
+            &transcript,
+        );
+
+        let Some(record) = Self::skip_dummy_change_cipher_specs(&mut io)? else {
+            return Err(ort_error(ErrorKind::TlsExpectedEncryptedRecords, ""));
+        };
+        let mut seq_dec_hs = 0u64;
+        let (_, ct, inner_type) = read_record_cipher_from_record(
+            record,
+            &handshake.aead_enc_hs,
+            &handshake.client_handshake_iv,
+            &mut seq_dec_hs,
+        )?;
+        let mut p = &ct[..];
+        let (fin_typ, fin_body, _) = read_handshake_message(&mut p)?;
+        if inner_type != REC_TYPE_HANDSHAKE || fin_typ != HS_FINISHED {
+            return Err(ort_error(
+                ErrorKind::TlsBadHandshakeFragment,
+                "Expected client Finished",
+            ));
+        }
+        let c_finished_key = hkdf_expand_label::<32>(&handshake.client_hs_ts, "finished", &[]);
+        let expected = hmac::sign(&c_finished_key, &digest_bytes(&transcript));
+        if expected.as_slice() != fin_body {
+            return Err(ort_error(ErrorKind::TlsFinishedVerifyFailed, ""));
+        }
+
+        Ok(TlsStream {
+            io,
+            aead_enc: sak,
+            aead_dec: cak,
+            iv_enc: saiv,
+            iv_dec: caiv,
+            seq_enc: 0,
+            seq_dec: 0,
+            rbuf: Vec::with_capacity(16 * 1024),
+            rpos: 0,
+            wire_dump: None,
+            is_read_closed: false,
+            is_write_closed: false,
+        })
+    }
+}
+
+/// The next `n` bytes of `p`, or an error if it is short
+fn take<'a>(p: &mut &'a [u8], n: usize) -> OrtResult<&'a [u8]> {
+    if p.len() < n {
+        return Err(ort_error(ErrorKind::TlsBadClientHello, "too short"));
+    }
+    let (head, tail) = p.split_at(n);
+    *p = tail;
+    Ok(head)
+}
+
+fn take_u16(p: &mut &[u8]) -> OrtResult<usize> {
+    let b = take(p, 2)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]) as usize)
+}
+
+/// The legacy session id, to echo back, and the client's X25519 public key
+fn parse_client_hello(ch: &[u8]) -> OrtResult<(&[u8], [u8; 32])> {
+    let mut p = ch;
+    take(&mut p, 2 + 32)?; // legacy_version, random
+    let sid_len = take(&mut p, 1)?[0] as usize;
+    let session_id = take(&mut p, sid_len)?;
+    let ciphers_len = take_u16(&mut p)?;
+    let ciphers = take(&mut p, ciphers_len)?;
+    if !ciphers
+        .chunks_exact(2)
+        .any(|c| u16::from_be_bytes([c[0], c[1]]) == CIPHER_TLS_AES_128_GCM_SHA256)
+    {
+        return Err(ort_error(
+            ErrorKind::TlsUnsupportedCipher,
+            "client does not offer TLS_AES_128_GCM_SHA256",
+        ));
+    }
+    let comp_len = take(&mut p, 1)?[0] as usize;
+    take(&mut p, comp_len)?;
+    let ext_len = take_u16(&mut p)?;
+    let mut ex = take(&mut p, ext_len)?;
+
+    while !ex.is_empty() {
+        let et = take_u16(&mut ex)? as u16;
+        let el = take_u16(&mut ex)?;
+        let mut ed = take(&mut ex, el)?;
+        if et != EXT_KEY_SHARE {
+            continue;
+        }
+        // KeyShareClientHello: client_shares<2..>, each group(2) kx_len(2) kx
+        let shares_len = take_u16(&mut ed)?;
+        let mut shares = take(&mut ed, shares_len)?;
+        while !shares.is_empty() {
+            let group = take_u16(&mut shares)? as u16;
+            let kx_len = take_u16(&mut shares)?;
+            let kx = take(&mut shares, kx_len)?;
+            if group == GROUP_X25519 && kx_len == 32 {
+                let mut pk = [0u8; 32];
+                pk.copy_from_slice(kx);
+                return Ok((session_id, pk));
+            }
+        }
+    }
+    Err(ort_error(
+        ErrorKind::TlsBadClientHello,
+        "no X25519 key share",
+    ))
+}
+
+fn server_hello_msg(session_id: &[u8], server_pub: &[u8; 32]) -> Vec<u8> {
+    let mut random = [0u8; 32];
+    syscall::getrandom(&mut random);
+
+    let mut body = Vec::with_capacity(128);
+    body.extend_from_slice(&0x0303u16.to_be_bytes());
+    body.extend_from_slice(&random);
+    body.push(session_id.len() as u8);
+    body.extend_from_slice(session_id);
+    put_u16(&mut body, CIPHER_TLS_AES_128_GCM_SHA256);
+    body.push(0); // compression: null
+
+    let mut exts = Vec::with_capacity(6 + 40);
+    put_u16(&mut exts, EXT_SUPPORTED_VERSIONS);
+    put_u16(&mut exts, 2);
+    put_u16(&mut exts, TLS13);
+    put_u16(&mut exts, EXT_KEY_SHARE);
+    put_u16(&mut exts, 2 + 2 + 32);
+    put_u16(&mut exts, GROUP_X25519);
+    put_u16(&mut exts, 32);
+    exts.extend_from_slice(server_pub);
+    put_u16(&mut body, exts.len() as u16);
+    body.extend_from_slice(&exts);
+
+    let mut msg = Vec::with_capacity(4 + body.len());
+    msg.push(HS_SERVER_HELLO);
+    put_u24(&mut msg, body.len());
+    msg.extend_from_slice(&body);
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{client_hello_msg, parse_server_hello_for_keys};
+    use super::*;
+
+    #[test]
+    fn client_hello_key_share() {
+        let private_key = [7u8; 32];
+        let msg = client_hello_msg("openrouter.ai", &private_key).unwrap();
+        let mut rd = &msg[..];
+        let (_, body, _) = read_handshake_message(&mut rd).unwrap();
+        let (session_id, client_pub) = parse_client_hello(body).unwrap();
+        assert_eq!(session_id.len(), 32);
+        assert_eq!(client_pub, ecdh::x25519_public_key(&private_key));
+    }
+
+    #[test]
+    fn client_hello_truncated() {
+        let msg = client_hello_msg("openrouter.ai", &[7u8; 32]).unwrap();
+        for n in 0..msg.len() - 4 {
+            assert!(parse_client_hello(&msg[4..4 + n]).is_err());
+        }
+    }
+
+    #[test]
+    fn server_hello_round_trip() {
+        let server_pub = [9u8; 32];
+        let msg = server_hello_msg(&[1, 2, 3], &server_pub);
+        let mut rd = &msg[..];
+        let (typ, body, _) = read_handshake_message(&mut rd).unwrap();
+        assert_eq!(typ, HS_SERVER_HELLO);
+        let (cipher, key) = parse_server_hello_for_keys(body).unwrap();
+        assert_eq!(cipher, CIPHER_TLS_AES_128_GCM_SHA256);
+        assert_eq!(key, server_pub);
+    }
+}
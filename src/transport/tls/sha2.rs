@@ -120,6 +120,59 @@ unsafe fn compress_blocks(h: &mut [u32; 8], blocks: &[[u8; 64]]) {
     _mm_storeu_si128(state_ptr.add(1), hgef);
 }
 
+/// Portable compression for CPUs without SHA-NI.
+/// K32X4 holds the round constants four at a time in reverse order.
+fn compress_blocks_soft(h: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    for block in blocks {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+        for (i, wi) in w.iter().enumerate() {
+            let k = K32X4[i / 4][3 - i % 4];
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(k)
+                .wrapping_add(*wi);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+}
+
+fn compress(h: &mut [u32; 8], blocks: &[[u8; 64]]) {
+    if crate::common::cpu::has_sha() {
+        unsafe { compress_blocks(h, blocks) };
+    } else {
+        compress_blocks_soft(h, blocks);
+    }
+}
+
 /// Calculate the SHA-256 digest of the input string.
 pub fn sha256(b: &[u8]) -> [u8; 32] {
     let mut h = INITIAL_STATE;
@@ -129,7 +182,7 @@ pub fn sha256(b: &[u8]) -> [u8; 32] {
     if full_blocks != 0 {
         let blocks =
             unsafe { core::slice::from_raw_parts(b.as_ptr() as *const [u8; 64], full_blocks) };
-        unsafe { compress_blocks(&mut h, blocks) };
+        compress(&mut h, blocks);
     }
     let remaining = &b[(full_blocks * 64)..];
 
@@ -145,7 +198,7 @@ pub fn sha256(b: &[u8]) -> [u8; 32] {
     let tail_blocks_count = if rem_len < 56 { 1 } else { 2 };
     let tail_blocks =
         unsafe { core::slice::from_raw_parts(tail.as_ptr() as *const [u8; 64], tail_blocks_count) };
-    unsafe { compress_blocks(&mut h, tail_blocks) };
+    compress(&mut h, tail_blocks);
 
     let mut out = [0u8; 32];
     for (chunk, word) in out.chunks_exact_mut(4).zip(h.iter()) {
@@ -159,7 +212,7 @@ mod tests {
     extern crate test;
     use test::Bencher;
 
-    use crate::net::tls::tests::string_to_bytes;
+    use crate::transport::tls::tests::string_to_bytes;
 
     #[test]
     fn sha256_empty() {
@@ -190,6 +243,33 @@ mod tests {
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn sha256_soft_matches() {
+        let mut block = [0u8; 64];
+        crate::syscall::getrandom(&mut block);
+        let mut soft = super::INITIAL_STATE;
+        super::compress_blocks_soft(&mut soft, &[block]);
+        if crate::common::cpu::has_sha() {
+            let mut hw = super::INITIAL_STATE;
+            unsafe { super::compress_blocks(&mut hw, &[block]) };
+            assert_eq!(soft, hw);
+        }
+
+        // "abc", padded by hand
+        let mut block = [0u8; 64];
+        block[..4].copy_from_slice(b"abc\x80");
+        block[63] = 24;
+        let mut h = super::INITIAL_STATE;
+        super::compress_blocks_soft(&mut h, &[block]);
+        assert_eq!(
+            h,
+            [
+                0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+                0xf20015ad
+            ]
+        );
+    }
+
     #[bench]
     fn bench_sha256_short(b: &mut Bencher) {
         let input = b"Hello";
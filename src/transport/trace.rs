@@ -0,0 +1,181 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--trace` shows where a request's time went. The steps of a request happen
+//! one after another, so each hook only marks the end of its step: DNS, TCP
+//! connect, TLS handshake, request write, first byte, headers parsed, first
+//! content token and stream end. When the answer ends the steps go to stderr
+//! as spans, one JSON object a line, in the shape of OpenTelemetry span events:
+//!
+//! `{"trace_id":"01J9Z3...","name":"tls_handshake","start_us":2104,"duration_us":18770}`
+//!
+//! The trace id is our `X-Client-Request-Id` for the request, times are
+//! microseconds since it started.
+
+extern crate alloc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::common::utils;
+use crate::syscall;
+
+// Only written once at startup, before any request
+static mut IS_ON: bool = false;
+
+struct Current {
+    is_locked: AtomicBool,
+    trace: UnsafeCell<Option<Trace>>,
+}
+
+// Only touched with `is_locked` held
+unsafe impl Sync for Current {}
+
+static CURRENT: Current = Current {
+    is_locked: AtomicBool::new(false),
+    trace: UnsafeCell::new(None),
+};
+
+fn with_current<F: FnOnce(&mut Option<Trace>) -> R, R>(f: F) -> R {
+    while CURRENT
+        .is_locked
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    let out = f(unsafe { &mut *CURRENT.trace.get() });
+    CURRENT.is_locked.store(false, Ordering::Release);
+    out
+}
+
+/// Trace every prompt request from now on
+pub fn set_on() {
+    unsafe { IS_ON = true };
+}
+
+fn is_on() -> bool {
+    unsafe { IS_ON }
+}
+
+/// A request is starting, the next `mark` ends its first step
+pub fn begin() {
+    if is_on() {
+        let now = syscall::monotonic_us();
+        with_current(|t| *t = Some(Trace::new(now)));
+    }
+}
+
+/// The step called `name` just finished. Nothing unless a trace was begun.
+pub fn mark(name: &'static str) {
+    if is_on() {
+        let now = syscall::monotonic_us();
+        with_current(|t| {
+            if let Some(t) = t.as_mut() {
+                t.mark(name, now);
+            }
+        });
+    }
+}
+
+/// The `X-Client-Request-Id` of the request being traced. A retry on a new
+/// connection sends a new one.
+pub fn set_id(trace_id: &str) {
+    if is_on() {
+        with_current(|t| {
+            if let Some(t) = t.as_mut() {
+                t.id = trace_id.to_string();
+            }
+        });
+    }
+}
+
+/// The request is over, whether it worked or not, print its spans. Later
+/// marks are ignored until the next `begin`.
+pub fn finish() {
+    if !is_on() {
+        return;
+    }
+    if let Some(t) = with_current(Option::take) {
+        let out = t.to_json_lines();
+        syscall::write(2, out.as_ptr().cast(), out.len());
+    }
+}
+
+struct Span {
+    name: &'static str,
+    start_us: u64,
+    end_us: u64,
+}
+
+struct Trace {
+    /// Empty if it failed before sending anything
+    id: String,
+    start_us: u64,
+    /// When the previous step ended
+    last_us: u64,
+    spans: Vec<Span>,
+}
+
+impl Trace {
+    fn new(now_us: u64) -> Trace {
+        Trace {
+            id: String::new(),
+            start_us: now_us,
+            last_us: now_us,
+            spans: Vec::new(),
+        }
+    }
+
+    fn mark(&mut self, name: &'static str, now_us: u64) {
+        self.spans.push(Span {
+            name,
+            start_us: self.last_us - self.start_us,
+            end_us: now_us - self.start_us,
+        });
+        self.last_us = now_us;
+    }
+
+    fn to_json_lines(&self) -> String {
+        let mut out = String::with_capacity(self.spans.len() * 96);
+        for span in &self.spans {
+            out.push_str("{\"trace_id\":\"");
+            out.push_str(&self.id);
+            out.push_str("\",\"name\":\"");
+            out.push_str(span.name);
+            out.push_str("\",\"start_us\":");
+            out.push_str(&utils::num_to_string(span.start_us));
+            out.push_str(",\"duration_us\":");
+            out.push_str(&utils::num_to_string(span.end_us - span.start_us));
+            out.push_str("}\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Trace;
+    extern crate alloc;
+    use alloc::string::ToString;
+
+    #[test]
+    fn spans_follow_each_other() {
+        let mut t = Trace::new(1_000);
+        t.mark("dns", 1_500);
+        t.mark("tcp_connect", 2_100);
+        t.mark("tls_handshake", 20_870);
+        t.id = "01ARYZ6S41TSV4RRFFQ69G5FAV".to_string();
+        assert_eq!(
+            t.to_json_lines(),
+            "{\"trace_id\":\"01ARYZ6S41TSV4RRFFQ69G5FAV\",\"name\":\"dns\",\"start_us\":0,\"duration_us\":500}\n\
+             {\"trace_id\":\"01ARYZ6S41TSV4RRFFQ69G5FAV\",\"name\":\"tcp_connect\",\"start_us\":500,\"duration_us\":600}\n\
+             {\"trace_id\":\"01ARYZ6S41TSV4RRFFQ69G5FAV\",\"name\":\"tls_handshake\",\"start_us\":1100,\"duration_us\":18770}\n"
+        );
+        assert!(Trace::new(5).to_json_lines().is_empty());
+    }
+}
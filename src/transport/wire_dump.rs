@@ -0,0 +1,104 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! `--dump-wire <dir>` records the chat completions exchange for bug reports:
+//! - request.http: The request, API key masked.
+//! - response.http: The response as received after TLS, headers, chunk sizes and SSE.
+//! - timing.tsv: Milliseconds since start of each step and each read.
+
+extern crate alloc;
+use alloc::ffi::CString;
+use alloc::string::{String, ToString};
+
+use crate::common::{file, time, utils};
+use crate::{ErrorKind, OrtResult, Write, ort_error, syscall};
+
+// Only written once at startup, before any connection
+static mut DUMP_DIR: Option<&'static str> = None;
+static mut MAX_BYTES: Option<usize> = None;
+
+/// Dump every chat completions exchange into `dir` from now on.
+/// With `max_bytes` the request body and response are cut at that size.
+pub fn set_dir(dir: &'static str, max_bytes: Option<usize>) {
+    unsafe {
+        DUMP_DIR = Some(dir);
+        MAX_BYTES = max_bytes;
+    }
+}
+
+pub struct WireDump {
+    response: file::File,
+    timing: file::File,
+    start_ms: u64,
+    max_bytes: usize,
+    response_len: usize,
+}
+
+impl WireDump {
+    /// A new dump if --dump-wire is on. Replaces the previous one.
+    pub fn start() -> OrtResult<Option<WireDump>> {
+        let Some(dir) = (unsafe { DUMP_DIR }) else {
+            return Ok(None);
+        };
+        utils::ensure_dir_exists(dir);
+        let mut timing = create(dir, "timing.tsv")?;
+        timing.write_str("# ")?;
+        timing.write_str(&time::utc_timestamp(syscall::unix_time()))?;
+        timing.write_str("\nms\tevent\n")?;
+        Ok(Some(WireDump {
+            response: create(dir, "response.http")?,
+            timing,
+            start_ms: syscall::monotonic_ms(),
+            max_bytes: unsafe { MAX_BYTES }.unwrap_or(usize::MAX),
+            response_len: 0,
+        }))
+    }
+
+    /// Note the time of a step, e.g. "connected"
+    pub fn event(&mut self, name: &str) {
+        let mut line = utils::num_to_string(syscall::monotonic_ms() - self.start_ms);
+        line.push('\t');
+        line.push_str(name);
+        line.push('\n');
+        let _ = self.timing.write_all(line.as_bytes());
+    }
+
+    /// Save the request we sent. Errors are ignored, this is a debugging aid.
+    pub fn request(&mut self, header: &[u8], body: &[u8], api_key: &str) {
+        let Some(dir) = (unsafe { DUMP_DIR }) else {
+            return;
+        };
+        let Ok(mut f) = create(dir, "request.http") else {
+            return;
+        };
+        let header = String::from_utf8_lossy(header);
+        let header = utils::redact(&header, &[api_key]);
+        let _ = f.write_all(header.as_bytes());
+        let _ = f.write_all(&body[..body.len().min(self.max_bytes)]);
+        self.event("request sent");
+    }
+
+    /// Record bytes as the HTTP layer reads them
+    pub fn response(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            self.event("eof");
+            return;
+        }
+        let keep = bytes
+            .len()
+            .min(self.max_bytes.saturating_sub(self.response_len));
+        let _ = self.response.write_all(&bytes[..keep]);
+        self.response_len += keep;
+        self.event(&("read ".to_string() + &utils::num_to_string(bytes.len())));
+    }
+}
+
+fn create(dir: &str, name: &str) -> OrtResult<file::File> {
+    let path = dir.to_string() + "/" + name;
+    let c_path = CString::new(path)
+        .map_err(|_| ort_error(ErrorKind::Other, "Dump path contains nul byte"))?;
+    unsafe { file::File::create(c_path.as_bytes_with_nul()) }
+}
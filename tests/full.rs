@@ -4,6 +4,7 @@
 //! MIT License
 //! Copyright (c) 2025 Graham King
 
+use ort_openrouter_cli::Term;
 use ort_openrouter_cli::cli::{self, Env};
 
 /*
@@ -36,7 +37,7 @@ fn test_hello() {
         .into_iter()
         .map(|s| s.to_string())
         .collect();
-    let ret = cli::main(&args, env(), false, &mut out);
+    let ret = cli::main(&args, env(), Term::Pipe, &mut out);
     assert!(matches!(ret, Ok(0)));
 
     let contents = String::from_utf8_lossy(&out);
@@ -63,7 +64,7 @@ fn test_list() {
     let mut out = Vec::new();
 
     let args: Vec<String> = ["ort", "list"].into_iter().map(|s| s.to_string()).collect();
-    match cli::main(&args, env(), false, &mut out) {
+    match cli::main(&args, env(), Term::Pipe, &mut out) {
         // success
         Ok(0) => {}
         Ok(x) => {
@@ -100,9 +101,18 @@ fn env() -> Env {
         HOME: env_str!("HOME"),
         PWD: env_str!("PWD"),
         TMUX_PANE: env_str!("TMUX_PANE"),
+        ORT_SESSION: env_str!("ORT_SESSION"),
+        ORT_PROFILE: env_str!("ORT_PROFILE"),
         XDG_CONFIG_HOME: env_str!("XDG_CONFIG_HOME"),
         XDG_CACHE_HOME: env_str!("XDG_CACHE_HOME"),
+        XDG_STATE_HOME: env_str!("XDG_STATE_HOME"),
+        ORT_CONFIG_DIR: env_str!("ORT_CONFIG_DIR"),
+        ORT_CACHE_DIR: env_str!("ORT_CACHE_DIR"),
+        ORT_STATE_DIR: env_str!("ORT_STATE_DIR"),
+        ORT_HISTORY_FILE: env_str!("ORT_HISTORY_FILE"),
         OPENROUTER_API_KEY: env_str!("OPENROUTER_API_KEY"),
         NVIDIA_API_KEY: env_str!("NVIDIA_API_KEY"),
+        SSLKEYLOGFILE: env_str!("SSLKEYLOGFILE"),
+        TERM: env_str!("TERM"),
     }
 }
@@ -0,0 +1,619 @@
+//! ort: Open Router CLI
+//! https://github.com/grahamking/ort
+//!
+//! MIT License
+//! Copyright (c) 2026 Graham King
+//!
+//! End to end tests against the local mock server, no network needed.
+//! The config's `dns` points at 127.0.0.1, so everything from the connect on
+//! is the real code path: TLS, HTTP, chunked, SSE, JSON and the writers.
+
+use std::fs;
+use std::os::fd::AsRawFd as _;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use ort_openrouter_cli::cli::{self, Env};
+use ort_openrouter_cli::mock_server::{
+    MockRequest, MockServer, json_response, sse_response, sse_response_keep_alive,
+};
+use ort_openrouter_cli::{ErrorKind, OrtResult, Term, syscall};
+
+const MODEL: &str = "test/model";
+
+/// The last event, with usage so ort doesn't go and ask for the cost
+const USAGE_EVENT: &str = "data: {\"id\":\"gen-1\",\"provider\":\"Mock\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"\"},\"finish_reason\":\"stop\"}],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":4,\"cost\":0.00001}}\n\n";
+
+fn content_event(text: &str) -> String {
+    format!(
+        "data: {{\"id\":\"gen-1\",\"provider\":\"Mock\",\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"{text}\"}}}}]}}\n\n"
+    )
+}
+
+#[test]
+fn test_stream() {
+    let hello = content_event("Hello from the mock");
+    let response = sse_response(&[
+        ": OPENROUTER PROCESSING\n\n",
+        &hello,
+        USAGE_EVENT,
+        "data: [DONE]\n\n",
+    ]);
+    let (ret, out, req) = run("stream", &["-m", MODEL, "Hi there"], response);
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+
+    assert_eq!(req.method, "POST");
+    assert_eq!(req.path, "/api/v1/chat/completions");
+    assert!(
+        req.body.contains("\"model\": \"test/model\""),
+        "{}",
+        req.body
+    );
+    assert!(req.body.contains("Hi there"), "{}", req.body);
+
+    let mut lines = out.lines();
+    assert_eq!(lines.next(), Some("Hello from the mock"));
+    let last_line = lines.last().unwrap();
+    assert!(
+        last_line.starts_with(&format!("Stats: {MODEL} at Mock")),
+        "Invalid last line: '{last_line}'"
+    );
+}
+
+#[test]
+fn test_split_chunks() {
+    // One event over three HTTP chunks, the é split between its two bytes
+    let event = content_event("Héllo");
+    let split = event.find('é').unwrap() + 1;
+    let (a, b) = event.as_bytes().split_at(split);
+    let (a, b) = unsafe { (str::from_utf8_unchecked(a), str::from_utf8_unchecked(b)) };
+    let response = sse_response(&[&a[..10], &a[10..], b, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, out, _) = run("split", &["-m", MODEL, "-q", "Hi"], response);
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert_eq!(out.trim_end(), "Héllo");
+}
+
+#[test]
+fn test_silent() {
+    let hello = content_event("Hello");
+    let response = sse_response(&[": OPENROUTER PROCESSING\n\n", &hello, USAGE_EVENT]);
+    let (ret, out, _) = run("silent", &["-m", MODEL, "--silent", "Hi"], response);
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    // Just the answer, no stats
+    assert_eq!(out, "Hello\n");
+}
+
+#[test]
+fn test_provider_error() {
+    let hello = content_event("Hel");
+    let error = "data: {\"id\":\"gen-1\",\"provider\":\"Mock\",\"error\":{\"code\":502,\"message\":\"overloaded\"},\"choices\":[{\"index\":0,\"delta\":{\"content\":\"\"},\"finish_reason\":\"error\"}]}\n\n";
+    let response = sse_response(&[&hello, error, "data: [DONE]\n\n"]);
+    let (ret, out, _) = run("provider-error", &["-m", MODEL, "Hi"], response);
+    // What came before the error is still shown
+    assert_eq!(out, "Hel");
+    assert!(
+        matches!(&ret, Err(err) if matches!(err.kind, ErrorKind::ResponseStreamError)),
+        "{}",
+        err_string(ret)
+    );
+}
+
+#[test]
+fn test_http_error() {
+    let body = "{\"error\":{\"message\":\"No auth credentials found\",\"code\":401}}";
+    let response = format!(
+        "HTTP/1.1 401 Unauthorized\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let (ret, out, _) = run("http-error", &["-m", MODEL, "Hi"], response.into_bytes());
+    assert!(out.is_empty(), "{out}");
+    assert!(
+        matches!(&ret, Err(err) if matches!(err.kind, ErrorKind::HttpClientError)),
+        "{}",
+        err_string(ret)
+    );
+}
+
+#[test]
+fn test_header_too_long() {
+    let api_key = format!("api_key: sk-or-{}\n", "x".repeat(600));
+    // Refused before connecting, the server sees nothing
+    let (ret, out, reqs) = run_on(
+        "long-header",
+        &["-m", MODEL, "Hi"],
+        &api_key,
+        |_| {},
+        |_| Ok(vec![]),
+    );
+    assert!(out.is_empty(), "{out}");
+    assert!(reqs.is_empty());
+    assert!(
+        matches!(&ret, Err(err) if matches!(err.kind, ErrorKind::HttpClientError)),
+        "{}",
+        err_string(ret)
+    );
+}
+
+#[test]
+fn test_models_file_out_dir() {
+    let out_dir = std::env::temp_dir().join(format!("ort-mock-{}-out", std::process::id()));
+    let _ = fs::remove_dir_all(&out_dir);
+    let models_file = out_dir.with_extension("txt");
+    fs::write(&models_file, "# The sweep\n\ntest/model  # the mock\n").unwrap();
+
+    let hello = content_event("Hello");
+    let response = sse_response(&[&hello, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, _, req) = run(
+        "models-file",
+        &[
+            "--models-file",
+            models_file.to_str().unwrap(),
+            "--out-dir",
+            out_dir.to_str().unwrap(),
+            "-q",
+            "Hi",
+        ],
+        response,
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert!(
+        req.body.contains("\"model\": \"test/model\""),
+        "{}",
+        req.body
+    );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("test-model.md")).unwrap(),
+        "Hello"
+    );
+    let _ = fs::remove_dir_all(&out_dir);
+    let _ = fs::remove_file(&models_file);
+}
+
+#[test]
+fn test_tee() {
+    let tee = std::env::temp_dir().join(format!("ort-mock-{}-tee.md", std::process::id()));
+    let hello = content_event("Hello");
+    let response = sse_response(&[&hello, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, out, _) = run(
+        "tee",
+        &["-m", MODEL, "--tee", tee.to_str().unwrap(), "Hi"],
+        response,
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert!(out.starts_with("Hello\n"), "{out}");
+    // The answer, without the stats
+    assert_eq!(fs::read_to_string(&tee).unwrap(), "Hello\n");
+    let _ = fs::remove_file(&tee);
+}
+
+#[test]
+fn test_reasoning_budget() {
+    let think = |text: &str| {
+        format!(
+            "data: {{\"id\":\"gen-1\",\"provider\":\"Mock\",\"choices\":[{{\"index\":0,\"delta\":{{\"reasoning\":\"{text}\"}}}}]}}\n\n"
+        )
+    };
+    let first = think("Hello world");
+    let second = think("and more thinking, far too much");
+    let third = think("still going");
+    let answer = content_event("Done");
+    let response = || {
+        sse_response(&[
+            &first,
+            &second,
+            &third,
+            &answer,
+            USAGE_EVENT,
+            "data: [DONE]\n\n",
+        ])
+    };
+
+    // The provider ignored the budget, the rest of the reasoning is hidden
+    let (ret, out, req) = run(
+        "reasoning-budget",
+        &["-m", MODEL, "-rr", "--reasoning-tokens", "4", "Hi"],
+        response(),
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert!(req.body.contains(r#""max_tokens": 4}"#), "{}", req.body);
+    assert!(out.contains("Hello world"), "{out}");
+    assert!(out.contains("not showing the rest"), "{out}");
+    assert!(
+        !out.contains("far too much") && !out.contains("still going"),
+        "{out}"
+    );
+    assert!(out.contains("Done"), "{out}");
+
+    let (ret, out, _) = run(
+        "strict-reasoning",
+        &[
+            "-m",
+            MODEL,
+            "--reasoning-tokens",
+            "4",
+            "--strict-reasoning",
+            "Hi",
+        ],
+        response(),
+    );
+    assert!(
+        matches!(&ret, Err(err) if matches!(err.kind, ErrorKind::ReasoningBudgetExceeded)),
+        "{}",
+        err_string(ret)
+    );
+    assert!(!out.contains("Done"), "{out}");
+}
+
+#[test]
+fn test_save_code() {
+    let code_dir = std::env::temp_dir().join(format!("ort-mock-{}-code", std::process::id()));
+    // The closing fence of the first block is split across events
+    let first = content_event("Two files:\\n```rust\\nfn main() {}\\n``");
+    let second = content_event("`\\n```\\nplain\\n```\\n");
+    let response = sse_response(&[&first, &second, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, out, _) = run(
+        "save-code",
+        &["-m", MODEL, "--save-code", code_dir.to_str().unwrap(), "Hi"],
+        response,
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert!(out.starts_with("Two files:\n```rust\n"), "{out}");
+    assert_eq!(
+        fs::read_to_string(code_dir.join("block-1.rs")).unwrap(),
+        "fn main() {}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(code_dir.join("block-2.txt")).unwrap(),
+        "plain\n"
+    );
+    let _ = fs::remove_dir_all(&code_dir);
+}
+
+#[test]
+fn test_history_titles() {
+    let title = content_event("\\\"Greeting the mock.\\\"");
+    let response = sse_response(&[&title, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, out, req) = run_with(
+        "history-titles",
+        &["history", "list"],
+        response,
+        "title_model: test/cheap\n",
+        |dir| {
+            fs::create_dir_all(dir.join("state/ort")).unwrap();
+            fs::write(
+                dir.join("state/ort/last-3.json"),
+                r#"{"opts":{},"messages":[{"role":"user","content":"Say hi to the mock"},{"role":"assistant","content":"Hi mock"}]}"#,
+            )
+            .unwrap();
+        },
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert!(
+        req.body.contains("\"model\": \"test/cheap\""),
+        "{}",
+        req.body
+    );
+    assert!(req.body.contains("Say hi to the mock"), "{}", req.body);
+    assert!(
+        out.starts_with("last-3.json  ") && out.ends_with(" ago  Greeting the mock  2 messages\n"),
+        "{out}"
+    );
+}
+
+#[test]
+fn test_config_path() {
+    // Only the --config file has the API key and server
+    let profile = std::env::temp_dir().join(format!("ort-mock-{}-profile.cfg", std::process::id()));
+    let hello = content_event("Hello");
+    let response = sse_response(&[&hello, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let moved = profile.clone();
+    let (ret, out, _) = run_with(
+        "config-path",
+        &[
+            "--config",
+            profile.to_str().unwrap(),
+            "-m",
+            MODEL,
+            "-q",
+            "Hi",
+        ],
+        response,
+        "",
+        move |dir| fs::rename(dir.join("config/ort.cfg"), moved).unwrap(),
+    );
+    let _ = fs::remove_file(&profile);
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert_eq!(out.trim_end(), "Hello");
+}
+
+#[test]
+fn test_profile() {
+    let hello = content_event("Hello");
+    let response = sse_response(&[&hello, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, out, req) = run_with(
+        "profile",
+        &["--profile", "work", "--show-model-source", "Hi"],
+        response,
+        "model: test/base\n[work]\nmodel: test/work\n",
+        |_| {},
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert!(
+        req.body.contains("\"model\": \"test/work\""),
+        "{}",
+        req.body
+    );
+    let last_line = out.lines().last().unwrap();
+    assert!(
+        last_line.ends_with("Model from: config profile work"),
+        "{last_line}"
+    );
+}
+
+#[test]
+fn test_keep_alive() {
+    // No usage in the stream, so ort asks for the generation's stats, on
+    // the same connection
+    let hello = content_event("Hello");
+    let stream = sse_response_keep_alive(&[&hello, "data: [DONE]\n\n"]);
+    let generation = json_response(
+        r#"{"data":{"provider_name":"MockGen","model":"test/model","total_cost":0.00002,"native_tokens_completion":4}}"#,
+    );
+    let (ret, out, reqs) = run_on(
+        "keep-alive",
+        &["-m", MODEL, "Hi"],
+        "",
+        |_| {},
+        move |server| server.serve_keep_alive(&[stream, generation]),
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert_eq!(reqs[1].method, "GET");
+    assert_eq!(reqs[1].path, "/api/v1/generation?id=gen-1");
+    let last_line = out.lines().last().unwrap();
+    assert!(
+        last_line.starts_with(&format!("Stats: {MODEL} at MockGen")),
+        "Invalid last line: '{last_line}'"
+    );
+}
+
+#[test]
+fn test_free_fallback() {
+    let body = "{\"error\":{\"message\":\"Insufficient credits\",\"code\":402}}";
+    let no_credits = format!(
+        "HTTP/1.1 402 Payment Required\r\nContent-Type: application/json\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let hello = content_event("Hello for free");
+    let answer = sse_response(&[&hello, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, out, reqs) = run_on(
+        "free-fallback",
+        &["-m", MODEL, "-q", "Hi"],
+        "free_fallback: test/model:free\n",
+        |_| {},
+        move |server| {
+            let first = server.serve(no_credits.as_bytes())?;
+            let second = server.serve(&answer)?;
+            Ok(vec![first, second])
+        },
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    assert!(
+        reqs[1].body.contains("\"model\": \"test/model:free\""),
+        "{}",
+        reqs[1].body
+    );
+    assert_eq!(out.trim_end(), "Hello for free");
+}
+
+#[test]
+fn test_pin_provider_retries() {
+    let body = "{\"error\":{\"message\":\"Provider overloaded\",\"code\":502}}";
+    let overloaded = format!(
+        "HTTP/1.1 502 Bad Gateway\r\nContent-Type: application/json\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    let hello = content_event("Hello again");
+    let answer = sse_response(&[&hello, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let (ret, out, reqs) = run_on(
+        "pin-provider",
+        &[
+            "-m",
+            MODEL,
+            "--pin-provider",
+            "Mock",
+            "--retries",
+            "2",
+            "Hi",
+        ],
+        "",
+        |_| {},
+        move |server| {
+            let first = server.serve(overloaded.as_bytes())?;
+            let second = server.serve(&answer)?;
+            Ok(vec![first, second])
+        },
+    );
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    for req in &reqs {
+        assert!(
+            req.body
+                .contains(r#""provider": {"order": ["mock"], "allow_fallbacks": false}"#),
+            "{}",
+            req.body
+        );
+    }
+    assert!(out.starts_with("Hello again\n"), "{out}");
+    assert!(
+        out.contains("Attempts: 1 failed after ")
+            && out.contains("(HttpStatusError: reading response header), 2 ok in "),
+        "{out}"
+    );
+}
+
+#[test]
+fn test_queue_on_fail() {
+    let dir = temp_dir("queue");
+    let write_cfg = |port: u16| {
+        let cfg =
+            format!("api_key: sk-or-test\nbase_url: localhost:{port}/api/v1\ndns: 127.0.0.1\n");
+        fs::write(dir.join("config/ort.cfg"), cfg).unwrap();
+    };
+    let dev_null = fs::File::open("/dev/null").unwrap();
+    syscall::dup2(dev_null.as_raw_fd(), 0);
+    let args = |args: &[&str]| -> Vec<String> {
+        ["ort", "--no-update-check", "--session", "later"]
+            .iter()
+            .chain(args)
+            .map(|s| s.to_string())
+            .collect()
+    };
+
+    // Nothing listens on port 1
+    write_cfg(1);
+    let mut out = Vec::new();
+    let ret = cli::main(
+        &args(&["-m", MODEL, "--queue-on-fail", "Hi later"]),
+        env(&dir),
+        Term::Pipe,
+        &mut out,
+    );
+    assert!(
+        matches!(&ret, Err(err) if matches!(err.kind, ErrorKind::NoNetwork)),
+        "{}",
+        err_string(ret)
+    );
+    let queue_dir = dir.join("state/ort/queue");
+    let queued: Vec<PathBuf> = fs::read_dir(&queue_dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert_eq!(queued.len(), 1);
+    assert!(queued[0].to_str().unwrap().ends_with("-s-later.json"));
+    let body = fs::read_to_string(&queued[0]).unwrap();
+    assert!(body.contains("Hi later"), "{body}");
+
+    let server = MockServer::bind().unwrap();
+    write_cfg(server.port());
+    let answer = content_event("Answered");
+    let response = sse_response(&[&answer, USAGE_EVENT, "data: [DONE]\n\n"]);
+    let handle = thread::spawn(move || server.serve(&response));
+    let mut out = Vec::new();
+    let ret = cli::main(&args(&["flush-queue"]), env(&dir), Term::Pipe, &mut out);
+    let req = handle.join().unwrap().unwrap();
+    assert!(matches!(ret, Ok(0)), "{}", err_string(ret));
+    // Sent as it was queued
+    assert_eq!(req.body, body);
+    let out = String::from_utf8_lossy(&out);
+    assert!(out.starts_with("[1/1] Hi later\nAnswered\n"), "{out}");
+    assert!(out.ends_with("Sent 1 of 1 queued prompts\n"), "{out}");
+    assert_eq!(fs::read_dir(&queue_dir).unwrap().count(), 0);
+    // The session that queued it can carry on with -c
+    let last = fs::read_to_string(dir.join("state/ort/last-s-later.json")).unwrap();
+    assert!(last.contains("Answered"), "{last}");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+/// Run ort with `args` against a mock server that answers `response` once.
+/// Returns what cli::main returned, what it printed, and the request it sent.
+fn run(name: &str, args: &[&str], response: Vec<u8>) -> (OrtResult<i32>, String, MockRequest) {
+    run_with(name, args, response, "", |_| {})
+}
+
+/// `run`, with `extra_cfg` lines in the config and `setup` given the temp
+/// dir before ort starts
+fn run_with(
+    name: &str,
+    args: &[&str],
+    response: Vec<u8>,
+    extra_cfg: &str,
+    setup: impl FnOnce(&Path),
+) -> (OrtResult<i32>, String, MockRequest) {
+    let (ret, out, mut reqs) = run_on(name, args, extra_cfg, setup, move |server| {
+        server.serve(&response).map(|req| vec![req])
+    });
+    (ret, out, reqs.remove(0))
+}
+
+/// `run_with`, the mock server doing `serve`
+fn run_on(
+    name: &str,
+    args: &[&str],
+    extra_cfg: &str,
+    setup: impl FnOnce(&Path),
+    serve: impl FnOnce(MockServer) -> OrtResult<Vec<MockRequest>> + Send + 'static,
+) -> (OrtResult<i32>, String, Vec<MockRequest>) {
+    let server = MockServer::bind().unwrap();
+    let dir = temp_dir(name);
+    fs::write(
+        dir.join("config/ort.cfg"),
+        format!(
+            "api_key: sk-or-test\nbase_url: localhost:{}/api/v1\ndns: 127.0.0.1\nsave_to_file: false\n{extra_cfg}",
+            server.port()
+        ),
+    )
+    .unwrap();
+    setup(&dir);
+
+    // ort reads a stdin that isn't a terminal, and under cargo test it might
+    // never close
+    let dev_null = fs::File::open("/dev/null").unwrap();
+    syscall::dup2(dev_null.as_raw_fd(), 0);
+
+    let handle = thread::spawn(move || serve(server));
+
+    let args: Vec<String> = ["ort", "--no-update-check"]
+        .iter()
+        .chain(args)
+        .map(|s| s.to_string())
+        .collect();
+    let mut out = Vec::new();
+    let ret = cli::main(&args, env(&dir), Term::Pipe, &mut out);
+
+    let reqs = match handle.join().unwrap() {
+        Ok(reqs) => reqs,
+        Err(err) => panic!("mock server: {}", err.as_string()),
+    };
+    let _ = fs::remove_dir_all(&dir);
+    (ret, String::from_utf8_lossy(&out).into_owned(), reqs)
+}
+
+fn err_string(ret: OrtResult<i32>) -> String {
+    match ret {
+        Ok(code) => format!("exit code {code}"),
+        Err(err) => err.as_string(),
+    }
+}
+
+/// A fresh XDG config, cache and state, so nothing from the real ones leaks in
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ort-mock-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for sub in ["config", "cache", "state"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    dir
+}
+
+fn env(dir: &Path) -> Env {
+    let leak = |sub: &str| -> Option<&'static str> {
+        Some(dir.join(sub).to_string_lossy().into_owned().leak())
+    };
+    Env {
+        HOME: leak(""),
+        PWD: leak(""),
+        TMUX_PANE: None,
+        ORT_SESSION: None,
+        ORT_PROFILE: None,
+        XDG_CONFIG_HOME: leak("config"),
+        XDG_CACHE_HOME: leak("cache"),
+        XDG_STATE_HOME: leak("state"),
+        ORT_CONFIG_DIR: None,
+        ORT_CACHE_DIR: None,
+        ORT_STATE_DIR: None,
+        ORT_HISTORY_FILE: None,
+        OPENROUTER_API_KEY: None,
+        NVIDIA_API_KEY: None,
+        SSLKEYLOGFILE: None,
+        TERM: None,
+    }
+}